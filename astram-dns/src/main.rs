@@ -1,8 +1,11 @@
+use astram_config::network::{default_magic, parse_magic};
+use astram_config::ports::Ports;
+use astram_config::time::with_timestamps;
 use axum::{
-    extract::{ConnectInfo, Query, State},
+    extract::{ConnectInfo, Path as UrlPath, Query, State},
     http::StatusCode,
     response::{IntoResponse, Json},
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use chrono::Utc;
@@ -12,6 +15,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
 };
@@ -22,14 +26,29 @@ use tracing::{info, warn};
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Port to bind DNS server
-    #[arg(short, long, default_value = "8053")]
+    #[arg(short, long, default_value_t = Ports::DEFAULT.dns)]
     port: u16,
 
     /// Maximum age of nodes in seconds before considering them stale
     #[arg(short, long, default_value = "3600")]
     max_age: u64,
+
+    /// JSON file the node registry is saved to periodically and on shutdown, and loaded from at startup
+    #[arg(long, default_value = "dns_nodes.json")]
+    data_file: PathBuf,
+
+    /// Serve the public testnet instead of mainnet
+    #[arg(long)]
+    testnet: bool,
+
+    /// Network magic nodes must register with (hex or decimal); overrides --testnet
+    #[arg(long, value_parser = parse_magic)]
+    network_magic: Option<u32>,
 }
 
+/// How often the node registry is written to `--data-file`
+const REGISTRY_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeInfo {
     pub address: String,
@@ -39,12 +58,34 @@ pub struct NodeInfo {
     pub last_seen: i64,
     pub first_seen: i64,   // When node was first registered
     pub uptime_hours: f64, // Hours since first registration
+    /// Network magic the node registered with (0 in registries saved before it was kept)
+    #[serde(default)]
+    pub network_magic: u32,
 }
 
 #[derive(Clone)]
 pub struct AppState {
     nodes: Arc<RwLock<HashMap<String, NodeInfo>>>,
+    /// Banned IP -> unix time the ban expires
+    banned: Arc<RwLock<HashMap<String, i64>>>,
     max_age: u64,
+    /// Only nodes of this network are registered and handed out
+    network_magic: u32,
+}
+
+/// On-disk form of the registry (`--data-file`)
+#[derive(Serialize, Deserialize, Default)]
+struct RegistryFile {
+    #[serde(default)]
+    nodes: HashMap<String, NodeInfo>,
+    #[serde(default)]
+    banned: HashMap<String, i64>,
+}
+
+#[derive(Deserialize)]
+struct BanRequest {
+    address: String,
+    duration_secs: u64,
 }
 
 #[derive(Deserialize)]
@@ -54,6 +95,10 @@ struct RegisterRequest {
     port: u16,
     version: String,
     height: u64,
+    /// Network the node is on; registrations without one predate the magic and are
+    /// refused like any other network's
+    #[serde(default)]
+    network_magic: Option<u32>,
 }
 
 #[derive(Serialize)]
@@ -82,10 +127,12 @@ struct GetNodesQuery {
 }
 
 impl AppState {
-    fn new(max_age: u64) -> Self {
+    fn new(max_age: u64, network_magic: u32) -> Self {
         Self {
             nodes: Arc::new(RwLock::new(HashMap::new())),
+            banned: Arc::new(RwLock::new(HashMap::new())),
             max_age,
+            network_magic,
         }
     }
 
@@ -103,6 +150,53 @@ impl AppState {
         if removed > 0 {
             info!("Cleaned up {} stale nodes", removed);
         }
+        drop(nodes);
+
+        self.banned.write().retain(|_, until| *until > now);
+    }
+
+    fn is_banned(&self, ip: &str, now: i64) -> bool {
+        self.banned.read().get(ip).is_some_and(|until| *until > now)
+    }
+
+    /// Write the registry to `path` (via a temp file, so a crash never leaves it half-written)
+    fn save_registry(&self, path: &Path) -> anyhow::Result<usize> {
+        let file = RegistryFile {
+            nodes: self.nodes.read().clone(),
+            banned: self.banned.read().clone(),
+        };
+        let count = file.nodes.len();
+        let json = serde_json::to_string_pretty(&file)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(count)
+    }
+
+    /// Load a saved registry, skipping entries older than `max_age` or of another
+    /// network, and expired bans. Stored `first_seen` timestamps are kept so uptime
+    /// carries across restarts.
+    fn load_registry(&self, path: &Path) -> anyhow::Result<usize> {
+        if !path.exists() {
+            return Ok(0);
+        }
+        let data = std::fs::read_to_string(path)?;
+        let saved: RegistryFile = serde_json::from_str(&data)?;
+        let now = Utc::now().timestamp();
+
+        self.banned
+            .write()
+            .extend(saved.banned.into_iter().filter(|(_, until)| *until > now));
+
+        let mut nodes = self.nodes.write();
+        for (node_id, node) in saved.nodes {
+            if now - node.last_seen < self.max_age as i64
+                && node.network_magic == self.network_magic
+            {
+                nodes.insert(node_id, node);
+            }
+        }
+        Ok(nodes.len())
     }
 
     /// Check node connectivity and remove unreachable nodes
@@ -141,7 +235,8 @@ impl AppState {
                 let address = address.clone();
 
                 let task = tokio::spawn(async move {
-                    let health_url = format!("http://{}:19533/health", address);
+                    let health_url =
+                        format!("http://{}:{}/health", address, Ports::DEFAULT.http_rpc);
                     match client.get(&health_url).send().await {
                         Ok(response) if response.status().is_success() => (node_id, true),
                         _ => (node_id, false),
@@ -264,6 +359,20 @@ async fn register_node(
         }
     };
 
+    if state.is_banned(&node_ip.to_string(), Utc::now().timestamp()) {
+        let node_count = state.nodes.read().len();
+        return (
+            StatusCode::FORBIDDEN,
+            Json(RegisterResponse {
+                success: false,
+                message: "Node IP is banned".to_string(),
+                node_count,
+                registered_address: node_address,
+                registered_port: req.port,
+            }),
+        );
+    }
+
     if req.port == 0 {
         let node_count = state.nodes.read().len();
         return (
@@ -278,6 +387,27 @@ async fn register_node(
         );
     }
 
+    if req.network_magic != Some(state.network_magic) {
+        warn!(
+            "Refused registration of {}:{} from another network (magic {:?})",
+            node_address, req.port, req.network_magic
+        );
+        let node_count = state.nodes.read().len();
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(RegisterResponse {
+                success: false,
+                message: format!(
+                    "Node is on another network: this seed serves magic 0x{:08x}",
+                    state.network_magic
+                ),
+                node_count,
+                registered_address: node_address,
+                registered_port: req.port,
+            }),
+        );
+    }
+
     if !is_public_ip(node_ip) {
         let node_count = state.nodes.read().len();
         return (
@@ -318,6 +448,7 @@ async fn register_node(
         last_seen: now,
         first_seen,
         uptime_hours,
+        network_magic: state.network_magic,
     };
 
     state.nodes.write().insert(node_id.clone(), node_info);
@@ -348,7 +479,12 @@ async fn get_nodes(
     state.cleanup_stale_nodes();
 
     let nodes = state.nodes.read();
-    let mut node_list: Vec<NodeInfo> = nodes.values().cloned().collect();
+    let now = Utc::now().timestamp();
+    let mut node_list: Vec<NodeInfo> = nodes
+        .values()
+        .filter(|n| n.network_magic == state.network_magic && !state.is_banned(&n.address, now))
+        .cloned()
+        .collect();
 
     // Filter by minimum height if specified
     if let Some(min_height) = query.min_height {
@@ -357,7 +493,6 @@ async fn get_nodes(
 
     // Calculate values needed for scoring before sorting
     let max_height = node_list.iter().map(|n| n.height).max().unwrap_or(1) as f64;
-    let now = Utc::now().timestamp();
 
     // Sort by composite score:
     // - 40% weight: blockchain height
@@ -398,15 +533,80 @@ async fn get_nodes(
     })
 }
 
+fn ban_response(status: StatusCode, message: String) -> (StatusCode, Json<serde_json::Value>) {
+    let success = status.is_success();
+    (
+        status,
+        Json(serde_json::json!({ "success": success, "message": message })),
+    )
+}
+
+// Ban an IP (operator only: accepted from loopback connections)
+async fn ban_node(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    Json(req): Json<BanRequest>,
+) -> impl IntoResponse {
+    if !addr.ip().is_loopback() {
+        return ban_response(
+            StatusCode::FORBIDDEN,
+            "Bans can only be managed locally".to_string(),
+        );
+    }
+    let ip: IpAddr = match req.address.parse() {
+        Ok(ip) => ip,
+        Err(_) => {
+            return ban_response(StatusCode::BAD_REQUEST, "Invalid IP address".to_string());
+        }
+    };
+
+    let ip = ip.to_string();
+    let until = Utc::now()
+        .timestamp()
+        .saturating_add(req.duration_secs.min(i64::MAX as u64) as i64);
+    state.banned.write().insert(ip.clone(), until);
+    let mut nodes = state.nodes.write();
+    let before = nodes.len();
+    nodes.retain(|_, node| node.address != ip);
+    let removed = before - nodes.len();
+
+    warn!(
+        "Banned {} until {} ({} registered nodes removed)",
+        ip, until, removed
+    );
+    ban_response(StatusCode::OK, format!("{} banned until {}", ip, until))
+}
+
+// Lift a ban (operator only: accepted from loopback connections)
+async fn unban_node(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    UrlPath(address): UrlPath<String>,
+) -> impl IntoResponse {
+    if !addr.ip().is_loopback() {
+        return ban_response(
+            StatusCode::FORBIDDEN,
+            "Bans can only be managed locally".to_string(),
+        );
+    }
+    match state.banned.write().remove(&address) {
+        Some(_) => {
+            info!("Unbanned {}", address);
+            ban_response(StatusCode::OK, format!("{} unbanned", address))
+        }
+        None => ban_response(StatusCode::NOT_FOUND, format!("{} is not banned", address)),
+    }
+}
+
 // Health check endpoint
 async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
     let node_count = state.nodes.read().len();
 
-    Json(serde_json::json!({
+    Json(with_timestamps(serde_json::json!({
         "status": "healthy",
         "node_count": node_count,
         "timestamp": Utc::now().timestamp(),
-    }))
+    })))
 }
 
 // Get statistics
@@ -432,13 +632,13 @@ async fn get_stats(State(state): State<AppState>) -> impl IntoResponse {
         0
     };
 
-    Json(serde_json::json!({
+    Json(with_timestamps(serde_json::json!({
         "node_count": node_count,
         "max_height": max_height,
         "avg_height": avg_height,
         "versions": versions,
         "timestamp": Utc::now().timestamp(),
-    }))
+    })))
 }
 
 #[tokio::main]
@@ -453,8 +653,32 @@ async fn main() -> anyhow::Result<()> {
 
     info!("Starting Astram DNS Server...");
     info!("Max node age: {} seconds", args.max_age);
+    let network = if args.testnet { "testnet" } else { "mainnet" };
+    let network_magic = args.network_magic.unwrap_or_else(|| default_magic(network));
+    info!("Serving network magic 0x{:08x}", network_magic);
+
+    let state = AppState::new(args.max_age, network_magic);
+    match state.load_registry(&args.data_file) {
+        Ok(count) => info!("Loaded {} nodes from {:?}", count, args.data_file),
+        Err(e) => warn!(
+            "Failed to load node registry from {:?}: {}",
+            args.data_file, e
+        ),
+    }
 
-    let state = AppState::new(args.max_age);
+    // Spawn periodic registry save task
+    let save_state = state.clone();
+    let save_path = args.data_file.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REGISTRY_SAVE_INTERVAL);
+        interval.tick().await; // Skip first immediate tick
+        loop {
+            interval.tick().await;
+            if let Err(e) = save_state.save_registry(&save_path) {
+                warn!("Failed to save node registry to {:?}: {}", save_path, e);
+            }
+        }
+    });
 
     // Spawn periodic cleanup task (removes stale nodes based on last_seen)
     let cleanup_state = state.clone();
@@ -485,8 +709,10 @@ async fn main() -> anyhow::Result<()> {
         .route("/register", post(register_node))
         .route("/nodes", get(get_nodes))
         .route("/stats", get(get_stats))
+        .route("/ban", post(ban_node))
+        .route("/ban/:address", delete(unban_node))
         .layer(CorsLayer::permissive())
-        .with_state(state);
+        .with_state(state.clone());
 
     let addr = SocketAddr::from(([0, 0, 0, 0], args.port));
     info!("DNS server listening on {}", addr);
@@ -496,7 +722,113 @@ async fn main() -> anyhow::Result<()> {
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
     )
+    .with_graceful_shutdown(async {
+        let _ = tokio::signal::ctrl_c().await;
+        info!("Shutdown signal received");
+    })
     .await?;
 
+    match state.save_registry(&args.data_file) {
+        Ok(count) => info!("Saved {} nodes to {:?}", count, args.data_file),
+        Err(e) => warn!(
+            "Failed to save node registry to {:?}: {}",
+            args.data_file, e
+        ),
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use astram_config::network::{MAINNET_MAGIC, TESTNET_MAGIC};
+
+    fn node(address: &str, first_seen: i64, last_seen: i64) -> NodeInfo {
+        NodeInfo {
+            address: address.to_string(),
+            port: 8335,
+            version: "0.1.0".to_string(),
+            height: 10,
+            last_seen,
+            first_seen,
+            uptime_hours: 0.0,
+            network_magic: MAINNET_MAGIC,
+        }
+    }
+
+    #[test]
+    fn registry_round_trips_and_drops_stale_nodes() {
+        let path = std::env::temp_dir().join(format!("astram-dns-{}.json", std::process::id()));
+        let now = Utc::now().timestamp();
+
+        let state = AppState::new(3600, MAINNET_MAGIC);
+        {
+            let mut nodes = state.nodes.write();
+            nodes.insert(
+                "1.1.1.1:8335".to_string(),
+                node("1.1.1.1", now - 86_400, now - 60),
+            );
+            nodes.insert(
+                "2.2.2.2:8335".to_string(),
+                node("2.2.2.2", now - 7200, now - 7200),
+            );
+        }
+        assert_eq!(state.save_registry(&path).unwrap(), 2);
+
+        let restarted = AppState::new(3600, MAINNET_MAGIC);
+        assert_eq!(restarted.load_registry(&path).unwrap(), 1);
+        let nodes = restarted.nodes.read();
+        assert_eq!(nodes["1.1.1.1:8335"].first_seen, now - 86_400);
+        assert!(!nodes.contains_key("2.2.2.2:8335"));
+        drop(nodes);
+
+        // bans are persisted too, and expired ones are dropped on load
+        restarted
+            .banned
+            .write()
+            .insert("3.3.3.3".to_string(), now + 600);
+        restarted
+            .banned
+            .write()
+            .insert("4.4.4.4".to_string(), now - 1);
+        restarted.save_registry(&path).unwrap();
+        let again = AppState::new(3600, MAINNET_MAGIC);
+        again.load_registry(&path).unwrap();
+        assert!(again.is_banned("3.3.3.3", now));
+        assert!(!again.banned.read().contains_key("4.4.4.4"));
+        assert!(!again.is_banned("3.3.3.3", now + 601));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn registry_keeps_only_its_own_network() {
+        let path = std::env::temp_dir().join(format!("astram-dns-net-{}.json", std::process::id()));
+        let now = Utc::now().timestamp();
+
+        let state = AppState::new(3600, MAINNET_MAGIC);
+        {
+            let mut nodes = state.nodes.write();
+            nodes.insert("1.1.1.1:8335".to_string(), node("1.1.1.1", now, now));
+            let mut testnet = node("2.2.2.2", now, now);
+            testnet.network_magic = TESTNET_MAGIC;
+            nodes.insert("2.2.2.2:8335".to_string(), testnet);
+            // Saved before registrations carried a magic
+            let mut legacy = node("3.3.3.3", now, now);
+            legacy.network_magic = 0;
+            nodes.insert("3.3.3.3:8335".to_string(), legacy);
+        }
+        state.save_registry(&path).unwrap();
+
+        let mainnet = AppState::new(3600, MAINNET_MAGIC);
+        assert_eq!(mainnet.load_registry(&path).unwrap(), 1);
+        assert!(mainnet.nodes.read().contains_key("1.1.1.1:8335"));
+        let testnet = AppState::new(3600, TESTNET_MAGIC);
+        assert_eq!(testnet.load_registry(&path).unwrap(), 1);
+        assert!(testnet.nodes.read().contains_key("2.2.2.2:8335"));
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -2,16 +2,22 @@ use anyhow::{Result, anyhow};
 use base64::{Engine as _, engine::general_purpose};
 use futures::{SinkExt, StreamExt};
 use astram_config::config::Config;
+use astram_config::ports::Ports;
+use astram_config::time::with_timestamps;
+use Astram_core::Blockchain;
 use Astram_core::block::{Block, BlockHeader, compute_header_hash, compute_merkle_root};
-use Astram_core::config::initial_block_reward;
+use Astram_core::config::calculate_block_reward;
+use Astram_core::consensus::select_block_transactions;
+use Astram_core::security::{BLOCK_RESERVED_BYTES, MAX_BLOCK_BYTES};
 use Astram_core::transaction::{BINCODE_CONFIG, Transaction};
 use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::broadcast;
+use tokio::sync::{Notify, broadcast};
 use tokio::time::{Duration, sleep};
 use tokio_util::codec::{Framed, LinesCodec};
 use warp::Filter;
@@ -25,7 +31,8 @@ struct NodeClient {
 #[derive(Debug, Clone)]
 struct MempoolSnapshot {
     txs: Vec<Transaction>,
-    total_fees: U256,
+    /// Fee of each transaction in `txs`
+    fees: Vec<U256>,
 }
 
 #[derive(Debug, Clone)]
@@ -47,10 +54,385 @@ struct MiningTemplate {
     coinbase_value: U256,
 }
 
+/// Default time a getblocktemplate long-poll is held before returning a fresh template
+const DEFAULT_LONGPOLL_TIMEOUT_SECS: u64 = 30;
+/// How often the GBT server polls the node for a new tip
+const LONGPOLL_TIP_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Long-poll bookkeeping for the GBT server: the last handed-out `longpollid`
+/// (height:merkle_root) and a notifier fired when the node's tip changes.
+struct LongPoll {
+    current_id: Mutex<Option<String>>,
+    tip_hash: Mutex<Option<String>>,
+    new_block: Notify,
+    timeout: Duration,
+}
+
+impl LongPoll {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            current_id: Mutex::new(None),
+            tip_hash: Mutex::new(None),
+            new_block: Notify::new(),
+            timeout,
+        }
+    }
+
+    /// True if `longpollid` was issued against the current tip. Merkle roots differ
+    /// between templates for the same tip, so the height alone decides.
+    fn is_current(&self, longpollid: &str) -> bool {
+        let current = self.current_id.lock().unwrap();
+        match current.as_deref() {
+            Some(id) => id == longpollid || longpoll_height(id) == longpoll_height(longpollid),
+            None => false,
+        }
+    }
+
+    /// Record the tip reported by the node; wakes long-poll waiters when it moved.
+    fn observe_tip(&self, tip_hash: String) {
+        let mut tip = self.tip_hash.lock().unwrap();
+        if tip.as_deref() == Some(tip_hash.as_str()) {
+            return;
+        }
+        let changed = tip.is_some();
+        *tip = Some(tip_hash);
+        drop(tip);
+        if changed {
+            *self.current_id.lock().unwrap() = None;
+            self.new_block.notify_waiters();
+        }
+    }
+
+    /// Hold the caller until a new block arrives or the timeout elapses.
+    /// Returns immediately when `longpollid` is already stale.
+    async fn wait(&self, longpollid: &str) {
+        let notified = self.new_block.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if !self.is_current(longpollid) {
+            return;
+        }
+        let _ = tokio::time::timeout(self.timeout, notified).await;
+    }
+}
+
+fn longpoll_height(longpollid: &str) -> Option<u64> {
+    longpollid.split(':').next()?.parse().ok()
+}
+
+/// Default share interval vardiff aims for (`STRATUM_VARDIFF_TARGET_SECS`)
+const DEFAULT_VARDIFF_TARGET_SECS: u64 = 15;
+/// Share timestamps kept per connection for the rate estimate
+const VARDIFF_WINDOW: usize = 16;
+/// Shares needed before the first rate-based retarget
+const VARDIFF_MIN_SHARES: usize = 4;
+/// Retarget when the measured interval is off by more than this factor. Difficulty is
+/// counted in leading hex zeros, so one step is 16x the work and the band must be wide.
+const VARDIFF_BAND: u32 = 4;
+
+/// Per-connection share difficulty (leading hex zeros) steered towards one share every
+/// `target_interval`. Capped by the network difficulty in the same unit, see
+/// `network_share_difficulty`.
+struct VarDiff {
+    difficulty: u32,
+    target_interval: Duration,
+    shares: VecDeque<Instant>,
+    last_retarget: Instant,
+}
+
+impl VarDiff {
+    fn new(target_interval: Duration, now: Instant) -> Self {
+        Self {
+            difficulty: 1,
+            target_interval,
+            shares: VecDeque::with_capacity(VARDIFF_WINDOW),
+            last_retarget: now,
+        }
+    }
+
+    /// Record a submitted share. Returns the new difficulty if it changed.
+    fn record_share(&mut self, now: Instant, network_difficulty: u32) -> Option<u32> {
+        if self.shares.len() == VARDIFF_WINDOW {
+            self.shares.pop_front();
+        }
+        self.shares.push_back(now);
+
+        if self.shares.len() < VARDIFF_MIN_SHARES
+            || now.duration_since(self.last_retarget) < self.target_interval
+        {
+            return None;
+        }
+
+        let first = *self.shares.front()?;
+        let average = now.duration_since(first) / (self.shares.len() as u32 - 1);
+        if average < self.target_interval / VARDIFF_BAND {
+            self.retarget(self.difficulty + 1, now, network_difficulty)
+        } else if average > self.target_interval * VARDIFF_BAND {
+            self.retarget(self.difficulty.saturating_sub(1), now, network_difficulty)
+        } else {
+            None
+        }
+    }
+
+    /// Lower the difficulty for a miner that has gone quiet. Returns the new difficulty if it changed.
+    fn check_idle(&mut self, now: Instant, network_difficulty: u32) -> Option<u32> {
+        let last_activity = self
+            .shares
+            .back()
+            .copied()
+            .map_or(self.last_retarget, |t| t.max(self.last_retarget));
+        if now.duration_since(last_activity) > self.target_interval * VARDIFF_BAND {
+            self.retarget(self.difficulty.saturating_sub(1), now, network_difficulty)
+        } else {
+            None
+        }
+    }
+
+    /// Share difficulty never exceeds the network difficulty
+    fn clamp_to_network(&mut self, network_difficulty: u32) -> Option<u32> {
+        let clamped = self.difficulty.min(network_difficulty.max(1));
+        if clamped == self.difficulty {
+            return None;
+        }
+        self.difficulty = clamped;
+        Some(clamped)
+    }
+
+    fn retarget(&mut self, difficulty: u32, now: Instant, network_difficulty: u32) -> Option<u32> {
+        let difficulty = difficulty.clamp(1, network_difficulty.max(1));
+        self.last_retarget = now;
+        self.shares.clear();
+        if difficulty == self.difficulty {
+            return None;
+        }
+        self.difficulty = difficulty;
+        Some(difficulty)
+    }
+}
+
+/// Stratum error codes for rejected submissions
+const STRATUM_ERR_OTHER: i64 = 20;
+const STRATUM_ERR_LOW_DIFFICULTY: i64 = 21;
+const STRATUM_ERR_DUPLICATE: i64 = 22;
+/// Jobs per connection whose submitted nonces are remembered for duplicate detection
+const DUPLICATE_TRACKED_JOBS: usize = 8;
+
+#[derive(Debug, PartialEq)]
+enum ShareOutcome {
+    /// Meets the connection's share difficulty only
+    Share,
+    /// Also meets the network difficulty; worth submitting as a block
+    Block,
+    LowDifficulty,
+}
+
+/// Accepted/rejected share counts and current share difficulty for one worker
+#[derive(Debug, Default, Clone)]
+struct WorkerShares {
+    accepted: u64,
+    rejected: u64,
+    difficulty: u32,
+    /// When the worker last submitted a share, accepted or not
+    last_share: Option<Instant>,
+}
+
+/// Window over which accepted share work is averaged into the pool hashrate
+const POOL_HASHRATE_WINDOW: Duration = Duration::from_secs(600);
+/// Workers listed in `/pool/stats`; worker names come from miners, so the map is capped
+const MAX_TRACKED_WORKERS: usize = 1024;
+/// A worker with no share for this long is dropped from the stats
+const WORKER_IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Pool-wide counters shared by all Stratum connections and served at `/pool/stats`
+#[derive(Default)]
+struct PoolStats {
+    connections: usize,
+    workers: HashMap<String, WorkerShares>,
+    /// (accepted at, expected hashes) for shares inside the hashrate window
+    recent_work: VecDeque<(Instant, f64)>,
+    current_job_id: Option<String>,
+    current_height: Option<u64>,
+    /// Unix time of the last block the pool submitted successfully
+    last_block_found: Option<i64>,
+}
+
+impl PoolStats {
+    fn record_share(&mut self, worker: &str, accepted: bool, difficulty: u32, now: Instant) {
+        if !self.workers.contains_key(worker) {
+            self.prune_workers(now);
+        }
+        let stats = self.workers.entry(worker.to_string()).or_default();
+        stats.difficulty = difficulty;
+        stats.last_share = Some(now);
+        if !accepted {
+            stats.rejected += 1;
+            return;
+        }
+        stats.accepted += 1;
+        // a share at `difficulty` leading hex zeros takes 16^difficulty hashes on average
+        self.recent_work.push_back((now, 16f64.powi(difficulty as i32)));
+        self.prune(now);
+    }
+
+    fn set_worker_difficulty(&mut self, worker: &str, difficulty: u32) {
+        if let Some(stats) = self.workers.get_mut(worker) {
+            stats.difficulty = difficulty;
+        }
+    }
+
+    /// Drop idle workers and, if the map is still full, the one seen longest ago, so a
+    /// new worker always has room
+    fn prune_workers(&mut self, now: Instant) {
+        let active = |stats: &WorkerShares| {
+            stats
+                .last_share
+                .is_some_and(|at| now.saturating_duration_since(at) < WORKER_IDLE_TIMEOUT)
+        };
+        self.workers.retain(|_, stats| active(stats));
+        if self.workers.len() >= MAX_TRACKED_WORKERS {
+            if let Some(oldest) = self
+                .workers
+                .iter()
+                .min_by_key(|(_, stats)| stats.last_share)
+                .map(|(name, _)| name.clone())
+            {
+                self.workers.remove(&oldest);
+            }
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some((at, _)) = self.recent_work.front() {
+            if now.duration_since(*at) <= POOL_HASHRATE_WINDOW {
+                break;
+            }
+            self.recent_work.pop_front();
+        }
+    }
+
+    /// Estimated pool hashrate (H/s) from accepted share work in the window
+    fn hashrate(&self, now: Instant) -> f64 {
+        let work: f64 = self
+            .recent_work
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) <= POOL_HASHRATE_WINDOW)
+            .map(|(_, work)| work)
+            .sum();
+        work / POOL_HASHRATE_WINDOW.as_secs_f64()
+    }
+
+    fn to_json(&self, now: Instant) -> Value {
+        let workers: serde_json::Map<String, Value> = self
+            .workers
+            .iter()
+            .map(|(name, stats)| {
+                (
+                    name.clone(),
+                    serde_json::json!({
+                        "accepted": stats.accepted,
+                        "rejected": stats.rejected,
+                        "difficulty": stats.difficulty
+                    }),
+                )
+            })
+            .collect();
+        serde_json::json!({
+            "connections": self.connections,
+            "workers": workers,
+            "hashrate": self.hashrate(now),
+            "job_id": self.current_job_id,
+            "height": self.current_height,
+            "last_block_found": self.last_block_found
+        })
+    }
+}
+
+/// Counts one Stratum connection in PoolStats while it is alive; dropping it (however
+/// the connection task ends, panics included) takes the count back down
+struct ConnectionGuard(Arc<Mutex<PoolStats>>);
+
+impl ConnectionGuard {
+    fn new(pool_stats: Arc<Mutex<PoolStats>>) -> Self {
+        pool_stats.lock().unwrap().connections += 1;
+        Self(pool_stats)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut stats = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        stats.connections = stats.connections.saturating_sub(1);
+    }
+}
+
+/// Nonces already submitted on a connection, for the most recent jobs only
+#[derive(Default)]
+struct SubmittedShares {
+    jobs: VecDeque<String>,
+    nonces: HashMap<String, HashSet<u64>>,
+}
+
+impl SubmittedShares {
+    /// Returns false if this (job, nonce) was already submitted
+    fn insert(&mut self, job_id: &str, nonce: u64) -> bool {
+        if !self.nonces.contains_key(job_id) {
+            if self.jobs.len() == DUPLICATE_TRACKED_JOBS {
+                if let Some(oldest) = self.jobs.pop_front() {
+                    self.nonces.remove(&oldest);
+                }
+            }
+            self.jobs.push_back(job_id.to_string());
+        }
+        self.nonces.entry(job_id.to_string()).or_default().insert(nonce)
+    }
+}
+
+fn meets_difficulty(hash: &str, difficulty: u32) -> bool {
+    hash.bytes().take_while(|b| *b == b'0').count() >= difficulty as usize
+}
+
+/// Whether `hash` is below the target of compact `bits`, exactly as the node checks a block
+fn meets_network_target(hash: &str, bits: u32) -> bool {
+    let target = Blockchain::compact_to_target(bits);
+    !target.is_zero() && Blockchain::hash_to_u256(hash).is_ok_and(|value| value < target)
+}
+
+/// Leading hex zeros of the target of compact `bits`: the highest share difficulty that
+/// is never harder than finding a block
+fn network_share_difficulty(bits: u32) -> u32 {
+    Blockchain::compact_to_target(bits).leading_zeros() / 4
+}
+
+/// Check a submitted nonce against the network target first, then the share target
+fn classify_share(template: &MiningTemplate, nonce: u64, share_difficulty: u32) -> Result<ShareOutcome> {
+    let hash = compute_header_hash(&template_header(template, nonce))?;
+    Ok(if meets_network_target(&hash, template.difficulty) {
+        ShareOutcome::Block
+    } else if meets_difficulty(&hash, share_difficulty) {
+        ShareOutcome::Share
+    } else {
+        ShareOutcome::LowDifficulty
+    })
+}
+
+fn stratum_error(id: Value, code: i64, message: &str) -> String {
+    serde_json::json!({"id": id, "result": null, "error": [code, message, null]}).to_string()
+}
+
+fn set_difficulty_message(difficulty: u32) -> String {
+    serde_json::json!({
+        "id": null,
+        "method": "mining.set_difficulty",
+        "params": [difficulty]
+    })
+    .to_string()
+}
+
 #[derive(Deserialize)]
 struct MempoolResponse {
     transactions_b64: String,
-    total_fees: String,
+    #[serde(default)]
+    fees: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -154,9 +536,22 @@ impl NodeClient {
         let (txs, _) = bincode::decode_from_slice::<Vec<Transaction>, _>(&bytes, *BINCODE_CONFIG)
             .map_err(|e| anyhow!("invalid mempool bincode: {}", e))?;
 
-        let total_fees = parse_u256(&resp.total_fees).unwrap_or_else(U256::zero);
+        // Per-transaction fees rank the template by fee rate; a node that doesn't report
+        // them can't be templated without over- or under-claiming the coinbase
+        if resp.fees.len() != txs.len() {
+            return Err(anyhow!(
+                "mempool reported {} fees for {} transactions",
+                resp.fees.len(),
+                txs.len()
+            ));
+        }
+        let fees = resp
+            .fees
+            .iter()
+            .map(|fee| parse_u256(fee).ok_or_else(|| anyhow!("invalid mempool fee: {}", fee)))
+            .collect::<Result<Vec<_>>>()?;
 
-        Ok(MempoolSnapshot { txs, total_fees })
+        Ok(MempoolSnapshot { txs, fees })
     }
 
     async fn submit_block(&self, block: &Block) -> Result<()> {
@@ -191,10 +586,9 @@ fn parse_u256(value: &str) -> Option<U256> {
     U256::from_dec_str(value).ok()
 }
 
-fn target_from_difficulty(difficulty: u32) -> String {
-    let zeros = difficulty.min(64) as usize;
-    let rest = 64usize.saturating_sub(zeros);
-    format!("{}{}", "0".repeat(zeros), "f".repeat(rest))
+/// The 256-bit target of compact `bits` (the header difficulty) as 64 hex chars
+fn target_from_difficulty(bits: u32) -> String {
+    format!("{:064x}", Blockchain::compact_to_target(bits))
 }
 
 fn load_pool_address(cfg: &Config) -> Result<String> {
@@ -225,12 +619,24 @@ async fn build_template(
         status.tip_hash.clone()
     };
 
-    let base_reward = initial_block_reward();
-    let coinbase_value = base_reward + mempool.total_fees;
+    // Best fee rate first up to the block size cap, like the node's own miner
+    let selected = select_block_transactions(
+        &mempool.txs,
+        &mempool.fees,
+        MAX_BLOCK_BYTES - BLOCK_RESERVED_BYTES,
+    );
+    let total_fees = selected
+        .iter()
+        .fold(U256::zero(), |sum, &i| sum.saturating_add(mempool.fees[i]));
+
+    let base_reward = calculate_block_reward(height);
+    let coinbase_value = base_reward
+        .checked_add(total_fees)
+        .ok_or_else(|| anyhow!("coinbase value overflows: {} + {}", base_reward, total_fees))?;
 
     let coinbase = Transaction::coinbase(pool_address, coinbase_value).with_hashes();
     let mut all_txs = vec![coinbase];
-    all_txs.extend(mempool.txs);
+    all_txs.extend(selected.iter().map(|&i| mempool.txs[i].clone()));
 
     let txids: Vec<String> = all_txs.iter().map(|t| t.txid.clone()).collect();
     let merkle_root = compute_merkle_root(&txids);
@@ -247,18 +653,22 @@ async fn build_template(
     })
 }
 
-fn build_block_from_template(template: &MiningTemplate, nonce: u64) -> Result<Block> {
-    let header = BlockHeader {
+fn template_header(template: &MiningTemplate, nonce: u64) -> BlockHeader {
+    BlockHeader {
         index: template.height,
         previous_hash: template.prev_hash.clone(),
         merkle_root: template.merkle_root.clone(),
         timestamp: template.timestamp,
         nonce,
         difficulty: template.difficulty,
-    };
+    }
+}
+
+fn build_block_from_template(template: &MiningTemplate, nonce: u64) -> Result<Block> {
+    let header = template_header(template, nonce);
 
     let hash = compute_header_hash(&header)?;
-    if !hash.starts_with(&"0".repeat(template.difficulty as usize)) {
+    if !meets_network_target(&hash, template.difficulty) {
         return Err(anyhow!("nonce does not meet difficulty"));
     }
 
@@ -275,9 +685,14 @@ async fn handle_stratum_connection(
     mut job_rx: broadcast::Receiver<MiningTemplate>,
     pool_address: String,
     client: NodeClient,
+    share_interval: Duration,
+    pool_stats: Arc<Mutex<PoolStats>>,
 ) -> Result<()> {
     let mut framed = Framed::new(stream, LinesCodec::new());
     let mut subscribed = false;
+    let mut vardiff = VarDiff::new(share_interval, Instant::now());
+    let mut network_difficulty = 1;
+    let mut submitted = SubmittedShares::default();
 
     loop {
         tokio::select! {
@@ -313,6 +728,9 @@ async fn handle_stratum_connection(
                         let new_job_id = format!("{}", chrono::Utc::now().timestamp_millis());
                         let template = build_template(&client, &pool_address, new_job_id.clone()).await?;
                         template_store.lock().unwrap().insert(new_job_id.clone(), template.clone());
+                        network_difficulty = network_share_difficulty(template.difficulty);
+                        vardiff.clamp_to_network(network_difficulty);
+                        framed.send(set_difficulty_message(vardiff.difficulty)).await?;
                         let notify = serde_json::json!({
                             "id": null,
                             "method": "mining.notify",
@@ -333,30 +751,65 @@ async fn handle_stratum_connection(
                     }
                     "mining.submit" => {
                         let params = params.and_then(|v| v.as_array()).cloned().unwrap_or_default();
+                        let worker = params.first().and_then(|v| v.as_str()).unwrap_or("").to_string();
                         let job_id = params.get(1).and_then(|v| v.as_str()).unwrap_or("").to_string();
                         let nonce_str = params.get(2).and_then(|v| v.as_str()).unwrap_or("");
-
-                        let nonce = parse_nonce(nonce_str)?;
                         let template = {
                             let guard = template_store.lock().unwrap();
                             guard.get(&job_id).cloned()
                         };
 
-                        if let Some(template) = template {
-                            let block = build_block_from_template(&template, nonce)?;
-                            match client.submit_block(&block).await {
-                                Ok(_) => {
-                                    let resp = serde_json::json!({"id": id, "result": true, "error": null});
-                                    framed.send(resp.to_string()).await?;
+                        let rejection = match (parse_nonce(nonce_str), template) {
+                            (Err(e), _) => Some((STRATUM_ERR_OTHER, e.to_string())),
+                            (_, None) => Some((STRATUM_ERR_OTHER, "job not found".to_string())),
+                            (Ok(nonce), Some(_)) if !submitted.insert(&job_id, nonce) => {
+                                Some((STRATUM_ERR_DUPLICATE, "duplicate share".to_string()))
+                            }
+                            (Ok(nonce), Some(template)) => match classify_share(&template, nonce, vardiff.difficulty) {
+                                Err(e) => Some((STRATUM_ERR_OTHER, e.to_string())),
+                                Ok(ShareOutcome::LowDifficulty) => {
+                                    Some((STRATUM_ERR_LOW_DIFFICULTY, "low difficulty share".to_string()))
+                                }
+                                Ok(outcome) => {
+                                    if outcome == ShareOutcome::Block {
+                                        // A stale or rejected block is still a valid share for the pool
+                                        match build_block_from_template(&template, nonce) {
+                                            Ok(block) => match client.submit_block(&block).await {
+                                                Ok(_) => {
+                                                    log::info!("block {} found by {}", block.hash, worker);
+                                                    pool_stats.lock().unwrap().last_block_found =
+                                                        Some(chrono::Utc::now().timestamp());
+                                                }
+                                                Err(e) => log::warn!("block submission from {} failed: {}", worker, e),
+                                            },
+                                            Err(e) => log::warn!("failed to build block from share: {}", e),
+                                        }
+                                    }
+                                    None
                                 }
-                                Err(e) => {
-                                    let resp = serde_json::json!({"id": id, "result": false, "error": e.to_string()});
-                                    framed.send(resp.to_string()).await?;
+                            },
+                        };
+
+                        pool_stats.lock().unwrap().record_share(
+                            &worker,
+                            rejection.is_none(),
+                            vardiff.difficulty,
+                            Instant::now(),
+                        );
+                        match rejection {
+                            None => {
+                                let resp = serde_json::json!({"id": id, "result": true, "error": null});
+                                framed.send(resp.to_string()).await?;
+                                if let Some(difficulty) = vardiff.record_share(Instant::now(), network_difficulty) {
+                                    log::debug!("vardiff: {} share difficulty -> {}", worker, difficulty);
+                                    pool_stats.lock().unwrap().set_worker_difficulty(&worker, difficulty);
+                                    framed.send(set_difficulty_message(difficulty)).await?;
                                 }
                             }
-                        } else {
-                            let resp = serde_json::json!({"id": id, "result": false, "error": "unknown job"});
-                            framed.send(resp.to_string()).await?;
+                            Some((code, message)) => {
+                                log::debug!("worker {}: share rejected ({})", worker, message);
+                                framed.send(stratum_error(id, code, &message)).await?;
+                            }
                         }
                     }
                     _ => {
@@ -368,12 +821,14 @@ async fn handle_stratum_connection(
             Ok(template) = job_rx.recv() => {
                 if subscribed {
                     template_store.lock().unwrap().insert(template.job_id.clone(), template.clone());
-                    let notify = serde_json::json!({
-                        "id": null,
-                        "method": "mining.set_difficulty",
-                        "params": [template.difficulty]
-                    });
-                    framed.send(notify.to_string()).await?;
+                    network_difficulty = network_share_difficulty(template.difficulty);
+                    let changed = vardiff
+                        .check_idle(Instant::now(), network_difficulty)
+                        .or(vardiff.clamp_to_network(network_difficulty));
+                    if let Some(difficulty) = changed {
+                        log::debug!("vardiff: share difficulty -> {}", difficulty);
+                        framed.send(set_difficulty_message(difficulty)).await?;
+                    }
 
                     let notify = serde_json::json!({
                         "id": null,
@@ -412,6 +867,8 @@ async fn run_stratum_server(
     bind_addr: &str,
     client: NodeClient,
     pool_address: String,
+    share_interval: Duration,
+    pool_stats: Arc<Mutex<PoolStats>>,
 ) -> Result<()> {
     let listener = TcpListener::bind(bind_addr).await?;
     let templates: Arc<Mutex<HashMap<String, MiningTemplate>>> =
@@ -422,11 +879,17 @@ async fn run_stratum_server(
     let pool_for_jobs = pool_address.clone();
     let templates_for_jobs = templates.clone();
     let job_tx_for_task = job_tx.clone();
+    let stats_for_jobs = pool_stats.clone();
     tokio::spawn(async move {
         loop {
             let job_id = format!("{}", chrono::Utc::now().timestamp_millis());
             match build_template(&client_for_jobs, &pool_for_jobs, job_id.clone()).await {
                 Ok(template) => {
+                    {
+                        let mut stats = stats_for_jobs.lock().unwrap();
+                        stats.current_job_id = Some(job_id.clone());
+                        stats.current_height = Some(template.height);
+                    }
                     templates_for_jobs
                         .lock()
                         .unwrap()
@@ -447,9 +910,20 @@ async fn run_stratum_server(
         let templates = templates.clone();
         let client = client.clone();
         let pool_address = pool_address.clone();
+        let pool_stats = pool_stats.clone();
         tokio::spawn(async move {
+            let _connection = ConnectionGuard::new(pool_stats.clone());
             if let Err(e) =
-                handle_stratum_connection(stream, templates, job_rx, pool_address, client).await
+                handle_stratum_connection(
+                    stream,
+                    templates,
+                    job_rx,
+                    pool_address,
+                    client,
+                    share_interval,
+                    pool_stats.clone(),
+                )
+                .await
             {
                 log::warn!("stratum connection closed: {}", e);
             }
@@ -457,16 +931,54 @@ async fn run_stratum_server(
     }
 }
 
-async fn run_gbt_server(bind_addr: &str, client: NodeClient, pool_address: String) -> Result<()> {
+async fn run_gbt_server(
+    bind_addr: &str,
+    client: NodeClient,
+    pool_address: String,
+    longpoll_timeout: Duration,
+    pool_stats: Arc<Mutex<PoolStats>>,
+) -> Result<()> {
+    let longpoll = Arc::new(LongPoll::new(longpoll_timeout));
+
+    let client_for_tip = client.clone();
+    let longpoll_for_tip = longpoll.clone();
+    tokio::spawn(async move {
+        loop {
+            match client_for_tip.fetch_status().await {
+                Ok(status) => longpoll_for_tip.observe_tip(status.tip_hash),
+                Err(e) => log::debug!("long-poll tip check failed: {}", e),
+            }
+            sleep(LONGPOLL_TIP_POLL_INTERVAL).await;
+        }
+    });
+
+    // GET /pool/stats - Stratum connections, per-worker shares and pool hashrate
+    let stats_route = warp::path!("pool" / "stats").and(warp::get()).map(move || {
+        let stats = pool_stats.lock().unwrap().to_json(Instant::now());
+        warp::reply::json(&with_timestamps(stats))
+    });
+
     let route = warp::post()
         .and(warp::body::json())
         .and_then(move |request: JsonRpcRequest| {
             let client = client.clone();
             let pool_address = pool_address.clone();
+            let longpoll = longpoll.clone();
             async move {
                 let id = request.id.clone();
                 match request.method.as_str() {
                     "getblocktemplate" => {
+                        let requested_longpollid = request
+                            .params
+                            .as_ref()
+                            .and_then(|p| p.get(0))
+                            .and_then(|p| p.get("longpollid"))
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string());
+                        if let Some(longpollid) = requested_longpollid {
+                            longpoll.wait(&longpollid).await;
+                        }
+
                         let job_id = format!("{}", chrono::Utc::now().timestamp_millis());
                         match build_template(&client, &pool_address, job_id).await {
                             Ok(template) => {
@@ -485,6 +997,9 @@ async fn run_gbt_server(bind_addr: &str, client: NodeClient, pool_address: Strin
                                     })
                                     .collect::<Vec<_>>();
 
+                                let longpollid = format!("{}:{}", template.height, template.merkle_root);
+                                *longpoll.current_id.lock().unwrap() = Some(longpollid.clone());
+
                                 let result = serde_json::json!({
                                     "version": 1,
                                     "previousblockhash": template.prev_hash,
@@ -497,7 +1012,7 @@ async fn run_gbt_server(bind_addr: &str, client: NodeClient, pool_address: Strin
                                     "mutable": ["time", "transactions", "prevblock"],
                                     "noncerange": "00000000ffffffff",
                                     "capabilities": ["proposal"],
-                                    "longpollid": format!("{}:{}", template.height, template.merkle_root)
+                                    "longpollid": longpollid
                                 });
 
                                 Ok::<_, warp::Rejection>(warp::reply::json(&JsonRpcResponse::success(id, result)))
@@ -537,11 +1052,11 @@ async fn run_gbt_server(bind_addr: &str, client: NodeClient, pool_address: Strin
                     ))),
                 }
             }
-        })
-        .with(warp::log("Astram::gbt"));
+        });
+    let routes = stats_route.or(route).with(warp::log("Astram::gbt"));
 
     let addr: std::net::SocketAddr = bind_addr.parse()?;
-    warp::serve(route).run(addr).await;
+    warp::serve(routes).run(addr).await;
     Ok(())
 }
 
@@ -572,16 +1087,40 @@ async fn main() -> Result<()> {
         .or_else(|| load_pool_address(&cfg).ok())
         .ok_or_else(|| anyhow!("POOL_ADDRESS not set and wallet missing"))?;
 
-    let stratum_bind = std::env::var("STRATUM_BIND").unwrap_or_else(|_| "0.0.0.0:3333".to_string());
-    let gbt_bind = std::env::var("GBT_BIND").unwrap_or_else(|_| "0.0.0.0:8332".to_string());
+    let ports = Ports::default();
+    let stratum_bind = std::env::var("STRATUM_BIND").unwrap_or_else(|_| format!("0.0.0.0:{}", ports.stratum));
+    let gbt_bind = std::env::var("GBT_BIND").unwrap_or_else(|_| format!("0.0.0.0:{}", ports.gbt));
+    let longpoll_timeout = Duration::from_secs(
+        std::env::var("GBT_LONGPOLL_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_LONGPOLL_TIMEOUT_SECS),
+    );
+    let share_interval = Duration::from_secs(
+        std::env::var("STRATUM_VARDIFF_TARGET_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_VARDIFF_TARGET_SECS),
+    );
 
     let client = NodeClient::new(node_url.clone());
+    let pool_stats = Arc::new(Mutex::new(PoolStats::default()));
 
     let gbt_client = client.clone();
     let gbt_pool = pool_address.clone();
     let gbt_bind_for_task = gbt_bind.clone();
+    let gbt_stats = pool_stats.clone();
     tokio::spawn(async move {
-        if let Err(e) = run_gbt_server(&gbt_bind_for_task, gbt_client, gbt_pool).await {
+        if let Err(e) = run_gbt_server(
+            &gbt_bind_for_task,
+            gbt_client,
+            gbt_pool,
+            longpoll_timeout,
+            gbt_stats,
+        )
+        .await
+        {
             log::error!("GBT server failed: {}", e);
         }
     });
@@ -590,6 +1129,167 @@ async fn main() -> Result<()> {
     log::info!("GBT server listening on {}", gbt_bind);
     log::info!("Using node RPC at {}", node_url);
 
-    run_stratum_server(&stratum_bind, client, pool_address).await
+    run_stratum_server(&stratum_bind, client, pool_address, share_interval, pool_stats).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vardiff_raises_for_fast_miners_and_respects_network_cap() {
+        let start = Instant::now();
+        let mut vardiff = VarDiff::new(Duration::from_secs(15), start);
+        let mut changed = None;
+        for i in 1..=20u64 {
+            // a share every 500ms, well above the target rate
+            let now = start + Duration::from_millis(15_000 + 500 * i);
+            changed = changed.or(vardiff.record_share(now, 3));
+        }
+        assert_eq!(changed, Some(2));
+
+        vardiff.difficulty = 3;
+        assert_eq!(vardiff.record_share(start + Duration::from_secs(60), 3), None);
+        assert_eq!(vardiff.clamp_to_network(2), Some(2));
+    }
+
+    fn test_template(difficulty: u32) -> MiningTemplate {
+        MiningTemplate {
+            job_id: "1".to_string(),
+            height: 1,
+            prev_hash: "0".repeat(64),
+            difficulty,
+            timestamp: 1_700_000_000,
+            merkle_root: "0".repeat(64),
+            transactions: Vec::new(),
+            coinbase_value: U256::zero(),
+        }
+    }
+
+    #[test]
+    fn network_target_matches_the_node_compact_bits() {
+        assert_eq!(
+            target_from_difficulty(0x1d00ffff),
+            format!("00000000ffff{}", "0".repeat(52))
+        );
+        assert_eq!(target_from_difficulty(0), "0".repeat(64));
+        assert_eq!(network_share_difficulty(0x1d00ffff), 8);
+        assert_eq!(network_share_difficulty(0x207fffff), 0);
+
+        let target = target_from_difficulty(0x1d00ffff);
+        assert!(!meets_network_target(&target, 0x1d00ffff));
+        let below = format!("00000000fffe{}", "f".repeat(52));
+        assert!(meets_network_target(&below, 0x1d00ffff));
+        assert!(!meets_network_target(&below, 0));
+    }
+
+    #[test]
+    fn shares_are_checked_against_share_then_network_difficulty() {
+        // Target of 1: no share is ever a block
+        let template = test_template(0x03000001);
+        // first nonce whose hash has exactly one leading zero: a share at difficulty 1, not a block
+        let nonce = (0u64..)
+            .find(|n| {
+                let hash = compute_header_hash(&template_header(&template, *n)).unwrap();
+                meets_difficulty(&hash, 1) && !meets_difficulty(&hash, 2)
+            })
+            .unwrap();
+        assert_eq!(classify_share(&template, nonce, 1).unwrap(), ShareOutcome::Share);
+        assert_eq!(classify_share(&template, nonce, 2).unwrap(), ShareOutcome::LowDifficulty);
+        assert!(build_block_from_template(&template, nonce).is_err());
+
+        // Roughly every other hash is a block
+        let easy = test_template(0x207fffff);
+        assert_eq!(classify_share(&easy, nonce, 1).unwrap(), ShareOutcome::Block);
+        assert!(build_block_from_template(&easy, nonce).is_ok());
+    }
+
+    #[test]
+    fn pool_stats_count_shares_and_estimate_hashrate() {
+        let start = Instant::now();
+        let mut stats = PoolStats::default();
+        stats.record_share("rig1", true, 2, start);
+        stats.record_share("rig1", false, 2, start);
+        stats.record_share("rig2", true, 1, start);
+
+        let json = stats.to_json(start);
+        assert_eq!(json["workers"]["rig1"]["accepted"], 1);
+        assert_eq!(json["workers"]["rig1"]["rejected"], 1);
+        assert_eq!(json["workers"]["rig2"]["difficulty"], 1);
+        let expected = (256.0 + 16.0) / POOL_HASHRATE_WINDOW.as_secs_f64();
+        assert!((stats.hashrate(start) - expected).abs() < 1e-9);
+
+        // work ages out of the window
+        assert_eq!(stats.hashrate(start + POOL_HASHRATE_WINDOW + Duration::from_secs(1)), 0.0);
+    }
+
+    #[test]
+    fn pool_stats_cap_workers_and_drop_idle_ones() {
+        let start = Instant::now();
+        let mut stats = PoolStats::default();
+        for i in 0..MAX_TRACKED_WORKERS {
+            let at = start + Duration::from_millis(i as u64);
+            stats.record_share(&format!("rig{}", i), true, 1, at);
+        }
+        assert_eq!(stats.workers.len(), MAX_TRACKED_WORKERS);
+
+        // A new name evicts the least recently seen worker rather than growing the map
+        let later = start + Duration::from_secs(60);
+        stats.record_share("fresh", false, 1, later);
+        assert_eq!(stats.workers.len(), MAX_TRACKED_WORKERS);
+        assert!(!stats.workers.contains_key("rig0"));
+        assert!(stats.workers.contains_key("fresh"));
+
+        // Everyone but a worker that kept submitting goes idle
+        let idle = start + WORKER_IDLE_TIMEOUT + Duration::from_secs(30);
+        stats.record_share("fresh", true, 1, idle);
+        stats.record_share("newcomer", true, 1, idle);
+        assert_eq!(stats.workers.len(), 2);
+    }
+
+    #[test]
+    fn connection_guard_counts_until_dropped() {
+        let stats = Arc::new(Mutex::new(PoolStats::default()));
+        let first = ConnectionGuard::new(stats.clone());
+        let second = ConnectionGuard::new(stats.clone());
+        assert_eq!(stats.lock().unwrap().connections, 2);
+        drop(first);
+        assert_eq!(stats.lock().unwrap().connections, 1);
+
+        // A connection task that panics still gives its slot back
+        let panicking = stats.clone();
+        let result = std::thread::spawn(move || {
+            let _guard = ConnectionGuard::new(panicking);
+            panic!("connection task failed");
+        })
+        .join();
+        assert!(result.is_err());
+        assert_eq!(stats.lock().unwrap().connections, 1);
+        drop(second);
+        assert_eq!(stats.lock().unwrap().connections, 0);
+    }
+
+    #[test]
+    fn duplicate_shares_are_detected_per_job() {
+        let mut submitted = SubmittedShares::default();
+        assert!(submitted.insert("a", 7));
+        assert!(!submitted.insert("a", 7));
+        assert!(submitted.insert("b", 7));
+        for job in 0..DUPLICATE_TRACKED_JOBS {
+            submitted.insert(&job.to_string(), 1);
+        }
+        // "a" has been evicted, so it is no longer tracked
+        assert!(submitted.insert("a", 7));
+    }
+
+    #[test]
+    fn vardiff_lowers_for_idle_miners() {
+        let start = Instant::now();
+        let mut vardiff = VarDiff::new(Duration::from_secs(15), start);
+        vardiff.difficulty = 3;
+        assert_eq!(vardiff.check_idle(start + Duration::from_secs(30), 8), None);
+        assert_eq!(vardiff.check_idle(start + Duration::from_secs(61), 8), Some(2));
+        assert_eq!(vardiff.difficulty, 2);
+    }
 }
 
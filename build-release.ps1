@@ -101,6 +101,11 @@ New-Item -ItemType Directory -Force -Path "$ReleaseDir/explorer_web" | Out-Null
 Copy-Item -Recurse -Force "explorer/web/dist/*" "$ReleaseDir/explorer_web/"
 Write-Success "Deployed explorer web to $ReleaseDir/explorer_web"
 
+# Node dashboard (served at / by the node; DASHBOARD_PATH defaults to web/dashboard.html)
+New-Item -ItemType Directory -Force -Path "$ReleaseDir/web" | Out-Null
+Copy-Item -Force "node/web/dashboard.html" "$ReleaseDir/web/"
+Write-Success "Deployed node dashboard to $ReleaseDir/web"
+
 # Copy executables
 Write-Info "Copying executables..."
 $Executables = @(
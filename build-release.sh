@@ -96,6 +96,11 @@ mkdir -p "$RELEASE_DIR/explorer_web"
 cp -R "explorer/web/dist/." "$RELEASE_DIR/explorer_web/"
 echo -e "${SUCCESS}OK    Deployed explorer web to $RELEASE_DIR/explorer_web${NC}"
 
+# Node dashboard (served at / by the node; DASHBOARD_PATH defaults to web/dashboard.html)
+mkdir -p "$RELEASE_DIR/web"
+cp "node/web/dashboard.html" "$RELEASE_DIR/web/"
+echo -e "${SUCCESS}OK    Deployed node dashboard to $RELEASE_DIR/web${NC}"
+
 # Copy executables
 echo -e "${INFO}INFO  Copying executables...${NC}"
 EXECUTABLES=(
@@ -0,0 +1,96 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Canonical JSON for payloads that get signed: object keys sorted by their UTF-8
+/// bytes at every depth and no whitespace, so the signer and the verifier hash the
+/// same bytes whatever order a JSON library happens to keep keys in.
+pub fn to_canonical_string(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+/// Canonical JSON bytes of any serializable payload
+pub fn to_canonical_bytes<T: Serialize>(payload: &T) -> serde_json::Result<Vec<u8>> {
+    let value = serde_json::to_value(payload)?;
+    Ok(to_canonical_string(&value).into_bytes())
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, key);
+                out.push(':');
+                write_value(out, value);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::String(s) => write_string(out, s),
+        // null, booleans and numbers already have a single compact form
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    // serde_json escapes only what JSON requires, the same way every time
+    out.push_str(&Value::String(s.to_string()).to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_payloads_have_identical_bytes() {
+        let a: Value = serde_json::from_str(
+            r#"{"port": 8335, "meta": {"z": [1, {"b": true, "a": null}], "a": "x\"y"}, "height": 12}"#,
+        )
+        .unwrap();
+        let b: Value = serde_json::from_str(
+            r#"{
+                "height": 12,
+                "meta": {"a": "x\"y", "z": [1, {"a": null, "b": true}]},
+                "port": 8335
+            }"#,
+        )
+        .unwrap();
+
+        let canonical = to_canonical_string(&a);
+        assert_eq!(canonical, to_canonical_string(&b));
+        assert_eq!(
+            canonical,
+            r#"{"height":12,"meta":{"a":"x\"y","z":[1,{"a":null,"b":true}]},"port":8335}"#
+        );
+
+        // Typed payloads agree with the same value built by hand
+        #[derive(Serialize)]
+        struct Register {
+            version: &'static str,
+            port: u16,
+        }
+        let typed = to_canonical_bytes(&Register {
+            version: "0.1.0",
+            port: 8335,
+        })
+        .unwrap();
+        let by_hand = serde_json::json!({"port": 8335, "version": "0.1.0"});
+        assert_eq!(typed, to_canonical_string(&by_hand).into_bytes());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ports::Ports;
 use serde::{Deserialize, Serialize};
 use std::{fs, path::PathBuf};
 
@@ -5,6 +6,30 @@ use std::{fs, path::PathBuf};
 pub struct Config {
     pub wallet_path: String,
     pub node_rpc_url: String,
+    /// Compact difficulty bits for the genesis block of a fresh chain (None = pow limit)
+    #[serde(default)]
+    pub initial_difficulty_bits: Option<u32>,
+    /// Target seconds between blocks (None = network default, 120)
+    #[serde(default)]
+    pub block_interval: Option<i64>,
+    /// Blocks the difficulty retarget averages over (None = network default, 30)
+    #[serde(default)]
+    pub retarget_window: Option<u64>,
+    /// Easiest allowed difficulty, as compact bits (None = network default)
+    #[serde(default)]
+    pub pow_limit_bits: Option<u32>,
+    /// Hardest allowed difficulty, as compact bits (None = network default)
+    #[serde(default)]
+    pub pow_min_bits: Option<u32>,
+    /// Address the genesis coinbase pays (None = network default, unspendable)
+    #[serde(default)]
+    pub genesis_address: Option<String>,
+    /// First height allowed to include version 2 transactions (None = network default)
+    #[serde(default)]
+    pub tx_expiry_height: Option<u64>,
+    /// P2P network magic (None = by ASTRAM_NETWORK: the mainnet or testnet default)
+    #[serde(default)]
+    pub network_magic: Option<u32>,
 }
 
 impl Config {
@@ -67,6 +92,63 @@ impl Config {
         match key {
             "wallet_path" => self.wallet_path = value.to_string(),
             "node_rpc_url" => self.node_rpc_url = value.to_string(),
+            "initial_difficulty_bits" | "pow_limit_bits" | "pow_min_bits" => {
+                let raw = value.trim_start_matches("0x");
+                let bits = match u32::from_str_radix(raw, 16) {
+                    Ok(bits) => Some(bits),
+                    Err(_) => {
+                        println!(
+                            "Invalid difficulty bits (expected hex, e.g. 0x1d0fffff): {}",
+                            value
+                        );
+                        return;
+                    }
+                };
+                match key {
+                    "initial_difficulty_bits" => self.initial_difficulty_bits = bits,
+                    "pow_limit_bits" => self.pow_limit_bits = bits,
+                    _ => self.pow_min_bits = bits,
+                }
+            }
+            // Same bounds as the node's ChainParams::validate
+            "block_interval" => match value.parse::<i64>() {
+                Ok(secs) if (1..=86_400).contains(&secs) => self.block_interval = Some(secs),
+                _ => {
+                    println!(
+                        "Invalid block interval (expected 1 to 86400 seconds): {}",
+                        value
+                    );
+                    return;
+                }
+            },
+            "genesis_address" => self.genesis_address = Some(value.to_string()),
+            "tx_expiry_height" => match value.parse::<u64>() {
+                Ok(height) => self.tx_expiry_height = Some(height),
+                Err(_) => {
+                    println!(
+                        "Invalid activation height (expected a block height): {}",
+                        value
+                    );
+                    return;
+                }
+            },
+            "network_magic" => match crate::network::parse_magic(value) {
+                Ok(magic) => self.network_magic = Some(magic),
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            },
+            "retarget_window" => match value.parse::<u64>() {
+                Ok(blocks) if (2..=10_080).contains(&blocks) => self.retarget_window = Some(blocks),
+                _ => {
+                    println!(
+                        "Invalid retarget window (expected 2 to 10080 blocks): {}",
+                        value
+                    );
+                    return;
+                }
+            },
             _ => {
                 println!("Unknown configuration key: {}", key);
                 return;
@@ -94,7 +176,15 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             wallet_path: Self::default_wallet_path(),
-            node_rpc_url: "http://127.0.0.1:19533".to_string(),
+            node_rpc_url: Ports::default().node_http_url(),
+            initial_difficulty_bits: None,
+            block_interval: None,
+            retarget_window: None,
+            pow_limit_bits: None,
+            pow_min_bits: None,
+            genesis_address: None,
+            tx_expiry_height: None,
+            network_magic: None,
         }
     }
 }
@@ -1 +1,5 @@
+pub mod canonical_json;
 pub mod config;
+pub mod network;
+pub mod ports;
+pub mod time;
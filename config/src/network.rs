@@ -0,0 +1,46 @@
+// Network magic shared by every Astram binary. Nodes send it in the p2p handshake and
+// with DNS seed registrations, so a testnet node and a mainnet node never peer, and a
+// seed only hands out nodes of its own network.
+
+/// Magic of the public network ("ASRM")
+pub const MAINNET_MAGIC: u32 = 0x4153_524d;
+/// Magic of the public testnet ("TSRM")
+pub const TESTNET_MAGIC: u32 = 0x5453_524d;
+
+/// Default magic for a network name: the testnet's for "testnet" (any case), the
+/// mainnet's for anything else
+pub fn default_magic(network: &str) -> u32 {
+    if network.trim().eq_ignore_ascii_case("testnet") {
+        TESTNET_MAGIC
+    } else {
+        MAINNET_MAGIC
+    }
+}
+
+/// Parse a magic given as hex (`0x5453524d`) or decimal
+pub fn parse_magic(value: &str) -> Result<u32, String> {
+    let value = value.trim();
+    let hex = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"));
+    let parsed = match hex {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| format!("invalid network magic {:?}: expected hex or decimal", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn networks_have_distinct_magics() {
+        assert_ne!(MAINNET_MAGIC, TESTNET_MAGIC);
+        assert_eq!(default_magic("mainnet"), MAINNET_MAGIC);
+        assert_eq!(default_magic("TestNet"), TESTNET_MAGIC);
+        assert_eq!(parse_magic("0x5453524d"), Ok(TESTNET_MAGIC));
+        assert_eq!(parse_magic(&MAINNET_MAGIC.to_string()), Ok(MAINNET_MAGIC));
+        assert!(parse_magic("0xnope").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+/// Default port map shared by every Astram binary.
+///
+/// All services should read their default ports from here rather than
+/// hardcoding numbers, so the explorer, wallet and DNS health checks always
+/// point at the port the node actually serves on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ports {
+    /// Node peer-to-peer listener (`P2P_PORT` in nodeSettings.conf)
+    pub p2p: u16,
+    /// Node HTTP API + dashboard (`HTTP_PORT`); used by explorer, wallet-cli and DNS health checks
+    pub http_rpc: u16,
+    /// Ethereum-compatible JSON-RPC for MetaMask (`ETH_RPC_PORT`)
+    pub eth_rpc: u16,
+    /// Block explorer web UI / API
+    pub explorer: u16,
+    /// Stratum mining pool listener (`STRATUM_BIND`)
+    pub stratum: u16,
+    /// getblocktemplate endpoint exposed by the stratum server (`GBT_BIND`)
+    pub gbt: u16,
+    /// DNS seed registry server
+    pub dns: u16,
+}
+
+impl Ports {
+    pub const DEFAULT: Ports = Ports {
+        p2p: 8335,
+        http_rpc: 19533,
+        eth_rpc: 8545,
+        explorer: 8080,
+        stratum: 3333,
+        gbt: 8332,
+        dns: 8053,
+    };
+
+    /// Base URL of the node HTTP API on localhost
+    pub fn node_http_url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.http_rpc)
+    }
+}
+
+impl Default for Ports {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports_are_distinct() {
+        let p = Ports::default();
+        let mut all = vec![
+            p.p2p, p.http_rpc, p.eth_rpc, p.explorer, p.stratum, p.gbt, p.dns,
+        ];
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), 7);
+    }
+
+    #[test]
+    fn wallet_default_url_matches_node_http_port() {
+        let cfg = crate::config::Config::default();
+        assert_eq!(cfg.node_rpc_url, Ports::default().node_http_url());
+    }
+}
@@ -0,0 +1,81 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Timestamp pair attached to JSON responses: unix seconds plus the same
+/// instant as RFC 3339, so clients don't have to guess which format they got.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timestamp {
+    pub timestamp_unix: i64,
+    pub timestamp_iso: String,
+}
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self::from_datetime(Utc::now())
+    }
+
+    /// Out-of-range values fall back to the unix epoch for the ISO field
+    pub fn from_unix(secs: i64) -> Self {
+        let dt = Utc.timestamp_opt(secs, 0).single().unwrap_or_default();
+        Self {
+            timestamp_unix: secs,
+            timestamp_iso: dt.to_rfc3339(),
+        }
+    }
+
+    pub fn from_datetime(dt: DateTime<Utc>) -> Self {
+        Self {
+            timestamp_unix: dt.timestamp(),
+            timestamp_iso: dt.to_rfc3339(),
+        }
+    }
+}
+
+/// Add `timestamp_unix` / `timestamp_iso` for the current time to a JSON object response.
+/// Non-object values are returned unchanged.
+pub fn with_timestamps(value: Value) -> Value {
+    with_timestamp(value, &Timestamp::now())
+}
+
+pub fn with_timestamp(mut value: Value, ts: &Timestamp) -> Value {
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("timestamp_unix".to_string(), Value::from(ts.timestamp_unix));
+        obj.insert(
+            "timestamp_iso".to_string(),
+            Value::from(ts.timestamp_iso.clone()),
+        );
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_fields_present_and_agree() {
+        let value = with_timestamp(
+            serde_json::json!({"status": "ok"}),
+            &Timestamp::from_unix(1_700_000_000),
+        );
+
+        assert_eq!(value["status"], "ok");
+        assert_eq!(value["timestamp_unix"], 1_700_000_000);
+        assert_eq!(value["timestamp_iso"], "2023-11-14T22:13:20+00:00");
+
+        let iso = value["timestamp_iso"].as_str().unwrap();
+        let parsed = DateTime::parse_from_rfc3339(iso).unwrap();
+        assert_eq!(
+            parsed.timestamp(),
+            value["timestamp_unix"].as_i64().unwrap()
+        );
+    }
+
+    #[test]
+    fn now_fields_agree() {
+        let ts = Timestamp::now();
+        let parsed = DateTime::parse_from_rfc3339(&ts.timestamp_iso).unwrap();
+        assert_eq!(parsed.timestamp(), ts.timestamp_unix);
+    }
+}
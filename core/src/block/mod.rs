@@ -50,41 +50,116 @@ pub fn compute_header_hash(header: &BlockHeader) -> Result<String, anyhow::Error
 
 /// Compute merkle root (assuming txids are in hex format)
 pub fn compute_merkle_root(txids: &[String]) -> String {
-    if txids.is_empty() {
-        return to_hex(&sha256d(&[]));
+    MerkleTree::build(txids).root()
+}
+
+/// Inclusion proof for `txids[target_index]`: sibling hashes from leaf to root, each
+/// flagged `true` when the sibling sits on the left. Empty if the index is out of range.
+pub fn compute_merkle_proof(txids: &[String], target_index: usize) -> Vec<(String, bool)> {
+    MerkleTree::build(txids).proof(target_index)
+}
+
+/// Recompute the root from `txid` and its proof and compare it with `root`
+pub fn verify_merkle_proof(txid: &str, proof: &[(String, bool)], root: &str) -> bool {
+    let decode = |h: &str| -> Option<[u8; 32]> { hex::decode(h).ok()?.try_into().ok() };
+    let Some(mut hash) = decode(txid) else {
+        return false;
+    };
+    for (sibling, is_left) in proof {
+        let Some(sibling) = decode(sibling) else {
+            return false;
+        };
+        let mut concat = Vec::with_capacity(64);
+        if *is_left {
+            concat.extend_from_slice(&sibling);
+            concat.extend_from_slice(&hash);
+        } else {
+            concat.extend_from_slice(&hash);
+            concat.extend_from_slice(&sibling);
+        }
+        hash = sha256d(&concat);
     }
+    to_hex(&hash) == root
+}
 
-    // decode hex -> bytes array [u8; 32]
-    let mut leaves: Vec<[u8; 32]> = txids
-        .iter()
-        .map(|h| {
-            let b = hex::decode(h).unwrap_or_else(|_| vec![0u8; 32]);
-            let mut a = [0u8; 32];
-            if b.len() == 32 {
-                a.copy_from_slice(&b);
+/// Full merkle tree over a block's txids.
+/// `levels[0]` holds the ordered leaves, the last level holds the root. Levels are stored
+/// unpadded; an odd node is paired with itself when hashing the next level up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    pub fn build(txids: &[String]) -> Self {
+        // decode hex -> bytes array [u8; 32]
+        let leaves: Vec<[u8; 32]> = txids
+            .iter()
+            .map(|h| {
+                let b = hex::decode(h).unwrap_or_else(|_| vec![0u8; 32]);
+                let mut a = [0u8; 32];
+                if b.len() == 32 {
+                    a.copy_from_slice(&b);
+                }
+                a
+            })
+            .collect();
+
+        let mut levels = vec![leaves];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let level = levels.last().unwrap();
+            let mut next = Vec::with_capacity(level.len().div_ceil(2));
+            for pair in level.chunks(2) {
+                let right = pair.get(1).unwrap_or(&pair[0]);
+                let mut concat = Vec::with_capacity(64);
+                concat.extend_from_slice(&pair[0]);
+                concat.extend_from_slice(right);
+                next.push(sha256d(&concat));
             }
-            a
-        })
-        .collect();
-
-    while leaves.len() > 1 {
-        if leaves.len() % 2 == 1 {
-            let last = *leaves.last().unwrap();
-            leaves.push(last);
+            levels.push(next);
         }
 
-        let mut next = Vec::with_capacity(leaves.len() / 2);
-        for i in (0..leaves.len()).step_by(2) {
-            let mut concat = Vec::with_capacity(64);
-            concat.extend_from_slice(&leaves[i]);
-            concat.extend_from_slice(&leaves[i + 1]);
-            let h = sha256d(&concat);
-            next.push(h);
+        MerkleTree { levels }
+    }
+
+    /// Root as hex; an empty tree hashes the empty string
+    pub fn root(&self) -> String {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => to_hex(root),
+            None => to_hex(&sha256d(&[])),
         }
-        leaves = next;
     }
 
-    to_hex(&leaves[0])
+    /// Ordered leaf hashes (decoded txids)
+    pub fn leaves(&self) -> &[[u8; 32]] {
+        &self.levels[0]
+    }
+
+    pub fn levels(&self) -> &[Vec<[u8; 32]>] {
+        &self.levels
+    }
+
+    /// Sibling path for the leaf at `index`; see `compute_merkle_proof`
+    pub fn proof(&self, index: usize) -> Vec<(String, bool)> {
+        if index >= self.leaves().len() {
+            return Vec::new();
+        }
+        let mut proof = Vec::with_capacity(self.levels.len());
+        let mut pos = index;
+        // every level but the root contributes one sibling
+        for level in &self.levels[..self.levels.len() - 1] {
+            let is_left = pos % 2 == 1;
+            let sibling = if is_left {
+                level[pos - 1]
+            } else {
+                // an odd node at the end is paired with itself
+                *level.get(pos + 1).unwrap_or(&level[pos])
+            };
+            proof.push((to_hex(&sibling), is_left));
+            pos /= 2;
+        }
+        proof
+    }
 }
 
 #[cfg(test)]
@@ -98,6 +173,35 @@ mod tests {
         assert!(!root.is_empty());
     }
 
+    #[test]
+    fn merkle_tree_matches_pairwise_root() {
+        let txids: Vec<String> = (0..5u8).map(|i| format!("{:02x}", i).repeat(32)).collect();
+        let tree = MerkleTree::build(&txids);
+
+        assert_eq!(tree.leaves().len(), 5);
+        assert_eq!(tree.levels().len(), 4); // 5 -> 3 -> 2 -> 1
+        assert_eq!(tree.root(), compute_merkle_root(&txids));
+        assert_eq!(MerkleTree::build(&[]).root(), to_hex(&sha256d(&[])));
+    }
+
+    #[test]
+    fn merkle_proof_verifies_every_leaf() {
+        for count in [1usize, 2, 5, 8] {
+            let txids: Vec<String> = (0..count as u8)
+                .map(|i| format!("{:02x}", i).repeat(32))
+                .collect();
+            let root = compute_merkle_root(&txids);
+            for (i, txid) in txids.iter().enumerate() {
+                let proof = compute_merkle_proof(&txids, i);
+                assert!(verify_merkle_proof(txid, &proof, &root));
+                // the proof doesn't vouch for a different tx
+                let other = "ff".repeat(32);
+                assert!(!verify_merkle_proof(&other, &proof, &root));
+            }
+        }
+        assert!(compute_merkle_proof(&["00".repeat(32)], 1).is_empty());
+    }
+
     #[test]
     fn serialize_header_and_hash() {
         let header = BlockHeader {
@@ -1,15 +1,21 @@
-use crate::block::{Block, BlockHeader, compute_header_hash, compute_merkle_root};
+use crate::block::{Block, BlockHeader, MerkleTree, compute_header_hash, compute_merkle_root};
+use crate::checkpoint::{self, Checkpoint};
 use crate::db::{open_db, put_batch};
-use crate::transaction::Transaction;
-use crate::utxo::Utxo;
+use crate::security::{BlockFailureReason, reject};
+use crate::transaction::{Transaction, TransactionOutput};
+use crate::utxo::{Utxo, UtxoFilter};
 use anyhow::{Result, anyhow};
 use bincode::config;
-use chrono::Utc;
 use hex;
 use log;
 use once_cell::sync::Lazy;
 use primitive_types::U256;
 use rocksdb::{DB, WriteBatch};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Headers held while waiting for them to link up to the assume-valid checkpoint
+const MAX_ASSUME_VALID_HEADERS: usize = 500_000;
 
 pub static BINCODE_CONFIG: Lazy<config::Configuration> = Lazy::new(|| config::standard());
 
@@ -24,18 +30,227 @@ pub struct Blockchain {
     pub db: DB,
     pub chain_tip: Option<String>, // tip hash hex
     pub difficulty: u32,
-    pub block_interval: i64,  // Target block generation interval (seconds)
+    /// Block interval, retarget window and difficulty bounds this node enforces
+    pub params: ChainParams,
     pub max_reorg_depth: u64, // Maximum allowed reorganization depth (security)
     pub max_future_block_time: i64, // Maximum seconds a block can be in the future
     pub enable_deep_reorg_alerts: bool, // Alert on deep reorgs (vs hard reject)
+    /// Pinned checkpoint (height, hash) for checkpoint-assisted initial sync; None
+    /// verifies every signature
+    pub assume_valid: Option<(u64, String)>,
+    /// Hashes on the header chain that ends at `assume_valid`'s hash. Only these blocks
+    /// skip signature checks.
+    assume_valid_chain: HashSet<String>,
+    /// Headers received towards `assume_valid` that don't link up to it yet, by hash
+    assume_valid_headers: HashMap<String, BlockHeader>,
+    /// Keep block bodies only this many blocks below the tip (never fewer than
+    /// `max_reorg_depth`); older ones are pruned down to their headers. None keeps all.
+    pub prune_depth: Option<u64>,
+    /// Checkpoints this node enforces: the compiled set merged with the data
+    /// directory's `checkpoints.json`
+    pub checkpoints: Vec<Checkpoint>,
+    merkle_cache: Mutex<MerkleCache>,
+    /// Bloom filter over `u:` keys; lets input validation reject missing outpoints
+    /// without a DB read
+    utxo_filter: Mutex<UtxoFilter>,
 }
 
-impl Blockchain {
-    const POW_LIMIT_BITS: u32 = 0x1d0fffff; // Easiest allowed target (testnet-like)
-    const POW_MIN_BITS: u32 = 0x1900ffff; // Hardest allowed target
-    const RETARGET_WINDOW: u64 = 30; // 30 blocks rolling window
+/// Consensus timing and difficulty bounds. The defaults are the network's values; a
+/// node with different ones (e.g. a fast local testnet) follows its own chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainParams {
+    /// Target seconds between blocks
+    pub block_interval: i64,
+    /// Blocks in the rolling window the difficulty retarget looks back over
+    pub retarget_window: u64,
+    /// Easiest allowed target, as compact bits
+    pub pow_limit_bits: u32,
+    /// Hardest allowed target, as compact bits
+    pub pow_min_bits: u32,
+    /// Address the genesis coinbase pays. Part of the genesis hash, so it identifies
+    /// the network as much as the block does.
+    pub genesis_address: String,
+    /// First height whose blocks may carry version 2 (expiring) transactions
+    pub tx_expiry_height: u64,
+}
+
+/// Default genesis coinbase address; nobody holds its key, so the genesis reward can
+/// never be spent
+pub const GENESIS_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+/// Longest block interval ChainParams accepts: one day
+pub const MAX_BLOCK_INTERVAL: i64 = 86_400;
+
+/// Largest retarget window ChainParams accepts: two weeks of blocks at the default interval
+pub const MAX_RETARGET_WINDOW: u64 = 10_080;
+
+/// Default activation height for version 2 transactions. Older nodes can't decode
+/// them, so blocks below this height must stick to the legacy format.
+pub const TX_EXPIRY_ACTIVATION_HEIGHT: u64 = 50_000;
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        Self {
+            block_interval: 120,        // Target: 2 minutes per block
+            retarget_window: 30,        // 30 blocks rolling window
+            pow_limit_bits: 0x1d0fffff, // Easiest allowed target (testnet-like)
+            pow_min_bits: 0x1900ffff,   // Hardest allowed target
+            genesis_address: GENESIS_ADDRESS.to_string(),
+            tx_expiry_height: TX_EXPIRY_ACTIVATION_HEIGHT,
+        }
+    }
+}
+
+impl ChainParams {
+    /// Reject parameters the retarget can't work with
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_BLOCK_INTERVAL).contains(&self.block_interval) {
+            return Err(anyhow!(
+                "block interval must be 1 to {} seconds, got {}",
+                MAX_BLOCK_INTERVAL,
+                self.block_interval
+            ));
+        }
+        if !(2..=MAX_RETARGET_WINDOW).contains(&self.retarget_window) {
+            return Err(anyhow!(
+                "retarget window must be 2 to {} blocks, got {}",
+                MAX_RETARGET_WINDOW,
+                self.retarget_window
+            ));
+        }
+        let pow_limit = Blockchain::compact_to_target(self.pow_limit_bits);
+        let min_target = Blockchain::compact_to_target(self.pow_min_bits);
+        if min_target.is_zero() || min_target > pow_limit {
+            return Err(anyhow!(
+                "pow limit 0x{:08x} must be at least as easy as the minimum target 0x{:08x}",
+                self.pow_limit_bits,
+                self.pow_min_bits
+            ));
+        }
+        let hex = self.genesis_address.strip_prefix("0x").unwrap_or("");
+        if hex.len() != 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(anyhow!(
+                "genesis address must be 0x followed by 40 hex digits, got {:?}",
+                self.genesis_address
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether a block at `height` may contain a transaction of this version
+    pub fn allows_tx_version(&self, version: u32, height: u64) -> bool {
+        version <= crate::transaction::TX_VERSION_LEGACY || height >= self.tx_expiry_height
+    }
+
+    fn pow_limit_target(&self) -> U256 {
+        Blockchain::compact_to_target(self.pow_limit_bits)
+    }
+
+    fn min_target(&self) -> U256 {
+        Blockchain::compact_to_target(self.pow_min_bits)
+    }
+}
+
+/// Number of recently validated blocks whose merkle trees are kept in memory
+const MERKLE_CACHE_CAPACITY: usize = 64;
+
+/// Heights scanned beyond each binary-search boundary in timestamp queries: MTP only
+/// requires a timestamp above the median of the previous 11 blocks, so block order
+/// and timestamp order can disagree by a few blocks
+const TIMESTAMP_SCAN_MARGIN: u64 = 11;
+
+/// Version of the tx index stored under `txindex`; bumping it rebuilds the index on
+/// startup. Version 2 added the per-address history (`ah:`).
+const TX_INDEX_VERSION: &[u8] = b"2";
+
+/// How one confirmed transaction affected one address
+#[derive(Debug, Clone)]
+pub struct AddressTx {
+    pub txid: String,
+    pub height: u64,
+    /// Timestamp of the containing block
+    pub timestamp: i64,
+    pub coinbase: bool,
+    /// Outputs paid to the address
+    pub received: U256,
+    /// Outputs of the address spent by the transaction
+    pub sent: U256,
+}
 
-    fn compact_to_target(bits: u32) -> U256 {
+impl AddressTx {
+    /// "coinbase", "received", "sent" or "self" (spent and paid back in full)
+    pub fn direction(&self) -> &'static str {
+        if self.coinbase {
+            "coinbase"
+        } else if self.sent.is_zero() {
+            "received"
+        } else if self.received >= self.sent {
+            "self"
+        } else {
+            "sent"
+        }
+    }
+}
+
+/// Error for a block whose body was removed by pruning. Its header is still served by
+/// `load_header`; callers can `downcast_ref` to tell this apart from a missing block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockPruned {
+    pub hash: String,
+}
+
+impl std::fmt::Display for BlockPruned {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "block {} has been pruned; only its header is kept",
+            self.hash
+        )
+    }
+}
+
+impl std::error::Error for BlockPruned {}
+
+/// Heights pruned per write batch, so catching up on a large backlog doesn't build one
+/// huge batch
+const PRUNE_BATCH_BLOCKS: u64 = 1000;
+
+/// One page of an address's UTXOs, largest first
+#[derive(Debug, Clone)]
+pub struct UtxoPage {
+    pub utxos: Vec<Utxo>,
+    /// Passed as `after` to get the next page; None on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Bounded FIFO cache of merkle trees keyed by block hash
+#[derive(Default)]
+struct MerkleCache {
+    trees: HashMap<String, Arc<MerkleTree>>,
+    order: VecDeque<String>,
+}
+
+impl MerkleCache {
+    fn get(&self, block_hash: &str) -> Option<Arc<MerkleTree>> {
+        self.trees.get(block_hash).cloned()
+    }
+
+    fn insert(&mut self, block_hash: &str, tree: Arc<MerkleTree>) {
+        if self.trees.insert(block_hash.to_string(), tree).is_some() {
+            return;
+        }
+        self.order.push_back(block_hash.to_string());
+        while self.order.len() > MERKLE_CACHE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.trees.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl Blockchain {
+    /// 256-bit target encoded by compact `bits` (Bitcoin nBits); zero if the mantissa is zero
+    pub fn compact_to_target(bits: u32) -> U256 {
         let exponent = bits >> 24;
         let mantissa = bits & 0x007f_ffff;
         if mantissa == 0 {
@@ -49,7 +264,8 @@ impl Blockchain {
         }
     }
 
-    fn target_to_compact(target: U256) -> u32 {
+    /// Compact bits for `target`, the inverse of `compact_to_target` up to mantissa precision
+    pub fn target_to_compact(target: U256) -> u32 {
         if target.is_zero() {
             return 0;
         }
@@ -79,7 +295,8 @@ impl Blockchain {
         (size << 24) | (mantissa & 0x007f_ffff)
     }
 
-    fn hash_to_u256(hash_hex: &str) -> Result<U256> {
+    /// A block hash as the big-endian number that is compared against the target
+    pub fn hash_to_u256(hash_hex: &str) -> Result<U256> {
         let normalized = hash_hex.strip_prefix("0x").unwrap_or(hash_hex);
         let bytes = hex::decode(normalized)?;
         if bytes.len() != 32 {
@@ -91,14 +308,6 @@ impl Blockchain {
         Ok(U256::from_big_endian(&bytes))
     }
 
-    fn pow_limit_target() -> U256 {
-        Self::compact_to_target(Self::POW_LIMIT_BITS)
-    }
-
-    fn min_target() -> U256 {
-        Self::compact_to_target(Self::POW_MIN_BITS)
-    }
-
     fn is_valid_pow(hash_hex: &str, bits: u32) -> Result<bool> {
         let hash = Self::hash_to_u256(hash_hex)?;
         let target = Self::compact_to_target(bits);
@@ -108,7 +317,15 @@ impl Blockchain {
         Ok(hash < target)
     }
 
+    /// Open the chain at `db_path` with the network's default `ChainParams`
     pub fn new(db_path: &str) -> Result<Self> {
+        Self::with_params(db_path, ChainParams::default())
+    }
+
+    /// Open the chain at `db_path`, enforcing `params` instead of the network defaults
+    pub fn with_params(db_path: &str, params: ChainParams) -> Result<Self> {
+        params.validate()?;
+        let checkpoints = checkpoint::load_checkpoints(std::path::Path::new(db_path))?;
         let db = open_db(db_path)?;
         // load tip if exists
         let tip = db.get(b"tip")?;
@@ -124,28 +341,354 @@ impl Blockchain {
                     block.header.difficulty
                 } else {
                     log::warn!("Failed to decode tip block, using default difficulty");
-                    Self::POW_LIMIT_BITS
+                    params.pow_limit_bits
                 }
             } else {
                 log::warn!("Tip block not found, using default difficulty");
-                Self::POW_LIMIT_BITS
+                params.pow_limit_bits
             }
         } else {
             // No chain exists yet, use default
-            Self::POW_LIMIT_BITS
+            params.pow_limit_bits
         };
 
         log::info!("Blockchain initialized with difficulty: {}", difficulty);
 
-        Ok(Blockchain {
+        let bc = Blockchain {
             db,
             chain_tip,
             difficulty,
-            block_interval: 120,            // Target: 2 minutes per block
+            params,
             max_reorg_depth: 100, // Maximum 100 blocks deep reorganization (security limit)
             max_future_block_time: 7200, // Max 2 hours in the future (clock drift tolerance)
             enable_deep_reorg_alerts: true, // Alert on suspicious reorgs
-        })
+            assume_valid: None,
+            assume_valid_chain: HashSet::new(),
+            assume_valid_headers: HashMap::new(),
+            prune_depth: None,
+            checkpoints,
+            merkle_cache: Mutex::new(MerkleCache::default()),
+            utxo_filter: Mutex::new(UtxoFilter::with_capacity(0)),
+        };
+        let utxo_count = bc.rebuild_utxo_filter()?;
+        log::info!("UTXO filter built over {} outputs", utxo_count);
+        bc.ensure_tx_index()?;
+        bc.ensure_address_index()?;
+
+        Ok(bc)
+    }
+
+    /// Index every transaction of `block` by txid (`tb:`) and eth hash (`eb:`), pointing at
+    /// its height and position in the block, and under each address it pays or spends
+    /// from (`ah:`)
+    fn put_tx_index(batch: &mut WriteBatch, block: &Block) -> Result<()> {
+        for (i, tx) in block.transactions.iter().enumerate() {
+            let location = bincode::encode_to_vec((block.header.index, i as u32), *BINCODE_CONFIG)?;
+            batch.put(format!("tb:{}", tx.txid).as_bytes(), &location);
+            if !tx.eth_hash.is_empty() {
+                batch.put(format!("eb:{}", tx.eth_hash).as_bytes(), &location);
+            }
+            for address in Self::involved_addresses(tx) {
+                let key = Self::address_history_key(&address, block.header.index, i as u32);
+                batch.put(key.as_bytes(), &location);
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the index entries of a block leaving the main chain
+    fn delete_tx_index(batch: &mut WriteBatch, block: &Block) {
+        for (i, tx) in block.transactions.iter().enumerate() {
+            batch.delete(format!("tb:{}", tx.txid).as_bytes());
+            if !tx.eth_hash.is_empty() {
+                batch.delete(format!("eb:{}", tx.eth_hash).as_bytes());
+            }
+            for address in Self::involved_addresses(tx) {
+                let key = Self::address_history_key(&address, block.header.index, i as u32);
+                batch.delete(key.as_bytes());
+            }
+        }
+    }
+
+    /// Lowercased addresses a transaction pays (outputs) or spends from (derived from
+    /// the input pubkeys)
+    fn involved_addresses(tx: &Transaction) -> std::collections::BTreeSet<String> {
+        let senders = tx
+            .inputs
+            .iter()
+            .filter_map(|inp| crate::crypto::eth_address_from_pubkey_hex(&inp.pubkey).ok());
+        let recipients = tx.outputs.iter().map(|out| out.to.clone());
+        senders
+            .chain(recipients)
+            .map(|address| address.to_lowercase())
+            .collect()
+    }
+
+    /// `ah:{address}:{u64::MAX - height}:{u32::MAX - position}`, zero-padded so a prefix
+    /// scan over an address walks its history newest first
+    fn address_history_key(address: &str, height: u64, position: u32) -> String {
+        format!(
+            "ah:{}:{:020}:{:010}",
+            address,
+            u64::MAX - height,
+            u32::MAX - position
+        )
+    }
+
+    /// Backfill the tx index for databases created before it existed, or before its
+    /// current version (once; marked by the `txindex` key)
+    fn ensure_tx_index(&self) -> Result<()> {
+        if self.db.get(b"txindex")?.as_deref() == Some(TX_INDEX_VERSION) {
+            return Ok(());
+        }
+        let mut indexed = 0u64;
+        for height in 0.. {
+            let Some(hash) = self.db.get(format!("i:{}", height).as_bytes())? else {
+                break;
+            };
+            let Some(block) = self.load_block(&String::from_utf8(hash)?)? else {
+                break;
+            };
+            let mut batch = WriteBatch::default();
+            Self::put_tx_index(&mut batch, &block)?;
+            put_batch(&self.db, batch)?;
+            indexed += 1;
+        }
+        self.db.put(b"txindex", TX_INDEX_VERSION)?;
+        if indexed > 0 {
+            log::info!("Indexed transactions of {} blocks", indexed);
+        }
+        Ok(())
+    }
+
+    /// Resolve a `tb:`/`eb:` index entry to the transaction and its block height
+    fn indexed_transaction(&self, key: &str) -> Result<Option<(Transaction, usize)>> {
+        let Some(location) = self.db.get(key.as_bytes())? else {
+            return Ok(None);
+        };
+        let ((height, position), _): ((u64, u32), usize) =
+            bincode::decode_from_slice(&location, *BINCODE_CONFIG)?;
+        let Some(hash) = self.db.get(format!("i:{}", height).as_bytes())? else {
+            return Ok(None);
+        };
+        let Some(block) = self.load_block(&String::from_utf8(hash)?)? else {
+            return Ok(None);
+        };
+        Ok(block
+            .transactions
+            .into_iter()
+            .nth(position as usize)
+            .map(|tx| (tx, height as usize)))
+    }
+
+    /// Rebuild the UTXO bloom filter from the `u:` keys in the database, sized for the
+    /// current set. Returns the number of UTXOs scanned.
+    pub fn rebuild_utxo_filter(&self) -> Result<usize> {
+        let mut keys = Vec::new();
+        let iter = self.db.iterator(rocksdb::IteratorMode::From(
+            b"u:",
+            rocksdb::Direction::Forward,
+        ));
+        for item in iter {
+            let (key, _) = item?;
+            if !key.starts_with(b"u:") {
+                break;
+            }
+            keys.push(key);
+        }
+
+        // Leave headroom so normal growth doesn't trigger an immediate rebuild
+        let mut filter = UtxoFilter::with_capacity(keys.len() * 2);
+        for key in &keys {
+            filter.insert(key);
+        }
+        *self.utxo_filter.lock().unwrap() = filter;
+        Ok(keys.len())
+    }
+
+    /// Record a UTXO key being written so the filter never reports it missing
+    fn note_utxo_key(&self, key: &str) {
+        self.utxo_filter.lock().unwrap().insert(key.as_bytes());
+    }
+
+    /// Address index key of a UTXO: `a:{address}:{U256::MAX - amount}:{txid}:{vout}`,
+    /// with the inverted amount in fixed-width hex so a prefix scan over an address
+    /// yields its outputs largest first
+    fn address_index_key(utxo: &Utxo) -> String {
+        let mut inverted = [0u8; 32];
+        (U256::MAX - utxo.amount()).to_big_endian(&mut inverted);
+        format!(
+            "a:{}:{}:{}:{}",
+            utxo.to.to_lowercase(),
+            hex::encode(inverted),
+            utxo.txid,
+            utxo.vout
+        )
+    }
+
+    /// Write a UTXO together with its address index entry
+    fn put_utxo(&self, batch: &mut WriteBatch, utxo: &Utxo) -> Result<()> {
+        let ukey = format!("u:{}:{}", utxo.txid, utxo.vout);
+        batch.put(
+            ukey.as_bytes(),
+            bincode::encode_to_vec(utxo, *BINCODE_CONFIG)?,
+        );
+        batch.put(Self::address_index_key(utxo).as_bytes(), b"");
+        self.note_utxo_key(&ukey);
+        Ok(())
+    }
+
+    /// Remove a UTXO together with its address index entry
+    fn delete_utxo(batch: &mut WriteBatch, utxo: &Utxo) {
+        batch.delete(format!("u:{}:{}", utxo.txid, utxo.vout).as_bytes());
+        batch.delete(Self::address_index_key(utxo).as_bytes());
+    }
+
+    /// Build the address index for databases created before it existed (once; marked
+    /// by the `addrindex` key)
+    fn ensure_address_index(&self) -> Result<()> {
+        if self.db.get(b"addrindex")?.is_some() {
+            return Ok(());
+        }
+        let mut batch = WriteBatch::default();
+        let mut indexed = 0u64;
+        let iter = self.db.iterator(rocksdb::IteratorMode::From(
+            b"u:",
+            rocksdb::Direction::Forward,
+        ));
+        for item in iter {
+            let (key, value) = item?;
+            if !key.starts_with(b"u:") {
+                break;
+            }
+            let (utxo, _): (Utxo, usize) = bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+            batch.put(Self::address_index_key(&utxo).as_bytes(), b"");
+            indexed += 1;
+        }
+        batch.put(b"addrindex", b"1");
+        put_batch(&self.db, batch)?;
+        if indexed > 0 {
+            log::info!("Indexed {} UTXOs by address", indexed);
+        }
+        Ok(())
+    }
+
+    /// Stored UTXOs referenced by the inputs of `block`, keyed by `u:` key, fetched
+    /// with a single `multi_get`. Outpoints that don't exist are simply absent.
+    fn fetch_spent_utxos(&self, block: &Block) -> Result<HashMap<String, Utxo>> {
+        // Filter miss means the outpoint was never created: leave it out of the read
+        let keys: Vec<String> = block
+            .transactions
+            .iter()
+            .flat_map(|tx| &tx.inputs)
+            .map(|inp| format!("u:{}:{}", inp.txid, inp.vout))
+            .filter(|key| self.utxo_may_exist(key))
+            .collect();
+
+        let mut utxos = HashMap::with_capacity(keys.len());
+        let blobs = self.db.multi_get(keys.iter().map(|key| key.as_bytes()));
+        for (key, blob) in keys.into_iter().zip(blobs) {
+            if let Some(blob) = blob? {
+                let (u, _): (Utxo, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+                utxos.insert(key, u);
+            }
+        }
+        Ok(utxos)
+    }
+
+    /// False only if the UTXO definitely doesn't exist
+    fn utxo_may_exist(&self, key: &str) -> bool {
+        self.utxo_filter
+            .lock()
+            .unwrap()
+            .might_contain(key.as_bytes())
+    }
+
+    /// Rebuild the filter once it has absorbed more keys than it was sized for
+    fn refresh_utxo_filter_if_saturated(&self) -> Result<()> {
+        if self.utxo_filter.lock().unwrap().is_saturated() {
+            let count = self.rebuild_utxo_filter()?;
+            log::info!("UTXO filter resized for {} outputs", count);
+        }
+        Ok(())
+    }
+
+    /// Checkpoint-assisted initial sync: once `accept_assume_valid_headers` has seen a
+    /// header chain ending at the latest pinned checkpoint, blocks on that chain below
+    /// it skip signature verification. PoW, merkle and UTXO checks still run, side
+    /// branches and blocks past the checkpoint are fully validated, and it ends when the
+    /// chain reaches the checkpoint. Returns the checkpoint height, or None if there is
+    /// no pinned checkpoint or the chain is already past it.
+    pub fn enable_checkpoint_sync(&mut self) -> Option<u64> {
+        let height = checkpoint::get_latest_verified_checkpoint_height(&self.checkpoints)?;
+        if self.get_next_index().unwrap_or(0) > height {
+            return None;
+        }
+        let hash = self
+            .checkpoints
+            .iter()
+            .find(|cp| cp.height == height && !cp.hash.is_empty())?
+            .hash
+            .clone();
+        self.assume_valid = Some((height, hash));
+        self.assume_valid_chain.clear();
+        self.assume_valid_headers.clear();
+        Some(height)
+    }
+
+    /// Collect `headers` (from a peer's Headers reply) towards the assume-valid
+    /// checkpoint. Once they form a chain from a block we already have (or genesis) up
+    /// to the checkpoint hash, that chain's blocks may skip signature checks. Returns
+    /// how many blocks the chain covers when this call completes it, otherwise 0.
+    pub fn accept_assume_valid_headers(&mut self, headers: &[BlockHeader]) -> usize {
+        let Some((height, checkpoint_hash)) = self.assume_valid.clone() else {
+            return 0;
+        };
+        if !self.assume_valid_chain.is_empty() {
+            return 0;
+        }
+        for header in headers.iter().filter(|h| h.index <= height) {
+            if let Ok(hash) = compute_header_hash(header) {
+                self.assume_valid_headers.insert(hash, header.clone());
+            }
+        }
+        if self.assume_valid_headers.len() > MAX_ASSUME_VALID_HEADERS {
+            self.assume_valid_headers.clear();
+            return 0;
+        }
+
+        // Walk back from the checkpoint; every link is fixed by the checkpoint's hash
+        let mut chain = HashSet::new();
+        let (mut hash, mut index) = (checkpoint_hash, height);
+        loop {
+            match self.assume_valid_headers.get(&hash) {
+                Some(header) if header.index == index => {
+                    chain.insert(hash.clone());
+                    if index == 0 {
+                        break;
+                    }
+                    hash = header.previous_hash.clone();
+                    index -= 1;
+                }
+                Some(_) => return 0,
+                None => match self.load_header(&hash) {
+                    Ok(Some(header)) if header.index == index => break,
+                    _ => return 0,
+                },
+            }
+        }
+        self.assume_valid_headers.clear();
+        self.assume_valid_chain = chain;
+        self.assume_valid_chain.len()
+    }
+
+    /// Whether `block` may skip signature checks: it is below the assume-valid
+    /// checkpoint and on the header chain that leads to it
+    fn is_assumed_valid(&self, block: &Block) -> bool {
+        self.assume_valid
+            .as_ref()
+            .is_some_and(|(height, _)| block.header.index < *height)
+            && self.assume_valid_chain.contains(&block.hash)
     }
 
     /// Helper: Iterate over all blocks efficiently
@@ -154,28 +697,81 @@ impl Blockchain {
         self.get_all_blocks()
     }
 
-    /// Create genesis block (with a single coinbase transaction)
-    pub fn create_genesis(&mut self, address: &str) -> Result<String> {
-        if self.chain_tip.is_some() {
-            return Err(anyhow!("chain already exists"));
+    /// Start a fresh chain at `bits` instead of the pow limit. Rejects bits whose target
+    /// falls outside [min target, pow limit]; ignored once the chain has a tip, since
+    /// the difficulty then comes from the blocks themselves.
+    pub fn set_initial_difficulty(&mut self, bits: u32) -> Result<()> {
+        let target = Self::compact_to_target(bits);
+        if target.is_zero()
+            || target < self.params.min_target()
+            || target > self.params.pow_limit_target()
+        {
+            return Err(anyhow!(
+                "initial difficulty 0x{:08x} must lie between 0x{:08x} (easiest) and 0x{:08x} (hardest)",
+                bits,
+                self.params.pow_limit_bits,
+                self.params.pow_min_bits
+            ));
+        }
+        if self.chain_tip.is_none() {
+            self.difficulty = bits;
+        }
+        Ok(())
+    }
+
+    /// The genesis block for `params` at `difficulty`: a single coinbase paying the
+    /// height-0 reward to `params.genesis_address`, stamped `GENESIS_TIMESTAMP`. Nothing
+    /// in it depends on the node or the clock, so every node of a network builds the
+    /// same block. It is not mined; nodes create it rather than receive it.
+    pub fn genesis_block(params: &ChainParams, difficulty: u32) -> Result<Block> {
+        let timestamp = crate::security::GENESIS_TIMESTAMP;
+        let reward = crate::config::calculate_block_reward(0);
+        let cb = Transaction {
+            txid: String::new(),
+            eth_hash: String::new(),
+            inputs: vec![],
+            outputs: vec![TransactionOutput::new(
+                params.genesis_address.clone(),
+                reward,
+            )],
+            timestamp,
+            valid_until: None,
         }
-        let cb = Transaction::coinbase(address, U256::from(50));
+        .with_hashes();
 
-        let merkle = compute_merkle_root(&vec![cb.txid.clone()]);
         let header = BlockHeader {
             index: 0,
             previous_hash: "0".repeat(64),
-            merkle_root: merkle,
-            timestamp: Utc::now().timestamp(),
+            merkle_root: compute_merkle_root(&vec![cb.txid.clone()]),
+            timestamp,
             nonce: 0,
-            difficulty: self.difficulty,
+            difficulty,
         };
         let hash = compute_header_hash(&header)?;
-        let block = Block {
+        Ok(Block {
             header,
-            transactions: vec![cb.clone()],
-            hash: hash.clone(),
-        };
+            transactions: vec![cb],
+            hash,
+        })
+    }
+
+    /// Hash of this chain's block 0: the stored one, or the one `create_genesis` would
+    /// build on an empty database
+    pub fn genesis_hash(&self) -> Result<String> {
+        if let Some(hash) = self.db.get(b"i:0")? {
+            return Ok(String::from_utf8(hash)?);
+        }
+        Ok(Self::genesis_block(&self.params, self.difficulty)?.hash)
+    }
+
+    /// Create the genesis block (see `genesis_block`) on an empty database
+    pub fn create_genesis(&mut self) -> Result<String> {
+        if self.chain_tip.is_some() {
+            return Err(anyhow!("chain already exists"));
+        }
+        let block = Self::genesis_block(&self.params, self.difficulty)?;
+        let cb = block.transactions[0].clone();
+        let hash = block.hash.clone();
 
         // commit atomically
         let mut batch = WriteBatch::default();
@@ -187,15 +783,23 @@ impl Blockchain {
         batch.put(format!("t:{}", cb.txid).as_bytes(), &tx_blob);
 
         for (i, out) in cb.outputs.iter().enumerate() {
-            let utxo = Utxo::new(cb.txid.clone(), i as u32, out.to.clone(), out.amount());
+            let utxo = Utxo::new(
+                cb.txid.clone(),
+                i as u32,
+                out.to.clone(),
+                out.amount(),
+                0,
+                true,
+            );
 
-            let utxo_blob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-            batch.put(format!("u:{}:{}", cb.txid, i).as_bytes(), &utxo_blob);
+            self.put_utxo(&mut batch, &utxo)?;
         }
 
         // index
         batch.put(format!("i:0").as_bytes(), hash.as_bytes());
+        Self::put_tx_index(&mut batch, &block)?;
         batch.put(b"tip", hash.as_bytes());
+        self.put_chain_work(&mut batch, &block);
 
         put_batch(&self.db, batch)?;
         self.chain_tip = Some(hash.clone());
@@ -204,6 +808,27 @@ impl Blockchain {
 
     /// validate and insert block (core of migration/consensus)
     pub fn validate_and_insert_block(&mut self, block: &Block) -> Result<()> {
+        // 0) size cap, before any per-transaction work
+        let block_bytes = bincode::encode_to_vec(block, *BINCODE_CONFIG)?.len();
+        if block_bytes > crate::security::MAX_BLOCK_BYTES {
+            crate::security::VALIDATION_STATS
+                .increment(crate::security::BlockFailureReason::SecurityConstraint);
+            log::warn!(
+                "🚫 Block validation failed [block_too_large]: height={} size={} max={}",
+                block.header.index,
+                block_bytes,
+                crate::security::MAX_BLOCK_BYTES
+            );
+            return Err(reject(
+                BlockFailureReason::SecurityConstraint,
+                format!(
+                    "block too large: {} bytes exceeds the {} byte limit",
+                    block_bytes,
+                    crate::security::MAX_BLOCK_BYTES
+                ),
+            ));
+        }
+
         // 1) header hash match
         let computed = compute_header_hash(&block.header)?;
         if computed != block.hash {
@@ -215,10 +840,12 @@ impl Blockchain {
                 &computed[..16],
                 &block.hash[..16]
             );
-            return Err(anyhow!(
-                "header hash mismatch: computed {} != block.hash {}",
-                computed,
-                block.hash
+            return Err(reject(
+                BlockFailureReason::HashMismatch,
+                format!(
+                    "header hash mismatch: computed {} != block.hash {}",
+                    computed, block.hash
+                ),
             ));
         }
 
@@ -233,11 +860,12 @@ impl Blockchain {
                 &block.hash[..16],
                 block.header.difficulty
             );
-            return Err(anyhow!(
-                "invalid PoW: hash {} is not below target {} (bits=0x{:08x})",
-                block.hash,
-                target,
-                block.header.difficulty
+            return Err(reject(
+                BlockFailureReason::InvalidPoW,
+                format!(
+                    "invalid PoW: hash {} is not below target {} (bits=0x{:08x})",
+                    block.hash, target, block.header.difficulty
+                ),
             ));
         }
 
@@ -273,20 +901,22 @@ impl Blockchain {
                             block.header.difficulty,
                             prev_header.difficulty
                         );
-                        return Err(anyhow!(
-                            "difficulty target changed too aggressively at block {}: got bits=0x{:08x}, previous bits=0x{:08x}",
-                            block.header.index,
-                            block.header.difficulty,
-                            prev_header.difficulty
+                        return Err(reject(
+                            BlockFailureReason::DifficultyOutOfRange,
+                            format!(
+                                "difficulty target changed too aggressively at block {}: got bits=0x{:08x}, previous bits=0x{:08x}",
+                                block.header.index, block.header.difficulty, prev_header.difficulty
+                            ),
                         ));
                     }
                 }
             }
         }
 
-        // 4) merkle check
+        // 4) merkle check (the tree is cached once the block is committed)
         let txids: Vec<String> = block.transactions.iter().map(|t| t.txid.clone()).collect();
-        let merkle = compute_merkle_root(&txids);
+        let merkle_tree = MerkleTree::build(&txids);
+        let merkle = merkle_tree.root();
         if merkle != block.header.merkle_root {
             crate::security::VALIDATION_STATS
                 .increment(crate::security::BlockFailureReason::MerkleRootMismatch);
@@ -296,7 +926,10 @@ impl Blockchain {
                 merkle,
                 block.header.merkle_root
             );
-            return Err(anyhow!("merkle mismatch"));
+            return Err(reject(
+                BlockFailureReason::MerkleRootMismatch,
+                "merkle mismatch",
+            ));
         }
 
         // 4.5) Median-Time-Past validation (prevent timestamp manipulation)
@@ -315,9 +948,9 @@ impl Blockchain {
                     block.header.index,
                     &block.header.previous_hash[..16]
                 );
-                return Err(anyhow!(
-                    "previous header not found: {}",
-                    block.header.previous_hash
+                return Err(reject(
+                    BlockFailureReason::PreviousNotFound,
+                    format!("previous header not found: {}", block.header.previous_hash),
                 ));
             }
         }
@@ -330,37 +963,79 @@ impl Blockchain {
         crate::security::validate_block_security(&block)?;
 
         // 🔒 Policy: Check against checkpoint policy (not consensus, but node policy)
-        if !crate::checkpoint::validate_against_checkpoints(block.header.index, &block.hash) {
+        if !checkpoint::validate_against_checkpoints(
+            &self.checkpoints,
+            block.header.index,
+            &block.hash,
+        ) {
             log::warn!(
                 "Block {} at height {} conflicts with checkpoint policy - rejecting",
                 &block.hash[..16],
                 block.header.index
             );
-            return Err(anyhow!(
-                "Block violates checkpoint policy at height {}",
-                block.header.index
+            return Err(reject(
+                BlockFailureReason::CheckpointViolation,
+                format!(
+                    "Block violates checkpoint policy at height {}",
+                    block.header.index
+                ),
             ));
         }
 
         // For coinbase check
         if block.transactions.is_empty() {
-            return Err(anyhow!("empty block"));
+            return Err(reject(BlockFailureReason::EmptyBlock, "empty block"));
         }
 
         // coinbase must be first tx and inputs empty
         let coinbase = &block.transactions[0];
         if !coinbase.inputs.is_empty() {
-            return Err(anyhow!("coinbase must have no inputs"));
+            return Err(reject(
+                BlockFailureReason::InvalidCoinbase,
+                "coinbase must have no inputs",
+            ));
         }
 
+        // UTXOs spent by this block, kept as undo data so a rollback restores them exactly
+        let mut spent_utxos: Vec<Utxo> = Vec::new();
+
+        let skip_signatures = self.is_assumed_valid(block);
+
+        // Every outpoint the block spends, read in one go. Outputs are added as their
+        // transactions connect and removed once spent, so a later transaction can spend
+        // an earlier one's output but nothing in the block can spend the same one twice.
+        let mut block_utxos = self.fetch_spent_utxos(block)?;
+
         // iterate non-coinbase txs
         for (i, tx) in block.transactions.iter().enumerate() {
             // 🔒 Security: Validate transaction-level constraints
             crate::security::validate_transaction_security(tx, block.header.timestamp)?;
+            if !self
+                .params
+                .allows_tx_version(tx.version(), block.header.index)
+            {
+                return Err(reject(
+                    BlockFailureReason::SecurityConstraint,
+                    format!(
+                        "tx {} has version {}, not allowed before height {}",
+                        tx.txid,
+                        tx.version(),
+                        self.params.tx_expiry_height
+                    ),
+                ));
+            }
 
-            // verify signature(s)
-            if !tx.verify_signatures()? {
-                return Err(anyhow!("tx signature invalid: {}", tx.txid));
+            // verify signature(s), unless the block is covered by a checkpoint; ones
+            // checked at mempool admission or on an earlier pass come from the cache
+            if !skip_signatures
+                && !crate::sigcache::SIGNATURE_CACHE
+                    .verify(tx)
+                    .map_err(|e| reject(BlockFailureReason::SignatureFailure, e.to_string()))?
+            {
+                return Err(reject(
+                    BlockFailureReason::SignatureFailure,
+                    format!("tx signature invalid: {}", tx.txid),
+                ));
             }
 
             // coinbase skip UTXO referencing checks
@@ -371,10 +1046,16 @@ impl Blockchain {
                 for (v, out) in tx.outputs.iter().enumerate() {
                     // Normalize address to lowercase for consistent storage
                     let normalized_address = out.to.to_lowercase();
-                    let utxo =
-                        Utxo::new(tx.txid.clone(), v as u32, normalized_address, out.amount());
-                    let ublob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-                    batch.put(format!("u:{}:{}", tx.txid, v).as_bytes(), &ublob);
+                    let utxo = Utxo::new(
+                        tx.txid.clone(),
+                        v as u32,
+                        normalized_address,
+                        out.amount(),
+                        block.header.index,
+                        true,
+                    );
+                    self.put_utxo(&mut batch, &utxo)?;
+                    block_utxos.insert(format!("u:{}:{}", utxo.txid, utxo.vout), utxo);
                 }
                 continue;
             }
@@ -388,79 +1069,111 @@ impl Blockchain {
 
                 // 🔒 Security: Prevent double-spending within same transaction
                 if !used_utxos.insert(ukey.clone()) {
-                    return Err(anyhow!(
-                        "duplicate input in tx {}: {}:{}",
-                        tx.txid,
-                        inp.txid,
-                        inp.vout
+                    return Err(reject(
+                        BlockFailureReason::DuplicateInput,
+                        format!(
+                            "duplicate input in tx {}: {}:{}",
+                            tx.txid, inp.txid, inp.vout
+                        ),
                     ));
                 }
 
-                match self.db.get(ukey.as_bytes())? {
-                    Some(blob) => {
-                        let (u, _): (Utxo, usize) =
-                            bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
-
+                match block_utxos.remove(&ukey) {
+                    Some(u) => {
                         // 🔒 Security: CRITICAL - Verify UTXO ownership
                         // Derive address from input's public key and compare with UTXO owner
                         let input_address = crate::crypto::eth_address_from_pubkey_hex(&inp.pubkey)
-                            .map_err(|e| anyhow!("invalid pubkey in input: {}", e))?;
+                            .map_err(|e| {
+                                reject(
+                                    BlockFailureReason::SignatureFailure,
+                                    format!("invalid pubkey in input: {}", e),
+                                )
+                            })?;
 
                         let utxo_owner = u.to.to_lowercase();
                         let input_addr_lower = input_address.to_lowercase();
 
                         if input_addr_lower != utxo_owner {
-                            return Err(anyhow!(
-                                "UTXO ownership verification failed for {}:{} - expected {}, got {}",
-                                inp.txid,
-                                inp.vout,
-                                utxo_owner,
-                                input_addr_lower
+                            return Err(reject(
+                                BlockFailureReason::UtxoOwnershipFailure,
+                                format!(
+                                    "UTXO ownership verification failed for {}:{} - expected {}, got {}",
+                                    inp.txid, inp.vout, utxo_owner, input_addr_lower
+                                ),
+                            ));
+                        }
+
+                        // 🔒 Consensus: coinbase outputs can't be spent before COINBASE_MATURITY blocks
+                        if !u.is_mature_at(block.header.index) {
+                            return Err(reject(
+                                BlockFailureReason::SecurityConstraint,
+                                format!(
+                                    "tx {} spends immature coinbase {}:{} (created at height {}, spent at {}, maturity {})",
+                                    tx.txid,
+                                    inp.txid,
+                                    inp.vout,
+                                    u.height,
+                                    block.header.index,
+                                    crate::config::COINBASE_MATURITY
+                                ),
                             ));
                         }
 
-                        input_sum = input_sum + u.amount();
+                        input_sum = input_sum.checked_add(u.amount()).ok_or_else(|| {
+                            reject(
+                                BlockFailureReason::SecurityConstraint,
+                                format!("invalid transaction {}: input sum overflows", tx.txid),
+                            )
+                        })?;
                         // mark as spent by deleting in batch
-                        batch.delete(ukey.as_bytes());
+                        Self::delete_utxo(&mut batch, &u);
+                        spent_utxos.push(u);
                     }
                     None => {
-                        return Err(anyhow!(
-                            "referenced utxo not found {}:{} (already spent or never existed)",
-                            inp.txid,
-                            inp.vout
+                        return Err(reject(
+                            BlockFailureReason::UtxoNotFound,
+                            format!(
+                                "referenced utxo not found {}:{} (already spent or never existed)",
+                                inp.txid, inp.vout
+                            ),
                         ));
                     }
                 }
             }
 
-            let mut output_sum = U256::zero();
-            for out in &tx.outputs {
-                output_sum = output_sum + out.amount();
-            }
+            let output_sum = tx.output_total().ok_or_else(|| {
+                reject(
+                    BlockFailureReason::SecurityConstraint,
+                    format!("invalid transaction {}: output sum overflows", tx.txid),
+                )
+            })?;
 
             // 🔒 Security: Validate fee is reasonable (outputs <= inputs)
-            if output_sum > input_sum {
-                return Err(anyhow!(
-                    "invalid transaction {}: outputs ({}) exceed inputs ({})",
-                    tx.txid,
-                    output_sum,
-                    input_sum
-                ));
-            }
+            let fee = input_sum.checked_sub(output_sum).ok_or_else(|| {
+                reject(
+                    BlockFailureReason::InsufficientFee,
+                    format!(
+                        "invalid transaction {}: outputs ({}) exceed inputs ({})",
+                        tx.txid, output_sum, input_sum
+                    ),
+                )
+            })?;
 
             // 🔒 Security: Enforce minimum fee based on transaction size (prevent DDoS)
             // Uses Anti-DDoS fee policy from config.rs: BASE_MIN_FEE + (size × rate)
-            let fee = input_sum - output_sum;
             let tx_blob = bincode::encode_to_vec(tx, *BINCODE_CONFIG)?;
             let min_fee = crate::config::calculate_min_fee(tx_blob.len());
 
             if fee < min_fee {
-                return Err(anyhow!(
-                    "transaction fee too low {}: got {} ram, need {} ram (base 100 Twei + {} bytes × 200 Gwei/byte)",
-                    tx.txid,
-                    fee,
-                    min_fee,
-                    tx_blob.len()
+                return Err(reject(
+                    BlockFailureReason::InsufficientFee,
+                    format!(
+                        "transaction fee too low {}: got {} ram, need {} ram (base 100 Twei + {} bytes × 200 Gwei/byte)",
+                        tx.txid,
+                        fee,
+                        min_fee,
+                        tx_blob.len()
+                    ),
                 ));
             }
 
@@ -470,12 +1183,22 @@ impl Blockchain {
             for (v, out) in tx.outputs.iter().enumerate() {
                 // Normalize address to lowercase for consistent storage
                 let normalized_address = out.to.to_lowercase();
-                let utxo = Utxo::new(tx.txid.clone(), v as u32, normalized_address, out.amount());
-                let ublob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-                batch.put(format!("u:{}:{}", tx.txid, v).as_bytes(), &ublob);
+                let utxo = Utxo::new(
+                    tx.txid.clone(),
+                    v as u32,
+                    normalized_address,
+                    out.amount(),
+                    block.header.index,
+                    false,
+                );
+                self.put_utxo(&mut batch, &utxo)?;
+                block_utxos.insert(format!("u:{}:{}", utxo.txid, utxo.vout), utxo);
             }
         }
 
+        let undo_blob = bincode::encode_to_vec(&spent_utxos, *BINCODE_CONFIG)?;
+        batch.put(format!("x:{}", block.hash).as_bytes(), &undo_blob);
+
         // persist complete block, index, tip
         let block_blob = bincode::encode_to_vec(&block, *BINCODE_CONFIG)?;
         batch.put(format!("b:{}", block.hash).as_bytes(), &block_blob);
@@ -483,11 +1206,37 @@ impl Blockchain {
             format!("i:{}", block.header.index).as_bytes(),
             block.hash.as_bytes(),
         );
+        Self::put_tx_index(&mut batch, block)?;
         batch.put(b"tip", block.hash.as_bytes());
+        // cumulative chain work, so fork choice doesn't have to walk back to genesis
+        self.put_chain_work(&mut batch, block);
 
         // commit
         put_batch(&self.db, batch)?;
         self.chain_tip = Some(block.hash.clone());
+        self.cache_merkle_tree(&block.hash, merkle_tree);
+        self.refresh_utxo_filter_if_saturated()?;
+        // Initial sync is over once the chain reaches the checkpoint
+        if self
+            .assume_valid
+            .as_ref()
+            .is_some_and(|(height, _)| block.header.index >= *height)
+        {
+            self.assume_valid = None;
+            self.assume_valid_chain.clear();
+        }
+        // The block is already committed, so a pruning failure is only worth a warning
+        if let Err(e) = self.prune_blocks() {
+            log::warn!("Pruning after block {} failed: {}", block.header.index, e);
+        }
+
+        // Emission audit: scheduled reward and total issuance through this height
+        log::info!(
+            "[EMISSION] height={} reward={} cumulative_supply={}",
+            block.header.index,
+            crate::config::calculate_block_reward(block.header.index),
+            crate::config::cumulative_emission(block.header.index)
+        );
 
         // Adjust difficulty every 30 blocks
         let next_index = block.header.index + 1;
@@ -508,31 +1257,127 @@ impl Blockchain {
     }
 
     /// helper: load block header by hash
+    /// The header is the first field of a stored block, so only it is decoded; the
+    /// transaction bodies are skipped
+    /// (pruned blocks keep just the header, under `h:`)
     pub fn load_header(&self, hash: &str) -> Result<Option<BlockHeader>> {
-        if let Some(blob) = self.db.get(format!("b:{}", hash).as_bytes())? {
-            let (block, _): (Block, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
-            return Ok(Some(block.header));
+        let blob = match self.db.get(format!("b:{}", hash).as_bytes())? {
+            Some(blob) => blob,
+            None => match self.db.get(format!("h:{}", hash).as_bytes())? {
+                Some(blob) => blob,
+                None => return Ok(None),
+            },
+        };
+        let (header, _): (BlockHeader, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+        Ok(Some(header))
+    }
+
+    /// Up to `count` consecutive main-chain headers starting at height `from`, read from
+    /// the `i:` index; stops early at the tip. Errors if the range runs past `u64::MAX`.
+    pub fn get_headers_range(&self, from: u64, count: usize) -> Result<Vec<BlockHeader>> {
+        let end = from
+            .checked_add(count as u64)
+            .ok_or_else(|| anyhow!("header range {} + {} overflows", from, count))?;
+        let mut headers = Vec::with_capacity(count.min(2000));
+        for height in from..end {
+            let Some(hash) = self.db.get(format!("i:{}", height).as_bytes())? else {
+                break;
+            };
+            match self.load_header(&String::from_utf8(hash)?)? {
+                Some(header) => headers.push(header),
+                None => break,
+            }
         }
-        Ok(None)
+        Ok(headers)
     }
 
-    /// load tx by id
-    pub fn load_tx(&self, txid: &str) -> Result<Option<Transaction>> {
-        if let Some(blob) = self.db.get(format!("t:{}", txid).as_bytes())? {
-            let (t, _): (Transaction, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
-            return Ok(Some(t));
+    /// Main-chain block at `height` via the `i:` index
+    fn main_chain_block(&self, height: u64) -> Result<Option<Block>> {
+        match self.main_chain_hash(height)? {
+            Some(hash) => self.load_block(&hash),
+            None => Ok(None),
         }
-        Ok(None)
     }
 
-    /// get balance by scanning UTXO set (use get_address_balance_from_db instead)
-    #[deprecated(note = "Use get_address_balance_from_db instead")]
-    pub fn get_balance(&self, address: &str) -> Result<U256, Box<dyn std::error::Error>> {
-        Ok(self.get_address_balance_from_db(address)?)
+    /// First height in `0..=tip` whose timestamp is after `time`, by binary search over
+    /// the `i:` index assuming timestamps rise with height (`tip + 1` if none are)
+    fn first_height_after(&self, tip: u64, time: i64) -> Result<u64> {
+        let (mut lo, mut hi) = (0, tip + 1);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.main_chain_block(mid)? {
+                Some(block) if block.header.timestamp <= time => lo = mid + 1,
+                _ => hi = mid,
+            }
+        }
+        Ok(lo)
     }
 
-    /// Determine next block index based on current tip
-    pub fn get_next_index(&self) -> Result<u64> {
+    /// Main-chain blocks with `from <= timestamp <= to`, oldest first, at most `limit`.
+    /// The height window comes from a binary search and is widened by
+    /// TIMESTAMP_SCAN_MARGIN on both sides so slightly out-of-order blocks are kept.
+    pub fn get_blocks_by_time(&self, from: i64, to: i64, limit: usize) -> Result<Vec<Block>> {
+        let Some(tip) = self.get_next_index()?.checked_sub(1) else {
+            return Ok(Vec::new());
+        };
+        if from > to {
+            return Ok(Vec::new());
+        }
+
+        let start = self
+            .first_height_after(tip, from.saturating_sub(1))?
+            .saturating_sub(TIMESTAMP_SCAN_MARGIN);
+        let end = (self.first_height_after(tip, to)? + TIMESTAMP_SCAN_MARGIN).min(tip + 1);
+
+        let mut blocks = Vec::new();
+        for height in start..end {
+            if blocks.len() >= limit {
+                break;
+            }
+            if let Some(block) = self.main_chain_block(height)? {
+                if (from..=to).contains(&block.header.timestamp) {
+                    blocks.push(block);
+                }
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Height of `hash` if it is on the main chain (indexed under `i:`), None for
+    /// unknown blocks and side branches
+    pub fn main_chain_height(&self, hash: &str) -> Result<Option<u64>> {
+        let Some(height) = self.get_block_height(hash)? else {
+            return Ok(None);
+        };
+        let indexed = self.db.get(format!("i:{}", height).as_bytes())?;
+        Ok((indexed.as_deref() == Some(hash.as_bytes())).then_some(height))
+    }
+
+    /// Hash of the main-chain block at `height` from the `i:` index, which pruning keeps
+    pub fn main_chain_hash(&self, height: u64) -> Result<Option<String>> {
+        match self.db.get(format!("i:{}", height).as_bytes())? {
+            Some(hash) => Ok(Some(String::from_utf8(hash)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// load tx by id
+    pub fn load_tx(&self, txid: &str) -> Result<Option<Transaction>> {
+        if let Some(blob) = self.db.get(format!("t:{}", txid).as_bytes())? {
+            let (t, _): (Transaction, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+            return Ok(Some(t));
+        }
+        Ok(None)
+    }
+
+    /// get balance by scanning UTXO set (use get_address_balance_from_db instead)
+    #[deprecated(note = "Use get_address_balance_from_db instead")]
+    pub fn get_balance(&self, address: &str) -> Result<U256, Box<dyn std::error::Error>> {
+        Ok(self.get_address_balance_from_db(address)?)
+    }
+
+    /// Determine next block index based on current tip
+    pub fn get_next_index(&self) -> Result<u64> {
         if let Some(ref tip_hash) = self.chain_tip {
             if let Some(prev) = self.load_header(tip_hash)? {
                 // assume BlockHeader.index is u64 or can be cast; adjust if different
@@ -552,12 +1397,12 @@ impl Blockchain {
 
         // Collect up to 11 previous block timestamps
         for _ in 0..MTP_SPAN {
-            if let Some(blk) = self.load_block(&current_hash)? {
-                timestamps.push(blk.header.timestamp);
-                if blk.header.index == 0 {
+            if let Some(header) = self.load_header(&current_hash)? {
+                timestamps.push(header.timestamp);
+                if header.index == 0 {
                     break; // Reached genesis
                 }
-                current_hash = blk.header.previous_hash.clone();
+                current_hash = header.previous_hash.clone();
             } else {
                 break;
             }
@@ -578,10 +1423,12 @@ impl Blockchain {
 
         // Block timestamp must be strictly greater than MTP
         if block.header.timestamp <= median {
-            return Err(anyhow!(
-                "Block timestamp {} violates Median-Time-Past {} (must be > MTP)",
-                block.header.timestamp,
-                median
+            return Err(reject(
+                BlockFailureReason::TimestampTooOld,
+                format!(
+                    "Block timestamp {} violates Median-Time-Past {} (must be > MTP)",
+                    block.header.timestamp, median
+                ),
             ));
         }
 
@@ -589,17 +1436,18 @@ impl Blockchain {
     }
 
     /// Calculate adjusted difficulty based on recent block times
-    /// Adjustment period: every block (using rolling 30-block window)
-    /// Target: 120 seconds per block (2 minutes)
+    /// Adjustment period: every block (using a rolling `retarget_window`-block window)
+    /// Target: `block_interval` seconds per block (2 minutes by default)
     /// Bitcoin-style: U256 hash target retargeting with damped updates
     pub fn calculate_adjusted_difficulty(&self, current_index: u64) -> Result<u32> {
+        let window = self.params.retarget_window;
         // No adjustment until enough history is available
-        if current_index < Self::RETARGET_WINDOW {
+        if current_index < window {
             return Ok(self.difficulty);
         }
 
         // Rolling window: compare timestamps of [current_index - window, current_index - 1]
-        let start_index = current_index - Self::RETARGET_WINDOW;
+        let start_index = current_index - window;
         let start_hash = self.db.get(format!("i:{}", start_index).as_bytes())?;
         let end_hash = self.db.get(format!("i:{}", current_index - 1).as_bytes())?;
 
@@ -623,30 +1471,36 @@ impl Blockchain {
         let end_time = end_header.unwrap().timestamp;
 
         // Calculate actual time taken for the last window
-        let raw_actual_time = (end_time - start_time).max(1);
-        let target_time = self.block_interval * Self::RETARGET_WINDOW as i64;
-        let clamped_actual_time = raw_actual_time.clamp(target_time / 4, target_time * 4);
+        // Saturating throughout: validate() bounds the params, but they are public
+        let raw_actual_time = end_time.saturating_sub(start_time).max(1);
+        let target_time = self
+            .params
+            .block_interval
+            .saturating_mul(i64::try_from(window).unwrap_or(i64::MAX))
+            .max(1);
+        let clamped_actual_time =
+            raw_actual_time.clamp((target_time / 4).max(1), target_time.saturating_mul(4));
 
         log::info!(
             "Difficulty adjustment at block {}: actual={}s, target={}s, avg={:.1}s/block",
             current_index,
             raw_actual_time,
             target_time,
-            raw_actual_time as f64 / Self::RETARGET_WINDOW as f64
+            raw_actual_time as f64 / window as f64
         );
 
         let ratio = raw_actual_time as f64 / target_time as f64;
 
         let current_difficulty = self.difficulty;
-        let pow_limit = Self::pow_limit_target();
-        let min_target = Self::min_target();
+        let pow_limit = self.params.pow_limit_target();
+        let min_target = self.params.min_target();
         let current_target = {
             let t = Self::compact_to_target(current_difficulty);
             if t.is_zero() { pow_limit } else { t }
         };
 
         // Core Bitcoin-style retarget: new_target = old_target * actual / target
-        let mut retargeted = (current_target * U256::from(clamped_actual_time as u64))
+        let mut retargeted = current_target.saturating_mul(U256::from(clamped_actual_time as u64))
             / U256::from(target_time as u64);
 
         // Clamp target bounds
@@ -675,8 +1529,8 @@ impl Blockchain {
                 current_difficulty,
                 final_difficulty,
                 ratio,
-                raw_actual_time as f64 / Self::RETARGET_WINDOW as f64,
-                self.block_interval
+                raw_actual_time as f64 / window as f64,
+                self.params.block_interval
             );
         } else {
             log::info!(
@@ -740,6 +1594,94 @@ impl Blockchain {
         Ok(utxos)
     }
 
+    /// Up to `limit` entries of the index under `prefix`, as (key past the prefix,
+    /// value), starting after the entry `after` names. The iterator seeks straight to
+    /// the cursor, so a page costs the same however deep it is. The cursor returned
+    /// names the page's last entry when more follow it.
+    fn index_page(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<(String, Box<[u8]>)>, Option<String>)> {
+        let start = format!("{}{}", prefix, after.unwrap_or_default());
+        let iter = self.db.iterator(rocksdb::IteratorMode::From(
+            start.as_bytes(),
+            rocksdb::Direction::Forward,
+        ));
+        let mut entries: Vec<(String, Box<[u8]>)> = Vec::new();
+        for item in iter {
+            let (key, value) = item?;
+            let Some(entry) = key.strip_prefix(prefix.as_bytes()) else {
+                break;
+            };
+            let entry = std::str::from_utf8(entry)?;
+            if after == Some(entry) {
+                continue;
+            }
+            if entries.len() >= limit {
+                let next = entries.last().map(|(entry, _)| entry.clone());
+                return Ok((entries, next));
+            }
+            entries.push((entry.to_string(), value));
+        }
+        Ok((entries, None))
+    }
+
+    /// Amount, txid and vout of an `a:` index entry (the key past `a:{address}:`)
+    fn parse_address_utxo_entry(entry: &str) -> Result<(U256, &str, &str)> {
+        let mut parts = entry.splitn(3, ':');
+        let (Some(inverted), Some(txid), Some(vout)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(anyhow!("malformed address index entry {}", entry));
+        };
+        let inverted = U256::from_str_radix(inverted, 16)
+            .map_err(|_| anyhow!("malformed address index entry {}", entry))?;
+        Ok((U256::MAX - inverted, txid, vout))
+    }
+
+    /// UTXOs of `address` sorted by amount descending: at most `limit` of them, after
+    /// the entry the `after` cursor names (from the largest without one). Served from
+    /// the `a:` index, so only the page itself is read and decoded.
+    pub fn get_utxo_page(
+        &self,
+        address: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<UtxoPage> {
+        let prefix = format!("a:{}:", address.to_lowercase());
+        let (entries, next_cursor) = self.index_page(&prefix, after, limit)?;
+        let mut utxos = Vec::with_capacity(entries.len());
+        for (entry, _) in &entries {
+            let (_, txid, vout) = Self::parse_address_utxo_entry(entry)?;
+            if let Some(blob) = self.db.get(format!("u:{}:{}", txid, vout).as_bytes())? {
+                let (utxo, _): (Utxo, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+                utxos.push(utxo);
+            }
+        }
+        Ok(UtxoPage { utxos, next_cursor })
+    }
+
+    /// Number and sum of `address`'s UTXOs, from the `a:` index keys alone
+    pub fn get_utxo_totals(&self, address: &str) -> Result<(usize, U256)> {
+        let prefix = format!("a:{}:", address.to_lowercase());
+        let iter = self.db.iterator(rocksdb::IteratorMode::From(
+            prefix.as_bytes(),
+            rocksdb::Direction::Forward,
+        ));
+        let (mut count, mut total) = (0, U256::zero());
+        for item in iter {
+            let (key, _) = item?;
+            let Some(entry) = key.strip_prefix(prefix.as_bytes()) else {
+                break;
+            };
+            let (amount, _, _) = Self::parse_address_utxo_entry(std::str::from_utf8(entry)?)?;
+            total = total.saturating_add(amount);
+            count += 1;
+        }
+        Ok((count, total))
+    }
+
     /// Count transactions stored in DB (keys starting with `t:`)
     pub fn count_transactions(&self) -> Result<usize> {
         let mut count: usize = 0;
@@ -765,10 +1707,9 @@ impl Blockchain {
                 Some(hash_bytes) => {
                     let hash = String::from_utf8(hash_bytes)?;
 
-                    // Load complete block (with transactions) by hash
-                    if let Some(blob) = self.db.get(format!("b:{}", hash).as_bytes())? {
-                        let (block, _): (Block, usize) =
-                            bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+                    // Load complete block (with transactions) by hash; a pruned block
+                    // fails the whole request rather than leaving a silent gap
+                    if let Some(block) = self.load_block(&hash)? {
                         blocks.push(block);
                     }
                     index += 1;
@@ -801,10 +1742,9 @@ impl Blockchain {
                 Some(hash_bytes) => {
                     let hash = String::from_utf8(hash_bytes)?;
 
-                    // Load complete block (with transactions) by hash
-                    if let Some(blob) = self.db.get(format!("b:{}", hash).as_bytes())? {
-                        let (block, _): (Block, usize) =
-                            bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+                    // Load complete block (with transactions) by hash; a pruned block
+                    // fails the whole request rather than leaving a silent gap
+                    if let Some(block) = self.load_block(&hash)? {
                         blocks.push(block);
                     }
                     index += 1;
@@ -819,18 +1759,134 @@ impl Blockchain {
         Ok(blocks)
     }
 
+    /// Stored (encoded) size of each main-chain block in a height range (inclusive),
+    /// read from the raw `b:` entries without decoding them
+    pub fn get_block_sizes_range(&self, from_height: u64, to_height: u64) -> Result<Vec<usize>> {
+        let mut sizes = Vec::new();
+        for index in from_height..=to_height {
+            let Some(hash_bytes) = self.db.get(format!("i:{}", index).as_bytes())? else {
+                break;
+            };
+            let hash = String::from_utf8(hash_bytes)?;
+            let Some(blob) = self.db.get_pinned(format!("b:{}", hash).as_bytes())? else {
+                return Err(BlockPruned { hash }.into());
+            };
+            sizes.push(blob.len());
+        }
+        Ok(sizes)
+    }
+
+    /// Main-chain height of a confirmed transaction from the `tb:` index alone, so it
+    /// answers for transactions in pruned blocks too
+    pub fn get_transaction_height(&self, txid: &str) -> Result<Option<u64>> {
+        let Some(location) = self.db.get(format!("tb:{}", txid).as_bytes())? else {
+            return Ok(None);
+        };
+        let ((height, _), _): ((u64, u32), usize) =
+            bincode::decode_from_slice(&location, *BINCODE_CONFIG)?;
+        Ok(Some(height))
+    }
+
     pub fn get_transaction(&self, txid: &str) -> anyhow::Result<Option<(Transaction, usize)>> {
-        let blocks = self.get_all_blocks()?;
+        Ok(self
+            .indexed_transaction(&format!("tb:{}", txid))?
+            .filter(|(tx, _)| tx.txid == txid))
+    }
 
-        for block in blocks {
-            for tx in block.transactions {
-                if tx.txid == txid {
-                    return Ok(Some((tx, block.header.index as usize)));
-                }
+    /// Confirmed transactions involving `address`, newest first: at most `limit` of
+    /// them, after the entry the `after` cursor names (from the newest without one),
+    /// plus the cursor for the next page. Served from the `ah:` index; amounts spent
+    /// come from each block's undo data.
+    pub fn get_address_history(
+        &self,
+        address: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<AddressTx>, Option<String>)> {
+        let address = address.to_lowercase();
+        let prefix = format!("ah:{}:", address);
+        let (entries, next_cursor) = self.index_page(&prefix, after, limit)?;
+        let mut locations = Vec::with_capacity(entries.len());
+        for (_, value) in &entries {
+            let (location, _): ((u64, u32), usize) =
+                bincode::decode_from_slice(value, *BINCODE_CONFIG)?;
+            locations.push(location);
+        }
+
+        let mut history = Vec::with_capacity(locations.len());
+        let mut block: Option<Block> = None;
+        let mut spent: HashMap<(String, u32), Utxo> = HashMap::new();
+        for (height, position) in locations {
+            if block.as_ref().map(|b| b.header.index) != Some(height) {
+                let Some(hash) = self.db.get(format!("i:{}", height).as_bytes())? else {
+                    continue;
+                };
+                let Some(loaded) = self.load_block(&String::from_utf8(hash)?)? else {
+                    continue;
+                };
+                spent = self
+                    .load_spent_utxos(&loaded.hash)?
+                    .into_iter()
+                    .map(|u| ((u.txid.clone(), u.vout), u))
+                    .collect();
+                block = Some(loaded);
             }
+            let Some(current) = block.as_ref() else {
+                continue;
+            };
+            let Some(tx) = current.transactions.get(position as usize) else {
+                continue;
+            };
+
+            // Validated blocks can't overflow these sums; saturate rather than fail
+            let received = tx
+                .outputs
+                .iter()
+                .filter(|out| out.to.to_lowercase() == address)
+                .fold(U256::zero(), |acc, out| acc.saturating_add(out.amount()));
+            let mut sent = U256::zero();
+            for inp in &tx.inputs {
+                let owned = match spent.get(&(inp.txid.clone(), inp.vout)) {
+                    Some(u) => (u.to.to_lowercase() == address).then(|| u.amount()),
+                    // Blocks connected before undo data existed: find the spent output
+                    None => self.get_transaction(&inp.txid)?.and_then(|(prev, _)| {
+                        prev.outputs
+                            .get(inp.vout as usize)
+                            .filter(|out| out.to.to_lowercase() == address)
+                            .map(|out| out.amount())
+                    }),
+                };
+                sent = sent.saturating_add(owned.unwrap_or_default());
+            }
+
+            history.push(AddressTx {
+                txid: tx.txid.clone(),
+                height,
+                timestamp: current.header.timestamp,
+                coinbase: tx.inputs.is_empty(),
+                received,
+                sent,
+            });
         }
+        Ok((history, next_cursor))
+    }
 
-        Ok(None)
+    /// Number of confirmed transactions involving `address`, from the `ah:` index keys
+    pub fn count_address_history(&self, address: &str) -> Result<usize> {
+        let prefix = format!("ah:{}:", address.to_lowercase());
+        let iter = self.db.iterator(rocksdb::IteratorMode::From(
+            prefix.as_bytes(),
+            rocksdb::Direction::Forward,
+        ));
+        let mut count = 0;
+        for item in iter {
+            let (key, _) = item?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
     }
 
     /// Get transaction by eth_hash (EVM-compatible hash)
@@ -838,8 +1894,6 @@ impl Blockchain {
         &self,
         eth_hash: &str,
     ) -> anyhow::Result<Option<(Transaction, usize)>> {
-        let blocks = self.get_all_blocks()?;
-
         // Normalize eth_hash (add 0x if missing)
         let normalized_hash = if eth_hash.starts_with("0x") {
             eth_hash.to_string()
@@ -847,15 +1901,9 @@ impl Blockchain {
             format!("0x{}", eth_hash)
         };
 
-        for block in blocks {
-            for tx in block.transactions {
-                if tx.eth_hash == normalized_hash {
-                    return Ok(Some((tx, block.header.index as usize)));
-                }
-            }
-        }
-
-        Ok(None)
+        Ok(self
+            .indexed_transaction(&format!("eb:{}", normalized_hash))?
+            .filter(|(tx, _)| tx.eth_hash == normalized_hash))
     }
 
     /// Calculate total transaction volume from all outputs in DB (in ram)
@@ -968,58 +2016,107 @@ impl Blockchain {
         Ok(seen_txids.len())
     }
 
+    /// Work contributed by a single block header
+    /// Each difficulty level represents 16x more work (hexadecimal): Work = 16^difficulty
+    fn block_work(header: &BlockHeader) -> Result<u64> {
+        // 🔒 Security: Validate difficulty is reasonable (prevent invalid blocks)
+        if header.difficulty == 0 {
+            return Err(anyhow!(
+                "Invalid block with difficulty 0 at height {}",
+                header.index
+            ));
+        }
+
+        if header.difficulty > 32 {
+            return Err(anyhow!(
+                "Invalid block with excessive difficulty {} at height {}",
+                header.difficulty,
+                header.index
+            ));
+        }
+
+        // Use checked operations to prevent overflow
+        match 16u64.checked_pow(header.difficulty) {
+            Some(work) => Ok(work),
+            None => {
+                log::warn!(
+                    "Work calculation overflow at difficulty {}, using max u64",
+                    header.difficulty
+                );
+                Ok(u64::MAX)
+            }
+        }
+    }
+
+    /// Read cached cumulative chain work (`w:<hash>`) if present
+    fn load_cached_chain_work(&self, block_hash: &str) -> Result<Option<u64>> {
+        match self.db.get(format!("w:{}", block_hash).as_bytes())? {
+            Some(bytes) => {
+                let raw: [u8; 8] = bytes
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow!("corrupt chain work entry for {}", block_hash))?;
+                Ok(Some(u64::from_be_bytes(raw)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Cumulative work of the chain ending at `header` (parent work + this block's work)
+    fn cumulative_work_for(&self, header: &BlockHeader) -> Result<u64> {
+        let parent_work = if header.index == 0 {
+            0
+        } else {
+            self.calculate_chain_work(&header.previous_hash)?
+        };
+        Ok(parent_work.saturating_add(Self::block_work(header)?))
+    }
+
+    /// Queue the `w:<hash>` entry for a block into a write batch.
+    /// Work is a best-effort index: failures are logged and the walking fallback is used later.
+    fn put_chain_work(&self, batch: &mut WriteBatch, block: &Block) {
+        match self.cumulative_work_for(&block.header) {
+            Ok(work) => batch.put(format!("w:{}", block.hash).as_bytes(), work.to_be_bytes()),
+            Err(e) => log::debug!(
+                "Chain work not cached for block {} at height {}: {}",
+                &block.hash[..16.min(block.hash.len())],
+                block.header.index,
+                e
+            ),
+        }
+    }
+
     /// Calculate total chain work (cumulative difficulty) from genesis to given block
-    /// Higher difficulty blocks contribute more work
+    /// Higher difficulty blocks contribute more work.
+    /// Uses the cached `w:<hash>` value; walks back to genesis only for legacy DBs without it.
     pub fn calculate_chain_work(&self, block_hash: &str) -> Result<u64> {
+        if let Some(work) = self.load_cached_chain_work(block_hash)? {
+            return Ok(work);
+        }
+        self.calculate_chain_work_walk(block_hash)
+    }
+
+    /// Slow path: sum block work by walking from the given block back to genesis
+    fn calculate_chain_work_walk(&self, block_hash: &str) -> Result<u64> {
         let mut total_work = 0u64;
         let mut current_hash = block_hash.to_string();
 
         loop {
-            let block = self.load_block(&current_hash)?;
-            if block.is_none() {
+            let header = self.load_header(&current_hash)?;
+            if header.is_none() {
                 break;
             }
 
-            let block = block.unwrap();
-
-            // 🔒 Security: Validate difficulty is reasonable (prevent invalid blocks)
-            if block.header.difficulty == 0 {
-                return Err(anyhow!(
-                    "Invalid block with difficulty 0 at height {}",
-                    block.header.index
-                ));
-            }
-
-            if block.header.difficulty > 32 {
-                return Err(anyhow!(
-                    "Invalid block with excessive difficulty {} at height {}",
-                    block.header.difficulty,
-                    block.header.index
-                ));
-            }
-
-            // Each difficulty level represents 16x more work (hexadecimal)
-            // Work = 16^difficulty
-            // Use checked operations to prevent overflow
-            let block_work = match 16u64.checked_pow(block.header.difficulty) {
-                Some(work) => work,
-                None => {
-                    log::warn!(
-                        "Work calculation overflow at difficulty {}, using max u64",
-                        block.header.difficulty
-                    );
-                    u64::MAX
-                }
-            };
+            let header = header.unwrap();
 
             // Saturating add to prevent overflow
-            total_work = total_work.saturating_add(block_work);
+            total_work = total_work.saturating_add(Self::block_work(&header)?);
 
-            if block.header.index == 0 {
+            if header.index == 0 {
                 break; // Reached genesis
             }
 
-            current_hash = block.header.previous_hash.clone();
+            current_hash = header.previous_hash.clone();
         }
 
         Ok(total_work)
@@ -1027,46 +2124,109 @@ impl Blockchain {
 
     /// Get block height (index) for a given block hash
     pub fn get_block_height(&self, block_hash: &str) -> Result<Option<u64>> {
-        if let Some(block) = self.load_block(block_hash)? {
-            Ok(Some(block.header.index))
-        } else {
-            Ok(None)
-        }
+        Ok(self.load_header(block_hash)?.map(|header| header.index))
     }
 
-    /// Load complete block by hash
+    /// Load complete block by hash. Fails with `BlockPruned` if only its header is left.
     pub fn load_block(&self, hash: &str) -> Result<Option<Block>> {
         if let Some(blob) = self.db.get(format!("b:{}", hash).as_bytes())? {
             let (block, _): (Block, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
             return Ok(Some(block));
         }
+        if self.db.get(format!("h:{}", hash).as_bytes())?.is_some() {
+            return Err(BlockPruned {
+                hash: hash.to_string(),
+            }
+            .into());
+        }
         Ok(None)
     }
 
+    /// Lowest height whose block body is still stored (0 on an unpruned chain)
+    pub fn pruned_below(&self) -> Result<u64> {
+        match self.db.get(b"pruned_below")? {
+            Some(height) => Ok(String::from_utf8(height)?.parse()?),
+            None => Ok(0),
+        }
+    }
+
+    /// Drop the bodies (transactions and undo data) of main-chain blocks more than
+    /// `prune_depth` below the tip, keeping their headers under `h:`. Blocks within
+    /// `max_reorg_depth` of the tip are always kept so a reorg can still roll them
+    /// back. The UTXO set and tx index are untouched. Returns the number of blocks
+    /// pruned; a no-op unless `prune_depth` is set.
+    pub fn prune_blocks(&mut self) -> Result<u64> {
+        let Some(depth) = self.prune_depth else {
+            return Ok(0);
+        };
+        let Some(tip) = self.get_next_index()?.checked_sub(1) else {
+            return Ok(0);
+        };
+        let Some(prune_to) = tip.checked_sub(depth.max(self.max_reorg_depth)) else {
+            return Ok(0);
+        };
+
+        let mut pruned = 0;
+        let mut height = self.pruned_below()?;
+        while height < prune_to {
+            let end = (height + PRUNE_BATCH_BLOCKS).min(prune_to);
+            let mut batch = WriteBatch::default();
+            for h in height..end {
+                let Some(hash) = self.db.get(format!("i:{}", h).as_bytes())? else {
+                    continue;
+                };
+                let hash = String::from_utf8(hash)?;
+                let Some(blob) = self.db.get(format!("b:{}", hash).as_bytes())? else {
+                    continue;
+                };
+                let (block, _): (Block, usize) =
+                    bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+                batch.put(
+                    format!("h:{}", hash).as_bytes(),
+                    bincode::encode_to_vec(&block.header, *BINCODE_CONFIG)?,
+                );
+                batch.delete(format!("b:{}", hash).as_bytes());
+                batch.delete(format!("x:{}", hash).as_bytes());
+                // `t:` holds a second copy of each transaction body
+                for tx in &block.transactions {
+                    batch.delete(format!("t:{}", tx.txid).as_bytes());
+                }
+                pruned += 1;
+            }
+            batch.put(b"pruned_below", end.to_string().as_bytes());
+            put_batch(&self.db, batch)?;
+            height = end;
+        }
+        if pruned > 0 {
+            log::info!("Pruned {} block bodies below height {}", pruned, prune_to);
+        }
+        Ok(pruned)
+    }
+
     /// Find common ancestor between two blocks
     fn find_common_ancestor(&self, hash_a: &str, hash_b: &str) -> Result<Option<String>> {
         let mut blocks_a = Vec::new();
         let mut current = hash_a.to_string();
 
         // Collect all blocks from hash_a to genesis
-        while let Some(block) = self.load_block(&current)? {
+        while let Some(header) = self.load_header(&current)? {
             blocks_a.push(current.clone());
-            if block.header.index == 0 {
+            if header.index == 0 {
                 break;
             }
-            current = block.header.previous_hash.clone();
+            current = header.previous_hash.clone();
         }
 
         // Walk from hash_b to genesis and find first common block
         let mut current = hash_b.to_string();
-        while let Some(block) = self.load_block(&current)? {
+        while let Some(header) = self.load_header(&current)? {
             if blocks_a.contains(&current) {
                 return Ok(Some(current));
             }
-            if block.header.index == 0 {
+            if header.index == 0 {
                 break;
             }
-            current = block.header.previous_hash.clone();
+            current = header.previous_hash.clone();
         }
 
         Ok(None)
@@ -1136,8 +2296,11 @@ impl Blockchain {
         )?;
 
         // 🔒 Policy: Check if reorg conflicts with checkpoint policy
-        let (checkpoint_allowed, checkpoint_reason) =
-            crate::checkpoint::check_reorg_against_checkpoints(reorg_depth, current_height);
+        let (checkpoint_allowed, checkpoint_reason) = checkpoint::check_reorg_against_checkpoints(
+            &self.checkpoints,
+            reorg_depth,
+            current_height,
+        );
 
         if !checkpoint_allowed {
             log::error!(
@@ -1191,6 +2354,7 @@ impl Blockchain {
 
         // Apply: replay new chain
         self.replay_blocks(&apply_blocks)?;
+        self.refresh_utxo_filter_if_saturated()?;
 
         // Update chain tip
         let mut batch = WriteBatch::default();
@@ -1206,27 +2370,190 @@ impl Blockchain {
         Ok(true)
     }
 
+    fn cache_merkle_tree(&self, block_hash: &str, tree: MerkleTree) -> Arc<MerkleTree> {
+        let tree = Arc::new(tree);
+        if let Ok(mut cache) = self.merkle_cache.lock() {
+            cache.insert(block_hash, tree.clone());
+        }
+        tree
+    }
+
+    /// Merkle tree for a stored block. Recently validated blocks are served from the cache;
+    /// otherwise the tree is rebuilt, checked against the header root, and cached.
+    pub fn merkle_tree(&self, block: &Block) -> Result<Arc<MerkleTree>> {
+        if let Some(tree) = self
+            .merkle_cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(&block.hash))
+        {
+            return Ok(tree);
+        }
+
+        let txids: Vec<String> = block.transactions.iter().map(|t| t.txid.clone()).collect();
+        let tree = MerkleTree::build(&txids);
+        if tree.root() != block.header.merkle_root {
+            return Err(anyhow!("merkle mismatch for block {}", block.hash));
+        }
+        Ok(self.cache_merkle_tree(&block.hash, tree))
+    }
+
+    /// Merkle inclusion proof for a confirmed transaction: the containing block, the tx's
+    /// index in it, and the sibling path (see `block::compute_merkle_proof`)
+    pub fn get_merkle_proof(
+        &self,
+        txid: &str,
+    ) -> Result<Option<(Block, usize, Vec<(String, bool)>)>> {
+        let Some((_, height)) = self.get_transaction(txid)? else {
+            return Ok(None);
+        };
+        let Some(hash) = self.db.get(format!("i:{}", height).as_bytes())? else {
+            return Ok(None);
+        };
+        let Some(block) = self.load_block(&String::from_utf8_lossy(&hash))? else {
+            return Ok(None);
+        };
+        let Some(index) = block.transactions.iter().position(|tx| tx.txid == txid) else {
+            return Ok(None);
+        };
+        let proof = self.merkle_tree(&block)?.proof(index);
+        Ok(Some((block, index, proof)))
+    }
+
+    /// UTXOs spent by a block, as recorded under `x:<hash>` when it was connected
+    fn load_spent_utxos(&self, block_hash: &str) -> Result<Vec<Utxo>> {
+        match self.db.get(format!("x:{}", block_hash).as_bytes())? {
+            Some(blob) => Ok(bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?.0),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns true if any input of `tx` spends a coinbase output that isn't mature
+    /// for a block at `spend_height`. Missing inputs are not reported here.
+    pub fn spends_immature_coinbase(&self, tx: &Transaction, spend_height: u64) -> Result<bool> {
+        for inp in &tx.inputs {
+            if let Some(blob) = self
+                .db
+                .get(format!("u:{}:{}", inp.txid, inp.vout).as_bytes())?
+            {
+                let (u, _): (Utxo, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+                if !u.is_mature_at(spend_height) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Persist pending transactions under `m:<txid>`, replacing any previous snapshot
+    pub fn save_mempool(&self, txs: &[Transaction]) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        for key in self.mempool_keys()? {
+            batch.delete(key);
+        }
+        for tx in txs {
+            let blob = bincode::encode_to_vec(tx, *BINCODE_CONFIG)?;
+            batch.put(format!("m:{}", tx.txid).as_bytes(), &blob);
+        }
+        put_batch(&self.db, batch)?;
+        Ok(())
+    }
+
+    /// Transactions saved by `save_mempool`. Callers must revalidate them against the chain.
+    pub fn load_mempool(&self) -> Result<Vec<Transaction>> {
+        let mut txs = Vec::new();
+        let iter = self.db.iterator(rocksdb::IteratorMode::From(
+            b"m:",
+            rocksdb::Direction::Forward,
+        ));
+        for item in iter {
+            let (key, value) = item?;
+            if !key.starts_with(b"m:") {
+                break;
+            }
+            let (tx, _): (Transaction, usize) =
+                bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+            txs.push(tx);
+        }
+        Ok(txs)
+    }
+
+    fn mempool_keys(&self) -> Result<Vec<Box<[u8]>>> {
+        let mut keys = Vec::new();
+        let iter = self.db.iterator(rocksdb::IteratorMode::From(
+            b"m:",
+            rocksdb::Direction::Forward,
+        ));
+        for item in iter {
+            let (key, _) = item?;
+            if !key.starts_with(b"m:") {
+                break;
+            }
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+
+    /// Write a consistent snapshot of the database to `dest_path` (which must not exist
+    /// yet) while the node keeps running. SST files are hard-linked when `dest_path` is
+    /// on the same filesystem, so this is cheap. The snapshot is crash-consistent: it
+    /// holds exactly the writes committed before the call, as if the node had crashed
+    /// at that instant, and opens with `Blockchain::new` like any data directory.
+    pub fn create_checkpoint(&self, dest_path: &str) -> Result<()> {
+        if std::path::Path::new(dest_path).exists() {
+            return Err(anyhow!(
+                "checkpoint destination already exists: {}",
+                dest_path
+            ));
+        }
+        rocksdb::checkpoint::Checkpoint::new(&self.db)?.create_checkpoint(dest_path)?;
+        Ok(())
+    }
+
     /// Rollback UTXO changes from a list of blocks (reverse order)
     fn rollback_blocks(&mut self, blocks: &[Block]) -> Result<()> {
         let mut batch = WriteBatch::default();
 
         for block in blocks {
             log::info!("Rolling back block {}", block.header.index);
+            // Reorged-out transactions are no longer confirmed
+            Self::delete_tx_index(&mut batch, block);
+
+            // Spent outputs recorded at insert time (blocks stored before undo data existed have none)
+            let undo: HashMap<(String, u32), Utxo> = self
+                .load_spent_utxos(&block.hash)?
+                .into_iter()
+                .map(|u| ((u.txid.clone(), u.vout), u))
+                .collect();
 
             // Process transactions in reverse order
             for tx in block.transactions.iter().rev() {
                 // Delete UTXOs created by this transaction
-                for i in 0..tx.outputs.len() {
-                    let ukey = format!("u:{}:{}", tx.txid, i);
-                    batch.delete(ukey.as_bytes());
+                for (i, output) in tx.outputs.iter().enumerate() {
+                    let created = Utxo::new(
+                        tx.txid.clone(),
+                        i as u32,
+                        output.to.clone(),
+                        output.amount(),
+                        block.header.index,
+                        tx.inputs.is_empty(),
+                    );
+                    Self::delete_utxo(&mut batch, &created);
                 }
 
                 // Restore UTXOs spent by this transaction (skip coinbase)
                 if !tx.inputs.is_empty() {
                     for input in &tx.inputs {
                         // Restore the UTXO that was spent
-                        let spent_tx = self
-                            .load_tx(&input.txid)?
+                        if let Some(utxo) = undo.get(&(input.txid.clone(), input.vout)) {
+                            self.put_utxo(&mut batch, utxo)?;
+                            continue;
+                        }
+
+                        // Slow path for blocks connected before undo data existed:
+                        // scan for the spent tx to recover its creation height
+                        let (spent_tx, spent_height) = self
+                            .get_transaction(&input.txid)?
                             .ok_or_else(|| anyhow!("Cannot find spent tx: {}", input.txid))?;
 
                         if let Some(output) = spent_tx.outputs.get(input.vout as usize) {
@@ -1235,12 +2562,10 @@ impl Blockchain {
                                 input.vout,
                                 output.to.clone(),
                                 output.amount(),
+                                spent_height as u64,
+                                spent_tx.inputs.is_empty(),
                             );
-                            let ublob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-                            batch.put(
-                                format!("u:{}:{}", input.txid, input.vout).as_bytes(),
-                                &ublob,
-                            );
+                            self.put_utxo(&mut batch, &utxo)?;
                         }
                     }
                 }
@@ -1258,8 +2583,22 @@ impl Blockchain {
 
             // We already have the block stored, just need to update UTXO set
             let mut batch = WriteBatch::default();
+            let mut spent_utxos: Vec<Utxo> = Vec::new();
+
+            // Same bookkeeping as validate_and_insert_block: outputs created earlier in
+            // the block are only in the batch, so they are tracked here to be spendable
+            let mut block_utxos = self.fetch_spent_utxos(block)?;
 
             for tx in &block.transactions {
+                // Spend UTXOs (coinbase has no inputs)
+                for input in &tx.inputs {
+                    let ukey = format!("u:{}:{}", input.txid, input.vout);
+                    if let Some(u) = block_utxos.remove(&ukey) {
+                        Self::delete_utxo(&mut batch, &u);
+                        spent_utxos.push(u);
+                    }
+                }
+
                 // Create new UTXOs
                 for (i, output) in tx.outputs.iter().enumerate() {
                     let utxo = Utxo::new(
@@ -1267,22 +2606,1139 @@ impl Blockchain {
                         i as u32,
                         output.to.clone(),
                         output.amount(),
+                        block.header.index,
+                        tx.inputs.is_empty(),
                     );
-                    let ublob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-                    batch.put(format!("u:{}:{}", tx.txid, i).as_bytes(), &ublob);
-                }
-
-                // Spend UTXOs (skip coinbase)
-                if !tx.inputs.is_empty() {
-                    for input in &tx.inputs {
-                        batch.delete(format!("u:{}:{}", input.txid, input.vout).as_bytes());
-                    }
+                    self.put_utxo(&mut batch, &utxo)?;
+                    block_utxos.insert(format!("u:{}:{}", utxo.txid, utxo.vout), utxo);
                 }
             }
 
+            let undo_blob = bincode::encode_to_vec(&spent_utxos, *BINCODE_CONFIG)?;
+            batch.put(format!("x:{}", block.hash).as_bytes(), &undo_blob);
+            batch.put(
+                format!("i:{}", block.header.index).as_bytes(),
+                block.hash.as_bytes(),
+            );
+            Self::put_tx_index(&mut batch, block)?;
+
             put_batch(&self.db, batch)?;
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn temp_chain(name: &str) -> (Blockchain, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "astram-{}-{}-{}",
+            name,
+            std::process::id(),
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let bc = Blockchain::new(dir.to_str().unwrap()).unwrap();
+        (bc, dir)
+    }
+
+    /// Height-0 block over `transactions` (coinbase first) with a solved easy PoW
+    fn solved_block(transactions: Vec<Transaction>) -> Block {
+        let timestamp = transactions[0].timestamp;
+        solved_block_at(transactions, timestamp)
+    }
+
+    /// solved_block with the header stamped `timestamp`
+    fn solved_block_at(transactions: Vec<Transaction>, timestamp: i64) -> Block {
+        solved_block_on(transactions, 0, "0".repeat(64), timestamp)
+    }
+
+    /// Block at height `index` over `transactions`, building on `previous_hash`
+    fn solved_block_on(
+        transactions: Vec<Transaction>,
+        index: u64,
+        previous_hash: String,
+        timestamp: i64,
+    ) -> Block {
+        let txids: Vec<String> = transactions.iter().map(|tx| tx.txid.clone()).collect();
+        let mut header = BlockHeader {
+            index,
+            previous_hash,
+            merkle_root: compute_merkle_root(&txids),
+            timestamp,
+            nonce: 0,
+            difficulty: 0x207fffff, // roughly every other hash qualifies
+        };
+        let hash = loop {
+            let hash = compute_header_hash(&header).unwrap();
+            if Blockchain::is_valid_pow(&hash, header.difficulty).unwrap() {
+                break hash;
+            }
+            header.nonce += 1;
+        };
+        Block {
+            header,
+            transactions,
+            hash,
+        }
+    }
+
+    /// Seed output 0 of `funding`, worth `amount` to `owner`, straight into the UTXO set
+    fn fund(bc: &Blockchain, funding: &str, owner: &str, amount: U256) {
+        let utxo = Utxo::new(funding.to_string(), 0, owner.to_string(), amount, 0, false);
+        bc.db
+            .put(
+                format!("u:{}:0", funding).as_bytes(),
+                bincode::encode_to_vec(&utxo, *BINCODE_CONFIG).unwrap(),
+            )
+            .unwrap();
+        bc.rebuild_utxo_filter().unwrap();
+    }
+
+    /// `keypair`'s signed spend of output 0 of `txid` into `outputs`, stamped two
+    /// minutes ago
+    fn signed_spend(
+        keypair: &crate::crypto::WalletKeypair,
+        txid: &str,
+        outputs: Vec<crate::transaction::TransactionOutput>,
+        valid_until: Option<i64>,
+    ) -> Transaction {
+        let mut tx = Transaction {
+            txid: String::new(),
+            eth_hash: String::new(),
+            inputs: vec![crate::transaction::TransactionInput {
+                txid: txid.to_string(),
+                vout: 0,
+                pubkey: keypair.public_hex(),
+                signature: None,
+            }],
+            outputs,
+            timestamp: Utc::now().timestamp() - 120,
+            valid_until,
+        };
+        tx.sign(keypair).unwrap();
+        tx.with_hashes()
+    }
+
+    #[test]
+    fn genesis_uses_the_configured_initial_difficulty() {
+        let (mut bc, dir) = temp_chain("initial-difficulty");
+        // Harder than the pow limit, easier than the minimum target
+        let bits = 0x1c0fffff;
+        bc.set_initial_difficulty(bits).unwrap();
+        assert!(bc.set_initial_difficulty(0x207fffff).is_err());
+        assert!(bc.set_initial_difficulty(0x1800ffff).is_err());
+        assert!(bc.set_initial_difficulty(0).is_err());
+
+        let genesis = bc.create_genesis().unwrap();
+        assert_eq!(bc.load_header(&genesis).unwrap().unwrap().difficulty, bits);
+
+        // An existing chain keeps the difficulty its blocks carry
+        bc.set_initial_difficulty(ChainParams::default().pow_limit_bits)
+            .unwrap();
+        assert_eq!(bc.difficulty, bits);
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn every_node_builds_the_same_genesis() {
+        let (mut first, first_dir) = temp_chain("genesis-a");
+        let (mut second, second_dir) = temp_chain("genesis-b");
+        // Known before it exists, and unchanged once created
+        let expected = first.genesis_hash().unwrap();
+        let genesis = first.create_genesis().unwrap();
+        assert_eq!(genesis, expected);
+        assert_eq!(first.genesis_hash().unwrap(), genesis);
+
+        // Independently bootstrapped nodes agree
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        assert_eq!(second.create_genesis().unwrap(), genesis);
+        let block = first.load_block(&genesis).unwrap().unwrap();
+        assert_eq!(block.header.timestamp, crate::security::GENESIS_TIMESTAMP);
+        let coinbase = &block.transactions[0];
+        assert_eq!(coinbase.outputs[0].to, GENESIS_ADDRESS);
+        assert_eq!(
+            coinbase.outputs[0].amount(),
+            crate::config::calculate_block_reward(0)
+        );
+
+        // Another genesis address is another network
+        let params = ChainParams {
+            genesis_address: format!("0x{}", "ab".repeat(20)),
+            ..ChainParams::default()
+        };
+        let other = Blockchain::genesis_block(&params, first.difficulty).unwrap();
+        assert_ne!(other.hash, genesis);
+        let bad = ChainParams {
+            genesis_address: "0xnot-an-address".to_string(),
+            ..ChainParams::default()
+        };
+        assert!(bad.validate().is_err());
+
+        drop(first);
+        drop(second);
+        let _ = std::fs::remove_dir_all(first_dir);
+        let _ = std::fs::remove_dir_all(second_dir);
+    }
+
+    /// Main-chain headers for heights 0..count, `spacing` seconds apart
+    fn put_timed_headers(bc: &mut Blockchain, count: u64, spacing: i64) {
+        for h in 0..count {
+            let header = BlockHeader {
+                index: h,
+                previous_hash: format!("{:064x}", h.saturating_sub(1)),
+                merkle_root: String::new(),
+                timestamp: 1_700_000_000 + spacing * h as i64,
+                nonce: 0,
+                difficulty: bc.difficulty,
+            };
+            let hash = format!("{:064x}", h);
+            let blob = bincode::encode_to_vec(&header, *BINCODE_CONFIG).unwrap();
+            bc.db.put(format!("h:{}", hash), blob).unwrap();
+            bc.db.put(format!("i:{}", h), hash.as_bytes()).unwrap();
+        }
+    }
+
+    #[test]
+    fn retarget_follows_the_configured_block_interval() {
+        let (mut bc, dir) = temp_chain("retarget");
+        let bits = 0x1c0fffff;
+        bc.set_initial_difficulty(bits).unwrap();
+        let window = bc.params.retarget_window;
+        // New target as thousandths of the old one
+        let permille = |new_bits: u32| {
+            let ratio = Blockchain::compact_to_target(new_bits) * U256::from(1000u32)
+                / Blockchain::compact_to_target(bits);
+            ratio.as_u64()
+        };
+
+        // Blocks every 5 seconds: at the default 2 minutes that is past the 4x clamp,
+        // so the target drops by a quarter of 3/4
+        put_timed_headers(&mut bc, window + 1, 5);
+        let default_bits = bc.calculate_adjusted_difficulty(window + 1).unwrap();
+        assert!((812..=813).contains(&permille(default_bits)));
+
+        // Against a 10-second interval they are twice as fast: a quarter of 1/2
+        bc.params = ChainParams {
+            block_interval: 10,
+            ..ChainParams::default()
+        };
+        let fast_bits = bc.calculate_adjusted_difficulty(window + 1).unwrap();
+        assert!((874..=875).contains(&permille(fast_bits)));
+
+        // and blocks arriving every 10 seconds are on target
+        put_timed_headers(&mut bc, window + 1, 10);
+        assert_eq!(bc.calculate_adjusted_difficulty(window + 1).unwrap(), bits);
+
+        // Params that never went through validate() can't overflow or panic the clamp
+        for block_interval in [i64::MAX, 0, -120] {
+            bc.params.block_interval = block_interval;
+            bc.calculate_adjusted_difficulty(window + 1).unwrap();
+        }
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn chain_params_are_bounds_checked() {
+        assert!(ChainParams::default().validate().is_ok());
+        let rejected = |edit: fn(&mut ChainParams)| {
+            let mut params = ChainParams::default();
+            edit(&mut params);
+            params.validate().is_err()
+        };
+        assert!(rejected(|p| p.block_interval = 0));
+        assert!(rejected(|p| p.block_interval = -120));
+        assert!(rejected(|p| p.block_interval = MAX_BLOCK_INTERVAL + 1));
+        assert!(rejected(|p| p.block_interval = i64::MAX));
+        assert!(!rejected(|p| p.block_interval = MAX_BLOCK_INTERVAL));
+        assert!(rejected(|p| p.retarget_window = 1));
+        assert!(!rejected(|p| p.retarget_window = 2));
+        assert!(!rejected(|p| p.retarget_window = MAX_RETARGET_WINDOW));
+        assert!(rejected(|p| p.retarget_window = MAX_RETARGET_WINDOW + 1));
+        assert!(rejected(|p| p.retarget_window = u64::MAX));
+        // Limit harder than the minimum target
+        assert!(rejected(|p| p.pow_limit_bits = 0x1800ffff));
+
+        let params = ChainParams {
+            block_interval: 0,
+            ..ChainParams::default()
+        };
+        let dir = std::env::temp_dir().join(format!("astram-bad-params-{}", std::process::id()));
+        assert!(Blockchain::with_params(dir.to_str().unwrap(), params).is_err());
+        // Rejected before anything is created on disk
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn oversized_block_is_rejected() {
+        let (mut bc, dir) = temp_chain("oversized");
+        let mut coinbase =
+            Transaction::coinbase("0x0000000000000000000000000000000000000001", U256::from(50));
+        let output = coinbase.outputs[0].clone();
+        let per_output = bincode::encode_to_vec(&output, *BINCODE_CONFIG)
+            .unwrap()
+            .len();
+        coinbase.outputs = vec![output; crate::security::MAX_BLOCK_BYTES / per_output + 1];
+        let block = solved_block(vec![coinbase.with_hashes()]);
+
+        let err = bc.validate_and_insert_block(&block).unwrap_err();
+        assert!(err.to_string().contains("block too large"), "{}", err);
+        assert!(bc.chain_tip.is_none());
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn headers_served_from_height_index() {
+        let (mut bc, dir) = temp_chain("headers");
+        let genesis = bc.create_genesis().unwrap();
+
+        let headers = bc.get_headers_range(0, 10).unwrap();
+        assert_eq!(headers.len(), 1);
+        assert_eq!(compute_header_hash(&headers[0]).unwrap(), genesis);
+        assert!(bc.get_headers_range(1, 10).unwrap().is_empty());
+        assert!(bc.get_headers_range(u64::MAX, 0).unwrap().is_empty());
+        assert!(bc.get_headers_range(u64::MAX, 10).is_err());
+
+        assert_eq!(bc.main_chain_height(&genesis).unwrap(), Some(0));
+        assert_eq!(bc.main_chain_height(&"ff".repeat(32)).unwrap(), None);
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn header_range_matches_full_blocks() {
+        let (mut bc, dir) = temp_chain("header-range");
+        for h in 0..6u64 {
+            let cb =
+                Transaction::coinbase("0x0000000000000000000000000000000000000001", U256::from(h));
+            let block = Block {
+                header: BlockHeader {
+                    index: h,
+                    previous_hash: format!("{:064x}", h.saturating_sub(1)),
+                    merkle_root: compute_merkle_root(&vec![cb.txid.clone()]),
+                    timestamp: 1000 + h as i64,
+                    nonce: h * 7,
+                    difficulty: 0x1d0fffff,
+                },
+                transactions: vec![cb],
+                hash: format!("{:064x}", h),
+            };
+            let blob = bincode::encode_to_vec(&block, *BINCODE_CONFIG).unwrap();
+            bc.db.put(format!("b:{}", block.hash), blob).unwrap();
+            bc.db
+                .put(format!("i:{}", h), block.hash.as_bytes())
+                .unwrap();
+            bc.chain_tip = Some(block.hash);
+        }
+
+        let encoded =
+            |header: &BlockHeader| bincode::encode_to_vec(header, *BINCODE_CONFIG).unwrap();
+        let headers = bc.get_headers_range(2, 3).unwrap();
+        assert_eq!(headers.len(), 3);
+        for (header, height) in headers.iter().zip(2..) {
+            let block = bc.main_chain_block(height).unwrap().unwrap();
+            assert_eq!(encoded(header), encoded(&block.header));
+        }
+        // Stops at the tip
+        assert_eq!(bc.get_headers_range(4, 100).unwrap().len(), 2);
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn address_utxos_paged_largest_first() {
+        let (bc, dir) = temp_chain("addr-utxos");
+        let owner = "0x00000000000000000000000000000000000000AA";
+        let other = "0x00000000000000000000000000000000000000bb";
+
+        // Written as a pre-index database would have them: `u:` keys only
+        let utxos: Vec<Utxo> = (0..25u64)
+            .map(|i| {
+                let amount =
+                    U256::from((i * 37) % 11 + 1) * U256::from(10u64).pow(U256::from(18u64));
+                Utxo::new(
+                    format!("{:064x}", i),
+                    (i % 3) as u32,
+                    owner.into(),
+                    amount,
+                    i,
+                    false,
+                )
+            })
+            .collect();
+        let unrelated = Utxo::new("e".repeat(64), 0, other.into(), U256::MAX, 0, false);
+        for utxo in utxos.iter().chain(std::iter::once(&unrelated)) {
+            bc.db
+                .put(
+                    format!("u:{}:{}", utxo.txid, utxo.vout).as_bytes(),
+                    bincode::encode_to_vec(utxo, *BINCODE_CONFIG).unwrap(),
+                )
+                .unwrap();
+        }
+        bc.db.delete(b"addrindex").unwrap();
+        drop(bc);
+        let bc = Blockchain::new(dir.to_str().unwrap()).unwrap();
+
+        let total = utxos.iter().fold(U256::zero(), |acc, u| acc + u.amount());
+        assert_eq!(bc.get_utxo_totals(owner).unwrap(), (25, total));
+        let mut paged = Vec::new();
+        let mut cursor = None;
+        for expected_len in [10, 10, 5] {
+            let page = bc.get_utxo_page(owner, cursor.as_deref(), 10).unwrap();
+            assert_eq!(page.utxos.len(), expected_len);
+            paged.extend(page.utxos);
+            cursor = page.next_cursor;
+        }
+        assert!(cursor.is_none());
+        // A page that ends exactly at the last UTXO has no cursor either
+        let page = bc.get_utxo_page(owner, None, 25).unwrap();
+        assert_eq!((page.utxos.len(), page.next_cursor), (25, None));
+
+        let amounts: Vec<U256> = paged.iter().map(|u| u.amount()).collect();
+        let mut expected: Vec<U256> = utxos.iter().map(|u| u.amount()).collect();
+        expected.sort_by(|a, b| b.cmp(a));
+        assert_eq!(amounts, expected);
+        let mut outpoints: Vec<_> = paged.iter().map(|u| (u.txid.clone(), u.vout)).collect();
+        outpoints.sort();
+        outpoints.dedup();
+        assert_eq!(outpoints.len(), 25);
+
+        // Spending an output drops it from the index
+        let mut batch = WriteBatch::default();
+        Blockchain::delete_utxo(&mut batch, &paged[0]);
+        put_batch(&bc.db, batch).unwrap();
+        let totals = bc.get_utxo_totals(owner).unwrap();
+        assert_eq!(totals, (24, total - paged[0].amount()));
+        assert_eq!(bc.get_utxo_page(owner, None, 100).unwrap().utxos.len(), 24);
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn blocks_found_by_timestamp_range() {
+        let (mut bc, dir) = temp_chain("bytime");
+        assert!(bc.get_blocks_by_time(0, i64::MAX, 10).unwrap().is_empty());
+
+        // 100s apart, except height 12 which is stamped far earlier than its neighbours
+        let timestamp = |h: u64| if h == 12 { 1050 } else { 1000 + 100 * h as i64 };
+        for h in 0..20u64 {
+            let block = Block {
+                header: BlockHeader {
+                    index: h,
+                    previous_hash: format!("{:064x}", h.saturating_sub(1)),
+                    merkle_root: String::new(),
+                    timestamp: timestamp(h),
+                    nonce: 0,
+                    difficulty: 1,
+                },
+                transactions: vec![],
+                hash: format!("{:064x}", h),
+            };
+            let blob = bincode::encode_to_vec(&block, *BINCODE_CONFIG).unwrap();
+            bc.db.put(format!("b:{}", block.hash), blob).unwrap();
+            bc.db
+                .put(format!("i:{}", h), block.hash.as_bytes())
+                .unwrap();
+            bc.chain_tip = Some(block.hash);
+        }
+
+        let heights = |from: i64, to: i64, limit: usize| -> Vec<u64> {
+            bc.get_blocks_by_time(from, to, limit)
+                .unwrap()
+                .iter()
+                .map(|b| b.header.index)
+                .collect()
+        };
+        assert_eq!(heights(1500, 1700, 100), vec![5, 6, 7]);
+        // the out-of-order block is still found by the margin scan
+        assert_eq!(heights(1000, 1100, 100), vec![0, 1, 12]);
+        assert_eq!(heights(2800, 9999, 100), vec![18, 19]);
+        assert_eq!(heights(1500, 2500, 2), vec![5, 6]);
+        assert!(heights(5000, 6000, 100).is_empty());
+        assert!(heights(1700, 1500, 100).is_empty());
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn transactions_found_through_index_until_rolled_back() {
+        let (mut bc, dir) = temp_chain("txindex");
+        let genesis_hash = bc.create_genesis().unwrap();
+        let genesis = bc.load_block(&genesis_hash).unwrap().unwrap();
+        let coinbase = genesis.transactions[0].clone();
+
+        let (tx, height) = bc.get_transaction(&coinbase.txid).unwrap().unwrap();
+        assert_eq!((tx.txid.as_str(), height), (coinbase.txid.as_str(), 0));
+        let eth_hash = coinbase.eth_hash.trim_start_matches("0x");
+        let (tx, _) = bc.get_transaction_by_eth_hash(eth_hash).unwrap().unwrap();
+        assert_eq!(tx.txid, coinbase.txid);
+        assert!(bc.get_transaction(&"ff".repeat(32)).unwrap().is_none());
+
+        // A reorged-out block's transactions are no longer reported as confirmed
+        bc.rollback_blocks(&[genesis]).unwrap();
+        assert!(bc.get_transaction(&coinbase.txid).unwrap().is_none());
+        assert!(bc.get_transaction_by_eth_hash(eth_hash).unwrap().is_none());
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn cached_chain_work_matches_walk() {
+        let (bc, dir) = temp_chain("chainwork");
+
+        let mut prev_hash = "0".repeat(64);
+        for index in 0..50u64 {
+            let cb = Transaction::coinbase(
+                "0x0000000000000000000000000000000000000001",
+                U256::from(index),
+            );
+            let header = BlockHeader {
+                index,
+                previous_hash: prev_hash.clone(),
+                merkle_root: compute_merkle_root(&vec![cb.txid.clone()]),
+                timestamp: 1_700_000_000 + index as i64 * 120,
+                nonce: 0,
+                difficulty: 1 + (index % 4) as u32,
+            };
+            let hash = compute_header_hash(&header).unwrap();
+            let block = Block {
+                header,
+                transactions: vec![cb],
+                hash: hash.clone(),
+            };
+
+            let mut batch = WriteBatch::default();
+            let blob = bincode::encode_to_vec(&block, *BINCODE_CONFIG).unwrap();
+            batch.put(format!("b:{}", hash).as_bytes(), &blob);
+            bc.put_chain_work(&mut batch, &block);
+            put_batch(&bc.db, batch).unwrap();
+
+            prev_hash = hash;
+        }
+
+        let cached = bc.load_cached_chain_work(&prev_hash).unwrap();
+        let walked = bc.calculate_chain_work_walk(&prev_hash).unwrap();
+        assert_eq!(cached, Some(walked));
+        assert_eq!(bc.calculate_chain_work(&prev_hash).unwrap(), walked);
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn cached_merkle_tree_matches_fresh_build() {
+        let (bc, dir) = temp_chain("merkle");
+
+        let txs: Vec<Transaction> = (0..7u64)
+            .map(|i| {
+                Transaction::coinbase("0x0000000000000000000000000000000000000001", U256::from(i))
+            })
+            .collect();
+        let txids: Vec<String> = txs.iter().map(|t| t.txid.clone()).collect();
+        let header = BlockHeader {
+            index: 1,
+            previous_hash: "0".repeat(64),
+            merkle_root: compute_merkle_root(&txids),
+            timestamp: 1_700_000_000,
+            nonce: 0,
+            difficulty: 1,
+        };
+        let block = Block {
+            hash: compute_header_hash(&header).unwrap(),
+            header,
+            transactions: txs,
+        };
+
+        let first = bc.merkle_tree(&block).unwrap();
+        let second = bc.merkle_tree(&block).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let fresh = MerkleTree::build(&txids);
+        assert_eq!(first.leaves(), fresh.leaves());
+        assert_eq!(first.root(), block.header.merkle_root);
+
+        // a header that doesn't commit to the transactions is refused
+        let mut forged = block.clone();
+        forged.hash = "ff".repeat(32);
+        forged.header.merkle_root = "00".repeat(32);
+        assert!(bc.merkle_tree(&forged).is_err());
+
+        // bounded: the oldest entry is evicted once capacity is exceeded
+        for i in 0..MERKLE_CACHE_CAPACITY {
+            bc.cache_merkle_tree(&format!("{:064x}", i), fresh.clone());
+        }
+        assert!(bc.merkle_cache.lock().unwrap().get(&block.hash).is_none());
+        assert_eq!(
+            bc.merkle_cache.lock().unwrap().trees.len(),
+            MERKLE_CACHE_CAPACITY
+        );
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn signatures_skipped_only_below_checkpoint() {
+        let keypair = crate::crypto::WalletKeypair::new();
+        let now = Utc::now().timestamp();
+        let funding = "f".repeat(64);
+
+        let spend = Transaction {
+            txid: String::new(),
+            eth_hash: String::new(),
+            inputs: vec![crate::transaction::TransactionInput {
+                txid: funding.clone(),
+                vout: 0,
+                pubkey: keypair.public_hex(),
+                signature: Some("00".repeat(64)), // not a valid signature
+            }],
+            outputs: vec![crate::transaction::TransactionOutput::new(
+                "0x0000000000000000000000000000000000000003".to_string(),
+                U256::from(10u64).pow(U256::from(18u64)),
+            )],
+            timestamp: now - 120,
+            valid_until: None,
+        }
+        .with_hashes();
+        let cb = Transaction::coinbase(
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+        );
+        let block = solved_block(vec![cb, spend]);
+        // The checkpointed block builds on it
+        let child = BlockHeader {
+            index: 1,
+            previous_hash: block.hash.clone(),
+            merkle_root: "0".repeat(64),
+            timestamp: now,
+            nonce: 0,
+            difficulty: block.header.difficulty,
+        };
+        let child_hash = compute_header_hash(&child).unwrap();
+        let headers = [block.header.clone(), child];
+
+        let try_insert = |assume_valid: Option<(u64, &str)>, headers: &[BlockHeader]| {
+            let (mut bc, dir) = temp_chain("assume-valid");
+            let owner = keypair.address().to_lowercase();
+            fund(
+                &bc,
+                &funding,
+                &owner,
+                U256::from(10u64).pow(U256::from(20u64)),
+            );
+            bc.assume_valid = assume_valid.map(|(height, hash)| (height, hash.to_string()));
+            bc.accept_assume_valid_headers(headers);
+            let result = bc.validate_and_insert_block(&block);
+            drop(bc);
+            let _ = std::fs::remove_dir_all(dir);
+            result
+        };
+
+        assert!(try_insert(None, &headers).is_err());
+        // The block is at the checkpoint height, not below it: fully validated
+        assert!(try_insert(Some((0, &block.hash)), &headers).is_err());
+        try_insert(Some((1, &child_hash)), &headers).unwrap();
+        // Below the checkpoint height but not shown to lead to the checkpoint hash
+        assert!(try_insert(Some((1, &child_hash)), &headers[..1]).is_err());
+        assert!(try_insert(Some((1, &"ab".repeat(32))), &headers).is_err());
+    }
+
+    #[test]
+    fn overflowing_output_sum_is_rejected() {
+        let (mut bc, dir) = temp_chain("overflow");
+        let keypair = crate::crypto::WalletKeypair::new();
+        let funding = "f".repeat(64);
+        let owner = keypair.address().to_lowercase();
+        fund(
+            &bc,
+            &funding,
+            &owner,
+            U256::from(10u64).pow(U256::from(20u64)),
+        );
+
+        // Two outputs of 2^255 each: the sum wraps to zero, which would look like
+        // a spend of nothing and pass the outputs <= inputs check
+        let half = U256::MAX / 2 + 1;
+        let to = "0x0000000000000000000000000000000000000003".to_string();
+        let outputs = vec![
+            crate::transaction::TransactionOutput::new(to.clone(), half),
+            crate::transaction::TransactionOutput::new(to, half),
+        ];
+        let spend = signed_spend(&keypair, &funding, outputs, None);
+        assert_eq!(spend.output_total(), None);
+
+        let cb = Transaction::coinbase(
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+        );
+        let err = bc
+            .validate_and_insert_block(&solved_block(vec![cb, spend]))
+            .unwrap_err();
+        assert!(err.to_string().contains("output sum overflows"), "{}", err);
+        // nothing was committed: the funding output is still unspent
+        assert!(
+            bc.db
+                .get(format!("u:{}:0", funding).as_bytes())
+                .unwrap()
+                .is_some()
+        );
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn block_may_spend_its_own_outputs_but_not_twice() {
+        let keypair = crate::crypto::WalletKeypair::new();
+        let sender = keypair.address().to_lowercase();
+        let recipient = "0x0000000000000000000000000000000000000003";
+        let asrm = U256::from(10u64).pow(U256::from(18u64));
+        let fee = asrm / 100;
+        let funding = "f".repeat(64);
+
+        let spend = |txid: &str, amount: U256, to: &str| {
+            let output = crate::transaction::TransactionOutput::new(to.to_string(), amount - fee);
+            signed_spend(&keypair, txid, vec![output], None)
+        };
+        let funded_chain = |name: &str| {
+            let (bc, dir) = temp_chain(name);
+            fund(&bc, &funding, &sender, asrm * 100);
+            (bc, dir)
+        };
+        let cb = || {
+            Transaction::coinbase(
+                "0x0000000000000000000000000000000000000001",
+                U256::from(50u64),
+            )
+        };
+
+        // Two transactions spending the funding output in one block
+        let (mut bc, dir) = funded_chain("double-spend");
+        let first = spend(&funding, asrm * 100, &sender);
+        let second = spend(&funding, asrm * 100, recipient);
+        let err = bc
+            .validate_and_insert_block(&solved_block(vec![cb(), first, second]))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("referenced utxo not found"),
+            "{}",
+            err
+        );
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+
+        // A child spending its parent's output earlier in the same block
+        let (mut bc, dir) = funded_chain("chained-spend");
+        let parent = spend(&funding, asrm * 100, &sender);
+        let child = spend(&parent.txid, asrm * 100 - fee, recipient);
+        let block = solved_block(vec![cb(), parent.clone(), child.clone()]);
+        bc.validate_and_insert_block(&block).unwrap();
+        let unspent = |bc: &Blockchain, txid: &str| {
+            bc.db
+                .get(format!("u:{}:0", txid).as_bytes())
+                .unwrap()
+                .is_some()
+        };
+        assert!(!unspent(&bc, &funding));
+        assert!(!unspent(&bc, &parent.txid));
+        assert!(unspent(&bc, &child.txid));
+
+        // A reorg rolls the block out and later replays it: the parent's output must be
+        // spent again and recorded in the undo data, or it could be spent twice
+        let spent_txids = |bc: &Blockchain| {
+            let mut txids: Vec<String> = bc
+                .load_spent_utxos(&block.hash)
+                .unwrap()
+                .into_iter()
+                .map(|u| u.txid)
+                .collect();
+            txids.sort();
+            txids
+        };
+        let mut expected = vec![funding.clone(), parent.txid.clone()];
+        expected.sort();
+        assert_eq!(spent_txids(&bc), expected);
+        bc.rollback_blocks(std::slice::from_ref(&block)).unwrap();
+        assert!(unspent(&bc, &funding));
+        assert!(!unspent(&bc, &parent.txid));
+        assert!(!unspent(&bc, &child.txid));
+
+        bc.replay_blocks(std::slice::from_ref(&block)).unwrap();
+        assert!(!unspent(&bc, &funding));
+        assert!(!unspent(&bc, &parent.txid));
+        assert!(unspent(&bc, &child.txid));
+        assert_eq!(spent_txids(&bc), expected);
+
+        bc.rollback_blocks(std::slice::from_ref(&block)).unwrap();
+        assert!(unspent(&bc, &funding));
+        assert!(!unspent(&bc, &parent.txid));
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn expiring_transactions_wait_for_the_activation_height() {
+        let (mut bc, dir) = temp_chain("tx-expiry-activation");
+        let keypair = crate::crypto::WalletKeypair::new();
+        let sender = keypair.address().to_lowercase();
+        let asrm = U256::from(10u64).pow(U256::from(18u64));
+        let funding = "f".repeat(64);
+        fund(&bc, &funding, &sender, asrm);
+
+        let output = crate::transaction::TransactionOutput::new(sender.clone(), asrm - asrm / 100);
+        let valid_until = Utc::now().timestamp() + 3600;
+        let tx = signed_spend(&keypair, &funding, vec![output], Some(valid_until));
+        let coinbase = Transaction::coinbase(
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+        );
+        let block = solved_block(vec![coinbase, tx]);
+
+        // Height 0 is below the default activation height
+        assert!(!bc.params.allows_tx_version(2, 0));
+        assert!(bc.params.allows_tx_version(1, 0));
+        let err = bc.validate_and_insert_block(&block).unwrap_err();
+        assert!(
+            err.to_string().contains("not allowed before height"),
+            "{}",
+            err
+        );
+
+        bc.params.tx_expiry_height = 0;
+        bc.validate_and_insert_block(&block).unwrap();
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn block_may_include_a_transaction_up_to_its_valid_until() {
+        let (mut bc, dir) = temp_chain("tx-expiry-block");
+        bc.params.tx_expiry_height = 0;
+        let keypair = crate::crypto::WalletKeypair::new();
+        let sender = keypair.address().to_lowercase();
+        let asrm = U256::from(10u64).pow(U256::from(18u64));
+        let funding = "f".repeat(64);
+        fund(&bc, &funding, &sender, asrm);
+
+        let output = crate::transaction::TransactionOutput::new(sender.clone(), asrm - asrm / 100);
+        let valid_until = Utc::now().timestamp() - 60;
+        let tx = signed_spend(&keypair, &funding, vec![output], Some(valid_until));
+        // The coinbase can't be newer than the block either
+        let mut coinbase = Transaction::coinbase(
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+        );
+        coinbase.timestamp = tx.timestamp;
+        let coinbase = coinbase.with_hashes();
+        let txs = vec![coinbase, tx];
+
+        let late = solved_block_at(txs.clone(), valid_until + 1);
+        let err = bc.validate_and_insert_block(&late).unwrap_err();
+        assert!(err.to_string().contains("transaction expired"), "{}", err);
+        assert!(bc.chain_tip.is_none());
+
+        let on_time = solved_block_at(txs, valid_until);
+        bc.validate_and_insert_block(&on_time).unwrap();
+        assert_eq!(bc.chain_tip.as_deref(), Some(on_time.hash.as_str()));
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn coinbase_outputs_are_spendable_only_at_maturity() {
+        use crate::config::COINBASE_MATURITY;
+
+        let (mut bc, dir) = temp_chain("coinbase-maturity");
+        let keypair = crate::crypto::WalletKeypair::new();
+        let miner = keypair.address().to_lowercase();
+        let asrm = U256::from(10u64).pow(U256::from(18u64));
+        let reward = Transaction::coinbase(&miner, asrm).with_hashes();
+        let parent = solved_block(vec![reward.clone()]);
+        bc.validate_and_insert_block(&parent).unwrap();
+
+        let output = crate::transaction::TransactionOutput::new(miner.clone(), asrm - asrm / 100);
+        let spend = signed_spend(&keypair, &reward.txid, vec![output], None);
+        let mut coinbase = Transaction::coinbase(
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+        );
+        coinbase.timestamp = spend.timestamp;
+        let txs = vec![coinbase.with_hashes(), spend];
+        let child = |index: u64| {
+            solved_block_on(
+                txs.clone(),
+                index,
+                parent.hash.clone(),
+                parent.header.timestamp + 1,
+            )
+        };
+
+        // One block short of maturity the spend is refused and nothing is written
+        let early = child(COINBASE_MATURITY - 1);
+        let err = bc.validate_and_insert_block(&early).unwrap_err();
+        assert!(err.to_string().contains("immature coinbase"), "{}", err);
+        assert_eq!(bc.chain_tip.as_deref(), Some(parent.hash.as_str()));
+        assert!(
+            bc.get_utxos(&miner)
+                .unwrap()
+                .iter()
+                .any(|u| u.txid == reward.txid)
+        );
+
+        let mature = child(COINBASE_MATURITY);
+        bc.validate_and_insert_block(&mature).unwrap();
+        assert_eq!(bc.chain_tip.as_deref(), Some(mature.hash.as_str()));
+        assert!(
+            !bc.get_utxos(&miner)
+                .unwrap()
+                .iter()
+                .any(|u| u.txid == reward.txid)
+        );
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn address_history_reports_both_sides_until_rolled_back() {
+        let (mut bc, dir) = temp_chain("history");
+        let keypair = crate::crypto::WalletKeypair::new();
+        let sender = keypair.address().to_lowercase();
+        let recipient = "0x0000000000000000000000000000000000000003";
+        let miner = "0x0000000000000000000000000000000000000001";
+        let asrm = U256::from(10u64).pow(U256::from(18u64));
+
+        let funding = "f".repeat(64);
+        fund(&bc, &funding, &sender, asrm * 100);
+
+        let fee = asrm / 100;
+        let outputs = vec![
+            crate::transaction::TransactionOutput::new(recipient.to_string(), asrm),
+            crate::transaction::TransactionOutput::new(sender.clone(), asrm * 99 - fee),
+        ];
+        let spend = signed_spend(&keypair, &funding, outputs, None);
+        let cb = Transaction::coinbase(miner, U256::from(50u64));
+        let block = solved_block(vec![cb.clone(), spend.clone()]);
+        bc.validate_and_insert_block(&block).unwrap();
+
+        assert_eq!(bc.count_address_history(&sender).unwrap(), 1);
+        let (history, next) = bc.get_address_history(&sender, None, 10).unwrap();
+        assert!(next.is_none());
+        let entry = &history[0];
+        assert_eq!(
+            (entry.txid.as_str(), entry.height),
+            (spend.txid.as_str(), 0)
+        );
+        assert_eq!(entry.timestamp, block.header.timestamp);
+        assert_eq!(entry.direction(), "sent");
+        assert_eq!(entry.sent - entry.received, asrm + fee);
+
+        // Mixed-case lookups hit the same lowercased index
+        let (history, _) = bc
+            .get_address_history(&recipient.to_uppercase().replace("0X", "0x"), None, 10)
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].direction(), "received");
+        assert_eq!(history[0].received, asrm);
+
+        let (history, _) = bc.get_address_history(miner, None, 10).unwrap();
+        assert_eq!(history[0].txid, cb.txid);
+        assert_eq!(history[0].direction(), "coinbase");
+
+        // Paging on from the last entry finds nothing more
+        let key = Blockchain::address_history_key(&sender, 0, 1);
+        let cursor = key.strip_prefix(&format!("ah:{}:", sender)).unwrap();
+        let (page, next) = bc.get_address_history(&sender, Some(cursor), 10).unwrap();
+        assert!(page.is_empty() && next.is_none());
+
+        bc.rollback_blocks(&[block]).unwrap();
+        assert_eq!(bc.count_address_history(&sender).unwrap(), 0);
+        assert_eq!(bc.count_address_history(recipient).unwrap(), 0);
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn checkpoint_opens_with_the_same_chain() {
+        let (mut bc, dir) = temp_chain("checkpoint-src");
+        let genesis = bc.create_genesis().unwrap();
+        for h in 1..4u64 {
+            let cb =
+                Transaction::coinbase("0x0000000000000000000000000000000000000001", U256::from(h));
+            let block = Block {
+                header: BlockHeader {
+                    index: h,
+                    previous_hash: bc.chain_tip.clone().unwrap(),
+                    merkle_root: compute_merkle_root(&vec![cb.txid.clone()]),
+                    timestamp: 1000 + h as i64,
+                    nonce: h,
+                    difficulty: 0x1d0fffff,
+                },
+                transactions: vec![cb],
+                hash: format!("{:064x}", h),
+            };
+            let blob = bincode::encode_to_vec(&block, *BINCODE_CONFIG).unwrap();
+            bc.db.put(format!("b:{}", block.hash), blob).unwrap();
+            bc.db
+                .put(format!("i:{}", h), block.hash.as_bytes())
+                .unwrap();
+            bc.db.put(b"tip", block.hash.as_bytes()).unwrap();
+            bc.chain_tip = Some(block.hash);
+        }
+
+        let snapshot = dir.with_extension("checkpoint");
+        bc.create_checkpoint(snapshot.to_str().unwrap()).unwrap();
+        // A second snapshot never overwrites the first
+        assert!(bc.create_checkpoint(snapshot.to_str().unwrap()).is_err());
+
+        // Writes after the snapshot don't leak into it
+        bc.db.put(b"tip", genesis.as_bytes()).unwrap();
+
+        let restored = Blockchain::new(snapshot.to_str().unwrap()).unwrap();
+        assert_eq!(restored.chain_tip, Some(format!("{:064x}", 3)));
+        assert_eq!(
+            restored.get_all_blocks().unwrap().len(),
+            bc.get_all_blocks().unwrap().len()
+        );
+        assert_eq!(restored.get_all_blocks().unwrap().len(), 4);
+
+        drop(restored);
+        drop(bc);
+        let _ = std::fs::remove_dir_all(snapshot);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn pruned_blocks_keep_headers_and_report_pruned() {
+        let (mut bc, dir) = temp_chain("prune");
+        let mut coinbases = Vec::new();
+        for h in 0..6u64 {
+            let cb =
+                Transaction::coinbase("0x0000000000000000000000000000000000000001", U256::from(h));
+            let block = Block {
+                header: BlockHeader {
+                    index: h,
+                    previous_hash: format!("{:064x}", h.saturating_sub(1)),
+                    merkle_root: compute_merkle_root(&vec![cb.txid.clone()]),
+                    timestamp: 1000 + h as i64,
+                    nonce: h,
+                    difficulty: 0x1d0fffff,
+                },
+                transactions: vec![cb.clone()],
+                hash: format!("{:064x}", h),
+            };
+            let mut batch = WriteBatch::default();
+            batch.put(
+                format!("b:{}", block.hash),
+                bincode::encode_to_vec(&block, *BINCODE_CONFIG).unwrap(),
+            );
+            batch.put(format!("i:{}", h), block.hash.as_bytes());
+            Blockchain::put_tx_index(&mut batch, &block).unwrap();
+            put_batch(&bc.db, batch).unwrap();
+            bc.chain_tip = Some(block.hash);
+            coinbases.push(cb);
+        }
+
+        // Off by default
+        assert_eq!(bc.prune_blocks().unwrap(), 0);
+
+        // Asking for 1 still keeps the 2 blocks a reorg could undo
+        bc.max_reorg_depth = 2;
+        bc.prune_depth = Some(1);
+        assert_eq!(bc.prune_blocks().unwrap(), 3);
+        assert_eq!(bc.pruned_below().unwrap(), 3);
+        assert_eq!(bc.prune_blocks().unwrap(), 0);
+
+        let pruned = format!("{:064x}", 0);
+        let err = bc.load_block(&pruned).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<BlockPruned>(),
+            Some(&BlockPruned {
+                hash: pruned.clone()
+            })
+        );
+        assert_eq!(bc.get_block_height(&pruned).unwrap(), Some(0));
+        assert_eq!(bc.main_chain_hash(0).unwrap(), Some(pruned.clone()));
+        assert_eq!(bc.main_chain_hash(6).unwrap(), None);
+        assert_eq!(bc.get_next_index().unwrap(), 6);
+        assert_eq!(bc.get_headers_range(0, 10).unwrap().len(), 6);
+        assert!(bc.load_block(&format!("{:064x}", 3)).unwrap().is_some());
+
+        // Range queries that reach into the pruned part fail instead of skipping it
+        let is_pruned = |e: anyhow::Error| e.downcast_ref::<BlockPruned>().is_some();
+        assert!(is_pruned(bc.get_blocks_range(0, None).unwrap_err()));
+        assert!(is_pruned(bc.get_all_blocks().unwrap_err()));
+        assert_eq!(bc.get_blocks_range(3, None).unwrap().len(), 3);
+        assert!(is_pruned(bc.get_block_sizes_range(2, 5).unwrap_err()));
+        let sizes = bc.get_block_sizes_range(3, 10).unwrap();
+        let expected: Vec<usize> = bc
+            .get_blocks_range(3, None)
+            .unwrap()
+            .iter()
+            .map(|block| {
+                bincode::encode_to_vec(block, *BINCODE_CONFIG)
+                    .unwrap()
+                    .len()
+            })
+            .collect();
+        assert_eq!(sizes, expected);
+        let err = bc.get_transaction(&coinbases[1].txid).unwrap_err();
+        assert!(is_pruned(err));
+        assert_eq!(
+            bc.get_transaction_height(&coinbases[1].txid).unwrap(),
+            Some(1)
+        );
+        assert!(bc.get_transaction(&coinbases[4].txid).unwrap().is_some());
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn mempool_snapshot_survives_restart() {
+        let (bc, dir) = temp_chain("mempool");
+
+        let pending: Vec<Transaction> = (0..3u64)
+            .map(|i| {
+                Transaction::coinbase("0x0000000000000000000000000000000000000002", U256::from(i))
+            })
+            .collect();
+        bc.save_mempool(&pending).unwrap();
+        // a later snapshot replaces the earlier one
+        bc.save_mempool(&pending[1..]).unwrap();
+        drop(bc);
+
+        let bc = Blockchain::new(dir.to_str().unwrap()).unwrap();
+        let mut restored: Vec<String> = bc
+            .load_mempool()
+            .unwrap()
+            .into_iter()
+            .map(|tx| tx.txid)
+            .collect();
+        restored.sort();
+        let mut expected: Vec<String> = pending[1..].iter().map(|tx| tx.txid.clone()).collect();
+        expected.sort();
+        assert_eq!(restored, expected);
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
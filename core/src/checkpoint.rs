@@ -13,10 +13,18 @@
 ///
 /// Important: Checkpoints do NOT affect the validity of blocks themselves.
 /// They only affect chain selection policy in this specific node implementation.
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Extra checkpoints read from the data directory at startup: a JSON object mapping
+/// height to block hash, e.g. `{"10000": "00ab...ef"}`
+pub const CHECKPOINTS_FILE: &str = "checkpoints.json";
 
 /// Checkpoint: Policy-level chain anchor point
 /// Format: (block_height, block_hash)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Checkpoint {
     pub height: u64,
     pub hash: String,
@@ -58,6 +66,57 @@ pub fn get_checkpoints() -> Vec<Checkpoint> {
     ]
 }
 
+/// Compiled checkpoints merged with `<data_dir>/checkpoints.json`, sorted by height.
+///
+/// Compiled entries take precedence: a file entry only adds a new height or pins a
+/// compiled placeholder (empty hash), and one contradicting a pinned compiled hash is
+/// ignored with a warning. No file means the compiled set. A malformed file is an
+/// error, so a typo can't silently drop a checkpoint the operator expects enforced.
+pub fn load_checkpoints(data_dir: &Path) -> Result<Vec<Checkpoint>> {
+    let mut checkpoints = get_checkpoints();
+    let path = data_dir.join(CHECKPOINTS_FILE);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(checkpoints),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+
+    let entries: BTreeMap<String, String> = serde_json::from_str(&content)
+        .with_context(|| format!("{} must map heights to block hashes", path.display()))?;
+    for (height, hash) in entries {
+        let height: u64 = height
+            .parse()
+            .map_err(|_| anyhow!("{}: invalid height {:?}", path.display(), height))?;
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(anyhow!(
+                "{}: hash at height {} is not 64 hex characters",
+                path.display(),
+                height
+            ));
+        }
+        let hash = hash.to_lowercase();
+
+        match checkpoints.iter_mut().find(|cp| cp.height == height) {
+            Some(cp) if cp.hash.is_empty() => cp.hash = hash,
+            Some(cp) if cp.hash != hash => log::warn!(
+                "{}: ignoring height {}, the compiled checkpoint pins {}",
+                path.display(),
+                height,
+                cp.hash
+            ),
+            Some(_) => {}
+            None => checkpoints.push(Checkpoint {
+                height,
+                hash,
+                description: format!("From {}", CHECKPOINTS_FILE),
+            }),
+        }
+    }
+
+    checkpoints.sort_by_key(|cp| cp.height);
+    Ok(checkpoints)
+}
+
 /// Policy check: Validate that a chain doesn't conflict with checkpoints
 ///
 /// This is a POLICY decision, not a consensus rule.
@@ -65,9 +124,7 @@ pub fn get_checkpoints() -> Vec<Checkpoint> {
 /// which means this chain should be rejected by THIS node's policy.
 ///
 /// Other nodes without checkpoints enabled will still see the block as valid.
-pub fn validate_against_checkpoints(height: u64, hash: &str) -> bool {
-    let checkpoints = get_checkpoints();
-
+pub fn validate_against_checkpoints(checkpoints: &[Checkpoint], height: u64, hash: &str) -> bool {
     for cp in checkpoints {
         if cp.height == height {
             if cp.hash.is_empty() {
@@ -99,18 +156,28 @@ pub fn validate_against_checkpoints(height: u64, hash: &str) -> bool {
 
 /// Get the latest checkpoint height
 /// Blocks below this height are considered policy-final by this node
-pub fn get_latest_checkpoint_height() -> u64 {
-    let checkpoints = get_checkpoints();
+pub fn get_latest_checkpoint_height(checkpoints: &[Checkpoint]) -> u64 {
     checkpoints.iter().map(|cp| cp.height).max().unwrap_or(0)
 }
 
+/// Height of the latest checkpoint whose hash is pinned, if any.
+/// Placeholder checkpoints (empty hash) vouch for nothing and are ignored.
+pub fn get_latest_verified_checkpoint_height(checkpoints: &[Checkpoint]) -> Option<u64> {
+    checkpoints
+        .iter()
+        .filter(|cp| !cp.hash.is_empty())
+        .map(|cp| cp.height)
+        .max()
+}
+
 /// Check if reorganization would conflict with checkpoint policy
 /// Returns (allowed, reason)
 pub fn check_reorg_against_checkpoints(
+    checkpoints: &[Checkpoint],
     reorg_depth: u64,
     current_height: u64,
 ) -> (bool, Option<String>) {
-    let latest_checkpoint = get_latest_checkpoint_height();
+    let latest_checkpoint = get_latest_checkpoint_height(checkpoints);
     let reorg_target_height = current_height.saturating_sub(reorg_depth);
 
     if reorg_target_height <= latest_checkpoint {
@@ -132,29 +199,72 @@ mod tests {
 
     #[test]
     fn test_checkpoint_validation() {
+        let checkpoints = get_checkpoints();
         // Valid block at non-checkpoint height
-        assert!(validate_against_checkpoints(50, "any_hash"));
+        assert!(validate_against_checkpoints(&checkpoints, 50, "any_hash"));
 
         // Genesis checkpoint (empty hash = not enforced yet)
-        assert!(validate_against_checkpoints(0, "any_hash"));
+        assert!(validate_against_checkpoints(&checkpoints, 0, "any_hash"));
     }
 
     #[test]
     fn test_latest_checkpoint() {
-        let height = get_latest_checkpoint_height();
+        let height = get_latest_checkpoint_height(&get_checkpoints());
         assert_eq!(height, 0); // Currently only genesis
     }
 
     #[test]
     fn test_reorg_checkpoint_policy() {
+        let checkpoints = get_checkpoints();
         // Reorg of 10 blocks from height 100 - should be allowed (target: 90)
-        let (allowed, _) = check_reorg_against_checkpoints(10, 100);
+        let (allowed, _) = check_reorg_against_checkpoints(&checkpoints, 10, 100);
         assert!(allowed);
 
         // Deep reorg that would go below genesis (checkpoint at 0)
-        let (allowed, reason) = check_reorg_against_checkpoints(150, 100);
+        let (allowed, reason) = check_reorg_against_checkpoints(&checkpoints, 150, 100);
         assert!(!allowed);
         assert!(reason.is_some());
         assert!(reason.unwrap().contains("checkpoint"));
     }
+
+    #[test]
+    fn test_checkpoints_file_merges_under_compiled() {
+        let dir = std::env::temp_dir().join(format!(
+            "astram-checkpoints-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // No file: just the compiled set
+        let compiled = get_checkpoints().len();
+        assert_eq!(load_checkpoints(&dir).unwrap().len(), compiled);
+
+        let write = |json: &str| std::fs::write(dir.join(CHECKPOINTS_FILE), json).unwrap();
+        let (genesis, pinned) = ("cd".repeat(32), "ab".repeat(32));
+        let upper = pinned.to_uppercase();
+        write(&format!(r#"{{"500": "{}", "0": "{}"}}"#, upper, genesis));
+        let cps = load_checkpoints(&dir).unwrap();
+        let heights: Vec<u64> = cps.iter().map(|cp| cp.height).collect();
+        assert_eq!(heights, vec![0, 500]);
+        // The file pins the compiled genesis placeholder; hashes are stored lowercase
+        assert_eq!(cps[0].hash, genesis);
+        assert_eq!(cps[1].hash, pinned);
+        assert!(!validate_against_checkpoints(&cps, 500, &genesis));
+        assert!(validate_against_checkpoints(&cps, 500, &pinned));
+        assert_eq!(get_latest_verified_checkpoint_height(&cps), Some(500));
+
+        // Malformed hashes and heights refuse to load
+        for bad in [
+            r#"{"10": "abc"}"#,
+            &format!(r#"{{"10": "{}"}}"#, "zz".repeat(32)),
+            &format!(r#"{{"ten": "{}"}}"#, "ab".repeat(32)),
+            "[1, 2]",
+        ] {
+            write(bad);
+            assert!(load_checkpoints(&dir).is_err(), "{}", bad);
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
 }
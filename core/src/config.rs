@@ -13,6 +13,9 @@ pub fn initial_block_reward() -> U256 {
 /// Halving occurs every 210,000 blocks (~4 years at 10 min block time)
 pub const HALVING_INTERVAL: u64 = 210_000;
 
+/// Blocks that must be built on top of a coinbase before its outputs can be spent
+pub const COINBASE_MATURITY: u64 = 100;
+
 /// Max supply: 42,000,000 ASRM in ram
 pub fn max_supply() -> U256 {
     RAM_PER_ASRM * U256::from(42_000_000)
@@ -36,9 +39,25 @@ pub const MIN_RELAY_FEE_NAT_PER_BYTE: U256 = U256([200_000_000_000, 0, 0, 0]);
 /// In ram: 300,000,000,000 (300 billion)
 pub const DEFAULT_WALLET_FEE_NAT_PER_BYTE: U256 = U256([300_000_000_000, 0, 0, 0]);
 
+// ========== Gas Mapping ==========
+// Ethereum wallets pay gas × gasPrice. A transaction's gas is defined from its encoded
+// size so that at GAS_PRICE it comes to exactly calculate_min_fee: paying the quoted
+// gas at the quoted price always clears the byte-fee check.
+
+/// Price per gas quoted to Ethereum wallets: 10 Gwei
+/// In ram: 10,000,000,000
+pub const GAS_PRICE: U256 = U256([10_000_000_000, 0, 0, 0]);
+
+/// Gas every transaction pays: BASE_MIN_FEE / GAS_PRICE
+pub const BASE_GAS: u64 = 10_000;
+
+/// Gas per encoded byte: MIN_RELAY_FEE_NAT_PER_BYTE / GAS_PRICE
+pub const GAS_PER_BYTE: u64 = 20;
+
 // ========== Helper Functions ==========
 
-/// Calculate block reward for given height based on halving schedule
+/// Calculate block reward for given height based on halving schedule.
+/// Each halving is a right shift, so odd ram amounts round down.
 pub fn calculate_block_reward(block_height: u64) -> U256 {
     let halvings = (block_height / HALVING_INTERVAL) as u32;
 
@@ -50,6 +69,28 @@ pub fn calculate_block_reward(block_height: u64) -> U256 {
     initial_block_reward() >> halvings
 }
 
+/// Total ram issued by blocks 0..=block_height, computed from the halving schedule
+/// (one multiplication per halving era, no coinbase scan)
+pub fn cumulative_emission(block_height: u64) -> U256 {
+    let mut total = U256::zero();
+    let mut era_start: u64 = 0;
+    while era_start <= block_height {
+        let reward = calculate_block_reward(era_start);
+        if reward.is_zero() {
+            break;
+        }
+        let era_end = era_start
+            .saturating_add(HALVING_INTERVAL - 1)
+            .min(block_height);
+        total += reward * U256::from(era_end - era_start + 1);
+        era_start = match era_start.checked_add(HALVING_INTERVAL) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    total
+}
+
 /// Calculate minimum fee for transaction in ram based on transaction size
 /// Formula: BASE_MIN_FEE + (size × MIN_RELAY_FEE_NAT_PER_BYTE)
 /// Example: 300 bytes -> 100,000,000,000,000 + (300 × 200,000,000,000) = 160 Twei = 0.00016 ASRM
@@ -64,6 +105,13 @@ pub fn calculate_default_fee(tx_size_bytes: usize) -> U256 {
     BASE_MIN_FEE + (DEFAULT_WALLET_FEE_NAT_PER_BYTE * U256::from(tx_size_bytes))
 }
 
+/// Gas for a transaction of `tx_size_bytes` encoded bytes
+/// Formula: BASE_GAS + (size × GAS_PER_BYTE)
+/// Example: 300 bytes -> 10,000 + (300 × 20) = 16,000 gas, × 10 Gwei = 0.00016 ASRM
+pub fn gas_for_size(tx_size_bytes: usize) -> u64 {
+    BASE_GAS.saturating_add(GAS_PER_BYTE.saturating_mul(tx_size_bytes as u64))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,6 +131,28 @@ mod tests {
         assert_eq!(reward_after, RAM_PER_ASRM * U256::from(4));
     }
 
+    #[test]
+    fn test_cumulative_emission_matches_schedule() {
+        let mut expected = U256::zero();
+        for height in 0..=(HALVING_INTERVAL * 2 + 5) {
+            expected += calculate_block_reward(height);
+            if height % 50_000 == 0
+                || height.abs_diff(HALVING_INTERVAL) <= 1
+                || height.abs_diff(HALVING_INTERVAL * 2) <= 1
+            {
+                assert_eq!(cumulative_emission(height), expected, "height {}", height);
+            }
+        }
+        assert_eq!(cumulative_emission(HALVING_INTERVAL * 2 + 5), expected);
+
+        // issuance stops once the reward shifts to zero
+        assert_eq!(
+            cumulative_emission(HALVING_INTERVAL * 40),
+            cumulative_emission(u64::MAX)
+        );
+        assert!(cumulative_emission(u64::MAX) <= max_supply());
+    }
+
     #[test]
     fn test_fee_calculation() {
         // Standard transaction: 300 bytes (typical)
@@ -113,4 +183,13 @@ mod tests {
         let ASRM_160 = RAM_PER_ASRM * U256::from(160);
         assert_eq!(spam_cost, ASRM_160);
     }
+
+    #[test]
+    fn test_gas_at_gas_price_is_the_min_fee() {
+        for size in [0, 1, 300, 1000, 65_536] {
+            let fee = GAS_PRICE * U256::from(gas_for_size(size));
+            assert_eq!(fee, calculate_min_fee(size), "size {}", size);
+        }
+        assert_eq!(gas_for_size(300), 16_000);
+    }
 }
@@ -1,6 +1,7 @@
 use crate::block::{
     Block, BlockHeader, compute_header_hash, compute_merkle_root, serialize_header,
 };
+use crate::blockchain::Blockchain;
 use crate::transaction::Transaction;
 use anyhow::{Result, anyhow};
 use chrono::Utc;
@@ -90,8 +91,20 @@ pub fn mine_block_with_coinbase_cuda(
     let function = module.get_function("mine_kernel")
         .map_err(|e| anyhow!("Failed to get CUDA kernel function 'mine_kernel': {}", e))?;
 
+    // The kernel compares hashes against the same target the node validates with
+    let target = Blockchain::compact_to_target(difficulty);
+    if target.is_zero() {
+        return Err(anyhow!(
+            "cannot mine with invalid target bits: 0x{:08x}",
+            difficulty
+        ));
+    }
+    let mut target_bytes = [0u8; 32];
+    target.to_big_endian(&mut target_bytes);
+
     let prefix_dev = DeviceBuffer::from_slice(&prefix)?;
     let suffix_dev = DeviceBuffer::from_slice(&suffix)?;
+    let target_dev = DeviceBuffer::from_slice(&target_bytes)?;
 
     let mut found_flag = DeviceBuffer::from_slice(&[0u32])?;
     let found_nonce = DeviceBuffer::from_slice(&[0u64])?;
@@ -125,7 +138,7 @@ pub fn mine_block_with_coinbase_cuda(
                 suffix.len() as i32,
                 start_nonce,
                 batch_size,
-                difficulty as i32,
+                target_dev.as_device_ptr(),
                 found_flag.as_device_ptr(),
                 found_nonce.as_device_ptr(),
                 found_hash.as_device_ptr()
@@ -168,7 +181,7 @@ pub fn mine_block_with_coinbase_cuda(
             if cpu_hash_hex != gpu_hash_hex {
                 return Err(anyhow!("GPU hash mismatch - GPU: {}, CPU: {}", gpu_hash_hex, cpu_hash_hex));
             }
-            if !cpu_hash_hex.starts_with(&"0".repeat(difficulty as usize)) {
+            if U256::from_big_endian(&cpu_hash) >= target {
                 return Err(anyhow!("GPU found nonce did not satisfy target"));
             }
 
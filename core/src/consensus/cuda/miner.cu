@@ -136,27 +136,15 @@ __device__ void sha256d(const uint8_t* data, int len, uint8_t out[32]) {
     sha256(tmp, 32, out);
 }
 
-__device__ __forceinline__ int meets_target(const uint8_t hash[32], int difficulty) {
-    if (difficulty <= 0) {
-        return 1;
-    }
-
-    int full_bytes = difficulty / 2;
-    int half = difficulty % 2;
-
-    for (int i = 0; i < full_bytes; i++) {
-        if (hash[i] != 0) {
-            return 0;
-        }
-    }
-
-    if (half) {
-        if ((hash[full_bytes] & 0xF0) != 0) {
-            return 0;
+// Hash and target are both big-endian 256-bit numbers; valid only if hash < target
+__device__ __forceinline__ int meets_target(const uint8_t hash[32], const uint8_t target[32]) {
+    for (int i = 0; i < 32; i++) {
+        if (hash[i] != target[i]) {
+            return hash[i] < target[i];
         }
     }
 
-    return 1;
+    return 0;
 }
 
 extern "C" __global__ void mine_kernel(
@@ -166,7 +154,7 @@ extern "C" __global__ void mine_kernel(
     int suffix_len,
     uint64_t start_nonce,
     uint64_t total,
-    int difficulty,
+    const uint8_t* target,
     unsigned int* found_flag,
     uint64_t* found_nonce,
     uint8_t* found_hash
@@ -199,7 +187,7 @@ extern "C" __global__ void mine_kernel(
         uint8_t hash[32];
         sha256d(msg, len, hash);
 
-        if (meets_target(hash, difficulty)) {
+        if (meets_target(hash, target)) {
             if (atomicCAS(found_flag, 0, 1) == 0) {
                 *found_nonce = nonce;
                 for (int j = 0; j < 32; j++) {
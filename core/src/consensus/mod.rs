@@ -1,13 +1,16 @@
 // core/consensus.rs
 use crate::block::{Block, BlockHeader, compute_header_hash, compute_merkle_root};
+use crate::blockchain::Blockchain;
 use crate::transaction::Transaction;
 use anyhow::{Result, anyhow};
 use chrono::Utc;
 use primitive_types::U256;
 use std::sync::{
-    Arc,
-    atomic::{AtomicBool, Ordering},
+    Arc, mpsc,
+    atomic::{AtomicBool, AtomicU64, Ordering},
 };
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "cuda-miner")]
 pub mod cuda;
@@ -15,16 +18,98 @@ pub mod cuda;
 #[cfg(feature = "cuda-miner")]
 pub use cuda::mine_block_with_coinbase_cuda;
 
+/// Indices of the transactions to put in a block, in their original order, so their
+/// encoded sizes stay within `max_bytes`. Higher fee per byte (`fees[i]` over the size
+/// of `txs[i]`) goes first; a transaction spending another candidate is only taken
+/// once that parent is, so the original order stays valid.
+pub fn select_block_transactions(
+    txs: &[Transaction],
+    fees: &[U256],
+    max_bytes: usize,
+) -> Vec<usize> {
+    use crate::transaction::BINCODE_CONFIG;
+    use std::collections::HashMap;
+
+    let sizes: Vec<usize> = txs
+        .iter()
+        .map(|tx| {
+            bincode::encode_to_vec(tx, *BINCODE_CONFIG)
+                .map(|b| b.len())
+                .unwrap_or(usize::MAX)
+        })
+        .collect();
+    let position: HashMap<&str, usize> = txs
+        .iter()
+        .enumerate()
+        .map(|(i, tx)| (tx.txid.as_str(), i))
+        .collect();
+    let parents: Vec<Vec<usize>> = txs
+        .iter()
+        .map(|tx| {
+            tx.inputs
+                .iter()
+                .filter_map(|inp| position.get(inp.txid.as_str()).copied())
+                .collect()
+        })
+        .collect();
+    let rate = |i: usize| {
+        let fee = fees.get(i).copied().unwrap_or_default();
+        fee / U256::from(sizes[i].max(1))
+    };
+
+    let mut by_rate: Vec<usize> = (0..txs.len()).collect();
+    by_rate.sort_by(|&a, &b| rate(b).cmp(&rate(a)).then(a.cmp(&b)));
+
+    let mut included = vec![false; txs.len()];
+    let mut used = 0usize;
+    // Children held back until the parent they wait for is included
+    let mut waiting: HashMap<usize, Vec<usize>> = HashMap::new();
+    for first in by_rate {
+        let mut work = vec![first];
+        while let Some(i) = work.pop() {
+            if included[i] {
+                continue;
+            }
+            if let Some(&parent) = parents[i].iter().find(|&&p| !included[p]) {
+                waiting.entry(parent).or_default().push(i);
+                continue;
+            }
+            if sizes[i] > max_bytes.saturating_sub(used) {
+                continue;
+            }
+            included[i] = true;
+            used += sizes[i];
+            work.extend(waiting.remove(&i).unwrap_or_default());
+        }
+    }
+
+    (0..txs.len()).filter(|&i| included[i]).collect()
+}
+
+/// Target for compact `bits`, the same one `validate_and_insert_block` checks the
+/// block hash against. Errors on bits that encode a zero target.
+fn mining_target(bits: u32) -> Result<U256> {
+    let target = Blockchain::compact_to_target(bits);
+    if target.is_zero() {
+        return Err(anyhow!(
+            "cannot mine with invalid target bits: 0x{:08x}",
+            bits
+        ));
+    }
+    Ok(target)
+}
+
 /// Find a valid nonce by updating header.nonce and returning (nonce, hash).
+/// `difficulty` is compact target bits, as stored in the header.
 /// Simple CPU single-threaded loop. Caller should run this in spawn_blocking.
 pub fn find_valid_nonce(header: &mut BlockHeader, difficulty: u32) -> Result<(u64, String)> {
-    let target_prefix = "0".repeat(difficulty as usize);
+    let target = mining_target(difficulty)?;
     let mut nonce: u64 = header.nonce;
 
     loop {
         header.nonce = nonce;
         let hash = compute_header_hash(header)?;
-        if hash.starts_with(&target_prefix) {
+        if Blockchain::hash_to_u256(&hash)? < target {
             return Ok((nonce, hash));
         }
 
@@ -36,12 +121,95 @@ pub fn find_valid_nonce(header: &mut BlockHeader, difficulty: u32) -> Result<(u6
     }
 }
 
+/// Resolve the CPU mining thread count: the `MINING_THREADS` env var wins, then
+/// `configured` (0 = auto), then one thread per available core.
+pub fn resolve_mining_threads(configured: usize) -> usize {
+    if let Some(n) = std::env::var("MINING_THREADS")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|n| *n > 0)
+    {
+        return n;
+    }
+    if configured > 0 {
+        return configured;
+    }
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Nonces each worker hashes between checks of the cancel/found flags
+const NONCE_BATCH: u64 = 10_000;
+
+/// Span the reported hashrate is averaged over
+const HASHRATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Longest single sleep of a throttled worker before it re-checks the cancel/found flags
+const THROTTLE_SLEEP_SLICE: Duration = Duration::from_millis(50);
+
+/// Consecutive header-hash failures a worker retries before giving up on the round
+const MAX_HASH_RETRIES: u32 = 5;
+
+/// Pause before the first retry after a hash failure; doubles with each consecutive one
+/// so a persistent failure can't spin the worker
+const HASH_RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Idle time after a batch that took `busy` so hashing fills `duty_percent` of wall time
+fn throttle_idle(busy: Duration, duty_percent: u32) -> Duration {
+    busy * (100 - duty_percent) / duty_percent
+}
+
+/// Sleep `throttle_idle` in slices so cancellation is still noticed promptly
+fn throttle_pause(busy: Duration, duty_percent: u32, found: &AtomicBool, cancel_flag: &AtomicBool) {
+    let mut idle = throttle_idle(busy, duty_percent);
+    while !idle.is_zero() {
+        if found.load(Ordering::Relaxed) || cancel_flag.load(Ordering::Relaxed) {
+            return;
+        }
+        let slice = idle.min(THROTTLE_SLEEP_SLICE);
+        std::thread::sleep(slice);
+        idle -= slice;
+    }
+}
+
+/// Hash counts sampled over the last HASHRATE_WINDOW
+struct HashrateWindow {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl HashrateWindow {
+    fn new(start: Instant) -> Self {
+        Self {
+            samples: VecDeque::from([(start, 0)]),
+        }
+    }
+
+    /// Record `total` hashes so far at `now`; the average rate since the oldest sample
+    /// still in the window, or None if no time has passed
+    fn record(&mut self, now: Instant, total: u64) -> Option<f64> {
+        while self.samples.len() > 1 && now.duration_since(self.samples[0].0) > HASHRATE_WINDOW {
+            self.samples.pop_front();
+        }
+        let (oldest_at, oldest_total) = self.samples[0];
+        self.samples.push_back((now, total));
+        let elapsed = now.duration_since(oldest_at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        Some(total.saturating_sub(oldest_total) as f64 / elapsed)
+    }
+}
+
 /// High-level miner function that prepends a coinbase tx, computes merkle, and runs PoW.
 /// - `index`: block index (must be provided by caller; index is part of header/hash)
 /// - `previous_hash`: previous block hash hex
-/// - `difficulty`: number of leading-hex-nibble zero characters to require (simple model)
+/// - `difficulty`: compact target bits for the header; the hash must be below their target
 /// - `transactions`: non-coinbase transactions (txids should already be set)
 /// - `miner_address`: address to receive coinbase reward
+/// - `threads`: CPU worker threads; worker `i` tries nonces `i, i + threads, i + 2*threads, ...`
+/// - `throttle_percent`: share of wall time each worker spends hashing (0 or 100 = unthrottled);
+///   workers sleep after every batch in proportion to how long it took
 ///
 /// Returns mined Block (header.nonce and hash set).
 pub fn mine_block_with_coinbase(
@@ -53,8 +221,52 @@ pub fn mine_block_with_coinbase(
     reward: U256,
     cancel_flag: Arc<AtomicBool>,
     hashrate: Option<Arc<std::sync::Mutex<f64>>>,
+    threads: usize,
+    throttle_percent: u8,
 ) -> Result<Block> {
-    println!("[DEBUG] Mining: mine_block_with_coinbase called with difficulty={}", difficulty);
+    mine_with_hasher(
+        index,
+        prev_hash,
+        difficulty,
+        txs,
+        miner_addr,
+        reward,
+        cancel_flag,
+        hashrate,
+        threads,
+        throttle_percent,
+        &compute_header_hash,
+    )
+}
+
+/// `mine_block_with_coinbase` with the header hash function injected, so tests can make
+/// it fail
+#[allow(clippy::too_many_arguments)]
+fn mine_with_hasher<H>(
+    index: u64,
+    prev_hash: String,
+    difficulty: u32,
+    txs: Vec<Transaction>,
+    miner_addr: &str,
+    reward: U256,
+    cancel_flag: Arc<AtomicBool>,
+    hashrate: Option<Arc<std::sync::Mutex<f64>>>,
+    threads: usize,
+    throttle_percent: u8,
+    hasher: &H,
+) -> Result<Block>
+where
+    H: Fn(&BlockHeader) -> Result<String> + Sync,
+{
+    let threads = threads.max(1);
+    let duty_percent = match throttle_percent {
+        1..=99 => throttle_percent as u32,
+        _ => 100,
+    };
+    println!(
+        "[DEBUG] Mining: mine_block_with_coinbase called with difficulty={} threads={} duty={}%",
+        difficulty, threads, duty_percent
+    );
     let coinbase = Transaction::coinbase(miner_addr, reward).with_hashes();
     let mut all_txs = vec![coinbase];
     all_txs.extend(txs);
@@ -71,76 +283,371 @@ pub fn mine_block_with_coinbase(
         difficulty,
     };
 
-    let target_prefix = "0".repeat(difficulty as usize);
-    let mut nonce: u64 = 0;
-    let mining_start = std::time::Instant::now();
-    let mut last_hashrate_update = mining_start;
-    let mut hashes_since_update: u64 = 0;
-    
-    println!("[DEBUG] Mining: Entering mining loop, target_zeros={}", target_prefix.len());
+    let target = mining_target(difficulty)?;
+    // Set by the first worker to finish (found a nonce or hit an error); stops the others
+    let found = AtomicBool::new(false);
+    let hashes_total = AtomicU64::new(0);
+    let (result_tx, result_rx) = mpsc::channel::<Result<(u64, String)>>();
 
-    // ⛏️ CPU mining loop
-    loop {
-        // ⛔ network cancellation check
-        if cancel_flag.load(Ordering::Relaxed) {
-            return Err(anyhow!("Mining cancelled due to new peer block"));
-        }
+    println!("[DEBUG] Mining: Entering mining loop, target={:x}", target);
+
+    // ⛏️ CPU mining workers
+    let outcome = std::thread::scope(|scope| {
+        for worker in 0..threads {
+            let template = &header;
+            let mut header = header.clone();
+            let result_tx = result_tx.clone();
+            let (found, hashes_total, cancel_flag) = (&found, &hashes_total, &cancel_flag);
+
+            scope.spawn(move || {
+                let step = threads as u64;
+                let mut nonce = worker as u64;
+                let mut failures = 0u32;
+                'mining: loop {
+                    // ⛔ network cancellation / another worker won
+                    if found.load(Ordering::Relaxed) || cancel_flag.load(Ordering::Relaxed) {
+                        return;
+                    }
 
-        // Log first iteration only
-        if nonce == 0 {
-            println!("[DEBUG] Mining loop: STARTING iteration with nonce=0");
+                    let batch_start = Instant::now();
+                    for _ in 0..NONCE_BATCH {
+                        header.nonce = nonce;
+                        let hash = match hasher(&header) {
+                            Ok(hash) => {
+                                failures = 0;
+                                hash
+                            }
+                            // Retry the same nonce from a fresh copy of the header rather
+                            // than losing the round; give up only if it keeps failing
+                            Err(e) => {
+                                failures += 1;
+                                if failures > MAX_HASH_RETRIES {
+                                    log::error!(
+                                        "Mining worker {}: header hash failed {} times in a row: {}",
+                                        worker,
+                                        failures,
+                                        e
+                                    );
+                                    if !found.swap(true, Ordering::SeqCst) {
+                                        let _ = result_tx.send(Err(e));
+                                    }
+                                    return;
+                                }
+                                log::warn!(
+                                    "Mining worker {}: header hash failed at nonce {} ({}), retrying",
+                                    worker,
+                                    nonce,
+                                    e
+                                );
+                                header = template.clone();
+                                std::thread::sleep(HASH_RETRY_BACKOFF * 2u32.pow(failures - 1));
+                                continue 'mining;
+                            }
+                        };
+                        let meets_target = Blockchain::hash_to_u256(&hash)
+                            .map(|value| value < target)
+                            .unwrap_or(false);
+                        if meets_target {
+                            // Only the first winner reports; later finds are discarded
+                            if !found.swap(true, Ordering::SeqCst) {
+                                let _ = result_tx.send(Ok((nonce, hash)));
+                            }
+                            return;
+                        }
+                        nonce = nonce.wrapping_add(step);
+                    }
+                    hashes_total.fetch_add(NONCE_BATCH, Ordering::Relaxed);
+                    if duty_percent < 100 {
+                        throttle_pause(batch_start.elapsed(), duty_percent, found, cancel_flag);
+                    }
+                }
+            });
         }
+        drop(result_tx);
 
-        header.nonce = nonce;
-        let hash = compute_header_hash(&header)?;
-        
-        if hash.starts_with(&target_prefix) {
-            println!("[DEBUG] Mining: FOUND valid hash! nonce={}, hash_prefix={}", nonce, &hash[..20]);
-            // Update final hashrate before returning
-            let final_elapsed = last_hashrate_update.elapsed();
-            if final_elapsed.as_secs_f64() > 0.0 {
-                let final_rate = hashes_since_update as f64 / final_elapsed.as_secs_f64();
-                if let Some(ref hr) = hashrate {
-                    if let Ok(mut hr_lock) = hr.try_lock() {
-                        *hr_lock = final_rate;
+        // Aggregate the hashrate across workers (every 100ms) while waiting for a result.
+        // The rate is averaged over HASHRATE_WINDOW so throttled workers' sleeps don't
+        // make it swing between bursts and zero.
+        let mut window = HashrateWindow::new(Instant::now());
+        let mut report_hashrate = || {
+            let total = hashes_total.load(Ordering::Relaxed);
+            let Some(current_hashrate) = window.record(Instant::now(), total) else {
+                return;
+            };
+            if let Some(ref hr) = hashrate {
+                if let Ok(mut hr_lock) = hr.try_lock() {
+                    *hr_lock = current_hashrate;
+                }
+            }
+        };
+
+        loop {
+            match result_rx.recv_timeout(std::time::Duration::from_millis(100)) {
+                Ok(result) => {
+                    report_hashrate();
+                    return Some(result);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if cancel_flag.load(Ordering::Relaxed) {
+                        found.store(true, Ordering::SeqCst);
+                        return None;
                     }
+                    report_hashrate();
                 }
+                // Every worker exited without a result: cancelled
+                Err(mpsc::RecvTimeoutError::Disconnected) => return None,
             }
-            
-            println!("[DEBUG] Mining: Creating block with {} transactions", all_txs.len());
-            let block = Block {
-                header: header.clone(),
-                transactions: all_txs,
-                hash,
-            };
-            println!("[DEBUG] Mining: Returning mined block!");
-            return Ok(block);
         }
+    });
 
-        nonce += 1;
-        hashes_since_update += 1;
+    let (nonce, hash) = match outcome {
+        Some(result) => result?,
+        None => return Err(anyhow!("Mining cancelled due to new peer block")),
+    };
 
-        // ⏸️ 100,000 nonces, check cancellation flag and show progress
-        if nonce % 100_000 == 0 {
-            if cancel_flag.load(Ordering::Relaxed) {
-                return Err(anyhow!("Mining cancelled"));
-            }
+    println!("[DEBUG] Mining: FOUND valid hash! nonce={}, hash_prefix={}", nonce, &hash[..20]);
+    header.nonce = nonce;
 
-            // Update hashrate more frequently (every 100ms) for more accurate reporting
-            let elapsed = last_hashrate_update.elapsed();
-            if elapsed.as_millis() >= 100 {
-                let current_hashrate = hashes_since_update as f64 / elapsed.as_secs_f64();
+    println!("[DEBUG] Mining: Creating block with {} transactions", all_txs.len());
+    let block = Block {
+        header,
+        transactions: all_txs,
+        hash,
+    };
+    println!("[DEBUG] Mining: Returning mined block!");
+    Ok(block)
+}
 
-                // Update shared hashrate if provided
-                if let Some(ref hr) = hashrate {
-                    if let Ok(mut hr_lock) = hr.try_lock() {
-                        *hr_lock = current_hashrate;
-                    }
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                hashes_since_update = 0;
-                last_hashrate_update = std::time::Instant::now();
+    /// Roughly every other hash qualifies
+    const EASY_BITS: u32 = 0x207fffff;
+    /// Target of 1: no hash is ever below it
+    const UNREACHABLE_BITS: u32 = 0x03000001;
+
+    fn spend(prev_txid: &str, n: u8) -> Transaction {
+        Transaction {
+            txid: String::new(),
+            eth_hash: String::new(),
+            inputs: vec![crate::transaction::TransactionInput {
+                txid: prev_txid.to_string(),
+                vout: 0,
+                pubkey: String::new(),
+                signature: None,
+            }],
+            outputs: vec![crate::transaction::TransactionOutput::new(
+                format!("0x{:040x}", n),
+                U256::from(n),
+            )],
+            timestamp: 0,
+            valid_until: None,
+        }
+        .with_hashes()
+    }
+
+    #[test]
+    fn block_selection_prefers_fee_rate_and_keeps_parents_first() {
+        let parent = spend(&"aa".repeat(32), 1);
+        let child = spend(&parent.txid, 2);
+        let rich = spend(&"bb".repeat(32), 3);
+        let poor = spend(&"cc".repeat(32), 4);
+        let txs = vec![parent, child, rich, poor];
+        let tx_len = bincode::encode_to_vec(&txs[0], *crate::transaction::BINCODE_CONFIG)
+            .unwrap()
+            .len();
+        // Fee per byte: parent 10, child 1000, rich 500, poor 1
+        let fees: Vec<U256> = [10u64, 1000, 500, 1]
+            .iter()
+            .map(|rate| U256::from(*rate) * U256::from(tx_len))
+            .collect();
+
+        let select = |max_bytes| select_block_transactions(&txs, &fees, max_bytes);
+        assert_eq!(select(usize::MAX), [0, 1, 2, 3]);
+        // Room for two: the child pays best but waits for its parent; "rich" and then
+        // the parent take both slots
+        assert_eq!(select(tx_len * 2 + 2), [0, 2]);
+        // Room for three: the parent brings the child in ahead of "poor"
+        assert_eq!(select(tx_len * 3 + 3), [0, 1, 2]);
+        assert!(select(0).is_empty());
+    }
+
+    #[test]
+    fn parallel_mining_finds_valid_block() {
+        let block = mine_block_with_coinbase(
+            1,
+            "0".repeat(64),
+            EASY_BITS,
+            Vec::new(),
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+            Arc::new(AtomicBool::new(false)),
+            Some(Arc::new(std::sync::Mutex::new(0.0))),
+            4,
+            100,
+        )
+        .unwrap();
+
+        // Same validity as the single-threaded path: the stored hash is the header hash
+        // and is below the target of the header's bits
+        assert_eq!(compute_header_hash(&block.header).unwrap(), block.hash);
+        assert_eq!(block.header.difficulty, EASY_BITS);
+        let target = Blockchain::compact_to_target(EASY_BITS);
+        assert!(Blockchain::hash_to_u256(&block.hash).unwrap() < target);
+        assert_eq!(
+            block.header.merkle_root,
+            compute_merkle_root(&[block.transactions[0].txid.clone()])
+        );
+    }
+
+    #[test]
+    fn mining_recovers_from_hash_failures() {
+        let calls = AtomicU64::new(0);
+        // Fails the first few hashes, as many as a worker tolerates in a row
+        let flaky = |header: &BlockHeader| {
+            if calls.fetch_add(1, Ordering::SeqCst) < MAX_HASH_RETRIES as u64 {
+                Err(anyhow!("injected hash failure"))
+            } else {
+                compute_header_hash(header)
             }
+        };
+        let block = mine_with_hasher(
+            1,
+            "0".repeat(64),
+            EASY_BITS,
+            Vec::new(),
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+            Arc::new(AtomicBool::new(false)),
+            None,
+            1,
+            100,
+            &flaky,
+        )
+        .unwrap();
+
+        assert!(calls.load(Ordering::SeqCst) > MAX_HASH_RETRIES as u64);
+        assert_eq!(compute_header_hash(&block.header).unwrap(), block.hash);
+    }
+
+    #[test]
+    fn persistent_hash_failure_ends_round() {
+        let calls = AtomicU64::new(0);
+        let broken = |_: &BlockHeader| -> Result<String> {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(anyhow!("injected hash failure"))
+        };
+        let started = Instant::now();
+        let result = mine_with_hasher(
+            1,
+            "0".repeat(64),
+            EASY_BITS,
+            Vec::new(),
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+            Arc::new(AtomicBool::new(false)),
+            None,
+            2,
+            100,
+            &broken,
+        );
+
+        assert!(result.is_err());
+        // Each worker backs off and stops after its retries instead of spinning
+        assert!(calls.load(Ordering::SeqCst) <= 2 * (MAX_HASH_RETRIES as u64 + 1));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn cancelled_mining_returns_error() {
+        let result = mine_block_with_coinbase(
+            1,
+            "0".repeat(64),
+            UNREACHABLE_BITS,
+            Vec::new(),
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+            Arc::new(AtomicBool::new(true)),
+            None,
+            4,
+            100,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn mined_block_passes_chain_validation() {
+        let dir = std::env::temp_dir().join(format!(
+            "astram-consensus-{}-{}",
+            std::process::id(),
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let mut bc = Blockchain::new(dir.to_str().unwrap()).unwrap();
+
+        let block = mine_block_with_coinbase(
+            0,
+            "0".repeat(64),
+            EASY_BITS,
+            Vec::new(),
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+            Arc::new(AtomicBool::new(false)),
+            None,
+            2,
+            100,
+        )
+        .unwrap();
+        bc.validate_and_insert_block(&block).unwrap();
+        assert_eq!(bc.chain_tip.as_deref(), Some(block.hash.as_str()));
+
+        // The single-threaded helper agrees on what a valid hash is
+        let mut header = block.header.clone();
+        header.nonce = 0;
+        let (nonce, hash) = find_valid_nonce(&mut header, EASY_BITS).unwrap();
+        assert_eq!(header.nonce, nonce);
+        let target = Blockchain::compact_to_target(EASY_BITS);
+        assert!(Blockchain::hash_to_u256(&hash).unwrap() < target);
+
+        // Bits that encode a zero target can never be met
+        assert!(find_valid_nonce(&mut header, 0x1d000000).is_err());
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// Reported hashrate of a worker that hashes NONCE_BATCH in `batch` and then idles
+    /// for its duty cycle, on a simulated clock
+    fn simulated_hashrate(duty_percent: u32, batch: Duration) -> f64 {
+        let start = Instant::now();
+        let cycle = batch + throttle_idle(batch, duty_percent);
+        let mut window = HashrateWindow::new(start);
+        let mut rate = 0.0;
+        for n in 1..=40u32 {
+            rate = window
+                .record(start + cycle * n, NONCE_BATCH * n as u64)
+                .unwrap();
         }
+        rate
+    }
+
+    #[test]
+    fn throttling_reduces_measured_hashrate() {
+        let batch = Duration::from_millis(10);
+        assert_eq!(throttle_idle(batch, 20), Duration::from_millis(40));
+        assert_eq!(throttle_idle(batch, 100), Duration::ZERO);
+
+        // A 20% duty cycle reports a fifth of full speed
+        let full = simulated_hashrate(100, batch);
+        let throttled = simulated_hashrate(20, batch);
+        assert!((full - 1_000_000.0).abs() < 1.0, "full {full:.0} H/s");
+        assert!(
+            (throttled - 200_000.0).abs() < 1.0,
+            "throttled {throttled:.0} H/s"
+        );
+
+        // No time passed, no rate
+        let start = Instant::now();
+        assert_eq!(HashrateWindow::new(start).record(start, 5), None);
     }
 }
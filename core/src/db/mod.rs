@@ -9,6 +9,9 @@ use std::path::Path;
   i:<height> -> block_hash (utf8)
   t:<txid> -> serialized tx (bincode)
   u:<txid>:<vout> -> serialized UTXO (bincode)
+  w:<block_hash> -> cumulative chain work (u64, big-endian)
+  x:<block_hash> -> UTXOs spent by the block (bincode Vec<Utxo>, undo data for rollback)
+  m:<txid> -> pending mempool transaction saved at shutdown (bincode)
   tip -> block_hash
 */
 
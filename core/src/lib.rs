@@ -9,7 +9,10 @@ pub mod crypto;
 pub mod db;
 pub mod network;
 pub mod security;
+pub mod sigcache;
 pub mod transaction;
+pub mod txbuilder;
+pub mod units;
 pub mod utxo;
 pub mod wallet;
 
@@ -7,6 +7,10 @@ use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Security constants
 pub const MAX_TX_SIZE: usize = 100_000; // 100KB max transaction size
+pub const MAX_BLOCK_BYTES: usize = 1_000_000; // 1MB max encoded block (consensus)
+/// Room kept free in a block for the header, coinbase and framing when filling it with
+/// mempool transactions
+pub const BLOCK_RESERVED_BYTES: usize = 2_000;
 pub const MIN_OUTPUT_VALUE: u64 = 1_000_000_000_000; // 1 Twei (0.000001 ASRM) minimum to prevent dust
 pub const MAX_TX_INPUTS: usize = 1000; // Prevent huge transactions
 pub const MAX_TX_OUTPUTS: usize = 1000;
@@ -59,6 +63,46 @@ impl BlockFailureReason {
             Self::Other => "other",
         }
     }
+
+    /// Reason carried by a `validate_and_insert_block` error, if the block itself was
+    /// at fault. Local failures (database, I/O, encoding) carry none, so a peer is never
+    /// blamed for them.
+    pub fn of(error: &anyhow::Error) -> Option<Self> {
+        error
+            .downcast_ref::<BlockRejection>()
+            .map(|rejection| rejection.reason)
+    }
+
+    /// Whether the failure depends on the UTXO set the block was checked against. That
+    /// is the current tip's, so a block on a side branch can fail this way and still be
+    /// valid on its own branch.
+    pub fn depends_on_utxo_set(&self) -> bool {
+        matches!(self, Self::UtxoNotFound)
+    }
+}
+
+/// A block (or one of its transactions) failed a consensus or policy check
+#[derive(Debug)]
+pub struct BlockRejection {
+    pub reason: BlockFailureReason,
+    pub message: String,
+}
+
+impl std::fmt::Display for BlockRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for BlockRejection {}
+
+/// Error for a block rejected for `reason`, recoverable with `BlockFailureReason::of`
+pub fn reject(reason: BlockFailureReason, message: impl Into<String>) -> anyhow::Error {
+    BlockRejection {
+        reason,
+        message: message.into(),
+    }
+    .into()
 }
 
 /// Global statistics for block validation failures
@@ -128,6 +172,31 @@ impl ValidationStats {
         counter.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Zero every counter, e.g. once an incident has been dealt with
+    pub fn reset(&self) {
+        for counter in [
+            &self.hash_mismatch,
+            &self.invalid_pow,
+            &self.difficulty_out_of_range,
+            &self.merkle_root_mismatch,
+            &self.timestamp_too_old,
+            &self.timestamp_too_future,
+            &self.previous_not_found,
+            &self.empty_block,
+            &self.invalid_coinbase,
+            &self.signature_failure,
+            &self.utxo_not_found,
+            &self.utxo_ownership_failure,
+            &self.duplicate_input,
+            &self.insufficient_fee,
+            &self.checkpoint_violation,
+            &self.security_constraint,
+            &self.other,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
     pub fn get_stats(&self) -> Vec<(String, u64)> {
         vec![
             (
@@ -210,27 +279,36 @@ pub fn validate_transaction_security(tx: &Transaction, block_timestamp: i64) ->
         .map_err(|e| anyhow!("failed to serialize tx: {}", e))?;
 
     if tx_bytes.len() > MAX_TX_SIZE {
-        return Err(anyhow!(
-            "transaction too large: {} bytes (max {})",
-            tx_bytes.len(),
-            MAX_TX_SIZE
+        return Err(reject(
+            BlockFailureReason::SecurityConstraint,
+            format!(
+                "transaction too large: {} bytes (max {})",
+                tx_bytes.len(),
+                MAX_TX_SIZE
+            ),
         ));
     }
 
     // 2. Check input/output count (prevent resource exhaustion)
     if tx.inputs.len() > MAX_TX_INPUTS {
-        return Err(anyhow!(
-            "too many inputs: {} (max {})",
-            tx.inputs.len(),
-            MAX_TX_INPUTS
+        return Err(reject(
+            BlockFailureReason::SecurityConstraint,
+            format!(
+                "too many inputs: {} (max {})",
+                tx.inputs.len(),
+                MAX_TX_INPUTS
+            ),
         ));
     }
 
     if tx.outputs.len() > MAX_TX_OUTPUTS {
-        return Err(anyhow!(
-            "too many outputs: {} (max {})",
-            tx.outputs.len(),
-            MAX_TX_OUTPUTS
+        return Err(reject(
+            BlockFailureReason::SecurityConstraint,
+            format!(
+                "too many outputs: {} (max {})",
+                tx.outputs.len(),
+                MAX_TX_OUTPUTS
+            ),
         ));
     }
 
@@ -238,19 +316,36 @@ pub fn validate_transaction_security(tx: &Transaction, block_timestamp: i64) ->
     let current_time = chrono::Utc::now().timestamp();
 
     if tx.timestamp > current_time + MAX_FUTURE_TIMESTAMP {
-        return Err(anyhow!(
-            "transaction timestamp too far in future: {} > {}",
-            tx.timestamp,
-            current_time + MAX_FUTURE_TIMESTAMP
+        return Err(reject(
+            BlockFailureReason::TimestampTooFuture,
+            format!(
+                "transaction timestamp too far in future: {} > {}",
+                tx.timestamp,
+                current_time + MAX_FUTURE_TIMESTAMP
+            ),
         ));
     }
 
     // Transaction shouldn't be newer than containing block
     if tx.timestamp > block_timestamp {
-        return Err(anyhow!(
-            "transaction timestamp ({}) exceeds block timestamp ({})",
-            tx.timestamp,
-            block_timestamp
+        return Err(reject(
+            BlockFailureReason::SecurityConstraint,
+            format!(
+                "transaction timestamp ({}) exceeds block timestamp ({})",
+                tx.timestamp, block_timestamp
+            ),
+        ));
+    }
+
+    // A transaction can't be mined after its deadline
+    if tx.is_expired_at(block_timestamp) {
+        return Err(reject(
+            BlockFailureReason::SecurityConstraint,
+            format!(
+                "transaction expired: valid until {}, block timestamp {}",
+                tx.valid_until.unwrap_or_default(),
+                block_timestamp
+            ),
         ));
     }
 
@@ -258,11 +353,14 @@ pub fn validate_transaction_security(tx: &Transaction, block_timestamp: i64) ->
     if !tx.inputs.is_empty() {
         for (idx, out) in tx.outputs.iter().enumerate() {
             if out.amount() < U256::from(MIN_OUTPUT_VALUE) {
-                return Err(anyhow!(
-                    "output {} is dust: {} (minimum {})",
-                    idx,
-                    out.amount(),
-                    MIN_OUTPUT_VALUE
+                return Err(reject(
+                    BlockFailureReason::SecurityConstraint,
+                    format!(
+                        "output {} is dust: {} (minimum {})",
+                        idx,
+                        out.amount(),
+                        MIN_OUTPUT_VALUE
+                    ),
                 ));
             }
         }
@@ -271,7 +369,10 @@ pub fn validate_transaction_security(tx: &Transaction, block_timestamp: i64) ->
     // 5. Validate no empty addresses
     for (idx, out) in tx.outputs.iter().enumerate() {
         if out.to.is_empty() {
-            return Err(anyhow!("output {} has empty address", idx));
+            return Err(reject(
+                BlockFailureReason::SecurityConstraint,
+                format!("output {} has empty address", idx),
+            ));
         }
     }
 
@@ -282,38 +383,52 @@ pub fn validate_transaction_security(tx: &Transaction, block_timestamp: i64) ->
 pub fn validate_block_security(block: &Block) -> Result<()> {
     // 1. Block must have at least coinbase transaction
     if block.transactions.is_empty() {
-        return Err(anyhow!("block has no transactions"));
+        return Err(reject(
+            BlockFailureReason::EmptyBlock,
+            "block has no transactions".to_string(),
+        ));
     }
 
     // 2. Validate block timestamp
     let current_time = chrono::Utc::now().timestamp();
 
     if block.header.timestamp > current_time + MAX_FUTURE_TIMESTAMP {
-        return Err(anyhow!(
-            "block timestamp too far in future: {} > {}",
-            block.header.timestamp,
-            current_time + MAX_FUTURE_TIMESTAMP
+        return Err(reject(
+            BlockFailureReason::TimestampTooFuture,
+            format!(
+                "block timestamp too far in future: {} > {}",
+                block.header.timestamp,
+                current_time + MAX_FUTURE_TIMESTAMP
+            ),
         ));
     }
 
     // Prevent pre-genesis blocks
     if block.header.timestamp < GENESIS_TIMESTAMP {
-        return Err(anyhow!(
-            "block timestamp predates genesis: {} < {}",
-            block.header.timestamp,
-            GENESIS_TIMESTAMP
+        return Err(reject(
+            BlockFailureReason::TimestampTooOld,
+            format!(
+                "block timestamp predates genesis: {} < {}",
+                block.header.timestamp, GENESIS_TIMESTAMP
+            ),
         ));
     }
 
     // 3. Coinbase must be first and only coinbase
     let coinbase = &block.transactions[0];
     if !coinbase.inputs.is_empty() {
-        return Err(anyhow!("first transaction is not coinbase"));
+        return Err(reject(
+            BlockFailureReason::InvalidCoinbase,
+            "first transaction is not coinbase".to_string(),
+        ));
     }
 
     for (idx, tx) in block.transactions.iter().enumerate().skip(1) {
         if tx.inputs.is_empty() {
-            return Err(anyhow!("non-first transaction {} is coinbase-like", idx));
+            return Err(reject(
+                BlockFailureReason::InvalidCoinbase,
+                format!("non-first transaction {} is coinbase-like", idx),
+            ));
         }
     }
 
@@ -441,6 +556,7 @@ mod tests {
             inputs,
             outputs: vec![TransactionOutput::new("addr".to_string(), U256::from(100))],
             timestamp: 0,
+            valid_until: None,
         };
 
         let result = validate_transaction_security(&tx, 100);
@@ -448,6 +564,97 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("too large"));
     }
 
+    #[test]
+    fn test_transaction_expiry() {
+        use crate::transaction::{Transaction, TransactionInput, TransactionOutput};
+
+        let mut tx = Transaction {
+            txid: String::new(),
+            eth_hash: String::new(),
+            inputs: vec![TransactionInput {
+                txid: "ab".repeat(32),
+                vout: 0,
+                pubkey: String::new(),
+                signature: None,
+            }],
+            outputs: vec![TransactionOutput::new(
+                "addr".to_string(),
+                U256::from(MIN_OUTPUT_VALUE),
+            )],
+            timestamp: 100,
+            valid_until: Some(200),
+        };
+
+        // Mineable up to and including the deadline, not after it
+        assert!(validate_transaction_security(&tx, 150).is_ok());
+        assert!(validate_transaction_security(&tx, 200).is_ok());
+        let err = validate_transaction_security(&tx, 201).unwrap_err();
+        assert!(err.to_string().contains("expired"), "{}", err);
+
+        // Without a deadline it never expires
+        tx.valid_until = None;
+        assert!(validate_transaction_security(&tx, 201).is_ok());
+    }
+
+    #[test]
+    fn test_validation_stats_reset() {
+        let stats = ValidationStats::new();
+        stats.increment(BlockFailureReason::InvalidPoW);
+        stats.increment(BlockFailureReason::Other);
+        let total = |stats: &ValidationStats| -> u64 {
+            stats.get_stats().iter().map(|(_, count)| count).sum()
+        };
+        assert_eq!(total(&stats), 2);
+
+        stats.reset();
+        assert_eq!(total(&stats), 0);
+    }
+
+    #[test]
+    fn test_failure_reason_of_error() {
+        let rejected = reject(BlockFailureReason::InvalidPoW, "invalid PoW");
+        assert_eq!(
+            BlockFailureReason::of(&rejected),
+            Some(BlockFailureReason::InvalidPoW)
+        );
+        // Context added on the way up doesn't hide the reason
+        let wrapped = rejected.context("while connecting block 7");
+        assert_eq!(
+            BlockFailureReason::of(&wrapped),
+            Some(BlockFailureReason::InvalidPoW)
+        );
+        // Local failures carry no reason, whatever their message says
+        let local = anyhow!("referenced utxo not found: database is locked");
+        assert_eq!(BlockFailureReason::of(&local), None);
+
+        let mut block = Block {
+            header: crate::block::BlockHeader {
+                index: 1,
+                previous_hash: "0".repeat(64),
+                merkle_root: String::new(),
+                timestamp: GENESIS_TIMESTAMP - 1,
+                nonce: 0,
+                difficulty: 0,
+            },
+            transactions: vec![],
+            hash: String::new(),
+        };
+        let err = validate_block_security(&block).unwrap_err();
+        assert_eq!(
+            BlockFailureReason::of(&err),
+            Some(BlockFailureReason::EmptyBlock)
+        );
+        block.transactions.push(Transaction::coinbase(
+            "0x0000000000000000000000000000000000000001",
+            U256::from(50u64),
+        ));
+        let err = validate_block_security(&block).unwrap_err();
+        assert_eq!(
+            BlockFailureReason::of(&err),
+            Some(BlockFailureReason::TimestampTooOld)
+        );
+    }
+
     #[test]
     fn test_reorg_depth_validation() {
         // Safe reorganization
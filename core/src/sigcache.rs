@@ -0,0 +1,170 @@
+// Remembers transactions whose signatures already verified, so block validation and
+// reorg replay skip the ECDSA work mempool admission did
+use crate::transaction::{BINCODE_CONFIG, Transaction};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// Verified transactions remembered by `SIGNATURE_CACHE`
+pub const SIGNATURE_CACHE_CAPACITY: usize = 50_000;
+
+pub static SIGNATURE_CACHE: Lazy<SignatureCache> =
+    Lazy::new(|| SignatureCache::new(SIGNATURE_CACHE_CAPACITY));
+
+/// Bounded LRU set of transactions with valid signatures. Entries are keyed by a
+/// digest of the whole encoded transaction: the txid leaves out pubkeys and
+/// signatures, so a malleated signature (or a copied txid field) never hits an entry
+/// made for the original.
+pub struct SignatureCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+#[derive(Default)]
+struct LruState {
+    /// Key -> tick of its last use
+    last_used: HashMap<[u8; 32], u64>,
+    /// Tick -> key, oldest first
+    by_tick: BTreeMap<u64, [u8; 32]>,
+    tick: u64,
+}
+
+impl SignatureCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(LruState::default()),
+        }
+    }
+
+    fn key(tx: &Transaction) -> Result<[u8; 32]> {
+        let bytes = bincode::encode_to_vec(tx, *BINCODE_CONFIG)?;
+        Ok(Sha256::digest(&bytes).into())
+    }
+
+    /// `tx.verify_signatures()`, answered from the cache when this exact transaction
+    /// verified before. Only successes are remembered.
+    pub fn verify(&self, tx: &Transaction) -> Result<bool> {
+        let key = Self::key(tx)?;
+        if self.touch(&key) {
+            return Ok(true);
+        }
+        let valid = tx.verify_signatures()?;
+        if valid {
+            self.insert(key);
+        }
+        Ok(valid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().last_used.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Mark `key` most recently used; false if it isn't cached
+    fn touch(&self, key: &[u8; 32]) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(old_tick) = state.last_used.get(key).copied() else {
+            return false;
+        };
+        state.tick += 1;
+        let tick = state.tick;
+        state.by_tick.remove(&old_tick);
+        state.by_tick.insert(tick, *key);
+        state.last_used.insert(*key, tick);
+        true
+    }
+
+    fn insert(&self, key: [u8; 32]) {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        if let Some(old_tick) = state.last_used.insert(key, tick) {
+            state.by_tick.remove(&old_tick);
+        }
+        state.by_tick.insert(tick, key);
+        while state.last_used.len() > self.capacity {
+            let Some((_, oldest)) = state.by_tick.pop_first() else {
+                break;
+            };
+            state.last_used.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::WalletKeypair;
+    use crate::transaction::{TransactionInput, TransactionOutput};
+    use primitive_types::U256;
+
+    fn signed_tx(n: u8) -> Transaction {
+        let mut tx = Transaction {
+            txid: String::new(),
+            eth_hash: String::new(),
+            inputs: vec![TransactionInput {
+                txid: format!("{:02x}", n).repeat(32),
+                vout: 0,
+                pubkey: String::new(),
+                signature: None,
+            }],
+            outputs: vec![TransactionOutput::new("0xabc".into(), U256::from(n))],
+            timestamp: 0,
+            valid_until: None,
+        };
+        tx.sign(&WalletKeypair::new()).unwrap();
+        tx.with_hashes()
+    }
+
+    #[test]
+    fn malleated_copies_miss_the_cache() {
+        let cache = SignatureCache::new(10);
+        let tx = signed_tx(1);
+        assert!(cache.verify(&tx).unwrap());
+        assert_eq!(cache.len(), 1);
+        assert!(cache.verify(&tx).unwrap());
+        assert_eq!(cache.len(), 1);
+
+        // Same txid, different signature: verified from scratch and rejected
+        let mut forged = tx.clone();
+        let sig = forged.inputs[0].signature.as_mut().unwrap();
+        let flipped = if sig.ends_with('0') { '1' } else { '0' };
+        sig.pop();
+        sig.push(flipped);
+        assert_eq!(forged.compute_txid().unwrap(), tx.txid);
+        assert!(!cache.verify(&forged).unwrap());
+
+        // A copied txid field over different outputs doesn't hit either
+        let mut redirected = tx.clone();
+        redirected.outputs[0] = TransactionOutput::new("0xdef".into(), U256::from(1));
+        assert!(!cache.verify(&redirected).unwrap());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        let cache = SignatureCache::new(2);
+        let (a, b, c) = (signed_tx(1), signed_tx(2), signed_tx(3));
+        cache.verify(&a).unwrap();
+        cache.verify(&b).unwrap();
+        // Using "a" again leaves "b" as the oldest
+        cache.verify(&a).unwrap();
+        cache.verify(&c).unwrap();
+
+        assert_eq!(cache.len(), 2);
+        let state = cache.state.lock().unwrap();
+        let cached = |tx: &Transaction| {
+            state
+                .last_used
+                .contains_key(&SignatureCache::key(tx).unwrap())
+        };
+        assert!(cached(&a) && cached(&c));
+        assert!(!cached(&b));
+    }
+}
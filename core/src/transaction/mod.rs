@@ -1,5 +1,8 @@
 use anyhow::Result;
-use bincode::error::EncodeError;
+use bincode::de::Decoder;
+use bincode::de::read::Reader;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
 use bincode::{Decode, Encode, config};
 use hex;
 use once_cell::sync::Lazy;
@@ -8,6 +11,15 @@ use sha2::{Digest, Sha256};
 
 pub static BINCODE_CONFIG: Lazy<config::Configuration> = Lazy::new(|| config::standard());
 
+/// Transactions without `valid_until`; encoded and hashed exactly as before versions existed
+pub const TX_VERSION_LEGACY: u32 = 1;
+/// Transactions with a `valid_until` deadline
+pub const TX_VERSION_EXPIRY: u32 = 2;
+
+/// First byte of a versioned transaction encoding. A legacy encoding starts with the
+/// txid's length, and bincode never begins a varint with 0xff, so the two can't clash.
+const VERSIONED_TX_MARKER: u8 = 0xff;
+
 /// Input: previous txid and vout index
 #[derive(Encode, Decode, Debug, Clone)]
 pub struct TransactionInput {
@@ -43,15 +55,74 @@ impl TransactionOutput {
 }
 
 /// Transaction: inputs / outputs / timestamp / txid
-#[derive(Encode, Decode, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub struct Transaction {
     pub txid: String,     // UTXO transaction tracking (SHA256 double hash)
     pub eth_hash: String, // EVM transaction hash (Keccak256, 0x prefix)
     pub inputs: Vec<TransactionInput>,
     pub outputs: Vec<TransactionOutput>,
     pub timestamp: i64,
+    /// Unix time after which the transaction can't be mined or enter the mempool.
+    /// Setting it makes this a `TX_VERSION_EXPIRY` transaction.
+    pub valid_until: Option<i64>,
+}
+
+// Legacy transactions keep their original layout so stored blocks and older peers
+// still read them; versioned ones are the marker and version, the same fields, then
+// the version's extra fields.
+impl Encode for Transaction {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        if self.valid_until.is_some() {
+            VERSIONED_TX_MARKER.encode(encoder)?;
+            TX_VERSION_EXPIRY.encode(encoder)?;
+        }
+        self.txid.encode(encoder)?;
+        self.eth_hash.encode(encoder)?;
+        self.inputs.encode(encoder)?;
+        self.outputs.encode(encoder)?;
+        self.timestamp.encode(encoder)?;
+        if let Some(valid_until) = self.valid_until {
+            valid_until.encode(encoder)?;
+        }
+        Ok(())
+    }
 }
 
+impl<Context> Decode<Context> for Transaction {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let versioned = decoder.reader().peek_read(1) == Some(&[VERSIONED_TX_MARKER][..]);
+        if versioned {
+            u8::decode(decoder)?;
+            let version = u32::decode(decoder)?;
+            if version != TX_VERSION_EXPIRY {
+                return Err(DecodeError::OtherString(format!(
+                    "unsupported transaction version {}",
+                    version
+                )));
+            }
+        }
+        let txid = String::decode(decoder)?;
+        let eth_hash = String::decode(decoder)?;
+        let inputs = Vec::<TransactionInput>::decode(decoder)?;
+        let outputs = Vec::<TransactionOutput>::decode(decoder)?;
+        let timestamp = i64::decode(decoder)?;
+        let valid_until = if versioned {
+            Some(i64::decode(decoder)?)
+        } else {
+            None
+        };
+        Ok(Transaction {
+            txid,
+            eth_hash,
+            inputs,
+            outputs,
+            timestamp,
+            valid_until,
+        })
+    }
+}
+bincode::impl_borrow_decode!(Transaction);
+
 impl Transaction {
     pub fn coinbase(to: &str, amount: U256) -> Self {
         let output = TransactionOutput::new(to.to_string(), amount);
@@ -61,10 +132,24 @@ impl Transaction {
             inputs: vec![],
             outputs: vec![output],
             timestamp: chrono::Utc::now().timestamp(),
+            valid_until: None,
         };
         tx.with_hashes()
     }
 
+    pub fn version(&self) -> u32 {
+        if self.valid_until.is_some() {
+            TX_VERSION_EXPIRY
+        } else {
+            TX_VERSION_LEGACY
+        }
+    }
+
+    /// Whether `time` (a block timestamp, or now for the mempool) is past `valid_until`
+    pub fn is_expired_at(&self, time: i64) -> bool {
+        self.valid_until.is_some_and(|until| time > until)
+    }
+
     pub fn serialize_for_hash(&self) -> Result<Vec<u8>, EncodeError> {
         let inputs_for_hash: Vec<_> = self
             .inputs
@@ -72,10 +157,23 @@ impl Transaction {
             .map(|i| (i.txid.clone(), i.vout)) // omit pubkey
             .collect();
 
-        Ok(bincode::encode_to_vec(
-            &(&inputs_for_hash, &self.outputs, &self.timestamp),
-            *BINCODE_CONFIG,
-        )?)
+        // Legacy transactions keep the hash (and so the txid and signatures) they always had
+        match self.valid_until {
+            None => Ok(bincode::encode_to_vec(
+                &(&inputs_for_hash, &self.outputs, &self.timestamp),
+                *BINCODE_CONFIG,
+            )?),
+            Some(valid_until) => Ok(bincode::encode_to_vec(
+                &(
+                    TX_VERSION_EXPIRY,
+                    &inputs_for_hash,
+                    &self.outputs,
+                    &self.timestamp,
+                    valid_until,
+                ),
+                *BINCODE_CONFIG,
+            )?),
+        }
     }
 
     /// Calculate txid for UTXO transaction tracking (Bitcoin style: SHA256 double hash)
@@ -95,6 +193,16 @@ impl Transaction {
         Ok(format!("0x{}", hex::encode(hash)))
     }
 
+    /// Total value of all outputs, or None if it overflows U256
+    pub fn output_total(&self) -> Option<U256> {
+        crate::units::checked_sum(self.outputs.iter().map(|out| out.amount()))
+    }
+
+    /// Fee left over from `input_total`, or None if the outputs overflow or exceed it
+    pub fn fee(&self, input_total: U256) -> Option<U256> {
+        input_total.checked_sub(self.output_total()?)
+    }
+
     /// Set both txid and eth_hash (recommended)
     pub fn with_hashes(mut self) -> Self {
         if let Ok(txid) = self.compute_txid() {
@@ -189,7 +297,71 @@ fn sign_and_verify() {
         inputs: vec![inp],
         outputs: vec![out],
         timestamp: chrono::Utc::now().timestamp(),
+        valid_until: None,
     };
     tx2.sign(&keypair).unwrap();
     assert!(tx2.verify_signatures().unwrap());
 }
+
+#[test]
+fn legacy_encoding_and_txid_are_unchanged_by_versioning() {
+    #[derive(Encode)]
+    struct LegacyTransaction {
+        txid: String,
+        eth_hash: String,
+        inputs: Vec<TransactionInput>,
+        outputs: Vec<TransactionOutput>,
+        timestamp: i64,
+    }
+
+    let mut tx = Transaction {
+        txid: String::new(),
+        eth_hash: String::new(),
+        inputs: vec![TransactionInput {
+            txid: "ab".repeat(32),
+            vout: 1,
+            pubkey: String::new(),
+            signature: None,
+        }],
+        outputs: vec![TransactionOutput::new("alice".to_string(), U256::from(10))],
+        timestamp: 1_700_000_000,
+        valid_until: None,
+    }
+    .with_hashes();
+    let legacy = LegacyTransaction {
+        txid: tx.txid.clone(),
+        eth_hash: tx.eth_hash.clone(),
+        inputs: tx.inputs.clone(),
+        outputs: tx.outputs.clone(),
+        timestamp: tx.timestamp,
+    };
+    let legacy_hash = bincode::encode_to_vec(
+        &(vec![("ab".repeat(32), 1u32)], &tx.outputs, &tx.timestamp),
+        *BINCODE_CONFIG,
+    )
+    .unwrap();
+    assert_eq!(tx.serialize_for_hash().unwrap(), legacy_hash);
+    let blob = bincode::encode_to_vec(&legacy, *BINCODE_CONFIG).unwrap();
+    assert_eq!(bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap(), blob);
+    let (decoded, _): (Transaction, usize) =
+        bincode::decode_from_slice(&blob, *BINCODE_CONFIG).unwrap();
+    assert_eq!(decoded.version(), TX_VERSION_LEGACY);
+    assert_eq!(decoded.txid, tx.txid);
+
+    // A deadline bumps the version, changes the txid and survives a round trip, also
+    // inside a list as transactions are stored in blocks
+    let legacy_txid = tx.txid.clone();
+    tx.valid_until = Some(1_700_003_600);
+    let tx = tx.with_hashes();
+    assert_ne!(tx.txid, legacy_txid);
+    let blob = bincode::encode_to_vec(vec![tx.clone(), decoded], *BINCODE_CONFIG).unwrap();
+    let (txs, _): (Vec<Transaction>, usize) =
+        bincode::decode_from_slice(&blob, *BINCODE_CONFIG).unwrap();
+    assert_eq!(txs[0].version(), TX_VERSION_EXPIRY);
+    assert_eq!(txs[0].valid_until, Some(1_700_003_600));
+    assert_eq!(txs[0].txid, tx.txid);
+    assert_eq!((txs[1].valid_until, &txs[1].txid), (None, &legacy_txid));
+
+    assert!(!tx.is_expired_at(1_700_003_600));
+    assert!(tx.is_expired_at(1_700_003_601));
+}
@@ -0,0 +1,259 @@
+// Shared transaction construction: input selection, fee settlement and change
+use crate::config::calculate_min_fee;
+use crate::security::MIN_OUTPUT_VALUE;
+use crate::transaction::{BINCODE_CONFIG, Transaction, TransactionInput, TransactionOutput};
+use crate::units::{checked_sum, format_amount};
+use crate::utxo::Utxo;
+use anyhow::{Result, anyhow};
+use primitive_types::U256;
+
+/// Passes of fee-then-size refinement before settling for any fee that covers the size
+const MAX_FEE_PASSES: usize = 4;
+
+/// How `build_transaction` decides the fee
+pub enum FeePolicy<'a> {
+    /// Pay exactly this much (e.g. gasPrice x gasLimit from an Ethereum wallet). Fails
+    /// if it is below `calculate_min_fee` for the signed size.
+    Fixed(U256),
+    /// Pay `fee_for(size)` for the encoded size of the signed transaction
+    BySize(&'a dyn Fn(usize) -> U256),
+}
+
+impl FeePolicy<'_> {
+    fn fee_for(&self, size: usize) -> U256 {
+        match self {
+            FeePolicy::Fixed(fee) => *fee,
+            FeePolicy::BySize(fee_for) => fee_for(size),
+        }
+    }
+}
+
+/// Spend `utxos` (in the given order, as few as needed) to pay `outputs` plus the fee
+/// `fee_policy` asks for, returning what is left to `change_address`. Change below
+/// `MIN_OUTPUT_VALUE` would be rejected as dust, so it is added to the fee instead.
+///
+/// `sign` fills in pubkeys and signatures and the hashes; it runs on every candidate
+/// so the fee is judged on exactly the bytes the node will see.
+pub fn build_transaction<S>(
+    utxos: &[Utxo],
+    outputs: Vec<TransactionOutput>,
+    fee_policy: &FeePolicy,
+    change_address: &str,
+    sign: S,
+) -> Result<Transaction>
+where
+    S: Fn(Transaction) -> Result<Transaction>,
+{
+    let output_total = checked_sum(outputs.iter().map(|out| out.amount()))
+        .ok_or_else(|| anyhow!("output sum overflows"))?;
+
+    let mut input_total = U256::zero();
+    let mut fee = U256::zero();
+    for (count, utxo) in utxos.iter().enumerate() {
+        input_total = input_total
+            .checked_add(utxo.amount())
+            .ok_or_else(|| anyhow!("input sum overflows"))?;
+        fee = fee_policy.fee_for(estimated_signed_size(count + 1, outputs.len()));
+        if input_total < output_total.saturating_add(fee) {
+            continue;
+        }
+        if let Some(tx) = settle_fee(
+            &utxos[..=count],
+            input_total,
+            &outputs,
+            output_total,
+            fee_policy,
+            change_address,
+            &sign,
+        )? {
+            return Ok(tx);
+        }
+    }
+
+    Err(anyhow!(
+        "Insufficient funds: have {}, need {} (amount {} + fee {})",
+        format_amount(input_total),
+        format_amount(output_total.saturating_add(fee)),
+        format_amount(output_total),
+        format_amount(fee)
+    ))
+}
+
+/// Sign candidates spending `selected` until the fee matches the signed size. None if
+/// the inputs can't cover the fee that size needs.
+fn settle_fee<S>(
+    selected: &[Utxo],
+    input_total: U256,
+    outputs: &[TransactionOutput],
+    output_total: U256,
+    fee_policy: &FeePolicy,
+    change_address: &str,
+    sign: &S,
+) -> Result<Option<Transaction>>
+where
+    S: Fn(Transaction) -> Result<Transaction>,
+{
+    let inputs: Vec<TransactionInput> = selected
+        .iter()
+        .map(|utxo| TransactionInput {
+            txid: utxo.txid.clone(),
+            vout: utxo.vout,
+            pubkey: String::new(),
+            signature: None,
+        })
+        .collect();
+
+    let mut fee = fee_policy.fee_for(estimated_signed_size(selected.len(), outputs.len()));
+    let mut passes = 0;
+    loop {
+        let Some(change) = input_total
+            .checked_sub(output_total)
+            .and_then(|rest| rest.checked_sub(fee))
+        else {
+            return Ok(None);
+        };
+
+        let mut tx_outputs = outputs.to_vec();
+        if change >= U256::from(MIN_OUTPUT_VALUE) {
+            tx_outputs.push(TransactionOutput::new(change_address.to_string(), change));
+        }
+        let tx = sign(Transaction {
+            txid: String::new(),
+            eth_hash: String::new(),
+            inputs: inputs.clone(),
+            outputs: tx_outputs,
+            timestamp: chrono::Utc::now().timestamp(),
+            valid_until: None,
+        })?;
+
+        // The node charges by the encoded size of exactly these bytes
+        let size = bincode::encode_to_vec(&tx, *BINCODE_CONFIG)
+            .map_err(|e| anyhow!("Failed to serialize transaction: {}", e))?
+            .len();
+
+        if let FeePolicy::Fixed(_) = fee_policy {
+            let min_fee = calculate_min_fee(size);
+            if fee < min_fee {
+                return Err(anyhow!(
+                    "Insufficient fee: provided {}, but {} bytes need {}",
+                    format_amount(fee),
+                    size,
+                    format_amount(min_fee)
+                ));
+            }
+            return Ok(Some(tx));
+        }
+
+        let required = fee_policy.fee_for(size);
+        passes += 1;
+        // A new fee can shift the change amount's encoding by a byte or two, so after a
+        // few passes accept any fee that covers the size instead of chasing it
+        if required == fee || (required < fee && passes >= MAX_FEE_PASSES) {
+            return Ok(Some(tx));
+        }
+        fee = required;
+    }
+}
+
+/// First guess at the size of a signed transaction with `inputs` inputs paying
+/// `outputs` outputs plus change, before one has been encoded
+/// - Base: ~150 bytes (txid, eth_hash, timestamp)
+/// - Per input: ~330 bytes (txid, vout, uncompressed pubkey and signature as hex)
+/// - Per output: ~60 bytes (address, amount)
+pub fn estimated_signed_size(inputs: usize, outputs: usize) -> usize {
+    150 + inputs * 330 + (outputs + 1) * 60
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::calculate_default_fee;
+    use crate::crypto::WalletKeypair;
+
+    const CHANGE: &str = "0xchange";
+
+    fn utxo(n: u8, amount: U256) -> Utxo {
+        let txid = format!("{:02x}", n).repeat(32);
+        Utxo::new(txid, 0, CHANGE.into(), amount, 1, false)
+    }
+
+    fn build(utxos: &[Utxo], amount: U256, fee_policy: &FeePolicy) -> Result<Transaction> {
+        let keypair = WalletKeypair::new();
+        let outputs = vec![TransactionOutput::new("0xabc".into(), amount)];
+        build_transaction(utxos, outputs, fee_policy, CHANGE, |mut tx| {
+            tx.sign(&keypair)?;
+            Ok(tx.with_hashes())
+        })
+    }
+
+    fn input_total(utxos: &[Utxo], tx: &Transaction) -> U256 {
+        checked_sum(tx.inputs.iter().map(|inp| {
+            let utxo = utxos.iter().find(|u| u.txid == inp.txid).unwrap();
+            utxo.amount()
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn change_returns_the_rest_after_a_size_based_fee() {
+        let asrm = crate::config::RAM_PER_ASRM;
+        let utxos = [utxo(1, asrm), utxo(2, asrm * U256::from(5u8))];
+        let fee_policy = FeePolicy::BySize(&calculate_default_fee);
+        let tx = build(&utxos, asrm * U256::from(3u8), &fee_policy).unwrap();
+
+        // Both inputs were needed; the change is what the fee didn't take
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[1].to, CHANGE);
+        let size = bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap().len();
+        let fee = tx.fee(input_total(&utxos, &tx)).unwrap();
+        assert!(fee >= calculate_default_fee(size));
+        assert!(fee <= calculate_default_fee(size + 2));
+        assert!(tx.verify_signatures().unwrap());
+    }
+
+    #[test]
+    fn exact_change_has_no_change_output() {
+        let fee = calculate_default_fee(1000);
+        let amount = U256::from(10u64).pow(U256::from(18u8));
+        let utxos = [utxo(1, amount + fee)];
+        let tx = build(&utxos, amount, &FeePolicy::Fixed(fee)).unwrap();
+
+        assert_eq!(tx.outputs.len(), 1);
+        assert_eq!(tx.fee(input_total(&utxos, &tx)), Some(fee));
+    }
+
+    #[test]
+    fn dust_change_goes_to_the_fee() {
+        let fee = calculate_default_fee(1000);
+        let amount = U256::from(10u64).pow(U256::from(18u8));
+        let dust = U256::from(MIN_OUTPUT_VALUE - 1);
+        let utxos = [utxo(1, amount + fee + dust)];
+        let tx = build(&utxos, amount, &FeePolicy::Fixed(fee)).unwrap();
+
+        assert_eq!(tx.outputs.len(), 1);
+        assert_eq!(tx.fee(input_total(&utxos, &tx)), Some(fee + dust));
+
+        // One ram more and it's worth keeping
+        let utxos = [utxo(1, amount + fee + dust + 1)];
+        let tx = build(&utxos, amount, &FeePolicy::Fixed(fee)).unwrap();
+        assert_eq!(tx.outputs[1].amount(), dust + 1);
+    }
+
+    #[test]
+    fn insufficient_funds_and_fee_are_rejected() {
+        let amount = U256::from(10u64).pow(U256::from(18u8));
+        let fee = calculate_default_fee(1000);
+
+        // Enough for the amount but not the fee
+        let utxos = [utxo(1, amount), utxo(2, fee / 2)];
+        let err = build(&utxos, amount, &FeePolicy::Fixed(fee)).unwrap_err();
+        assert!(err.to_string().contains("Insufficient funds"), "{}", err);
+        assert!(build(&[], amount, &FeePolicy::Fixed(fee)).is_err());
+
+        // A fixed fee under the relay minimum for the signed size
+        let utxos = [utxo(1, amount * U256::from(2u8))];
+        let err = build(&utxos, amount, &FeePolicy::Fixed(U256::one())).unwrap_err();
+        assert!(err.to_string().contains("Insufficient fee"), "{}", err);
+    }
+}
@@ -0,0 +1,109 @@
+// Exact conversions between ASRM and ram (1 ASRM = 10^18 ram) for display and input
+use crate::config::RAM_PER_ASRM;
+use primitive_types::U256;
+
+/// Decimal places of ASRM
+pub const ASRM_DECIMALS: usize = 18;
+
+/// Exact ASRM value of `ram` as a decimal string with trailing zeros trimmed
+/// ("12.5", "0.00016", "3"). No floating point, so nothing is rounded away.
+pub fn format_asrm(ram: U256) -> String {
+    let whole = ram / RAM_PER_ASRM;
+    let frac = ram % RAM_PER_ASRM;
+    if frac.is_zero() {
+        return whole.to_string();
+    }
+    let frac = format!("{:0>width$}", frac.to_string(), width = ASRM_DECIMALS);
+    format!("{}.{}", whole, frac.trim_end_matches('0'))
+}
+
+/// Human and raw value together, e.g. `12.5 ASRM (12500000000000000000 ram)`
+pub fn format_amount(ram: U256) -> String {
+    format!("{} ASRM ({} ram)", format_asrm(ram), ram)
+}
+
+/// Sum of `amounts`, or None if the total doesn't fit in a U256. Amounts come from
+/// untrusted transactions, so plain `+` could panic (debug) or wrap (release).
+pub fn checked_sum(amounts: impl IntoIterator<Item = U256>) -> Option<U256> {
+    amounts
+        .into_iter()
+        .try_fold(U256::zero(), |acc, amount| acc.checked_add(amount))
+}
+
+/// Parse a decimal ASRM amount ("1.5", "0.0001", "42") into ram exactly.
+/// Returns None for malformed input, more than 18 decimals, or overflow.
+pub fn parse_asrm(input: &str) -> Option<U256> {
+    let (whole, frac) = input.trim().split_once('.').unwrap_or((input.trim(), ""));
+    if whole.is_empty() && frac.is_empty() {
+        return None;
+    }
+    let digits_only = whole
+        .bytes()
+        .chain(frac.bytes())
+        .all(|b| b.is_ascii_digit());
+    if !digits_only || frac.len() > ASRM_DECIMALS {
+        return None;
+    }
+    let whole = if whole.is_empty() {
+        U256::zero()
+    } else {
+        U256::from_dec_str(whole).ok()?
+    };
+    let frac = if frac.is_empty() {
+        U256::zero()
+    } else {
+        U256::from_dec_str(&format!("{:0<width$}", frac, width = ASRM_DECIMALS)).ok()?
+    };
+    whole.checked_mul(RAM_PER_ASRM)?.checked_add(frac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_representative_amounts() {
+        let ram = |s: &str| U256::from_dec_str(s).unwrap();
+        assert_eq!(
+            format_amount(ram("12500000000000000000")),
+            "12.5 ASRM (12500000000000000000 ram)"
+        );
+        assert_eq!(format_amount(U256::zero()), "0 ASRM (0 ram)");
+        assert_eq!(format_asrm(ram("1")), "0.000000000000000001");
+        assert_eq!(format_asrm(RAM_PER_ASRM * U256::from(8)), "8");
+        // the default fee for a 300-byte tx
+        assert_eq!(format_asrm(ram("160000000000000")), "0.00016");
+        // beyond f64 precision: every digit survives
+        assert_eq!(
+            format_asrm(ram("42000000123456789012345678")),
+            "42000000.123456789012345678"
+        );
+    }
+
+    #[test]
+    fn parse_round_trips_format() {
+        for s in ["1.5", "0.00016", "42", "0.000000000000000001", ".5"] {
+            let ram = parse_asrm(s).unwrap();
+            assert_eq!(parse_asrm(&format_asrm(ram)), Some(ram));
+        }
+        assert_eq!(
+            parse_asrm("12.5"),
+            U256::from_dec_str("12500000000000000000").ok()
+        );
+        for bad in ["", ".", "1.2.3", "-1", "abc", "0.0000000000000000001"] {
+            assert_eq!(parse_asrm(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn checked_sum_reports_overflow() {
+        let half = U256::MAX / 2 + 1;
+        assert_eq!(
+            checked_sum([U256::from(2u8), U256::from(3u8)]),
+            Some(U256::from(5u8))
+        );
+        assert_eq!(checked_sum([]), Some(U256::zero()));
+        assert_eq!(checked_sum([half, half - 1]), Some(U256::MAX));
+        assert_eq!(checked_sum([half, half]), None);
+    }
+}
@@ -1,23 +1,40 @@
+use bincode::de::Decoder;
+use bincode::error::DecodeError;
 use bincode::{Decode, Encode};
 use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 
 /// UTXO with amount stored as [u64; 4] for bincode compatibility
-#[derive(Encode, Decode, Debug, Clone, Serialize, Deserialize)]
+#[derive(Encode, Debug, Clone, Serialize, Deserialize)]
 pub struct Utxo {
     pub txid: String,
     pub vout: u32,
     pub to: String,
     amount_raw: [u64; 4], // U256 internal representation
+    /// Height of the block that created this output
+    #[serde(default)]
+    pub height: u64,
+    /// Created by a coinbase transaction (subject to COINBASE_MATURITY)
+    #[serde(default)]
+    pub coinbase: bool,
 }
 
 impl Utxo {
-    pub fn new(txid: String, vout: u32, to: String, amount: U256) -> Self {
+    pub fn new(
+        txid: String,
+        vout: u32,
+        to: String,
+        amount: U256,
+        height: u64,
+        coinbase: bool,
+    ) -> Self {
         Utxo {
             txid,
             vout,
             to,
             amount_raw: amount.0,
+            height,
+            coinbase,
         }
     }
 
@@ -28,4 +45,181 @@ impl Utxo {
     pub fn set_amount(&mut self, amount: U256) {
         self.amount_raw = amount.0;
     }
+
+    /// Whether this output may be spent by a transaction in a block at `spend_height`.
+    /// Coinbase outputs need COINBASE_MATURITY blocks on top of the block that created them.
+    pub fn is_mature_at(&self, spend_height: u64) -> bool {
+        !self.coinbase
+            || spend_height.saturating_sub(self.height) >= crate::config::COINBASE_MATURITY
+    }
+}
+
+// Records written before `height`/`coinbase` existed end after `amount_raw`;
+// they decode with height 0 and coinbase false.
+impl<Context> Decode<Context> for Utxo {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let txid = String::decode(decoder)?;
+        let vout = u32::decode(decoder)?;
+        let to = String::decode(decoder)?;
+        let amount_raw = <[u64; 4]>::decode(decoder)?;
+        let (height, coinbase) = match u64::decode(decoder) {
+            Ok(height) => (height, bool::decode(decoder)?),
+            Err(DecodeError::UnexpectedEnd { .. }) => (0, false),
+            Err(e) => return Err(e),
+        };
+        Ok(Utxo {
+            txid,
+            vout,
+            to,
+            amount_raw,
+            height,
+            coinbase,
+        })
+    }
+}
+bincode::impl_borrow_decode!(Utxo);
+
+/// Hash functions per key; with 10 bits per expected key this keeps the false
+/// positive rate around 1%
+const FILTER_HASHES: usize = 7;
+const FILTER_BITS_PER_KEY: usize = 10;
+/// Smallest capacity a filter is sized for, so a fresh chain doesn't rebuild constantly
+pub const MIN_FILTER_CAPACITY: usize = 1 << 16;
+
+/// In-memory bloom filter over UTXO keys (`u:<txid>:<vout>`).
+///
+/// A negative answer is definitive, so input validation can reject a missing
+/// outpoint without touching the database. Spent outputs are never cleared; they
+/// only cost a false positive that falls through to the real lookup.
+pub struct UtxoFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    capacity: usize,
+    inserted: usize,
+}
+
+impl UtxoFilter {
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(MIN_FILTER_CAPACITY);
+        let num_bits = (capacity * FILTER_BITS_PER_KEY) as u64;
+        UtxoFilter {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            capacity,
+            inserted: 0,
+        }
+    }
+
+    fn positions(&self, key: &[u8]) -> [u64; FILTER_HASHES] {
+        use std::hash::{Hash, Hasher};
+
+        // Double hashing: h1 + i * h2 gives k well-spread positions from two hashes
+        let mut first = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut first);
+        let h1 = first.finish();
+        let mut second = std::collections::hash_map::DefaultHasher::new();
+        (key, 0x9e37_79b9_7f4a_7c15u64).hash(&mut second);
+        let h2 = second.finish() | 1;
+        std::array::from_fn(|i| h1.wrapping_add((i as u64).wrapping_mul(h2)) % self.num_bits)
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        for pos in self.positions(key) {
+            self.bits[(pos / 64) as usize] |= 1 << (pos % 64);
+        }
+        self.inserted += 1;
+    }
+
+    /// False means the key was never inserted; true means it may have been
+    pub fn might_contain(&self, key: &[u8]) -> bool {
+        self.positions(key)
+            .into_iter()
+            .all(|pos| self.bits[(pos / 64) as usize] & (1 << (pos % 64)) != 0)
+    }
+
+    /// More keys were inserted than the filter was sized for, so the false positive
+    /// rate is degrading and it should be rebuilt from the UTXO set
+    pub fn is_saturated(&self) -> bool {
+        self.inserted > self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Encode)]
+    struct LegacyUtxo {
+        txid: String,
+        vout: u32,
+        to: String,
+        amount_raw: [u64; 4],
+    }
+
+    #[test]
+    fn legacy_record_decodes_with_defaults() {
+        let config = bincode::config::standard();
+        let legacy = LegacyUtxo {
+            txid: "ab".repeat(32),
+            vout: 1,
+            to: "0x0000000000000000000000000000000000000001".to_string(),
+            amount_raw: U256::from(42u64).0,
+        };
+        let blob = bincode::encode_to_vec(&legacy, config).unwrap();
+        let (utxo, _): (Utxo, usize) = bincode::decode_from_slice(&blob, config).unwrap();
+        assert_eq!(utxo.amount(), U256::from(42u64));
+        assert_eq!((utxo.height, utxo.coinbase), (0, false));
+
+        let current = Utxo::new(legacy.txid, 1, legacy.to, U256::from(42u64), 7, true);
+        let blob = bincode::encode_to_vec(&current, config).unwrap();
+        let (decoded, _): (Utxo, usize) = bincode::decode_from_slice(&blob, config).unwrap();
+        assert_eq!((decoded.height, decoded.coinbase), (7, true));
+    }
+
+    #[test]
+    fn coinbase_spendable_only_at_maturity() {
+        use crate::config::COINBASE_MATURITY;
+
+        let created_at = 10;
+        let reward = Utxo::new(
+            "cd".repeat(32),
+            0,
+            "0x0000000000000000000000000000000000000001".to_string(),
+            U256::from(8u64),
+            created_at,
+            true,
+        );
+        assert!(!reward.is_mature_at(created_at + COINBASE_MATURITY - 1));
+        assert!(reward.is_mature_at(created_at + COINBASE_MATURITY));
+
+        // regular outputs are spendable immediately
+        let mut regular = reward.clone();
+        regular.coinbase = false;
+        assert!(regular.is_mature_at(created_at + 1));
+    }
+
+    #[test]
+    fn filter_has_no_false_negatives() {
+        // Insert well past capacity so saturation can't hide a missing bit
+        let count = MIN_FILTER_CAPACITY * 2;
+        let mut filter = UtxoFilter::with_capacity(0);
+        let keys: Vec<String> = (0..count)
+            .map(|i| format!("u:{:064x}:{}", i, i % 3))
+            .collect();
+        for key in &keys {
+            filter.insert(key.as_bytes());
+        }
+        assert!(filter.is_saturated());
+        assert!(keys.iter().all(|k| filter.might_contain(k.as_bytes())));
+
+        // An unsaturated filter should mostly reject keys it never saw
+        let mut sized = UtxoFilter::with_capacity(count);
+        for key in &keys {
+            sized.insert(key.as_bytes());
+        }
+        let false_positives = (0..10_000)
+            .filter(|i| sized.might_contain(format!("u:absent{}:0", i).as_bytes()))
+            .count();
+        assert!(false_positives < 500, "{} false positives", false_positives);
+    }
 }
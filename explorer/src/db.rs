@@ -343,6 +343,34 @@ impl ExplorerDB {
         Ok(())
     }
 
+    /// 출력 금액 캐시 저장 (수수료 계산용)
+    /// Key: o:<txid>:<vout> -> amount (hex)
+    pub fn save_output_amounts(
+        &self,
+        outputs: &std::collections::HashMap<(String, u32), U256>,
+    ) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        for ((txid, vout), amount) in outputs {
+            let key = format!("o:{}:{}", txid, vout);
+            batch.put(key.as_bytes(), format!("0x{:x}", amount).as_bytes());
+        }
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// 출력 금액 캐시 조회
+    pub fn get_output_amount(&self, txid: &str, vout: u32) -> Result<Option<U256>> {
+        let key = format!("o:{}:{}", txid, vout);
+        match self.db.get(key.as_bytes())? {
+            Some(data) => {
+                let s = String::from_utf8(data.to_vec())?;
+                let hex = s.strip_prefix("0x").unwrap_or(&s);
+                Ok(U256::from_str_radix(hex, 16).ok())
+            }
+            None => Ok(None),
+        }
+    }
+
     /// 마지막 동기화된 블록 높이 조회
     pub fn get_last_synced_height(&self) -> Result<u64> {
         let key = "meta:last_synced";
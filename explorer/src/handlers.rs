@@ -1,16 +1,20 @@
 use crate::db::ExplorerDB;
 use crate::rpc::NodeRpcClient;
-use crate::state::BlockchainStats;
+use crate::state::{BlockchainStats, SyncStatus};
 use actix_web::{HttpResponse, web};
-use chrono::Utc;
+use astram_config::time::Timestamp;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HealthResponse {
     pub status: String,
     pub version: String,
     pub timestamp: String,
+    #[serde(flatten)]
+    pub time: Timestamp,
+    #[serde(flatten)]
+    pub sync: SyncStatus,
 }
 
 /// Reorg alert information for security monitoring
@@ -32,12 +36,71 @@ pub struct PaginationParams {
     pub limit: Option<u32>,
 }
 
+/// Address history pages follow the node's cursor rather than a page number
+#[derive(Debug, Deserialize)]
+pub struct CursorParams {
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    pub q: String,
+}
+
+/// Largest page of address history the explorer asks the node for
+const MAX_ADDRESS_HISTORY_PAGE: u32 = 100;
+
+/// What a search string looks like; hashes are lowercased with any 0x stripped
+#[derive(Debug, PartialEq)]
+enum SearchQuery {
+    Height(u64),
+    Address(String),
+    /// 32-byte hash: a block hash, an EVM transaction hash or an Astram txid
+    Hash(String),
+    Unknown,
+}
+
+fn classify_search(q: &str) -> SearchQuery {
+    let q = q.trim();
+    if let Ok(height) = q.parse::<u64>() {
+        return SearchQuery::Height(height);
+    }
+    let hex = q.strip_prefix("0x").unwrap_or(q);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return SearchQuery::Unknown;
+    }
+    match hex.len() {
+        40 if q.starts_with("0x") => SearchQuery::Address(q.to_lowercase()),
+        64 => SearchQuery::Hash(hex.to_lowercase()),
+        _ => SearchQuery::Unknown,
+    }
+}
+
+fn search_hit(kind: &str, path: String, result: impl Serialize) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "type": kind,
+        "path": path,
+        "result": result,
+    }))
+}
+
 // 헬스 체크 엔드포인트
-pub async fn health() -> HttpResponse {
+// 노드에 연결할 수 없으면 "degraded" (인덱스 데이터가 오래되었을 수 있음)
+pub async fn health(sync: web::Data<Arc<Mutex<SyncStatus>>>) -> HttpResponse {
+    let time = Timestamp::now();
+    let sync = sync.lock().unwrap().clone();
+    let status = if sync.node_connected {
+        "healthy"
+    } else {
+        "degraded"
+    };
     HttpResponse::Ok().json(HealthResponse {
-        status: "healthy".to_string(),
+        status: status.to_string(),
         version: "0.1.0".to_string(),
-        timestamp: Utc::now().to_rfc3339(),
+        timestamp: time.timestamp_iso.clone(),
+        time,
+        sync,
     })
 }
 
@@ -163,7 +226,10 @@ pub async fn get_transaction_by_hash(
 }
 
 // 블록체인 통계 조회
-pub async fn get_blockchain_stats(db: web::Data<Arc<ExplorerDB>>) -> HttpResponse {
+pub async fn get_blockchain_stats(
+    db: web::Data<Arc<ExplorerDB>>,
+    sync: web::Data<Arc<Mutex<SyncStatus>>>,
+) -> HttpResponse {
     match db.get_stats() {
         Ok((total_blocks, total_transactions, total_volume)) => {
             let stats = BlockchainStats {
@@ -174,6 +240,7 @@ pub async fn get_blockchain_stats(db: web::Data<Arc<ExplorerDB>>) -> HttpRespons
                 average_block_size: 250,
                 current_difficulty: 1, // TODO: 최신 블록에서 가져오기
                 network_hashrate: "0.00 TH/s".to_string(),
+                sync: sync.lock().unwrap().clone(),
             };
 
             HttpResponse::Ok().json(stats)
@@ -184,6 +251,92 @@ pub async fn get_blockchain_stats(db: web::Data<Arc<ExplorerDB>>) -> HttpRespons
     }
 }
 
+// 블록 높이, 블록 해시, 트랜잭션 해시(EVM/txid), 주소 통합 검색
+pub async fn search(
+    db: web::Data<Arc<ExplorerDB>>,
+    rpc: web::Data<Arc<NodeRpcClient>>,
+    query: web::Query<SearchParams>,
+) -> HttpResponse {
+    let q = query.q.trim();
+    log::info!("🔍 API: Search for {}", q);
+
+    let db_error = |e: anyhow::Error| {
+        HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        }))
+    };
+
+    match classify_search(q) {
+        SearchQuery::Height(height) => match db.get_block_by_height(height) {
+            Ok(Some(block)) => {
+                return search_hit("block", format!("/api/blocks/{}", height), block);
+            }
+            Ok(None) => {}
+            Err(e) => return db_error(e),
+        },
+        SearchQuery::Address(address) => {
+            let info = match db.get_address_info(&address) {
+                Ok(Some(info)) => Ok(info),
+                Ok(None) => db.update_address_info(&address),
+                Err(e) => Err(e),
+            };
+            return match info {
+                Ok(info) => search_hit("address", format!("/api/address/{}", address), info),
+                Err(e) => db_error(e),
+            };
+        }
+        SearchQuery::Hash(hash) => {
+            match db.get_block_by_hash(&hash) {
+                Ok(Some(block)) => {
+                    return search_hit("block", format!("/api/blocks/hash/{}", hash), block);
+                }
+                Ok(None) => {}
+                Err(e) => return db_error(e),
+            }
+
+            // The explorer keys transactions by EVM hash; an Astram txid is resolved
+            // through the node's transaction index
+            let eth_hash = format!("0x{}", hash);
+            let eth_hash = match db.get_transaction(&eth_hash) {
+                Ok(Some(tx)) => {
+                    return search_hit(
+                        "transaction",
+                        format!("/api/transactions/{}", eth_hash),
+                        tx,
+                    );
+                }
+                Ok(None) => rpc
+                    .fetch_transaction_eth_hash(&hash)
+                    .await
+                    .unwrap_or_else(|e| {
+                        log::warn!("Node txid lookup failed for {}: {}", hash, e);
+                        None
+                    }),
+                Err(e) => return db_error(e),
+            };
+            if let Some(eth_hash) = eth_hash {
+                match db.get_transaction(&eth_hash) {
+                    Ok(Some(tx)) => {
+                        return search_hit(
+                            "transaction",
+                            format!("/api/transactions/{}", eth_hash),
+                            tx,
+                        );
+                    }
+                    Ok(None) => {}
+                    Err(e) => return db_error(e),
+                }
+            }
+        }
+        SearchQuery::Unknown => {}
+    }
+
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "No block, transaction or address matches the query",
+        "query": q,
+    }))
+}
+
 // 주소별 정보 조회
 pub async fn get_address_info(
     db: web::Data<Arc<ExplorerDB>>,
@@ -209,6 +362,39 @@ pub async fn get_address_info(
     }
 }
 
+// 주소별 트랜잭션 내역 (페이징, 노드의 주소 인덱스 사용)
+pub async fn get_address_transactions(
+    rpc: web::Data<Arc<NodeRpcClient>>,
+    path: web::Path<String>,
+    query: web::Query<CursorParams>,
+) -> HttpResponse {
+    let address = path.into_inner().to_lowercase();
+    let limit = query.limit.unwrap_or(20).clamp(1, MAX_ADDRESS_HISTORY_PAGE);
+
+    match rpc
+        .fetch_address_transactions(&address, query.cursor.as_deref(), limit as u64)
+        .await
+    {
+        Ok(history) => HttpResponse::Ok().json(serde_json::json!({
+            "address": address,
+            "limit": limit,
+            // Only the first page (no cursor) carries the total
+            "total": history.get("total").cloned().unwrap_or(serde_json::Value::Null),
+            "next_cursor": history
+                .get("next_cursor")
+                .cloned()
+                .unwrap_or(serde_json::Value::Null),
+            "transactions": history
+                .get("transactions")
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!([])),
+        })),
+        Err(e) => HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": format!("Failed to fetch address history: {}", e)
+        })),
+    }
+}
+
 // Node status proxy
 pub async fn get_node_status(rpc: web::Data<Arc<NodeRpcClient>>) -> HttpResponse {
     match rpc.fetch_status().await {
@@ -218,3 +404,32 @@ pub async fn get_node_status(rpc: web::Data<Arc<NodeRpcClient>>) -> HttpResponse
         })),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_queries_are_classified() {
+        assert_eq!(classify_search(" 42 "), SearchQuery::Height(42));
+
+        let address = format!("0x{}", "AB".repeat(20));
+        assert_eq!(
+            classify_search(&address),
+            SearchQuery::Address(address.to_lowercase())
+        );
+        // 40 hex digits without the prefix is not an address
+        assert_eq!(classify_search(&"ab".repeat(20)), SearchQuery::Unknown);
+
+        let hash = "cd".repeat(32);
+        assert_eq!(classify_search(&hash), SearchQuery::Hash(hash.clone()));
+        assert_eq!(
+            classify_search(&format!("0x{}", hash.to_uppercase())),
+            SearchQuery::Hash(hash)
+        );
+
+        assert_eq!(classify_search("not-a-hash"), SearchQuery::Unknown);
+        assert_eq!(classify_search(&"zz".repeat(32)), SearchQuery::Unknown);
+        assert_eq!(classify_search(""), SearchQuery::Unknown);
+    }
+}
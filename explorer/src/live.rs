@@ -0,0 +1,310 @@
+// WebSocket live updates: the sync task pushes new-block and new-transaction events to
+// connected clients
+use crate::state::BlockInfo;
+use Astram_core::transaction::Transaction;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_ws::Message;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+/// Connected clients past which new WebSocket upgrades are refused
+const MAX_LIVE_SESSIONS: usize = 1000;
+/// Events queued for one client; a client this far behind is dropped as lagging
+const LIVE_QUEUE: usize = 64;
+/// Most transactions listed in one `new_transactions` event
+const MAX_EVENT_TRANSACTIONS: usize = 100;
+/// Longest a client may take to accept one frame before it is dropped as lagging
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the server pings each client
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// Clients that send nothing (not even a pong) for this long are disconnected
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// Pushed to every connected client after a sync indexes new blocks
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncEvent {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub tip_height: u64,
+    pub new_blocks: usize,
+    pub new_transactions: usize,
+    pub latest_block: BlockInfo,
+}
+
+impl SyncEvent {
+    pub fn new_block(latest_block: BlockInfo, new_blocks: usize, new_transactions: usize) -> Self {
+        SyncEvent {
+            kind: "new_block",
+            tip_height: latest_block.height,
+            new_blocks,
+            new_transactions,
+            latest_block,
+        }
+    }
+}
+
+/// A transaction that reached the node's mempool; the explorer indexes transactions
+/// by `eth_hash` once they are mined
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingTransaction {
+    pub txid: String,
+    pub eth_hash: String,
+}
+
+/// Pushed to every connected client when the node's mempool gains transactions
+#[derive(Debug, Clone, Serialize)]
+pub struct MempoolEvent {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub mempool_size: usize,
+    pub new_transactions: usize,
+    /// The first MAX_EVENT_TRANSACTIONS of the new transactions
+    pub transactions: Vec<PendingTransaction>,
+}
+
+/// The node's mempool as of the last poll, so only newly arrived transactions are
+/// announced
+#[derive(Default)]
+pub struct MempoolWatch {
+    seen: HashSet<String>,
+}
+
+impl MempoolWatch {
+    /// Record `pending`, the node's current mempool. Returns the event announcing the
+    /// transactions that weren't pending at the last poll, or None if there are none.
+    pub fn update(&mut self, pending: &[Transaction]) -> Option<MempoolEvent> {
+        let arrived: Vec<PendingTransaction> = pending
+            .iter()
+            .filter(|tx| !self.seen.contains(&tx.txid))
+            .map(|tx| PendingTransaction {
+                txid: tx.txid.clone(),
+                eth_hash: tx.eth_hash.clone(),
+            })
+            .collect();
+        self.seen = pending.iter().map(|tx| tx.txid.clone()).collect();
+        if arrived.is_empty() {
+            return None;
+        }
+
+        Some(MempoolEvent {
+            kind: "new_transactions",
+            mempool_size: pending.len(),
+            new_transactions: arrived.len(),
+            transactions: arrived.into_iter().take(MAX_EVENT_TRANSACTIONS).collect(),
+        })
+    }
+}
+
+/// Open `/api/ws` sessions: the queue feeding each one's connection task, keyed by a
+/// connection id
+#[derive(Default)]
+pub struct LiveUpdates {
+    sessions: Mutex<HashMap<u64, mpsc::Sender<String>>>,
+    next_id: AtomicU64,
+}
+
+impl LiveUpdates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    fn register(&self) -> (u64, mpsc::Receiver<String>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (queue, frames) = mpsc::channel(LIVE_QUEUE);
+        self.sessions.lock().unwrap().insert(id, queue);
+        (id, frames)
+    }
+
+    fn remove(&self, id: u64) {
+        self.sessions.lock().unwrap().remove(&id);
+    }
+
+    /// Queue `event` for every client without waiting on any of them, so one slow
+    /// reader can't hold up the rest. Sessions that have closed, or that have fallen
+    /// LIVE_QUEUE events behind, are dropped.
+    pub fn broadcast<E: Serialize>(&self, event: &E) {
+        let text = match serde_json::to_string(event) {
+            Ok(text) => text,
+            Err(e) => {
+                log::error!("Failed to encode live update: {}", e);
+                return;
+            }
+        };
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        // Dropping a queue ends its connection task once the queued frames are sent
+        sessions.retain(|_, queue| queue.try_send(text.clone()).is_ok());
+        let dropped = before - sessions.len();
+        if dropped > 0 {
+            log::debug!("Dropped {} closed or lagging live update sessions", dropped);
+        }
+    }
+}
+
+// GET /api/ws - WebSocket upgrade; the server pushes a JSON SyncEvent per sync that
+// indexed new blocks and a MempoolEvent per poll that found new pending transactions,
+// and pings every HEARTBEAT_INTERVAL, closing clients silent for CLIENT_TIMEOUT.
+// Client messages other than ping/close only count as activity.
+pub async fn ws_updates(
+    req: HttpRequest,
+    body: web::Payload,
+    live: web::Data<Arc<LiveUpdates>>,
+) -> actix_web::Result<HttpResponse> {
+    if live.session_count() >= MAX_LIVE_SESSIONS {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "too many live update connections"
+        })));
+    }
+
+    let (response, mut session, mut messages) = actix_ws::handle(&req, body)?;
+    let (id, mut frames) = live.register();
+
+    let live = live.get_ref().clone();
+    actix_web::rt::spawn(async move {
+        let mut reason = None;
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut last_seen = Instant::now();
+        loop {
+            tokio::select! {
+                frame = frames.recv() => {
+                    // None once broadcast has dropped this session
+                    let Some(text) = frame else { break };
+                    match tokio::time::timeout(SEND_TIMEOUT, session.text(text)).await {
+                        Ok(Ok(())) => {}
+                        _ => break,
+                    }
+                }
+                msg = messages.recv() => {
+                    let Some(Ok(msg)) = msg else { break };
+                    last_seen = Instant::now();
+                    match msg {
+                        Message::Ping(bytes) => {
+                            if session.pong(&bytes).await.is_err() {
+                                break;
+                            }
+                        }
+                        Message::Close(close) => {
+                            reason = close;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > CLIENT_TIMEOUT {
+                        log::debug!("Live update session {} timed out", id);
+                        break;
+                    }
+                    match tokio::time::timeout(SEND_TIMEOUT, session.ping(b"")).await {
+                        Ok(Ok(())) => {}
+                        _ => break,
+                    }
+                }
+            }
+        }
+        live.remove(id);
+        let _ = session.close(reason).await;
+    });
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    use primitive_types::U256;
+
+    fn block(height: u64) -> BlockInfo {
+        BlockInfo {
+            height,
+            hash: "ab".repeat(32),
+            timestamp: Utc::now(),
+            transactions: 3,
+            miner: "0xminer".to_string(),
+            difficulty: 1,
+            nonce: 0,
+            previous_hash: "cd".repeat(32),
+            confirmations: 0,
+        }
+    }
+
+    fn frame(frames: &mut mpsc::Receiver<String>) -> serde_json::Value {
+        serde_json::from_str(&frames.try_recv().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn sync_event_carries_the_tip() {
+        let event = serde_json::to_value(SyncEvent::new_block(block(7), 2, 5)).unwrap();
+
+        assert_eq!(event["type"], "new_block");
+        assert_eq!(event["tip_height"], 7);
+        assert_eq!(event["new_blocks"], 2);
+        assert_eq!(event["new_transactions"], 5);
+        assert_eq!(event["latest_block"]["height"], 7);
+    }
+
+    #[test]
+    fn broadcast_reaches_every_session() {
+        let live = LiveUpdates::new();
+        let (_, mut a) = live.register();
+        let (_, mut b) = live.register();
+
+        live.broadcast(&SyncEvent::new_block(block(7), 1, 1));
+        assert_eq!(frame(&mut a)["tip_height"], 7);
+        assert_eq!(frame(&mut b)["tip_height"], 7);
+        assert_eq!(live.session_count(), 2);
+    }
+
+    #[test]
+    fn closed_and_lagging_sessions_are_dropped() {
+        let live = LiveUpdates::new();
+        let (_, closed) = live.register();
+        let (_, _lagging) = live.register();
+        let (_, mut reading) = live.register();
+        drop(closed);
+
+        // A closed session goes on the next event; one that stops reading only once
+        // its queue is full
+        for height in 0..LIVE_QUEUE as u64 {
+            live.broadcast(&SyncEvent::new_block(block(height), 1, 0));
+            assert_eq!(frame(&mut reading)["tip_height"], height);
+            assert_eq!(live.session_count(), 2);
+        }
+        live.broadcast(&SyncEvent::new_block(block(99), 1, 0));
+        assert_eq!(live.session_count(), 1);
+        assert_eq!(frame(&mut reading)["tip_height"], 99);
+    }
+
+    #[test]
+    fn only_newly_pending_transactions_are_announced() {
+        let tx = |n: u64| Transaction::coinbase("0xminer", U256::from(n)).with_hashes();
+        let (a, b, c) = (tx(1), tx(2), tx(3));
+        let mut watch = MempoolWatch::default();
+
+        let event = watch.update(&[a.clone(), b.clone()]).unwrap();
+        assert_eq!(event.new_transactions, 2);
+        assert_eq!(event.transactions[1].eth_hash, b.eth_hash);
+        assert!(watch.update(&[a.clone(), b.clone()]).is_none());
+
+        // A mined transaction leaves the mempool quietly
+        let event = serde_json::to_value(watch.update(&[b, c.clone()]).unwrap()).unwrap();
+        assert_eq!(event["type"], "new_transactions");
+        assert_eq!(event["mempool_size"], 2);
+        assert_eq!(event["new_transactions"], 1);
+        assert_eq!(event["transactions"][0]["txid"], c.txid.as_str());
+        assert!(watch.update(&[]).is_none());
+        assert!(watch.update(&[a]).is_some());
+    }
+}
@@ -1,16 +1,21 @@
 mod api;
 mod db;
 mod handlers;
+mod live;
 mod rpc;
+mod settings;
 mod state;
 
 use actix_cors::Cors;
 use actix_web::{App, HttpServer, middleware, web};
 use db::ExplorerDB;
+use live::{LiveUpdates, MempoolWatch, SyncEvent};
 use log::{error, info};
-use rpc::NodeRpcClient;
-use std::sync::Arc;
-use tokio::time::{Duration, interval};
+use rpc::{NodeRpcClient, SYNC_PAGE_BLOCKS};
+use settings::ExplorerSettings;
+use state::SyncStatus;
+use std::sync::{Arc, Mutex};
+use tokio::time::Duration;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -20,6 +25,16 @@ async fn main() -> std::io::Result<()> {
 
     info!("Astram Explorer starting...");
 
+    let settings = ExplorerSettings::from_env().map_err(|e| {
+        error!("Invalid explorer settings: {}", e);
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+    })?;
+    info!(
+        "Indexing from node {} every {}s",
+        settings.node_url,
+        settings.sync_interval.as_secs()
+    );
+
     // Explorer database initialization
     let db_path = "explorer_data";
     let explorer_db = Arc::new(ExplorerDB::new(db_path).expect("Failed to open explorer database"));
@@ -28,46 +43,46 @@ async fn main() -> std::io::Result<()> {
 
     // Background sync with the Node process
     let db_sync = explorer_db.clone();
-    let rpc_client = Arc::new(NodeRpcClient::new("http://127.0.0.1:19533"));
+    let rpc_client = Arc::new(NodeRpcClient::new(&settings.node_url));
     let rpc_for_sync = rpc_client.clone();
+    let live_updates = Arc::new(LiveUpdates::new());
+    let live_for_sync = live_updates.clone();
+    let sync_status = Arc::new(Mutex::new(SyncStatus::default()));
+    let status_for_sync = sync_status.clone();
+    let sync_interval = settings.sync_interval;
     tokio::spawn(async move {
 
         info!("Starting blockchain indexing...");
 
-        // Initial sync
-        match sync_blockchain(&db_sync, &rpc_for_sync).await {
-            Ok(()) => {
-                info!("Initial blockchain sync completed");
-            }
-            Err(e) => {
-                error!("Failed to sync blockchain on startup: {}", e);
-            }
-        }
-
-        // Sync every 10 seconds
-        let mut sync_interval = interval(Duration::from_secs(10));
-
+        // Sync every sync_interval; while the node is unreachable, back off exponentially
+        let mut failures: u32 = 0;
+        let mut mempool_watch = MempoolWatch::default();
         loop {
-            sync_interval.tick().await;
-
-            match sync_blockchain(&db_sync, &rpc_for_sync).await {
+            match sync_to_tip(&db_sync, &rpc_for_sync, &live_for_sync).await {
                 Ok(()) => {
                     // Success logging is handled in sync_blockchain
+                    if failures > 0 {
+                        info!("Node reachable again after {} failed syncs", failures);
+                    }
+                    failures = 0;
+                    status_for_sync.lock().unwrap().record_success();
+                    sync_mempool(&rpc_for_sync, &mut mempool_watch, &live_for_sync).await;
                 }
                 Err(e) => {
-                    error!("Failed to sync blockchain: {}", e);
+                    failures = failures.saturating_add(1);
+                    status_for_sync.lock().unwrap().record_failure();
+                    error!(
+                        "Failed to sync blockchain: {} (retrying in {}s)",
+                        e,
+                        sync_delay(sync_interval, failures).as_secs()
+                    );
                 }
             }
+            tokio::time::sleep(sync_delay(sync_interval, failures)).await;
         }
     });
 
-    let server_address = "0.0.0.0";
-    let server_port = 8080;
-
-    info!(
-        "Server listening on http://{}:{}",
-        server_address, server_port
-    );
+    info!("Server listening on http://{}", settings.bind);
 
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -79,6 +94,8 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(web::Data::new(explorer_db.clone()))
             .app_data(web::Data::new(rpc_client.clone()))
+            .app_data(web::Data::new(live_updates.clone()))
+            .app_data(web::Data::new(sync_status.clone()))
             .wrap(middleware::Logger::default())
             .wrap(cors)
             .service(
@@ -103,25 +120,76 @@ async fn main() -> std::io::Result<()> {
                         "/address/{address}",
                         web::get().to(handlers::get_address_info),
                     )
-                    .route("/node/status", web::get().to(handlers::get_node_status)),
+                    .route(
+                        "/address/{address}/transactions",
+                        web::get().to(handlers::get_address_transactions),
+                    )
+                    .route("/search", web::get().to(handlers::search))
+                    .route("/node/status", web::get().to(handlers::get_node_status))
+                    .route("/ws", web::get().to(live::ws_updates)),
             )
     })
-    .bind(format!("{}:{}", server_address, server_port))?
+    .bind(&settings.bind)
+    .map_err(|e| {
+        error!("Failed to bind explorer API to {}: {}", settings.bind, e);
+        e
+    })?
     .run()
     .await
 }
 
-/// Fetch blockchain data from the node and index into the database
-async fn sync_blockchain(db: &ExplorerDB, rpc_client: &NodeRpcClient) -> anyhow::Result<()> {
+/// Longest wait between retries while the node is down
+const MAX_SYNC_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Wait before the next sync after `failures` consecutive failed ones: `interval`,
+/// doubled per failure up to MAX_SYNC_BACKOFF (or `interval`, if that is longer)
+fn sync_delay(interval: Duration, failures: u32) -> Duration {
+    interval
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(MAX_SYNC_BACKOFF.max(interval))
+}
+
+/// Sync page after page until the node hands back less than a full page
+async fn sync_to_tip(
+    db: &ExplorerDB,
+    rpc_client: &NodeRpcClient,
+    live: &LiveUpdates,
+) -> anyhow::Result<()> {
+    while sync_blockchain(db, rpc_client, live).await? >= SYNC_PAGE_BLOCKS as usize {}
+    Ok(())
+}
+
+/// Tell live update clients about transactions that reached the node's mempool since
+/// the last poll. The mempool isn't indexed, so a failed poll only skips the event.
+async fn sync_mempool(rpc_client: &NodeRpcClient, watch: &mut MempoolWatch, live: &LiveUpdates) {
+    match rpc_client.fetch_mempool().await {
+        Ok(pending) => {
+            if let Some(event) = watch.update(&pending) {
+                live.broadcast(&event);
+            }
+        }
+        Err(e) => log::debug!("Failed to fetch mempool: {}", e),
+    }
+}
+
+/// Fetch the next page of blocks from the node, index it into the database and tell
+/// live update clients about the new tip. Returns how many blocks the node sent.
+async fn sync_blockchain(
+    db: &ExplorerDB,
+    rpc_client: &NodeRpcClient,
+    live: &LiveUpdates,
+) -> anyhow::Result<usize> {
     // Load last synced height
     let last_synced = db.get_last_synced_height()?;
 
     let mut utxo_map = std::collections::HashMap::new();
+    // Inputs spending outputs from earlier syncs are resolved from the output cache
+    let resolve_input = |txid: &str, vout: u32| db.get_output_amount(txid, vout).ok().flatten();
     let (blocks, transactions) = if last_synced == 0 {
-        // Full sync: fetch entire blockchain
-        log::info!("Initial sync: fetching entire blockchain from Node");
+        // Initial sync: the first page from genesis
+        log::info!("Initial sync: fetching blocks from genesis");
         rpc_client
-            .fetch_blockchain_with_transactions(&mut utxo_map)
+            .fetch_blockchain_with_transactions(&mut utxo_map, &resolve_input)
             .await
             .map_err(|e| anyhow::anyhow!("Failed to fetch blockchain: {}", e))?
     } else {
@@ -132,14 +200,14 @@ async fn sync_blockchain(db: &ExplorerDB, rpc_client: &NodeRpcClient) -> anyhow:
             last_synced
         );
         rpc_client
-            .fetch_blocks_range(last_synced + 1, &mut utxo_map)
+            .fetch_blocks_range(last_synced + 1, &mut utxo_map, &resolve_input)
             .await
             .map_err(|e| anyhow::anyhow!("Failed to fetch blockchain: {}", e))?
     };
 
     if blocks.is_empty() {
         log::debug!("ℹ️  No new blocks from node RPC");
-        return Ok(());
+        return Ok(0);
     }
 
     let latest_height = blocks.iter().map(|b| b.height).max().unwrap_or(last_synced);
@@ -155,6 +223,9 @@ async fn sync_blockchain(db: &ExplorerDB, rpc_client: &NodeRpcClient) -> anyhow:
         new_blocks += 1;
     }
 
+    // Cache output amounts so later syncs can compute fees for txs spending them
+    db.save_output_amounts(&utxo_map)?;
+
     for tx in &transactions {
         db.save_transaction(tx)?;
         new_transactions += 1;
@@ -180,5 +251,29 @@ async fn sync_blockchain(db: &ExplorerDB, rpc_client: &NodeRpcClient) -> anyhow:
         );
     }
 
-    Ok(())
+    if let Some(latest) = blocks.iter().max_by_key(|b| b.height) {
+        let event = SyncEvent::new_block(latest.clone(), new_blocks, new_transactions);
+        live.broadcast(&event);
+    }
+
+    Ok(blocks.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_backoff_doubles_up_to_the_cap() {
+        let interval = Duration::from_secs(10);
+        assert_eq!(sync_delay(interval, 0), interval);
+        assert_eq!(sync_delay(interval, 1), Duration::from_secs(20));
+        assert_eq!(sync_delay(interval, 3), Duration::from_secs(80));
+        assert_eq!(sync_delay(interval, 5), MAX_SYNC_BACKOFF);
+        assert_eq!(sync_delay(interval, u32::MAX), MAX_SYNC_BACKOFF);
+
+        // An interval past the cap is never shortened
+        let slow = Duration::from_secs(600);
+        assert_eq!(sync_delay(slow, 3), slow);
+    }
 }
@@ -26,6 +26,24 @@ fn parse_u256_from_json(value: &serde_json::Value) -> Option<U256> {
     value.as_u64().map(U256::from)
 }
 
+/// Safe prefix for log output (hashes/addresses may be shorter than expected)
+fn short(s: &str, n: usize) -> &str {
+    s.get(..n).unwrap_or(s)
+}
+
+/// Sum output amounts, returning None on overflow
+fn checked_output_sum(tx: &Astram_core::transaction::Transaction) -> Option<U256> {
+    tx.outputs
+        .iter()
+        .try_fold(U256::zero(), |acc, out| acc.checked_add(out.amount()))
+}
+
+/// Resolves the amount of a previous output that is not in the in-memory UTXO map
+pub type InputResolver<'a> = &'a (dyn Fn(&str, u32) -> Option<U256> + Sync);
+
+/// Blocks pulled per sync request; the node caps block queries at 1000 per request
+pub const SYNC_PAGE_BLOCKS: u64 = 1000;
+
 pub struct NodeRpcClient {
     node_url: String,
 }
@@ -141,10 +159,11 @@ impl NodeRpcClient {
         }
     }
 
-    /// Query blockchain data from Node /blockchain/db (direct DB)
+    /// Query up to SYNC_PAGE_BLOCKS blocks starting at `from_height` from Node
+    /// /blockchain/range (direct DB); callers page on with the next height
     #[allow(dead_code)]
-    pub async fn fetch_blocks(&self) -> Result<Vec<BlockInfo>, String> {
-        let url = format!("{}/blockchain/db", self.node_url);
+    pub async fn fetch_blocks(&self, from_height: u64) -> Result<Vec<BlockInfo>, String> {
+        let url = self.range_url(from_height);
 
         match reqwest::get(&url).await {
             Ok(response) => {
@@ -185,13 +204,22 @@ impl NodeRpcClient {
         }
     }
 
-    /// Fetch blocks in a specific height range
+    /// `/blockchain/range` URL for one page of blocks starting at `from_height`
+    fn range_url(&self, from_height: u64) -> String {
+        format!(
+            "{}/blockchain/range?from={}&limit={}",
+            self.node_url, from_height, SYNC_PAGE_BLOCKS
+        )
+    }
+
+    /// Fetch up to SYNC_PAGE_BLOCKS blocks starting at `from_height`
     pub async fn fetch_blocks_range(
         &self,
         from_height: u64,
         existing_utxo_map: &mut std::collections::HashMap<(String, u32), primitive_types::U256>,
+        resolve_input: InputResolver<'_>,
     ) -> Result<(Vec<BlockInfo>, Vec<TransactionInfo>), String> {
-        let url = format!("{}/blockchain/range?from={}", self.node_url, from_height);
+        let url = self.range_url(from_height);
 
         match reqwest::get(&url).await {
             Ok(response) => match response.json::<serde_json::Value>().await {
@@ -201,8 +229,11 @@ impl NodeRpcClient {
                     {
                         match self.decode_blockchain(encoded_blockchain) {
                             Ok((blocks, raw_blocks)) => {
-                                let transactions =
-                                    self.extract_transactions(&raw_blocks, existing_utxo_map);
+                                let transactions = self.extract_transactions(
+                                    &raw_blocks,
+                                    existing_utxo_map,
+                                    resolve_input,
+                                );
                                 info!(
                                     "Fetched {} blocks (from height {}) and {} transactions from Node",
                                     blocks.len(),
@@ -236,12 +267,14 @@ impl NodeRpcClient {
         }
     }
 
-    /// Fetch full blockchain (direct DB, blocks + transactions)
+    /// Fetch the start of the chain (direct DB, blocks + transactions).
+    /// Only the first SYNC_PAGE_BLOCKS blocks; later syncs continue with fetch_blocks_range.
     pub async fn fetch_blockchain_with_transactions(
         &self,
         existing_utxo_map: &mut std::collections::HashMap<(String, u32), primitive_types::U256>,
+        resolve_input: InputResolver<'_>,
     ) -> Result<(Vec<BlockInfo>, Vec<TransactionInfo>), String> {
-        let url = format!("{}/blockchain/db", self.node_url);
+        let url = self.range_url(0);
 
         info!("Fetching blockchain from: {}", url);
 
@@ -273,8 +306,11 @@ impl NodeRpcClient {
 
                             match self.decode_blockchain(encoded_blockchain) {
                                 Ok((blocks, raw_blocks)) => {
-                                    let transactions =
-                                        self.extract_transactions(&raw_blocks, existing_utxo_map);
+                                    let transactions = self.extract_transactions(
+                                        &raw_blocks,
+                                        existing_utxo_map,
+                                        resolve_input,
+                                    );
                                     info!(
                                         "Fetched {} blocks and {} transactions from Node",
                                         blocks.len(),
@@ -353,11 +389,13 @@ impl NodeRpcClient {
     }
 
     /// Extract transaction info from blocks
-    /// Track UTXO state to calculate accurate fees
+    /// Track UTXO state to calculate accurate fees; inputs missing from the map
+    /// are looked up through `resolve_input` (outputs cached by earlier syncs)
     pub fn extract_transactions(
         &self,
         blocks: &[Block],
         existing_utxo_map: &mut std::collections::HashMap<(String, u32), U256>,
+        resolve_input: InputResolver<'_>,
     ) -> Vec<TransactionInfo> {
         let mut transactions = Vec::new();
 
@@ -374,211 +412,323 @@ impl NodeRpcClient {
         for block in &sorted_blocks {
             let timestamp = chrono::DateTime::<Utc>::from_timestamp(block.header.timestamp, 0)
                 .unwrap_or_else(|| Utc::now());
+            let height = block.header.index;
 
+            // Each tx is interpreted on its own: one that can't be makes an entry marked
+            // "malformed" instead of dropping the rest of the block
             for tx in &block.transactions {
-                let is_coinbase = tx.inputs.is_empty();
-
-                // Coinbase transaction: reward
-                if is_coinbase {
-                    // Reward tx: sum all outputs as total amount
-                    let total_amount = tx
-                        .outputs
-                        .iter()
-                        .fold(U256::zero(), |acc, out| acc + out.amount());
-                    let to_address = if tx.outputs.len() == 1 {
-                        tx.outputs[0].to.clone()
-                    } else {
-                        format!("{} recipients", tx.outputs.len())
-                    };
-
-                    transactions.push(TransactionInfo {
-                        hash: tx.eth_hash.clone(), // EVM hash
-                        txid: tx.txid.clone(),     // UTXO txid
-                        from: "Block_Reward".to_string(),
-                        to: to_address,
-                        amount: total_amount,
-                        fee: U256::zero(),
-                        total: total_amount,
-                        timestamp,
-                        block_height: Some(block.header.index),
-                        status: "confirmed".to_string(),
-                        confirmations: Some(0), // Will be calculated when queried
-                    });
-
-                    // Insert coinbase outputs into UTXO map
-                    for (vout, output) in tx.outputs.iter().enumerate() {
-                        existing_utxo_map.insert((tx.txid.clone(), vout as u32), output.amount());
-                    }
-                } else {
-                    // Standard tx: compute input/output sums and fee
-                    let from_pubkey = tx
-                        .inputs
-                        .first()
-                        .map(|i| i.pubkey.clone())
-                        .unwrap_or_else(|| "Unknown".to_string());
-
-                    // Convert pubkey to address (for change exclusion)
-                    let from_address = if from_pubkey != "Unknown" {
-                        Astram_core::crypto::eth_address_from_pubkey_hex(&from_pubkey)
-                            .unwrap_or_else(|_| from_pubkey.clone())
-                    } else {
-                        from_pubkey.clone()
-                    };
-
-                    // Sum inputs (lookup from UTXO map)
-                    let mut input_sum = U256::zero();
-                    let mut missing_inputs = 0;
-                    for (idx, input) in tx.inputs.iter().enumerate() {
-                        if let Some(amount) =
-                            existing_utxo_map.get(&(input.txid.clone(), input.vout))
-                        {
-                            input_sum += *amount;
-                        } else {
-                            missing_inputs += 1;
-                            // Log details for first 3 and last missing inputs
-                            if idx < 3 || idx == tx.inputs.len() - 1 {
-                                log::warn!(
-                                    "UTXO not found: {}:{} (input #{} of {})",
-                                    &input.txid[..8],
-                                    input.vout,
-                                    idx + 1,
-                                    tx.inputs.len()
-                                );
-                            }
+                let result =
+                    Self::transaction_info(tx, height, timestamp, existing_utxo_map, resolve_input);
+                for input in &tx.inputs {
+                    existing_utxo_map.remove(&(input.txid.clone(), input.vout));
+                }
+                let info = match result {
+                    Ok(info) => {
+                        for (vout, output) in tx.outputs.iter().enumerate() {
+                            existing_utxo_map
+                                .insert((tx.txid.clone(), vout as u32), output.amount());
                         }
+                        info
                     }
-
-                    // Summary log
-                    if missing_inputs > 0 {
+                    Err(reason) => {
                         log::warn!(
-                            "TX {}: Missing {}/{} inputs, UTXO map size: {}",
-                            &tx.txid[..8],
-                            missing_inputs,
-                            tx.inputs.len(),
-                            existing_utxo_map.len()
+                            "TX {} in block {}: {}; marking malformed",
+                            short(&tx.txid, 8),
+                            height,
+                            reason
                         );
+                        Self::malformed_transaction_info(tx, height, timestamp)
                     }
+                };
+                transactions.push(info);
+            }
+        }
 
-                    // Sum outputs
-                    let output_sum = tx
-                        .outputs
-                        .iter()
-                        .fold(U256::zero(), |acc, out| acc + out.amount());
-
-                    // Fee = input sum - output sum
-                    let fee = if input_sum >= output_sum {
-                        input_sum - output_sum
-                    } else {
-                        // Missing inputs: estimate fee by tx size
-                        if missing_inputs > 0 {
-                            // Measure actual size by serialization
-                            let tx_size = bincode::encode_to_vec(
-                                tx,
-                                Astram_core::blockchain::BINCODE_CONFIG.clone(),
-                            )
-                            .map(|bytes| bytes.len())
-                            .unwrap_or(300); // default 300 bytes
-
-                            // Astram fee policy: BASE_MIN_FEE + (size × MIN_RELAY_FEE_NAT_PER_BYTE)
-                            // 100 Twei + (size × 200 Gwei)
-                            let calculated_fee = U256::from(100_000_000_000_000u64)
-                                + U256::from(tx_size as u64) * U256::from(200_000_000_000u64);
-
-                            log::warn!(
-                                "TX {}: Estimated fee from size: {} bytes = {} natoshi ({} Twei)",
-                                &tx.txid[..8],
-                                tx_size,
-                                calculated_fee,
-                                calculated_fee / U256::from(1_000_000_000_000u64)
-                            );
-
-                            calculated_fee
-                        } else {
-                            U256::zero()
-                        }
-                    };
-
-                    // Exclude change outputs to compute actual transfer amount
-                    // Outputs to different addresses are treated as transfers
-                    let mut actual_transfer_amount = U256::zero();
-                    let mut recipient_addresses = Vec::new();
-
-                    for output in &tx.outputs {
-                        // Count outputs to other addresses only (exclude change)
-                        if output.to != from_address {
-                            actual_transfer_amount += output.amount();
-                            recipient_addresses.push(output.to.clone());
-                        }
-                    }
+        log::info!(
+            "Processed {} transactions, UTXO map contains {} entries",
+            transactions.len(),
+            existing_utxo_map.len()
+        );
 
-                    // If all outputs are to the sender, use total output sum
-                    let amount = if recipient_addresses.is_empty() {
-                        output_sum
-                    } else {
-                        actual_transfer_amount
-                    };
+        transactions
+    }
 
-                    let total = if input_sum > U256::zero() {
-                        input_sum
-                    } else {
-                        // Missing input sums: output + estimated fee
-                        output_sum + fee
-                    };
-
-                    let to_address = if recipient_addresses.len() == 1 {
-                        recipient_addresses[0].clone()
-                    } else if recipient_addresses.len() > 1 {
-                        format!("{} recipients", recipient_addresses.len())
-                    } else if tx.outputs.len() == 1 {
-                        tx.outputs[0].to.clone()
-                    } else {
-                        format!("{} outputs", tx.outputs.len())
-                    };
+    /// Interpret one confirmed transaction: sender, recipients, amount and fee. Inputs are
+    /// priced from `utxo_map` or `resolve_input`; the map isn't modified here.
+    fn transaction_info(
+        tx: &Astram_core::transaction::Transaction,
+        height: u64,
+        timestamp: chrono::DateTime<Utc>,
+        utxo_map: &std::collections::HashMap<(String, u32), U256>,
+        resolve_input: InputResolver<'_>,
+    ) -> Result<TransactionInfo, String> {
+        let is_coinbase = tx.inputs.is_empty();
+        if tx.outputs.is_empty() {
+            return Err("no outputs".to_string());
+        }
+        let output_sum = checked_output_sum(tx).ok_or("output amounts overflow")?;
+
+        // Coinbase transaction: reward
+        if is_coinbase {
+            let to_address = if tx.outputs.len() == 1 {
+                tx.outputs[0].to.clone()
+            } else {
+                format!("{} recipients", tx.outputs.len())
+            };
+
+            return Ok(TransactionInfo {
+                hash: tx.eth_hash.clone(), // EVM hash
+                txid: tx.txid.clone(),     // UTXO txid
+                from: "Block_Reward".to_string(),
+                to: to_address,
+                amount: output_sum,
+                fee: U256::zero(),
+                total: output_sum,
+                timestamp,
+                block_height: Some(height),
+                status: "confirmed".to_string(),
+                input_count: 0,
+                output_count: tx.outputs.len(),
+                confirmations: Some(0), // Will be calculated when queried
+            });
+        }
 
-                    log::info!(
-                        "TX {}: from_addr={}, outputs={}, actual_transfer={}, change_excluded={}, fee={}",
-                        &tx.txid[..8],
-                        &from_address[..10],
-                        output_sum,
-                        amount,
-                        output_sum - amount,
-                        fee
+        // Standard tx: compute input/output sums and fee
+        let from_pubkey = &tx.inputs[0].pubkey;
+        // Convert pubkey to address (for change exclusion)
+        let from_address = Astram_core::crypto::eth_address_from_pubkey_hex(from_pubkey)
+            .unwrap_or_else(|_| from_pubkey.clone());
+
+        // Sum inputs (lookup from UTXO map)
+        let mut input_sum = U256::zero();
+        let mut missing_inputs = 0;
+        for (idx, input) in tx.inputs.iter().enumerate() {
+            let amount = utxo_map
+                .get(&(input.txid.clone(), input.vout))
+                .copied()
+                .or_else(|| resolve_input(&input.txid, input.vout));
+            if let Some(amount) = amount {
+                input_sum = input_sum.saturating_add(amount);
+            } else {
+                missing_inputs += 1;
+                // Log details for first 3 and last missing inputs
+                if idx < 3 || idx == tx.inputs.len() - 1 {
+                    log::warn!(
+                        "UTXO not found: {}:{} (input #{} of {})",
+                        short(&input.txid, 8),
+                        input.vout,
+                        idx + 1,
+                        tx.inputs.len()
                     );
-
-                    transactions.push(TransactionInfo {
-                        hash: tx.eth_hash.clone(), // EVM hash
-                        txid: tx.txid.clone(),     // UTXO txid
-                        from: from_address,
-                        to: to_address,
-                        amount,
-                        fee,
-                        total,
-                        timestamp,
-                        block_height: Some(block.header.index),
-                        status: "confirmed".to_string(),
-                        confirmations: Some(0), // Will be calculated when queried
-                    });
-
-                    // Remove spent inputs from UTXO map
-                    for input in &tx.inputs {
-                        existing_utxo_map.remove(&(input.txid.clone(), input.vout));
-                    }
-
-                    // Add new outputs to UTXO map
-                    for (vout, output) in tx.outputs.iter().enumerate() {
-                        existing_utxo_map.insert((tx.txid.clone(), vout as u32), output.amount());
-                    }
                 }
             }
         }
 
+        // Summary log
+        if missing_inputs > 0 {
+            log::warn!(
+                "TX {}: Missing {}/{} inputs, UTXO map size: {}",
+                short(&tx.txid, 8),
+                missing_inputs,
+                tx.inputs.len(),
+                utxo_map.len()
+            );
+        }
+
+        // Fee = input sum - output sum
+        let fee = if input_sum >= output_sum {
+            input_sum - output_sum
+        } else if missing_inputs > 0 {
+            // Missing inputs: estimate fee by tx size
+            let tx_size =
+                bincode::encode_to_vec(tx, Astram_core::blockchain::BINCODE_CONFIG.clone())
+                    .map(|bytes| bytes.len())
+                    .unwrap_or(300); // default 300 bytes
+
+            // Astram fee policy: BASE_MIN_FEE + (size × MIN_RELAY_FEE_NAT_PER_BYTE)
+            // 100 Twei + (size × 200 Gwei)
+            let calculated_fee = U256::from(100_000_000_000_000u64)
+                + U256::from(tx_size as u64) * U256::from(200_000_000_000u64);
+
+            log::warn!(
+                "TX {}: Estimated fee from size: {} bytes = {} natoshi ({} Twei)",
+                short(&tx.txid, 8),
+                tx_size,
+                calculated_fee,
+                calculated_fee / U256::from(1_000_000_000_000u64)
+            );
+
+            calculated_fee
+        } else {
+            U256::zero()
+        };
+
+        // Exclude change outputs to compute actual transfer amount
+        // Outputs to different addresses are treated as transfers
+        let recipients: Vec<_> = tx
+            .outputs
+            .iter()
+            .filter(|output| output.to != from_address)
+            .collect();
+        // Can't overflow: a subset of outputs whose full sum was checked above
+        let actual_transfer_amount = recipients
+            .iter()
+            .fold(U256::zero(), |acc, output| acc + output.amount());
+
+        // If all outputs are to the sender, use total output sum
+        let amount = if recipients.is_empty() {
+            output_sum
+        } else {
+            actual_transfer_amount
+        };
+
+        let total = if input_sum > U256::zero() {
+            input_sum
+        } else {
+            // Missing input sums: output + estimated fee
+            output_sum.saturating_add(fee)
+        };
+
+        let to_address = if recipients.len() == 1 {
+            recipients[0].to.clone()
+        } else if recipients.len() > 1 {
+            format!("{} recipients", recipients.len())
+        } else if tx.outputs.len() == 1 {
+            tx.outputs[0].to.clone()
+        } else {
+            format!("{} outputs", tx.outputs.len())
+        };
+
         log::info!(
-            "Processed {} transactions, UTXO map contains {} entries",
-            transactions.len(),
-            existing_utxo_map.len()
+            "TX {}: from_addr={}, outputs={}, actual_transfer={}, change_excluded={}, fee={}",
+            short(&tx.txid, 8),
+            short(&from_address, 10),
+            output_sum,
+            amount,
+            output_sum.saturating_sub(amount),
+            fee
         );
 
-        transactions
+        Ok(TransactionInfo {
+            hash: tx.eth_hash.clone(), // EVM hash
+            txid: tx.txid.clone(),     // UTXO txid
+            from: from_address,
+            to: to_address,
+            amount,
+            fee,
+            total,
+            timestamp,
+            block_height: Some(height),
+            status: "confirmed".to_string(),
+            input_count: tx.inputs.len(),
+            output_count: tx.outputs.len(),
+            confirmations: Some(0), // Will be calculated when queried
+        })
+    }
+
+    /// Placeholder for a transaction that couldn't be interpreted: keeps it visible in
+    /// its block without guessing at amounts
+    fn malformed_transaction_info(
+        tx: &Astram_core::transaction::Transaction,
+        height: u64,
+        timestamp: chrono::DateTime<Utc>,
+    ) -> TransactionInfo {
+        TransactionInfo {
+            hash: tx.eth_hash.clone(),
+            txid: tx.txid.clone(),
+            from: if tx.inputs.is_empty() {
+                "Block_Reward".to_string()
+            } else {
+                "malformed".to_string()
+            },
+            to: "malformed".to_string(),
+            amount: U256::zero(),
+            fee: U256::zero(),
+            total: U256::zero(),
+            timestamp,
+            block_height: Some(height),
+            status: "malformed".to_string(),
+            input_count: tx.inputs.len(),
+            output_count: tx.outputs.len(),
+            confirmations: Some(0),
+        }
+    }
+
+    /// One page of an address's confirmed history from the node's address index
+    /// (`/address/{address}/transactions`), newest first, as the node returns it
+    pub async fn fetch_address_transactions(
+        &self,
+        address: &str,
+        cursor: Option<&str>,
+        limit: u64,
+    ) -> Result<serde_json::Value, String> {
+        let mut url = format!(
+            "{}/address/{}/transactions?limit={}",
+            self.node_url, address, limit
+        );
+        if let Some(cursor) = cursor {
+            url = format!("{}&cursor={}", url, cursor);
+        }
+        match reqwest::get(&url).await {
+            Ok(resp) if resp.status().is_success() => resp
+                .json::<serde_json::Value>()
+                .await
+                .map_err(|e| format!("Failed to parse address history response: {}", e)),
+            Ok(resp) => Err(format!(
+                "Node returned {} for address history",
+                resp.status()
+            )),
+            Err(e) => Err(format!("Network error fetching address history: {}", e)),
+        }
+    }
+
+    /// The node's pending transactions, from /mempool
+    pub async fn fetch_mempool(
+        &self,
+    ) -> Result<Vec<Astram_core::transaction::Transaction>, String> {
+        let url = format!("{}/mempool", self.node_url);
+        let v = reqwest::get(&url)
+            .await
+            .map_err(|e| format!("Network error fetching mempool: {}", e))?
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| format!("Failed to parse mempool response: {}", e))?;
+        let encoded = v
+            .get("transactions_b64")
+            .and_then(|t| t.as_str())
+            .ok_or("No transactions in mempool response")?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("Base64 decode error: {}", e))?;
+        bincode::decode_from_slice(&bytes, *BINCODE_CONFIG)
+            .map(|(txs, _)| txs)
+            .map_err(|e| format!("Bincode decode error: {}", e))
+    }
+
+    /// Look an Astram txid up in the node's transaction index and return its EVM hash,
+    /// the key the explorer DB stores transactions under. None if the node doesn't know it.
+    pub async fn fetch_transaction_eth_hash(&self, txid: &str) -> Result<Option<String>, String> {
+        let url = format!("{}/tx/{}", self.node_url, txid);
+        let resp = reqwest::get(&url)
+            .await
+            .map_err(|e| format!("Network error fetching transaction: {}", e))?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let v = resp
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| format!("Failed to parse transaction response: {}", e))?;
+        let Some(encoded) = v.get("transaction").and_then(|t| t.as_str()) else {
+            return Ok(None);
+        };
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("Base64 decode error: {}", e))?;
+        let (tx, _): (Astram_core::transaction::Transaction, usize) =
+            bincode::decode_from_slice(&bytes, *BINCODE_CONFIG)
+                .map_err(|e| format!("Bincode decode error: {}", e))?;
+        Ok(Some(tx.eth_hash))
     }
 
     /// Resolve Ethereum transaction hash to Astram txid
@@ -604,3 +754,108 @@ impl NodeRpcClient {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Astram_core::block::BlockHeader;
+    use Astram_core::transaction::{Transaction, TransactionInput, TransactionOutput};
+
+    fn block_with(transactions: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                index: 1,
+                previous_hash: "0".repeat(64),
+                merkle_root: String::new(),
+                timestamp: 1_700_000_000,
+                nonce: 0,
+                difficulty: 1,
+            },
+            transactions,
+            hash: "ab".repeat(32),
+        }
+    }
+
+    fn spend(prev: &str, outputs: Vec<TransactionOutput>) -> Transaction {
+        Transaction {
+            txid: "ff".to_string(), // shorter than the 8 chars logs slice
+            eth_hash: "0xff".to_string(),
+            inputs: vec![TransactionInput {
+                txid: prev.to_string(),
+                vout: 0,
+                pubkey: "bad".to_string(),
+                signature: None,
+            }],
+            outputs,
+            timestamp: 0,
+            valid_until: None,
+        }
+    }
+
+    #[test]
+    fn degenerate_transaction_is_marked_malformed() {
+        let client = NodeRpcClient::new("http://127.0.0.1:0");
+        let block = block_with(vec![spend("aa", vec![])]);
+        let mut utxos = std::collections::HashMap::new();
+
+        let txs = client.extract_transactions(&[block], &mut utxos, &|_, _| None);
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].status, "malformed");
+        assert_eq!(txs[0].output_count, 0);
+        assert_eq!(txs[0].input_count, 1);
+    }
+
+    #[test]
+    fn malformed_transaction_keeps_rest_of_block() {
+        let client = NodeRpcClient::new("http://127.0.0.1:0");
+        let coinbase = Transaction {
+            txid: "cb".repeat(32),
+            eth_hash: "0xcb".to_string(),
+            inputs: vec![],
+            outputs: vec![TransactionOutput::new(
+                "0xminer".to_string(),
+                U256::from(50u64),
+            )],
+            timestamp: 0,
+            valid_until: None,
+        };
+        let overflowing = vec![
+            TransactionOutput::new("0xa".to_string(), U256::MAX),
+            TransactionOutput::new("0xb".to_string(), U256::from(1u64)),
+        ];
+        let mut bad = spend("aa", overflowing);
+        bad.txid = "bad".to_string();
+        let good = spend(
+            &"cb".repeat(32),
+            vec![TransactionOutput::new(
+                "0xbeef".to_string(),
+                U256::from(40u64),
+            )],
+        );
+        let block = block_with(vec![coinbase, bad, good]);
+        let mut utxos = std::collections::HashMap::new();
+
+        let txs = client.extract_transactions(&[block], &mut utxos, &|_, _| None);
+        let statuses: Vec<&str> = txs.iter().map(|t| t.status.as_str()).collect();
+        assert_eq!(statuses, ["confirmed", "malformed", "confirmed"]);
+        // the tx after the bad one still prices its input from the coinbase output
+        assert_eq!(txs[2].fee, U256::from(10u64));
+        // a malformed tx's outputs are never treated as spendable
+        assert!(!utxos.contains_key(&("bad".to_string(), 0)));
+    }
+
+    #[test]
+    fn fee_uses_resolved_input_amount() {
+        let client = NodeRpcClient::new("http://127.0.0.1:0");
+        let out = TransactionOutput::new("0xbeef".to_string(), U256::from(90u64));
+        let block = block_with(vec![spend("cached", vec![out])]);
+        let mut utxos = std::collections::HashMap::new();
+
+        let resolver =
+            |txid: &str, vout: u32| (txid == "cached" && vout == 0).then(|| U256::from(100u64));
+        let txs = client.extract_transactions(&[block], &mut utxos, &resolver);
+        assert_eq!(txs[0].status, "confirmed");
+        assert_eq!(txs[0].fee, U256::from(10u64));
+        assert_eq!(txs[0].amount, U256::from(90u64));
+    }
+}
@@ -0,0 +1,114 @@
+// Explorer runtime settings from environment variables, with localhost defaults
+use astram_config::ports::Ports;
+use std::time::Duration;
+
+/// Default time between syncs while the node is reachable
+pub const DEFAULT_SYNC_INTERVAL_SECS: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplorerSettings {
+    /// Node HTTP API the explorer indexes from (`NODE_RPC_URL`)
+    pub node_url: String,
+    /// Address the explorer API listens on (`EXPLORER_BIND`)
+    pub bind: String,
+    /// Time between syncs while the node is reachable (`EXPLORER_SYNC_INTERVAL_SECS`)
+    pub sync_interval: Duration,
+}
+
+impl Default for ExplorerSettings {
+    fn default() -> Self {
+        let ports = Ports::default();
+        Self {
+            node_url: ports.node_http_url(),
+            bind: format!("0.0.0.0:{}", ports.explorer),
+            sync_interval: Duration::from_secs(DEFAULT_SYNC_INTERVAL_SECS),
+        }
+    }
+}
+
+impl ExplorerSettings {
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    /// Settings from `var` lookups; unset or empty keys keep their defaults. A node URL
+    /// that isn't an http(s) URL or a bad interval is an error rather than a silent default.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let var = |key: &str| var(key).filter(|v| !v.trim().is_empty());
+        let mut settings = Self::default();
+
+        if let Some(url) = var("NODE_RPC_URL") {
+            let parsed = reqwest::Url::parse(url.trim())
+                .map_err(|e| format!("NODE_RPC_URL '{}' is not a valid URL: {}", url, e))?;
+            if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+                return Err(format!(
+                    "NODE_RPC_URL '{}' must be an http:// or https:// URL with a host",
+                    url
+                ));
+            }
+            settings.node_url = url.trim().trim_end_matches('/').to_string();
+        }
+        if let Some(bind) = var("EXPLORER_BIND") {
+            settings.bind = bind.trim().to_string();
+        }
+        if let Some(secs) = var("EXPLORER_SYNC_INTERVAL_SECS") {
+            match secs.trim().parse::<u64>() {
+                Ok(secs) if secs > 0 => settings.sync_interval = Duration::from_secs(secs),
+                _ => {
+                    return Err(format!(
+                        "EXPLORER_SYNC_INTERVAL_SECS '{}' must be a positive number of seconds",
+                        secs
+                    ));
+                }
+            }
+        }
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn settings(vars: &[(&str, &str)]) -> Result<ExplorerSettings, String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ExplorerSettings::from_vars(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn defaults_match_the_shared_ports() {
+        let defaults = settings(&[]).unwrap();
+        assert!(
+            defaults
+                .node_url
+                .ends_with(&format!(":{}", Ports::DEFAULT.http_rpc))
+        );
+        assert_eq!(
+            defaults.bind,
+            format!("0.0.0.0:{}", Ports::DEFAULT.explorer)
+        );
+        assert_eq!(defaults.sync_interval, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn environment_overrides_are_validated() {
+        let custom = settings(&[
+            ("NODE_RPC_URL", "https://node.example.org:19533/"),
+            ("EXPLORER_BIND", "127.0.0.1:9090"),
+            ("EXPLORER_SYNC_INTERVAL_SECS", "30"),
+        ])
+        .unwrap();
+        assert_eq!(custom.node_url, "https://node.example.org:19533");
+        assert_eq!(custom.bind, "127.0.0.1:9090");
+        assert_eq!(custom.sync_interval, Duration::from_secs(30));
+
+        assert!(settings(&[("NODE_RPC_URL", "127.0.0.1:19533")]).is_err());
+        assert!(settings(&[("NODE_RPC_URL", "ftp://node.example.org")]).is_err());
+        assert!(settings(&[("EXPLORER_SYNC_INTERVAL_SECS", "0")]).is_err());
+        assert!(settings(&[("EXPLORER_SYNC_INTERVAL_SECS", "soon")]).is_err());
+    }
+}
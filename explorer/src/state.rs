@@ -40,7 +40,11 @@ pub struct TransactionInfo {
     pub total: U256, // 총액 (amount + fee)
     pub timestamp: DateTime<Utc>,
     pub block_height: Option<u64>,
-    pub status: String, // "confirmed", "pending"
+    pub status: String, // "confirmed", "pending", "malformed"
+    #[serde(default)]
+    pub input_count: usize,
+    #[serde(default)]
+    pub output_count: usize,
     /// Number of confirmations (None if pending, Some(n) if in a block)
     /// 0 = just mined, 6+ = safe/confirmed
     pub confirmations: Option<u64>,
@@ -59,6 +63,25 @@ pub struct AddressInfo {
     pub last_transaction: Option<DateTime<Utc>>,
 }
 
+/// The sync task's view of the node, reported by /api/health and /api/stats so the UI
+/// can warn that data may be stale while the node is unreachable
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub node_connected: bool,
+    pub last_successful_sync: Option<DateTime<Utc>>,
+}
+
+impl SyncStatus {
+    pub fn record_success(&mut self) {
+        self.node_connected = true;
+        self.last_successful_sync = Some(Utc::now());
+    }
+
+    pub fn record_failure(&mut self) {
+        self.node_connected = false;
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockchainStats {
     pub total_blocks: u64,
@@ -69,4 +92,6 @@ pub struct BlockchainStats {
     pub average_block_size: usize,
     pub current_difficulty: u32,
     pub network_hashrate: String,
+    #[serde(flatten)]
+    pub sync: SyncStatus,
 }
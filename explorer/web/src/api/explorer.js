@@ -56,4 +56,17 @@ export const explorerAPI = {
   getNodeStatus() {
     return axios.get(`${API_BASE_URL}/node/status`)
   },
+
+  // Live updates: onEvent gets each pushed sync event ({ type, tip_height, latest_block, ... })
+  openLiveUpdates(onEvent) {
+    const socket = new WebSocket(`${API_BASE_URL.replace(/^http/, 'ws')}/ws`)
+    socket.onmessage = (message) => {
+      try {
+        onEvent(JSON.parse(message.data))
+      } catch (error) {
+        console.error('Bad live update:', error)
+      }
+    }
+    return socket
+  },
 }
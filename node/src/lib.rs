@@ -1,5 +1,6 @@
 pub mod p2p;
 pub mod server;
+pub mod settings;
 
 pub use crate::p2p::manager::PeerManager;
 pub use server::*;
@@ -15,6 +16,47 @@ pub struct NodeHandles {
     pub mempool: Arc<Mutex<MempoolState>>,
     /// Maps Ethereum transaction hash to Astram UTXO txid (for MetaMask compatibility)
     pub mining: Arc<MiningState>,
+    /// New-block / new-pending-tx notifications (eth_subscribe)
+    pub events: tokio::sync::broadcast::Sender<NodeEvent>,
+}
+
+/// Size of the node event broadcast buffer; slow subscribers skip what they missed
+pub const NODE_EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum NodeEvent {
+    /// A block was committed to the local chain
+    NewBlock(Block),
+    /// A transaction was accepted into the mempool
+    NewPendingTx(Transaction),
+}
+
+impl NodeHandles {
+    /// Publish a committed block. No subscribers is not an error.
+    pub fn notify_block(&self, block: &Block) {
+        let _ = self.events.send(NodeEvent::NewBlock(block.clone()));
+    }
+
+    /// Publish a transaction accepted into the mempool
+    pub fn notify_tx(&self, tx: &Transaction) {
+        let _ = self.events.send(NodeEvent::NewPendingTx(tx.clone()));
+    }
+
+    /// Fees of the pending transactions `tx` double-spends, for
+    /// `MempoolState::replace_conflicting`. Call without holding either lock: the
+    /// conflicts come from the mempool, their fees from bc, which must not be locked
+    /// under the mempool.
+    pub fn conflict_fees(&self, tx: &Transaction) -> HashMap<String, primitive_types::U256> {
+        let conflicts = self.mempool.lock().unwrap().conflicts_with(tx);
+        if conflicts.is_empty() {
+            return HashMap::new();
+        }
+        let bc = self.bc.lock().unwrap();
+        conflicts
+            .iter()
+            .map(|c| (c.txid.clone(), confirmed_input_fee(&bc, c)))
+            .collect()
+    }
 }
 
 // Lock order (when nested): bc -> chain -> mempool -> mining -> meta.
@@ -22,9 +64,9 @@ pub struct NodeHandles {
 pub struct ChainState {
     pub blockchain: Vec<Block>,
     /// Orphan blocks pool: blocks waiting for their parent
-    /// Key: block hash, Value: (block, received_timestamp)
+    /// Key: block hash, Value: (block, received_timestamp, sending peer)
     /// Security: Limited to MAX_ORPHAN_BLOCKS to prevent memory exhaustion attacks
-    pub orphan_blocks: HashMap<String, (Block, i64)>,
+    pub orphan_blocks: HashMap<String, (Block, i64, Option<String>)>,
     /// Recently mined block hashes (to ignore when received from peers)
     /// Key: block hash, Value: timestamp when mined
     pub recently_mined_blocks: HashMap<String, i64>,
@@ -47,7 +89,107 @@ pub struct NodeMeta {
     pub my_public_address: Arc<Mutex<Option<String>>>,
     pub node_start_time: std::time::Instant,
     /// Maps Ethereum transaction hash to Astram UTXO txid (for MetaMask compatibility)
-    pub eth_to_astram_tx: Arc<Mutex<HashMap<String, String>>>,
+    pub eth_to_astram_tx: Arc<Mutex<EthTxMap>>,
+}
+
+/// Ethereum hash -> Astram txid mappings kept for MetaMask lookups
+pub const MAX_ETH_TX_MAPPINGS: usize = 10_000;
+/// Confirmations after which a mapping is dropped and the `eb:` DB index answers instead
+pub const ETH_TX_MAPPING_DEPTH: u64 = 6;
+
+/// Ethereum hash -> Astram txid for transactions sent through eth_sendRawTransaction,
+/// needed until they are mined and indexed. Holds at most `capacity` entries; the
+/// oldest go first, whether dropped once deeply confirmed or evicted when full.
+pub struct EthTxMap {
+    txids: HashMap<String, String>,
+    /// Keys of `txids`, oldest first
+    order: std::collections::VecDeque<String>,
+    capacity: usize,
+}
+
+impl Default for EthTxMap {
+    fn default() -> Self {
+        Self::with_capacity(MAX_ETH_TX_MAPPINGS)
+    }
+}
+
+impl EthTxMap {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            txids: HashMap::new(),
+            order: std::collections::VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Hashes are stored lowercase without 0x, however the caller spells them
+    fn key(eth_hash: &str) -> String {
+        eth_hash
+            .strip_prefix("0x")
+            .unwrap_or(eth_hash)
+            .to_lowercase()
+    }
+
+    pub fn insert(&mut self, eth_hash: &str, txid: &str) {
+        let key = Self::key(eth_hash);
+        if self.txids.insert(key.clone(), txid.to_string()).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.txids.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get(&self, eth_hash: &str) -> Option<&String> {
+        self.txids.get(&Self::key(eth_hash))
+    }
+
+    pub fn len(&self) -> usize {
+        self.txids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txids.is_empty()
+    }
+
+    /// Drop mappings from the oldest end while `is_settled(txid)` says the DB index can
+    /// answer for them, stopping at the first that can't. Returns how many were dropped.
+    pub fn drop_settled(&mut self, is_settled: impl Fn(&str) -> bool) -> usize {
+        let mut dropped = 0;
+        while let Some(oldest) = self.order.front() {
+            if !self.txids.get(oldest).is_some_and(|txid| is_settled(txid)) {
+                break;
+            }
+            if let Some(key) = self.order.pop_front() {
+                self.txids.remove(&key);
+            }
+            dropped += 1;
+        }
+        dropped
+    }
+}
+
+/// Whether `txid` is on the main chain with at least `depth` confirmations
+pub fn confirmed_at_depth(bc: &Blockchain, txid: &str, depth: u64) -> bool {
+    let Ok(Some(height)) = bc.get_transaction_height(txid) else {
+        return false;
+    };
+    let next_index = bc.get_next_index().unwrap_or(0);
+    next_index.saturating_sub(height) >= depth
+}
+
+/// Astram txid for an Ethereum transaction hash: from `map` while it's pending or
+/// shallow, otherwise from the `eb:` DB index
+pub fn resolve_eth_hash(bc: &Blockchain, map: &EthTxMap, eth_hash: &str) -> Option<String> {
+    if let Some(txid) = map.get(eth_hash) {
+        return Some(txid.clone());
+    }
+    bc.get_transaction_by_eth_hash(eth_hash)
+        .ok()
+        .flatten()
+        .map(|(tx, _)| tx.txid)
 }
 
 pub struct MiningState {
@@ -58,6 +200,9 @@ pub struct MiningState {
     pub current_difficulty: Arc<Mutex<u32>>,
     pub current_hashrate: Arc<Mutex<f64>>,
     pub blocks_mined: Arc<std::sync::atomic::AtomicU64>,
+    /// Configured CPU duty cycle in percent (100 = unthrottled); `current_hashrate` is
+    /// measured after throttling
+    pub throttle_percent: Arc<std::sync::atomic::AtomicU8>,
 }
 
 impl Default for MiningState {
@@ -68,6 +213,7 @@ impl Default for MiningState {
             current_difficulty: Arc::new(Mutex::new(1)),
             current_hashrate: Arc::new(Mutex::new(0.0)),
             blocks_mined: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            throttle_percent: Arc::new(std::sync::atomic::AtomicU8::new(100)),
         }
     }
 }
@@ -77,6 +223,16 @@ pub struct MempoolState {
     /// Seen transactions with timestamp (to prevent relay loops and track when seen)
     /// Key: txid, Value: timestamp when first seen
     pub seen_tx: HashMap<String, i64>,
+    /// Txids sent through eth_sendRawTransaction that are not yet mined or expired,
+    /// keyed by lowercase sender address; they count toward the "pending" nonce
+    pub pending_nonces: HashMap<String, Vec<String>>,
+    /// Fee (ram per byte of the replacement) a replace-by-fee transaction must add on
+    /// top of the fees of the transactions it evicts
+    pub rbf_fee_increment_per_byte: u64,
+    /// Relay floor (ram per byte); admission requires it on top of `calculate_min_fee`
+    pub min_relay_fee_per_byte: u64,
+    /// Encoded size of each pending transaction by txid, kept by `pending_bytes`
+    pub tx_sizes: HashMap<String, usize>,
 }
 
 impl Default for MempoolState {
@@ -84,6 +240,10 @@ impl Default for MempoolState {
         Self {
             pending: Vec::new(),
             seen_tx: HashMap::new(),
+            pending_nonces: HashMap::new(),
+            rbf_fee_increment_per_byte: DEFAULT_RBF_FEE_INCREMENT_PER_BYTE,
+            min_relay_fee_per_byte: MIN_RELAY_FEE_PER_BYTE,
+            tx_sizes: HashMap::new(),
         }
     }
 }
@@ -97,11 +257,246 @@ pub const ORPHAN_TIMEOUT: i64 = 1800; // 30 minutes - orphans older than this ar
 pub const MAX_MEMPOOL_SIZE: usize = 10000; // Maximum transactions in mempool
 pub const MAX_MEMPOOL_BYTES: usize = 300_000_000; // 300MB max mempool size
 pub const MEMPOOL_EXPIRY_TIME: i64 = 86400; // 24 hours - old transactions expire
-pub const MIN_RELAY_FEE_PER_BYTE: u64 = 1_000_000; // 1 Gwei per byte minimum
+/// Default relay floor: the per-byte rate `calculate_min_fee` already charges
+/// (`MIN_RELAY_FEE_NAT_PER_BYTE`); configure a higher value to tighten admission
+pub const MIN_RELAY_FEE_PER_BYTE: u64 = 200_000_000_000;
+/// Default replace-by-fee increment: a replacement pays for its own bytes at the
+/// minimum relay rate (`MIN_RELAY_FEE_NAT_PER_BYTE`) on top of what it replaces
+pub const DEFAULT_RBF_FEE_INCREMENT_PER_BYTE: u64 = 200_000_000_000;
+/// Confirmations below which a UTXO is flagged as not recommended to spend (policy, not consensus)
+pub const DEFAULT_MIN_CONFIRMATIONS_FOR_SPEND: u64 = 6;
+/// Transaction bytes a block is assumed to carry when estimating confirmation time
+pub const TARGET_BLOCK_TX_BYTES: usize = 1_000_000;
+
+/// Where a pending transaction sits in the fee-rate ordered mempool
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolPosition {
+    /// 1-based rank by fee rate (1 = mined first)
+    pub rank: usize,
+    /// Bytes of this and every better-paying transaction
+    pub bytes_through: usize,
+    /// Blocks until confirmation if each carries `block_bytes` of the best-paying txs
+    pub estimated_blocks: u64,
+}
+
+/// Rank `txid` among `entries` (txid, fee per byte, size) by fee rate, highest first
+/// (ties by txid), and estimate how many blocks of `block_bytes` it takes to reach it.
+/// Returns None if `txid` isn't among the entries.
+pub fn mempool_position(
+    entries: &[(String, primitive_types::U256, usize)],
+    txid: &str,
+    block_bytes: usize,
+) -> Option<MempoolPosition> {
+    let mut ordered: Vec<_> = entries.iter().collect();
+    ordered.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut bytes_through = 0;
+    for (i, (id, _, size)) in ordered.into_iter().enumerate() {
+        bytes_through += size;
+        if id == txid {
+            return Some(MempoolPosition {
+                rank: i + 1,
+                bytes_through,
+                estimated_blocks: bytes_through.div_ceil(block_bytes.max(1)).max(1) as u64,
+            });
+        }
+    }
+    None
+}
+
+/// Lowest fee rate at which a new transaction of `tx_bytes` would still make the next
+/// block of `block_bytes`, given the pending `entries` (txid, fee per byte, size). Zero
+/// when the mempool leaves enough room for it anyway.
+pub fn next_block_fee_rate(
+    entries: &[(String, primitive_types::U256, usize)],
+    tx_bytes: usize,
+    block_bytes: usize,
+) -> primitive_types::U256 {
+    let room = block_bytes.saturating_sub(tx_bytes);
+    let mut rates: Vec<_> = entries
+        .iter()
+        .map(|(_, rate, size)| (*rate, *size))
+        .collect();
+    rates.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let mut bytes_through = 0;
+    for (rate, size) in rates {
+        bytes_through += size;
+        if bytes_through > room {
+            // Outbid the first transaction that would push ours out
+            return rate.saturating_add(primitive_types::U256::one());
+        }
+    }
+    primitive_types::U256::zero()
+}
+
+/// Percentiles of pending fee rates reported by `/mempool/feerates`
+pub const FEE_RATE_PERCENTILES: [u8; 5] = [10, 25, 50, 75, 90];
+
+/// Fee rate at each of `percentiles` (nearest rank, lowest rate = 0th) across the
+/// pending `entries` (txid, fee per byte, size). An empty mempool reports `floor`
+/// for every percentile.
+pub fn fee_rate_percentiles(
+    entries: &[(String, primitive_types::U256, usize)],
+    percentiles: &[u8],
+    floor: primitive_types::U256,
+) -> Vec<primitive_types::U256> {
+    let mut rates: Vec<_> = entries.iter().map(|(_, rate, _)| *rate).collect();
+    if rates.is_empty() {
+        return vec![floor; percentiles.len()];
+    }
+    rates.sort();
+    percentiles
+        .iter()
+        .map(|p| {
+            let rank = (usize::from((*p).min(100)) * rates.len()).div_ceil(100);
+            rates[rank.saturating_sub(1)]
+        })
+        .collect()
+}
+
+/// Connect the buffered orphans descending from `parent_hash`, a block just added to
+/// the DB. Each child that validates has its own children tried next, so a whole
+/// buffered chain is promoted in one pass. Returns the connected blocks, parents before
+/// children, for the caller's per-block follow-up (height, events, mempool, reorg).
+/// A child that fails validation is dropped, and reported to `on_rejected` with the
+/// peer that sent it and whether it built on the tip; its descendants wait out
+/// ORPHAN_TIMEOUT. Children already in the DB are skipped.
+pub fn connect_orphans(
+    bc: &mut Blockchain,
+    chain: &mut ChainState,
+    parent_hash: &str,
+    mut on_rejected: impl FnMut(&str, &anyhow::Error, bool),
+) -> Vec<Block> {
+    let mut connected = Vec::new();
+    let mut parents = vec![parent_hash.to_string()];
+    while let Some(parent) = parents.pop() {
+        for (block, from) in chain.take_orphan_children(&parent) {
+            if matches!(bc.load_header(&block.hash), Ok(Some(_))) {
+                continue;
+            }
+            let extends_tip = bc.chain_tip.as_deref() == Some(block.header.previous_hash.as_str());
+            match bc.validate_and_insert_block(&block) {
+                Ok(()) => {
+                    log::info!(
+                        "[OK] Orphan block connected: index={} hash={}",
+                        block.header.index,
+                        &block.hash[..16]
+                    );
+                    chain.push_block(&block);
+                    parents.push(block.hash.clone());
+                    connected.push(block);
+                }
+                Err(e) => {
+                    log::warn!(
+                        "[WARN] Orphan block rejected: index={} hash={}: {}",
+                        block.header.index,
+                        &block.hash[..16],
+                        e
+                    );
+                    if let Some(from) = from {
+                        on_rejected(&from, &e, extends_tip);
+                    }
+                }
+            }
+        }
+    }
+    connected
+}
+
+/// Fee paid by `tx`, counting only inputs already in the UTXO set (a parent still in
+/// the mempool contributes nothing, which only makes the estimate more conservative)
+pub fn confirmed_input_fee(bc: &Blockchain, tx: &Transaction) -> primitive_types::U256 {
+    use Astram_core::transaction::BINCODE_CONFIG;
+    use Astram_core::utxo::Utxo;
+
+    let input_sum = Astram_core::units::checked_sum(tx.inputs.iter().filter_map(|inp| {
+        let blob = bc
+            .db
+            .get(format!("u:{}:{}", inp.txid, inp.vout).as_bytes())
+            .ok()??;
+        bincode::decode_from_slice::<Utxo, _>(&blob, *BINCODE_CONFIG)
+            .ok()
+            .map(|(utxo, _)| utxo.amount())
+    }));
+    input_sum
+        .and_then(|sum| tx.fee(sum))
+        .unwrap_or_else(primitive_types::U256::zero)
+}
 
 pub type NodeHandle = Arc<NodeHandles>;
 
 impl ChainState {
+    /// Append a block that was just committed to the DB to the in-memory view.
+    /// Callers hold the bc lock across the DB insert and this call, so the two views
+    /// move together. Returns false if the block is already present.
+    pub fn push_block(&mut self, block: &Block) -> bool {
+        if self.blockchain.iter().rev().any(|b| b.hash == block.hash) {
+            return false;
+        }
+        self.blockchain.push(block.clone());
+        self.enforce_memory_limit(); // Security: Enforce memory limit
+        true
+    }
+
+    /// Buffer a block whose parent hasn't arrived yet, remembering the peer it came
+    /// `from` (None for our own). Orphans held longer than ORPHAN_TIMEOUT go first; if
+    /// the pool is still full the oldest makes room.
+    pub fn add_orphan(&mut self, block: Block, from: Option<&str>, now: i64) {
+        self.expire_orphans(now);
+        if self.orphan_blocks.len() >= MAX_ORPHAN_BLOCKS
+            && !self.orphan_blocks.contains_key(&block.hash)
+        {
+            let oldest = self
+                .orphan_blocks
+                .iter()
+                .min_by_key(|(_, (_, received, _))| *received)
+                .map(|(hash, _)| hash.clone());
+            if let Some(hash) = oldest {
+                log::warn!(
+                    "[WARN] Orphan pool full ({} blocks), dropping oldest orphan {}",
+                    self.orphan_blocks.len(),
+                    &hash[..16]
+                );
+                self.orphan_blocks.remove(&hash);
+            }
+        }
+        let from = from.map(str::to_string);
+        self.orphan_blocks
+            .insert(block.hash.clone(), (block, now, from));
+    }
+
+    /// Drop orphans buffered ORPHAN_TIMEOUT or more seconds ago
+    pub fn expire_orphans(&mut self, now: i64) {
+        self.orphan_blocks
+            .retain(|_, (_, received, _)| now - *received < ORPHAN_TIMEOUT);
+    }
+
+    /// Remove and return the buffered orphans whose parent is `parent_hash`, each with
+    /// the peer that sent it
+    fn take_orphan_children(&mut self, parent_hash: &str) -> Vec<(Block, Option<String>)> {
+        let children: Vec<String> = self
+            .orphan_blocks
+            .iter()
+            .filter(|(_, (block, _, _))| block.header.previous_hash == parent_hash)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        children
+            .iter()
+            .filter_map(|hash| self.orphan_blocks.remove(hash))
+            .map(|(block, _, from)| (block, from))
+            .collect()
+    }
+
+    /// Remember a locally mined block so the copy echoed back by peers is ignored
+    pub fn mark_mined(&mut self, hash: &str, now: i64) {
+        self.recently_mined_blocks.insert(hash.to_string(), now);
+
+        // Clean up old entries (older than 5 minutes)
+        self.recently_mined_blocks
+            .retain(|_, &mut timestamp| now - timestamp < 300);
+    }
+
     /// Security: Enforce memory block limit by removing oldest blocks
     /// Keeps only the most recent MAX_MEMORY_BLOCKS in memory
     pub fn enforce_memory_limit(&mut self) {
@@ -126,6 +521,225 @@ impl ChainState {
 }
 
 impl MempoolState {
+    /// Least fee a transaction of `tx_size` bytes must pay to be admitted: the
+    /// consensus `calculate_min_fee`, or the configured relay floor if that is higher
+    pub fn min_relay_fee(&self, tx_size: usize) -> primitive_types::U256 {
+        let size = primitive_types::U256::from(tx_size);
+        Astram_core::config::calculate_min_fee(tx_size)
+            .max(primitive_types::U256::from(self.min_relay_fee_per_byte) * size)
+    }
+
+    /// Total encoded size of the pending transactions. Sizes are remembered by txid,
+    /// so only transactions admitted since the last call are encoded.
+    pub fn pending_bytes(&mut self) -> usize {
+        let mut total = 0;
+        for tx in &self.pending {
+            total += match self.tx_sizes.get(&tx.txid) {
+                Some(size) => *size,
+                None => {
+                    let size =
+                        bincode::encode_to_vec(tx, Astram_core::blockchain::BINCODE_CONFIG.clone())
+                            .map(|bytes| bytes.len())
+                            .unwrap_or(0);
+                    self.tx_sizes.insert(tx.txid.clone(), size);
+                    size
+                }
+            };
+        }
+        // Forget what has left the mempool
+        if self.tx_sizes.len() > self.pending.len() {
+            let pending: std::collections::HashSet<&str> =
+                self.pending.iter().map(|tx| tx.txid.as_str()).collect();
+            self.tx_sizes
+                .retain(|txid, _| pending.contains(txid.as_str()));
+        }
+        total
+    }
+
+    /// Per-byte rate of the cheapest admissible transaction, as reported to wallets
+    pub fn min_relay_fee_rate(&self) -> primitive_types::U256 {
+        Astram_core::config::MIN_RELAY_FEE_NAT_PER_BYTE
+            .max(primitive_types::U256::from(self.min_relay_fee_per_byte))
+    }
+
+    /// Count `txid` toward `address`'s pending nonce until it is mined or expires
+    pub fn record_pending_nonce(&mut self, address: &str, txid: &str) {
+        let txids = self
+            .pending_nonces
+            .entry(address.to_lowercase())
+            .or_default();
+        if !txids.iter().any(|t| t == txid) {
+            txids.push(txid.to_string());
+        }
+    }
+
+    /// Transactions from `address` accepted but not yet mined
+    pub fn pending_nonce_count(&self, address: &str) -> u64 {
+        self.pending_nonces
+            .get(&address.to_lowercase())
+            .map_or(0, |txids| txids.len() as u64)
+    }
+
+    /// Forget transactions already taken out of `pending` (the miner drops expired ones
+    /// itself): they leave seen_tx and stop counting toward a pending nonce
+    pub fn forget(&mut self, txids: &[&str]) {
+        for txid in txids {
+            self.seen_tx.remove(*txid);
+        }
+        Self::release_nonces(&mut self.pending_nonces, |txid| !txids.contains(&txid));
+    }
+
+    /// Keep only the pending-nonce txids for which `keep` holds
+    fn release_nonces(
+        pending_nonces: &mut HashMap<String, Vec<String>>,
+        keep: impl Fn(&str) -> bool,
+    ) {
+        pending_nonces.retain(|_, txids| {
+            txids.retain(|txid| keep(txid));
+            !txids.is_empty()
+        });
+    }
+
+    /// Pending transactions (other than `tx` itself) spending an outpoint `tx` spends
+    pub fn conflicts_with(&self, tx: &Transaction) -> Vec<Transaction> {
+        let spends: std::collections::HashSet<(&str, u32)> = tx
+            .inputs
+            .iter()
+            .map(|inp| (inp.txid.as_str(), inp.vout))
+            .collect();
+        self.pending
+            .iter()
+            .filter(|pending| pending.txid != tx.txid)
+            .filter(|pending| {
+                pending
+                    .inputs
+                    .iter()
+                    .any(|inp| spends.contains(&(inp.txid.as_str(), inp.vout)))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Evict the pending transactions `tx` double-spends, provided `tx` (paying `fee`,
+    /// `tx_size` bytes) outbids them: at least their combined fee plus
+    /// `rbf_fee_increment_per_byte` for each of its bytes, and strictly more in any
+    /// case. `fees` holds the fee of every transaction `conflicts_with` returned, looked
+    /// up before the mempool lock was taken; a conflict missing from it is refused.
+    ///
+    /// The evicted txids leave `pending` and the pending nonces but stay in `seen_tx`,
+    /// so copies echoed back by peers are neither re-accepted nor relayed again.
+    /// Returns them (empty when nothing conflicts); nothing changes on Err. A `tx`
+    /// already past its `valid_until` at `now` is refused outright.
+    pub fn replace_conflicting(
+        &mut self,
+        tx: &Transaction,
+        fee: primitive_types::U256,
+        tx_size: usize,
+        fees: &HashMap<String, primitive_types::U256>,
+        now: i64,
+    ) -> Result<Vec<String>, String> {
+        if tx.is_expired_at(now) {
+            return Err(format!(
+                "Transaction expired: valid until {}",
+                tx.valid_until.unwrap_or_default()
+            ));
+        }
+        let replaced = self.check_replacement(tx, fee, tx_size, fees)?;
+        if replaced.is_empty() {
+            return Ok(replaced);
+        }
+
+        self.pending
+            .retain(|pending| !replaced.contains(&pending.txid));
+        for txid in &replaced {
+            self.seen_tx.insert(txid.clone(), now);
+        }
+        Self::release_nonces(&mut self.pending_nonces, |txid| {
+            !replaced.iter().any(|r| r == txid)
+        });
+        Ok(replaced)
+    }
+
+    /// The pending txids `replace_conflicting` would evict for `tx`, or why it would
+    /// refuse it, without changing the mempool
+    pub fn check_replacement(
+        &self,
+        tx: &Transaction,
+        fee: primitive_types::U256,
+        tx_size: usize,
+        fees: &HashMap<String, primitive_types::U256>,
+    ) -> Result<Vec<String>, String> {
+        use primitive_types::U256;
+
+        let conflicts = self.conflicts_with(tx);
+        if conflicts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut replaced_fees = U256::zero();
+        for conflict in &conflicts {
+            let conflict_fee = fees.get(&conflict.txid).ok_or_else(|| {
+                format!(
+                    "Double-spend: conflicts with pending TX {} whose fee is unknown",
+                    conflict.txid
+                )
+            })?;
+            replaced_fees = replaced_fees.saturating_add(*conflict_fee);
+        }
+        let increment = U256::from(self.rbf_fee_increment_per_byte)
+            .saturating_mul(U256::from(tx_size))
+            .max(U256::one());
+        let required = replaced_fees.saturating_add(increment);
+        if fee < required {
+            let txids: Vec<&str> = conflicts.iter().map(|c| c.txid.as_str()).collect();
+            return Err(format!(
+                "Double-spend: inputs already used by pending TX {}; a replacement must pay at least {} ram (offered {} ram)",
+                txids.join(", "),
+                required,
+                fee
+            ));
+        }
+
+        Ok(conflicts.into_iter().map(|c| c.txid).collect())
+    }
+
+    /// Drop pending transactions confirmed by `block`, plus any pending transaction
+    /// that spends an outpoint the block already spent (conflicts can never confirm).
+    /// Returns the number of transactions removed.
+    pub fn remove_confirmed(&mut self, block: &Block) -> usize {
+        let block_txids: std::collections::HashSet<&str> =
+            block.transactions.iter().map(|tx| tx.txid.as_str()).collect();
+        let spent: std::collections::HashSet<(&str, u32)> = block
+            .transactions
+            .iter()
+            .flat_map(|tx| tx.inputs.iter())
+            .map(|inp| (inp.txid.as_str(), inp.vout))
+            .collect();
+
+        let before = self.pending.len();
+        let mut conflicts = std::collections::HashSet::new();
+        self.pending.retain(|tx| {
+            if block_txids.contains(tx.txid.as_str()) {
+                return false;
+            }
+            let conflicting = tx
+                .inputs
+                .iter()
+                .any(|inp| spent.contains(&(inp.txid.as_str(), inp.vout)));
+            if conflicting {
+                conflicts.insert(tx.txid.clone());
+            }
+            !conflicting
+        });
+        let removed = before - self.pending.len();
+
+        // Mined transactions now count in the confirmed nonce; conflicts never will
+        Self::release_nonces(&mut self.pending_nonces, |txid| {
+            !block_txids.contains(txid) && !conflicts.contains(txid)
+        });
+        removed
+    }
+
     /// Security: Enforce mempool limits to prevent DoS attacks
     /// Evicts low-fee or old transactions when limits are exceeded
     pub fn enforce_mempool_limit(&mut self) {
@@ -133,11 +747,11 @@ impl MempoolState {
 
         let now = chrono::Utc::now().timestamp();
 
-        // 1. Remove expired transactions (older than 24 hours)
+        // 1. Remove expired transactions (older than 24 hours or past their valid_until)
         let initial_count = self.pending.len();
         self.pending.retain(|tx| {
             let age = now - tx.timestamp;
-            if age > MEMPOOL_EXPIRY_TIME {
+            if age > MEMPOOL_EXPIRY_TIME || tx.is_expired_at(now) {
                 self.seen_tx.remove(&tx.txid);
                 false
             } else {
@@ -169,27 +783,18 @@ impl MempoolState {
                         .unwrap_or_default();
                 let tx_size = tx_bytes.len().max(1) as u64;
 
-                // Calculate total fee
-                let input_sum: U256 = tx
-                    .inputs
-                    .iter()
-                    .filter_map(|_| Some(U256::from(1_000_000_000_000_000_000u64))) // Estimate
-                    .fold(U256::zero(), |acc, amt| acc + amt);
-
-                let output_sum: U256 = tx
-                    .outputs
-                    .iter()
-                    .map(|out| out.amount())
-                    .fold(U256::zero(), |acc, amt| acc + amt);
-
-                let fee = if input_sum > output_sum {
-                    (input_sum - output_sum).as_u64()
-                } else {
-                    0
-                };
+                // Calculate total fee (inputs estimated at 1 ASRM each); overflowing
+                // amounts count as no fee so such txs are evicted first
+                let input_sum = U256::from(1_000_000_000_000_000_000u64) // Estimate
+                    .checked_mul(U256::from(tx.inputs.len()));
+                let fee = input_sum
+                    .and_then(|sum| tx.fee(sum))
+                    .unwrap_or_else(U256::zero);
 
                 // Fee per byte (lower = evict first)
-                fee / tx_size
+                (fee / U256::from(tx_size))
+                    .min(U256::from(u64::MAX))
+                    .as_u64()
             });
 
             // Remove lowest fee transactions
@@ -208,14 +813,7 @@ impl MempoolState {
         }
 
         // 3. Check total mempool byte size
-        let total_bytes: usize = self
-            .pending
-            .iter()
-            .filter_map(|tx| {
-                bincode::encode_to_vec(tx, Astram_core::blockchain::BINCODE_CONFIG.clone()).ok()
-            })
-            .map(|bytes| bytes.len())
-            .sum();
+        let total_bytes = self.pending_bytes();
 
         if total_bytes > MAX_MEMPOOL_BYTES {
             log::warn!(
@@ -225,27 +823,541 @@ impl MempoolState {
             );
 
             // Already sorted by fee rate, remove more low-fee txs
-            while !self.pending.is_empty() {
-                let current_size: usize = self
-                    .pending
-                    .iter()
-                    .filter_map(|tx| {
-                        bincode::encode_to_vec(tx, Astram_core::blockchain::BINCODE_CONFIG.clone())
-                            .ok()
-                    })
-                    .map(|bytes| bytes.len())
-                    .sum();
+            let mut current_size = total_bytes;
+            while current_size > MAX_MEMPOOL_BYTES && !self.pending.is_empty() {
+                let tx = self.pending.remove(0);
+                let size = self.tx_sizes.remove(&tx.txid).unwrap_or(0);
+                current_size = current_size.saturating_sub(size);
+                self.seen_tx.remove(&tx.txid);
+            }
+        }
 
-                if current_size <= MAX_MEMPOOL_BYTES {
-                    break;
-                }
+        // Expired and evicted transactions leave seen_tx; they'll never use their nonce
+        Self::release_nonces(&mut self.pending_nonces, |txid| {
+            self.seen_tx.contains_key(txid)
+        });
+    }
+}
 
-                if let Some(tx) = self.pending.first() {
-                    let txid = tx.txid.clone();
-                    self.pending.remove(0);
-                    self.seen_tx.remove(&txid);
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Astram_core::block::BlockHeader;
+    use Astram_core::transaction::{TransactionInput, TransactionOutput};
+    use primitive_types::U256;
+
+    fn spend(prev_txid: &str, vout: u32, amount: u64) -> Transaction {
+        Transaction {
+            txid: String::new(),
+            eth_hash: String::new(),
+            inputs: vec![TransactionInput {
+                txid: prev_txid.to_string(),
+                vout,
+                pubkey: String::new(),
+                signature: None,
+            }],
+            outputs: vec![TransactionOutput::new("0xabc".to_string(), U256::from(amount))],
+            timestamp: 0,
+            valid_until: None,
+        }
+        .with_hashes()
+    }
+
+    fn test_block(index: u64, transactions: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                index,
+                previous_hash: "0".repeat(64),
+                merkle_root: String::new(),
+                timestamp: 0,
+                nonce: 0,
+                difficulty: 1,
+            },
+            transactions,
+            hash: format!("{:064x}", index),
+        }
+    }
+
+    #[test]
+    fn same_block_from_submit_and_peer_is_pushed_once() {
+        let chain = Arc::new(Mutex::new(ChainState::default()));
+        let block = test_block(1, vec![Transaction::coinbase("0xabc", U256::from(1))]);
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+
+        let handles: Vec<_> = (0..2)
+            .map(|i| {
+                let chain = chain.clone();
+                let block = block.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let mut chain = chain.lock().unwrap();
+                    let pushed = chain.push_block(&block);
+                    if i == 0 {
+                        chain.mark_mined(&block.hash, 0);
+                    }
+                    pushed
+                })
+            })
+            .collect();
+
+        let pushed: usize = handles
+            .into_iter()
+            .map(|h| h.join().unwrap() as usize)
+            .sum();
+        assert_eq!(pushed, 1);
+        let chain = chain.lock().unwrap();
+        assert_eq!(chain.blockchain.len(), 1);
+        assert!(chain.recently_mined_blocks.contains_key(&block.hash));
+    }
+
+    #[test]
+    fn higher_fee_rate_confirms_sooner() {
+        // 4 txs of 400 bytes against 1000-byte blocks: two fit per block
+        let entries: Vec<(String, U256, usize)> =
+            [("low", 1u64), ("mid", 5), ("high", 50), ("mid2", 5)]
+                .iter()
+                .map(|(id, rate)| (id.to_string(), U256::from(*rate), 400))
+                .collect();
+
+        let high = mempool_position(&entries, "high", 1000).unwrap();
+        let low = mempool_position(&entries, "low", 1000).unwrap();
+        assert_eq!(high.rank, 1);
+        assert_eq!(high.estimated_blocks, 1);
+        assert_eq!(low.rank, 4);
+        assert_eq!(low.bytes_through, 1600);
+        assert_eq!(low.estimated_blocks, 2);
+        assert!(high.estimated_blocks < low.estimated_blocks);
+        assert!(mempool_position(&entries, "missing", 1000).is_none());
+    }
+
+    #[test]
+    fn pending_bytes_follow_the_mempool() {
+        use Astram_core::transaction::BINCODE_CONFIG;
+
+        let size = |tx: &Transaction| bincode::encode_to_vec(tx, *BINCODE_CONFIG).unwrap().len();
+        let (a, b) = (spend("aa", 0, 900), spend("bb", 0, 900));
+        let mut mempool = MempoolState::default();
+        assert_eq!(mempool.pending_bytes(), 0);
+
+        mempool.pending = vec![a.clone(), b.clone()];
+        assert_eq!(mempool.pending_bytes(), size(&a) + size(&b));
+        assert_eq!(mempool.tx_sizes.len(), 2);
+
+        // Removed however it leaves, the tx stops counting
+        mempool.pending.retain(|tx| tx.txid != a.txid);
+        assert_eq!(mempool.pending_bytes(), size(&b));
+        assert_eq!(mempool.tx_sizes.len(), 1);
+    }
+
+    #[test]
+    fn relay_floor_only_raises_the_minimum_fee() {
+        use Astram_core::config::{MIN_RELAY_FEE_NAT_PER_BYTE, calculate_min_fee};
+
+        // A floor below the consensus rate changes nothing
+        let mut mempool = MempoolState {
+            min_relay_fee_per_byte: 1,
+            ..MempoolState::default()
+        };
+        assert_eq!(mempool.min_relay_fee(300), calculate_min_fee(300));
+        assert_eq!(mempool.min_relay_fee_rate(), MIN_RELAY_FEE_NAT_PER_BYTE);
+
+        // One above it is what admission charges and what wallets are told
+        let rate = MIN_RELAY_FEE_NAT_PER_BYTE * U256::from(10u8);
+        mempool.min_relay_fee_per_byte = rate.as_u64();
+        assert_eq!(mempool.min_relay_fee(300), rate * U256::from(300u64));
+        assert_eq!(mempool.min_relay_fee_rate(), rate);
+    }
+
+    #[test]
+    fn higher_fee_replacement_evicts_the_pending_tx() {
+        let original = spend("aa", 0, 900);
+        let replacement = spend("aa", 0, 800);
+        let unrelated = spend("bb", 0, 900);
+
+        let mut mempool = MempoolState {
+            rbf_fee_increment_per_byte: 10,
+            ..MempoolState::default()
+        };
+        for tx in [&original, &unrelated] {
+            mempool.seen_tx.insert(tx.txid.clone(), 0);
+            mempool.pending.push(tx.clone());
+        }
+        mempool.record_pending_nonce("0xsender", &original.txid);
+
+        assert_eq!(mempool.conflicts_with(&replacement).len(), 1);
+        // the pending copy doesn't conflict with itself
+        assert!(mempool.conflicts_with(&original).is_empty());
+
+        // 100 more than the original's 1000, against 10 per byte for 9 bytes
+        let fees = HashMap::from([(original.txid.clone(), U256::from(1000u64))]);
+        // A dry run names the same evictions but leaves the mempool alone
+        assert_eq!(
+            mempool.check_replacement(&replacement, U256::from(1100u64), 9, &fees),
+            Ok(vec![original.txid.clone()])
+        );
+        assert_eq!(mempool.pending.len(), 2);
+        let replaced = mempool
+            .replace_conflicting(&replacement, U256::from(1100u64), 9, &fees, 5)
+            .unwrap();
+
+        assert_eq!(replaced, vec![original.txid.clone()]);
+        let pending: Vec<_> = mempool.pending.iter().map(|tx| &tx.txid).collect();
+        assert_eq!(pending, vec![&unrelated.txid]);
+        assert_eq!(mempool.pending_nonce_count("0xsender"), 0);
+        // still seen, so echoes from peers are dropped rather than re-relayed
+        assert_eq!(mempool.seen_tx.get(&original.txid), Some(&5));
+        // nothing conflicts with an unrelated tx
+        assert_eq!(
+            mempool.replace_conflicting(&unrelated, U256::zero(), 9, &HashMap::new(), 5),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn insufficient_fee_bump_is_rejected() {
+        let original = spend("aa", 0, 900);
+        let replacement = spend("aa", 0, 800);
+        let mut mempool = MempoolState {
+            rbf_fee_increment_per_byte: 10,
+            ..MempoolState::default()
+        };
+        mempool.pending.push(original.clone());
+        let fees = HashMap::from([(original.txid.clone(), U256::from(1000u64))]);
+
+        // Higher, but 89 short of the 10-per-byte increment
+        let bump = mempool.replace_conflicting(&replacement, U256::from(1001u64), 9, &fees, 5);
+        assert!(bump.unwrap_err().contains(&original.txid));
+        // A conflict whose fee wasn't looked up is never outbid blindly
+        let unknown = mempool.replace_conflicting(&replacement, U256::MAX, 9, &HashMap::new(), 5);
+        assert!(unknown.is_err());
+        assert_eq!(mempool.pending.len(), 1);
+
+        // With no increment configured the fee must still be strictly higher
+        mempool.rbf_fee_increment_per_byte = 0;
+        let equal = mempool.replace_conflicting(&replacement, U256::from(1000u64), 9, &fees, 5);
+        assert!(equal.is_err());
+        let higher = mempool.replace_conflicting(&replacement, U256::from(1001u64), 9, &fees, 5);
+        assert_eq!(higher, Ok(vec![original.txid]));
+    }
+
+    #[test]
+    fn transactions_past_their_deadline_leave_the_mempool() {
+        let now = chrono::Utc::now().timestamp();
+        let with_deadline = |prev_txid: &str, valid_until: i64| {
+            let mut tx = spend(prev_txid, 0, 900);
+            tx.timestamp = now - 60;
+            tx.valid_until = Some(valid_until);
+            tx.with_hashes()
+        };
+        let live = with_deadline("aa", now + 3600);
+        let lapsed = with_deadline("bb", now - 1);
+        let legacy = {
+            let mut tx = spend("cc", 0, 900);
+            tx.timestamp = now - 60;
+            tx.with_hashes()
+        };
+
+        let mut mempool = MempoolState::default();
+        let none = HashMap::new();
+        // Admitted up to the deadline, refused after it
+        assert_eq!(
+            mempool.replace_conflicting(&live, U256::zero(), 9, &none, now + 3600),
+            Ok(vec![])
+        );
+        let late = mempool.replace_conflicting(&live, U256::zero(), 9, &none, now + 3601);
+        assert!(late.unwrap_err().contains("expired"));
+        let lapsed_now = mempool.replace_conflicting(&lapsed, U256::zero(), 9, &none, now);
+        assert!(lapsed_now.is_err());
+
+        // One that lapses while pending is dropped with the 24-hour expiry
+        for tx in [&live, &lapsed, &legacy] {
+            mempool.seen_tx.insert(tx.txid.clone(), now);
+            mempool.pending.push(tx.clone());
+        }
+        mempool.enforce_mempool_limit();
+        let pending: Vec<_> = mempool.pending.iter().map(|tx| &tx.txid).collect();
+        assert_eq!(pending, vec![&live.txid, &legacy.txid]);
+        assert!(!mempool.seen_tx.contains_key(&lapsed.txid));
+    }
+
+    #[test]
+    fn next_block_rate_outbids_the_displaced_tx() {
+        let entries: Vec<(String, U256, usize)> = [("a", 50u64), ("b", 5), ("c", 1)]
+            .iter()
+            .map(|(id, rate)| (id.to_string(), U256::from(*rate), 400))
+            .collect();
+
+        // 1000-byte block: "a" and "b" leave room for a 200-byte tx, so only "c" is outbid
+        assert_eq!(next_block_fee_rate(&entries, 200, 1000), U256::from(2u8));
+        // a 300-byte tx fits next to "a" but not "b"
+        assert_eq!(next_block_fee_rate(&entries, 300, 1000), U256::from(6u8));
+        // a 700-byte tx only fits ahead of "a"
+        assert_eq!(next_block_fee_rate(&entries, 700, 1000), U256::from(51u8));
+        // a nearly empty mempool asks for nothing beyond the minimum
+        assert_eq!(next_block_fee_rate(&entries[..1], 300, 1000), U256::zero());
+        assert_eq!(next_block_fee_rate(&[], 300, 1000), U256::zero());
+    }
+
+    #[test]
+    fn fee_rate_percentiles_rank_pending_rates() {
+        let entries: Vec<(String, U256, usize)> = (1..=10u64)
+            .rev()
+            .map(|rate| (format!("tx{}", rate), U256::from(rate * 100), 250))
+            .collect();
+        let rates = fee_rate_percentiles(&entries, &FEE_RATE_PERCENTILES, U256::one());
+        let expected: Vec<U256> = [100u64, 300, 500, 800, 900]
+            .iter()
+            .map(|r| U256::from(*r))
+            .collect();
+        assert_eq!(rates, expected);
+
+        // One transaction is every percentile; none falls back to the relay floor
+        assert_eq!(
+            fee_rate_percentiles(&entries[..1], &[10, 90], U256::one()),
+            vec![U256::from(1000u64); 2]
+        );
+        assert_eq!(
+            fee_rate_percentiles(&[], &FEE_RATE_PERCENTILES, U256::from(7u8)),
+            vec![U256::from(7u8); 5]
+        );
+    }
+
+    /// Block at `index` on `parent` with a solved easy PoW (bits 0x207fffff)
+    fn mined_block(index: u64, parent: &str) -> Block {
+        let cb = Transaction::coinbase(
+            "0x0000000000000000000000000000000000000001",
+            U256::from(index + 1),
+        );
+        let mut header = BlockHeader {
+            index,
+            previous_hash: parent.to_string(),
+            merkle_root: Astram_core::compute_merkle_root(&[cb.txid.clone()]),
+            timestamp: cb.timestamp + index as i64,
+            nonce: 0,
+            difficulty: 0x207fffff,
+        };
+        let target = U256::from(0x7fffffu64) << 232;
+        loop {
+            let hash = Astram_core::compute_header_hash(&header).unwrap();
+            if U256::from_str_radix(&hash, 16).unwrap() < target {
+                return Block {
+                    header,
+                    transactions: vec![cb],
+                    hash,
+                };
             }
+            header.nonce += 1;
+        }
+    }
+
+    #[test]
+    fn orphans_delivered_in_reverse_connect_in_one_pass() {
+        let dir = std::env::temp_dir().join(format!(
+            "astram-orphans-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let mut bc = Blockchain::new(dir.to_str().unwrap()).unwrap();
+        let mut chain = ChainState::default();
+
+        let mut blocks = vec![mined_block(0, &"0".repeat(64))];
+        for index in 1..=4 {
+            let parent = blocks.last().unwrap().hash.clone();
+            blocks.push(mined_block(index, &parent));
         }
+        bc.validate_and_insert_block(&blocks[0]).unwrap();
+
+        // 4, 3 and 2 arrive before their parents and wait in the pool
+        let now = chrono::Utc::now().timestamp();
+        for block in blocks[2..].iter().rev() {
+            let err = bc.validate_and_insert_block(block).unwrap_err();
+            assert!(err.to_string().contains("previous header not found"));
+            chain.add_orphan(block.clone(), Some("203.0.113.7:8335"), now);
+        }
+        assert_eq!(chain.orphan_blocks.len(), 3);
+        let no_rejections = |from: &str, e: &anyhow::Error, _: bool| panic!("{} sent {}", from, e);
+        assert!(connect_orphans(&mut bc, &mut chain, &blocks[0].hash, no_rejections).is_empty());
+
+        // Block 1 connects, and the whole buffered chain follows it
+        bc.validate_and_insert_block(&blocks[1]).unwrap();
+        let connected = connect_orphans(&mut bc, &mut chain, &blocks[1].hash, no_rejections);
+        let heights: Vec<u64> = connected.iter().map(|b| b.header.index).collect();
+        assert_eq!(heights, vec![2, 3, 4]);
+        assert!(chain.orphan_blocks.is_empty());
+        assert_eq!(bc.chain_tip.as_deref(), Some(blocks[4].hash.as_str()));
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rejected_block_reaches_neither_the_db_nor_the_memory_chain() {
+        let dir = std::env::temp_dir().join(format!(
+            "astram-rejected-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let mut bc = Blockchain::new(dir.to_str().unwrap()).unwrap();
+        let mut chain = ChainState::default();
+        let genesis = mined_block(0, &"0".repeat(64));
+        bc.validate_and_insert_block(&genesis).unwrap();
+        chain.push_block(&genesis);
+
+        // Block 1 with its coinbase swapped after mining no longer matches its merkle root
+        let mut bad = mined_block(1, &genesis.hash);
+        bad.transactions[0] = Transaction::coinbase(
+            "0x0000000000000000000000000000000000000002",
+            U256::from(5u8),
+        );
+        let now = chrono::Utc::now().timestamp();
+        chain.add_orphan(bad.clone(), Some("203.0.113.7:8335"), now);
+
+        let mut rejected = Vec::new();
+        let connected = connect_orphans(&mut bc, &mut chain, &genesis.hash, |from, _, tip| {
+            rejected.push((from.to_string(), tip))
+        });
+        assert!(connected.is_empty());
+        assert_eq!(rejected, vec![("203.0.113.7:8335".to_string(), true)]);
+        // Neither view moved
+        assert!(matches!(bc.load_header(&bad.hash), Ok(None)));
+        assert_eq!(bc.chain_tip.as_deref(), Some(genesis.hash.as_str()));
+        assert_eq!(chain.blockchain.len(), 1);
+        assert!(chain.orphan_blocks.is_empty());
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn orphan_pool_is_capped_and_expires() {
+        let mut chain = ChainState::default();
+        let cap = MAX_ORPHAN_BLOCKS as u64;
+        for index in 0..=cap {
+            chain.add_orphan(test_block(index, Vec::new()), None, 1000 + index as i64);
+        }
+        // Full: the oldest made room for the newest
+        assert_eq!(chain.orphan_blocks.len(), MAX_ORPHAN_BLOCKS);
+        assert!(!chain.orphan_blocks.contains_key(&format!("{:064x}", 0)));
+        assert!(chain.orphan_blocks.contains_key(&format!("{:064x}", cap)));
+
+        // Everything received ORPHAN_TIMEOUT before the next arrival is gone
+        let later = 1000 + cap as i64 + ORPHAN_TIMEOUT;
+        chain.add_orphan(test_block(cap + 1, Vec::new()), None, later);
+        assert_eq!(chain.orphan_blocks.len(), 1);
+    }
+
+    #[test]
+    fn eth_tx_map_is_capped_and_falls_back_to_the_db_index() {
+        let dir = std::env::temp_dir().join(format!(
+            "astram-ethmap-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let mut bc = Blockchain::new(dir.to_str().unwrap()).unwrap();
+        let genesis = bc.create_genesis().unwrap();
+        let coinbase = bc.load_block(&genesis).unwrap().unwrap().transactions[0].clone();
+
+        let mut map = EthTxMap::with_capacity(3);
+        map.insert(&coinbase.eth_hash, &coinbase.txid);
+        for n in 1..=3u8 {
+            map.insert(&format!("0x{:064x}", 0xa0 + n), &format!("tx{}", n));
+        }
+        // Full: the oldest mapping went first
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&coinbase.eth_hash), None);
+        // Lookups ignore 0x and case
+        let upper = format!("{:064X}", 0xa1);
+        assert_eq!(map.get(&upper).map(String::as_str), Some("tx1"));
+
+        // The evicted, mined transaction still resolves through the index
+        let resolved = resolve_eth_hash(&bc, &map, &coinbase.eth_hash);
+        assert_eq!(resolved, Some(coinbase.txid.clone()));
+        assert_eq!(resolve_eth_hash(&bc, &map, &format!("{:064x}", 9)), None);
+
+        // Settled mappings drop from the old end until one isn't
+        assert_eq!(map.drop_settled(|txid| txid == "tx1"), 1);
+        assert_eq!(map.drop_settled(|txid| txid == "tx3"), 0);
+        assert_eq!(map.len(), 2);
+
+        // Genesis has one confirmation
+        assert!(confirmed_at_depth(&bc, &coinbase.txid, 1));
+        let depth = ETH_TX_MAPPING_DEPTH;
+        assert!(!confirmed_at_depth(&bc, &coinbase.txid, depth));
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rapid_sends_from_one_address_get_distinct_nonces() {
+        let first = spend("aa", 0, 1);
+        let second = spend("bb", 0, 1);
+        let sender = "0xABC0000000000000000000000000000000000001";
+
+        let mut mempool = MempoolState::default();
+        for tx in [&first, &second] {
+            mempool.seen_tx.insert(tx.txid.clone(), chrono::Utc::now().timestamp());
+            mempool.pending.push(tx.clone());
+            mempool.record_pending_nonce(sender, &tx.txid);
+        }
+        // re-recording the same tx doesn't double count
+        mempool.record_pending_nonce(sender, &first.txid);
+        assert_eq!(mempool.pending_nonce_count(&sender.to_lowercase()), 2);
+
+        // mined: counted by the confirmed nonce from then on
+        let block = test_block(
+            1,
+            vec![Transaction::coinbase("0xabc", U256::from(1)), first],
+        );
+        mempool.remove_confirmed(&block);
+        assert_eq!(mempool.pending_nonce_count(sender), 1);
+
+        // expired (test txs are timestamped 0): its nonce is never used
+        mempool.enforce_mempool_limit();
+        assert_eq!(mempool.pending_nonce_count(sender), 0);
+        assert!(mempool.pending_nonces.is_empty());
+    }
+
+    #[test]
+    fn forget_clears_seen_tx_and_pending_nonces() {
+        let expired = spend("aa", 0, 1);
+        let kept = spend("bb", 0, 1);
+        let sender = "0xabc0000000000000000000000000000000000001";
+
+        let mut mempool = MempoolState::default();
+        for tx in [&expired, &kept] {
+            mempool
+                .seen_tx
+                .insert(tx.txid.clone(), chrono::Utc::now().timestamp());
+            mempool.record_pending_nonce(sender, &tx.txid);
+        }
+        mempool.record_pending_nonce("0xother", &expired.txid);
+
+        mempool.forget(&[expired.txid.as_str()]);
+        assert!(!mempool.seen_tx.contains_key(&expired.txid));
+        assert!(mempool.seen_tx.contains_key(&kept.txid));
+        assert_eq!(mempool.pending_nonce_count(sender), 1);
+        // An address left with no pending txids is dropped entirely
+        assert!(!mempool.pending_nonces.contains_key("0xother"));
+    }
+
+    #[test]
+    fn remove_confirmed_drops_included_and_conflicting_txs() {
+        let included = spend("aa", 0, 1);
+        let conflict = spend("aa", 0, 2);
+        let unrelated = spend("bb", 1, 3);
+
+        let mut mempool = MempoolState::default();
+        mempool.pending = vec![included.clone(), conflict, unrelated.clone()];
+
+        let block = test_block(
+            1,
+            vec![Transaction::coinbase("0xabc", U256::from(1)), included],
+        );
+
+        assert_eq!(mempool.remove_confirmed(&block), 2);
+        assert_eq!(mempool.pending.len(), 1);
+        assert_eq!(mempool.pending[0].txid, unrelated.txid);
     }
 }
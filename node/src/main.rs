@@ -1,19 +1,27 @@
 // Use library exports instead of declaring local modules to avoid duplicate crate types
 use Astram_core::Blockchain;
 use Astram_core::block::Block;
-use Astram_core::config::initial_block_reward;
+use Astram_core::blockchain::ChainParams;
+use Astram_core::config::calculate_block_reward;
 use Astram_core::consensus;
+use Astram_core::security::{BLOCK_RESERVED_BYTES, MAX_BLOCK_BYTES};
 use Astram_core::transaction::BINCODE_CONFIG;
 use Astram_core::utxo::Utxo;
+use astram_config::canonical_json::to_canonical_bytes;
 use astram_config::config::Config;
+use astram_config::ports::Ports;
 use astram_node::ChainState;
+use astram_node::EthTxMap;
 use astram_node::MempoolState;
 use astram_node::MiningState;
+use astram_node::NODE_EVENT_CAPACITY;
 use astram_node::NodeHandle;
 use astram_node::NodeHandles;
 use astram_node::NodeMeta;
+use astram_node::p2p::manager::PEERS_FILE;
 use astram_node::p2p::service::P2PService;
-use astram_node::server::run_server;
+use astram_node::server::{RateLimiter, restore_transaction, run_server};
+use astram_node::settings::NodeSettings;
 use hex;
 use log::{info, warn};
 use primitive_types::U256;
@@ -22,7 +30,6 @@ use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
 use std::net::SocketAddr;
-use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering as OtherOrdering;
 use std::sync::{Arc, Mutex};
@@ -49,133 +56,63 @@ struct DnsNodesResponse {
     count: usize,
 }
 
-#[derive(Debug, Clone)]
-struct NodeSettings {
-    data_dir: String,
-    p2p_bind_addr: String,
-    p2p_port: u16,
-    http_bind_addr: String,
-    http_port: u16,
-    eth_rpc_bind_addr: String,
-    eth_rpc_port: u16,
-    dns_server_url: String,
-}
-
-impl Default for NodeSettings {
-    fn default() -> Self {
-        Self {
-            data_dir: default_data_dir(),
-            p2p_bind_addr: "0.0.0.0".to_string(),
-            p2p_port: 8335,
-            http_bind_addr: "127.0.0.1".to_string(),
-            http_port: 19533,
-            eth_rpc_bind_addr: "127.0.0.1".to_string(),
-            eth_rpc_port: 8545,
-            dns_server_url: "http://161.33.19.183:8053".to_string(),
-        }
-    }
+/// Value of `--prune N` if given: Err with the raw value when it isn't a number
+fn prune_arg() -> Option<Result<u64, String>> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--prune");
+    args.next()?;
+    let value = args.next().unwrap_or_default();
+    Some(value.parse().map_err(|_| value))
 }
 
-fn default_data_dir() -> String {
-    let home = dirs::home_dir().expect("Cannot find home directory");
-
-    if cfg!(target_os = "windows") {
-        let base = dirs::data_dir().unwrap_or(home).join("Astram");
-        return base.join("data").to_string_lossy().into_owned();
-    }
-
-    home.join(".Astram")
-        .join("data")
-        .to_string_lossy()
-        .into_owned()
-}
-
-fn expand_path_value(value: &str) -> String {
-    let expanded = shellexpand::tilde(value).into_owned();
-    if expanded.contains("%USERPROFILE%") {
-        if let Ok(profile) = std::env::var("USERPROFILE") {
-            return expanded.replace("%USERPROFILE%", &profile);
-        }
-    }
-    expanded
+fn to_socket_addr(addr: &str, port: u16, fallback: SocketAddr) -> SocketAddr {
+    format!("{}:{}", addr, port).parse().unwrap_or(fallback)
 }
 
-fn resolve_node_settings_path() -> PathBuf {
-    let exe_path = std::env::current_exe().ok().and_then(|path| {
-        path.parent()
-            .map(|parent| parent.join("config/nodeSettings.conf"))
-    });
-
-    if let Some(ref path) = exe_path {
-        if path.exists() {
-            return path.clone();
-        }
-    }
-
-    let cwd_path = PathBuf::from("config/nodeSettings.conf");
-    if cwd_path.exists() {
-        return cwd_path;
-    }
-
-    exe_path.unwrap_or(cwd_path)
+/// Per-IP limiter shared by the HTTP API and the Ethereum JSON-RPC. Whitelist entries
+/// that aren't IP addresses are skipped with a warning.
+fn http_rate_limiter(settings: &NodeSettings) -> RateLimiter {
+    let whitelist = settings
+        .http_rate_limit_whitelist
+        .iter()
+        .filter_map(|entry| match entry.parse() {
+            Ok(ip) => Some(ip),
+            Err(_) => {
+                warn!(
+                    "Ignoring HTTP_RATE_LIMIT_WHITELIST entry {:?}: not an IP address",
+                    entry
+                );
+                None
+            }
+        })
+        .collect();
+    RateLimiter::new(
+        settings.http_rate_limit_per_sec,
+        settings.http_rate_limit_burst,
+        whitelist,
+    )
 }
 
-fn load_node_settings() -> NodeSettings {
-    let mut settings = NodeSettings::default();
-    let path = resolve_node_settings_path();
-
-    match fs::read_to_string(&path) {
-        Ok(contents) => {
-            for (line_no, raw_line) in contents.lines().enumerate() {
-                let line = raw_line.trim();
-                if line.is_empty() || line.starts_with('#') {
-                    continue;
-                }
-
-                let (key, value) = match line.split_once('=') {
-                    Some(pair) => pair,
-                    None => {
-                        println!(
-                            "[WARN] Invalid node setting on line {}: {}",
-                            line_no + 1,
-                            raw_line
-                        );
-                        continue;
-                    }
-                };
-
-                let key = key.trim();
-                let value = value.trim();
-                match key {
-                    "DATA_DIR" => settings.data_dir = expand_path_value(value),
-                    "P2P_BIND_ADDR" => settings.p2p_bind_addr = value.to_string(),
-                    "P2P_PORT" => settings.p2p_port = value.parse().unwrap_or(settings.p2p_port),
-                    "HTTP_BIND_ADDR" => settings.http_bind_addr = value.to_string(),
-                    "HTTP_PORT" => settings.http_port = value.parse().unwrap_or(settings.http_port),
-                    "ETH_RPC_BIND_ADDR" => settings.eth_rpc_bind_addr = value.to_string(),
-                    "ETH_RPC_PORT" => {
-                        settings.eth_rpc_port = value.parse().unwrap_or(settings.eth_rpc_port)
-                    }
-                    "DNS_SERVER_URL" => settings.dns_server_url = value.to_string(),
-                    _ => println!("[WARN] Unknown node setting key: {}", key),
-                }
+#[tokio::main]
+async fn main() {
+    if std::env::args().any(|arg| arg == "--init-config") {
+        let path = NodeSettings::resolve_path();
+        match NodeSettings::write_default(&path) {
+            Ok(()) => println!("[INFO] Default node settings written to {:?}", path),
+            Err(e) => {
+                println!("[ERROR] Failed to write node settings: {}", e);
+                std::process::exit(1);
             }
         }
-        Err(err) => {
-            println!("[WARN] Node settings file not found at {:?}: {}", path, err);
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--backup") {
+        if let Err(e) = request_backup(&NodeSettings::load()).await {
+            println!("[ERROR] Backup failed: {}", e);
+            std::process::exit(1);
         }
+        return;
     }
 
-    settings.data_dir = expand_path_value(&settings.data_dir);
-    settings
-}
-
-fn to_socket_addr(addr: &str, port: u16, fallback: SocketAddr) -> SocketAddr {
-    format!("{}:{}", addr, port).parse().unwrap_or(fallback)
-}
-
-#[tokio::main]
-async fn main() {
     println!("[INFO] Astram node starting...");
 
     env_logger::Builder::from_default_env()
@@ -183,7 +120,13 @@ async fn main() {
         .init();
 
     let cfg = Config::load();
-    let node_settings = Arc::new(load_node_settings());
+    let node_settings = Arc::new(NodeSettings::load());
+    if let Some(chain_id) = node_settings.chain_id {
+        astram_node::p2p::manager::set_chain_id(chain_id);
+    }
+    if let Some(magic) = cfg.network_magic {
+        astram_node::p2p::manager::set_network_magic(magic);
+    }
 
     // Read wallet address from file (expand paths configured via CLI)
     let wallet_path = cfg.wallet_path_resolved();
@@ -226,8 +169,31 @@ async fn main() {
         }
     }
 
-    // Initialize core Blockchain (RocksDB-backed)
-    let bc = match Blockchain::new(db_path.as_str()) {
+    // Initialize core Blockchain (RocksDB-backed); consensus parameters missing from
+    // the config keep the network defaults
+    let defaults = ChainParams::default();
+    let params = ChainParams {
+        block_interval: cfg.block_interval.unwrap_or(defaults.block_interval),
+        retarget_window: cfg.retarget_window.unwrap_or(defaults.retarget_window),
+        pow_limit_bits: cfg.pow_limit_bits.unwrap_or(defaults.pow_limit_bits),
+        pow_min_bits: cfg.pow_min_bits.unwrap_or(defaults.pow_min_bits),
+        genesis_address: cfg
+            .genesis_address
+            .clone()
+            .unwrap_or_else(|| defaults.genesis_address.clone()),
+        tx_expiry_height: cfg.tx_expiry_height.unwrap_or(defaults.tx_expiry_height),
+    };
+    if let Err(e) = params.validate() {
+        eprintln!("Invalid chain parameters in config: {}", e);
+        std::process::exit(1);
+    }
+    if params != defaults {
+        println!(
+            "[WARN] Non-default chain parameters ({:?}); this node will not agree with the public network",
+            params
+        );
+    }
+    let mut bc = match Blockchain::with_params(db_path.as_str(), params) {
         Ok(b) => b,
         Err(e) => {
             eprintln!("Failed to open blockchain DB: {}", e);
@@ -235,6 +201,60 @@ async fn main() {
             std::process::exit(1);
         }
     };
+    if let Some(bits) = cfg.initial_difficulty_bits {
+        if let Err(e) = bc.set_initial_difficulty(bits) {
+            eprintln!("Invalid initial_difficulty_bits in config: {}", e);
+            std::process::exit(1);
+        }
+    }
+    // Every node of a network builds the same genesis, so a fresh node starts from it
+    // rather than mining a block 0 of its own
+    if bc.chain_tip.is_none() {
+        match bc.create_genesis() {
+            Ok(hash) => println!("[INFO] Created genesis block {}", hash),
+            Err(e) => {
+                eprintln!("Failed to create genesis block: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let genesis_hash = match bc.genesis_hash() {
+        Ok(hash) => hash,
+        Err(e) => {
+            eprintln!("Failed to read genesis block: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let prune_depth = match prune_arg() {
+        Some(Ok(depth)) => depth,
+        Some(Err(value)) => {
+            eprintln!("Invalid --prune value {:?}: expected a block count", value);
+            std::process::exit(1);
+        }
+        None => node_settings.prune_depth,
+    };
+    if prune_depth > 0 {
+        if prune_depth < bc.max_reorg_depth {
+            println!(
+                "[WARN] Prune depth {} is below the reorg limit; keeping {} blocks instead",
+                prune_depth, bc.max_reorg_depth
+            );
+        }
+        bc.prune_depth = Some(prune_depth);
+        match bc.prune_blocks() {
+            Ok(0) => {}
+            Ok(pruned) => println!("[INFO] Pruned {} old block bodies", pruned),
+            Err(e) => eprintln!("[WARN] Pruning failed: {}", e),
+        }
+    }
+    if node_settings.checkpoint_sync {
+        if let Some(height) = bc.enable_checkpoint_sync() {
+            println!(
+                "[INFO] Checkpoint sync: blocks leading to checkpoint {} may skip signature checks",
+                height
+            );
+        }
+    }
     let bc = Arc::new(Mutex::new(bc));
 
     // Initialize P2P networking
@@ -243,23 +263,58 @@ async fn main() {
     let mining_state = Arc::new(MiningState::default());
 
     let p2p_handle = p2p_service.manager();
+    p2p_handle.set_genesis_hash(genesis_hash);
 
     let chain_state = Arc::new(Mutex::new(ChainState::default()));
     let node_meta = Arc::new(NodeMeta {
         miner_address: Arc::new(Mutex::new(miner_address.clone())),
         my_public_address: Arc::new(Mutex::new(None)),
         node_start_time: std::time::Instant::now(),
-        eth_to_astram_tx: Arc::new(Mutex::new(HashMap::new())),
+        eth_to_astram_tx: Arc::new(Mutex::new(EthTxMap::default())),
     });
 
     let node = NodeHandles {
         bc: bc.clone(),
-        mempool: Arc::new(Mutex::new(MempoolState::default())),
+        mempool: Arc::new(Mutex::new(MempoolState {
+            rbf_fee_increment_per_byte: node_settings.rbf_fee_increment_per_byte,
+            min_relay_fee_per_byte: node_settings.min_relay_fee_per_byte,
+            ..MempoolState::default()
+        })),
         mining: mining_state.clone(),
+        events: tokio::sync::broadcast::channel(NODE_EVENT_CAPACITY).0,
     };
 
     let node_handle = Arc::new(node);
 
+    // Restore the mempool saved at the last shutdown through the same admission checks
+    // as /tx, dropping anything the chain has since confirmed, whose inputs are gone,
+    // or that no longer pays the relay fee
+    let saved = node_handle.bc.lock().unwrap().load_mempool();
+    match saved {
+        Ok(saved) => {
+            let total = saved.len();
+            let mut restored = 0;
+            for tx in &saved {
+                match restore_transaction(
+                    &node_handle,
+                    tx,
+                    node_settings.min_confirmations_for_spend,
+                ) {
+                    Ok(()) => restored += 1,
+                    Err(reason) => log::debug!("Dropped saved mempool tx {}: {}", tx.txid, reason),
+                }
+            }
+            if total > 0 {
+                println!(
+                    "[INFO] Restored {} of {} saved mempool transactions",
+                    restored, total
+                );
+            }
+            node_handle.mempool.lock().unwrap().enforce_mempool_limit();
+        }
+        Err(e) => log::warn!("Failed to load saved mempool: {}", e),
+    }
+
     // Set current blockchain height in P2P manager
     let my_height = {
         let bc = node_handle.bc.lock().unwrap();
@@ -280,27 +335,34 @@ async fn main() {
 
     // Set listening port in P2P manager (for self-connection detection)
     p2p_handle.set_my_listening_port(node_settings.p2p_port);
+    p2p_handle.set_max_outbound(node_settings.max_outbound_peers);
+    p2p_handle.set_max_inbound(node_settings.max_inbound_peers);
+    p2p_handle.set_ban_duration(node_settings.peer_ban_secs);
 
     p2p_service
         .start(bind_addr, node_handle.clone(), chain_state.clone())
         .await
         .expect("p2p start failed");
 
+    let rate_limiter = Arc::new(http_rate_limiter(&node_settings));
+
     // Start Ethereum JSON-RPC server for MetaMask
     let eth_rpc_node = node_handle.clone();
     let eth_rpc_addr = to_socket_addr(
         &node_settings.eth_rpc_bind_addr,
         node_settings.eth_rpc_port,
-        SocketAddr::from(([127, 0, 0, 1], 8545)),
+        SocketAddr::from(([127, 0, 0, 1], Ports::DEFAULT.eth_rpc)),
     );
     let eth_rpc_p2p = p2p_handle.clone();
     let eth_rpc_meta = node_meta.clone();
+    let eth_rpc_limiter = rate_limiter.clone();
     tokio::spawn(async move {
         astram_node::server::run_eth_rpc_server(
             eth_rpc_node,
             eth_rpc_p2p,
             eth_rpc_meta,
             eth_rpc_addr,
+            eth_rpc_limiter,
         )
         .await;
     });
@@ -341,6 +403,7 @@ async fn main() {
         miner_address,
         shutdown_flag.clone(),
         node_settings.clone(),
+        rate_limiter,
     )
     .await;
 
@@ -362,6 +425,23 @@ async fn main() {
     // Give more time for all resources to be released
     tokio::time::sleep(Duration::from_secs(2)).await;
 
+    // Persist node state before the DB is flushed: pending transactions, then peers
+    {
+        let pending = node_handle.mempool.lock().unwrap().pending.clone();
+        if let Ok(bc) = node_handle.bc.lock() {
+            match bc.save_mempool(&pending) {
+                Ok(()) => println!("[OK] Saved {} mempool transactions", pending.len()),
+                Err(e) => log::warn!("Failed to save mempool: {}", e),
+            }
+        }
+
+        let saved_peers = p2p_handle.save_connected_peers();
+        println!(
+            "[OK] Saved {} connected peers to {}",
+            saved_peers, PEERS_FILE
+        );
+    }
+
     // Cleanup: Close database properly
     {
         println!("[INFO] Closing database...");
@@ -418,6 +498,13 @@ async fn measure_latency(address: &str) -> Option<u64> {
     }
 }
 
+/// Composite peer score weights (sum to 1.0)
+const PEER_SCORE_HEIGHT_WEIGHT: f64 = 0.3;
+const PEER_SCORE_UPTIME_WEIGHT: f64 = 0.2;
+const PEER_SCORE_LATENCY_WEIGHT: f64 = 0.5;
+/// Uptime beyond a week earns no extra score
+const PEER_SCORE_UPTIME_CAP_HOURS: f64 = 168.0;
+
 #[derive(Debug, Clone)]
 struct ScoredPeer {
     address: String,
@@ -427,161 +514,297 @@ struct ScoredPeer {
     score: f64,
 }
 
-/// Fetch best nodes from DNS server, excluding self
+/// Fetch best nodes from DNS server, excluding self. `known_latency` holds ping
+/// round-trips (ms) of peers we're already connected to, which are used instead of
+/// re-measuring them with a throwaway TCP connect.
 async fn fetch_best_nodes_from_dns(
     node_meta: Arc<NodeMeta>,
     settings: &NodeSettings,
     my_port: u16,
     limit: usize,
+    known_latency: &HashMap<String, u64>,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     // Get my public address from state
     let my_address = { node_meta.my_public_address.lock().unwrap().clone() };
 
-    let dns_url = settings.dns_server_url.clone();
     let client = reqwest::Client::builder()
         .timeout(Duration::from_secs(5)) // 5 second timeout
         .build()?;
-    let nodes_url = format!("{}/nodes?limit={}", dns_url, limit * 3); // Fetch more to test latency
+    // Fetch more to test latency
+    let nodes = fetch_dns_nodes(&client, &settings.dns_seeds, limit * 3).await?;
+
+    // Filter out self - use public address if available
+    let candidates: Vec<DnsNodeInfo> = nodes
+        .into_iter()
+        .filter(|node| {
+            let node_id = format!("{}:{}", node.address, node.port);
+
+            // Filter out exact match with public address (if we have it)
+            if let Some(ref my_public_ip) = my_address {
+                let my_id = format!("{}:{}", my_public_ip, my_port);
+                if node_id == my_id {
+                    info!("  Skipping {} - matches my public address", node_id);
+                    return false;
+                }
+            }
 
-    info!("Fetching best nodes from DNS server at {}", dns_url);
+            // Filter out localhost addresses
+            if node.address == "127.0.0.1" || node.address == "localhost" || node.address == "::1" {
+                info!(
+                    "  Skipping {}:{} - localhost address",
+                    node.address, node.port
+                );
+                return false;
+            }
 
-    let response = client.get(&nodes_url).send().await?;
+            true
+        })
+        .collect();
 
-    if response.status().is_success() {
-        let result: DnsNodesResponse = response.json().await?;
-        info!("Retrieved {} nodes from DNS server", result.count);
+    info!(
+        "Testing latency for {} candidate nodes...",
+        candidates.len()
+    );
 
-        // Filter out self - use public address if available
-        let candidates: Vec<DnsNodeInfo> = result
-            .nodes
-            .into_iter()
-            .filter(|node| {
-                let node_id = format!("{}:{}", node.address, node.port);
-
-                // Filter out exact match with public address (if we have it)
-                if let Some(ref my_public_ip) = my_address {
-                    let my_id = format!("{}:{}", my_public_ip, my_port);
-                    if node_id == my_id {
-                        info!("  Skipping {} - matches my public address", node_id);
-                        return false;
-                    }
-                }
+    let scored_peers = probe_candidates(candidates, known_latency, |addr| async move {
+        measure_latency(&addr).await
+    })
+    .await;
 
-                // Filter out localhost addresses
-                if node.address == "127.0.0.1"
-                    || node.address == "localhost"
-                    || node.address == "::1"
-                {
-                    info!(
-                        "  Skipping {}:{} - localhost address",
-                        node.address, node.port
-                    );
-                    return false;
-                }
+    if scored_peers.is_empty() {
+        return Ok(vec![]);
+    }
 
-                true
-            })
-            .collect();
+    let scored_peers = rank_peers(scored_peers);
 
+    // Log top peers
+    info!("\n[INFO] Best peers by composite score:");
+    for (i, peer) in scored_peers.iter().take(limit).enumerate() {
         info!(
-            "Testing latency for {} candidate nodes...",
-            candidates.len()
+            "  {}. {} - score: {:.3} (height: {}, uptime: {:.1}h, latency: {}ms)",
+            i + 1,
+            peer.address,
+            peer.score,
+            peer.height,
+            peer.uptime_hours,
+            peer.latency_ms
         );
+    }
 
-        // Measure latency for each candidate in parallel
-        let mut scored_peers = Vec::new();
+    let best_peers: Vec<String> = scored_peers
+        .into_iter()
+        .take(limit)
+        .map(|p| p.address)
+        .collect();
 
-        for node in candidates {
-            let addr = format!("{}:{}", node.address, node.port);
-            let latency = measure_latency(&addr).await;
+    Ok(best_peers)
+}
 
-            if let Some(latency_ms) = latency {
-                // Calculate composite score:
-                // - 30% height (normalized)
-                // - 20% uptime (capped at 168h)
-                // - 50% network latency (lower is better)
+/// Latency for each candidate, from `known_latency` or else `probe` (None = unreachable,
+/// dropped). The probe is injectable so tests can supply fixed latencies.
+async fn probe_candidates<F, Fut>(
+    candidates: Vec<DnsNodeInfo>,
+    known_latency: &HashMap<String, u64>,
+    probe: F,
+) -> Vec<ScoredPeer>
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = Option<u64>>,
+{
+    let mut scored_peers = Vec::new();
+
+    for node in candidates {
+        let addr = format!("{}:{}", node.address, node.port);
+        let latency = match known_latency.get(&addr) {
+            Some(&ms) => Some(ms),
+            None => probe(addr.clone()).await,
+        };
 
-                // For scoring, we need to normalize. We'll do final scoring after collecting all
-                scored_peers.push(ScoredPeer {
-                    address: addr,
-                    height: node.height,
-                    uptime_hours: node.uptime_hours,
-                    latency_ms,
-                    score: 0.0, // Will calculate after we have all data
-                });
+        if let Some(latency_ms) = latency {
+            // Scores are normalized across all candidates, so they're computed in rank_peers
+            scored_peers.push(ScoredPeer {
+                address: addr,
+                height: node.height,
+                uptime_hours: node.uptime_hours,
+                latency_ms,
+                score: 0.0, // Will calculate after we have all data
+            });
 
-                info!(
-                    "  {} - height: {}, uptime: {:.1}h, latency: {}ms",
-                    scored_peers.last().unwrap().address,
-                    node.height,
-                    node.uptime_hours,
-                    latency_ms
-                );
-            } else {
-                info!("  {}:{} - unreachable", node.address, node.port);
-            }
+            info!(
+                "  {} - height: {}, uptime: {:.1}h, latency: {}ms",
+                scored_peers.last().unwrap().address,
+                node.height,
+                node.uptime_hours,
+                latency_ms
+            );
+        } else {
+            info!("  {}:{} - unreachable", node.address, node.port);
         }
+    }
 
-        if scored_peers.is_empty() {
-            return Ok(vec![]);
-        }
+    scored_peers
+}
 
-        // Normalize and calculate final scores
-        let max_height = scored_peers.iter().map(|p| p.height).max().unwrap_or(1) as f64;
-        let min_latency = scored_peers.iter().map(|p| p.latency_ms).min().unwrap_or(1) as f64;
-        let max_latency = scored_peers
-            .iter()
-            .map(|p| p.latency_ms)
-            .max()
-            .unwrap_or(1000) as f64;
+/// Score peers and sort best first. Composite score:
+/// - 30% height (relative to the highest candidate)
+/// - 20% uptime (capped at 168h)
+/// - 50% network latency (lower is better, normalized between min and max)
+///
+/// Equal scores are ordered by address, so the ranking doesn't depend on the order
+/// the DNS seed listed the nodes in.
+fn rank_peers(mut scored_peers: Vec<ScoredPeer>) -> Vec<ScoredPeer> {
+    let max_height = scored_peers.iter().map(|p| p.height).max().unwrap_or(1) as f64;
+    let min_latency = scored_peers.iter().map(|p| p.latency_ms).min().unwrap_or(1) as f64;
+    let max_latency = scored_peers
+        .iter()
+        .map(|p| p.latency_ms)
+        .max()
+        .unwrap_or(1000) as f64;
+
+    for peer in &mut scored_peers {
+        let height_score = (peer.height as f64 / max_height.max(1.0)) * PEER_SCORE_HEIGHT_WEIGHT;
+        let uptime_score = (peer.uptime_hours.min(PEER_SCORE_UPTIME_CAP_HOURS)
+            / PEER_SCORE_UPTIME_CAP_HOURS)
+            * PEER_SCORE_UPTIME_WEIGHT;
+
+        // Latency score: lower latency = higher score
+        let latency_normalized = if max_latency > min_latency {
+            1.0 - ((peer.latency_ms as f64 - min_latency) / (max_latency - min_latency))
+        } else {
+            1.0
+        };
+        let latency_score = latency_normalized * PEER_SCORE_LATENCY_WEIGHT;
 
-        for peer in &mut scored_peers {
-            let height_score = (peer.height as f64 / max_height.max(1.0)) * 0.3;
-            let uptime_score = (peer.uptime_hours.min(168.0) / 168.0) * 0.2;
+        peer.score = height_score + uptime_score + latency_score;
+    }
 
-            // Latency score: lower latency = higher score
-            let latency_normalized = if max_latency > min_latency {
-                1.0 - ((peer.latency_ms as f64 - min_latency) / (max_latency - min_latency))
-            } else {
-                1.0
-            };
-            let latency_score = latency_normalized * 0.5;
+    // Sort by score (descending), then address
+    scored_peers.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.address.cmp(&b.address))
+    });
 
-            peer.score = height_score + uptime_score + latency_score;
-        }
+    scored_peers
+}
 
-        // Sort by score (descending)
-        scored_peers.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
+/// How long the saved peers get to connect before the fallback seeds are dialled
+const SAVED_PEER_WAIT_SECS: u64 = 10;
 
-        // Log top peers
-        info!("\n[INFO] Best peers by composite score:");
-        for (i, peer) in scored_peers.iter().take(limit).enumerate() {
-            info!(
-                "  {}. {} - score: {:.3} (height: {}, uptime: {:.1}h, latency: {}ms)",
-                i + 1,
-                peer.address,
-                peer.score,
-                peer.height,
-                peer.uptime_hours,
-                peer.latency_ms
+/// Peers to dial at startup: the best DNS nodes, or the configured fallback seeds when
+/// DNS yields nothing and there are no saved peers either (see
+/// `fallback_if_saved_peers_fail` for when there are). The flag is true for DNS nodes,
+/// which may also take the outbound slots reserved for DNS.
+async fn discover_initial_peers(
+    node_meta: Arc<NodeMeta>,
+    settings: &NodeSettings,
+    my_port: u16,
+    saved_peer_count: usize,
+) -> (Vec<String>, bool) {
+    let dns_peers =
+        match fetch_best_nodes_from_dns(node_meta, settings, my_port, 10, &HashMap::new()).await {
+            Ok(peers) => peers,
+            Err(e) => {
+                log::warn!("Failed to fetch best nodes from DNS: {}", e);
+                Vec::new()
+            }
+        };
+
+    if !dns_peers.is_empty() {
+        info!(
+            "[INFO] Connecting to {} best nodes from DNS",
+            dns_peers.len()
+        );
+        return (dns_peers, true);
+    }
+    if saved_peer_count > 0 {
+        return (Vec::new(), false);
+    }
+
+    warn!(
+        "[WARN] No peers from DNS or peers.json; falling back to {} seed peers",
+        settings.fallback_seeds.len()
+    );
+    (settings.fallback_seeds.clone(), false)
+}
+
+/// The fallback seeds if none of the saved peers the P2P service dialled has connected
+/// within `wait`, or nothing as soon as one has
+async fn fallback_if_saved_peers_fail(
+    p2p: &astram_node::p2p::manager::PeerManager,
+    settings: &NodeSettings,
+    wait: Duration,
+) -> Vec<String> {
+    let deadline = std::time::Instant::now() + wait;
+    while p2p.peer_count() == 0 {
+        if std::time::Instant::now() >= deadline {
+            warn!(
+                "[WARN] No saved peer connected; falling back to {} seed peers",
+                settings.fallback_seeds.len()
             );
+            return settings.fallback_seeds.clone();
         }
+        sleep(Duration::from_millis(100)).await;
+    }
+    Vec::new()
+}
 
-        let best_peers: Vec<String> = scored_peers
-            .into_iter()
-            .take(limit)
-            .map(|p| p.address)
-            .collect();
+/// Query every DNS seed in order and merge their node lists, deduplicated by address
+/// (keeping the highest reported height). Fails only if no seed answered.
+async fn fetch_dns_nodes(
+    client: &reqwest::Client,
+    seeds: &[String],
+    limit: usize,
+) -> Result<Vec<DnsNodeInfo>, Box<dyn std::error::Error>> {
+    let mut merged: HashMap<String, DnsNodeInfo> = HashMap::new();
+    let mut last_error = None;
+    let mut answered = 0;
+
+    for dns_url in seeds {
+        info!("Fetching best nodes from DNS server at {}", dns_url);
+        let nodes_url = format!("{}/nodes?limit={}", dns_url, limit);
+        let result = match client.get(&nodes_url).send().await {
+            Ok(response) if response.status().is_success() => response
+                .json::<DnsNodesResponse>()
+                .await
+                .map_err(|e| e.to_string()),
+            Ok(response) => Err(format!("HTTP {}", response.status())),
+            Err(e) => Err(e.to_string()),
+        };
 
-        Ok(best_peers)
-    } else {
-        let error_text = response.text().await?;
-        Err(format!("Failed to fetch nodes from DNS server: {}", error_text).into())
+        match result {
+            Ok(result) => {
+                info!(
+                    "Retrieved {} nodes from DNS server {}",
+                    result.count, dns_url
+                );
+                answered += 1;
+                for node in result.nodes {
+                    let key = format!("{}:{}", node.address, node.port);
+                    match merged.get(&key) {
+                        Some(existing) if existing.height >= node.height => {}
+                        _ => {
+                            merged.insert(key, node);
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("DNS seed {} failed: {}", dns_url, e);
+                last_error = Some(e);
+            }
+        }
     }
+
+    if answered == 0 {
+        return Err(format!(
+            "Failed to fetch nodes from any DNS seed: {}",
+            last_error.unwrap_or_else(|| "no DNS seeds configured".to_string())
+        )
+        .into());
+    }
+    Ok(merged.into_values().collect())
 }
 
 /// Register this node with the DNS server (non-blocking version)
@@ -591,21 +814,84 @@ async fn register_with_dns(
     settings: &NodeSettings,
     height: u64,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let dns_url = settings.dns_server_url.clone();
     let node_port = settings.p2p_port;
 
     let client = reqwest::Client::builder()
         .timeout(Duration::from_secs(2))
         .build()?;
-    let register_url = format!("{}/register", dns_url);
 
     let payload = serde_json::json!({
         "port": node_port,
         "version": "0.1.0",
-        "height": height
+        "height": height,
+        "network_magic": astram_node::p2p::manager::resolve_network_magic(),
     });
 
-    let response = client.post(&register_url).json(&payload).send().await?;
+    // Register with every seed so each registry can hand us out; one success is enough
+    let mut last_error = None;
+    let mut registered = false;
+    for dns_url in &settings.dns_seeds {
+        match register_with_dns_seed(&client, dns_url, &payload).await {
+            Ok(()) => registered = true,
+            Err(e) => {
+                warn!("DNS registration with {} failed: {}", dns_url, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    match (registered, last_error) {
+        (true, _) => Ok(()),
+        (false, Some(e)) => Err(e),
+        (false, None) => Err("no DNS seeds configured".into()),
+    }
+}
+
+/// Ask the node running with `settings` to snapshot its database (`--backup`). The
+/// running node holds the DB lock, so the snapshot has to be taken from inside it.
+async fn request_backup(settings: &NodeSettings) -> Result<(), Box<dyn std::error::Error>> {
+    if settings.admin_token.is_empty() {
+        return Err("ADMIN_TOKEN is not set in nodeSettings.conf".into());
+    }
+    // A wildcard bind address is reachable on loopback
+    let host = match settings.http_bind_addr.as_str() {
+        "0.0.0.0" | "::" | "" => "127.0.0.1",
+        addr => addr,
+    };
+    let url = format!("http://{}:{}/admin/backup", host, settings.http_port);
+    let response = reqwest::Client::new()
+        .post(&url)
+        .bearer_auth(&settings.admin_token)
+        .send()
+        .await?;
+
+    let status = response.status();
+    let body: Value = response.json().await?;
+    if !status.is_success() {
+        return Err(format!("{} {}", status, body["error"].as_str().unwrap_or("")).into());
+    }
+    println!(
+        "[INFO] Snapshot at tip {} written to {}",
+        body["tip"].as_str().unwrap_or("none"),
+        body["path"].as_str().unwrap_or("?")
+    );
+    Ok(())
+}
+
+async fn register_with_dns_seed(
+    client: &reqwest::Client,
+    dns_url: &str,
+    payload: &serde_json::Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let register_url = format!("{}/register", dns_url);
+    // Sent in canonical form so a signature over the payload covers exactly these bytes
+    let body = to_canonical_bytes(payload)?;
+    let response = client
+        .post(&register_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await?;
 
     if response.status().is_success() {
         #[derive(serde::Deserialize)]
@@ -761,6 +1047,22 @@ async fn sync_blockchain(
     Ok(())
 }
 
+/// Mining rounds are skipped while the chain trails most peers by more than this
+const MINING_MAX_LAG_BLOCKS: u64 = 6;
+
+/// Poll `is_syncing` until it reports false or `max_wait` elapses. Returns whether the
+/// node caught up in time.
+async fn wait_for_sync(is_syncing: impl Fn() -> bool, max_wait: Duration, poll: Duration) -> bool {
+    let start = std::time::Instant::now();
+    while is_syncing() {
+        if start.elapsed() >= max_wait {
+            return false;
+        }
+        sleep(poll).await;
+    }
+    true
+}
+
 async fn start_services(
     node_handle: NodeHandle,
     p2p_handle: Arc<astram_node::p2p::manager::PeerManager>,
@@ -769,6 +1071,7 @@ async fn start_services(
     miner_address: String,
     shutdown_flag: Arc<AtomicBool>,
     settings: Arc<NodeSettings>,
+    rate_limiter: Arc<RateLimiter>,
 ) -> (
     Vec<tokio::task::JoinHandle<()>>,
     tokio::task::JoinHandle<()>,
@@ -905,30 +1208,38 @@ async fn start_services(
         // Wait a bit for DNS registration to complete
         sleep(Duration::from_secs(2)).await;
 
-        // Initial connection to best nodes
-        match fetch_best_nodes_from_dns(node_meta_for_p2p.clone(), &settings_p2p, my_node_port, 10)
-            .await
-        {
-            Ok(peer_addrs) => {
-                info!(
-                    "[INFO] Connecting to {} best nodes from DNS",
-                    peer_addrs.len()
-                );
-                for addr in peer_addrs {
-                    let p2p_clone = p2p_handle_for_task.clone();
-                    let addr_clone = addr.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = p2p_clone.connect_peer(&addr_clone).await {
-                            log::warn!("Failed to connect to peer {}: {:?}", addr_clone, e);
-                        } else {
-                            info!("[OK] Connected to peer: {}", addr_clone);
-                        }
-                    });
+        // Initial connection to best nodes (saved peers are dialled by the P2P service)
+        let saved_peer_count = p2p_handle_for_task.load_saved_peers().len();
+        let (mut peer_addrs, from_dns) = discover_initial_peers(
+            node_meta_for_p2p.clone(),
+            &settings_p2p,
+            my_node_port,
+            saved_peer_count,
+        )
+        .await;
+        if peer_addrs.is_empty() && saved_peer_count > 0 {
+            peer_addrs = fallback_if_saved_peers_fail(
+                &p2p_handle_for_task,
+                &settings_p2p,
+                Duration::from_secs(SAVED_PEER_WAIT_SECS),
+            )
+            .await;
+        }
+        for addr in peer_addrs {
+            let p2p_clone = p2p_handle_for_task.clone();
+            let addr_clone = addr.clone();
+            tokio::spawn(async move {
+                let connected = if from_dns {
+                    p2p_clone.connect_dns_peer(&addr_clone).await
+                } else {
+                    p2p_clone.connect_peer(&addr_clone).await
+                };
+                if let Err(e) = connected {
+                    log::warn!("Failed to connect to peer {}: {:?}", addr_clone, e);
+                } else {
+                    info!("[OK] Connected to peer: {}", addr_clone);
                 }
-            }
-            Err(e) => {
-                log::warn!("Failed to fetch best nodes from DNS: {}", e);
-            }
+            });
         }
 
         // Periodically refresh connections to best nodes (every 10 minutes)
@@ -947,6 +1258,7 @@ async fn start_services(
                         &settings_p2p,
                         my_node_port,
                         10,
+                        &p2p_handle_for_task.get_peer_latencies(),
                     )
                     .await
                     {
@@ -959,7 +1271,7 @@ async fn start_services(
                         let p2p_clone = p2p_handle_for_task.clone();
                         let addr_clone = addr.clone();
                         tokio::spawn(async move {
-                            let _ = p2p_clone.connect_peer(&addr_clone).await;
+                            let _ = p2p_clone.connect_dns_peer(&addr_clone).await;
                         });
                     }
                 }
@@ -994,17 +1306,55 @@ async fn start_services(
     let http_addr = to_socket_addr(
         &settings.http_bind_addr,
         settings.http_port,
-        SocketAddr::from(([127, 0, 0, 1], 19533)),
+        SocketAddr::from(([127, 0, 0, 1], Ports::DEFAULT.http_rpc)),
     );
     // start HTTP server in background thread (warp is async so run in tokio)
     let server_p2p = p2p_handle.clone();
     let server_chain = chain_state.clone();
     let server_meta = node_meta.clone();
+    let dashboard_path = settings.resolve_dashboard_path();
+    let access_log = settings.access_log;
+    let min_confirmations_for_spend = settings.min_confirmations_for_spend;
+    let max_concurrent_requests = settings.http_max_concurrent_requests;
+    let admin_token = settings.admin_token.clone();
+    let backup_dir = std::path::PathBuf::from(&settings.backup_dir);
     let server_handle = tokio::spawn(async move {
-        run_server(nh, server_p2p, server_chain, server_meta, http_addr).await;
+        run_server(
+            nh,
+            server_p2p,
+            server_chain,
+            server_meta,
+            http_addr,
+            dashboard_path,
+            access_log,
+            min_confirmations_for_spend,
+            max_concurrent_requests,
+            admin_token,
+            backup_dir,
+            rate_limiter,
+        )
+        .await;
     });
 
-    // Step 6: Start mining
+    // Step 6: Start mining once caught up, so startup hashpower isn't spent on a stale tip
+    let sync_max_wait = Duration::from_secs(settings.sync_max_wait_secs);
+    let p2p_sync = p2p_handle.clone();
+    if wait_for_sync(
+        || p2p_sync.is_syncing(),
+        sync_max_wait,
+        Duration::from_secs(1),
+    )
+    .await
+    {
+        info!("[OK] Synced with peers");
+    } else {
+        log::warn!(
+            "Still {} blocks behind after waiting {:?}; mining resumes once within {} blocks",
+            p2p_handle.blocks_behind(),
+            sync_max_wait,
+            MINING_MAX_LAG_BLOCKS
+        );
+    }
     println!("[INFO] Step 6: Starting mining...");
 
     // Mining loop - run in main task, not spawned
@@ -1014,6 +1364,9 @@ async fn start_services(
         chain_state.clone(),
         miner_address,
         shutdown_flag.clone(),
+        settings.mining_threads,
+        settings.mining_throttle_percent,
+        settings.mining_propagate_first,
     )
     .await;
 
@@ -1021,12 +1374,32 @@ async fn start_services(
     (task_handles, server_handle)
 }
 
+/// Run what follows a mined block's successful commit. `broadcast` gets the validated
+/// block and should only start the send; with `propagate_first` it runs before the
+/// local `bookkeeping`, so peers hear of the block without waiting on it.
+fn publish_mined_block<B, K>(block: &Block, propagate_first: bool, broadcast: B, bookkeeping: K)
+where
+    B: FnOnce(Block),
+    K: FnOnce(&Block),
+{
+    if propagate_first {
+        broadcast(block.clone());
+        bookkeeping(block);
+    } else {
+        bookkeeping(block);
+        broadcast(block.clone());
+    }
+}
+
 async fn mining_loop(
     node_handle: NodeHandle,
     p2p_handle: Arc<astram_node::p2p::manager::PeerManager>,
     chain_state: Arc<Mutex<ChainState>>,
     miner_address: String,
     shutdown_flag: Arc<AtomicBool>,
+    mining_threads: usize,
+    throttle_percent: u8,
+    propagate_first: bool,
 ) {
     let requested_backend = std::env::var("MINER_BACKEND")
         .unwrap_or_else(|_| "cpu".to_string())
@@ -1043,6 +1416,24 @@ async fn mining_loop(
     } else {
         println!("[INFO] Using CPU miner backend");
     }
+    let mining_threads = consensus::resolve_mining_threads(mining_threads);
+    let throttle_percent = match throttle_percent {
+        1..=99 => throttle_percent,
+        _ => 100,
+    };
+    node_handle
+        .mining
+        .throttle_percent
+        .store(throttle_percent, OtherOrdering::Relaxed);
+    if miner_backend != "cuda" {
+        println!("[INFO] CPU mining threads: {}", mining_threads);
+        if throttle_percent < 100 {
+            println!(
+                "[INFO] CPU mining throttled to {}% duty cycle",
+                throttle_percent
+            );
+        }
+    }
 
     loop {
         // Check shutdown flag
@@ -1056,6 +1447,21 @@ async fn mining_loop(
             break;
         }
 
+        // Don't mine on a tip our peers have long moved past; the block would be orphaned
+        let blocks_behind = p2p_handle.blocks_behind();
+        if blocks_behind > MINING_MAX_LAG_BLOCKS {
+            node_handle
+                .mining
+                .active
+                .store(false, OtherOrdering::SeqCst);
+            info!(
+                "[INFO] Mining paused: {} blocks behind peers, waiting for sync",
+                blocks_behind
+            );
+            sleep(Duration::from_secs(5)).await;
+            continue;
+        }
+
         // Snapshot pending txs + mining params while holding the lock briefly
         println!("[DEBUG] Mining: Attempting to acquire WRITE lock...");
         let (snapshot_txs, difficulty, prev_hash, index_snapshot, cancel_flag, hashrate_shared) = {
@@ -1136,47 +1542,105 @@ async fn mining_loop(
         println!("[DEBUG] Mining: WRITE lock released");
         // Write lock released - calculate fees OUTSIDE the lock
 
-        // Calculate total fees from pending transactions (with separate read lock for DB)
+        // Transactions past their valid_until can't be mined; they are already out of
+        // pending, so dropping them only means forgetting them
+        let snapshot_txs: Vec<_> = {
+            let now = chrono::Utc::now().timestamp();
+            let (live, expired): (Vec<_>, Vec<_>) = snapshot_txs
+                .into_iter()
+                .partition(|tx| !tx.is_expired_at(now));
+            if !expired.is_empty() {
+                println!("[INFO] Dropping {} expired tx(s)", expired.len());
+                let txids: Vec<&str> = expired.iter().map(|tx| tx.txid.as_str()).collect();
+                node_handle.mempool.lock().unwrap().forget(&txids);
+            }
+            live
+        };
+
+        // Coinbase maturity: spends that only become valid at a later height stay in the mempool
+        let snapshot_txs: Vec<_> = {
+            let (ready, immature): (Vec<_>, Vec<_>) = {
+                let bc = node_handle.bc.lock().unwrap();
+                snapshot_txs.into_iter().partition(|tx| {
+                    !bc.spends_immature_coinbase(tx, index_snapshot)
+                        .unwrap_or(false)
+                })
+            };
+            if !immature.is_empty() {
+                println!(
+                    "[INFO] Deferring {} tx(s) spending immature coinbase outputs",
+                    immature.len()
+                );
+                node_handle.mempool.lock().unwrap().pending.extend(immature);
+            }
+            ready
+        };
+
+        // Calculate the fee of each pending transaction (with separate read lock for DB)
         println!("[DEBUG] Mining: Attempting to acquire READ lock for fees...");
-        let total_fees = {
+        let tx_fees: Vec<U256> = {
             let state = node_handle.clone();
             println!("[DEBUG] Mining: READ lock acquired for fees");
-            let mut fee_sum = U256::zero();
             let bc = state.bc.lock().unwrap();
 
-            for tx in &snapshot_txs {
-                // Calculate fee: input_sum - output_sum
-                let mut input_sum = U256::zero();
-                let mut output_sum = U256::zero();
-
-                // Sum inputs (from UTXO)
-                for inp in &tx.inputs {
-                    let ukey = format!("u:{}:{}", inp.txid, inp.vout);
-                    if let Ok(Some(blob)) = bc.db.get(ukey.as_bytes()) {
-                        if let Ok((utxo, _)) =
-                            bincode::decode_from_slice::<Utxo, _>(&blob, *BINCODE_CONFIG)
-                        {
-                            input_sum += utxo.amount();
+            snapshot_txs
+                .iter()
+                .map(|tx| {
+                    // Calculate fee: input_sum - output_sum
+                    let mut input_sum = Some(U256::zero());
+
+                    // Sum inputs (from UTXO)
+                    for inp in &tx.inputs {
+                        let ukey = format!("u:{}:{}", inp.txid, inp.vout);
+                        if let Ok(Some(blob)) = bc.db.get(ukey.as_bytes()) {
+                            if let Ok((utxo, _)) =
+                                bincode::decode_from_slice::<Utxo, _>(&blob, *BINCODE_CONFIG)
+                            {
+                                input_sum =
+                                    input_sum.and_then(|sum| sum.checked_add(utxo.amount()));
+                            }
                         }
                     }
-                }
 
-                // Sum outputs
-                for out in &tx.outputs {
-                    output_sum += out.amount();
-                }
+                    // Fee is the difference; an overflowing tx contributes nothing (and the
+                    // block would be rejected for it anyway)
+                    input_sum.and_then(|sum| tx.fee(sum)).unwrap_or_default()
+                })
+                .collect()
+        };
+        println!("[DEBUG] Mining: READ lock released after fees");
+        // Read lock released
 
-                // Fee is the difference
-                if input_sum >= output_sum {
-                    let fee = input_sum - output_sum;
-                    fee_sum += fee;
+        // Fill the block best fee rate first up to the size cap; the rest waits in the
+        // mempool for a later block
+        let (snapshot_txs, total_fees) = {
+            let selected: std::collections::HashSet<usize> = consensus::select_block_transactions(
+                &snapshot_txs,
+                &tx_fees,
+                MAX_BLOCK_BYTES - BLOCK_RESERVED_BYTES,
+            )
+            .into_iter()
+            .collect();
+            let mut included = Vec::with_capacity(selected.len());
+            let mut deferred = Vec::new();
+            let mut total_fees = U256::zero();
+            for (i, (tx, fee)) in snapshot_txs.into_iter().zip(tx_fees).enumerate() {
+                if selected.contains(&i) {
+                    total_fees = total_fees.saturating_add(fee);
+                    included.push(tx);
+                } else {
+                    deferred.push(tx);
                 }
             }
-
-            fee_sum
+            if !deferred.is_empty() {
+                println!(
+                    "[INFO] Block full: deferring {} tx(s) to a later block",
+                    deferred.len()
+                );
+                node_handle.mempool.lock().unwrap().pending.extend(deferred);
+            }
+            (included, total_fees)
         };
-        println!("[DEBUG] Mining: READ lock released after fees");
-        // Read lock released
 
         // prepare block transactions: coinbase + pending
         // NOTE: we pass pending txs to consensus::mine_block_with_coinbase which will prepend coinbase
@@ -1184,8 +1648,23 @@ async fn mining_loop(
         println!("[INFO] Mining {} pending tx(s)...", block_txs_for_logging);
 
         // Coinbase reward = block reward + total fees
-        let base_reward = current_block_reward_snapshot();
-        let coinbase_reward = base_reward + total_fees;
+        let base_reward = calculate_block_reward(index_snapshot);
+        let Some(coinbase_reward) = base_reward.checked_add(total_fees) else {
+            // The block would be invalid; hand the transactions back and try again later
+            log::warn!(
+                "Coinbase reward overflows (base: {} + fees: {}), skipping this template",
+                base_reward,
+                total_fees
+            );
+            node_handle
+                .mempool
+                .lock()
+                .unwrap()
+                .pending
+                .extend(snapshot_txs);
+            sleep(Duration::from_secs(5)).await;
+            continue;
+        };
 
         if total_fees > U256::zero() {
             let fees_asrm = total_fees / U256::from(1_000_000_000_000_000_000u64);
@@ -1253,6 +1732,8 @@ async fn mining_loop(
                 coinbase_reward,
                 cancel_for_thread,
                 Some(hashrate_for_thread),
+                mining_threads,
+                throttle_percent,
             );
             println!("[DEBUG] 🔨 Mining thread: consensus::mine_block_with_coinbase returned!");
             block
@@ -1269,64 +1750,59 @@ async fn mining_loop(
                 // The block is already valid as-is from mining.
 
                 println!("[DEBUG] Validating and inserting block into blockchain DB...");
-                match node_handle
-                    .bc
-                    .lock()
-                    .unwrap()
-                    .validate_and_insert_block(&block)
-                {
+                // Commit to DB and update the in-memory chain in one bc -> chain section.
+                // The block is marked as ours here, before any broadcast, so a peer echoing
+                // it back is always recognized.
+                let inserted = {
+                    let mut bc = node_handle.bc.lock().unwrap();
+                    let mut chain = chain_state.lock().unwrap();
+                    bc.validate_and_insert_block(&block).map(|_| {
+                        chain.push_block(&block);
+                        // Track this block as recently mined (to ignore when received from peers)
+                        chain.mark_mined(&block.hash, chrono::Utc::now().timestamp());
+                    })
+                };
+                match inserted {
                     Ok(_) => {
                         println!(
                             "[OK]✅ Block saved to DB - index={} hash={}",
                             block.header.index, block.hash
                         );
-
-                        // Update mining statistics
-                        node_handle
-                            .mining
-                            .blocks_mined
-                            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-
-                        // Calculate hashrate (rough estimate)
-                        let mining_duration = mining_start.elapsed().as_secs_f64();
-                        if mining_duration > 0.0 {
-                            // Estimate: 2^difficulty hashes attempted in mining_duration seconds
-                            let estimated_hashes = 2_u64.pow(difficulty_local) as f64;
-                            let hashrate = estimated_hashes / mining_duration;
-                            *node_handle.mining.current_hashrate.lock().unwrap() = hashrate;
-                        }
-
-                        let block_to_broadcast = block.clone();
-
-                        {
-                            let mut chain = chain_state.lock().unwrap();
-                            chain.blockchain.push(block.clone());
-                            chain.enforce_memory_limit(); // Security: Enforce memory limit
-                        }
-                        // pending already cleared earlier
-
-                        // Update P2P manager height
-                        p2p_handle.set_my_height(block.header.index + 1);
-
-                        // Track this block as recently mined (to ignore when received from peers)
-                        let now = chrono::Utc::now().timestamp();
-                        {
-                            let mut chain = chain_state.lock().unwrap();
-                            chain.recently_mined_blocks.insert(block.hash.clone(), now);
-
-                            // Clean up old entries (older than 5 minutes)
-                            chain
-                                .recently_mined_blocks
-                                .retain(|_, &mut timestamp| now - timestamp < 300);
-                        }
-
-                        println!("[OK] Block mined! Broadcasting...");
-
-                        // -------------------------
-                        // Broadcast mined block
-                        // -------------------------
-                        // broadcast_block returns () (fire-and-forget), so just await it
-                        p2p_handle.broadcast_block(&block_to_broadcast).await;
+                        publish_mined_block(
+                            &block,
+                            propagate_first,
+                            |validated| {
+                                // Update P2P manager height
+                                p2p_handle.set_my_height(validated.header.index + 1);
+
+                                println!("[OK] Block mined! Broadcasting...");
+                                let p2p = p2p_handle.clone();
+                                tokio::spawn(async move {
+                                    p2p.broadcast_block(&validated).await;
+                                });
+                            },
+                            |block| {
+                                node_handle.notify_block(block);
+                                // Pending txs were taken before mining; this releases their
+                                // pending nonces and drops conflicts that arrived meanwhile
+                                node_handle.mempool.lock().unwrap().remove_confirmed(block);
+
+                                // Update mining statistics
+                                node_handle
+                                    .mining
+                                    .blocks_mined
+                                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+                                // Calculate hashrate (rough estimate)
+                                let mining_duration = mining_start.elapsed().as_secs_f64();
+                                if mining_duration > 0.0 {
+                                    // Estimate: 2^difficulty hashes attempted in mining_duration seconds
+                                    let estimated_hashes = 2_u64.pow(difficulty_local) as f64;
+                                    let hashrate = estimated_hashes / mining_duration;
+                                    *node_handle.mining.current_hashrate.lock().unwrap() = hashrate;
+                                }
+                            },
+                        );
                     }
                     Err(e) => {
                         eprintln!("Block insertion failed: {}", e);
@@ -1359,8 +1835,11 @@ async fn mining_loop(
                     .store(false, OtherOrdering::SeqCst);
                 *node_handle.mining.current_hashrate.lock().unwrap() = 0.0;
 
-                // Only requeue txs if it wasn't a cancellation
-                if !error_msg.contains("cancelled") && !error_msg.contains("Mining cancelled") {
+                // Only requeue txs if it wasn't a cancellation, unless we're shutting down
+                // (the mempool is saved on exit, so the snapshot must not be lost)
+                let cancelled =
+                    error_msg.contains("cancelled") || error_msg.contains("Mining cancelled");
+                if !cancelled || shutdown_flag.load(OtherOrdering::SeqCst) {
                     let mut mempool = node_handle.mempool.lock().unwrap();
                     for tx in snapshot_txs.into_iter() {
                         mempool.pending.push(tx);
@@ -1384,8 +1863,254 @@ async fn mining_loop(
     }
 }
 
-fn current_block_reward_snapshot() -> U256 {
-    // For now, always return initial reward (genesis/early blocks)
-    // In production, this would take current blockchain height as parameter
-    initial_block_reward()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve a single canned /nodes response and return the seed URL
+    async fn fake_dns_seed(nodes: serde_json::Value) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let body =
+            serde_json::json!({ "count": nodes.as_array().map_or(0, |n| n.len()), "nodes": nodes })
+                .to_string();
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn dns_node(address: &str, height: u64) -> serde_json::Value {
+        serde_json::json!({
+            "address": address,
+            "port": 8335,
+            "version": "0.1.0",
+            "height": height,
+            "last_seen": 0,
+            "first_seen": 0,
+            "uptime_hours": 1.0
+        })
+    }
+
+    #[tokio::test]
+    async fn dns_seeds_fail_over_and_merge() {
+        // a port nothing listens on
+        let dead = {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        let first = fake_dns_seed(serde_json::json!([dns_node("10.0.0.1", 5)])).await;
+        let second = fake_dns_seed(serde_json::json!([
+            dns_node("10.0.0.1", 9),
+            dns_node("10.0.0.2", 3)
+        ]))
+        .await;
+
+        let client = reqwest::Client::new();
+        let mut nodes = fetch_dns_nodes(&client, &[dead.clone(), first, second], 10)
+            .await
+            .unwrap();
+        nodes.sort_by(|a, b| a.address.cmp(&b.address));
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].height, 9);
+
+        assert!(fetch_dns_nodes(&client, &[dead], 10).await.is_err());
+    }
+
+    fn scored(address: &str, height: u64, uptime_hours: f64, latency_ms: u64) -> ScoredPeer {
+        ScoredPeer {
+            address: address.to_string(),
+            height,
+            uptime_hours,
+            latency_ms,
+            score: 0.0,
+        }
+    }
+
+    #[test]
+    fn peer_ranking_is_pinned_for_fixed_candidates() {
+        let candidates = vec![
+            scored("10.0.0.1:8335", 100, 168.0, 50), // 0.3 + 0.2 + 0.0
+            scored("10.0.0.2:8335", 100, 0.0, 10),   // 0.3 + 0.0 + 0.5
+            scored("10.0.0.3:8335", 50, 168.0, 10),  // 0.15 + 0.2 + 0.5
+            scored("10.0.0.4:8335", 100, 168.0, 10), // 1.0
+            scored("10.0.0.0:8335", 100, 500.0, 10), // ties with .4 (uptime capped)
+        ];
+        let expected = [
+            ("10.0.0.0:8335", 1.0),
+            ("10.0.0.4:8335", 1.0),
+            ("10.0.0.3:8335", 0.85),
+            ("10.0.0.2:8335", 0.8),
+            ("10.0.0.1:8335", 0.5),
+        ];
+
+        let forward = rank_peers(candidates.clone());
+        for (peer, (address, score)) in forward.iter().zip(expected) {
+            assert_eq!(peer.address, address);
+            assert!(
+                (peer.score - score).abs() < 1e-9,
+                "{}: {}",
+                address,
+                peer.score
+            );
+        }
+
+        // DNS listing order doesn't change the result
+        let reversed = rank_peers(candidates.into_iter().rev().collect());
+        let addresses =
+            |peers: &[ScoredPeer]| peers.iter().map(|p| p.address.clone()).collect::<Vec<_>>();
+        assert_eq!(addresses(&reversed), addresses(&forward));
+    }
+
+    #[tokio::test]
+    async fn candidates_probed_through_injected_latency() {
+        let node = |address: &str| DnsNodeInfo {
+            address: address.to_string(),
+            port: 8335,
+            _version: "0.1.0".to_string(),
+            height: 10,
+            _last_seen: 0,
+            _first_seen: 0,
+            uptime_hours: 1.0,
+        };
+        let probed: HashMap<String, u64> = [("10.0.0.1:8335".to_string(), 40)].into();
+        // already-connected peers use their ping latency and are never probed
+        let known: HashMap<String, u64> = [("10.0.0.2:8335".to_string(), 7)].into();
+
+        let peers = probe_candidates(
+            vec![node("10.0.0.1"), node("10.0.0.2"), node("10.0.0.3")],
+            &known,
+            |addr| {
+                assert_ne!(addr, "10.0.0.2:8335");
+                let latency = probed.get(&addr).copied();
+                async move { latency }
+            },
+        )
+        .await;
+
+        let latencies: Vec<(&str, u64)> = peers
+            .iter()
+            .map(|p| (p.address.as_str(), p.latency_ms))
+            .collect();
+        // 10.0.0.3 is unreachable and dropped
+        assert_eq!(latencies, [("10.0.0.1:8335", 40), ("10.0.0.2:8335", 7)]);
+    }
+
+    #[tokio::test]
+    async fn fallback_seeds_used_when_dns_is_empty() {
+        let node_meta = Arc::new(NodeMeta {
+            miner_address: Arc::new(Mutex::new(String::new())),
+            my_public_address: Arc::new(Mutex::new(None)),
+            node_start_time: std::time::Instant::now(),
+            eth_to_astram_tx: Arc::new(Mutex::new(EthTxMap::default())),
+        });
+        let settings = NodeSettings {
+            dns_seeds: vec![fake_dns_seed(serde_json::json!([])).await],
+            fallback_seeds: vec!["10.1.1.1:8335".to_string()],
+            ..NodeSettings::default()
+        };
+
+        let (peers, from_dns) = discover_initial_peers(node_meta.clone(), &settings, 8335, 0).await;
+        assert_eq!(peers, settings.fallback_seeds);
+        assert!(!from_dns);
+
+        // saved peers take precedence over the fallback list
+        let settings = NodeSettings {
+            dns_seeds: vec![fake_dns_seed(serde_json::json!([])).await],
+            ..settings
+        };
+        assert!(
+            discover_initial_peers(node_meta, &settings, 8335, 2)
+                .await
+                .0
+                .is_empty()
+        );
+
+        // ...until none of them connects
+        let p2p = astram_node::p2p::manager::PeerManager::new();
+        let fallback = fallback_if_saved_peers_fail(&p2p, &settings, Duration::ZERO).await;
+        assert_eq!(fallback, settings.fallback_seeds);
+    }
+
+    #[tokio::test]
+    async fn mining_waits_while_syncing() {
+        use std::sync::atomic::AtomicU64;
+
+        let poll = Duration::from_millis(5);
+        // A node that never catches up gives up after the max wait instead of mining
+        let start = std::time::Instant::now();
+        let max_wait = Duration::from_millis(50);
+        assert!(!wait_for_sync(|| true, max_wait, poll).await);
+        assert!(start.elapsed() >= max_wait);
+
+        // Once sync finishes the wait ends without using the whole budget
+        let polls = AtomicU64::new(0);
+        let caught_up = wait_for_sync(
+            || polls.fetch_add(1, OtherOrdering::SeqCst) < 3,
+            Duration::from_secs(10),
+            poll,
+        )
+        .await;
+        assert!(caught_up);
+        assert_eq!(polls.load(OtherOrdering::SeqCst), 4);
+    }
+
+    fn mined_block() -> Block {
+        Block {
+            header: Astram_core::block::BlockHeader {
+                index: 7,
+                previous_hash: "0".repeat(64),
+                merkle_root: String::new(),
+                timestamp: 0,
+                nonce: 0,
+                difficulty: 1,
+            },
+            transactions: vec![],
+            hash: format!("{:064x}", 7),
+        }
+    }
+
+    #[test]
+    fn propagate_first_broadcasts_before_bookkeeping() {
+        let block = mined_block();
+        let (sent_tx, sent_rx) = std::sync::mpsc::channel::<Block>();
+        let mut bookkept = false;
+
+        publish_mined_block(
+            &block,
+            true,
+            |validated| sent_tx.send(validated).unwrap(),
+            |b| {
+                // The broadcast has already been handed the validated block
+                let sent = sent_rx.try_recv().expect("broadcast should come first");
+                assert_eq!(sent.hash, b.hash);
+                bookkept = true;
+            },
+        );
+        assert!(bookkept);
+    }
+
+    #[test]
+    fn legacy_order_keeps_bookkeeping_first() {
+        let block = mined_block();
+        let (sent_tx, sent_rx) = std::sync::mpsc::channel::<Block>();
+
+        publish_mined_block(
+            &block,
+            false,
+            |validated| sent_tx.send(validated).unwrap(),
+            |_| assert!(sent_rx.try_recv().is_err()),
+        );
+        assert_eq!(sent_rx.try_recv().unwrap().hash, block.hash);
+    }
 }
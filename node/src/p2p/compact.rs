@@ -0,0 +1,236 @@
+// Compact block relay (BIP152-style): a block travels as its header plus short ids of
+// its transactions, and the receiver rebuilds it from its own mempool
+use crate::p2p::messages::{CompactBlock, PrefilledTx};
+use Astram_core::block::{Block, compute_merkle_root};
+use Astram_core::transaction::Transaction;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+/// Short id of `txid` in the compact block `block_hash`: the first 6 bytes of
+/// sha256(block hash || nonce || txid). The per-block salt keeps an attacker from
+/// precomputing colliding transactions.
+pub fn short_id(block_hash: &str, nonce: u64, txid: &str) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update(block_hash.as_bytes());
+    hasher.update(nonce.to_le_bytes());
+    hasher.update(txid.as_bytes());
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes[..6].copy_from_slice(&digest[..6]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Compact form of `block`, with the coinbase prefilled and every other transaction as
+/// a short id salted with `nonce`
+pub fn compact_block(block: &Block, nonce: u64) -> CompactBlock {
+    let mut prefilled = Vec::new();
+    let mut short_ids = Vec::new();
+    for (index, tx) in block.transactions.iter().enumerate() {
+        if index == 0 {
+            prefilled.push(PrefilledTx {
+                index: 0,
+                tx: tx.clone(),
+            });
+        } else {
+            short_ids.push(short_id(&block.hash, nonce, &tx.txid));
+        }
+    }
+    CompactBlock {
+        header: block.header.clone(),
+        hash: block.hash.clone(),
+        nonce,
+        short_ids,
+        prefilled,
+    }
+}
+
+/// Transactions from `mempool` whose short id appears in `compact`, by short id. An id
+/// matched by two transactions is left out, so it is fetched rather than guessed.
+pub fn mempool_matches<'a>(
+    compact: &CompactBlock,
+    mempool: impl IntoIterator<Item = &'a Transaction>,
+) -> HashMap<u64, Transaction> {
+    let wanted: HashSet<u64> = compact.short_ids.iter().copied().collect();
+    let mut matches = HashMap::new();
+    let mut ambiguous = HashSet::new();
+    for tx in mempool {
+        let id = short_id(&compact.hash, compact.nonce, &tx.txid);
+        if wanted.contains(&id) && matches.insert(id, tx.clone()).is_some() {
+            ambiguous.insert(id);
+        }
+    }
+    matches.retain(|id, _| !ambiguous.contains(id));
+    matches
+}
+
+/// A compact block being rebuilt: its transactions in block order, None where the
+/// mempool had no match
+#[derive(Debug, Clone)]
+pub struct PartialBlock {
+    compact: CompactBlock,
+    txs: Vec<Option<Transaction>>,
+}
+
+impl PartialBlock {
+    /// Lay out the block from its prefilled transactions and the mempool `matches`.
+    /// Err if the compact block can't be laid out (a prefilled index out of range or
+    /// repeated, or two transactions sharing a short id); the full block is needed then.
+    pub fn new(compact: CompactBlock, matches: &HashMap<u64, Transaction>) -> Result<Self, String> {
+        let total = compact.short_ids.len() + compact.prefilled.len();
+        let mut txs: Vec<Option<Transaction>> = vec![None; total];
+        let mut prefilled = vec![false; total];
+        for entry in &compact.prefilled {
+            let index = entry.index as usize;
+            if index >= total || prefilled[index] {
+                return Err(format!("bad prefilled index {}", entry.index));
+            }
+            prefilled[index] = true;
+            txs[index] = Some(entry.tx.clone());
+        }
+
+        let mut seen = HashSet::new();
+        let mut short_ids = compact.short_ids.iter();
+        for (slot, _) in txs.iter_mut().zip(&prefilled).filter(|(_, p)| !**p) {
+            // One short id per slot that isn't prefilled, by construction of `total`
+            let Some(id) = short_ids.next() else {
+                break;
+            };
+            if !seen.insert(*id) {
+                return Err(format!("short id {:012x} appears twice", id));
+            }
+            *slot = matches.get(id).cloned();
+        }
+        Ok(Self { compact, txs })
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.compact.hash
+    }
+
+    /// Positions still missing, to ask for with GetBlockTxn
+    pub fn missing(&self) -> Vec<u32> {
+        self.txs
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.is_none())
+            .map(|(index, _)| index as u32)
+            .collect()
+    }
+
+    /// Fill the missing positions, in order, with the transactions of a BlockTxn
+    pub fn fill(&mut self, txs: Vec<Transaction>) -> Result<(), String> {
+        let missing = self.missing();
+        if txs.len() != missing.len() {
+            return Err(format!(
+                "{} transactions for {} missing",
+                txs.len(),
+                missing.len()
+            ));
+        }
+        for (index, tx) in missing.into_iter().zip(txs) {
+            self.txs[index as usize] = Some(tx);
+        }
+        Ok(())
+    }
+
+    /// The rebuilt block. None while transactions are missing, or if they don't hash to
+    /// the header's merkle root (a short id matched the wrong mempool transaction), in
+    /// which case the full block has to be fetched.
+    pub fn into_block(self) -> Option<Block> {
+        let transactions: Vec<Transaction> = self.txs.into_iter().collect::<Option<_>>()?;
+        let txids: Vec<String> = transactions.iter().map(|tx| tx.txid.clone()).collect();
+        if compute_merkle_root(&txids) != self.compact.header.merkle_root {
+            return None;
+        }
+        Some(Block {
+            header: self.compact.header,
+            transactions,
+            hash: self.compact.hash,
+        })
+    }
+}
+
+/// Block and transaction fixtures for the compact relay tests here and in the manager
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use Astram_core::block::BlockHeader;
+
+    /// An input-less transaction whose txid is byte `n` repeated
+    pub fn tx(n: u8) -> Transaction {
+        Transaction {
+            txid: format!("{:02x}", n).repeat(32),
+            eth_hash: String::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            timestamp: n as i64,
+            valid_until: None,
+        }
+    }
+
+    pub fn txids(txs: &[Transaction]) -> Vec<String> {
+        txs.iter().map(|tx| tx.txid.clone()).collect()
+    }
+
+    /// A block carrying `txs` under a matching merkle root
+    pub fn block(txs: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                index: 7,
+                previous_hash: "00".repeat(32),
+                merkle_root: compute_merkle_root(&txids(&txs)),
+                timestamp: 0,
+                nonce: 0,
+                difficulty: 0,
+            },
+            transactions: txs,
+            hash: "ab".repeat(32),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::{block, tx, txids};
+    use super::*;
+
+    #[test]
+    fn block_is_rebuilt_from_mempool_and_fetched_transactions() {
+        let original = block((0..5).map(tx).collect());
+        let compact = compact_block(&original, 42);
+        assert_eq!(compact.prefilled.len(), 1);
+        assert_eq!(compact.short_ids.len(), 4);
+
+        // The mempool has txs 1, 3 and 4 plus an unrelated one
+        let mempool = [tx(1), tx(3), tx(4), tx(9)];
+        let matches = mempool_matches(&compact, &mempool);
+        assert_eq!(matches.len(), 3);
+
+        let mut partial = PartialBlock::new(compact, &matches).unwrap();
+        assert_eq!(partial.missing(), vec![2]);
+        assert!(partial.fill(vec![tx(2), tx(5)]).is_err());
+        partial.fill(vec![tx(2)]).unwrap();
+
+        let rebuilt = partial.into_block().unwrap();
+        assert_eq!(rebuilt.hash, original.hash);
+        assert_eq!(txids(&rebuilt.transactions), txids(&original.transactions));
+    }
+
+    #[test]
+    fn wrong_or_malformed_compact_blocks_need_the_full_block() {
+        let original = block((0..3).map(tx).collect());
+        let compact = compact_block(&original, 7);
+
+        // A transaction filled in at the wrong place breaks the merkle root
+        let mut partial = PartialBlock::new(compact.clone(), &HashMap::new()).unwrap();
+        partial.fill(vec![tx(2), tx(1)]).unwrap();
+        assert!(partial.into_block().is_none());
+
+        let mut bad = compact.clone();
+        bad.prefilled[0].index = 3;
+        assert!(PartialBlock::new(bad, &HashMap::new()).is_err());
+        let mut bad = compact;
+        bad.short_ids[1] = bad.short_ids[0];
+        assert!(PartialBlock::new(bad, &HashMap::new()).is_err());
+    }
+}
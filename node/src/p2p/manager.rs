@@ -1,7 +1,11 @@
-use crate::p2p::messages::{HandshakeInfo, InventoryType, P2pMessage};
+use crate::p2p::compact::PartialBlock;
+use crate::p2p::messages::{CompactBlock, HandshakeInfo, InvItem, InventoryType, P2pMessage};
 use crate::p2p::peer::{Peer, PeerId};
 use Astram_core::block;
+use Astram_core::security::BlockFailureReason;
 use Astram_core::transaction::Transaction;
+use astram_config::network::{default_magic, parse_magic};
+use astram_config::ports::Ports;
 use bincode::{Decode, Encode};
 use bytes::Bytes;
 use futures::SinkExt;
@@ -15,6 +19,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::sync::Arc;
 use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
@@ -26,8 +31,14 @@ pub struct SavedPeer {
 }
 
 pub const MAX_OUTBOUND: usize = 8;
+pub const MAX_INBOUND: usize = 32;
+pub const DNS_RESERVED_OUTBOUND: usize = 2; // Outbound slots only the DNS refresh may fill
 pub const PEERS_FILE: &str = "peers.json";
-pub const PROTOCOL_VERSION: u32 = 1;
+pub const PROTOCOL_VERSION: u32 = 3; // 3: handshake carries network magic and genesis hash
+pub const MIN_PROTOCOL_VERSION: u32 = 3; // v2 handshakes lack the network magic, so can't be checked
+pub const FEATURE_INV_RELAY: &str = "inv-relay"; // Peer fetches announced blocks/txs with GetData
+pub const FEATURE_COMPACT_BLOCKS: &str = "compact-blocks"; // Peer serves and rebuilds compact blocks
+pub const FEATURE_TX_V2: &str = "tx-v2"; // Peer decodes version 2 (expiring) transactions
 pub const MAINNET_NETWORK_ID: &str = "Astram-mainnet";
 pub const TESTNET_NETWORK_ID: &str = "Astram-testnet";
 pub const MAINNET_CHAIN_ID: u64 = 1;
@@ -35,6 +46,7 @@ pub const TESTNET_CHAIN_ID: u64 = 8888;
 
 static NETWORK_ID: OnceLock<String> = OnceLock::new();
 static CHAIN_ID: OnceLock<u64> = OnceLock::new();
+static NETWORK_MAGIC: OnceLock<u32> = OnceLock::new();
 
 fn resolve_network_id() -> &'static str {
     NETWORK_ID
@@ -56,7 +68,34 @@ fn resolve_network_id() -> &'static str {
         .as_str()
 }
 
-fn resolve_chain_id() -> u64 {
+/// Pin the chain id from node settings. Must be called before the first handshake;
+/// returns false if the chain id was already resolved.
+pub fn set_chain_id(chain_id: u64) -> bool {
+    CHAIN_ID.set(chain_id).is_ok()
+}
+
+/// Pin the network magic from the config. Must be called before the first handshake;
+/// returns false if the magic was already resolved.
+pub fn set_network_magic(magic: u32) -> bool {
+    NETWORK_MAGIC.set(magic).is_ok()
+}
+
+/// Magic sent in handshakes and DNS registrations: ASTRAM_NETWORK_MAGIC if set, else
+/// the default for ASTRAM_NETWORK
+pub fn resolve_network_magic() -> u32 {
+    *NETWORK_MAGIC.get_or_init(|| {
+        if let Ok(value) = std::env::var("ASTRAM_NETWORK_MAGIC") {
+            match parse_magic(&value) {
+                Ok(magic) => return magic,
+                Err(e) => warn!("Ignoring ASTRAM_NETWORK_MAGIC: {}", e),
+            }
+        }
+        let network = std::env::var("ASTRAM_NETWORK").unwrap_or_else(|_| "mainnet".to_string());
+        default_magic(&network)
+    })
+}
+
+pub(crate) fn resolve_chain_id() -> u64 {
     *CHAIN_ID.get_or_init(|| {
         if let Ok(value) = std::env::var("ASTRAM_CHAIN_ID") {
             if let Ok(parsed) = value.trim().parse::<u64>() {
@@ -79,21 +118,229 @@ pub const HANDSHAKE_TIMEOUT_SECS: u64 = 30; // Handshake must complete within 30
 pub const MAX_INV_PER_MESSAGE: usize = 50000; // Maximum inventory items per message
 pub const BLOCK_ANNOUNCE_RATE_LIMIT: u64 = 10; // Max block announcements per minute per peer
 
+// Inventory relay: objects are announced by hash and only sent on request
+pub const KNOWN_INV_TTL_SECS: u64 = 600; // How long we remember that a peer has an object
+pub const MAX_KNOWN_INV_PER_PEER: usize = 10_000; // Cap on the per-peer known-inventory set
+pub const GETDATA_TIMEOUT_SECS: u64 = 30; // Re-request an announced object after this long
+pub const MAX_PARENT_FETCH_DEPTH: u32 = 100; // Orphan ancestors fetched one by one; deeper gaps are left to header sync
+pub const PARENT_RETRY_INTERVAL_SECS: u64 = 10; // How often overdue missing-parent requests are re-sent
+pub const MAX_PARENT_REQUESTS: usize = 64; // Missing parents in flight at once; more are left to header sync
+pub const PING_INTERVAL_SECS: u64 = 30; // How often each peer is pinged
+pub const PING_TIMEOUT_SECS: u64 = 90; // Peers that don't pong within this long are dropped
+pub const SYNC_TOLERANCE_BLOCKS: u64 = 1; // Being this close to the best peer counts as synced
+pub const MAX_PARTIAL_BLOCKS: usize = 16; // Compact blocks awaiting BlockTxn at once
+pub const ADMIN_CONNECT_WAIT_SECS: u64 = 5; // How long /admin/peers/connect waits for the handshake
+
 // Security: Peer diversity for Eclipse attack protection
 pub const MAX_PEERS_PER_SUBNET_24: usize = 2; // Max peers from same /24 subnet
 pub const MAX_PEERS_PER_SUBNET_16: usize = 4; // Max peers from same /16 subnet
 pub const MIN_OUTBOUND_SUBNET_DIVERSITY: usize = 3; // Require connections to at least 3 different /16 subnets
 
+// Security: Misbehavior scoring
+pub const BAN_SCORE_THRESHOLD: u32 = 100; // Score at which a peer is disconnected and banned
+pub const DEFAULT_BAN_DURATION_SECS: u64 = 24 * 60 * 60; // How long a banned IP is refused
+pub const MAX_BAN_DURATION_SECS: u64 = 365 * 24 * 60 * 60; // Longest ban the config or /admin may set
+pub const MISBEHAVIOR_DECAY_SECS: u64 = 60; // One penalty point is forgiven per interval
+pub const REPEATED_BLOCK_FAILURES: u64 = 5; // Unpenalized invalid blocks from one IP before they count as an offense
+pub const MAX_BLOCK_FAILURE_IPS: usize = 1024; // IPs whose block failures are tallied for /status
+
+/// Offenses that add to a peer's misbehavior score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misbehavior {
+    InvalidPoW,       // Block hash doesn't match its header or misses the target
+    BadMerkleRoot,    // Merkle root doesn't match the block's transactions
+    OversizedMessage, // INV/GETDATA with more than MAX_INV_PER_MESSAGE items
+    MalformedMessage, // Frame that doesn't decode as a P2pMessage
+    InvalidBlocks,    // REPEATED_BLOCK_FAILURES blocks failing validation for other reasons
+    WrongGenesis,     // Block or header at height 0 that isn't our genesis: another network
+}
+
+impl Misbehavior {
+    pub fn penalty(self) -> u32 {
+        match self {
+            Self::InvalidPoW => 20,
+            Self::BadMerkleRoot => 20,
+            Self::OversizedMessage => 50,
+            Self::MalformedMessage => 10,
+            Self::InvalidBlocks => 20,
+            Self::WrongGenesis => BAN_SCORE_THRESHOLD,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidPoW => "invalid_pow",
+            Self::BadMerkleRoot => "bad_merkle_root",
+            Self::OversizedMessage => "oversized_message",
+            Self::MalformedMessage => "malformed_message",
+            Self::InvalidBlocks => "invalid_blocks",
+            Self::WrongGenesis => "wrong_genesis",
+        }
+    }
+
+    /// The offense a block validation failure proves, if any. Failures an honest peer
+    /// can trigger (orphans, spent UTXOs after a reorg, ...) carry no penalty.
+    pub fn from_block_failure(reason: BlockFailureReason) -> Option<Self> {
+        match reason {
+            BlockFailureReason::HashMismatch | BlockFailureReason::InvalidPoW => {
+                Some(Self::InvalidPoW)
+            }
+            BlockFailureReason::MerkleRootMismatch => Some(Self::BadMerkleRoot),
+            _ => None,
+        }
+    }
+}
+
+/// Accumulated misbehavior of one IP; decay is applied lazily when the score is read
+#[derive(Debug, Clone, Copy)]
+struct MisbehaviorScore {
+    score: u32,
+    updated: Instant,
+}
+
+impl MisbehaviorScore {
+    fn decayed(&mut self, now: Instant) -> u32 {
+        let steps = now.saturating_duration_since(self.updated).as_secs() / MISBEHAVIOR_DECAY_SECS;
+        if steps > 0 {
+            self.score = self.score.saturating_sub(steps.min(u32::MAX as u64) as u32);
+            self.updated += Duration::from_secs(steps * MISBEHAVIOR_DECAY_SECS);
+        }
+        self.score
+    }
+}
+
+/// An orphan's missing parent that has been asked for and not yet received
+#[derive(Debug, Clone)]
+struct ParentRequest {
+    /// Orphans chained below this parent; capped at `MAX_PARENT_FETCH_DEPTH`
+    depth: u32,
+    /// Peers already asked, so a retry goes to someone else
+    asked: Vec<PeerId>,
+    /// When the latest getdata went out
+    sent: Instant,
+}
+
+/// Blocks from one IP that failed validation, by `BlockFailureReason`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BlockFailureTally {
+    pub total: u64,
+    pub reasons: HashMap<&'static str, u64>,
+    /// Failures not yet penalized as a `Misbehavior`
+    #[serde(skip)]
+    unpenalized: u64,
+}
+
+/// Penalized and banned peers, keyed by IP, for status reporting
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MisbehaviorReport {
+    /// Current (decayed) non-zero scores
+    pub scores: HashMap<String, u32>,
+    /// Seconds until each ban expires
+    pub banned: HashMap<String, u64>,
+}
+
+/// Bookkeeping for one open connection, kept from connect to teardown
+#[derive(Debug, Clone)]
+struct PeerConnection {
+    /// Whether the peer dialed us
+    inbound: bool,
+    connected_at: Instant,
+    /// When the last message from the peer arrived
+    last_message: Instant,
+    /// Frame payload bytes written to / read from the socket (length prefixes excluded)
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+impl PeerConnection {
+    fn new(inbound: bool, now: Instant) -> Self {
+        Self {
+            inbound,
+            connected_at: now,
+            last_message: now,
+            bytes_sent: 0,
+            bytes_received: 0,
+        }
+    }
+}
+
+/// Connection slots in use, as reported by `GET /peers`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SlotUsage {
+    pub inbound: usize,
+    pub max_inbound: usize,
+    /// Includes dials still in progress
+    pub outbound: usize,
+    pub max_outbound: usize,
+    /// Part of `max_outbound` held back for peers from the DNS refresh
+    pub dns_reserved_outbound: usize,
+}
+
+/// One connected peer, as reported by `GET /peers`
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerSummary {
+    pub address: String,
+    /// "inbound" or "outbound"
+    pub direction: &'static str,
+    /// Negotiated protocol version; None until the peer has handshaken
+    pub protocol_version: Option<u32>,
+    pub software_version: Option<String>,
+    /// Height from the handshake, raised by later announcements
+    pub height: Option<u64>,
+    /// Last measured ping round-trip
+    pub latency_ms: Option<u64>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Current (decayed) misbehavior score of the peer's IP
+    pub misbehavior_score: u32,
+    pub connected_secs: u64,
+    /// Seconds since the peer last sent anything
+    pub last_message_secs: u64,
+}
+
+/// IP part of a `host:port` peer address
+fn ip_of(addr: &str) -> &str {
+    addr.split(':').next().unwrap_or("")
+}
+
 type Shared<T> = Arc<Mutex<T>>;
 pub struct PeerManager {
     peers: Shared<HashMap<PeerId, UnboundedSender<P2pMessage>>>,
     peer_heights: Shared<HashMap<PeerId, u64>>,
     peer_handshakes: Shared<HashMap<PeerId, HandshakeInfo>>,
     peer_ips: Shared<HashMap<String, Vec<PeerId>>>, // IP -> list of peer IDs
+    peer_conns: Shared<HashMap<PeerId, PeerConnection>>,
     my_height: Arc<Mutex<u64>>,
     my_listening_port: Arc<Mutex<u16>>,
-    /// callback when a new block is received
-    on_block: Arc<Mutex<Option<Arc<dyn Fn(block::Block) + Send + Sync>>>>,
+    max_outbound: Arc<Mutex<usize>>,
+    max_inbound: Arc<Mutex<usize>>,
+    /// Misbehavior scores by IP
+    misbehavior: Shared<HashMap<String, MisbehaviorScore>>,
+    /// Banned IPs and when their ban expires
+    banned: Shared<HashMap<String, Instant>>,
+    /// Invalid blocks received, by sender IP
+    block_failures: Shared<HashMap<String, BlockFailureTally>>,
+    ban_duration: Arc<Mutex<Duration>>,
+    /// Hash our block 0 has; peers serving a different one are on another network
+    genesis_hash: Shared<Option<String>>,
+    /// Objects each peer is known to have (announced to or by it, or sent by it), so
+    /// nothing is announced twice to the same peer
+    known_inv: Shared<HashMap<PeerId, HashMap<InvItem, Instant>>>,
+    /// Objects requested via GetData and not yet received, so several peers announcing
+    /// the same block don't each get asked for it
+    requested_inv: Shared<HashMap<InvItem, Instant>>,
+    /// Missing parents of orphan blocks, by hash, asked for and not yet received
+    parent_requests: Shared<HashMap<String, ParentRequest>>,
+    /// Unanswered ping per peer: nonce and when it was sent
+    pending_pings: Shared<HashMap<PeerId, (u64, Instant)>>,
+    /// Last measured ping round-trip per peer
+    peer_latency: Shared<HashMap<PeerId, Duration>>,
+    /// Compact blocks waiting for a BlockTxn, by block hash: sender, block, when asked
+    partial_blocks: Shared<HashMap<String, (PeerId, PartialBlock, Instant)>>,
+    /// callback deciding whether we already have an announced object
+    on_has_inv: Arc<Mutex<Option<Arc<dyn Fn(&InvItem) -> bool + Send + Sync>>>>,
+    /// callback when a new block is received (with the peer that sent it)
+    on_block: Arc<Mutex<Option<Arc<dyn Fn(PeerId, block::Block) + Send + Sync>>>>,
     /// callback when a new transaction is received
     on_tx: Arc<Mutex<Option<Arc<dyn Fn(Transaction) + Send + Sync>>>>,
     on_getheaders: Arc<
@@ -103,7 +350,13 @@ pub struct PeerManager {
             >,
         >,
     >,
-    on_getdata: Arc<Mutex<Option<Arc<dyn Fn(PeerId, InventoryType, Vec<Vec<u8>>) + Send + Sync>>>>,
+    on_getdata: Arc<Mutex<Option<Arc<dyn Fn(PeerId, Vec<InvItem>) + Send + Sync>>>>,
+    /// callback with the headers a peer sent, before their blocks are requested
+    on_headers: Arc<Mutex<Option<Arc<dyn Fn(&[block::BlockHeader]) + Send + Sync>>>>,
+    /// Mempool transactions matching a compact block's short ids
+    on_compact_matches:
+        Arc<Mutex<Option<Arc<dyn Fn(&CompactBlock) -> HashMap<u64, Transaction> + Send + Sync>>>>,
+    on_getblocktxn: Arc<Mutex<Option<Arc<dyn Fn(PeerId, String, Vec<u32>) + Send + Sync>>>>,
 }
 
 impl PeerManager {
@@ -113,18 +366,36 @@ impl PeerManager {
             peer_heights: Arc::new(Mutex::new(HashMap::new())),
             peer_handshakes: Arc::new(Mutex::new(HashMap::new())),
             peer_ips: Arc::new(Mutex::new(HashMap::new())),
+            peer_conns: Arc::new(Mutex::new(HashMap::new())),
             my_height: Arc::new(Mutex::new(0)),
-            my_listening_port: Arc::new(Mutex::new(8335)), // Default port
+            my_listening_port: Arc::new(Mutex::new(Ports::DEFAULT.p2p)), // Default port
+            max_outbound: Arc::new(Mutex::new(MAX_OUTBOUND)),
+            max_inbound: Arc::new(Mutex::new(MAX_INBOUND)),
+            misbehavior: Arc::new(Mutex::new(HashMap::new())),
+            banned: Arc::new(Mutex::new(HashMap::new())),
+            block_failures: Arc::new(Mutex::new(HashMap::new())),
+            ban_duration: Arc::new(Mutex::new(Duration::from_secs(DEFAULT_BAN_DURATION_SECS))),
+            genesis_hash: Arc::new(Mutex::new(None)),
+            known_inv: Arc::new(Mutex::new(HashMap::new())),
+            requested_inv: Arc::new(Mutex::new(HashMap::new())),
+            parent_requests: Arc::new(Mutex::new(HashMap::new())),
+            pending_pings: Arc::new(Mutex::new(HashMap::new())),
+            peer_latency: Arc::new(Mutex::new(HashMap::new())),
+            partial_blocks: Arc::new(Mutex::new(HashMap::new())),
+            on_has_inv: Arc::new(Mutex::new(None)),
             on_block: Arc::new(Mutex::new(None)),
             on_tx: Arc::new(Mutex::new(None)),
             on_getheaders: Arc::new(Mutex::new(None)),
+            on_headers: Arc::new(Mutex::new(None)),
             on_getdata: Arc::new(Mutex::new(None)),
+            on_compact_matches: Arc::new(Mutex::new(None)),
+            on_getblocktxn: Arc::new(Mutex::new(None)),
         }
     }
 
     pub fn set_on_block<F>(&self, cb: F)
     where
-        F: Fn(block::Block) + Send + Sync + 'static,
+        F: Fn(PeerId, block::Block) + Send + Sync + 'static,
     {
         *self.on_block.lock() = Some(Arc::new(cb));
     }
@@ -143,13 +414,64 @@ impl PeerManager {
         *self.on_getheaders.lock() = Some(Arc::new(cb));
     }
 
+    pub fn set_on_headers<F>(&self, cb: F)
+    where
+        F: Fn(&[block::BlockHeader]) + Send + Sync + 'static,
+    {
+        *self.on_headers.lock() = Some(Arc::new(cb));
+    }
+
     pub fn set_on_getdata<F>(&self, cb: F)
     where
-        F: Fn(PeerId, InventoryType, Vec<Vec<u8>>) + Send + Sync + 'static,
+        F: Fn(PeerId, Vec<InvItem>) + Send + Sync + 'static,
     {
         *self.on_getdata.lock() = Some(Arc::new(cb));
     }
 
+    pub fn set_on_has_inv<F>(&self, cb: F)
+    where
+        F: Fn(&InvItem) -> bool + Send + Sync + 'static,
+    {
+        *self.on_has_inv.lock() = Some(Arc::new(cb));
+    }
+
+    pub fn set_on_compact_matches<F>(&self, cb: F)
+    where
+        F: Fn(&CompactBlock) -> HashMap<u64, Transaction> + Send + Sync + 'static,
+    {
+        *self.on_compact_matches.lock() = Some(Arc::new(cb));
+    }
+
+    pub fn set_on_getblocktxn<F>(&self, cb: F)
+    where
+        F: Fn(PeerId, String, Vec<u32>) + Send + Sync + 'static,
+    {
+        *self.on_getblocktxn.lock() = Some(Arc::new(cb));
+    }
+
+    /// Remember that `peer_id` has `items`; returns the ones it wasn't known to have
+    fn mark_known(&self, peer_id: &PeerId, items: &[InvItem]) -> Vec<InvItem> {
+        let now = Instant::now();
+        let ttl = Duration::from_secs(KNOWN_INV_TTL_SECS);
+        let mut known_inv = self.known_inv.lock();
+        let known = known_inv.entry(peer_id.clone()).or_default();
+        if known.len() + items.len() > MAX_KNOWN_INV_PER_PEER {
+            known.retain(|_, seen| now.duration_since(*seen) < ttl);
+            if known.len() + items.len() > MAX_KNOWN_INV_PER_PEER {
+                known.clear();
+            }
+        }
+
+        let mut fresh = Vec::new();
+        for item in items {
+            match known.insert(item.clone(), now) {
+                Some(seen) if now.duration_since(seen) < ttl => {}
+                _ => fresh.push(item.clone()),
+            }
+        }
+        fresh
+    }
+
     pub fn set_my_height(&self, height: u64) {
         *self.my_height.lock() = height;
     }
@@ -158,6 +480,28 @@ impl PeerManager {
         *self.my_height.lock()
     }
 
+    /// Height most connected peers report having reached: the lower median, so a peer
+    /// claiming an absurd height can't make us think we're behind (and pause mining)
+    /// unless it is the only one we have
+    pub fn network_height(&self) -> u64 {
+        let mut heights: Vec<u64> = self.peer_heights.lock().values().copied().collect();
+        if heights.is_empty() {
+            return 0;
+        }
+        heights.sort_unstable();
+        heights[(heights.len() - 1) / 2]
+    }
+
+    /// How far our chain trails the height most connected peers report
+    pub fn blocks_behind(&self) -> u64 {
+        self.network_height().saturating_sub(self.get_my_height())
+    }
+
+    /// Whether we're still catching up to our peers
+    pub fn is_syncing(&self) -> bool {
+        self.blocks_behind() > SYNC_TOLERANCE_BLOCKS
+    }
+
     pub fn set_my_listening_port(&self, port: u16) {
         *self.my_listening_port.lock() = port;
     }
@@ -166,6 +510,375 @@ impl PeerManager {
         *self.my_listening_port.lock()
     }
 
+    pub fn set_max_outbound(&self, max: usize) {
+        *self.max_outbound.lock() = max;
+    }
+
+    pub fn max_outbound(&self) -> usize {
+        *self.max_outbound.lock()
+    }
+
+    pub fn set_max_inbound(&self, max: usize) {
+        *self.max_inbound.lock() = max;
+    }
+
+    pub fn max_inbound(&self) -> usize {
+        *self.max_inbound.lock()
+    }
+
+    /// Outbound slots held back for the DNS refresh: DNS_RESERVED_OUTBOUND, but never
+    /// more than half of `max_outbound`
+    fn dns_reserved_outbound(&self) -> usize {
+        DNS_RESERVED_OUTBOUND.min(self.max_outbound() / 2)
+    }
+
+    /// Connection slots in use against the configured limits
+    pub fn slot_usage(&self) -> SlotUsage {
+        let (inbound, outbound) = {
+            let conns = self.peer_conns.lock();
+            let inbound = conns.values().filter(|conn| conn.inbound).count();
+            (inbound, conns.len() - inbound)
+        };
+        SlotUsage {
+            inbound,
+            max_inbound: self.max_inbound(),
+            outbound,
+            max_outbound: self.max_outbound(),
+            dns_reserved_outbound: self.dns_reserved_outbound(),
+        }
+    }
+
+    /// Claim an outbound slot for dialing `addr`; `from_dns` may also use the slots
+    /// reserved for the DNS refresh. The slot is held by a placeholder connection until
+    /// the dial succeeds (and the connection takes it over) or `release_slot` is called.
+    fn reserve_outbound(&self, addr: &str, from_dns: bool, now: Instant) -> anyhow::Result<()> {
+        let mut limit = self.max_outbound();
+        if !from_dns {
+            limit -= self.dns_reserved_outbound();
+        }
+        let mut conns = self.peer_conns.lock();
+        if conns.contains_key(addr) {
+            return Err(anyhow::anyhow!("already connected to {}", addr));
+        }
+        let outbound = conns.values().filter(|conn| !conn.inbound).count();
+        if outbound >= limit {
+            return Err(anyhow::anyhow!(
+                "no outbound slot for {} ({} of {} in use)",
+                addr,
+                outbound,
+                limit
+            ));
+        }
+        conns.insert(addr.to_string(), PeerConnection::new(false, now));
+        Ok(())
+    }
+
+    /// Give back a slot claimed by `reserve_outbound` or `reserve_inbound` whose
+    /// connection never got going
+    fn release_slot(&self, peer_id: &str) {
+        self.peer_conns.lock().remove(peer_id);
+    }
+
+    /// Claim an inbound slot for `peer_id`. When all MAX_INBOUND slots are taken, the
+    /// least valuable inbound peer is evicted if the newcomer is worth more: one whose
+    /// IP has a higher misbehavior score than the newcomer's, or one that still hasn't
+    /// handshaken after HANDSHAKE_TIMEOUT_SECS. Returns the evicted peer, or an error
+    /// if the newcomer is refused.
+    fn reserve_inbound(&self, peer_id: &PeerId, now: Instant) -> Result<Option<PeerId>, String> {
+        let max_inbound = self.max_inbound();
+        let inbound: Vec<(PeerId, Instant)> = self
+            .peer_conns
+            .lock()
+            .iter()
+            .filter(|(_, conn)| conn.inbound)
+            .map(|(id, conn)| (id.clone(), conn.connected_at))
+            .collect();
+
+        let mut evicted = None;
+        if inbound.len() >= max_inbound {
+            let newcomer_score = self.misbehavior_score(ip_of(peer_id), now);
+            let handshake_timeout = Duration::from_secs(HANDSHAKE_TIMEOUT_SECS);
+            let latencies = self.get_peer_latencies();
+            // Worst first: most penalized, then never handshaken, then slowest
+            let worst = inbound
+                .into_iter()
+                .map(|(id, connected_at)| {
+                    let score = self.misbehavior_score(ip_of(&id), now);
+                    let stalled = !self.peer_handshakes.lock().contains_key(&id)
+                        && now.saturating_duration_since(connected_at) >= handshake_timeout;
+                    let latency = latencies.get(&id).copied().unwrap_or(u64::MAX);
+                    (score, stalled, latency, id)
+                })
+                .max();
+            match worst {
+                Some((score, stalled, _, id)) if score > newcomer_score || stalled => {
+                    self.close_peer(&id);
+                    evicted = Some(id);
+                }
+                _ => {
+                    return Err(format!(
+                        "all {} inbound slots taken by peers at least as good",
+                        max_inbound
+                    ));
+                }
+            }
+        }
+
+        // Re-check under the lock: other connections may have arrived meanwhile
+        let mut conns = self.peer_conns.lock();
+        let used = conns.values().filter(|conn| conn.inbound).count();
+        if used >= max_inbound {
+            return Err(format!("all {} inbound slots taken", max_inbound));
+        }
+        conns.insert(peer_id.clone(), PeerConnection::new(true, now));
+        Ok(evicted)
+    }
+
+    pub fn set_ban_duration(&self, secs: u64) {
+        *self.ban_duration.lock() = Duration::from_secs(secs.min(MAX_BAN_DURATION_SECS));
+    }
+
+    /// Record an offense by `peer_id`. Once its IP's score reaches BAN_SCORE_THRESHOLD,
+    /// every connection from that IP is dropped and the IP is banned for the configured
+    /// duration. Returns true if this offense triggered the ban.
+    pub fn misbehaving(&self, peer_id: &PeerId, offense: Misbehavior) -> bool {
+        let ip = ip_of(peer_id).to_string();
+        let now = Instant::now();
+        let score = {
+            let mut scores = self.misbehavior.lock();
+            let entry = scores.entry(ip.clone()).or_insert(MisbehaviorScore {
+                score: 0,
+                updated: now,
+            });
+            entry.decayed(now);
+            entry.score = entry.score.saturating_add(offense.penalty());
+            entry.score
+        };
+        warn!(
+            "[P2P] Peer {} misbehaved ({}): +{} -> score {}",
+            peer_id,
+            offense.as_str(),
+            offense.penalty(),
+            score
+        );
+        if score < BAN_SCORE_THRESHOLD {
+            return false;
+        }
+
+        let ban_duration = *self.ban_duration.lock();
+        self.banned.lock().insert(ip.clone(), now + ban_duration);
+        self.misbehavior.lock().remove(&ip);
+
+        // Dropping a peer's sender ends its writer task, which tears the connection down
+        let mut peer_ids = self.peer_ips.lock().get(&ip).cloned().unwrap_or_default();
+        peer_ids.push(peer_id.clone());
+        {
+            let mut peers = self.peers.lock();
+            for id in &peer_ids {
+                peers.remove(id);
+            }
+        }
+        warn!(
+            "[P2P] Banned {} for {:?} (misbehavior score {})",
+            ip, ban_duration, score
+        );
+        true
+    }
+
+    /// Whether the IP of `addr` is currently banned; expired bans are dropped
+    pub fn is_banned(&self, addr: &str) -> bool {
+        let ip = ip_of(addr);
+        let mut banned = self.banned.lock();
+        match banned.get(ip) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                banned.remove(ip);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Tally a block from `peer_id` that failed validation with `error` and penalize
+    /// the sender: at once if the error proves misbehavior (see
+    /// `Misbehavior::from_block_failure`), otherwise once its IP has sent
+    /// REPEATED_BLOCK_FAILURES such blocks, so an honest peer that trips over a reorg
+    /// now and then isn't banned but one feeding us bad blocks is. `extends_tip` says
+    /// whether the block built on our tip when it was checked. Errors without a
+    /// `BlockFailureReason` are local (database, I/O) and neither tallied nor
+    /// penalized. Returns true if this triggered a ban.
+    pub fn block_rejected(
+        &self,
+        peer_id: &PeerId,
+        error: &anyhow::Error,
+        extends_tip: bool,
+    ) -> bool {
+        let reason = match BlockFailureReason::of(error) {
+            Some(reason) if reason != BlockFailureReason::Other => reason,
+            _ => return false,
+        };
+        // Only our tip's UTXO set was consulted, which a side-branch block needn't match
+        if !extends_tip && reason.depends_on_utxo_set() {
+            return false;
+        }
+        let ip = ip_of(peer_id).to_string();
+        let repeated = {
+            let mut failures = self.block_failures.lock();
+            if failures.len() >= MAX_BLOCK_FAILURE_IPS && !failures.contains_key(&ip) {
+                // Make room by forgetting the IP with the fewest failures
+                let fewest = failures
+                    .iter()
+                    .min_by_key(|(_, tally)| tally.total)
+                    .map(|(ip, _)| ip.clone());
+                if let Some(fewest) = fewest {
+                    failures.remove(&fewest);
+                }
+            }
+            let tally = failures.entry(ip).or_default();
+            tally.total += 1;
+            *tally.reasons.entry(reason.as_str()).or_default() += 1;
+            tally.unpenalized += 1;
+            let repeated = tally.unpenalized >= REPEATED_BLOCK_FAILURES;
+            if repeated {
+                tally.unpenalized = 0;
+            }
+            repeated
+        };
+
+        match Misbehavior::from_block_failure(reason) {
+            Some(offense) => self.misbehaving(peer_id, offense),
+            None if repeated => self.misbehaving(peer_id, Misbehavior::InvalidBlocks),
+            None => false,
+        }
+    }
+
+    /// Set the genesis hash peers must agree on; until it is set any is accepted
+    pub fn set_genesis_hash(&self, hash: String) {
+        *self.genesis_hash.lock() = Some(hash);
+    }
+
+    /// True (and the peer banned) if `peer_id` sent a height-0 block or header hashing
+    /// to something other than our genesis
+    fn wrong_genesis(&self, peer_id: &PeerId, index: u64, hash: &str) -> bool {
+        if index != 0 {
+            return false;
+        }
+        let expected = self.genesis_hash.lock().clone();
+        let Some(expected) = expected else {
+            return false;
+        };
+        if hash == expected {
+            return false;
+        }
+        warn!(
+            "[P2P] Peer {} is on another chain: genesis {} (ours {})",
+            peer_id, hash, expected
+        );
+        self.misbehaving(peer_id, Misbehavior::WrongGenesis);
+        true
+    }
+
+    /// Invalid blocks received so far, by sender IP
+    pub fn block_failure_report(&self) -> HashMap<String, BlockFailureTally> {
+        self.block_failures.lock().clone()
+    }
+
+    /// Forget the per-IP invalid block tallies (misbehavior scores and bans stay)
+    pub fn reset_block_failures(&self) {
+        self.block_failures.lock().clear();
+    }
+
+    /// Snapshot of penalized and banned peers; prunes fully decayed scores and expired bans
+    pub fn misbehavior_report(&self) -> MisbehaviorReport {
+        let now = Instant::now();
+        let scores = {
+            let mut scores = self.misbehavior.lock();
+            scores.retain(|_, entry| entry.decayed(now) > 0);
+            scores
+                .iter()
+                .map(|(ip, entry)| (ip.clone(), entry.score))
+                .collect()
+        };
+        let banned = {
+            let mut banned = self.banned.lock();
+            banned.retain(|_, until| *until > now);
+            banned
+                .iter()
+                .map(|(ip, until)| (ip.clone(), until.duration_since(now).as_secs()))
+                .collect()
+        };
+        MisbehaviorReport { scores, banned }
+    }
+
+    /// Current (decayed) misbehavior score of an IP
+    fn misbehavior_score(&self, ip: &str, now: Instant) -> u32 {
+        self.misbehavior
+            .lock()
+            .get_mut(ip)
+            .map_or(0, |entry| entry.decayed(now))
+    }
+
+    /// Start tracking a freshly opened connection (taking over its reserved slot)
+    fn register_connection(&self, peer_id: &PeerId, inbound: bool, now: Instant) {
+        self.peer_conns
+            .lock()
+            .insert(peer_id.clone(), PeerConnection::new(inbound, now));
+    }
+
+    /// Count a frame read from `peer_id`
+    fn record_received(&self, peer_id: &PeerId, bytes: usize, now: Instant) {
+        if let Some(conn) = self.peer_conns.lock().get_mut(peer_id) {
+            conn.bytes_received += bytes as u64;
+            conn.last_message = now;
+        }
+    }
+
+    /// Every connected peer with its direction, versions, height, latency, traffic,
+    /// misbehavior score and uptime, oldest connection first
+    pub fn peer_summaries(&self) -> Vec<PeerSummary> {
+        self.peer_summaries_at(Instant::now())
+    }
+
+    fn peer_summaries_at(&self, now: Instant) -> Vec<PeerSummary> {
+        // Peers dropped from `peers` are on their way out even if the socket lingers
+        let connected: Vec<PeerId> = self.peers.lock().keys().cloned().collect();
+        let conns: Vec<(PeerId, PeerConnection)> = {
+            let conns = self.peer_conns.lock();
+            connected
+                .into_iter()
+                .filter_map(|id| conns.get(&id).cloned().map(|conn| (id, conn)))
+                .collect()
+        };
+        let heights = self.get_peer_heights();
+        let latencies = self.get_peer_latencies();
+
+        let mut summaries: Vec<PeerSummary> = conns
+            .into_iter()
+            .map(|(peer_id, conn)| {
+                let handshake = self.get_peer_handshake(&peer_id);
+                PeerSummary {
+                    direction: if conn.inbound { "inbound" } else { "outbound" },
+                    protocol_version: self.negotiated_version(&peer_id),
+                    software_version: handshake.map(|info| info.software_version),
+                    height: heights.get(&peer_id).copied(),
+                    latency_ms: latencies.get(&peer_id).copied(),
+                    bytes_sent: conn.bytes_sent,
+                    bytes_received: conn.bytes_received,
+                    misbehavior_score: self.misbehavior_score(ip_of(&peer_id), now),
+                    connected_secs: now.saturating_duration_since(conn.connected_at).as_secs(),
+                    last_message_secs: now.saturating_duration_since(conn.last_message).as_secs(),
+                    address: peer_id,
+                }
+            })
+            .collect();
+        summaries.sort_by(|a, b| {
+            b.connected_secs
+                .cmp(&a.connected_secs)
+                .then_with(|| a.address.cmp(&b.address))
+        });
+        summaries
+    }
+
     /// Get handshake info for a specific peer
     pub fn get_peer_handshake(&self, peer_id: &str) -> Option<HandshakeInfo> {
         self.peer_handshakes.lock().get(peer_id).cloned()
@@ -176,6 +889,85 @@ impl PeerManager {
         self.peer_handshakes.lock().clone()
     }
 
+    /// Handshake describing this node, sent on connect and in reply to a peer's handshake
+    fn local_handshake_info(&self) -> HandshakeInfo {
+        HandshakeInfo {
+            protocol_version: PROTOCOL_VERSION,
+            software_version: env!("CARGO_PKG_VERSION").to_string(),
+            supported_features: vec![
+                "blocks".to_string(),
+                "transactions".to_string(),
+                "headers".to_string(),
+                FEATURE_INV_RELAY.to_string(),
+                FEATURE_COMPACT_BLOCKS.to_string(),
+                FEATURE_TX_V2.to_string(),
+            ],
+            network_id: resolve_network_id().to_string(),
+            chain_id: resolve_chain_id(),
+            network_magic: resolve_network_magic(),
+            genesis_hash: self.genesis_hash.lock().clone().unwrap_or_default(),
+            height: self.get_my_height(),
+            listening_port: self.get_my_listening_port(),
+        }
+    }
+
+    /// Disconnect peers below MIN_PROTOCOL_VERSION; returns whether the peer may stay
+    fn accept_protocol_version(&self, peer_id: &PeerId, version: u32) -> bool {
+        if version >= MIN_PROTOCOL_VERSION {
+            return true;
+        }
+        warn!(
+            "Peer {} speaks protocol version {} (minimum {}), disconnecting",
+            peer_id, version, MIN_PROTOCOL_VERSION
+        );
+        // Dropping the sender ends the writer task, closing the connection
+        self.peers.lock().remove(peer_id);
+        false
+    }
+
+    /// Disconnect a peer from another network: a different magic, or (once both sides
+    /// know theirs) a different genesis. Returns whether the peer may stay.
+    fn accept_network(&self, peer_id: &PeerId, info: &HandshakeInfo) -> bool {
+        let magic = resolve_network_magic();
+        if info.network_magic != magic {
+            warn!(
+                "Peer {} has network magic 0x{:08x} (ours 0x{:08x}), disconnecting",
+                peer_id, info.network_magic, magic
+            );
+            self.peers.lock().remove(peer_id);
+            return false;
+        }
+        info.genesis_hash.is_empty() || !self.wrong_genesis(peer_id, 0, &info.genesis_hash)
+    }
+
+    /// Protocol version used with a peer: the lower of ours and the one it advertised
+    pub fn negotiated_version(&self, peer_id: &str) -> Option<u32> {
+        self.peer_handshakes
+            .lock()
+            .get(peer_id)
+            .map(|info| info.protocol_version.min(PROTOCOL_VERSION))
+    }
+
+    /// Whether a peer advertised `feature` in its handshake (false until it has handshaken)
+    pub fn peer_supports(&self, peer_id: &str, feature: &str) -> bool {
+        self.peer_handshakes
+            .lock()
+            .get(peer_id)
+            .is_some_and(|info| info.supported_features.iter().any(|f| f == feature))
+    }
+
+    /// Negotiated protocol version of every connected, handshaken peer
+    pub fn peer_protocol_versions(&self) -> HashMap<PeerId, u32> {
+        let connected: Vec<PeerId> = self.peers.lock().keys().cloned().collect();
+        connected
+            .into_iter()
+            .filter_map(|peer_id| {
+                self.negotiated_version(&peer_id)
+                    .map(|version| (peer_id, version))
+            })
+            .collect()
+    }
+
     /// Security: Extract subnet prefixes from IP address for diversity checking
     fn get_subnet_prefixes(ip: &str) -> Option<(String, String)> {
         let parts: Vec<&str> = ip.split('.').collect();
@@ -274,6 +1066,83 @@ impl PeerManager {
         (subnet_24s.len(), subnet_16s.len())
     }
 
+    /// Connect to `addr` in the background (see `connect_peer`) and wait up to `wait`
+    /// for its handshake. Ok(true) once handshaken (or if it already was), Ok(false) if
+    /// still pending when `wait` runs out, an error if the dial failed or the connection
+    /// closed first.
+    pub async fn connect_peer_and_wait(
+        self: Arc<Self>,
+        addr: &str,
+        wait: Duration,
+    ) -> anyhow::Result<bool> {
+        if self.peer_handshakes.lock().contains_key(addr) {
+            return Ok(true);
+        }
+        let task = {
+            let manager = self.clone();
+            let addr = addr.to_string();
+            tokio::spawn(async move { manager.connect_peer(&addr).await })
+        };
+        let deadline = Instant::now() + wait;
+        loop {
+            if self.peer_handshakes.lock().contains_key(addr) {
+                return Ok(true);
+            }
+            if task.is_finished() {
+                return match task.await {
+                    Ok(Ok(())) => Err(anyhow::anyhow!("connection to {} closed", addr)),
+                    Ok(Err(e)) => Err(e),
+                    Err(e) => Err(e.into()),
+                };
+            }
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Close the connection to `peer_id` and forget its height, handshake, latency and
+    /// relay state; the misbehavior score goes with its IP's last connection. With
+    /// `ban`, every connection from that IP is closed and the IP refused for that long,
+    /// up to MAX_BAN_DURATION_SECS. Returns the number of connections closed.
+    pub fn disconnect_peer(&self, peer_id: &str, ban: Option<Duration>) -> usize {
+        let ip = ip_of(peer_id).to_string();
+        let mut peer_ids = vec![peer_id.to_string()];
+        if let Some(duration) = ban {
+            let until = Instant::now() + duration.min(Duration::from_secs(MAX_BAN_DURATION_SECS));
+            self.banned.lock().insert(ip.clone(), until);
+            peer_ids.extend(self.peer_ips.lock().get(&ip).cloned().unwrap_or_default());
+            peer_ids.sort();
+            peer_ids.dedup();
+        }
+
+        let closed = peer_ids.iter().filter(|id| self.close_peer(id)).count();
+        let ip_still_connected = self.peers.lock().keys().any(|id| ip_of(id) == ip.as_str());
+        if !ip_still_connected {
+            self.misbehavior.lock().remove(&ip);
+        }
+        info!(
+            "[P2P] Disconnected {} ({} connection(s) closed, ban {:?})",
+            peer_id, closed, ban
+        );
+        closed
+    }
+
+    /// Close the connection to `peer_id`, freeing its slot, and forget its height,
+    /// handshake, latency and relay state. Returns whether it was connected.
+    fn close_peer(&self, peer_id: &str) -> bool {
+        // Dropping a peer's sender ends its writer task, and with it the connection
+        let closed = self.peers.lock().remove(peer_id).is_some();
+        self.peer_heights.lock().remove(peer_id);
+        self.peer_handshakes.lock().remove(peer_id);
+        self.known_inv.lock().remove(peer_id);
+        self.pending_pings.lock().remove(peer_id);
+        self.peer_latency.lock().remove(peer_id);
+        self.peer_conns.lock().remove(peer_id);
+        closed
+    }
+
     /// inbound connections accept loop (spawn)
     pub async fn start_listener(self: Arc<Self>, bind_addr: &str) -> anyhow::Result<()> {
         let listener = TcpListener::bind(bind_addr).await?;
@@ -291,11 +1160,31 @@ impl PeerManager {
         }
     }
 
-    /// outbound connection to peer
+    /// outbound connection to peer, in one of the slots not reserved for DNS refresh
     pub async fn connect_peer(self: Arc<Self>, addr: &str) -> anyhow::Result<()> {
-        let stream = TcpStream::connect(addr).await?;
+        self.dial(addr, false).await
+    }
+
+    /// outbound connection to a peer from the DNS refresh, which may also take the
+    /// DNS_RESERVED_OUTBOUND slots
+    pub async fn connect_dns_peer(self: Arc<Self>, addr: &str) -> anyhow::Result<()> {
+        self.dial(addr, true).await
+    }
+
+    async fn dial(self: Arc<Self>, addr: &str, from_dns: bool) -> anyhow::Result<()> {
+        if self.is_banned(addr) {
+            return Err(anyhow::anyhow!("peer {} is banned for misbehavior", addr));
+        }
+        self.reserve_outbound(addr, from_dns, Instant::now())?;
+        let stream = match TcpStream::connect(addr).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.release_slot(addr);
+                return Err(e.into());
+            }
+        };
         let peer_id = addr.to_string();
-        self.spawn_peer_loop(stream, peer_id).await?;
+        self.spawn_peer_loop(stream, peer_id, false).await?;
         Ok(())
     }
 
@@ -307,6 +1196,11 @@ impl PeerManager {
         // Security: Extract IP address and check connection limit
         let peer_ip = peer_id.split(':').next().unwrap_or("").to_string();
 
+        if self.is_banned(&peer_ip) {
+            warn!("[WARN] Rejecting connection from banned peer {}", peer_id);
+            return Ok(()); // Silently drop connection
+        }
+
         info!(
             "[P2P] 🔒 handle_incoming {}: acquiring peer_ips lock for validation...",
             peer_id
@@ -417,20 +1311,35 @@ impl PeerManager {
             return Ok(()); // Silently drop connection
         }
 
+        match self.reserve_inbound(&peer_id, Instant::now()) {
+            Ok(Some(evicted)) => {
+                info!(
+                    "[INFO] Inbound slots full: evicted {} to make room for {}",
+                    evicted, peer_id
+                );
+            }
+            Ok(None) => {}
+            Err(reason) => {
+                warn!("[WARN] Rejecting connection from {} - {}", peer_id, reason);
+                return Ok(()); // Silently drop connection
+            }
+        }
+
         info!(
             "[INFO] Accepting connection from {} ({} existing from IP, diversity: {}/24 subnets, {}/16 subnets)",
             peer_id, peer_count, subnet_24_count, subnet_16_count
         );
 
-        self.spawn_peer_loop(stream, peer_id).await?;
+        self.spawn_peer_loop(stream, peer_id, true).await?;
         Ok(())
     }
 
-    /// spawn peer read/write loops
+    /// spawn peer read/write loops; `inbound` is whether the peer dialed us
     pub async fn spawn_peer_loop(
         self: Arc<Self>,
         stream: TcpStream,
         peer_id: PeerId,
+        inbound: bool,
     ) -> anyhow::Result<()> {
         let (r, w) = tokio::io::split(stream);
 
@@ -455,6 +1364,7 @@ impl PeerManager {
 
         // register sender in the manager so other parts can send to this peer
         self.peers.lock().insert(peer_id_clone.clone(), tx.clone());
+        self.register_connection(&peer_id_clone, inbound, Instant::now());
 
         // Security: Track IP address for connection limiting
         info!(
@@ -485,23 +1395,8 @@ impl PeerManager {
 
         // Send handshake immediately
         if let Some(tx) = self.peers.lock().get(&peer_id_clone) {
-            let my_height = self.get_my_height();
-            let my_port = self.get_my_listening_port();
-            let handshake_info = HandshakeInfo {
-                protocol_version: PROTOCOL_VERSION,
-                software_version: env!("CARGO_PKG_VERSION").to_string(),
-                supported_features: vec![
-                    "blocks".to_string(),
-                    "transactions".to_string(),
-                    "headers".to_string(),
-                ],
-                network_id: resolve_network_id().to_string(),
-                chain_id: resolve_chain_id(),
-                height: my_height,
-                listening_port: my_port,
-            };
             let _ = tx.send(P2pMessage::Handshake {
-                info: handshake_info,
+                info: self.local_handshake_info(),
             });
         }
 
@@ -509,19 +1404,36 @@ impl PeerManager {
         let config_read = bincode::config::standard();
 
         // writer task: consumes rx and writes framed bytes to the socket
+        let peer_conns = self.peer_conns.clone();
+        let writer_manager = self.clone();
         let write_handle = tokio::spawn(async move {
             let mut rx = rx;
             loop {
                 match rx.recv().await {
                     Some(msg) => {
+                        // Older peers can't decode version 2 transactions; sending one would
+                        // break their read loop, so they go without
+                        if msg.carries_versioned_tx()
+                            && !writer_manager.peer_supports(&peer_id, FEATURE_TX_V2)
+                        {
+                            log::debug!(
+                                "not sending version 2 transaction data to peer {}",
+                                peer_id
+                            );
+                            continue;
+                        }
                         match bincode::encode_to_vec(&msg, config) {
                             Ok(vec) => {
                                 // convert Vec<u8> -> Bytes (LengthDelimitedCodec accepts bytes)
+                                let len = vec.len() as u64;
                                 let bytes: Bytes = Bytes::from(vec);
                                 if let Err(e) = writer.send(bytes).await {
                                     log::warn!("write error to peer {}: {:?}", peer_id, e);
                                     break;
                                 }
+                                if let Some(conn) = peer_conns.lock().get_mut(&peer_id) {
+                                    conn.bytes_sent += len;
+                                }
                             }
                             Err(e) => {
                                 log::warn!("bincode encode error for {}: {:?}", peer_id, e);
@@ -549,6 +1461,7 @@ impl PeerManager {
                     Some(Ok(bytes_mut)) => {
                         // bytes_mut is BytesMut; get slice for bincode
                         let slice = bytes_mut.as_ref();
+                        manager_clone.record_received(&peer_id_clone, slice.len(), Instant::now());
                         match bincode::decode_from_slice::<P2pMessage, _>(slice, config_read) {
                             Ok((msg, _remaining)) => {
                                 // delegate to manager
@@ -558,6 +1471,8 @@ impl PeerManager {
                             }
                             Err(e) => {
                                 log::warn!("peer {} decode error: {:?}", peer_id_clone, e);
+                                manager_clone
+                                    .misbehaving(&peer_id_clone, Misbehavior::MalformedMessage);
                                 break;
                             }
                         }
@@ -588,6 +1503,11 @@ impl PeerManager {
                     log::warn!("read task error: {:?}", e);
                 }
                 self.peers.lock().remove(&peer_id_clone2);
+                self.known_inv.lock().remove(&peer_id_clone2);
+                self.peer_handshakes.lock().remove(&peer_id_clone2);
+                self.pending_pings.lock().remove(&peer_id_clone2);
+                self.peer_latency.lock().remove(&peer_id_clone2);
+                self.peer_conns.lock().remove(&peer_id_clone2);
 
                 // Security: Remove from IP tracking (OPTIMIZED: single lock)
                 info!(
@@ -622,6 +1542,11 @@ impl PeerManager {
                     log::warn!("write task error: {:?}", e);
                 }
                 self.peers.lock().remove(&peer_id_clone2);
+                self.known_inv.lock().remove(&peer_id_clone2);
+                self.peer_handshakes.lock().remove(&peer_id_clone2);
+                self.pending_pings.lock().remove(&peer_id_clone2);
+                self.peer_latency.lock().remove(&peer_id_clone2);
+                self.peer_conns.lock().remove(&peer_id_clone2);
 
                 // Security: Remove from IP tracking (OPTIMIZED: single lock)
                 info!(
@@ -648,7 +1573,11 @@ impl PeerManager {
                     }
                 } // peer_ips lock released
 
-                let _ = read_fut.await; // await the remaining reader
+                // The writer ends when the peer is dropped from `peers` (disconnect, ban,
+                // ping timeout); stop reading too so the socket closes even if the peer
+                // never hangs up
+                read_fut.abort();
+                let _ = read_fut.await;
             }
         }
 
@@ -671,12 +1600,10 @@ impl PeerManager {
                 );
 
                 // Validate protocol compatibility
-                if info.protocol_version != PROTOCOL_VERSION {
-                    warn!(
-                        "Peer {} has incompatible protocol version {}",
-                        peer_id, info.protocol_version
-                    );
-                    // Could disconnect here
+                if !self.accept_protocol_version(&peer_id, info.protocol_version)
+                    || !self.accept_network(&peer_id, &info)
+                {
+                    return;
                 }
 
                 if info.network_id != resolve_network_id() {
@@ -714,21 +1641,9 @@ impl PeerManager {
 
                 // Send handshake ack with our info
                 if let Some(tx) = self.peers.lock().get(&peer_id) {
-                    let my_height = self.get_my_height();
-                    let my_info = HandshakeInfo {
-                        protocol_version: PROTOCOL_VERSION,
-                        software_version: env!("CARGO_PKG_VERSION").to_string(),
-                        supported_features: vec![
-                            "blocks".to_string(),
-                            "transactions".to_string(),
-                            "headers".to_string(),
-                        ],
-                        network_id: resolve_network_id().to_string(),
-                        chain_id: resolve_chain_id(),
-                        height: my_height,
-                        listening_port: my_port,
-                    };
-                    let _ = tx.send(HandshakeAck { info: my_info });
+                    let _ = tx.send(HandshakeAck {
+                        info: self.local_handshake_info(),
+                    });
                 }
 
                 // Start syncing headers
@@ -752,6 +1667,12 @@ impl PeerManager {
                     info.height
                 );
 
+                if !self.accept_protocol_version(&peer_id, info.protocol_version)
+                    || !self.accept_network(&peer_id, &info)
+                {
+                    return;
+                }
+
                 // Check if this is ourselves (same listening port)
                 let my_port = self.get_my_listening_port();
                 if info.listening_port == my_port {
@@ -831,67 +1752,111 @@ impl PeerManager {
 
             Headers { headers } => {
                 info!("{} sent {} headers", peer_id, headers.len());
+                let on_headers = self.on_headers.lock().clone();
+                if let Some(cb) = on_headers {
+                    cb(&headers);
+                }
                 if !headers.is_empty() {
                     // request full blocks for these headers
-                    let mut hashes: Vec<Vec<u8>> = Vec::new();
+                    let mut items: Vec<InvItem> = Vec::new();
                     for hdr in headers.iter() {
                         if let Ok(hash_hex) = block::compute_header_hash(hdr) {
-                            if let Ok(bytes) = hex::decode(hash_hex) {
-                                hashes.push(bytes);
+                            if self.wrong_genesis(&peer_id, hdr.index, &hash_hex) {
+                                return;
+                            }
+                            if let Some(item) = InvItem::block(&hash_hex) {
+                                items.push(item);
                             }
                         }
                     }
+                    self.mark_known(&peer_id, &items);
                     if let Some(tx) = self.peers.lock().get(&peer_id) {
-                        let _ = tx.send(P2pMessage::GetData {
-                            object_type: InventoryType::Block,
-                            hashes,
-                        });
+                        let _ = tx.send(P2pMessage::GetData(items));
                     }
                 }
             }
 
-            Inv {
-                object_type,
-                hashes,
-            } => {
+            Inv(items) => {
                 // Security: Validate INV message size to prevent memory exhaustion
-                if hashes.len() > MAX_INV_PER_MESSAGE {
+                if items.len() > MAX_INV_PER_MESSAGE {
                     warn!(
                         "Peer {} sent excessive INV message: {} items (max: {}), ignoring",
                         peer_id,
-                        hashes.len(),
+                        items.len(),
                         MAX_INV_PER_MESSAGE
                     );
+                    self.misbehaving(&peer_id, Misbehavior::OversizedMessage);
                     return; // Drop the message
                 }
 
-                info!("{} inv {} items", peer_id, hashes.len());
-                if let Some(tx) = self.peers.lock().get(&peer_id) {
-                    let _ = tx.send(GetData {
-                        object_type,
-                        hashes,
-                    });
+                // The announcer has these, so never announce them back to it
+                self.mark_known(&peer_id, &items);
+
+                // Request only what we lack and haven't already asked another peer for
+                let has_inv = self.on_has_inv.lock().clone();
+                let now = Instant::now();
+                let timeout = Duration::from_secs(GETDATA_TIMEOUT_SECS);
+                let wanted: Vec<InvItem> = {
+                    let mut requested = self.requested_inv.lock();
+                    requested.retain(|_, at| now.duration_since(*at) < timeout);
+                    items
+                        .into_iter()
+                        .filter(|item| {
+                            matches!(
+                                item.object_type,
+                                InventoryType::Block | InventoryType::Transaction
+                            )
+                        })
+                        .filter(|item| !has_inv.as_ref().is_some_and(|cb| (cb)(item)))
+                        .filter(|item| requested.insert(item.clone(), now).is_none())
+                        .collect()
+                };
+
+                info!("{} inv: requesting {} new item(s)", peer_id, wanted.len());
+                // Blocks come as compact blocks from peers that can send them; the
+                // request is still tracked under the block's own inventory item
+                let wanted: Vec<InvItem> = if self.peer_supports(&peer_id, FEATURE_COMPACT_BLOCKS) {
+                    wanted
+                        .into_iter()
+                        .map(|item| match item.object_type {
+                            InventoryType::Block => item.with_type(InventoryType::CompactBlock),
+                            _ => item,
+                        })
+                        .collect()
+                } else {
+                    wanted
+                };
+                if !wanted.is_empty() {
+                    if let Some(tx) = self.peers.lock().get(&peer_id) {
+                        let _ = tx.send(GetData(wanted));
+                    }
                 }
             }
 
-            GetData {
-                object_type,
-                hashes,
-            } => {
+            GetData(items) => {
                 // Security: Validate GetData message size
-                if hashes.len() > MAX_INV_PER_MESSAGE {
+                if items.len() > MAX_INV_PER_MESSAGE {
                     warn!(
                         "Peer {} sent excessive GetData: {} items (max: {}), ignoring",
                         peer_id,
-                        hashes.len(),
+                        items.len(),
                         MAX_INV_PER_MESSAGE
                     );
+                    self.misbehaving(&peer_id, Misbehavior::OversizedMessage);
                     return; // Drop the message
                 }
 
-                info!("{} requested {} items", peer_id, hashes.len());
+                info!("{} requested {} items", peer_id, items.len());
+                let known: Vec<InvItem> = items
+                    .iter()
+                    .map(|item| match item.object_type {
+                        InventoryType::CompactBlock => item.with_type(InventoryType::Block),
+                        _ => item.clone(),
+                    })
+                    .collect();
+                self.mark_known(&peer_id, &known);
                 if let Some(cb) = &*self.on_getdata.lock() {
-                    (cb)(peer_id.clone(), object_type, hashes);
+                    (cb)(peer_id.clone(), items);
                 }
             }
 
@@ -900,32 +1865,52 @@ impl PeerManager {
                     "[P2P] 📦 {} sent block #{} {}",
                     peer_id, block.header.index, block.hash
                 );
-                let callback_start = std::time::Instant::now();
-                let lock_start = std::time::Instant::now();
-                let cb = self.on_block.lock().clone();
-                let lock_duration = lock_start.elapsed();
+                self.partial_blocks.lock().remove(&block.hash);
+                self.deliver_block(&peer_id, block);
+            }
 
-                if let Some(cb) = cb {
-                    if lock_duration.as_micros() > 100 {
-                        info!(
-                            "[P2P] 🔒 Block callback: on_block lock took {:?}",
-                            lock_duration
-                        );
-                    }
-                    (cb)(block.clone());
-                    info!(
-                        "[P2P] ✅ Block callback completed in {:?}",
-                        callback_start.elapsed()
+            CmpctBlock { block } => {
+                info!(
+                    "[P2P] 📦 {} sent compact block #{} {} ({} short ids)",
+                    peer_id,
+                    block.header.index,
+                    block.hash,
+                    block.short_ids.len()
+                );
+                self.receive_compact_block(&peer_id, block);
+            }
+
+            GetBlockTxn { hash, indexes } => {
+                if indexes.len() > MAX_INV_PER_MESSAGE {
+                    warn!(
+                        "Peer {} sent excessive GetBlockTxn: {} indexes (max: {}), ignoring",
+                        peer_id,
+                        indexes.len(),
+                        MAX_INV_PER_MESSAGE
                     );
+                    self.misbehaving(&peer_id, Misbehavior::OversizedMessage);
+                    return;
+                }
+                let cb = self.on_getblocktxn.lock().clone();
+                if let Some(cb) = cb {
+                    (cb)(peer_id.clone(), hash, indexes);
                 }
             }
 
+            BlockTxn { hash, txs } => {
+                self.receive_block_txn(&peer_id, &hash, txs);
+            }
+
             Tx { tx } => {
                 info!(
                     "[P2P] 💸 {} sent transaction {}",
                     peer_id,
                     hex::encode(&tx.txid[..8])
                 );
+                if let Some(item) = InvItem::tx(&tx.txid) {
+                    self.requested_inv.lock().remove(&item);
+                    self.mark_known(&peer_id, &[item]);
+                }
                 let callback_start = std::time::Instant::now();
                 let lock_start = std::time::Instant::now();
                 let cb = self.on_tx.lock().clone();
@@ -943,33 +1928,386 @@ impl PeerManager {
                 }
             }
 
+            Ping(nonce) => {
+                self.send_to_peer(&peer_id, Pong(nonce));
+            }
+
+            Pong(nonce) => {
+                self.record_pong(&peer_id, nonce);
+            }
+
             _ => {
                 info!("{} sent {:?}", peer_id, msg);
             }
         }
     }
 
-    pub fn broadcast_inv(&self, object_type: InventoryType, hashes: Vec<Vec<u8>>) {
-        info!("[P2P] 🔒 broadcast_inv: acquiring peers lock...");
+    /// Hand a block received from `peer_id` (in full or rebuilt from a compact block) to
+    /// the block callback
+    fn deliver_block(&self, peer_id: &PeerId, block: block::Block) {
+        if let Some(item) = InvItem::block(&block.hash) {
+            self.requested_inv.lock().remove(&item);
+            self.mark_known(peer_id, &[item]);
+        }
+        if block.header.index == 0 {
+            // We build genesis ourselves; a matching one has nothing to add
+            self.wrong_genesis(peer_id, 0, &block.hash);
+            return;
+        }
+        let callback_start = std::time::Instant::now();
         let lock_start = std::time::Instant::now();
-        let peers = self.peers.lock().clone();
+        let cb = self.on_block.lock().clone();
         let lock_duration = lock_start.elapsed();
+
+        if let Some(cb) = cb {
+            if lock_duration.as_micros() > 100 {
+                info!(
+                    "[P2P] 🔒 Block callback: on_block lock took {:?}",
+                    lock_duration
+                );
+            }
+            (cb)(peer_id.clone(), block);
+            info!(
+                "[P2P] ✅ Block callback completed in {:?}",
+                callback_start.elapsed()
+            );
+        }
+    }
+
+    /// Ask `peer_id` for the full block, after a compact block couldn't be rebuilt
+    fn request_full_block(&self, peer_id: &PeerId, hash: &str) {
+        self.partial_blocks.lock().remove(hash);
+        if let Some(item) = InvItem::block(hash) {
+            self.send_to_peer(peer_id, P2pMessage::GetData(vec![item]));
+        }
+    }
+
+    /// Rebuild a compact block from the mempool. Delivered at once if nothing is
+    /// missing; otherwise the missing transactions are asked for with GetBlockTxn.
+    /// Anything that can't be laid out or doesn't match its merkle root falls back to
+    /// fetching the full block.
+    fn receive_compact_block(&self, peer_id: &PeerId, compact: CompactBlock) {
+        let hash = compact.hash.clone();
+        if compact.header.index == 0 {
+            self.wrong_genesis(peer_id, 0, &hash);
+            return;
+        }
+        let cb = self.on_compact_matches.lock().clone();
+        let matches = match cb {
+            Some(cb) => (cb)(&compact),
+            None => HashMap::new(),
+        };
+        let partial = match PartialBlock::new(compact, &matches) {
+            Ok(partial) => partial,
+            Err(e) => {
+                info!("[P2P] Compact block {} from {}: {}", hash, peer_id, e);
+                self.request_full_block(peer_id, &hash);
+                return;
+            }
+        };
+
+        let missing = partial.missing();
+        if missing.is_empty() {
+            match partial.into_block() {
+                Some(block) => self.deliver_block(peer_id, block),
+                None => self.request_full_block(peer_id, &hash),
+            }
+            return;
+        }
+
         info!(
-            "[P2P] ✅ broadcast_inv: peers lock acquired (took {:?}), {} peers",
-            lock_duration,
-            peers.len()
+            "[P2P] Compact block {}: {} transaction(s) missing from mempool, asking {}",
+            hash,
+            missing.len(),
+            peer_id
+        );
+        let now = Instant::now();
+        {
+            let timeout = Duration::from_secs(GETDATA_TIMEOUT_SECS);
+            let mut partials = self.partial_blocks.lock();
+            partials.retain(|_, (_, _, asked)| now.saturating_duration_since(*asked) < timeout);
+            if partials.len() >= MAX_PARTIAL_BLOCKS && !partials.contains_key(&hash) {
+                drop(partials);
+                self.request_full_block(peer_id, &hash);
+                return;
+            }
+            partials.insert(hash.clone(), (peer_id.clone(), partial, now));
+        }
+        self.send_to_peer(
+            peer_id,
+            P2pMessage::GetBlockTxn {
+                hash,
+                indexes: missing,
+            },
         );
+    }
 
-        for (_id, tx) in peers {
-            let _ = tx.send(P2pMessage::Inv {
-                object_type: object_type.clone(),
-                hashes: hashes.clone(),
-            });
+    /// Complete a compact block with the transactions `peer_id` sent for it
+    fn receive_block_txn(&self, peer_id: &PeerId, hash: &str, txs: Vec<Transaction>) {
+        let partial = {
+            let mut partials = self.partial_blocks.lock();
+            match partials.get(hash) {
+                Some((from, _, _)) if from == peer_id => partials.remove(hash),
+                _ => None,
+            }
+        };
+        let Some((_, mut partial, _)) = partial else {
+            info!("[P2P] Unrequested BlockTxn for {} from {}", hash, peer_id);
+            return;
+        };
+        if let Err(e) = partial.fill(txs) {
+            warn!("[P2P] BlockTxn for {} from {}: {}", hash, peer_id, e);
+            self.request_full_block(peer_id, hash);
+            return;
+        }
+        match partial.into_block() {
+            Some(block) => self.deliver_block(peer_id, block),
+            None => self.request_full_block(peer_id, hash),
+        }
+    }
+
+    /// Match a pong against the outstanding ping and record the round-trip
+    fn record_pong(&self, peer_id: &PeerId, nonce: u64) {
+        let sent = {
+            let mut pings = self.pending_pings.lock();
+            match pings.get(peer_id) {
+                Some(&(expected, sent)) if expected == nonce => {
+                    pings.remove(peer_id);
+                    sent
+                }
+                _ => return,
+            }
+        };
+        self.peer_latency.lock().insert(peer_id.clone(), sent.elapsed());
+    }
+
+    /// Ping every peer without an outstanding ping and disconnect peers whose ping has
+    /// gone unanswered for PING_TIMEOUT_SECS. Returns the evicted peers.
+    pub fn ping_peers(&self) -> Vec<PeerId> {
+        self.ping_peers_at(Instant::now())
+    }
+
+    fn ping_peers_at(&self, now: Instant) -> Vec<PeerId> {
+        let timeout = Duration::from_secs(PING_TIMEOUT_SECS);
+        let peer_ids: Vec<PeerId> = self.peers.lock().keys().cloned().collect();
+        let mut stale = Vec::new();
+        let mut to_ping = Vec::new();
+        {
+            let mut pings = self.pending_pings.lock();
+            for peer_id in peer_ids {
+                match pings.get(&peer_id) {
+                    Some(&(_, sent)) if now.saturating_duration_since(sent) >= timeout => {
+                        stale.push(peer_id)
+                    }
+                    Some(_) => {}
+                    None => {
+                        let nonce = rand::random::<u64>();
+                        pings.insert(peer_id.clone(), (nonce, now));
+                        to_ping.push((peer_id, nonce));
+                    }
+                }
+            }
+        }
+
+        for (peer_id, nonce) in to_ping {
+            self.send_to_peer(&peer_id, P2pMessage::Ping(nonce));
+        }
+        // Dropping the sender ends the writer task, which tears the connection down
+        for peer_id in &stale {
+            self.peers.lock().remove(peer_id);
+            self.pending_pings.lock().remove(peer_id);
+            self.peer_latency.lock().remove(peer_id);
+        }
+        stale
+    }
+
+    /// Last measured round-trip per connected peer, in milliseconds
+    pub fn get_peer_latencies(&self) -> HashMap<PeerId, u64> {
+        self.peer_latency
+            .lock()
+            .iter()
+            .map(|(id, rtt)| (id.clone(), rtt.as_millis() as u64))
+            .collect()
+    }
+
+    /// Ask `peer_id`, which sent orphan `orphan_hash`, for its missing parent. An orphan
+    /// that was itself fetched as a missing parent extends that backward fill by one;
+    /// past MAX_PARENT_FETCH_DEPTH the fill stops and the gap is left to header sync, so
+    /// a peer feeding a fake fork can't walk us back without bound. Likewise at most
+    /// MAX_PARENT_REQUESTS parents are in flight at once. Returns false if nothing was
+    /// sent (the parent is already in flight, or the fill is too deep or too wide).
+    pub fn request_parent(&self, peer_id: &PeerId, orphan_hash: &str, parent_hash: &str) -> bool {
+        self.request_parent_at(peer_id, orphan_hash, parent_hash, Instant::now())
+    }
+
+    fn request_parent_at(
+        &self,
+        peer_id: &PeerId,
+        orphan_hash: &str,
+        parent_hash: &str,
+        now: Instant,
+    ) -> bool {
+        let Some(item) = InvItem::block(parent_hash) else {
+            return false;
+        };
+        {
+            let mut requests = self.parent_requests.lock();
+            let depth = requests.remove(orphan_hash).map_or(0, |r| r.depth) + 1;
+            if depth > MAX_PARENT_FETCH_DEPTH {
+                warn!(
+                    "[P2P] Not fetching parent {} of orphan {}: {} blocks back already",
+                    &parent_hash[..16],
+                    &orphan_hash[..16],
+                    MAX_PARENT_FETCH_DEPTH
+                );
+                return false;
+            }
+            if requests.contains_key(parent_hash) {
+                return false;
+            }
+            if requests.len() >= MAX_PARENT_REQUESTS {
+                warn!(
+                    "[P2P] Not fetching parent {} of orphan {}: {} parents in flight already",
+                    &parent_hash[..16],
+                    &orphan_hash[..16],
+                    MAX_PARENT_REQUESTS
+                );
+                return false;
+            }
+            requests.insert(
+                parent_hash.to_string(),
+                ParentRequest {
+                    depth,
+                    asked: vec![peer_id.clone()],
+                    sent: now,
+                },
+            );
         }
         info!(
-            "[P2P] ✅ broadcast_inv: completed (total {:?})",
+            "[P2P] Requesting missing parent {} from {}",
+            &parent_hash[..16],
+            peer_id
+        );
+        self.requested_inv.lock().insert(item.clone(), now);
+        self.send_to_peer(peer_id, P2pMessage::GetData(vec![item]));
+        true
+    }
+
+    /// Re-ask for missing parents not received within GETDATA_TIMEOUT_SECS, each time
+    /// from a connected peer not asked yet. A request ends once the block is known
+    /// (per `on_has_inv`) or every peer has been tried.
+    pub fn retry_parent_requests(&self) {
+        self.retry_parent_requests_at(Instant::now())
+    }
+
+    fn retry_parent_requests_at(&self, now: Instant) {
+        use std::collections::HashSet;
+
+        // The inventory check takes the bc and chain locks, and the block handler holds
+        // those while requesting parents, so it runs without parent_requests held
+        let hashes: Vec<String> = self.parent_requests.lock().keys().cloned().collect();
+        let has_inv = self.on_has_inv.lock().clone();
+        let arrived: HashSet<String> = hashes
+            .into_iter()
+            .filter(|hash| match InvItem::block(hash) {
+                Some(item) => has_inv.as_ref().is_some_and(|cb| cb(&item)),
+                None => true,
+            })
+            .collect();
+
+        let timeout = Duration::from_secs(GETDATA_TIMEOUT_SECS);
+        let peers: Vec<PeerId> = self.peers.lock().keys().cloned().collect();
+        let mut resend = Vec::new();
+        self.parent_requests.lock().retain(|hash, request| {
+            if arrived.contains(hash) {
+                return false;
+            }
+            if now.saturating_duration_since(request.sent) < timeout {
+                return true;
+            }
+            match peers.iter().find(|peer| !request.asked.contains(peer)) {
+                Some(peer) => {
+                    request.asked.push(peer.clone());
+                    request.sent = now;
+                    resend.push((peer.clone(), hash.clone()));
+                    true
+                }
+                None => {
+                    warn!(
+                        "[P2P] Giving up on missing parent {}: no peer left to ask",
+                        &hash[..16]
+                    );
+                    false
+                }
+            }
+        });
+
+        for (peer_id, hash) in resend {
+            if let Some(item) = InvItem::block(&hash) {
+                info!(
+                    "[P2P] Re-requesting missing parent {} from {}",
+                    &hash[..16],
+                    peer_id
+                );
+                self.requested_inv.lock().insert(item.clone(), now);
+                self.send_to_peer(&peer_id, P2pMessage::GetData(vec![item]));
+            }
+        }
+    }
+
+    /// Re-send overdue missing-parent requests every PARENT_RETRY_INTERVAL_SECS
+    pub async fn start_parent_retry_loop(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(PARENT_RETRY_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+            self.retry_parent_requests();
+        }
+    }
+
+    /// Keepalive: ping peers every PING_INTERVAL_SECS and drop the ones that stopped answering
+    pub async fn start_keepalive_loop(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(PING_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            for peer_id in self.ping_peers() {
+                warn!(
+                    "[P2P] Disconnecting {}: no pong within {}s",
+                    peer_id, PING_TIMEOUT_SECS
+                );
+            }
+        }
+    }
+
+    /// Announce `items` via Inv to every connected peer that advertised FEATURE_INV_RELAY
+    /// and isn't already known to have them. Returns how many peers were sent an announcement.
+    pub fn broadcast_inv(&self, items: Vec<InvItem>) -> usize {
+        if items.is_empty() {
+            return 0;
+        }
+        let lock_start = std::time::Instant::now();
+        let peers = self.peers.lock().clone();
+
+        let mut announced = 0;
+        for (peer_id, tx) in peers {
+            if !self.peer_supports(&peer_id, FEATURE_INV_RELAY) {
+                continue;
+            }
+            let fresh = self.mark_known(&peer_id, &items);
+            if !fresh.is_empty() {
+                let _ = tx.send(P2pMessage::Inv(fresh));
+                announced += 1;
+            }
+        }
+        info!(
+            "[P2P] ✅ broadcast_inv: {} item(s) announced to {} peer(s) (total {:?})",
+            items.len(),
+            announced,
             lock_start.elapsed()
         );
+        announced
     }
 
     pub fn send_to_peer(&self, peer_id: &PeerId, msg: P2pMessage) {
@@ -1007,6 +2345,42 @@ impl PeerManager {
         }
     }
 
+    /// Number of open peer connections
+    pub fn peer_count(&self) -> usize {
+        self.peers.lock().len()
+    }
+
+    /// Dialable addresses (peer IP + advertised listening port) of handshaked peers
+    pub fn connected_peer_addrs(&self) -> Vec<String> {
+        self.peer_handshakes
+            .lock()
+            .iter()
+            .filter_map(|(peer_id, info)| {
+                let (ip, _) = peer_id.rsplit_once(':')?;
+                Some(format!("{}:{}", ip, info.listening_port))
+            })
+            .collect()
+    }
+
+    /// Merge the connected peers into peers.json, refreshing `last_seen`.
+    /// Returns the number of connected peers written.
+    pub fn save_connected_peers(&self) -> usize {
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        let connected = self.connected_peer_addrs();
+        let mut saved = self.load_saved_peers();
+        for addr in &connected {
+            match saved.iter_mut().find(|p| &p.addr == addr) {
+                Some(peer) => peer.last_seen = now,
+                None => saved.push(SavedPeer {
+                    addr: addr.clone(),
+                    last_seen: now,
+                }),
+            }
+        }
+        self.save_saved_peers(&saved);
+        connected.len()
+    }
+
     pub async fn dns_seed_lookup(&self) -> anyhow::Result<Vec<String>> {
         let _seeds = vec![
             "seed1.Astram.org:19533",
@@ -1031,58 +2405,48 @@ impl PeerManager {
         Ok(peers)
     }
 
-    /// Broadcast a block to all connected peers (fire-and-forget)
+    /// Announce a block to all connected peers via Inv (fire-and-forget)
     pub async fn broadcast_block(&self, block: &block::Block) {
-        info!(
-            "[P2P] 🔒 broadcast_block #{}: acquiring peers lock...",
-            block.header.index
-        );
-        let lock_start = std::time::Instant::now();
-        let peers = self.peers.lock().clone();
-        let lock_duration = lock_start.elapsed();
-        info!(
-            "[P2P] ✅ broadcast_block #{}: peers lock acquired (took {:?}), {} peers",
-            block.header.index,
-            lock_duration,
-            peers.len()
-        );
-
-        for (_id, tx) in peers {
-            // clone the block for each peer
-            let _ = tx.send(P2pMessage::Block {
-                block: block.clone(),
-            });
+        // Peers fetch the full block with GetData if they lack it; the block must
+        // already be in the local DB so the getdata handler can serve it
+        match InvItem::block(&block.hash) {
+            Some(item) => {
+                self.broadcast_inv(vec![item.clone()]);
+                self.push_to_legacy_peers(&item, || P2pMessage::Block {
+                    block: block.clone(),
+                });
+            }
+            None => warn!(
+                "[P2P] broadcast_block #{}: invalid block hash {}",
+                block.header.index, block.hash
+            ),
         }
-        info!(
-            "[P2P] ✅ broadcast_block #{}: completed (total {:?})",
-            block.header.index,
-            lock_start.elapsed()
-        );
     }
 
-    /// Broadcast a transaction to all connected peers (async so callers can `.await`)
-    pub async fn broadcast_tx(&self, tx_obj: &Transaction) {
-        info!(
-            "[P2P] 🔒 broadcast_tx {}: acquiring peers lock...",
-            hex::encode(&tx_obj.txid[..8])
-        );
-        let lock_start = std::time::Instant::now();
+    /// Send the full object to peers that haven't advertised FEATURE_INV_RELAY (including
+    /// peers whose handshake hasn't arrived yet) and aren't known to have it
+    fn push_to_legacy_peers(&self, item: &InvItem, message: impl Fn() -> P2pMessage) {
         let peers = self.peers.lock().clone();
-        let lock_duration = lock_start.elapsed();
-        info!(
-            "[P2P] ✅ broadcast_tx: peers lock acquired (took {:?}), {} peers",
-            lock_duration,
-            peers.len()
-        );
+        for (peer_id, tx) in peers {
+            if self.peer_supports(&peer_id, FEATURE_INV_RELAY) {
+                continue;
+            }
+            if !self.mark_known(&peer_id, std::slice::from_ref(item)).is_empty() {
+                let _ = tx.send(message());
+            }
+        }
+    }
 
-        for (_id, tx) in peers {
-            // clone the transaction for each peer
-            let _ = tx.send(P2pMessage::Tx { tx: tx_obj.clone() });
+    /// Announce a transaction to all connected peers (async so callers can `.await`).
+    /// The transaction must be in the mempool so the getdata handler can serve it.
+    pub async fn broadcast_tx(&self, tx_obj: &Transaction) {
+        match InvItem::tx(&tx_obj.txid) {
+            Some(item) => {
+                self.broadcast_inv(vec![item.clone()]);
+                self.push_to_legacy_peers(&item, || P2pMessage::Tx { tx: tx_obj.clone() });
+            }
+            None => warn!("[P2P] broadcast_tx: invalid txid {}", tx_obj.txid),
         }
-        info!(
-            "[P2P] ✅ broadcast_tx: completed (total {:?})",
-            lock_start.elapsed()
-        );
     }
 
     /// Request headers from all connected peers using a GetHeaders message.
@@ -1171,6 +2535,7 @@ impl PeerManager {
             port: my_port,
             version,
             height: my_height,
+            network_magic: resolve_network_magic(),
         };
 
         let response = client
@@ -1263,6 +2628,7 @@ struct DnsRegisterRequest {
     port: u16,
     version: String,
     height: u64,
+    network_magic: u32,
 }
 
 #[derive(Deserialize)]
@@ -1291,3 +2657,493 @@ struct DnsNodesResponse {
     nodes: Vec<DnsNodeInfo>,
     count: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn misbehavior_bans_at_threshold() {
+        let manager = PeerManager::new();
+        let peer: PeerId = "203.0.113.7:8335".to_string();
+
+        for _ in 0..4 {
+            assert!(!manager.misbehaving(&peer, Misbehavior::InvalidPoW));
+        }
+        assert_eq!(manager.misbehavior_report().scores.get("203.0.113.7"), Some(&80));
+        assert!(!manager.is_banned("203.0.113.7:9000"));
+
+        assert!(manager.misbehaving(&peer, Misbehavior::OversizedMessage));
+        // The ban covers the IP, whatever port it reconnects from
+        assert!(manager.is_banned("203.0.113.7:9000"));
+        let report = manager.misbehavior_report();
+        assert!(report.scores.is_empty());
+        assert!(report.banned.contains_key("203.0.113.7"));
+
+        // Bans expire after the configured duration
+        manager.set_ban_duration(0);
+        let other: PeerId = "198.51.100.1:8335".to_string();
+        manager.misbehaving(&other, Misbehavior::OversizedMessage);
+        assert!(manager.misbehaving(&other, Misbehavior::OversizedMessage));
+        assert!(!manager.is_banned(&other));
+    }
+
+    #[test]
+    fn inventory_is_announced_once_per_peer() {
+        let manager = PeerManager::new();
+        let (tx_a, mut rx_a) = mpsc::unbounded_channel();
+        let (tx_b, mut rx_b) = mpsc::unbounded_channel();
+        manager.peers.lock().insert("10.0.0.1:8335".to_string(), tx_a);
+        manager.peers.lock().insert("10.0.0.2:8335".to_string(), tx_b);
+        for peer in ["10.0.0.1:8335", "10.0.0.2:8335"] {
+            let info = manager.local_handshake_info();
+            manager.peer_handshakes.lock().insert(peer.to_string(), info);
+        }
+
+        // Peer B announced the block to us, so only A needs to hear about it
+        let item = InvItem::block(&"ab".repeat(32)).unwrap();
+        manager.mark_known(&"10.0.0.2:8335".to_string(), std::slice::from_ref(&item));
+
+        assert_eq!(manager.broadcast_inv(vec![item.clone()]), 1);
+        assert!(matches!(rx_a.try_recv(), Ok(P2pMessage::Inv(items)) if items == vec![item.clone()]));
+        assert!(rx_b.try_recv().is_err());
+
+        // A second announcement of the same object goes nowhere
+        assert_eq!(manager.broadcast_inv(vec![item]), 0);
+        assert!(rx_a.try_recv().is_err());
+    }
+
+    #[test]
+    fn relay_is_gated_on_negotiated_features() {
+        let manager = PeerManager::new();
+        let (tx_old, _rx_old) = mpsc::unbounded_channel();
+        let (tx_plain, mut rx_plain) = mpsc::unbounded_channel();
+        manager.peers.lock().insert("10.0.0.1:8335".to_string(), tx_old);
+        manager.peers.lock().insert("10.0.0.2:8335".to_string(), tx_plain);
+
+        // Below the minimum version: disconnected
+        assert!(!manager.accept_protocol_version(&"10.0.0.1:8335".to_string(), 1));
+        assert!(!manager.peers.lock().contains_key("10.0.0.1:8335"));
+
+        // Current version without inv-relay: negotiated, but gets full objects pushed
+        let mut info = manager.local_handshake_info();
+        info.protocol_version = PROTOCOL_VERSION + 1;
+        info.supported_features.retain(|f| f != FEATURE_INV_RELAY);
+        manager.peer_handshakes.lock().insert("10.0.0.2:8335".to_string(), info);
+        assert_eq!(manager.negotiated_version("10.0.0.2:8335"), Some(PROTOCOL_VERSION));
+        assert_eq!(
+            manager.peer_protocol_versions(),
+            HashMap::from([("10.0.0.2:8335".to_string(), PROTOCOL_VERSION)])
+        );
+
+        let item = InvItem::tx(&"cd".repeat(32)).unwrap();
+        assert_eq!(manager.broadcast_inv(vec![item.clone()]), 0);
+        manager.push_to_legacy_peers(&item, || P2pMessage::Ping(7));
+        assert!(matches!(rx_plain.try_recv(), Ok(P2pMessage::Ping(7))));
+    }
+
+    #[test]
+    fn unanswered_pings_evict_peers() {
+        let manager = PeerManager::new();
+        let alive: PeerId = "203.0.113.7:8335".to_string();
+        let dead: PeerId = "198.51.100.1:8335".to_string();
+        let (tx_alive, mut rx_alive) = mpsc::unbounded_channel();
+        let (tx_dead, _rx_dead) = mpsc::unbounded_channel();
+        manager.peers.lock().insert(alive.clone(), tx_alive);
+        manager.peers.lock().insert(dead.clone(), tx_dead);
+
+        let start = Instant::now();
+        assert!(manager.ping_peers_at(start).is_empty());
+        let Ok(P2pMessage::Ping(nonce)) = rx_alive.try_recv() else {
+            panic!("expected a ping");
+        };
+
+        // A pong with the wrong nonce is ignored; the right one records latency
+        manager.record_pong(&alive, nonce.wrapping_add(1));
+        assert!(manager.get_peer_latencies().is_empty());
+        manager.record_pong(&alive, nonce);
+        assert!(manager.get_peer_latencies().contains_key(&alive));
+
+        // The next round re-pings the live peer; the silent one is dropped on timeout
+        let later = start + Duration::from_secs(PING_TIMEOUT_SECS);
+        assert_eq!(manager.ping_peers_at(later), vec![dead.clone()]);
+        assert!(matches!(rx_alive.try_recv(), Ok(P2pMessage::Ping(_))));
+        assert!(!manager.peers.lock().contains_key(&dead));
+        assert!(manager.peers.lock().contains_key(&alive));
+    }
+
+    #[test]
+    fn syncing_until_caught_up_with_most_peers() {
+        let manager = PeerManager::new();
+        // no peers: nothing to sync from
+        assert!(!manager.is_syncing());
+
+        manager.peer_heights.lock().insert("203.0.113.7:8335".to_string(), 40);
+        manager.peer_heights.lock().insert("198.51.100.1:8335".to_string(), 41);
+        manager.peer_heights.lock().insert("192.0.2.1:8335".to_string(), 12);
+        manager.set_my_height(10);
+        assert_eq!(manager.blocks_behind(), 30);
+        assert!(manager.is_syncing());
+
+        manager.set_my_height(40 - SYNC_TOLERANCE_BLOCKS);
+        assert!(!manager.is_syncing());
+        manager.set_my_height(41);
+        assert_eq!(manager.blocks_behind(), 0);
+
+        // One peer claiming an impossible height doesn't move the estimate
+        manager.peer_heights.lock().insert("192.0.2.1:8335".to_string(), u64::MAX);
+        assert_eq!(manager.network_height(), 41);
+        assert!(!manager.is_syncing());
+    }
+
+    #[test]
+    fn missing_parents_are_requested_once_and_retried_elsewhere() {
+        let manager = PeerManager::new();
+        let (a, b): (PeerId, PeerId) = ("10.0.0.1:8335".into(), "10.0.0.2:8335".into());
+        let (tx_a, mut rx_a) = mpsc::unbounded_channel();
+        let (tx_b, mut rx_b) = mpsc::unbounded_channel();
+        manager.peers.lock().insert(a.clone(), tx_a);
+        manager.peers.lock().insert(b.clone(), tx_b);
+        let hash = |n: u32| format!("{:064x}", n);
+        let wanted = vec![InvItem::block(&hash(99)).unwrap()];
+
+        // An orphan from A: its parent is asked of A, and only once
+        let start = Instant::now();
+        assert!(manager.request_parent_at(&a, &hash(100), &hash(99), start));
+        assert!(matches!(rx_a.try_recv(), Ok(P2pMessage::GetData(items)) if items == wanted));
+        assert!(!manager.request_parent_at(&b, &hash(100), &hash(99), start));
+        assert!(rx_b.try_recv().is_err());
+
+        // A never answers: B is asked after the timeout, then nobody is left
+        let timeout = Duration::from_secs(GETDATA_TIMEOUT_SECS);
+        manager.retry_parent_requests_at(start + timeout / 2);
+        assert!(rx_b.try_recv().is_err());
+        manager.retry_parent_requests_at(start + timeout);
+        assert!(matches!(rx_b.try_recv(), Ok(P2pMessage::GetData(items)) if items == wanted));
+        manager.retry_parent_requests_at(start + timeout * 2);
+        assert!(manager.parent_requests.lock().is_empty());
+
+        // Each fetched parent that is an orphan too reaches one further back, up to the cap
+        let mut child = 1000;
+        for depth in 1..=MAX_PARENT_FETCH_DEPTH {
+            let sent = manager.request_parent_at(&a, &hash(child), &hash(child - 1), start);
+            assert!(sent, "depth {}", depth);
+            child -= 1;
+        }
+        assert!(!manager.request_parent_at(&a, &hash(child), &hash(child - 1), start));
+
+        // Unrelated orphans can't grow the request map past its cap either
+        manager.parent_requests.lock().clear();
+        for n in 0..MAX_PARENT_REQUESTS as u32 {
+            let orphan = 10_000 + 2 * n;
+            assert!(manager.request_parent_at(&a, &hash(orphan), &hash(orphan + 1), start));
+        }
+        assert!(!manager.request_parent_at(&a, &hash(20_000), &hash(20_001), start));
+        assert_eq!(manager.parent_requests.lock().len(), MAX_PARENT_REQUESTS);
+    }
+
+    #[test]
+    fn invalid_blocks_are_tallied_per_ip_and_repeats_penalized() {
+        let manager = PeerManager::new();
+        let peer: PeerId = "203.0.113.7:8335".to_string();
+        let spent = Astram_core::security::reject(
+            BlockFailureReason::UtxoNotFound,
+            "referenced utxo not found ab:0 (already spent or never existed)",
+        );
+        let score = || {
+            manager
+                .misbehavior_report()
+                .scores
+                .get("203.0.113.7")
+                .copied()
+        };
+
+        // A failure an honest peer can cause is only counted at first...
+        for _ in 0..REPEATED_BLOCK_FAILURES - 1 {
+            assert!(!manager.block_rejected(&peer, &spent, true));
+        }
+        assert!(manager.misbehavior_report().scores.is_empty());
+        // ...until the same IP keeps doing it
+        manager.block_rejected(&peer, &spent, true);
+        assert_eq!(score(), Some(20));
+
+        // Provable misbehavior is penalized on the spot
+        let merkle = Astram_core::security::reject(
+            BlockFailureReason::MerkleRootMismatch,
+            "merkle mismatch",
+        );
+        manager.block_rejected(&"203.0.113.7:9000".to_string(), &merkle, true);
+        assert_eq!(score(), Some(40));
+
+        // Neither local failures nor side-branch blocks missing a UTXO count at all
+        let local = anyhow::anyhow!("referenced utxo not found: IO error");
+        for _ in 0..REPEATED_BLOCK_FAILURES {
+            assert!(!manager.block_rejected(&peer, &local, true));
+            assert!(!manager.block_rejected(&peer, &spent, false));
+        }
+        assert_eq!(score(), Some(40));
+
+        let report = manager.block_failure_report();
+        let tally = &report["203.0.113.7"];
+        assert_eq!(tally.total, REPEATED_BLOCK_FAILURES + 1);
+        assert_eq!(tally.reasons["utxo_not_found"], REPEATED_BLOCK_FAILURES);
+        assert_eq!(tally.reasons["merkle_root_mismatch"], 1);
+
+        manager.reset_block_failures();
+        assert!(manager.block_failure_report().is_empty());
+        assert_eq!(score(), Some(40));
+    }
+
+    #[test]
+    fn peers_with_another_genesis_are_banned() {
+        let manager = PeerManager::new();
+        let peer: PeerId = "203.0.113.7:8335".to_string();
+        let ours = "ab".repeat(32);
+        let theirs = "cd".repeat(32);
+        // Nothing to compare against until our genesis is known
+        assert!(!manager.wrong_genesis(&peer, 0, &theirs));
+
+        manager.set_genesis_hash(ours.clone());
+        assert!(!manager.wrong_genesis(&peer, 0, &ours));
+        assert!(!manager.wrong_genesis(&peer, 1, &theirs));
+        assert!(!manager.is_banned(&peer));
+
+        assert!(manager.wrong_genesis(&peer, 0, &theirs));
+        assert!(manager.is_banned(&peer));
+    }
+
+    #[test]
+    fn peers_from_another_network_are_disconnected() {
+        let manager = PeerManager::new();
+        manager.set_genesis_hash("ab".repeat(32));
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let peer: PeerId = "10.0.0.2:8335".to_string();
+        manager.peers.lock().insert(peer.clone(), tx.clone());
+
+        let mut info = manager.local_handshake_info();
+        assert!(manager.accept_network(&peer, &info));
+        // A peer that hasn't created its genesis yet is judged on the magic alone
+        info.genesis_hash.clear();
+        assert!(manager.accept_network(&peer, &info));
+
+        // Another magic is dropped; it may well be an honest node of that network
+        info.network_magic = resolve_network_magic().wrapping_add(1);
+        assert!(!manager.accept_network(&peer, &info));
+        assert!(!manager.peers.lock().contains_key(&peer));
+        assert!(!manager.is_banned(&peer));
+
+        // Same magic but another genesis is banned like any wrong-genesis peer
+        let other: PeerId = "10.0.0.3:8335".to_string();
+        manager.peers.lock().insert(other.clone(), tx);
+        let mut info = manager.local_handshake_info();
+        info.genesis_hash = "cd".repeat(32);
+        assert!(!manager.accept_network(&other, &info));
+        assert!(manager.is_banned(&other));
+    }
+
+    #[test]
+    fn peer_summaries_report_each_connection() {
+        let manager = PeerManager::new();
+        let inbound: PeerId = "203.0.113.7:40000".to_string();
+        let outbound: PeerId = "198.51.100.1:8335".to_string();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        manager.peers.lock().insert(inbound.clone(), tx.clone());
+        manager.peers.lock().insert(outbound.clone(), tx);
+
+        let start = Instant::now();
+        manager.register_connection(&inbound, true, start);
+        manager.register_connection(&outbound, false, start + Duration::from_secs(5));
+        manager.record_received(&inbound, 120, start + Duration::from_secs(10));
+        let info = manager.local_handshake_info();
+        let version = info.software_version.clone();
+        manager.peer_handshakes.lock().insert(inbound.clone(), info);
+        manager.peer_heights.lock().insert(inbound.clone(), 42);
+        manager
+            .peer_latency
+            .lock()
+            .insert(inbound.clone(), Duration::from_millis(35));
+        manager.misbehaving(&inbound, Misbehavior::MalformedMessage);
+
+        let summaries = manager.peer_summaries_at(start + Duration::from_secs(30));
+        assert_eq!(summaries.len(), 2);
+        // Oldest connection first
+        let (first, second) = (&summaries[0], &summaries[1]);
+        assert_eq!(first.address, inbound);
+        assert_eq!(first.direction, "inbound");
+        assert_eq!(first.protocol_version, Some(PROTOCOL_VERSION));
+        assert_eq!(first.software_version, Some(version));
+        assert_eq!(first.height, Some(42));
+        assert_eq!(first.latency_ms, Some(35));
+        assert_eq!(first.bytes_received, 120);
+        let penalty = Misbehavior::MalformedMessage.penalty();
+        assert_eq!(first.misbehavior_score, penalty);
+        assert_eq!((first.connected_secs, first.last_message_secs), (30, 20));
+
+        // Not handshaken yet: only what the connection itself knows
+        assert_eq!(second.direction, "outbound");
+        assert_eq!(second.protocol_version, None);
+        assert_eq!(second.height, None);
+        assert_eq!((second.connected_secs, second.last_message_secs), (25, 25));
+
+        // A dropped peer is gone even before its socket closes
+        manager.peers.lock().remove(&outbound);
+        assert_eq!(manager.peer_summaries_at(start).len(), 1);
+    }
+
+    #[test]
+    fn disconnect_forgets_the_peer_and_can_ban_its_ip() {
+        let manager = PeerManager::new();
+        let a: PeerId = "203.0.113.7:8335".to_string();
+        let a2: PeerId = "203.0.113.7:40000".to_string();
+        let b: PeerId = "198.51.100.1:8335".to_string();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        for peer in [&a, &a2, &b] {
+            manager.peers.lock().insert(peer.clone(), tx.clone());
+            manager.peer_heights.lock().insert(peer.clone(), 10);
+            manager.register_connection(peer, false, Instant::now());
+            manager
+                .peer_ips
+                .lock()
+                .entry(ip_of(peer).to_string())
+                .or_default()
+                .push(peer.clone());
+        }
+        manager.misbehaving(&a, Misbehavior::MalformedMessage);
+
+        // Only the named connection goes; the IP's score stays while it has another
+        assert_eq!(manager.disconnect_peer(&a, None), 1);
+        assert!(!manager.peers.lock().contains_key(&a));
+        assert!(!manager.peer_heights.lock().contains_key(&a));
+        assert!(manager.peers.lock().contains_key(&a2));
+        let scores = manager.misbehavior_report().scores;
+        assert!(scores.contains_key("203.0.113.7"));
+        assert!(!manager.is_banned(&a));
+        assert_eq!(manager.disconnect_peer(&a, None), 0);
+
+        // A ban closes every connection from the IP and refuses it for the duration
+        assert_eq!(
+            manager.disconnect_peer(&b, Some(Duration::from_secs(600))),
+            1
+        );
+        assert!(manager.is_banned("198.51.100.1:9000"));
+        assert_eq!(
+            manager.disconnect_peer(&a2, Some(Duration::from_secs(600))),
+            1
+        );
+        assert!(manager.misbehavior_report().scores.is_empty());
+        assert!(manager.peer_summaries().is_empty());
+
+        // An absurd ban is clamped rather than overflowing the deadline
+        assert_eq!(
+            manager.disconnect_peer(&a, Some(Duration::from_secs(u64::MAX))),
+            0
+        );
+        assert!(manager.is_banned(&a));
+        manager.set_ban_duration(u64::MAX);
+        assert_eq!(
+            *manager.ban_duration.lock(),
+            Duration::from_secs(MAX_BAN_DURATION_SECS)
+        );
+    }
+
+    #[test]
+    fn connection_slots_are_capped_and_evict_worse_inbound_peers() {
+        let manager = PeerManager::new();
+        manager.set_max_outbound(4);
+        manager.set_max_inbound(2);
+        let now = Instant::now();
+
+        // Two of four outbound slots are held back for the DNS refresh
+        let dial = |host: u8, from_dns: bool| {
+            let addr = format!("203.0.113.{}:8335", host);
+            manager.reserve_outbound(&addr, from_dns, now).is_ok()
+        };
+        assert!(dial(1, false) && dial(2, false));
+        assert!(!dial(3, false));
+        assert!(dial(3, true));
+        assert!(!dial(3, true), "already connected");
+        assert!(dial(4, true));
+        assert!(!dial(5, true));
+        // A failed dial gives its slot back
+        manager.release_slot("203.0.113.4:8335");
+        assert_eq!(manager.slot_usage().outbound, 3);
+
+        let clean: PeerId = "198.51.100.1:40000".to_string();
+        let noisy: PeerId = "198.51.100.2:40000".to_string();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        for peer in [&clean, &noisy] {
+            assert_eq!(manager.reserve_inbound(peer, now), Ok(None));
+            manager.peers.lock().insert(peer.clone(), tx.clone());
+            let info = manager.local_handshake_info();
+            manager.peer_handshakes.lock().insert(peer.clone(), info);
+        }
+        manager.misbehaving(&noisy, Misbehavior::MalformedMessage);
+
+        // Full: a newcomer with the same record as the worst peer is turned away...
+        let equal: PeerId = "198.51.100.2:40001".to_string();
+        assert!(manager.reserve_inbound(&equal, now).is_err());
+        // ...one with a better record takes the penalized peer's slot
+        let newcomer: PeerId = "192.0.2.9:40000".to_string();
+        let evicted = manager.reserve_inbound(&newcomer, now);
+        assert_eq!(evicted, Ok(Some(noisy.clone())));
+        assert!(!manager.peers.lock().contains_key(&noisy));
+        let usage = manager.slot_usage();
+        assert_eq!((usage.inbound, usage.max_inbound), (2, 2));
+
+        // A peer that never handshakes is evicted once the handshake timeout has passed
+        let late = now + Duration::from_secs(HANDSHAKE_TIMEOUT_SECS);
+        let another: PeerId = "192.0.2.10:40000".to_string();
+        assert_eq!(manager.reserve_inbound(&another, late), Ok(Some(newcomer)));
+    }
+
+    #[test]
+    fn compact_blocks_are_completed_with_blocktxn_or_fetched_in_full() {
+        use crate::p2p::compact::{self, test_support, test_support::tx};
+
+        let block = test_support::block((0..4).map(tx).collect());
+
+        let manager = PeerManager::new();
+        let peer: PeerId = "203.0.113.7:8335".to_string();
+        let (tx_peer, mut rx) = mpsc::unbounded_channel();
+        manager.peers.lock().insert(peer.clone(), tx_peer);
+        let mempool = vec![tx(1), tx(3)];
+        manager.set_on_compact_matches(move |cb| compact::mempool_matches(cb, &mempool));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let sink = delivered.clone();
+        manager.set_on_block(move |_, block| sink.lock().push(block.hash));
+
+        // Tx 2 isn't in the mempool, so it is asked for
+        manager.receive_compact_block(&peer, compact::compact_block(&block, 1));
+        let Ok(P2pMessage::GetBlockTxn { hash, indexes }) = rx.try_recv() else {
+            panic!("expected GetBlockTxn");
+        };
+        assert_eq!((hash.as_str(), indexes), (block.hash.as_str(), vec![2]));
+
+        // Only the peer that was asked can complete it
+        let other: PeerId = "198.51.100.1:8335".to_string();
+        manager.receive_block_txn(&other, &block.hash, vec![tx(2)]);
+        assert!(delivered.lock().is_empty());
+        manager.receive_block_txn(&peer, &block.hash, vec![tx(2)]);
+        assert_eq!(*delivered.lock(), vec![block.hash.clone()]);
+
+        // A transaction that doesn't fit the merkle root means fetching the full block
+        manager.receive_compact_block(&peer, compact::compact_block(&block, 2));
+        assert!(matches!(rx.try_recv(), Ok(P2pMessage::GetBlockTxn { .. })));
+        manager.receive_block_txn(&peer, &block.hash, vec![tx(9)]);
+        let full = vec![InvItem::block(&block.hash).unwrap()];
+        assert!(matches!(rx.try_recv(), Ok(P2pMessage::GetData(items)) if items == full));
+        assert!(manager.partial_blocks.lock().is_empty());
+        assert_eq!(delivered.lock().len(), 1);
+    }
+
+    #[test]
+    fn misbehavior_score_decays() {
+        let now = Instant::now();
+        let mut entry = MisbehaviorScore { score: 5, updated: now };
+        let after = |secs: u64| now + Duration::from_secs(secs);
+        assert_eq!(entry.decayed(after(MISBEHAVIOR_DECAY_SECS * 2 + 30)), 3);
+        // The partial interval carries over instead of being lost
+        assert_eq!(entry.decayed(after(MISBEHAVIOR_DECAY_SECS * 3)), 2);
+        assert_eq!(entry.decayed(after(MISBEHAVIOR_DECAY_SECS * 100)), 0);
+    }
+}
@@ -3,7 +3,7 @@
 use bincode::{Decode, Encode};
 use Astram_core::block::Block;
 use Astram_core::block::BlockHeader;
-use Astram_core::transaction::Transaction;
+use Astram_core::transaction::{TX_VERSION_LEGACY, Transaction};
 
 /// Peer handshake information
 #[derive(Debug, Clone, Encode, Decode)]
@@ -13,17 +13,76 @@ pub struct HandshakeInfo {
     pub supported_features: Vec<String>,
     pub network_id: String,
     pub chain_id: u64,
+    /// Network magic; peers with another one are disconnected
+    pub network_magic: u32,
+    /// Hash of the sender's block 0 (empty if not known yet)
+    pub genesis_hash: String,
     pub height: u64,
     /// Listening port of this node (to detect self-connections)
     pub listening_port: u16,
 }
 
 /// (inv/getdata)
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
 pub enum InventoryType {
     Error = 0,
     Transaction = 1,
     Block = 2,
+    /// A block requested as a CmpctBlock (only from peers with the compact-blocks feature)
+    CompactBlock = 3,
+}
+
+/// One announced (inv) or requested (getdata) object: its type and raw hash bytes
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct InvItem {
+    pub object_type: InventoryType,
+    pub hash: Vec<u8>,
+}
+
+impl InvItem {
+    pub fn block(hash_hex: &str) -> Option<Self> {
+        hex::decode(hash_hex).ok().map(|hash| Self {
+            object_type: InventoryType::Block,
+            hash,
+        })
+    }
+
+    pub fn tx(txid_hex: &str) -> Option<Self> {
+        hex::decode(txid_hex).ok().map(|hash| Self {
+            object_type: InventoryType::Transaction,
+            hash,
+        })
+    }
+
+    /// The same object under another type, e.g. a block to be sent as a compact block
+    pub fn with_type(&self, object_type: InventoryType) -> Self {
+        Self {
+            object_type,
+            hash: self.hash.clone(),
+        }
+    }
+}
+
+/// A transaction sent in full inside a compact block (at least the coinbase, which no
+/// mempool has)
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct PrefilledTx {
+    /// Position in the block
+    pub index: u32,
+    pub tx: Transaction,
+}
+
+/// A block as its header plus 6-byte short ids of its transactions (BIP152-style); the
+/// receiver rebuilds it from its mempool and asks for the rest with GetBlockTxn
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct CompactBlock {
+    pub header: BlockHeader,
+    pub hash: String,
+    /// Salt for the short ids, chosen by the sender
+    pub nonce: u64,
+    /// Short ids of the transactions that aren't prefilled, in block order
+    pub short_ids: Vec<u64>,
+    pub prefilled: Vec<PrefilledTx>,
 }
 
 /// message type
@@ -47,14 +106,10 @@ pub enum P2pMessage {
     Headers {
         headers: Vec<BlockHeader>,
     },
-    Inv {
-        object_type: InventoryType,
-        hashes: Vec<Vec<u8>>,
-    },
-    GetData {
-        object_type: InventoryType,
-        hashes: Vec<Vec<u8>>,
-    },
+    /// Announce objects by hash; peers answer with GetData for the ones they lack
+    Inv(Vec<InvItem>),
+    /// Request full objects previously announced via Inv (or found through headers)
+    GetData(Vec<InvItem>),
     Block {
         block: Block,
     },
@@ -63,5 +118,34 @@ pub enum P2pMessage {
     },
     Ping(u64),
     Pong(u64),
+    /// Answer to GetData for InventoryType::CompactBlock
+    CmpctBlock {
+        block: CompactBlock,
+    },
+    /// Ask for the transactions at `indexes` of a compact block the mempool couldn't fill
+    GetBlockTxn {
+        hash: String,
+        indexes: Vec<u32>,
+    },
+    /// Answer to GetBlockTxn: the transactions asked for, in the order asked
+    BlockTxn {
+        hash: String,
+        txs: Vec<Transaction>,
+    },
+}
+
+impl P2pMessage {
+    /// Whether the message contains a transaction newer than the legacy format, which
+    /// peers without the tx-v2 feature can't decode
+    pub fn carries_versioned_tx(&self) -> bool {
+        let versioned = |tx: &Transaction| tx.version() > TX_VERSION_LEGACY;
+        match self {
+            P2pMessage::Tx { tx } => versioned(tx),
+            P2pMessage::Block { block } => block.transactions.iter().any(versioned),
+            P2pMessage::CmpctBlock { block } => block.prefilled.iter().any(|p| versioned(&p.tx)),
+            P2pMessage::BlockTxn { txs, .. } => txs.iter().any(versioned),
+            _ => false,
+        }
+    }
 }
 
@@ -1,3 +1,4 @@
+pub mod compact;
 pub mod manager;
 pub mod messages;
 pub mod peer;
@@ -1,14 +1,23 @@
 // node/src/p2p/service.rs
 use crate::ChainState;
 use crate::NodeHandle;
-use crate::p2p::manager::{MAX_OUTBOUND, PeerManager};
+use crate::p2p::compact;
+use crate::p2p::manager::PeerManager;
+use crate::p2p::messages::{InvItem, InventoryType, P2pMessage};
+use crate::p2p::peer::PeerId;
 use hex;
 use log::{info, warn};
 use Astram_core::block;
+use Astram_core::blockchain::BlockPruned;
+use Astram_core::security::BlockFailureReason;
+use Astram_core::sigcache::SIGNATURE_CACHE;
 use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::time::{Duration, sleep};
 
+/// Headers returned per GetHeaders reply
+const MAX_HEADERS_PER_GETHEADERS: usize = 200;
+
 pub struct P2PService {
     pub manager: Arc<PeerManager>,
 }
@@ -34,6 +43,8 @@ impl P2PService {
         self.connect_initial_peers().await;
         self.register_handlers(node_handle.clone(), chain_state.clone());
         self.start_header_sync(chain_state.clone());
+        tokio::spawn(self.manager.clone().start_keepalive_loop());
+        tokio::spawn(self.manager.clone().start_parent_retry_loop());
 
         Ok(())
     }
@@ -62,7 +73,7 @@ impl P2PService {
             peers.insert(sp.addr);
         }
 
-        for addr in peers.into_iter().take(MAX_OUTBOUND) {
+        for addr in peers.into_iter().take(p2p.max_outbound()) {
             let p2p_clone = p2p.clone();
             tokio::spawn(async move {
                 if let Err(e) = p2p_clone.connect_peer(&addr).await {
@@ -82,72 +93,40 @@ impl P2PService {
         // getheaders handler - load headers from DB
         let nh = node_handle.clone();
         p2p.set_on_getheaders(move |locator_hashes, _stop_hash| {
-            let mut headers = Vec::new();
-
             let bc = nh.bc.lock().unwrap();
 
-            // Get chain tip
-            let tip_hash = match &bc.chain_tip {
-                Some(h) => h.clone(),
-                None => return headers,
-            };
+            // Start right after the first locator on our main chain; with no match (or no
+            // locator) start from genesis. Headers come straight from the `i:` index, so
+            // this works no matter how much of the chain is loaded in memory.
+            let start_height = locator_hashes
+                .iter()
+                .find_map(|loc| bc.main_chain_height(&hex::encode(loc)).ok().flatten())
+                .map_or(0, |height| height + 1);
+
+            bc.get_headers_range(start_height, MAX_HEADERS_PER_GETHEADERS)
+                .unwrap_or_else(|e| {
+                    log::warn!("getheaders: failed to read headers from {}: {}", start_height, e);
+                    Vec::new()
+                })
+        });
 
-            // Build full chain from tip backwards
-            let mut chain = Vec::new();
-            let mut current_hash = Some(tip_hash);
-            
-            while let Some(hash) = current_hash {
-                if let Ok(Some(header)) = bc.load_header(&hash) {
-                    chain.push(header.clone());
-                    if header.index == 0 {
-                        break;
-                    }
-                    current_hash = Some(header.previous_hash.clone());
-                } else {
-                    break;
+        // Headers lead checkpoint-assisted sync to the checkpoint it may trust
+        let nh_headers = node_handle.clone();
+        p2p.set_on_headers(move |headers| {
+            let mut bc = nh_headers.bc.lock().unwrap();
+            if bc.assume_valid.is_some() {
+                let linked = bc.accept_assume_valid_headers(headers);
+                if linked > 0 {
+                    log::info!("Checkpoint sync: header chain of {} blocks leads to the checkpoint", linked);
                 }
             }
-            
-            // Reverse to get genesis-first order
-            chain.reverse();
-
-            // Determine starting point
-            let start_index = if locator_hashes.is_empty() {
-                // No locator - start from genesis
-                0
-            } else {
-                // Find first matching locator
-                let mut found_index = 0;
-                for loc_hash in &locator_hashes {
-                    let hash_hex = hex::encode(loc_hash);
-                    if let Some(pos) = chain.iter().position(|h| {
-                        if let Ok(computed) = Astram_core::block::compute_header_hash(h) {
-                            computed == hash_hex
-                        } else {
-                            false
-                        }
-                    }) {
-                        found_index = pos + 1; // Start from next block
-                        break;
-                    }
-                }
-                found_index
-            };
-
-            // Return up to 200 headers starting from start_index
-            headers = chain.into_iter()
-                .skip(start_index)
-                .take(200)
-                .collect();
-
-            headers
         });
 
         // block handler
         let nh2 = node_handle.clone();
         let chain_for_block = chain_state.clone();
         let p2p_for_block = p2p.clone();
-        p2p.set_on_block(move |block: block::Block| {
+        p2p.set_on_block(move |peer_id: PeerId, block: block::Block| {
             info!("[P2P] 📦 Block handler START for block #{} {}", block.header.index, &block.hash[..16]);
             let handler_start = std::time::Instant::now();
             
@@ -157,20 +136,32 @@ impl P2PService {
             tokio::spawn(async move {
                 let state = nh_async;
 
+                // Lock order bc -> chain; both are held across the recently-mined check,
+                // the DB insert and the in-memory push so /mining/submit can't interleave
+                info!("[P2P] 🔒 Block handler: acquiring bc lock for validation...");
+                let lock_start = std::time::Instant::now();
+                let mut bc = state.bc.lock().unwrap();
+                info!("[P2P] ✅ Block handler: bc lock acquired (took {:?})", lock_start.elapsed());
+
+                let mut chain = chain_async.lock().unwrap();
+
                 // Check if this is a block we recently mined ourselves
-                {
-                    info!("[P2P] 🔒 Block handler: acquiring chain lock for recently_mined check...");
-                    let lock_start = std::time::Instant::now();
-                    let chain = chain_async.lock().unwrap();
-                    info!("[P2P] ✅ Block handler: chain lock acquired (took {:?})", lock_start.elapsed());
-                    
-                    if chain.recently_mined_blocks.contains_key(&block.hash) {
-                        info!(
-                            "[INFO] Ignoring block we mined ourselves: index={} hash={}",
-                            block.header.index, block.hash
-                        );
-                        return;
-                    }
+                if chain.recently_mined_blocks.contains_key(&block.hash) {
+                    info!(
+                        "[INFO] Ignoring block we mined ourselves: index={} hash={}",
+                        block.header.index, block.hash
+                    );
+                    return;
+                }
+
+                // A block we already have (relayed again, or an old side-branch block)
+                // would only fail the UTXO checks against our tip
+                if matches!(bc.load_header(&block.hash), Ok(Some(_))) {
+                    info!(
+                        "[INFO] Ignoring already known block: index={} hash={}",
+                        block.header.index, block.hash
+                    );
+                    return;
                 }
 
                 // Cancel ongoing mining when receiving a new block
@@ -180,13 +171,15 @@ impl P2PService {
                     .store(true, std::sync::atomic::Ordering::SeqCst);
 
                 // Try to insert the block
-                info!("[P2P] 🔒 Block handler: acquiring bc lock for validation...");
-                let lock_start = std::time::Instant::now();
-                let mut bc = state.bc.lock().unwrap();
-                info!("[P2P] ✅ Block handler: bc lock acquired (took {:?})", lock_start.elapsed());
-                
                 let validation_start = std::time::Instant::now();
-                match bc.validate_and_insert_block(&block) {
+                let extends_tip = bc.chain_tip.as_deref() == Some(block.header.previous_hash.as_str());
+                let result = bc.validate_and_insert_block(&block);
+                if result.is_ok() {
+                    chain.push_block(&block);
+                }
+                drop(chain);
+
+                match result {
                     Ok(_) => {
                         info!(
                             "[P2P] ✅ Block #{} validated and inserted (validation took {:?})",
@@ -197,36 +190,20 @@ impl P2PService {
                             block.header.index, block.hash
                         );
                         
-                        // Release bc lock before taking chain lock
                         drop(bc);
-                        
-                        {
-                            info!("[P2P] 🔒 Block handler: acquiring chain lock for blockchain update...");
-                            let lock_start = std::time::Instant::now();
-                            let mut chain = chain_async.lock().unwrap();
-                            info!("[P2P] ✅ Block handler: chain lock acquired (took {:?})", lock_start.elapsed());
-                            chain.blockchain.push(block.clone());
-                            chain.enforce_memory_limit(); // Security: Enforce memory limit
-                        }
 
                         // Update P2P manager height
                         p2p_block.set_my_height(block.header.index + 1);
+                        state.notify_block(&block);
 
-                        // Remove transactions from pending pool that are in the new block
-                        let block_txids: std::collections::HashSet<String> = block
-                            .transactions
-                            .iter()
-                            .map(|tx| tx.txid.clone())
-                            .collect();
-
-                        let removed_count = block_txids.len().saturating_sub(1); // -1 for coinbase
-                        {
+                        // Remove transactions from pending pool that are in (or conflict with) the new block
+                        let removed_count = {
                             info!("[P2P] 🔒 Block handler: acquiring mempool lock to remove txs...");
                             let lock_start = std::time::Instant::now();
                             let mut mempool = state.mempool.lock().unwrap();
                             info!("[P2P] ✅ Block handler: mempool lock acquired (took {:?})", lock_start.elapsed());
-                            mempool.pending.retain(|tx| !block_txids.contains(&tx.txid));
-                        }
+                            mempool.remove_confirmed(&block)
+                        };
 
                         if removed_count > 0 {
                             info!(
@@ -254,13 +231,14 @@ impl P2PService {
                             }
                         }
 
-                        // Try to process orphan blocks that may now be valid
+                        // Connect any buffered orphans that descend from this block
                         {
                             let mut chain = chain_async.lock().unwrap();
                             Self::process_orphan_blocks(
                                 &mut bc,
                                 &mut chain,
-                                &state.mempool,
+                                &block.hash,
+                                &state,
                                 p2p_block.clone(),
                             );
                         }
@@ -270,36 +248,12 @@ impl P2PService {
                     }
                     Err(e) => {
                         // Block validation failed - check if it's an orphan
-                        let error_msg = format!("{:?}", e);
-                        
-                        if error_msg.contains("previous header not found") {
-                            // Security: Check orphan pool size limit before adding
+                        if BlockFailureReason::of(&e) == Some(BlockFailureReason::PreviousNotFound) {
+                            // Security: the pool drops expired orphans and stays within
+                            // MAX_ORPHAN_BLOCKS
                             let now = chrono::Utc::now().timestamp();
-                            
                             let mut chain = chain_async.lock().unwrap();
-                            if chain.orphan_blocks.len() >= crate::MAX_ORPHAN_BLOCKS {
-                                warn!(
-                                    "[WARN] Orphan pool full ({} blocks), dropping oldest orphan to accept new one",
-                                    chain.orphan_blocks.len()
-                                );
-                                
-                                // Find and remove oldest orphan
-                                let oldest_hash = chain.orphan_blocks
-                                    .iter()
-                                    .min_by_key(|(_, (_, timestamp))| *timestamp)
-                                    .map(|(h, _)| h.clone());
-                                
-                                if let Some(hash) = oldest_hash {
-                                    chain.orphan_blocks.remove(&hash);
-                                }
-                            }
-                            
-                            // Clean up expired orphans (older than 30 minutes)
-                            chain.orphan_blocks.retain(|_, (_, timestamp)| {
-                                now - *timestamp < crate::ORPHAN_TIMEOUT
-                            });
-                            
-                            chain.orphan_blocks.insert(block.hash.clone(), (block.clone(), now));
+                            chain.add_orphan(block.clone(), Some(&peer_id), now);
                             
                             info!(
                                 "[INFO] Orphan block received (index={}, hash={}), storing for later (orphan pool size: {})",
@@ -308,11 +262,19 @@ impl P2PService {
                                 chain.orphan_blocks.len()
                             );
                             
-                            // Request the parent block
-                            // TODO: implement getdata request for parent block
+                            // Ask the sender for the missing parent, outside the bc and
+                            // chain locks since the manager's inventory check takes them
+                            drop(chain);
+                            drop(bc);
+                            p2p_block.request_parent(
+                                &peer_id,
+                                &block.hash,
+                                &block.header.previous_hash,
+                            );
                             info!("[P2P] ⏸️ Block handler: orphan block stored (total time {:?})", handler_start.elapsed());
                         } else {
                             warn!("[WARN] Invalid block from p2p: {:?}", e);
+                            p2p_block.block_rejected(&peer_id, &e, extends_tip);
                             info!("[P2P] ❌ Block handler: invalid block rejected (total time {:?})", handler_start.elapsed());
                         }
                     }
@@ -356,19 +318,36 @@ impl P2PService {
                         }
                     }
 
+                    // Version 2 transactions can't be mined before their activation height
+                    {
+                        let bc = state.bc.lock().unwrap();
+                        let next_height = bc.get_next_index().unwrap_or(0);
+                        if !bc.params.allows_tx_version(tx.version(), next_height) {
+                            warn!(
+                                "[WARN] P2P TX {} rejected: version {} not allowed before height {}",
+                                tx.txid,
+                                tx.version(),
+                                bc.params.tx_expiry_height
+                            );
+                            return;
+                        }
+                    }
+
                     // Validate transaction signatures
                     info!("[P2P] 🔐 TX handler: validating signatures...");
                     let validation_start = std::time::Instant::now();
-                    match tx.verify_signatures() {
+                    match SIGNATURE_CACHE.verify(&tx) {
                         Ok(true) => {
                             info!("[P2P] ✅ TX handler: signatures validated (took {:?})", validation_start.elapsed());
                             info!("[OK] Transaction {} received and validated from p2p", tx.txid);
                             
-                            // Security: Check for double-spending in mempool
-                            let mut tx_utxos = std::collections::HashSet::new();
-                            for inp in &tx.inputs {
-                                tx_utxos.insert(format!("{}:{}", inp.txid, inp.vout));
-                            }
+                            // Security: Check for double-spending in mempool. A conflicting
+                            // tx may replace pending ones if it outbids them (replace-by-fee)
+                            let conflict_fees = state.conflict_fees(&tx);
+                            let fee = crate::confirmed_input_fee(&state.bc.lock().unwrap(), &tx);
+                            let tx_size = bincode::encode_to_vec(&tx, *Astram_core::transaction::BINCODE_CONFIG)
+                                .map(|bytes| bytes.len())
+                                .unwrap_or(0);
                             
                             let now = chrono::Utc::now().timestamp();
                             
@@ -384,42 +363,38 @@ impl P2PService {
                                 return;
                             }
 
-                            let mut has_conflict = false;
-                            for pending_tx in &mempool.pending {
-                                for pending_inp in &pending_tx.inputs {
-                                    let pending_utxo =
-                                        format!("{}:{}", pending_inp.txid, pending_inp.vout);
-                                    if tx_utxos.contains(&pending_utxo) {
-                                        warn!(
-                                            "[WARN] Double-spend detected in P2P TX {}: UTXO {} already used by pending TX {}",
-                                            tx.txid, pending_utxo, pending_tx.txid
-                                        );
-                                        has_conflict = true;
-                                        break;
-                                    }
-                                }
-                                if has_conflict {
-                                    break;
-                                }
+                            // Same fee floor as /tx (inputs missing from the UTXO set count as no fee)
+                            let min_fee = mempool.min_relay_fee(tx_size);
+                            if fee < min_fee {
+                                warn!("[WARN] P2P TX {} rejected: fee {} below {}", tx.txid, fee, min_fee);
+                                return;
                             }
 
-                            if has_conflict {
-                                false
-                            } else {
-                                // Mark transaction as seen with timestamp
-                                mempool.seen_tx.insert(tx.txid.clone(), now);
+                            match mempool.replace_conflicting(&tx, fee, tx_size, &conflict_fees, now) {
+                                Err(e) => {
+                                    warn!("[WARN] P2P TX {} rejected: {}", tx.txid, e);
+                                    false
+                                }
+                                Ok(replaced) => {
+                                    if !replaced.is_empty() {
+                                        info!("[INFO] P2P TX {} replaces pending {:?} by fee", tx.txid, replaced);
+                                    }
+                                    // Mark transaction as seen with timestamp
+                                    mempool.seen_tx.insert(tx.txid.clone(), now);
 
-                                // Clean up old seen_tx entries (older than 1 hour)
-                                mempool.seen_tx.retain(|_, &mut timestamp| now - timestamp < 3600);
+                                    // Clean up old seen_tx entries (older than 1 hour)
+                                    mempool.seen_tx.retain(|_, &mut timestamp| now - timestamp < 3600);
 
-                                // Add to mempool
-                                mempool.pending.push(tx.clone());
-                                // Security: Enforce mempool limits after adding transaction
-                                mempool.enforce_mempool_limit();
-                                info!("[INFO] Mempool size: {} transactions", mempool.pending.len());
-                                info!("[P2P] ✅ TX handler: transaction added to mempool (total handler time {:?})", handler_start.elapsed());
+                                    // Add to mempool
+                                    mempool.pending.push(tx.clone());
+                                    state.notify_tx(&tx);
+                                    // Security: Enforce mempool limits after adding transaction
+                                    mempool.enforce_mempool_limit();
+                                    info!("[INFO] Mempool size: {} transactions", mempool.pending.len());
+                                    info!("[P2P] ✅ TX handler: transaction added to mempool (total handler time {:?})", handler_start.elapsed());
 
-                                true // Should relay to other peers
+                                    true // Should relay to other peers
+                                }
                             }
                         }
                         Ok(false) => {
@@ -449,122 +424,134 @@ impl P2PService {
         // getdata handler - send requested blocks/transactions
         let nh4 = node_handle.clone();
         let p2p_clone = p2p.clone();
-        p2p.set_on_getdata(move |peer_id, object_type, hashes| {
-            use crate::p2p::messages::InventoryType;
-            
+        p2p.set_on_getdata(move |peer_id, items| {
             let state = nh4.clone();
             let p2p_inner = p2p_clone.clone();
             
-            match object_type {
-                InventoryType::Block => {
-                    // Load and send requested blocks
-                    for hash_bytes in hashes {
-                        let hash_hex = hex::encode(&hash_bytes);
+            for item in items {
+                let hash_hex = hex::encode(&item.hash);
+                match item.object_type {
+                    InventoryType::Block | InventoryType::CompactBlock => {
                         // Try to load block from DB
-                        if let Ok(Some(block)) = state.bc.lock().unwrap().load_block(&hash_hex) {
-                            // Send block to peer
-                            let peer_id_clone = peer_id.clone();
-                            let p2p_for_send = p2p_inner.clone();
-                            tokio::spawn(async move {
-                                p2p_for_send.send_block_to_peer(&peer_id_clone, &block).await;
-                            });
+                        let loaded = state.bc.lock().unwrap().load_block(&hash_hex);
+                        match loaded {
+                            Ok(Some(block)) if item.object_type == InventoryType::CompactBlock => {
+                                let block = compact::compact_block(&block, rand::random());
+                                p2p_inner.send_to_peer(&peer_id, P2pMessage::CmpctBlock { block });
+                            }
+                            Ok(Some(block)) => {
+                                // Send block to peer
+                                let peer_id_clone = peer_id.clone();
+                                let p2p_for_send = p2p_inner.clone();
+                                tokio::spawn(async move {
+                                    p2p_for_send.send_block_to_peer(&peer_id_clone, &block).await;
+                                });
+                            }
+                            // Pruned bodies can't be served; the header still goes out
+                            // through GetHeaders, so the peer fetches the body elsewhere
+                            Err(e) if e.is::<BlockPruned>() => {
+                                info!("getdata from {}: {}", peer_id, e);
+                            }
+                            _ => {}
                         }
                     }
+                    InventoryType::Transaction => {
+                        // Serve from the mempool; confirmed transactions travel inside blocks
+                        let tx = state
+                            .mempool
+                            .lock()
+                            .unwrap()
+                            .pending
+                            .iter()
+                            .find(|tx| tx.txid == hash_hex)
+                            .cloned();
+                        if let Some(tx) = tx {
+                            p2p_inner.send_to_peer(&peer_id, P2pMessage::Tx { tx });
+                        }
+                    }
+                    InventoryType::Error => {
+                        // Ignore error type
+                    }
                 }
-                InventoryType::Transaction => {
-                    // TODO: Send transactions from mempool
+            }
+        });
+
+        // compact block matching - mempool transactions for a compact block's short ids
+        let nh6 = node_handle.clone();
+        p2p.set_on_compact_matches(move |compact_block| {
+            let mempool = nh6.mempool.lock().unwrap();
+            compact::mempool_matches(compact_block, &mempool.pending)
+        });
+
+        // getblocktxn handler - send the transactions a peer couldn't find for a compact block
+        let nh7 = node_handle.clone();
+        let p2p_for_blocktxn = p2p.clone();
+        p2p.set_on_getblocktxn(move |peer_id, hash, indexes| {
+            let loaded = nh7.bc.lock().unwrap().load_block(&hash);
+            let Ok(Some(block)) = loaded else {
+                info!("getblocktxn from {}: block {} not available", peer_id, hash);
+                return;
+            };
+            let txs: Option<Vec<_>> = indexes
+                .iter()
+                .map(|&index| block.transactions.get(index as usize).cloned())
+                .collect();
+            match txs {
+                Some(txs) => {
+                    p2p_for_blocktxn.send_to_peer(&peer_id, P2pMessage::BlockTxn { hash, txs })
                 }
-                InventoryType::Error => {
-                    // Ignore error type
+                None => warn!(
+                    "getblocktxn from {}: index out of range for block {}",
+                    peer_id, hash
+                ),
+            }
+        });
+
+        // inventory check - decides which announced objects get requested with GetData
+        let nh5 = node_handle.clone();
+        let chain_for_inv = chain_state.clone();
+        p2p.set_on_has_inv(move |item: &InvItem| {
+            let hash_hex = hex::encode(&item.hash);
+            match item.object_type {
+                InventoryType::Block | InventoryType::CompactBlock => {
+                    let bc = nh5.bc.lock().unwrap();
+                    matches!(bc.load_header(&hash_hex), Ok(Some(_)))
+                        || chain_for_inv.lock().unwrap().orphan_blocks.contains_key(&hash_hex)
                 }
+                InventoryType::Transaction => {
+                    nh5.mempool.lock().unwrap().seen_tx.contains_key(&hash_hex)
+                }
+                InventoryType::Error => true,
             }
         });
     }
 
-    /// Process orphan blocks that may now be valid
+    /// Connect the buffered orphans descending from `parent_hash`, just added, and do
+    /// the same follow-up for each as for a block received directly
     fn process_orphan_blocks(
         bc: &mut Astram_core::Blockchain,
         chain: &mut ChainState,
-        mempool: &std::sync::Mutex<crate::MempoolState>,
+        parent_hash: &str,
+        state: &crate::NodeHandles,
         p2p_handle: Arc<PeerManager>,
     ) {
-        let mut processed_any = true;
-        let max_iterations = 100; // Prevent infinite loops
-        let mut iterations = 0;
-
-        while processed_any && iterations < max_iterations {
-            processed_any = false;
-            iterations += 1;
-
-            // Find orphan blocks whose parent now exists
-            let orphans_to_try: Vec<_> = chain
-                .orphan_blocks
-                .iter()
-                .map(|(hash, (block, _))| (hash.clone(), block.clone()))
-                .collect();
-
-            for (hash, block) in orphans_to_try {
-                // Check if parent exists now
-                if let Ok(Some(_)) = bc.load_block(&block.header.previous_hash) {
-                    // Parent exists! Try to validate and insert
-                    match bc.validate_and_insert_block(&block) {
-                        Ok(_) => {
-                            info!(
-                                "[OK] Orphan block now valid: index={} hash={}",
-                                block.header.index, &hash[..16]
-                            );
-                            chain.blockchain.push(block.clone());
-                            chain.enforce_memory_limit(); // Security: Enforce memory limit
-                            chain.orphan_blocks.remove(&hash);
-                            processed_any = true;
-
-                            // Update P2P manager height
-                            p2p_handle.set_my_height(block.header.index + 1);
-
-                            // Remove transactions from mempool
-                            let block_txids: std::collections::HashSet<String> = block
-                                .transactions
-                                .iter()
-                                .map(|tx| tx.txid.clone())
-                                .collect();
-                            {
-                                let mut mempool = mempool.lock().unwrap();
-                                mempool.pending.retain(|tx| !block_txids.contains(&tx.txid));
-                            }
-
-                            // Check for reorganization
-                            let _ = bc.reorganize_if_needed(&hash);
-                        }
-                        Err(e) => {
-                            warn!(
-                                "[WARN] Orphan block still invalid: index={} hash={}, error: {:?}",
-                                block.header.index, &hash[..16], e
-                            );
-                            // Keep in orphan pool for now
-                        }
-                    }
-                }
-            }
+        // A bad orphan counts against the peer that sent it, like any other invalid block
+        let on_rejected = |peer: &str, e: &anyhow::Error, extends_tip: bool| {
+            p2p_handle.block_rejected(&peer.to_string(), e, extends_tip);
+        };
+        for block in crate::connect_orphans(bc, chain, parent_hash, on_rejected) {
+            // Update P2P manager height
+            p2p_handle.set_my_height(block.header.index + 1);
+            state.notify_block(&block);
+
+            // Remove transactions from mempool
+            state.mempool.lock().unwrap().remove_confirmed(&block);
+
+            // Check for reorganization
+            let _ = bc.reorganize_if_needed(&block.hash);
         }
 
-        // Clean up old orphan blocks (older than 1 hour)
-        let now = chrono::Utc::now().timestamp();
-        let one_hour = 3600;
-        chain.orphan_blocks.retain(|hash, (block, timestamp)| {
-            let age = now - *timestamp;
-            if age > one_hour {
-                info!(
-                    "[INFO] Removing old orphan block: index={} hash={} (age: {}s)",
-                    block.header.index,
-                    &hash[..16],
-                    age
-                );
-                false
-            } else {
-                true
-            }
-        });
-
+        chain.expire_orphans(chrono::Utc::now().timestamp());
         if !chain.orphan_blocks.is_empty() {
             info!("Orphan pool size: {}", chain.orphan_blocks.len());
         }
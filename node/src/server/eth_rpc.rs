@@ -1,18 +1,58 @@
 /// Ethereum-compatible JSON-RPC server for MetaMask integration
+use crate::NodeEvent;
 use crate::NodeHandle;
 use crate::NodeMeta;
 use crate::PeerManager;
-use Astram_core::transaction::{BINCODE_CONFIG, Transaction, TransactionInput, TransactionOutput};
+use crate::server::rate_limit::{RateLimiter, rate_limit, rate_limited, recover_rate_limited};
+use Astram_core::Blockchain;
+use Astram_core::block::Block;
+use Astram_core::config::{
+    GAS_PER_BYTE, GAS_PRICE, calculate_default_fee, calculate_min_fee, gas_for_size,
+};
+use Astram_core::sigcache::SIGNATURE_CACHE;
+use Astram_core::transaction::{BINCODE_CONFIG, Transaction, TransactionOutput};
+use Astram_core::txbuilder::{FeePolicy, build_transaction};
+use Astram_core::utxo::Utxo;
+use futures::{SinkExt, StreamExt};
 use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{Notify, mpsc};
+use tokio::task::JoinHandle;
+use warp::ws::{Message, WebSocket};
 use warp::{Filter, Reply};
 
+/// Security: cap on live eth_subscribe subscriptions per WebSocket connection
+const MAX_WS_SUBSCRIPTIONS: usize = 32;
+
+/// Security: frames queued for one WebSocket client; a client that lets this many
+/// notifications pile up is disconnected rather than buffered without bound
+const WS_OUTBOUND_QUEUE: usize = 256;
+
+/// Security: most calls accepted in one JSON-RPC batch
+const MAX_BATCH_SIZE: usize = 100;
+
+/// Security: widest block range a single eth_getLogs query may scan
+const MAX_LOG_BLOCK_RANGE: u64 = 1000;
+
+/// Security: most blocks a single eth_feeHistory query may cover
+const MAX_FEE_HISTORY_BLOCKS: u64 = 128;
+
+/// keccak256("Transfer(address,address,uint256)"), the ERC-20 Transfer event signature
+const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
 #[derive(Debug, Deserialize)]
 struct JsonRpcRequest {
     #[serde(rename = "jsonrpc")]
     _jsonrpc: String,
+    /// Missing for notifications, which get no response
+    #[serde(default)]
     id: Value,
     method: String,
     params: Option<Vec<Value>>,
@@ -60,16 +100,85 @@ impl JsonRpcResponse {
     }
 }
 
-/// Handle JSON-RPC requests
+/// Handle JSON-RPC requests over HTTP: a single call or a batch (JSON array).
+/// Batch responses keep request order; notifications (no `id`) get no entry, and a
+/// body made only of notifications gets 204 No Content. A batch costs the client one
+/// rate limit token per call.
 async fn handle_rpc(
+    body: Value,
+    remote: Option<SocketAddr>,
+    rate_limiter: std::sync::Arc<RateLimiter>,
+    node: NodeHandle,
+    p2p: std::sync::Arc<PeerManager>,
+    node_meta: std::sync::Arc<NodeMeta>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let response = match body {
+        Value::Array(calls) => {
+            if calls.is_empty() || calls.len() > MAX_BATCH_SIZE {
+                let message = format!(
+                    "Invalid Request: batch must hold 1-{} calls",
+                    MAX_BATCH_SIZE
+                );
+                let error = JsonRpcResponse::error(Value::Null, -32600, message);
+                return Ok(warp::reply::json(&error).into_response());
+            }
+            // The route took a token for the request; the batch pays for the other calls
+            let extra_calls = calls.len() as u32 - 1;
+            if remote.is_some_and(|addr| !rate_limiter.allow_n(addr.ip(), extra_calls)) {
+                return Err(rate_limited());
+            }
+            let mut responses = Vec::new();
+            for call in calls {
+                if let Some(response) =
+                    handle_call(call, node.clone(), p2p.clone(), node_meta.clone()).await
+                {
+                    responses.push(response);
+                }
+            }
+            (!responses.is_empty()).then(|| json!(responses))
+        }
+        call => handle_call(call, node, p2p, node_meta)
+            .await
+            .map(|response| json!(response)),
+    };
+
+    Ok(match response {
+        Some(response) => warp::reply::json(&response).into_response(),
+        None => warp::http::StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+/// One call of a (possibly batched) request; None for a notification
+async fn handle_call(
+    call: Value,
+    node: NodeHandle,
+    p2p: std::sync::Arc<PeerManager>,
+    node_meta: std::sync::Arc<NodeMeta>,
+) -> Option<JsonRpcResponse> {
+    let is_notification = call.get("id").is_none();
+    match serde_json::from_value::<JsonRpcRequest>(call) {
+        Ok(request) => {
+            let response = dispatch(request, node, p2p, node_meta).await;
+            (!is_notification).then_some(response)
+        }
+        Err(e) => Some(JsonRpcResponse::error(
+            Value::Null,
+            -32600,
+            format!("Invalid Request: {}", e),
+        )),
+    }
+}
+
+/// Route a JSON-RPC request to its method (shared by the HTTP and WebSocket transports)
+async fn dispatch(
     request: JsonRpcRequest,
     node: NodeHandle,
     p2p: std::sync::Arc<PeerManager>,
     node_meta: std::sync::Arc<NodeMeta>,
-) -> Result<impl Reply, warp::Rejection> {
+) -> JsonRpcResponse {
     log::info!("RPC method called: {}", request.method);
 
-    let response = match request.method.as_str() {
+    match request.method.as_str() {
         // Chain information
         "eth_chainId" => eth_chain_id(request.id),
         "net_version" => net_version(request.id),
@@ -92,17 +201,20 @@ async fn handle_rpc(
         "eth_getTransactionReceipt" => {
             eth_get_transaction_receipt(request.id, request.params, node).await
         }
+        "eth_getLogs" => eth_get_logs(request.id, request.params, node).await,
 
         // Block information
         "eth_getBlockByNumber" => eth_get_block_by_number(request.id, request.params, node).await,
         "eth_getBlockByHash" => eth_get_block_by_hash(request.id, request.params, node).await,
 
         // Gas
-        "eth_gasPrice" => eth_gas_price(request.id),
-        "eth_estimateGas" => eth_estimate_gas(request.id),
+        "eth_gasPrice" => eth_gas_price(request.id, node),
+        "eth_maxPriorityFeePerGas" => eth_max_priority_fee_per_gas(request.id),
+        "eth_feeHistory" => eth_fee_history(request.id, request.params, node).await,
+        "eth_estimateGas" => eth_estimate_gas(request.id, request.params, node).await,
 
         // Call & Code
-        "eth_call" => eth_call(request.id),
+        "eth_call" => eth_call(request.id, request.params, node).await,
         "eth_getCode" => eth_get_code(request.id),
 
         // Other
@@ -113,26 +225,235 @@ async fn handle_rpc(
             -32601,
             format!("Method '{}' not found", request.method),
         ),
-    };
+    }
+}
 
-    Ok(warp::reply::json(&response))
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SubscriptionKind {
+    NewHeads,
+    NewPendingTransactions,
 }
 
-// RPC Method implementations
+/// Queue a subscription notification for the writer. A full queue means the client
+/// isn't reading: `lagged` is signalled so the connection gets closed. False once the
+/// notification stream should stop.
+fn push_notification(out: &mpsc::Sender<String>, text: String, lagged: &Notify) -> bool {
+    match out.try_send(text) {
+        Ok(()) => true,
+        Err(mpsc::error::TrySendError::Full(_)) => {
+            lagged.notify_one();
+            false
+        }
+        Err(mpsc::error::TrySendError::Closed(_)) => false,
+    }
+}
 
-fn resolve_chain_id() -> u64 {
-    if let Ok(value) = std::env::var("ASTRAM_CHAIN_ID") {
-        if let Ok(parsed) = value.trim().parse::<u64>() {
-            return parsed;
+/// Serve one WebSocket connection: regular JSON-RPC calls plus eth_subscribe / eth_unsubscribe.
+/// All subscription tasks are aborted when the client disconnects, or when it falls
+/// WS_OUTBOUND_QUEUE frames behind on notifications.
+async fn handle_ws(
+    socket: WebSocket,
+    node: NodeHandle,
+    p2p: std::sync::Arc<PeerManager>,
+    node_meta: std::sync::Arc<NodeMeta>,
+) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+
+    // Responses and notifications funnel through one writer task
+    let (out_tx, mut out_rx) = mpsc::channel::<String>(WS_OUTBOUND_QUEUE);
+    let lagged = std::sync::Arc::new(Notify::new());
+    let writer = tokio::spawn(async move {
+        while let Some(text) = out_rx.recv().await {
+            if ws_tx.send(Message::text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscriptions: HashMap<String, JoinHandle<()>> = HashMap::new();
+
+    loop {
+        let result = tokio::select! {
+            result = ws_rx.next() => match result {
+                Some(result) => result,
+                None => break,
+            },
+            _ = lagged.notified() => {
+                log::debug!("eth_rpc websocket client fell behind on notifications, disconnecting");
+                break;
+            }
+        };
+        let msg = match result {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::debug!("eth_rpc websocket error: {}", e);
+                break;
+            }
+        };
+        if msg.is_close() {
+            break;
+        }
+        // Ping/pong and binary frames carry no JSON-RPC payload
+        let Ok(text) = msg.to_str() else {
+            continue;
+        };
+
+        let response = match serde_json::from_str::<JsonRpcRequest>(text) {
+            Ok(request) => match request.method.as_str() {
+                "eth_subscribe" => ws_subscribe(
+                    request.id,
+                    request.params,
+                    &node,
+                    &out_tx,
+                    &lagged,
+                    &mut subscriptions,
+                ),
+                "eth_unsubscribe" => ws_unsubscribe(request.id, request.params, &mut subscriptions),
+                _ => dispatch(request, node.clone(), p2p.clone(), node_meta.clone()).await,
+            },
+            Err(e) => JsonRpcResponse::error(Value::Null, -32700, format!("Parse error: {}", e)),
+        };
+
+        // A reply waits for queue space, which holds off reading the client's next call
+        match serde_json::to_string(&response) {
+            Ok(text) => {
+                if out_tx.send(text).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => log::warn!("Failed to serialize eth_rpc response: {}", e),
         }
     }
 
-    let network = std::env::var("ASTRAM_NETWORK").unwrap_or_else(|_| "mainnet".to_string());
-    if network.eq_ignore_ascii_case("testnet") {
-        8888
-    } else {
-        1
+    for (_, task) in subscriptions.drain() {
+        task.abort();
     }
+    writer.abort();
+}
+
+fn ws_subscribe(
+    id: Value,
+    params: Option<Vec<Value>>,
+    node: &NodeHandle,
+    out: &mpsc::Sender<String>,
+    lagged: &std::sync::Arc<Notify>,
+    subscriptions: &mut HashMap<String, JoinHandle<()>>,
+) -> JsonRpcResponse {
+    let kind = match params
+        .as_ref()
+        .and_then(|p| p.get(0))
+        .and_then(|v| v.as_str())
+    {
+        Some("newHeads") => SubscriptionKind::NewHeads,
+        Some("newPendingTransactions") => SubscriptionKind::NewPendingTransactions,
+        Some(other) => {
+            return JsonRpcResponse::error(
+                id,
+                -32602,
+                format!("Unsupported subscription type: {}", other),
+            );
+        }
+        None => {
+            return JsonRpcResponse::error(id, -32602, "Missing subscription type".to_string());
+        }
+    };
+
+    if subscriptions.len() >= MAX_WS_SUBSCRIPTIONS {
+        return JsonRpcResponse::error(
+            id,
+            -32005,
+            format!("Too many subscriptions (max {})", MAX_WS_SUBSCRIPTIONS),
+        );
+    }
+
+    let sub_id = format!(
+        "0x{:x}",
+        NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed)
+    );
+    let mut events = node.events.subscribe();
+    let out = out.clone();
+    let lagged = lagged.clone();
+    let task_sub_id = sub_id.clone();
+
+    let task = tokio::spawn(async move {
+        loop {
+            let result = match events.recv().await {
+                Ok(NodeEvent::NewBlock(block)) if kind == SubscriptionKind::NewHeads => {
+                    let mut head = eth_block_object(
+                        block.header.index,
+                        Some(&block.hash),
+                        &block.header.previous_hash,
+                        block.header.timestamp,
+                        &block.transactions,
+                        false,
+                    );
+                    // newHeads carries the header only
+                    if let Some(obj) = head.as_object_mut() {
+                        obj.remove("transactions");
+                        obj.remove("uncles");
+                    }
+                    head
+                }
+                Ok(NodeEvent::NewPendingTx(tx))
+                    if kind == SubscriptionKind::NewPendingTransactions =>
+                {
+                    json!(eth_tx_hash(&tx))
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!(
+                        "Subscription {} fell behind, skipped {} events",
+                        task_sub_id,
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": {
+                    "subscription": task_sub_id,
+                    "result": result,
+                }
+            });
+            if !push_notification(&out, notification.to_string(), &lagged) {
+                break;
+            }
+        }
+    });
+
+    subscriptions.insert(sub_id.clone(), task);
+    JsonRpcResponse::success(id, json!(sub_id))
+}
+
+fn ws_unsubscribe(
+    id: Value,
+    params: Option<Vec<Value>>,
+    subscriptions: &mut HashMap<String, JoinHandle<()>>,
+) -> JsonRpcResponse {
+    let removed = params
+        .as_ref()
+        .and_then(|p| p.get(0))
+        .and_then(|v| v.as_str())
+        .and_then(|sub_id| subscriptions.remove(sub_id));
+
+    match removed {
+        Some(task) => {
+            task.abort();
+            JsonRpcResponse::success(id, json!(true))
+        }
+        None => JsonRpcResponse::success(id, json!(false)),
+    }
+}
+
+// RPC Method implementations
+
+fn resolve_chain_id() -> u64 {
+    // Share the P2P resolution so a CHAIN_ID node setting applies to both
+    crate::p2p::manager::resolve_chain_id()
 }
 
 fn eth_chain_id(id: Value) -> JsonRpcResponse {
@@ -145,14 +466,15 @@ fn net_version(id: Value) -> JsonRpcResponse {
     JsonRpcResponse::success(id, json!(chain_id.to_string()))
 }
 
+/// Height of the chain tip, None before genesis. Every "latest" lookup goes through
+/// this so an empty chain and a genesis-only chain (tip 0) can't be confused.
+fn tip_height(bc: &Blockchain) -> Option<u64> {
+    bc.get_next_index().unwrap_or(0).checked_sub(1)
+}
+
 async fn eth_block_number(id: Value, node: NodeHandle) -> JsonRpcResponse {
-    let height = node
-        .bc
-        .lock()
-        .unwrap()
-        .get_all_blocks()
-        .map(|b| b.len())
-        .unwrap_or(0);
+    // Before genesis there is no block to number; 0x0 keeps wallets polling
+    let height = tip_height(&node.bc.lock().unwrap()).unwrap_or(0);
     JsonRpcResponse::success(id, json!(format!("0x{:x}", height)))
 }
 
@@ -199,12 +521,18 @@ async fn eth_get_transaction_count(
             // Keep 0x prefix - addresses are stored with 0x in DB
             let address = address.to_lowercase();
 
-            let count = node
+            let mut count = node
                 .bc
                 .lock()
                 .unwrap()
                 .get_address_transaction_count_from_db(&address)
-                .unwrap_or(0);
+                .unwrap_or(0) as u64;
+
+            // MetaMask asks for the "pending" count to pick the next nonce; without
+            // the mempool a second quick send would reuse the first one's nonce
+            if params.get(1).and_then(|v| v.as_str()) == Some("pending") {
+                count += node.mempool.lock().unwrap().pending_nonce_count(&address);
+            }
 
             return JsonRpcResponse::success(id, json!(format!("0x{:x}", count)));
         }
@@ -237,12 +565,7 @@ async fn eth_send_raw_transaction(
             };
 
             // Calculate Ethereum transaction hash (for MetaMask compatibility)
-            use tiny_keccak::{Hasher, Keccak};
-            let mut hasher = Keccak::v256();
-            hasher.update(&tx_bytes);
-            let mut eth_tx_hash = [0u8; 32];
-            hasher.finalize(&mut eth_tx_hash);
-            let eth_tx_hash_hex = hex::encode(&eth_tx_hash);
+            let eth_tx_hash_hex = hex::encode(keccak256(&tx_bytes));
 
             log::info!("Ethereum transaction hash: 0x{}", eth_tx_hash_hex);
 
@@ -267,6 +590,13 @@ async fn eth_send_raw_transaction(
                 eth_tx.nonce
             );
 
+            let sender = eth_tx
+                .from
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+
             // Convert Ethereum transaction to Astram UTXO transaction
             let astram_tx = match convert_eth_to_utxo_transaction(eth_tx, node.clone()).await {
                 Ok(tx) => tx,
@@ -287,11 +617,19 @@ async fn eth_send_raw_transaction(
             );
 
             // Verify signatures before taking mempool lock
-            if !astram_tx.verify_signatures().unwrap_or(false) {
+            if !SIGNATURE_CACHE.verify(&astram_tx).unwrap_or(false) {
                 log::error!("Transaction signature verification failed");
                 return JsonRpcResponse::error(id, -32000, "Invalid signature".to_string());
             }
 
+            // Conversion may pick UTXOs a pending tx already spends (e.g. a wallet's
+            // "speed up"); it then has to outbid that tx (replace-by-fee)
+            let conflict_fees = node.conflict_fees(&astram_tx);
+            let fee = crate::confirmed_input_fee(&node.bc.lock().unwrap(), &astram_tx);
+            let tx_size = bincode::encode_to_vec(&astram_tx, *BINCODE_CONFIG)
+                .map(|bytes| bytes.len())
+                .unwrap_or(0);
+
             // Add to mempool
             {
                 let mut mempool = node.mempool.lock().unwrap();
@@ -302,46 +640,59 @@ async fn eth_send_raw_transaction(
                     return JsonRpcResponse::success(id, json!(astram_tx.eth_hash));
                 }
 
-                // Security: Check for double-spending in mempool
-                let mut tx_utxos = std::collections::HashSet::new();
-                for inp in &astram_tx.inputs {
-                    tx_utxos.insert(format!("{}:{}", inp.txid, inp.vout));
+                let min_fee = mempool.min_relay_fee(tx_size);
+                if fee < min_fee {
+                    log::warn!(
+                        "eth_sendRawTransaction TX {} rejected: fee {} below {}",
+                        astram_tx.txid,
+                        fee,
+                        min_fee
+                    );
+                    return JsonRpcResponse::error(
+                        id,
+                        -32000,
+                        format!("fee too low: got {} ram, need {} ram", fee, min_fee),
+                    );
                 }
 
-                for pending_tx in &mempool.pending {
-                    for pending_inp in &pending_tx.inputs {
-                        let pending_utxo = format!("{}:{}", pending_inp.txid, pending_inp.vout);
-                        if tx_utxos.contains(&pending_utxo) {
-                            log::warn!(
-                                "Double-spend attempt via eth_sendRawTransaction: TX {} tries to use UTXO {} already used by pending TX {}",
-                                astram_tx.txid,
-                                pending_utxo,
-                                pending_tx.txid
-                            );
-                            return JsonRpcResponse::error(
-                                id,
-                                -32000,
-                                format!(
-                                    "Double-spend: UTXO {} already used in mempool",
-                                    pending_utxo
-                                ),
-                            );
-                        }
+                // Security: Check for double-spending in mempool
+                let now = chrono::Utc::now().timestamp();
+                match mempool.replace_conflicting(&astram_tx, fee, tx_size, &conflict_fees, now) {
+                    Ok(replaced) if !replaced.is_empty() => {
+                        log::info!(
+                            "eth_sendRawTransaction TX {} replaces pending {:?} by fee",
+                            astram_tx.txid,
+                            replaced
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(message) => {
+                        log::warn!(
+                            "eth_sendRawTransaction TX {} rejected: {}",
+                            astram_tx.txid,
+                            message
+                        );
+                        return JsonRpcResponse::error(id, -32000, message);
                     }
                 }
 
                 // Add to pending
-                let now = chrono::Utc::now().timestamp();
                 mempool.seen_tx.insert(astram_tx.txid.clone(), now);
                 mempool.pending.push(astram_tx.clone());
+                mempool.record_pending_nonce(&sender, &astram_tx.txid);
+                node.notify_tx(&astram_tx);
             }
 
-            // Store mapping: eth_hash -> txid
-            node_meta
-                .eth_to_astram_tx
-                .lock()
-                .unwrap()
-                .insert(astram_tx.eth_hash.clone(), astram_tx.txid.clone());
+            // Store mapping: eth_hash -> txid, dropping old ones the DB index now covers
+            let mapping_size = {
+                let bc = node.bc.lock().unwrap();
+                let mut mapping = node_meta.eth_to_astram_tx.lock().unwrap();
+                mapping.insert(&astram_tx.eth_hash, &astram_tx.txid);
+                mapping.drop_settled(|txid| {
+                    crate::confirmed_at_depth(&bc, txid, crate::ETH_TX_MAPPING_DEPTH)
+                });
+                mapping.len()
+            };
 
             log::info!(
                 "[INFO] Stored mapping: ETH hash {} -> Astram txid {}",
@@ -349,10 +700,7 @@ async fn eth_send_raw_transaction(
                 astram_tx.txid
             );
             log::info!("[INFO] Transaction added to mempool: {}", astram_tx.txid);
-            log::info!(
-                "[INFO] Current mapping size: {}",
-                node_meta.eth_to_astram_tx.lock().unwrap().len()
-            );
+            log::info!("[INFO] Current mapping size: {}", mapping_size);
 
             // Broadcast to peers
             let p2p_clone = p2p.clone();
@@ -375,10 +723,18 @@ async fn eth_send_raw_transaction(
     JsonRpcResponse::error(id, -32602, "Invalid params".to_string())
 }
 
+/// Base fee assumed for EIP-1559 transactions; Astram has no fee market, so this is
+/// the GAS_PRICE eth_gasPrice quotes at the default relay floor
+const BASE_FEE_PER_GAS: U256 = GAS_PRICE;
+
+/// EIP-2718 type byte of EIP-1559 (dynamic fee) transactions
+const EIP1559_TX_TYPE: u8 = 0x02;
+
 /// Ethereum transaction structure (simplified)
 #[derive(Debug)]
 struct EthereumTransaction {
     nonce: u64,
+    /// Effective price per gas (for type-2: min(maxFeePerGas, baseFee + maxPriorityFeePerGas))
     gas_price: U256,
     gas_limit: u64,
     to: String,
@@ -390,8 +746,124 @@ struct EthereumTransaction {
     from: String, // Recovered from signature
 }
 
-/// Decode Ethereum RLP transaction
+/// A big-endian RLP integer field as U256; longer than 32 bytes is an error rather
+/// than a panic in `U256::from_big_endian`
+fn u256_field(bytes: &[u8], name: &str) -> Result<U256, String> {
+    if bytes.len() > 32 {
+        return Err(format!("{}: {} bytes exceeds 32", name, bytes.len()));
+    }
+    Ok(U256::from_big_endian(bytes))
+}
+
+/// Decode an Ethereum raw transaction, routing on the first byte: an RLP list prefix
+/// (>= 0xc0) is a legacy transaction, 0x02 an EIP-1559 typed envelope
 fn decode_ethereum_transaction(tx_bytes: &[u8]) -> Result<EthereumTransaction, String> {
+    match tx_bytes.first() {
+        Some(&EIP1559_TX_TYPE) => decode_eip1559_transaction(&tx_bytes[1..]),
+        Some(&b) if b >= 0xc0 => decode_legacy_transaction(tx_bytes),
+        Some(&b) => Err(format!("unsupported transaction type 0x{:02x}", b)),
+        None => Err("empty transaction".to_string()),
+    }
+}
+
+/// Decode a type-2 payload: [chainId, nonce, maxPriorityFeePerGas, maxFeePerGas,
+/// gasLimit, to, value, data, accessList, yParity, r, s]
+fn decode_eip1559_transaction(payload: &[u8]) -> Result<EthereumTransaction, String> {
+    use rlp::{Rlp, RlpStream};
+
+    let rlp = Rlp::new(payload);
+    let item_count = rlp.item_count().map_err(|e| format!("payload: {}", e))?;
+    if item_count != 12 {
+        return Err(format!("expected 12 fields, got {}", item_count));
+    }
+
+    let bytes_at = |i: usize, name: &str| -> Result<Vec<u8>, String> {
+        rlp.at(i)
+            .and_then(|item| item.data().map(|d| d.to_vec()))
+            .map_err(|e| format!("{}: {}", name, e))
+    };
+    let u64_at = |i: usize, name: &str| -> Result<u64, String> {
+        rlp.at(i)
+            .and_then(|item| item.as_val())
+            .map_err(|e| format!("{}: {}", name, e))
+    };
+
+    let chain_id = u64_at(0, "chain_id")?;
+    let nonce = u64_at(1, "nonce")?;
+    let max_priority_fee = u256_field(
+        &bytes_at(2, "max_priority_fee_per_gas")?,
+        "max_priority_fee_per_gas",
+    )?;
+    let max_fee = u256_field(&bytes_at(3, "max_fee_per_gas")?, "max_fee_per_gas")?;
+    let gas_limit = u64_at(4, "gas_limit")?;
+    let to_bytes = bytes_at(5, "to")?;
+    let value = u256_field(&bytes_at(6, "value")?, "value")?;
+    let data = bytes_at(7, "data")?;
+    let access_list = rlp.at(8).map_err(|e| format!("access_list: {}", e))?;
+    if !access_list.is_list() {
+        return Err("access_list: expected a list".to_string());
+    }
+    let y_parity = u64_at(9, "y_parity")?;
+    let r = bytes_at(10, "r")?;
+    let s = bytes_at(11, "s")?;
+
+    if y_parity > 1 {
+        return Err(format!("invalid y_parity {}", y_parity));
+    }
+    if chain_id != resolve_chain_id() {
+        return Err(format!(
+            "chain id {} does not match this network ({})",
+            chain_id,
+            resolve_chain_id()
+        ));
+    }
+
+    let gas_price = max_fee.min(BASE_FEE_PER_GAS.saturating_add(max_priority_fee));
+
+    // Signing hash: keccak256(0x02 || rlp(first 9 fields))
+    let mut stream = RlpStream::new_list(9);
+    for i in 0..8 {
+        stream.append_raw(rlp.at(i).map_err(|e| e.to_string())?.as_raw(), 1);
+    }
+    stream.append_raw(access_list.as_raw(), 1);
+    let mut preimage = vec![EIP1559_TX_TYPE];
+    preimage.extend_from_slice(&stream.out());
+
+    let (from, pubkey) = recover_signer(
+        &keccak256(&preimage),
+        y_parity as i32,
+        &pad_to_32_bytes(&r),
+        &pad_to_32_bytes(&s),
+    )
+    .map_err(|e| format!("sender recovery: {}", e))?;
+
+    log::info!(
+        "Recovered type-2 sender: {} (chain_id={}, effective gas price={})",
+        from,
+        chain_id,
+        gas_price
+    );
+
+    Ok(EthereumTransaction {
+        nonce,
+        gas_price,
+        gas_limit,
+        to: if to_bytes.is_empty() {
+            String::new()
+        } else {
+            format!("0x{}", hex::encode(&to_bytes))
+        },
+        value,
+        _data: data,
+        v: y_parity,
+        r,
+        s,
+        from: format!("{};{}", from, pubkey),
+    })
+}
+
+/// Decode a legacy RLP transaction
+fn decode_legacy_transaction(tx_bytes: &[u8]) -> Result<EthereumTransaction, String> {
     use rlp::Rlp;
 
     let rlp = Rlp::new(tx_bytes);
@@ -409,7 +881,7 @@ fn decode_ethereum_transaction(tx_bytes: &[u8]) -> Result<EthereumTransaction, S
         .data()
         .map_err(|e| format!("gas_price data: {}", e))?
         .to_vec();
-    let gas_price = U256::from_big_endian(&gas_price_bytes);
+    let gas_price = u256_field(&gas_price_bytes, "gas_price")?;
 
     let gas_limit: u64 = rlp
         .at(2)
@@ -435,7 +907,7 @@ fn decode_ethereum_transaction(tx_bytes: &[u8]) -> Result<EthereumTransaction, S
         .data()
         .map_err(|e| format!("value data: {}", e))?
         .to_vec();
-    let value = U256::from_big_endian(&value_bytes);
+    let value = u256_field(&value_bytes, "value")?;
 
     let data: Vec<u8> = rlp
         .at(5)
@@ -522,8 +994,6 @@ fn recover_sender_address_eip155(
     s: &[u8],
 ) -> Result<(String, String), String> {
     use rlp::RlpStream;
-    use secp256k1::{Message, Secp256k1, ecdsa::RecoverableSignature};
-    use tiny_keccak::{Hasher, Keccak};
 
     // Calculate chain_id from v (EIP-155)
     let chain_id = if v >= 35 {
@@ -550,25 +1020,48 @@ fn recover_sender_address_eip155(
         stream.append(&0u8);
     }
 
-    let unsigned_tx = stream.out();
+    let tx_hash = keccak256(&stream.out());
+
+    let recovery_id = if v >= 35 {
+        ((v - 35) % 2) as i32
+    } else {
+        v.checked_sub(27).ok_or("Invalid v")? as i32
+    };
+
+    let (address, pubkey_hex) = recover_signer(&tx_hash, recovery_id, r, s)?;
+    log::info!(
+        "Recovered sender: {} (chain_id={}, v={})",
+        address,
+        chain_id,
+        v
+    );
+    Ok((address, pubkey_hex))
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    use tiny_keccak::{Hasher, Keccak};
 
-    // Hash the unsigned transaction
     let mut hasher = Keccak::v256();
-    hasher.update(&unsigned_tx);
-    let mut tx_hash = [0u8; 32];
-    hasher.finalize(&mut tx_hash);
+    hasher.update(data);
+    let mut hash = [0u8; 32];
+    hasher.finalize(&mut hash);
+    hash
+}
+
+/// Recover the signer's address and uncompressed public key (hex) from a signing hash
+fn recover_signer(
+    tx_hash: &[u8; 32],
+    recovery_id: i32,
+    r: &[u8],
+    s: &[u8],
+) -> Result<(String, String), String> {
+    use secp256k1::{Message, Secp256k1, ecdsa::RecoverableSignature};
 
     // Parse signature
     if r.len() != 32 || s.len() != 32 {
         return Err("Invalid signature length".to_string());
     }
 
-    let recovery_id = if v >= 35 {
-        ((v - 35) % 2) as i32
-    } else {
-        (v - 27) as i32
-    };
-
     let mut sig_data = [0u8; 64];
     sig_data[..32].copy_from_slice(r);
     sig_data[32..].copy_from_slice(s);
@@ -581,7 +1074,7 @@ fn recover_sender_address_eip155(
         .map_err(|e| format!("Invalid signature: {}", e))?;
 
     let message =
-        Message::from_digest_slice(&tx_hash).map_err(|e| format!("Invalid message: {}", e))?;
+        Message::from_digest_slice(tx_hash).map_err(|e| format!("Invalid message: {}", e))?;
 
     let public_key = secp
         .recover_ecdsa(&message, &recoverable_sig)
@@ -589,21 +1082,9 @@ fn recover_sender_address_eip155(
 
     // Convert to Ethereum address
     let public_key_bytes = public_key.serialize_uncompressed();
-
-    let mut hasher = Keccak::v256();
-    hasher.update(&public_key_bytes[1..]); // Skip 0x04
-    let mut pub_hash = [0u8; 32];
-    hasher.finalize(&mut pub_hash);
-
+    let pub_hash = keccak256(&public_key_bytes[1..]); // Skip 0x04
     let address = format!("0x{}", hex::encode(&pub_hash[12..]));
 
-    log::info!(
-        "Recovered sender: {} (chain_id={}, v={})",
-        address,
-        chain_id,
-        v
-    );
-
     // Return both address and public key hex
     let pubkey_hex = hex::encode(public_key.serialize_uncompressed());
     Ok((address, pubkey_hex))
@@ -637,21 +1118,17 @@ async fn convert_eth_to_utxo_transaction(
         to_addr
     );
 
-    // Get UTXOs for sender
-    let utxos = node
-        .bc
-        .lock()
-        .unwrap()
-        .get_utxos(&from_addr)
-        .map_err(|e| format!("Failed to get UTXOs: {}", e))?;
-
+    let utxos = spendable_utxos(&node, &from_addr)?;
     if utxos.is_empty() {
         return Err(format!("No UTXOs found for address {}", from_addr));
     }
 
     // Use fee from Ethereum gas parameters (MetaMask already calculated this)
     // MetaMask sends: gasPrice (in ram/gas) × gasLimit (in gas units)
-    let fee_from_eth = eth_tx.gas_price * U256::from(eth_tx.gas_limit);
+    let fee_from_eth = eth_tx
+        .gas_price
+        .checked_mul(U256::from(eth_tx.gas_limit))
+        .ok_or_else(|| "Fee (gasPrice x gasLimit) overflows".to_string())?;
 
     log::info!(
         "ETH transaction fee: {} ram (gasPrice={}, gasLimit={})",
@@ -660,29 +1137,7 @@ async fn convert_eth_to_utxo_transaction(
         eth_tx.gas_limit
     );
 
-    let total_needed = amount + fee_from_eth;
-
-    let mut selected_utxos = Vec::new();
-    let mut total_input = U256::zero();
-
-    for utxo in utxos {
-        selected_utxos.push(utxo.clone());
-        total_input = total_input + utxo.amount();
-
-        if total_input >= total_needed {
-            break;
-        }
-    }
-
-    if total_input < total_needed {
-        return Err(format!(
-            "Insufficient funds: have {}, need {} (amount {} + fee {})",
-            total_input, total_needed, amount, fee_from_eth
-        ));
-    }
-
-    // Create inputs with Ethereum signature
-    // We create a special signature format: eth_sig:v:r:s
+    // Inputs carry the Ethereum signature in a special format: eth_sig:v:r:s
     // This will be validated differently for Ethereum-originated transactions
     let eth_sig = format!(
         "eth_sig:{}:{}:{}",
@@ -691,82 +1146,81 @@ async fn convert_eth_to_utxo_transaction(
         hex::encode(&eth_tx.s)
     );
 
-    let inputs: Vec<TransactionInput> = selected_utxos
-        .iter()
-        .map(|utxo| TransactionInput {
-            txid: utxo.txid.clone(),
-            vout: utxo.vout,
-            pubkey: pubkey_hex.clone(), // Keep original format for verify_signatures()
-            signature: Some(eth_sig.clone()),
-        })
-        .collect();
-
-    // Create outputs (temporary, will recalculate after measuring actual tx size)
-    let mut outputs = vec![TransactionOutput::new(to_addr.clone(), amount)];
-
-    // Add temporary change output
-    let temp_change = total_input - amount - fee_from_eth;
-    if temp_change > U256::zero() {
-        outputs.push(TransactionOutput::new(from_addr.clone(), temp_change));
-    }
-
-    // Create transaction to measure actual size
-    let mut tx = Transaction {
-        txid: String::new(),
-        eth_hash: String::new(),
-        inputs,
+    let outputs = vec![TransactionOutput::new(to_addr.clone(), amount)];
+    let tx = build_transaction(
+        &utxos,
         outputs,
-        timestamp: chrono::Utc::now().timestamp(),
-    };
-
-    tx = tx.with_hashes();
-
-    // Calculate actual transaction size in bytes using bincode v2
-    let tx_bytes = bincode::encode_to_vec(&tx, *BINCODE_CONFIG)
-        .map_err(|e| format!("Failed to serialize transaction: {}", e))?;
-    let actual_tx_size = tx_bytes.len();
-
-    // Calculate minimum required fee based on actual size
-    let min_fee_required = Astram_core::config::calculate_min_fee(actual_tx_size);
+        &FeePolicy::Fixed(fee_from_eth),
+        &from_addr,
+        |tx| Ok(with_eth_signature(tx, &pubkey_hex, &eth_sig)),
+    )
+    .map_err(|e| e.to_string())?;
 
     log::info!(
-        "Transaction size: {} bytes, ETH fee: {} ram, Astram min required: {} ram",
-        actual_tx_size,
+        "Created UTXO tx: {} inputs, {} outputs, fee={} ram, txid={}",
+        tx.inputs.len(),
+        tx.outputs.len(),
         fee_from_eth,
-        min_fee_required
+        tx.txid
     );
 
-    // Verify fee is sufficient
-    if fee_from_eth < min_fee_required {
-        return Err(format!(
-            "Insufficient fee: provided {} ram, but need {} ram (base 100 Twei + {} bytes × 200 Gwei/byte)",
-            fee_from_eth, min_fee_required, actual_tx_size
-        ));
-    }
+    Ok(tx)
+}
 
-    // Recalculate change with actual fee
-    let final_change = total_input - amount - fee_from_eth;
+/// UTXOs `address` can spend now (coinbase outputs that aren't mature yet can't be)
+fn spendable_utxos(node: &NodeHandle, address: &str) -> Result<Vec<Utxo>, String> {
+    let bc = node.bc.lock().unwrap();
+    let next_height = bc.get_next_index().unwrap_or(0);
+    Ok(bc
+        .get_utxos(address)
+        .map_err(|e| format!("Failed to get UTXOs: {}", e))?
+        .into_iter()
+        .filter(|u| u.is_mature_at(next_height))
+        .collect())
+}
 
-    // Recreate outputs with correct change
-    let mut final_outputs = vec![TransactionOutput::new(to_addr.clone(), amount)];
-    if final_change > U256::zero() {
-        final_outputs.push(TransactionOutput::new(from_addr.clone(), final_change));
+/// Every input carries the sender's pubkey (kept in the original format for
+/// verify_signatures()) and its `eth_sig:v:r:s` signature
+fn with_eth_signature(mut tx: Transaction, pubkey_hex: &str, eth_sig: &str) -> Transaction {
+    for input in &mut tx.inputs {
+        input.pubkey = pubkey_hex.to_string();
+        input.signature = Some(eth_sig.to_string());
     }
+    tx.with_hashes()
+}
 
-    // Recreate transaction with final outputs
-    tx.outputs = final_outputs;
-    tx = tx.with_hashes();
-
-    log::info!(
-        "Created UTXO tx: {} inputs, {} outputs, {} bytes, fee={} ram, txid={}",
-        tx.inputs.len(),
-        tx.outputs.len(),
-        actual_tx_size,
-        fee_from_eth,
-        tx.txid
+/// Gas to send `value` to `to`: `gas_for_size` of the transaction
+/// convert_eth_to_utxo_transaction would build from `utxos`, signed with the widest
+/// possible Ethereum signature. At `gas_price` (the quoted_gas_price) this covers the
+/// relay minimum for a transaction at least as large as the real one, so paying it
+/// always clears the byte-fee check.
+fn estimate_transfer_gas(
+    utxos: &[Utxo],
+    from: &str,
+    to: &str,
+    value: U256,
+    gas_price: U256,
+) -> Result<u64, String> {
+    let widest_sig = format!(
+        "eth_sig:{}:{}:{}",
+        u64::MAX,
+        "ff".repeat(32),
+        "ff".repeat(32)
     );
-
-    Ok(tx)
+    let pubkey_hex = "04".repeat(65);
+    let outputs = vec![TransactionOutput::new(to.to_string(), value)];
+    let tx = build_transaction(
+        utxos,
+        outputs,
+        &FeePolicy::BySize(&|size| gas_price * U256::from(gas_for_size(size))),
+        from,
+        |tx| Ok(with_eth_signature(tx, &pubkey_hex, &widest_sig)),
+    )
+    .map_err(|e| e.to_string())?;
+    let size = bincode::encode_to_vec(&tx, *BINCODE_CONFIG)
+        .map_err(|e| format!("Failed to serialize transaction: {}", e))?
+        .len();
+    Ok(gas_for_size(size))
 }
 
 async fn eth_get_transaction_by_hash(
@@ -780,16 +1234,13 @@ async fn eth_get_transaction_by_hash(
             let tx_hash = tx_hash.strip_prefix("0x").unwrap_or(tx_hash);
 
             // Try to resolve Ethereum tx hash to Astram txid
+            let bc = node.bc.lock().unwrap();
             let astram_txid = {
                 let mapping = node_meta.eth_to_astram_tx.lock().unwrap();
-                mapping
-                    .get(tx_hash)
-                    .cloned()
+                crate::resolve_eth_hash(&bc, &mapping, tx_hash)
                     .unwrap_or_else(|| tx_hash.to_string())
             };
-            if let Ok(Some((tx, block_height))) =
-                node.bc.lock().unwrap().get_transaction(&astram_txid)
-            {
+            if let Ok(Some((tx, block_height))) = bc.get_transaction(&astram_txid) {
                 // ram and wei are now the same (both 10^18 decimals)
                 let amount = tx
                     .outputs
@@ -844,19 +1295,21 @@ async fn eth_get_transaction_receipt(
                         tx.txid
                     );
 
-                    // Get block hash
-                    let block_hash = match bc.get_all_blocks() {
-                        Ok(blocks) => {
-                            if let Some(block) = blocks.get(block_height) {
-                                format!("0x{}", block.hash)
-                            } else {
-                                "0x0000000000000000000000000000000000000000000000000000000000000000"
-                                    .to_string()
-                            }
-                        }
-                        Err(_) => {
-                            "0x0000000000000000000000000000000000000000000000000000000000000000"
-                                .to_string()
+                    // Block hash from the height index, which survives pruning
+                    let block_hash = match bc.main_chain_hash(block_height as u64) {
+                        Ok(Some(hash)) => format!("0x{}", hash),
+                        Ok(None) => return JsonRpcResponse::success(id, json!(null)),
+                        Err(e) => {
+                            log::error!(
+                                "[ERROR] Block hash lookup at {} failed: {}",
+                                block_height,
+                                e
+                            );
+                            return JsonRpcResponse::error(
+                                id,
+                                -32000,
+                                "failed to load block hash".to_string(),
+                            );
                         }
                     };
 
@@ -919,32 +1372,459 @@ async fn eth_get_transaction_receipt(
     JsonRpcResponse::success(id, json!(null))
 }
 
-fn eth_gas_price(id: Value) -> JsonRpcResponse {
-    // Astram fee structure (EVM-compatible 18 decimals):
-    // - Base fee: 100,000,000,000,000 ram (100 Twei = 0.0001 ASRM)
-    // - Per-byte fee: 200,000,000,000 ram/byte (200 Gwei/byte)
-    // - Typical UTXO tx size: ~300-1000 bytes
-    // - Total typical fee: ~160,000,000,000,000-300,000,000,000,000 ram
-    //
-    // For Ethereum compatibility:
-    // - Standard transfer gas: 21,000
-    // - Target fee for 300 byte tx: ~160,000,000,000,000 ram
-    // - Required gasPrice: 160,000,000,000,000 / 21,000 = ~7,619,047,619 ram/gas
-    // - Use 10,000,000,000 (10 Gwei) for safety margin
-    //
-    // This gives: 21,000 × 10,000,000,000 = 210,000,000,000,000 ram (~0.00021 ASRM)
-    // Hex: 10,000,000,000 = 0x2540BE400
-    JsonRpcResponse::success(id, json!("0x2540be400")) // 10 Gwei (10,000,000,000 ram/gas)
-}
-
-fn eth_estimate_gas(id: Value) -> JsonRpcResponse {
-    // Astram UTXO transactions are larger than Ethereum account model
-    // Typical UTXO tx size: 300-1000 bytes
-    // Required fee for 1000 byte tx: 100 Twei + (1000 × 200 Gwei) = 300 Twei
-    // With gasPrice of 10 Gwei: 300 Twei ÷ 10 Gwei = 30,000 gas
-    // Use 50,000 gas for safety margin (covers up to 1450 byte transactions)
-    // Total fee: 50,000 × 10 Gwei = 500 Twei (0.0005 ASRM)
-    JsonRpcResponse::success(id, json!("0xc350")) // 50,000 gas (UTXO transaction)
+/// eth_getLogs filter object (`fromBlock`, `toBlock`, `blockHash`, `address`, `topics`)
+struct LogFilter {
+    from_block: u64,
+    to_block: u64,
+    /// Lowercased addresses; empty matches every address
+    addresses: Vec<String>,
+    /// Per-position alternatives; None (or a missing position) matches anything
+    topics: Vec<Option<Vec<String>>>,
+}
+
+/// One string or an array of strings, lowercased (`address` and each `topics` entry)
+fn string_or_list(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::String(s) => Some(vec![s.to_lowercase()]),
+        Value::Array(items) => Some(
+            items
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_lowercase()))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+impl LogFilter {
+    /// Resolve block tags against `tip` ("latest"/"pending" = tip, "earliest" = 0)
+    fn parse(filter: &Value, tip: u64) -> Result<Self, String> {
+        let block_number = |key: &str| -> Result<u64, String> {
+            match filter.get(key).and_then(|v| v.as_str()) {
+                None | Some("latest") | Some("pending") | Some("safe") | Some("finalized") => {
+                    Ok(tip)
+                }
+                Some("earliest") => Ok(0),
+                Some(hex) => u64::from_str_radix(hex.strip_prefix("0x").unwrap_or(hex), 16)
+                    .map_err(|_| format!("invalid {}: {}", key, hex)),
+            }
+        };
+        let from_block = block_number("fromBlock")?;
+        let to_block = block_number("toBlock")?;
+        if from_block > to_block {
+            return Err("fromBlock is after toBlock".to_string());
+        }
+        if to_block - from_block >= MAX_LOG_BLOCK_RANGE {
+            return Err(format!(
+                "query exceeds max block range {}",
+                MAX_LOG_BLOCK_RANGE
+            ));
+        }
+
+        let addresses = filter
+            .get("address")
+            .and_then(string_or_list)
+            .unwrap_or_default();
+        let topics = filter
+            .get("topics")
+            .and_then(|v| v.as_array())
+            .map(|positions| positions.iter().map(string_or_list).collect())
+            .unwrap_or_default();
+
+        Ok(LogFilter {
+            from_block,
+            to_block,
+            addresses,
+            topics,
+        })
+    }
+
+    fn matches(&self, address: &str, topics: &[String]) -> bool {
+        if !self.addresses.is_empty() && !self.addresses.contains(&address.to_lowercase()) {
+            return false;
+        }
+        self.topics
+            .iter()
+            .enumerate()
+            .all(|(i, wanted)| match wanted {
+                None => true,
+                Some(options) if options.is_empty() => true,
+                Some(options) => topics
+                    .get(i)
+                    .is_some_and(|topic| options.contains(&topic.to_lowercase())),
+            })
+    }
+}
+
+/// 20-byte address left-padded to a 32-byte topic
+fn address_topic(address: &str) -> String {
+    let hex = address.strip_prefix("0x").unwrap_or(address).to_lowercase();
+    format!("0x{:0>64}", hex)
+}
+
+/// Transfer-style logs for a block, one per output with the recipient as the log
+/// address (Astram has no EVM events; this keeps log-reading libraries working)
+fn block_logs(block: &Block, filter: &LogFilter) -> Vec<Value> {
+    let mut logs = Vec::new();
+    let mut log_index = 0u64;
+    for (tx_index, tx) in block.transactions.iter().enumerate() {
+        let from = eth_tx_sender(tx);
+        for output in &tx.outputs {
+            let topics = vec![
+                TRANSFER_TOPIC.to_string(),
+                address_topic(&from),
+                address_topic(&output.to),
+            ];
+            if filter.matches(&output.to, &topics) {
+                logs.push(json!({
+                    "address": output.to.to_lowercase(),
+                    "topics": topics,
+                    "data": format!("0x{:064x}", output.amount()),
+                    "blockNumber": format!("0x{:x}", block.header.index),
+                    "blockHash": format!("0x{}", block.hash),
+                    "transactionHash": eth_tx_hash(tx),
+                    "transactionIndex": format!("0x{:x}", tx_index),
+                    "logIndex": format!("0x{:x}", log_index),
+                    "removed": false,
+                }));
+            }
+            log_index += 1;
+        }
+    }
+    logs
+}
+
+async fn eth_get_logs(id: Value, params: Option<Vec<Value>>, node: NodeHandle) -> JsonRpcResponse {
+    let mut filter_obj = params
+        .and_then(|p| p.into_iter().next())
+        .unwrap_or_else(|| json!({}));
+
+    let bc = node.bc.lock().unwrap();
+    let Some(tip) = tip_height(&bc) else {
+        return JsonRpcResponse::success(id, json!([]));
+    };
+
+    // blockHash pins the query to one block and excludes fromBlock/toBlock
+    if let Some(hash) = filter_obj.get("blockHash").and_then(|v| v.as_str()) {
+        let hash = hash.strip_prefix("0x").unwrap_or(hash);
+        let height = match bc.main_chain_height(hash) {
+            Ok(Some(height)) => height,
+            _ => return JsonRpcResponse::error(id, -32000, "unknown block".to_string()),
+        };
+        filter_obj["fromBlock"] = json!(format!("0x{:x}", height));
+        filter_obj["toBlock"] = json!(format!("0x{:x}", height));
+    }
+
+    let filter = match LogFilter::parse(&filter_obj, tip) {
+        Ok(filter) => filter,
+        Err(e) => return JsonRpcResponse::error(id, -32602, e),
+    };
+    if filter.from_block > tip {
+        return JsonRpcResponse::success(id, json!([]));
+    }
+
+    match bc.get_blocks_range(filter.from_block, Some(filter.to_block.min(tip))) {
+        Ok(blocks) => {
+            let logs: Vec<Value> = blocks
+                .iter()
+                .flat_map(|block| block_logs(block, &filter))
+                .collect();
+            JsonRpcResponse::success(id, json!(logs))
+        }
+        Err(e) => {
+            log::error!("[ERROR] eth_getLogs failed: {}", e);
+            JsonRpcResponse::error(id, -32000, "failed to load blocks".to_string())
+        }
+    }
+}
+
+/// Price per gas for a relay floor of `min_relay_fee_rate` ram per byte. Gas is defined
+/// from the encoded size (see gas_for_size), so at the default floor this is GAS_PRICE
+/// and the estimated gas pays exactly the byte-based min fee; a node that raised its
+/// floor quotes proportionally more, so GAS_PER_BYTE gas still covers a byte.
+fn quoted_gas_price(min_relay_fee_rate: U256) -> U256 {
+    let per_byte = U256::from(GAS_PER_BYTE);
+    GAS_PRICE.max((min_relay_fee_rate + per_byte - 1) / per_byte)
+}
+
+fn eth_gas_price(id: Value, node: NodeHandle) -> JsonRpcResponse {
+    let gas_price = quoted_gas_price(node.mempool.lock().unwrap().min_relay_fee_rate());
+    JsonRpcResponse::success(id, json!(format!("0x{:x}", gas_price)))
+}
+
+/// Tip suggested on top of BASE_FEE_PER_GAS: what the CLI wallet's default fee pays
+/// per byte above calculate_min_fee, per gas (5 Gwei). A type-2 transaction paying
+/// BASE_FEE_PER_GAS plus this pays 1.5x calculate_min_fee for its size.
+fn priority_fee_per_gas() -> U256 {
+    (calculate_default_fee(1) - calculate_min_fee(1)) / U256::from(GAS_PER_BYTE)
+}
+
+fn eth_max_priority_fee_per_gas(id: Value) -> JsonRpcResponse {
+    JsonRpcResponse::success(id, json!(format!("0x{:x}", priority_fee_per_gas())))
+}
+
+/// eth_feeHistory(blockCount, newestBlock, rewardPercentiles). Astram has no fee
+/// market, so every base fee is BASE_FEE_PER_GAS and every reward the suggested
+/// priority fee; gasUsedRatio is each block's size against TARGET_BLOCK_TX_BYTES.
+async fn eth_fee_history(
+    id: Value,
+    params: Option<Vec<Value>>,
+    node: NodeHandle,
+) -> JsonRpcResponse {
+    let params = params.unwrap_or_default();
+    // Wallets send a hex quantity; some libraries a plain number
+    let block_count = match params.first() {
+        Some(Value::String(hex)) => u64::from_str_radix(hex.trim_start_matches("0x"), 16).ok(),
+        Some(value) => value.as_u64(),
+        None => None,
+    };
+    let Some(block_count) = block_count else {
+        return JsonRpcResponse::error(id, -32602, "invalid blockCount".to_string());
+    };
+    let percentiles = match reward_percentiles(params.get(2)) {
+        Ok(percentiles) => percentiles,
+        Err(e) => return JsonRpcResponse::error(id, -32602, e),
+    };
+
+    let bc = node.bc.lock().unwrap();
+    let tag = params.get(1).and_then(|v| v.as_str()).unwrap_or("latest");
+    let newest = match resolve_block_tag(&bc, tag) {
+        Some(BlockTag::Mined(number)) if Some(number) <= tip_height(&bc) => number,
+        // Nothing has been paid in a block that isn't mined yet
+        Some(BlockTag::Pending { number, .. }) => number - 1,
+        _ => return JsonRpcResponse::error(id, -32602, format!("unknown block {}", tag)),
+    };
+    let block_count = block_count.min(MAX_FEE_HISTORY_BLOCKS).min(newest + 1);
+    let oldest = newest + 1 - block_count;
+    // Sizes come straight from the stored blobs; no block is decoded under the lock
+    let block_bytes = if block_count == 0 {
+        Vec::new()
+    } else {
+        match bc.get_block_sizes_range(oldest, newest) {
+            Ok(sizes) => sizes,
+            Err(e) => {
+                log::error!("[ERROR] eth_feeHistory failed: {}", e);
+                return JsonRpcResponse::error(id, -32000, "failed to load blocks".to_string());
+            }
+        }
+    };
+    drop(bc);
+
+    JsonRpcResponse::success(id, fee_history(oldest, &block_bytes, &percentiles))
+}
+
+/// The optional rewardPercentiles argument: numbers from 0 to 100, non-decreasing
+fn reward_percentiles(value: Option<&Value>) -> Result<Vec<f64>, String> {
+    let values = match value {
+        None | Some(Value::Null) => return Ok(Vec::new()),
+        Some(Value::Array(values)) => values,
+        Some(_) => return Err("rewardPercentiles must be an array".to_string()),
+    };
+    let mut percentiles = Vec::with_capacity(values.len());
+    for value in values {
+        let percentile = value
+            .as_f64()
+            .filter(|p| (0.0..=100.0).contains(p))
+            .ok_or_else(|| format!("invalid reward percentile {}", value))?;
+        if percentiles.last().is_some_and(|last| percentile < *last) {
+            return Err("reward percentiles must be non-decreasing".to_string());
+        }
+        percentiles.push(percentile);
+    }
+    Ok(percentiles)
+}
+
+/// eth_feeHistory reply for the blocks from `oldest` whose encoded sizes are
+/// `block_bytes`. baseFeePerGas has one extra entry, for the next block.
+fn fee_history(oldest: u64, block_bytes: &[usize], percentiles: &[f64]) -> Value {
+    let base_fee = format!("0x{:x}", BASE_FEE_PER_GAS);
+    let tip = format!("0x{:x}", priority_fee_per_gas());
+
+    let gas_used_ratio: Vec<f64> = block_bytes
+        .iter()
+        .map(|&bytes| (bytes as f64 / crate::TARGET_BLOCK_TX_BYTES as f64).min(1.0))
+        .collect();
+    let mut reply = json!({
+        "oldestBlock": format!("0x{:x}", oldest),
+        "baseFeePerGas": vec![base_fee; block_bytes.len() + 1],
+        "gasUsedRatio": gas_used_ratio,
+    });
+    // Only present when percentiles were asked for, as in geth
+    if !percentiles.is_empty() {
+        reply["reward"] = json!(vec![vec![tip; percentiles.len()]; block_bytes.len()]);
+    }
+    reply
+}
+
+async fn eth_estimate_gas(
+    id: Value,
+    params: Option<Vec<Value>>,
+    node: NodeHandle,
+) -> JsonRpcResponse {
+    let call = params
+        .and_then(|p| p.into_iter().next())
+        .unwrap_or_else(|| json!({}));
+    let field = |key: &str| {
+        call.get(key)
+            .and_then(|v| v.as_str())
+            .map(str::to_lowercase)
+    };
+
+    let value = match field("value") {
+        Some(hex) => match U256::from_str_radix(hex.trim_start_matches("0x"), 16) {
+            Ok(value) => value,
+            Err(_) => return JsonRpcResponse::error(id, -32602, "invalid value".to_string()),
+        },
+        None => U256::zero(),
+    };
+    let to = field("to").unwrap_or_else(|| format!("0x{}", "00".repeat(20)));
+
+    // Price the inputs the sender would really spend; with no sender, one input
+    // large enough for any amount
+    let (from, utxos) = match field("from") {
+        Some(from) => match spendable_utxos(&node, &from) {
+            Ok(utxos) => (from, utxos),
+            Err(e) => return JsonRpcResponse::error(id, -32000, e),
+        },
+        None => {
+            let from = format!("0x{}", "00".repeat(20));
+            let utxo = Utxo::new("00".repeat(32), 0, from.clone(), U256::MAX, 0, false);
+            (from, vec![utxo])
+        }
+    };
+
+    let gas_price = quoted_gas_price(node.mempool.lock().unwrap().min_relay_fee_rate());
+    match estimate_transfer_gas(&utxos, &from, &to, value, gas_price) {
+        Ok(gas) => JsonRpcResponse::success(id, json!(format!("0x{:x}", gas))),
+        Err(e) => JsonRpcResponse::error(id, -32000, e),
+    }
+}
+
+/// Sender address for an Ethereum-style view of a UTXO transaction (zero address for coinbase)
+fn eth_tx_sender(tx: &Transaction) -> String {
+    tx.inputs
+        .get(0)
+        .map(|i| {
+            Astram_core::crypto::eth_address_from_pubkey_hex(&i.pubkey)
+                .unwrap_or_else(|_| i.pubkey.clone())
+        })
+        .unwrap_or_else(|| "0x0000000000000000000000000000000000000000".to_string())
+}
+
+fn eth_tx_hash(tx: &Transaction) -> String {
+    if tx.eth_hash.is_empty() {
+        format!("0x{}", tx.txid)
+    } else if tx.eth_hash.starts_with("0x") {
+        tx.eth_hash.clone()
+    } else {
+        format!("0x{}", tx.eth_hash)
+    }
+}
+
+/// Full transaction object as embedded in eth_getBlockBy* when the full_tx flag is set
+fn eth_tx_object(
+    tx: &Transaction,
+    block_hash: Option<&str>,
+    block_number: Option<u64>,
+    index: usize,
+) -> Value {
+    let amount = tx
+        .outputs
+        .get(0)
+        .map(|o| o.amount())
+        .unwrap_or_else(U256::zero);
+
+    json!({
+        "hash": eth_tx_hash(tx),
+        "nonce": "0x0",
+        "blockHash": block_hash.map(|h| format!("0x{}", h)),
+        "blockNumber": block_number.map(|n| format!("0x{:x}", n)),
+        "transactionIndex": block_number.map(|_| format!("0x{:x}", index)),
+        "from": eth_tx_sender(tx),
+        "to": tx.outputs.get(0).map(|o| o.to.clone()),
+        "value": format!("0x{:x}", amount),
+        "gasPrice": "0x2540be400", // 10 Gwei
+        "gas": "0x5208", // 21000 gas
+        "input": "0x",
+    })
+}
+
+/// Ethereum block object; `hash` is None for the synthesized pending block
+fn eth_block_object(
+    number: u64,
+    hash: Option<&str>,
+    parent_hash: &str,
+    timestamp: i64,
+    transactions: &[Transaction],
+    full_tx: bool,
+) -> Value {
+    let txs: Vec<Value> = if full_tx {
+        transactions
+            .iter()
+            .enumerate()
+            .map(|(i, tx)| eth_tx_object(tx, hash, hash.map(|_| number), i))
+            .collect()
+    } else {
+        transactions.iter().map(|tx| json!(eth_tx_hash(tx))).collect()
+    };
+
+    // Miner is the coinbase recipient; pending blocks have no coinbase yet
+    let miner = hash.and_then(|_| {
+        transactions
+            .get(0)
+            .and_then(|tx| tx.outputs.get(0))
+            .map(|o| o.to.clone())
+    });
+
+    json!({
+        "number": format!("0x{:x}", number),
+        "hash": hash.map(|h| format!("0x{}", h)),
+        "parentHash": format!("0x{}", parent_hash),
+        "nonce": hash.map(|_| "0x0000000000000000"),
+        "sha3Uncles": "0x0000000000000000000000000000000000000000000000000000000000000000",
+        "logsBloom": "0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        "transactionsRoot": "0x0000000000000000000000000000000000000000000000000000000000000000",
+        "stateRoot": "0x0000000000000000000000000000000000000000000000000000000000000000",
+        "receiptsRoot": "0x0000000000000000000000000000000000000000000000000000000000000000",
+        "miner": miner,
+        "difficulty": "0x1",
+        "totalDifficulty": format!("0x{:x}", number + 1),
+        "extraData": "0x",
+        "size": "0x400",
+        "gasLimit": "0x1fffffffffffff",
+        "gasUsed": "0x0",
+        "timestamp": format!("0x{:x}", timestamp),
+        "transactions": txs,
+        "uncles": []
+    })
+}
+
+/// What an eth_getBlockByNumber tag names
+#[derive(Debug, PartialEq)]
+enum BlockTag {
+    /// A main-chain height (which may not exist yet)
+    Mined(u64),
+    /// The candidate block the mempool would make on top of the tip
+    Pending { number: u64, parent_hash: String },
+}
+
+/// Resolve "latest", "earliest", "pending" or a hex height. None on an empty chain,
+/// where no tag names a block (not even "pending", which needs a parent), and for
+/// anything that isn't a tag or a height.
+fn resolve_block_tag(bc: &Blockchain, tag: &str) -> Option<BlockTag> {
+    let tip = tip_height(bc)?;
+    match tag {
+        "latest" => Some(BlockTag::Mined(tip)),
+        "earliest" => Some(BlockTag::Mined(0)),
+        "pending" => Some(BlockTag::Pending {
+            number: tip + 1,
+            parent_hash: bc.chain_tip.clone()?,
+        }),
+        _ => {
+            let num_str = tag.strip_prefix("0x").unwrap_or(tag);
+            u64::from_str_radix(num_str, 16).ok().map(BlockTag::Mined)
+        }
+    }
 }
 
 async fn eth_get_block_by_number(
@@ -954,53 +1834,54 @@ async fn eth_get_block_by_number(
 ) -> JsonRpcResponse {
     if let Some(params) = params {
         if let Some(block_param) = params.get(0).and_then(|v| v.as_str()) {
-            let bc = node.bc.lock().unwrap();
-
-            // Parse block number or handle "latest", "earliest", "pending"
-            let block_number = match block_param {
-                "latest" | "pending" => bc
-                    .get_all_blocks()
-                    .map(|b| b.len())
-                    .unwrap_or(0)
-                    .saturating_sub(1),
-                "earliest" => 0,
-                _ => {
-                    // Parse hex number
-                    let num_str = block_param.strip_prefix("0x").unwrap_or(block_param);
-                    u64::from_str_radix(num_str, 16).unwrap_or(0) as usize
-                }
-            };
-
             // Get full transaction details flag
-            let _full_tx = params.get(1).and_then(|v| v.as_bool()).unwrap_or(false);
+            let full_tx = params.get(1).and_then(|v| v.as_bool()).unwrap_or(false);
 
-            if let Ok(blocks) = bc.get_all_blocks() {
-                if let Some(block) = blocks.get(block_number) {
+            let bc = node.bc.lock().unwrap();
+            let block_number = match resolve_block_tag(&bc, block_param) {
+                Some(BlockTag::Mined(number)) => number,
+                Some(BlockTag::Pending {
+                    number,
+                    parent_hash,
+                }) => {
+                    drop(bc);
+                    let pending = node.mempool.lock().unwrap().pending.clone();
                     return JsonRpcResponse::success(
                         id,
-                        json!({
-                            "number": format!("0x{:x}", block_number),
-                            "hash": format!("0x{}", block.hash),
-                            "parentHash": format!("0x{}", block.header.previous_hash),
-                            "nonce": "0x0000000000000000",
-                            "sha3Uncles": "0x0000000000000000000000000000000000000000000000000000000000000000",
-                            "logsBloom": "0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
-                            "transactionsRoot": "0x0000000000000000000000000000000000000000000000000000000000000000",
-                            "stateRoot": "0x0000000000000000000000000000000000000000000000000000000000000000",
-                            "receiptsRoot": "0x0000000000000000000000000000000000000000000000000000000000000000",
-                            "miner": block.transactions.get(0).and_then(|tx| tx.outputs.get(0)).map(|o| &o.to).unwrap_or(&String::new()).clone(),
-                            "difficulty": "0x1",
-                            "totalDifficulty": format!("0x{:x}", block_number + 1),
-                            "extraData": "0x",
-                            "size": "0x400",
-                            "gasLimit": "0x1fffffffffffff",
-                            "gasUsed": "0x0",
-                            "timestamp": format!("0x{:x}", block.header.timestamp),
-                            "transactions": block.transactions.iter().map(|tx| format!("0x{}", tx.txid)).collect::<Vec<_>>(),
-                            "uncles": []
-                        }),
+                        eth_block_object(
+                            number,
+                            None,
+                            &parent_hash,
+                            chrono::Utc::now().timestamp(),
+                            &pending,
+                            full_tx,
+                        ),
                     );
                 }
+                // Nothing mined yet: a null result, not an error, as for an unknown height
+                None => return JsonRpcResponse::success(id, json!(null)),
+            };
+
+            let block = match bc.db.get(format!("i:{}", block_number).as_bytes()) {
+                Ok(Some(hash)) => bc
+                    .load_block(&String::from_utf8_lossy(&hash))
+                    .ok()
+                    .flatten(),
+                _ => None,
+            };
+
+            if let Some(block) = block {
+                return JsonRpcResponse::success(
+                    id,
+                    eth_block_object(
+                        block.header.index,
+                        Some(&block.hash),
+                        &block.header.previous_hash,
+                        block.header.timestamp,
+                        &block.transactions,
+                        full_tx,
+                    ),
+                );
             }
         }
     }
@@ -1016,28 +1897,20 @@ async fn eth_get_block_by_hash(
     if let Some(params) = params {
         if let Some(block_hash) = params.get(0).and_then(|v| v.as_str()) {
             let block_hash = block_hash.strip_prefix("0x").unwrap_or(block_hash);
-            let _full_tx = params.get(1).and_then(|v| v.as_bool()).unwrap_or(false);
+            let full_tx = params.get(1).and_then(|v| v.as_bool()).unwrap_or(false);
 
-            if let Ok(blocks) = node.bc.lock().unwrap().get_all_blocks() {
-                if let Some((block_number, block)) = blocks
-                    .iter()
-                    .enumerate()
-                    .find(|(_, b)| b.hash == block_hash)
-                {
-                    return JsonRpcResponse::success(
-                        id,
-                        json!({
-                            "number": format!("0x{:x}", block_number),
-                            "hash": format!("0x{}", block.hash),
-                            "parentHash": format!("0x{}", block.header.previous_hash),
-                            "timestamp": format!("0x{:x}", block.header.timestamp),
-                            "transactions": block.transactions.iter().map(|tx| format!("0x{}", tx.txid)).collect::<Vec<_>>(),
-                            "miner": block.transactions.get(0).and_then(|tx| tx.outputs.get(0)).map(|o| &o.to).unwrap_or(&String::new()).clone(),
-                            "gasLimit": "0x1fffffffffffff",
-                            "gasUsed": "0x0",
-                        }),
-                    );
-                }
+            if let Ok(Some(block)) = node.bc.lock().unwrap().load_block(block_hash) {
+                return JsonRpcResponse::success(
+                    id,
+                    eth_block_object(
+                        block.header.index,
+                        Some(&block.hash),
+                        &block.header.previous_hash,
+                        block.header.timestamp,
+                        &block.transactions,
+                        full_tx,
+                    ),
+                );
             }
         }
     }
@@ -1045,14 +1918,57 @@ async fn eth_get_block_by_hash(
     JsonRpcResponse::success(id, json!(null))
 }
 
-fn eth_call(id: Value) -> JsonRpcResponse {
-    // For UTXO-based blockchain, eth_call is not directly applicable
-    // Return empty result for contract calls
-    JsonRpcResponse::success(id, json!("0x"))
+/// 4-byte selector of ERC-20 `balanceOf(address)`
+const BALANCE_OF_SELECTOR: &str = "70a08231";
+
+/// There is no EVM: every call returns empty data (`0x`), never an error, except
+/// `balanceOf(address)`. Wallet UIs that list the native coin as an ERC-20 token call
+/// that on whatever contract address they were given, so it is answered with the
+/// queried address's UTXO balance, ABI-encoded as a uint256, whatever `to` is. This is
+/// a compatibility shim; nothing else about the call is interpreted.
+async fn eth_call(id: Value, params: Option<Vec<Value>>, node: NodeHandle) -> JsonRpcResponse {
+    let call = params
+        .and_then(|p| p.into_iter().next())
+        .unwrap_or_default();
+    // ethers v6 sends `input`, older libraries `data`
+    let data = call
+        .get("input")
+        .or_else(|| call.get("data"))
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    let Some(address) = balance_of_address(data) else {
+        return JsonRpcResponse::success(id, json!("0x"));
+    };
+    // Totalled from the address's `a:` index keys, with no full UTXO-set scan or decoding
+    let balance = node
+        .bc
+        .lock()
+        .unwrap()
+        .get_utxo_totals(&address)
+        .map(|(_, total)| total)
+        .unwrap_or_else(|_| U256::zero());
+    JsonRpcResponse::success(id, json!(format!("0x{:064x}", balance)))
+}
+
+/// The address a `balanceOf(address)` call asks about, lowercased with 0x, or None
+/// for any other calldata. The argument is one 32-byte word: 12 zero bytes and the
+/// 20-byte address.
+fn balance_of_address(data: &str) -> Option<String> {
+    let data = data.strip_prefix("0x").unwrap_or(data).to_lowercase();
+    let word = data.strip_prefix(BALANCE_OF_SELECTOR)?;
+    if word.len() != 64 || !word.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let (padding, address) = word.split_at(24);
+    if padding.bytes().any(|b| b != b'0') {
+        return None;
+    }
+    Some(format!("0x{}", address))
 }
 
 fn eth_get_code(id: Value) -> JsonRpcResponse {
-    // No smart contracts in UTXO model
+    // No smart contracts in UTXO model: every address reads as "no code"
     JsonRpcResponse::success(id, json!("0x"))
 }
 
@@ -1065,6 +1981,7 @@ pub fn eth_rpc_routes(
     node: NodeHandle,
     p2p: std::sync::Arc<PeerManager>,
     node_meta: std::sync::Arc<NodeMeta>,
+    rate_limiter: std::sync::Arc<RateLimiter>,
 ) -> impl Filter<Extract = impl Reply, Error = warp::Rejection> + Clone {
     let node_filter = warp::any().map(move || node.clone());
     let p2p_filter = warp::any().map(move || p2p.clone());
@@ -1076,13 +1993,38 @@ pub fn eth_rpc_routes(
         .allow_methods(vec!["GET", "POST", "OPTIONS"])
         .allow_headers(vec!["Content-Type", "Authorization"]);
 
-    warp::post()
+    // WebSocket transport (eth_subscribe) on the same address
+    let ws_route = warp::path::end()
+        .and(warp::ws())
+        .and(rate_limit(rate_limiter.clone()))
+        .and(node_filter.clone())
+        .and(p2p_filter.clone())
+        .and(meta_filter.clone())
+        .map(
+            |ws: warp::ws::Ws,
+             node: NodeHandle,
+             p2p: std::sync::Arc<PeerManager>,
+             node_meta: std::sync::Arc<NodeMeta>| {
+                ws.on_upgrade(move |socket| handle_ws(socket, node, p2p, node_meta))
+            },
+        );
+
+    let batch_limiter = rate_limiter.clone();
+    let limiter_filter = warp::any().map(move || batch_limiter.clone());
+    let http_route = warp::post()
         .and(warp::path::end())
+        .and(rate_limit(rate_limiter))
         .and(warp::body::json())
+        .and(warp::addr::remote())
+        .and(limiter_filter)
         .and(node_filter)
         .and(p2p_filter)
         .and(meta_filter)
-        .and_then(handle_rpc)
+        .and_then(handle_rpc);
+
+    ws_route
+        .or(http_route)
+        .recover(recover_rate_limited)
         .with(cors)
         .with(warp::log("Astram::eth_rpc"))
 }
@@ -1093,12 +2035,13 @@ pub async fn run_eth_rpc_server(
     p2p: std::sync::Arc<PeerManager>,
     node_meta: std::sync::Arc<NodeMeta>,
     bind_addr: SocketAddr,
+    rate_limiter: std::sync::Arc<RateLimiter>,
 ) {
-    let routes = eth_rpc_routes(node, p2p, node_meta);
+    let routes = eth_rpc_routes(node, p2p, node_meta, rate_limiter);
 
     println!(
-        "[INFO] Ethereum JSON-RPC server running at http://{}",
-        bind_addr
+        "[INFO] Ethereum JSON-RPC server running at http://{} (ws://{} for eth_subscribe)",
+        bind_addr, bind_addr
     );
     println!("   Chain ID: 8888 (0x22b8)");
     println!("   Ready for MetaMask connection!");
@@ -1106,3 +2049,332 @@ pub async fn run_eth_rpc_server(
 
     warp::serve(routes).run(bind_addr).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rlp::RlpStream;
+    use secp256k1::{Message, Secp256k1, SecretKey};
+
+    /// Fields before the signature: chain id, nonce, tip, max fee, gas, to, value, data
+    fn append_unsigned_fields(stream: &mut RlpStream, chain_id: u64) {
+        stream.append(&chain_id);
+        stream.append(&7u64);
+        stream.append(&2_000_000_000u64); // 2 Gwei tip
+        stream.append(&30_000_000_000u64); // 30 Gwei max fee
+        stream.append(&21_000u64);
+        stream.append(&vec![0x11u8; 20]);
+        stream.append(&1_000_000u64);
+        stream.append(&Vec::<u8>::new());
+        stream.begin_list(0); // empty access list
+    }
+
+    #[test]
+    fn decodes_signed_eip1559_transaction() {
+        let chain_id = resolve_chain_id();
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&[0x42; 32]).unwrap();
+
+        let mut unsigned = RlpStream::new_list(9);
+        append_unsigned_fields(&mut unsigned, chain_id);
+        let mut preimage = vec![EIP1559_TX_TYPE];
+        preimage.extend_from_slice(&unsigned.out());
+        let message = Message::from_digest_slice(&keccak256(&preimage)).unwrap();
+        let (rec_id, sig) = secp
+            .sign_ecdsa_recoverable(&message, &secret)
+            .serialize_compact();
+
+        let mut signed = RlpStream::new_list(12);
+        append_unsigned_fields(&mut signed, chain_id);
+        signed.append(&(rec_id.to_i32() as u64));
+        signed.append(&sig[..32].to_vec());
+        signed.append(&sig[32..].to_vec());
+        let mut raw = vec![EIP1559_TX_TYPE];
+        raw.extend_from_slice(&signed.out());
+
+        let tx = decode_ethereum_transaction(&raw).unwrap();
+        let public_key = secp256k1::PublicKey::from_secret_key(&secp, &secret);
+        let expected_from = format!(
+            "0x{}",
+            hex::encode(&keccak256(&public_key.serialize_uncompressed()[1..])[12..])
+        );
+        assert_eq!(tx.from.split(';').next(), Some(expected_from.as_str()));
+        assert_eq!(tx.nonce, 7);
+        assert_eq!(tx.to, format!("0x{}", "11".repeat(20)));
+        assert_eq!(tx.value, U256::from(1_000_000u64));
+        // min(30 Gwei, 10 Gwei base + 2 Gwei tip)
+        assert_eq!(tx.gas_price, U256::from(12_000_000_000u64));
+    }
+
+    #[test]
+    fn oversized_integer_fields_are_rejected() {
+        let oversized = vec![0x01u8; 33];
+
+        // Legacy: [nonce, gasPrice, gasLimit, to, value, data, v, r, s]
+        let mut legacy = RlpStream::new_list(9);
+        legacy.append(&0u64);
+        legacy.append(&1_000_000_000u64);
+        legacy.append(&21_000u64);
+        legacy.append(&vec![0x11u8; 20]);
+        legacy.append(&oversized);
+        legacy.append(&Vec::<u8>::new());
+        legacy.append(&27u64);
+        legacy.append(&vec![0x22u8; 32]);
+        legacy.append(&vec![0x33u8; 32]);
+        let err = decode_ethereum_transaction(&legacy.out()).unwrap_err();
+        assert!(err.contains("value"), "{}", err);
+
+        // Type 2 with a 33-byte value field
+        let mut typed = RlpStream::new_list(12);
+        typed.append(&resolve_chain_id());
+        typed.append(&0u64);
+        typed.append(&1u64);
+        typed.append(&1u64);
+        typed.append(&21_000u64);
+        typed.append(&vec![0x11u8; 20]);
+        typed.append(&oversized);
+        typed.append(&Vec::<u8>::new());
+        typed.begin_list(0);
+        typed.append(&0u64);
+        typed.append(&vec![0x22u8; 32]);
+        typed.append(&vec![0x33u8; 32]);
+        let mut raw = vec![EIP1559_TX_TYPE];
+        raw.extend_from_slice(&typed.out());
+        let err = decode_ethereum_transaction(&raw).unwrap_err();
+        assert!(err.contains("value"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_transaction_type_is_rejected() {
+        assert!(decode_ethereum_transaction(&[0x01, 0xc0]).is_err());
+        assert!(decode_ethereum_transaction(&[]).is_err());
+    }
+
+    /// Pay the estimated gas at the price quoted under a relay floor of `floor` ram per
+    /// byte, signed the way a wallet would, and check the mempool takes the fee
+    fn assert_estimate_clears_floor(floor: u64) {
+        let mempool = crate::MempoolState {
+            min_relay_fee_per_byte: floor,
+            ..Default::default()
+        };
+        let gas_price = quoted_gas_price(mempool.min_relay_fee_rate());
+        let from = format!("0x{}", "aa".repeat(20));
+        let to = format!("0x{}", "bb".repeat(20));
+        let asrm = Astram_core::config::RAM_PER_ASRM;
+        let utxos = [
+            Utxo::new("01".repeat(32), 0, from.clone(), asrm, 1, false),
+            Utxo::new("02".repeat(32), 0, from.clone(), asrm * 2, 1, false),
+        ];
+        let value = asrm + asrm / 2;
+        let gas = estimate_transfer_gas(&utxos, &from, &to, value, gas_price).unwrap();
+
+        // What a wallet signs: a legacy v and full-width r and s
+        let pubkey_hex = format!("04{}", "11".repeat(64));
+        let eth_sig = format!("eth_sig:{}:{}:{}", 37, "12".repeat(32), "34".repeat(32));
+        let fee = gas_price * U256::from(gas);
+        let outputs = vec![TransactionOutput::new(to, value)];
+        let tx = build_transaction(&utxos, outputs, &FeePolicy::Fixed(fee), &from, |tx| {
+            Ok(with_eth_signature(tx, &pubkey_hex, &eth_sig))
+        })
+        .unwrap();
+
+        // Both inputs were needed, and the fee paid covers the relay minimum
+        assert_eq!(tx.inputs.len(), 2);
+        let size = bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap().len();
+        assert_eq!(tx.fee(asrm * 3), Some(fee));
+        assert!(fee >= calculate_min_fee(size));
+        assert!(fee >= mempool.min_relay_fee(size));
+    }
+
+    #[test]
+    fn paying_the_estimated_gas_at_gas_price_is_accepted() {
+        let rate = Astram_core::config::MIN_RELAY_FEE_NAT_PER_BYTE;
+        assert_eq!(quoted_gas_price(rate), GAS_PRICE);
+        assert_estimate_clears_floor(0);
+    }
+
+    #[test]
+    fn a_raised_relay_floor_raises_the_quoted_gas_price() {
+        // 1000 Gwei per byte, five times the protocol minimum
+        let floor = 1_000_000_000_000;
+        let rate = U256::from(floor);
+        assert_eq!(quoted_gas_price(rate), GAS_PRICE * U256::from(5u8));
+        assert_estimate_clears_floor(floor);
+    }
+
+    #[test]
+    fn fee_history_is_sized_to_the_request() {
+        let history = fee_history(5, &[500_000, 2_000_000, 0], &[25.0, 75.0]);
+        assert_eq!(history["oldestBlock"], "0x5");
+        // One base fee per block plus the next block's
+        let base_fees = history["baseFeePerGas"].as_array().unwrap();
+        assert_eq!(base_fees.len(), 4);
+        assert!(base_fees.iter().all(|fee| fee == "0x2540be400")); // 10 Gwei
+        assert_eq!(history["gasUsedRatio"], json!([0.5, 1.0, 0.0]));
+
+        let reward = history["reward"].as_array().unwrap();
+        assert_eq!(reward.len(), 3);
+        assert_eq!(reward[0], json!(["0x12a05f200", "0x12a05f200"])); // 5 Gwei tips
+
+        // A type-2 tx paying base fee plus the tip clears the minimum by half again
+        let gas = gas_for_size(300);
+        let fee = (BASE_FEE_PER_GAS + priority_fee_per_gas()) * U256::from(gas);
+        assert_eq!(
+            fee,
+            calculate_min_fee(300) * U256::from(3u8) / U256::from(2u8)
+        );
+        assert!(fee_history(0, &[], &[]).get("reward").is_none());
+    }
+
+    #[test]
+    fn balance_of_calldata_names_the_address() {
+        let holder = "ab".repeat(20);
+        let call = format!("0x70a08231{}{}", "0".repeat(24), holder.to_uppercase());
+        assert_eq!(balance_of_address(&call), Some(format!("0x{}", holder)));
+
+        // Other selectors, short or padded-wrong arguments are plain calls
+        let decimals = "0x313ce567";
+        let short = format!("0x70a08231{}", holder);
+        let dirty = format!("0x70a08231{}{}", "1".repeat(24), holder);
+        for data in ["", "0x", decimals, &short, &dirty] {
+            assert_eq!(balance_of_address(data), None, "{}", data);
+        }
+    }
+
+    #[test]
+    fn reward_percentiles_are_validated() {
+        assert_eq!(reward_percentiles(None), Ok(vec![]));
+        assert_eq!(
+            reward_percentiles(Some(&json!([10, 50.5, 50.5, 100]))),
+            Ok(vec![10.0, 50.5, 50.5, 100.0])
+        );
+        for bad in [
+            json!([50, 10]),
+            json!([101]),
+            json!([-1]),
+            json!(["50"]),
+            json!(5),
+        ] {
+            assert!(reward_percentiles(Some(&bad)).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn block_tags_on_empty_and_genesis_only_chains() {
+        let dir = std::env::temp_dir().join(format!(
+            "astram-blocktag-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let mut bc = Blockchain::new(dir.to_str().unwrap()).unwrap();
+
+        // Empty: every tag is null and the height reads as 0x0
+        assert_eq!(tip_height(&bc), None);
+        for tag in ["latest", "pending", "earliest", "0x0"] {
+            assert_eq!(resolve_block_tag(&bc, tag), None, "{}", tag);
+        }
+
+        // Genesis only: "latest" is genesis and "pending" builds on it
+        let genesis = bc.create_genesis().unwrap();
+        assert_eq!(tip_height(&bc), Some(0));
+        assert_eq!(resolve_block_tag(&bc, "latest"), Some(BlockTag::Mined(0)));
+        assert_eq!(resolve_block_tag(&bc, "earliest"), Some(BlockTag::Mined(0)));
+        assert_eq!(
+            resolve_block_tag(&bc, "pending"),
+            Some(BlockTag::Pending {
+                number: 1,
+                parent_hash: genesis
+            })
+        );
+        assert_eq!(resolve_block_tag(&bc, "0x1"), Some(BlockTag::Mined(1)));
+        assert_eq!(resolve_block_tag(&bc, "bogus"), None);
+
+        drop(bc);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn batches_answer_every_call_and_pay_per_call() {
+        let (node, dir) = crate::server::tests::node_with_utxos("rpc-batch", &[]);
+        let node_meta = std::sync::Arc::new(NodeMeta {
+            miner_address: Default::default(),
+            my_public_address: Default::default(),
+            node_start_time: std::time::Instant::now(),
+            eth_to_astram_tx: Default::default(),
+        });
+        let p2p = std::sync::Arc::new(PeerManager::new());
+        let limiter = std::sync::Arc::new(RateLimiter::new(1, 7, Vec::new()));
+        let routes = eth_rpc_routes(node, p2p, node_meta, limiter);
+        let client: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+        let call = |id: u64, method: &str| json!({"jsonrpc": "2.0", "id": id, "method": method});
+        let post = |body: Value| {
+            warp::test::request()
+                .method("POST")
+                .path("/")
+                .remote_addr(client)
+                .json(&body)
+        };
+
+        // Successes and errors answer in order; the notification gets no entry
+        let batch = json!([
+            call(1, "eth_chainId"),
+            call(2, "eth_noSuchMethod"),
+            {"jsonrpc": "2.0", "method": "eth_chainId"},
+            {"jsonrpc": "2.0", "id": 4},
+        ]);
+        let reply = post(batch).reply(&routes).await;
+        assert_eq!(reply.status(), warp::http::StatusCode::OK);
+        let responses: Value = serde_json::from_slice(reply.body()).unwrap();
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["id"], 1);
+        assert!(responses[0]["result"].is_string());
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["error"]["code"], -32601);
+        assert_eq!(responses[2]["error"]["code"], -32600);
+
+        // Oversized batches are refused whole
+        let oversized = Value::Array(vec![call(1, "eth_chainId"); MAX_BATCH_SIZE + 1]);
+        let reply = post(oversized).reply(&routes).await;
+        let error: Value = serde_json::from_slice(reply.body()).unwrap();
+        assert_eq!(error["error"]["code"], -32600);
+
+        // Five of seven tokens are spent: a three-call batch is over budget, one call isn't
+        let batch = Value::Array((1..=3).map(|id| call(id, "eth_chainId")).collect());
+        let reply = post(batch).reply(&routes).await;
+        assert_eq!(reply.status(), warp::http::StatusCode::TOO_MANY_REQUESTS);
+        let reply = post(call(1, "eth_chainId")).reply(&routes).await;
+        assert_eq!(reply.status(), warp::http::StatusCode::OK);
+
+        drop(routes);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn a_client_that_stops_reading_is_flagged_once_its_queue_fills() {
+        let (out, mut queued) = mpsc::channel::<String>(2);
+        let lagged = Notify::new();
+        assert!(push_notification(&out, "a".to_string(), &lagged));
+        assert!(push_notification(&out, "b".to_string(), &lagged));
+
+        // The third notification finds the queue full: stop and signal the connection
+        assert!(!push_notification(&out, "c".to_string(), &lagged));
+        tokio::time::timeout(std::time::Duration::from_secs(1), lagged.notified())
+            .await
+            .expect("lag was signalled");
+        // Nothing past the bound was buffered
+        assert_eq!(queued.recv().await.as_deref(), Some("a"));
+        assert_eq!(queued.recv().await.as_deref(), Some("b"));
+        assert!(queued.try_recv().is_err());
+
+        // A closed connection just ends the stream
+        drop(queued);
+        let idle = Notify::new();
+        assert!(!push_notification(&out, "d".to_string(), &idle));
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(50), idle.notified())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,368 @@
+// Header templates for solo miners: GET /mining/template hands out a job, POST
+// /mining/solution turns a nonce for it back into a full block
+use crate::NodeHandle;
+use Astram_core::block::{Block, BlockHeader, compute_header_hash, compute_merkle_root};
+use Astram_core::blockchain::Blockchain;
+use Astram_core::config::calculate_block_reward;
+use Astram_core::consensus::select_block_transactions;
+use Astram_core::security::{BLOCK_RESERVED_BYTES, MAX_BLOCK_BYTES};
+use Astram_core::transaction::Transaction;
+use primitive_types::U256;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// How long a handed-out template can still be solved
+pub const WORK_TEMPLATE_TTL: Duration = Duration::from_secs(120);
+/// Templates kept at once; the oldest is dropped to make room
+const MAX_WORK_TEMPLATES: usize = 256;
+/// Templates one client can hold; past this its own oldest is dropped, not someone else's
+const MAX_JOBS_PER_CLIENT: usize = 4;
+/// A template built on the same tip this recently is handed out again instead of
+/// rescanning the mempool under the chain lock
+const TEMPLATE_REUSE: Duration = Duration::from_secs(5);
+
+struct WorkJob {
+    /// Template block: coinbase first, header with nonce 0 and no hash yet
+    block: Block,
+    /// Remote IP the job was handed to; only that client can solve it
+    client: Option<IpAddr>,
+    created: Instant,
+}
+
+/// Why a solution was not turned into a block
+#[derive(Debug, PartialEq)]
+pub enum SolutionError {
+    /// Never issued, expired, or already used
+    UnknownJob,
+    /// The tip moved since the template was built
+    Stale,
+    /// The header hash is not below the template's target
+    InsufficientWork,
+}
+
+/// Short-lived templates keyed by job id
+#[derive(Default)]
+pub struct WorkTemplates {
+    jobs: Mutex<HashMap<String, WorkJob>>,
+    next_id: AtomicU64,
+    /// Last template built and the miner address it pays
+    latest: Mutex<Option<(String, Block, Instant)>>,
+}
+
+impl WorkTemplates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Next block paying `miner_address`: the last one built if it is on the current tip
+    /// and younger than TEMPLATE_REUSE, otherwise a fresh build_work_template
+    pub fn template(&self, node: &NodeHandle, miner_address: &str) -> anyhow::Result<Block> {
+        let tip = node
+            .bc
+            .lock()
+            .unwrap()
+            .chain_tip
+            .clone()
+            .unwrap_or_default();
+        let now = Instant::now();
+        if let Some(block) = self.reusable_at(&tip, miner_address, now) {
+            return Ok(block);
+        }
+        let block = build_work_template(node, miner_address)?;
+        *self.latest.lock().unwrap() = Some((miner_address.to_string(), block.clone(), now));
+        Ok(block)
+    }
+
+    fn reusable_at(&self, tip: &str, miner_address: &str, now: Instant) -> Option<Block> {
+        let latest = self.latest.lock().unwrap();
+        let (address, block, built) = latest.as_ref()?;
+        let fresh = now.saturating_duration_since(*built) < TEMPLATE_REUSE;
+        (fresh && address == miner_address && block.header.previous_hash == tip)
+            .then(|| block.clone())
+    }
+
+    /// Store `block` for `client` and return the job id a solution must quote
+    pub fn insert(&self, block: Block, client: Option<IpAddr>) -> String {
+        self.insert_at(block, client, Instant::now())
+    }
+
+    fn insert_at(&self, block: Block, client: Option<IpAddr>, now: Instant) -> String {
+        let id = format!("{:x}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, job| now.saturating_duration_since(job.created) < WORK_TEMPLATE_TTL);
+        let held = jobs.values().filter(|job| job.client == client).count();
+        if held >= MAX_JOBS_PER_CLIENT || jobs.len() >= MAX_WORK_TEMPLATES {
+            // A client at its own cap makes room among its own jobs
+            let own_only = held >= MAX_JOBS_PER_CLIENT;
+            if let Some(oldest) = jobs
+                .iter()
+                .filter(|(_, job)| !own_only || job.client == client)
+                .min_by_key(|(_, job)| job.created)
+                .map(|(id, _)| id.clone())
+            {
+                jobs.remove(&oldest);
+            }
+        }
+        jobs.insert(
+            id.clone(),
+            WorkJob {
+                block,
+                client,
+                created: now,
+            },
+        );
+        id
+    }
+
+    /// Fill `nonce` (and `timestamp`, if given) into job `job_id`'s header and return the
+    /// finished block if its hash meets the target. `tip` is the current chain tip; a
+    /// template built on another tip is stale. A block is handed out once per job, and
+    /// only to the client the job was issued to.
+    pub fn solve(
+        &self,
+        job_id: &str,
+        client: Option<IpAddr>,
+        nonce: u64,
+        timestamp: Option<i64>,
+        tip: &str,
+    ) -> Result<Block, SolutionError> {
+        self.solve_at(job_id, client, nonce, timestamp, tip, Instant::now())
+    }
+
+    fn solve_at(
+        &self,
+        job_id: &str,
+        client: Option<IpAddr>,
+        nonce: u64,
+        timestamp: Option<i64>,
+        tip: &str,
+        now: Instant,
+    ) -> Result<Block, SolutionError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = match jobs.get(job_id) {
+            Some(job)
+                if job.client == client
+                    && now.saturating_duration_since(job.created) < WORK_TEMPLATE_TTL =>
+            {
+                job
+            }
+            _ => return Err(SolutionError::UnknownJob),
+        };
+        if job.block.header.previous_hash != tip {
+            jobs.remove(job_id);
+            return Err(SolutionError::Stale);
+        }
+
+        let mut block = job.block.clone();
+        block.header.nonce = nonce;
+        if let Some(timestamp) = timestamp {
+            block.header.timestamp = timestamp;
+        }
+        if !meets_target(&block.header) {
+            return Err(SolutionError::InsufficientWork);
+        }
+        block.hash =
+            compute_header_hash(&block.header).map_err(|_| SolutionError::InsufficientWork)?;
+        jobs.remove(job_id);
+        Ok(block)
+    }
+}
+
+/// Whether `header` hashes below the target of its own compact bits
+fn meets_target(header: &BlockHeader) -> bool {
+    let target = Blockchain::compact_to_target(header.difficulty);
+    compute_header_hash(header)
+        .and_then(|hash| Blockchain::hash_to_u256(&hash))
+        .map(|value| !target.is_zero() && value < target)
+        .unwrap_or(false)
+}
+
+/// Next block on the current tip paying `miner_address`: pending transactions chosen the
+/// way the node's own miner does (unexpired, mature spends, best fee rate first, within
+/// the block size cap) behind a coinbase for the reward plus their fees. The mempool is
+/// only read.
+pub fn build_work_template(node: &NodeHandle, miner_address: &str) -> anyhow::Result<Block> {
+    let pending: Vec<Transaction> = node.mempool.lock().unwrap().pending.clone();
+    let timestamp = chrono::Utc::now().timestamp();
+
+    let (prev_hash, index, difficulty, txs, fees) = {
+        let bc = node.bc.lock().unwrap();
+        let prev_hash = bc.chain_tip.clone().unwrap_or_else(|| "0".repeat(64));
+        let index = match &bc.chain_tip {
+            Some(tip) => bc.load_header(tip)?.map(|h| h.index + 1).unwrap_or(0),
+            None => 0,
+        };
+        let difficulty = bc
+            .calculate_adjusted_difficulty(index)
+            .unwrap_or(bc.difficulty);
+        let txs: Vec<Transaction> = pending
+            .into_iter()
+            .filter(|tx| !tx.is_expired_at(timestamp))
+            .filter(|tx| !bc.spends_immature_coinbase(tx, index).unwrap_or(true))
+            .collect();
+        let fees: Vec<U256> = txs
+            .iter()
+            .map(|tx| crate::confirmed_input_fee(&bc, tx))
+            .collect();
+        (prev_hash, index, difficulty, txs, fees)
+    };
+
+    let selected = select_block_transactions(&txs, &fees, MAX_BLOCK_BYTES - BLOCK_RESERVED_BYTES);
+    let total_fees = selected
+        .iter()
+        .fold(U256::zero(), |sum, &i| sum.saturating_add(fees[i]));
+    let reward = calculate_block_reward(index).saturating_add(total_fees);
+
+    let mut transactions = vec![Transaction::coinbase(miner_address, reward).with_hashes()];
+    transactions.extend(selected.into_iter().map(|i| txs[i].clone()));
+    let txids: Vec<String> = transactions.iter().map(|tx| tx.txid.clone()).collect();
+
+    Ok(Block {
+        header: BlockHeader {
+            index,
+            previous_hash: prev_hash,
+            merkle_root: compute_merkle_root(&txids),
+            timestamp,
+            nonce: 0,
+            difficulty,
+        },
+        transactions,
+        hash: String::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Astram_core::consensus::find_valid_nonce;
+
+    const EASY_BITS: u32 = 0x207fffff;
+    const UNREACHABLE_BITS: u32 = 0x03000001;
+
+    fn client(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::from([10, 0, 0, last]))
+    }
+
+    fn template(bits: u32) -> Block {
+        let coinbase = Transaction::coinbase("0xminer", U256::from(50u8)).with_hashes();
+        Block {
+            header: BlockHeader {
+                index: 1,
+                previous_hash: "ab".repeat(32),
+                merkle_root: compute_merkle_root(&[coinbase.txid.clone()]),
+                timestamp: 1_700_000_000,
+                nonce: 0,
+                difficulty: bits,
+            },
+            transactions: vec![coinbase],
+            hash: String::new(),
+        }
+    }
+
+    #[test]
+    fn solved_template_becomes_a_block_once() {
+        let templates = WorkTemplates::new();
+        let tip = "ab".repeat(32);
+        let block = template(EASY_BITS);
+        let mut header = block.header.clone();
+        let (nonce, hash) = find_valid_nonce(&mut header, EASY_BITS).unwrap();
+
+        let job = templates.insert(block, client(1));
+        // Another client can't claim it
+        assert_eq!(
+            templates.solve(&job, client(2), nonce, None, &tip),
+            Err(SolutionError::UnknownJob)
+        );
+        let solved = templates.solve(&job, client(1), nonce, None, &tip).unwrap();
+        assert_eq!(solved.hash, hash);
+        assert_eq!(solved.header.nonce, nonce);
+
+        // The job is used up
+        assert_eq!(
+            templates.solve(&job, client(1), nonce, None, &tip),
+            Err(SolutionError::UnknownJob)
+        );
+        assert_eq!(
+            templates.solve("nope", client(1), nonce, None, &tip),
+            Err(SolutionError::UnknownJob)
+        );
+    }
+
+    #[test]
+    fn weak_stale_and_expired_solutions_are_rejected() {
+        let templates = WorkTemplates::new();
+        let tip = "ab".repeat(32);
+        let now = Instant::now();
+
+        let job = templates.insert_at(template(UNREACHABLE_BITS), client(1), now);
+        assert_eq!(
+            templates.solve_at(&job, client(1), 0, None, &tip, now),
+            Err(SolutionError::InsufficientWork)
+        );
+        // A weak share doesn't burn the job, but a new tip does
+        assert_eq!(
+            templates.solve_at(&job, client(1), 0, None, &"cd".repeat(32), now),
+            Err(SolutionError::Stale)
+        );
+        assert_eq!(
+            templates.solve_at(&job, client(1), 0, None, &tip, now),
+            Err(SolutionError::UnknownJob)
+        );
+
+        let job = templates.insert_at(template(EASY_BITS), client(1), now);
+        let later = now + WORK_TEMPLATE_TTL;
+        assert_eq!(
+            templates.solve_at(&job, client(1), 0, None, &tip, later),
+            Err(SolutionError::UnknownJob)
+        );
+    }
+
+    #[test]
+    fn a_client_past_its_cap_only_evicts_its_own_jobs() {
+        let templates = WorkTemplates::new();
+        let tip = "ab".repeat(32);
+        let now = Instant::now();
+
+        let other = templates.insert_at(template(UNREACHABLE_BITS), client(2), now);
+        let first = templates.insert_at(template(UNREACHABLE_BITS), client(1), now);
+        for i in 1..=MAX_JOBS_PER_CLIENT as u64 {
+            let at = now + Duration::from_millis(i);
+            templates.insert_at(template(UNREACHABLE_BITS), client(1), at);
+        }
+
+        let jobs = templates.jobs.lock().unwrap();
+        let held = jobs.values().filter(|job| job.client == client(1)).count();
+        assert_eq!(held, MAX_JOBS_PER_CLIENT);
+        assert!(!jobs.contains_key(&first));
+        drop(jobs);
+        assert_eq!(
+            templates.solve_at(&other, client(2), 0, None, &tip, now),
+            Err(SolutionError::InsufficientWork)
+        );
+    }
+
+    #[test]
+    fn recent_template_is_reused_on_the_same_tip_only() {
+        let templates = WorkTemplates::new();
+        let tip = "ab".repeat(32);
+        let now = Instant::now();
+        assert!(templates.reusable_at(&tip, "0xminer", now).is_none());
+
+        *templates.latest.lock().unwrap() = Some(("0xminer".to_string(), template(EASY_BITS), now));
+        assert!(templates.reusable_at(&tip, "0xminer", now).is_some());
+        assert!(
+            templates
+                .reusable_at(&"cd".repeat(32), "0xminer", now)
+                .is_none()
+        );
+        assert!(templates.reusable_at(&tip, "0xother", now).is_none());
+        assert!(
+            templates
+                .reusable_at(&tip, "0xminer", now + TEMPLATE_REUSE)
+                .is_none()
+        );
+    }
+}
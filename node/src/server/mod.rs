@@ -1,6 +1,9 @@
 pub mod eth_rpc;
+pub mod getwork;
+pub mod rate_limit;
 
 pub use eth_rpc::run_eth_rpc_server;
+pub use rate_limit::RateLimiter;
 
 use crate::ChainState;
 use crate::NodeHandle;
@@ -8,8 +11,13 @@ use crate::NodeMeta;
 use crate::PeerManager;
 use base64::{Engine as _, engine::general_purpose};
 use Astram_core::block::Block;
+use Astram_core::blockchain::BlockPruned;
+use Astram_core::checkpoint;
+use Astram_core::sigcache::SIGNATURE_CACHE;
 use Astram_core::transaction::{BINCODE_CONFIG, Transaction};
+use Astram_core::units::checked_sum;
 use Astram_core::utxo::Utxo;
+use astram_config::time::with_timestamps;
 use log::info;
 use primitive_types::U256;
 use serde::Deserialize;
@@ -17,6 +25,7 @@ use warp::Filter;
 use warp::{http::StatusCode, reply::with_status}; // bincode v2
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::Path;
 /// run_server expects NodeHandle (Arc<NodeHandles>)
 pub async fn run_server(
     node: NodeHandle,
@@ -24,7 +33,16 @@ pub async fn run_server(
     chain_state: std::sync::Arc<std::sync::Mutex<ChainState>>,
     node_meta: std::sync::Arc<NodeMeta>,
     bind_addr: SocketAddr,
+    dashboard_path: std::path::PathBuf,
+    access_log: bool,
+    min_confirmations_for_spend: u64,
+    max_concurrent_requests: usize,
+    admin_token: String,
+    backup_dir: std::path::PathBuf,
+    rate_limiter: std::sync::Arc<RateLimiter>,
 ) {
+    // Per-IP limit on the routes that take several locks or serialize the whole chain
+    let per_ip_limit = rate_limit::rate_limit(rate_limiter);
     let node_filter = {
         let node = node.clone();
         warp::any().map(move || node.clone())
@@ -41,11 +59,20 @@ pub async fn run_server(
         let node_meta = node_meta.clone();
         warp::any().map(move || node_meta.clone())
     };
+    let work_filter = {
+        let templates = std::sync::Arc::new(getwork::WorkTemplates::new());
+        warp::any().map(move || templates.clone())
+    };
+    let fee_rates_filter = {
+        let cache = std::sync::Arc::new(FeeRateCache::default());
+        warp::any().map(move || cache.clone())
+    };
 
     // -------------------------------
     // GET /blockchain/memory - In-memory blockchain state
     let get_chain_memory = warp::path!("blockchain" / "memory")
         .and(warp::get())
+        .and(per_ip_limit.clone())
         .and(chain_filter.clone())
         .and_then(|chain_state: std::sync::Arc<std::sync::Mutex<ChainState>>| async move {
             let chain = chain_state.lock().unwrap();
@@ -59,53 +86,84 @@ pub async fn run_server(
             })))
         });
 
-    // GET /blockchain/db - Blocks from database
+    // GET /blockchain/db?from=0&to=999 (or &limit=1000) - Blocks from database (deprecated, bounded)
+    // Security: the whole-chain dump is gone; a span of at most MAX_DB_QUERY_SPAN blocks is required
     let get_chain_db = warp::path!("blockchain" / "db")
         .and(warp::get())
+        .and(per_ip_limit.clone())
+        .and(warp::query::<std::collections::HashMap<String, String>>())
         .and(node_filter.clone())
-        .and_then(|node: NodeHandle| async move {
+        .and_then(|params: std::collections::HashMap<String, String>, node: NodeHandle| async move {
+            let (from_height, to_height) = match db_query_span(&params) {
+                Ok(span) => span,
+                Err(message) => {
+                    return Ok::<_, warp::Rejection>(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "error": message,
+                            "max_span": MAX_DB_QUERY_SPAN,
+                            "use": "/blockchain/range?from=<height>&limit=<count>"
+                        })),
+                        StatusCode::BAD_REQUEST,
+                    ));
+                }
+            };
+
             let state = node.clone();
             let bc = state.bc.lock().unwrap();
-            match bc.get_all_blocks() {
-                Ok(all_blocks) => {
-                    let bincode_bytes =
-                        bincode::encode_to_vec(&all_blocks, *BINCODE_CONFIG).unwrap();
+            match bc.get_blocks_range(from_height, Some(to_height)) {
+                Ok(blocks) => {
+                    let bincode_bytes = bincode::encode_to_vec(&blocks, *BINCODE_CONFIG).unwrap();
                     let encoded = general_purpose::STANDARD.encode(&bincode_bytes);
-                    log::info!("[INFO] Returning {} blocks from DB", all_blocks.len());
-                    Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
-                        "blockchain": encoded,
-                        "count": all_blocks.len(),
-                        "source": "database"
-                    })))
+                    log::info!(
+                        "[INFO] Returning {} blocks from DB (height {} to {})",
+                        blocks.len(),
+                        from_height,
+                        to_height
+                    );
+                    Ok::<_, warp::Rejection>(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "blockchain": encoded,
+                            "count": blocks.len(),
+                            "from": from_height,
+                            "to": to_height,
+                            "source": "database",
+                            "deprecated": "use /blockchain/range"
+                        })),
+                        StatusCode::OK,
+                    ))
                 }
                 Err(e) => {
                     log::error!("[ERROR] Failed to fetch blocks from DB: {}", e);
-                    Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
-                        "error": format!("Failed to fetch blockchain from DB: {}", e),
-                        "count": 0,
-                        "source": "database"
-                    })))
+                    Ok::<_, warp::Rejection>(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "error": format!("Failed to fetch blockchain from DB: {}", e),
+                            "count": 0,
+                            "source": "database"
+                        })),
+                        db_error_status(&e),
+                    ))
                 }
             }
         });
 
-    // GET /blockchain/range?from=0&to=10 - Blocks from specific height range
+    // GET /blockchain/range?from=0&to=10 (or &limit=10) - Blocks from specific height range,
+    // at most MAX_DB_QUERY_SPAN per request; page with from=<last height + 1>
     let get_chain_range = warp::path!("blockchain" / "range")
         .and(warp::get())
+        .and(per_ip_limit.clone())
         .and(warp::query::<std::collections::HashMap<String, String>>())
         .and(node_filter.clone())
         .and_then(|params: std::collections::HashMap<String, String>, node: NodeHandle| async move {
-            let from_height = params.get("from").and_then(|s| s.parse::<u64>().ok()).unwrap_or(0);
-            let to_height = params.get("to").and_then(|s| s.parse::<u64>().ok());
+            let (from_height, to_height) = range_query_span(&params);
             
             let state = node.clone();
             let bc = state.bc.lock().unwrap();
-            match bc.get_blocks_range(from_height, to_height) {
+            match bc.get_blocks_range(from_height, Some(to_height)) {
                 Ok(blocks) => {
                     let bincode_bytes = bincode::encode_to_vec(&blocks, *BINCODE_CONFIG).unwrap();
                     let encoded = general_purpose::STANDARD.encode(&bincode_bytes);
                     
-                    log::info!("[INFO] Returning {} blocks from DB (height {} to {:?})", 
+                    log::info!("[INFO] Returning {} blocks from DB (height {} to {})", 
                         blocks.len(), from_height, to_height);
                     
                     Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
@@ -126,6 +184,117 @@ pub async fn run_server(
             }
         });
 
+    // GET /headers?from=0&count=2000 (or &to=1999, inclusive) - Main-chain headers read
+    // from the DB height index, without the transaction bodies
+    let get_headers = warp::path("headers")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(node_filter.clone())
+        .and_then(|params: HashMap<String, String>, node: NodeHandle| async move {
+            let from = match params.get("from").map(|v| v.parse::<u64>()) {
+                None => 0,
+                Some(Ok(v)) => v,
+                Some(Err(_)) => {
+                    return Ok::<_, warp::Rejection>(bad_request("invalid 'from' height"));
+                }
+            };
+            let count = match (params.get("to"), params.get("count")) {
+                (Some(to), _) => match to.parse::<u64>() {
+                    Ok(to) if to >= from => {
+                        ((to - from).saturating_add(1) as usize).min(MAX_HEADERS_PER_REQUEST)
+                    }
+                    _ => return Ok(bad_request("invalid 'to' height")),
+                },
+                (None, None) => MAX_HEADERS_PER_REQUEST,
+                (None, Some(count)) => match count.parse::<usize>() {
+                    Ok(v) => v.min(MAX_HEADERS_PER_REQUEST),
+                    Err(_) => return Ok(bad_request("invalid 'count'")),
+                },
+            };
+            if from.checked_add(count as u64).is_none() {
+                return Ok(bad_request("'from' height out of range"));
+            }
+
+            let result = node.bc.lock().unwrap().get_headers_range(from, count);
+            match result {
+                Ok(headers) => {
+                    let bincode_bytes = bincode::encode_to_vec(&headers, *BINCODE_CONFIG).unwrap();
+                    Ok(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "from": from,
+                            "count": headers.len(),
+                            "headers": general_purpose::STANDARD.encode(&bincode_bytes),
+                            "encoding": "bincode+base64"
+                        })),
+                        StatusCode::OK,
+                    ))
+                }
+                Err(e) => Ok(with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "error": format!("db error: {}", e)
+                    })),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                )),
+            }
+        });
+
+    // GET /blocks/time?from=&to=&limit= - Summaries of main-chain blocks stamped in [from, to]
+    let get_blocks_by_time = warp::path!("blocks" / "time")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(node_filter.clone())
+        .and_then(|params: HashMap<String, String>, node: NodeHandle| async move {
+            let from = match params.get("from").map(|v| v.parse::<i64>()) {
+                Some(Ok(v)) => v,
+                _ => return Ok::<_, warp::Rejection>(bad_request("missing or invalid 'from' timestamp")),
+            };
+            let to = match params.get("to").map(|v| v.parse::<i64>()) {
+                None => i64::MAX,
+                Some(Ok(v)) => v,
+                Some(Err(_)) => return Ok(bad_request("invalid 'to' timestamp")),
+            };
+            let limit = match params.get("limit").map(|v| v.parse::<usize>()) {
+                None => MAX_BLOCKS_PER_TIME_QUERY,
+                Some(Ok(v)) => v.min(MAX_BLOCKS_PER_TIME_QUERY),
+                Some(Err(_)) => return Ok(bad_request("invalid 'limit'")),
+            };
+
+            let result = node.bc.lock().unwrap().get_blocks_by_time(from, to, limit);
+            match result {
+                Ok(blocks) => {
+                    let summaries: Vec<serde_json::Value> = blocks
+                        .iter()
+                        .map(|b| {
+                            serde_json::json!({
+                                "height": b.header.index,
+                                "hash": b.hash,
+                                "previous_hash": b.header.previous_hash,
+                                "timestamp": b.header.timestamp,
+                                "difficulty": b.header.difficulty,
+                                "transaction_count": b.transactions.len(),
+                            })
+                        })
+                        .collect();
+                    Ok(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "from": from,
+                            "to": to,
+                            "count": summaries.len(),
+                            "blocks": summaries
+                        })),
+                        StatusCode::OK,
+                    ))
+                }
+                Err(e) => Ok(with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "error": format!("db error: {}", e)
+                    })),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                )),
+            }
+        });
+
     // GET /debug/block-counts - Simple debug endpoint
     let debug_counts = warp::path!("debug" / "block-counts")
         .and(warp::get())
@@ -135,13 +304,8 @@ pub async fn run_server(
             let state = node.clone();
             let chain = chain_state.lock().unwrap();
             let memory_count = chain.blockchain.len();
-            let db_count = state
-                .bc
-                .lock()
-                .unwrap()
-                .get_all_blocks()
-                .map(|b| b.len())
-                .unwrap_or(0);
+            // Heights come from the index, so pruned block bodies still count
+            let db_count = state.bc.lock().unwrap().get_next_index().unwrap_or(0) as usize;
 
             log::info!(
                 "[INFO] Block counts - Memory: {}, DB: {}",
@@ -156,6 +320,42 @@ pub async fn run_server(
             })))
         });
 
+    // GET /debug/checkpoints - Checkpoints this node enforces (compiled + checkpoints.json)
+    let debug_checkpoints = warp::path!("debug" / "checkpoints")
+        .and(warp::get())
+        .and(node_filter.clone())
+        .and_then(|node: NodeHandle| async move {
+            let bc = node.bc.lock().unwrap();
+            Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
+                "checkpoints": bc.checkpoints,
+                "latest_verified_height":
+                    checkpoint::get_latest_verified_checkpoint_height(&bc.checkpoints),
+                "assume_valid": bc.assume_valid.as_ref().map(|(height, hash)| serde_json::json!({
+                    "height": height,
+                    "hash": hash,
+                }))
+            })))
+        });
+
+    // GET /metrics - Prometheus text exposition of node, mempool, P2P and mining state
+    // (per-IP limited; whitelist the scraper in HTTP_RATE_LIMIT_WHITELIST)
+    let metrics = warp::path!("metrics")
+        .and(warp::get())
+        .and(per_ip_limit.clone())
+        .and(node_filter.clone())
+        .and(p2p_filter.clone())
+        .and(meta_filter.clone())
+        .map(|node: NodeHandle, p2p: std::sync::Arc<PeerManager>, node_meta: std::sync::Arc<NodeMeta>| {
+            let text = render_metrics(&collect_metrics(&node, &p2p, &node_meta));
+            warp::Reply::into_response(warp::reply::with_header(
+                text,
+                "content-type",
+                "text/plain; version=0.0.4",
+            ))
+        })
+        .recover(rate_limit::recover_rate_limited)
+        .unify();
+
     // GET /health - Health check endpoint for DNS server
     let health_check = warp::path!("health")
         .and(warp::get())
@@ -173,11 +373,12 @@ pub async fn run_server(
                 0
             };
 
-            Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
+            Ok::<_, warp::Rejection>(warp::reply::json(&with_timestamps(serde_json::json!({
                 "status": "ok",
                 "height": height,
+                "tip": bc.chain_tip,
                 "timestamp": chrono::Utc::now().timestamp()
-            })))
+            }))))
         });
 
     // GET /counts - lightweight counts for blocks and transactions (DB)
@@ -187,7 +388,7 @@ pub async fn run_server(
         .and_then(|node: NodeHandle| async move {
             let state = node.clone();
             let bc = state.bc.lock().unwrap();
-            let blocks = bc.get_all_blocks().map(|b| b.len()).unwrap_or(0);
+            let blocks = bc.get_next_index().unwrap_or(0);
             let transactions = bc.count_transactions().unwrap_or(0);
             let volume = bc.calculate_total_volume().unwrap_or(U256::zero());
             log::info!(
@@ -206,35 +407,24 @@ pub async fn run_server(
     // GET /status - Node status information (real-time monitoring)
     let get_status = warp::path("status")
         .and(warp::get())
+        .and(per_ip_limit.clone())
         .and(node_filter.clone())
         .and(chain_filter.clone())
         .and(meta_filter.clone())
         .and(p2p_filter.clone())
         .and_then(|node: NodeHandle, chain_state: std::sync::Arc<std::sync::Mutex<ChainState>>, node_meta: std::sync::Arc<NodeMeta>, p2p: std::sync::Arc<PeerManager>| async move {
-            let request_start = std::time::Instant::now();
-            info!("[DASHBOARD] 📊 /status request START");
-            
             // Get P2P data FIRST to avoid nested lock contention
             // Use a non-blocking snapshot to keep /status responsive
-            info!("[DASHBOARD] 🌐 P2P snapshot attempt...");
-            let p2p_start = std::time::Instant::now();
             let (peer_heights, my_height, subnet_24_count, subnet_16_count) =
                 match p2p.try_get_status_snapshot() {
-                    Some(data) => {
-                        info!("[DASHBOARD] ✅ P2P snapshot OK (took {:?})", p2p_start.elapsed());
-                        data
-                    }
+                    Some(data) => data,
                     None => {
-                        log::warn!(
-                            "[DASHBOARD] ⚠️ P2P snapshot contended (took {:?}), using defaults",
-                            p2p_start.elapsed()
-                        );
+                        log::warn!("/status: P2P snapshot contended, using defaults");
                         (HashMap::new(), 0, 0, 0)
                     }
                 };
             
             // Quick snapshot of blockchain data with ONE read lock - no nested locks!
-            info!("[DASHBOARD] 🔄 Collecting node state...");
             let (
                 memory_blocks,
                 pending_tx,
@@ -249,50 +439,32 @@ pub async fn run_server(
             ) = {
                 let state = node.clone();
 
-                info!("[DASHBOARD] 🔒 Acquiring blockchain lock...");
-                let bc_lock_start = std::time::Instant::now();
                 let chain_tip = {
                     let bc = state.bc.lock().unwrap();
-                    info!("[DASHBOARD] ✅ Blockchain lock acquired (took {:?})", bc_lock_start.elapsed());
                     bc.chain_tip
                         .as_ref()
                         .map(|h| hex::encode(h))
                         .unwrap_or_else(|| "none".to_string())
                 };
-                info!("[DASHBOARD] ✅ Blockchain lock released (held {:?})", bc_lock_start.elapsed());
 
-                info!("[DASHBOARD] 🔒 Acquiring chain_state lock...");
-                let chain_lock_start = std::time::Instant::now();
                 let memory_count = {
                     let chain = chain_state.lock().unwrap();
-                    info!("[DASHBOARD] ✅ Chain_state lock acquired (took {:?})", chain_lock_start.elapsed());
                     chain.blockchain.len()
                 };
-                info!("[DASHBOARD] ✅ Chain_state lock released (held {:?})", chain_lock_start.elapsed());
 
-                info!("[DASHBOARD] 🔒 Acquiring mempool lock...");
-                let mempool_lock_start = std::time::Instant::now();
                 let (pending_count, seen_count) = {
                     let mempool = state.mempool.lock().unwrap();
-                    info!("[DASHBOARD] ✅ Mempool lock acquired (took {:?})", mempool_lock_start.elapsed());
                     (mempool.pending.len(), mempool.seen_tx.len())
                 };
-                info!("[DASHBOARD] ✅ Mempool lock released (held {:?})", mempool_lock_start.elapsed());
 
-                info!("[DASHBOARD] 🔒 Acquiring mining locks...");
-                let mining_start = std::time::Instant::now();
                 let diff = *state.mining.current_difficulty.lock().unwrap();
                 let hash = *state.mining.current_hashrate.lock().unwrap();
                 let blocks_mined = state
                     .mining
                     .blocks_mined
                     .load(std::sync::atomic::Ordering::Relaxed);
-                info!("[DASHBOARD] ✅ Mining state collected (took {:?})", mining_start.elapsed());
 
-                info!("[DASHBOARD] 🔒 Acquiring miner_address lock...");
-                let wallet_start = std::time::Instant::now();
                 let wallet_addr = node_meta.miner_address.lock().unwrap().clone();
-                info!("[DASHBOARD] ✅ Miner_address acquired (took {:?})", wallet_start.elapsed());
 
                 (
                     memory_count,
@@ -307,11 +479,8 @@ pub async fn run_server(
                     wallet_addr,
                 )
             };
-            info!("[DASHBOARD] ✅ All state collected");
             
             // Get wallet balance OUTSIDE the lock (DB operation)
-            info!("[DASHBOARD] 💰 Fetching wallet balance from DB...");
-            let balance_start = std::time::Instant::now();
             let wallet_balance = {
                 node
                     .bc
@@ -320,18 +489,18 @@ pub async fn run_server(
                     .get_address_balance_from_db(&miner_address)
                     .unwrap_or(U256::zero())
             };
-            info!("[DASHBOARD] ✅ Wallet balance fetched (took {:?})", balance_start.elapsed());
 
             let connected_peers = peer_heights.len();
+            let misbehavior = p2p.misbehavior_report();
+            let block_failures = p2p.block_failure_report();
+            let peer_protocol_versions = p2p.peer_protocol_versions();
+            let peer_latency_ms = p2p.get_peer_latencies();
             let block_height = my_height;
 
-            info!("[DASHBOARD] 📈 Fetching validation statistics...");
             // Get validation statistics (lock-free)
             let validation_stats = Astram_core::security::VALIDATION_STATS.get_stats();
             let total_failures: u64 = validation_stats.iter().map(|(_, count)| count).sum();
-            info!("[DASHBOARD] ✅ Validation stats collected");
 
-            info!("[DASHBOARD] 📦 Building response JSON...");
             let response = serde_json::json!({
                 "node": {
                     "version": "0.1.0",
@@ -353,6 +522,11 @@ pub async fn run_server(
                 "network": {
                     "connected_peers": connected_peers,
                     "peer_heights": peer_heights,
+                    "protocol_version": crate::p2p::manager::PROTOCOL_VERSION,
+                    "peer_protocol_versions": peer_protocol_versions,
+                    "peer_latency_ms": peer_latency_ms,
+                    "penalized_peers": misbehavior.scores,
+                    "banned_peers": misbehavior.banned,
                     "subnet_diversity": {
                         "unique_24_subnets": subnet_24_count,
                         "unique_16_subnets": subnet_16_count,
@@ -361,6 +535,10 @@ pub async fn run_server(
                 "mining": {
                     "active": is_mining,
                     "hashrate": hashrate,
+                    "throttle_percent": node
+                        .mining
+                        .throttle_percent
+                        .load(std::sync::atomic::Ordering::Relaxed),
                     "difficulty": current_difficulty,
                     "blocks_mined": blocks_mined_count,
                 },
@@ -373,17 +551,34 @@ pub async fn run_server(
                     "validation_failures": validation_stats.into_iter()
                         .filter(|(_, count)| *count > 0)
                         .collect::<Vec<_>>(),
+                    "block_failures_by_peer": block_failures,
                 },
                 "timestamp": chrono::Utc::now().to_rfc3339(),
             });
+            let response = with_timestamps(response);
 
-            info!("[DASHBOARD] ✅ /status request COMPLETED in {:?}", request_start.elapsed());
             Ok::<_, warp::Rejection>(warp::reply::json(&response))
         });
 
+    // GET /peers - Connected peers with direction, version, height, latency and traffic,
+    // plus inbound/outbound slot usage
+    let get_peers = warp::path!("peers")
+        .and(warp::get())
+        .and(per_ip_limit.clone())
+        .and(p2p_filter.clone())
+        .map(|p2p: std::sync::Arc<PeerManager>| {
+            let peers = p2p.peer_summaries();
+            warp::reply::json(&serde_json::json!({
+                "count": peers.len(),
+                "slots": p2p.slot_usage(),
+                "peers": peers,
+            }))
+        });
+
     // GET /blockchain - Default endpoint (use memory for now)
     let get_chain = warp::path("blockchain")
         .and(warp::get())
+        .and(per_ip_limit.clone())
         .and(chain_filter.clone())
         .and_then(|chain_state: std::sync::Arc<std::sync::Mutex<ChainState>>| async move {
             let chain = chain_state.lock().unwrap();
@@ -399,10 +594,12 @@ pub async fn run_server(
     // -------------------------------
     let post_tx = warp::path("tx")
         .and(warp::post())
+        .and(per_ip_limit.clone())
         .and(warp::body::bytes())
         .and(node_filter.clone())
         .and(p2p_filter.clone())
-        .and_then(|body: bytes::Bytes, node: NodeHandle, p2p: std::sync::Arc<PeerManager>| async move {
+        .and(fee_rates_filter.clone())
+        .and_then(move |body: bytes::Bytes, node: NodeHandle, p2p: std::sync::Arc<PeerManager>, fee_rates: std::sync::Arc<FeeRateCache>| async move {
             let tx: Transaction;
 
             match bincode::decode_from_slice::<Transaction, _>(&body, *BINCODE_CONFIG) {
@@ -423,130 +620,105 @@ pub async fn run_server(
             }
 
             let state = node.clone();
+            let mut report = TxReport::default();
+            if let Err(message) =
+                check_transaction(&state, &tx, min_confirmations_for_spend, &mut report)
+            {
+                log::warn!("TX {} rejected: {}", tx.txid, message);
+                return Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "status": "error",
+                        "message": message
+                    })),
+                    StatusCode::BAD_REQUEST,
+                ));
+            }
+            log::info!("TX {} signature and fee OK", tx.txid);
+            let fee = report.fee.unwrap_or_default();
 
-            // Signature check
-            match tx.verify_signatures() {
-                Ok(true) => {
-                    log::info!("TX {} signature OK", tx.txid);
-                    
-                    // Security: Validate fee before accepting to mempool
-                    // Calculate input/output sums to verify fee
-                    let mut input_sum = U256::zero();
-                    let mut output_sum = U256::zero();
-                    
-                    // Get UTXOs from blockchain to calculate input sum
-                    {
-                        let bc = state.bc.lock().unwrap();
-                        for inp in &tx.inputs {
-                            let ukey = format!("u:{}:{}", inp.txid, inp.vout);
-                            if let Ok(Some(blob)) = bc.db.get(ukey.as_bytes()) {
-                                if let Ok((utxo, _)) =
-                                    bincode::decode_from_slice::<Utxo, _>(&blob, *BINCODE_CONFIG)
-                                {
-                                    input_sum = input_sum + utxo.amount();
-                                }
-                            }
-                        }
-                    }
-                    
-                    for out in &tx.outputs {
-                        output_sum = output_sum + out.amount();
-                    }
-                    
-                    let fee = if input_sum >= output_sum {
-                        input_sum - output_sum
-                    } else {
-                        U256::zero()
-                    };
-                    
-                    // Check minimum fee
-                    let tx_blob = bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap();
-                    let min_fee = Astram_core::config::calculate_min_fee(tx_blob.len());
-                    
-                    if fee < min_fee {
-                        log::warn!("TX {} fee too low: got {}, need {}", tx.txid, fee, min_fee);
-                        return Ok::<_, warp::Rejection>(with_status(
-                            warp::reply::json(&serde_json::json!({
-                                "status": "error",
-                                "message": format!("fee too low: got {} ram, need {} ram", fee, min_fee)
-                            })),
-                            StatusCode::BAD_REQUEST,
-                        ));
-                    }
-
-                    let mut mempool = state.mempool.lock().unwrap();
-
-                    // Duplicate protection
-                    if mempool.seen_tx.contains_key(&tx.txid) {
-                        log::info!("Duplicate TX {}", tx.txid);
-                        return Ok::<_, warp::Rejection>(with_status(
-                            warp::reply::json(&serde_json::json!({
-                                "status": "duplicate"
-                            })),
-                            StatusCode::OK,
-                        ));
-                    }
-
-                    // Security: Check for double-spending in mempool
-                    // Collect all UTXOs used by this transaction
-                    let mut tx_utxos = std::collections::HashSet::new();
-                    for inp in &tx.inputs {
-                        tx_utxos.insert(format!("{}:{}", inp.txid, inp.vout));
-                    }
-
-                    // Check if any pending transaction uses the same UTXOs
-                    for pending_tx in &mempool.pending {
-                        for pending_inp in &pending_tx.inputs {
-                            let pending_utxo = format!("{}:{}", pending_inp.txid, pending_inp.vout);
-                            if tx_utxos.contains(&pending_utxo) {
-                                log::warn!(
-                                    "Double-spend attempt: TX {} tries to use UTXO {} already used by pending TX {}",
-                                    tx.txid, pending_utxo, pending_tx.txid
-                                );
-                                return Ok::<_, warp::Rejection>(with_status(
-                                    warp::reply::json(&serde_json::json!({
-                                        "status": "error",
-                                        "message": format!("Double-spend: UTXO {} already used in mempool", pending_utxo)
-                                    })),
-                                    StatusCode::BAD_REQUEST,
-                                ));
-                            }
-                        }
-                    }
+            // Security: Check for double-spending in mempool. A conflicting tx
+            // may replace the pending ones if it pays more (replace-by-fee)
+            let conflict_fees = state.conflict_fees(&tx);
 
-                    let now = chrono::Utc::now().timestamp();
-                    mempool.seen_tx.insert(tx.txid.clone(), now);
-                    mempool.pending.push(tx.clone());
+            let mut mempool = state.mempool.lock().unwrap();
 
-                    // ---- broadcast to peers (async) ----
-                    let p2p_clone = p2p.clone();
-                    let tx_clone = tx.clone();
+            // Duplicate protection
+            if mempool.seen_tx.contains_key(&tx.txid) {
+                log::info!("Duplicate TX {}", tx.txid);
+                return Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "status": "duplicate"
+                    })),
+                    StatusCode::OK,
+                ));
+            }
 
-                    tokio::spawn(async move {
-                        p2p_clone.broadcast_tx(&tx_clone).await;
-                    });
-                }
-                _ => {
-                    log::warn!("TX {} signature invalid", tx.txid);
+            let now = chrono::Utc::now().timestamp();
+            let replaced = match mempool.replace_conflicting(&tx, fee, report.size, &conflict_fees, now)
+            {
+                Ok(replaced) => replaced,
+                Err(message) => {
+                    log::warn!("TX {} rejected: {}", tx.txid, message);
                     return Ok::<_, warp::Rejection>(with_status(
                         warp::reply::json(&serde_json::json!({
                             "status": "error",
-                            "message": "invalid signature"
+                            "message": message
                         })),
                         StatusCode::BAD_REQUEST,
                     ));
                 }
+            };
+            if !replaced.is_empty() {
+                log::info!("TX {} replaces pending {:?} by fee", tx.txid, replaced);
             }
 
+            mempool.seen_tx.insert(tx.txid.clone(), now);
+            mempool.pending.push(tx.clone());
+            state.notify_tx(&tx);
+            drop(mempool);
+
+            let size = report.size.max(1);
+            let entry = (tx.txid.clone(), fee / U256::from(size), size);
+            let mempool_estimate = admitted_status(&state, &fee_rates, entry, &replaced);
+
+            // ---- broadcast to peers (async) ----
+            let p2p_clone = p2p.clone();
+            let tx_clone = tx.clone();
+
+            tokio::spawn(async move {
+                p2p_clone.broadcast_tx(&tx_clone).await;
+            });
+
             Ok::<_, warp::Rejection>(with_status(
                 warp::reply::json(&serde_json::json!({
                     "status": "ok",
-                    "message": "tx queued"
+                    "message": "tx queued",
+                    // Policy warnings (never a reason to reject)
+                    "warnings": report.warnings,
+                    "replaced": replaced,
+                    "mempool": mempool_estimate
                 })),
                 StatusCode::OK,
             ))
         });
 
+    // -------------------------------
+    // POST /tx/validate  (dry run of /tx: nothing is queued or relayed)
+    // -------------------------------
+    let validate_tx = warp::path!("tx" / "validate")
+        .and(warp::post())
+        .and(per_ip_limit.clone())
+        .and(warp::body::bytes())
+        .and(node_filter.clone())
+        .and_then(move |body: bytes::Bytes, node: NodeHandle| async move {
+            let tx = match bincode::decode_from_slice::<Transaction, _>(&body, *BINCODE_CONFIG) {
+                Ok((tx, _)) => tx,
+                Err(_) => return Ok::<_, warp::Rejection>(bad_request("invalid bincode")),
+            };
+            let verdict = validate_transaction(&node, &tx, min_confirmations_for_spend);
+            Ok(with_status(warp::reply::json(&verdict), StatusCode::OK))
+        });
+
     // -------------------------------
     // POST /tx/relay  (node -> node)
     // -------------------------------
@@ -570,7 +742,7 @@ pub async fn run_server(
             let state = node.clone();
 
             // Verify signature + fee
-            if !tx.verify_signatures().unwrap_or(false) {
+            if !SIGNATURE_CACHE.verify(&tx).unwrap_or(false) {
                 log::warn!("relay invalid signature");
                 return Ok::<_, warp::Rejection>(with_status(
                     warp::reply::json(&serde_json::json!({"status":"invalid_signature"})),
@@ -579,8 +751,7 @@ pub async fn run_server(
             }
             
             // Security: Validate fee for relayed transactions
-            let mut input_sum = U256::zero();
-            let mut output_sum = U256::zero();
+            let mut input_amounts = Vec::with_capacity(tx.inputs.len());
             
             {
                 let bc = state.bc.lock().unwrap();
@@ -590,19 +761,18 @@ pub async fn run_server(
                         if let Ok((utxo, _)) =
                             bincode::decode_from_slice::<Utxo, _>(&blob, *BINCODE_CONFIG)
                         {
-                            input_sum = input_sum + utxo.amount();
+                            input_amounts.push(utxo.amount());
                         }
                     }
                 }
             }
             
-            for out in &tx.outputs {
-                output_sum = output_sum + out.amount();
-            }
-            
-            let fee = if input_sum >= output_sum { input_sum - output_sum } else { U256::zero() };
+            // Overflowing sums count as no fee, so the tx is refused below
+            let fee = checked_sum(input_amounts)
+                .and_then(|input_sum| tx.fee(input_sum))
+                .unwrap_or_else(U256::zero);
             let tx_blob = bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap();
-            let min_fee = Astram_core::config::calculate_min_fee(tx_blob.len());
+            let min_fee = state.mempool.lock().unwrap().min_relay_fee(tx_blob.len());
             
             if fee >= min_fee {
                 let mut mempool = state.mempool.lock().unwrap();
@@ -620,6 +790,7 @@ pub async fn run_server(
                 mempool.seen_tx.insert(tx.txid.clone(), now);
 
                 log::info!("relay accepted tx {} (fee: {} >= {})", tx.txid, fee, min_fee);
+                state.notify_tx(&tx);
                 mempool.pending.push(tx);
             } else {
                 log::warn!("relay rejected tx {}: fee too low ({} < {})", tx.txid, fee, min_fee);
@@ -666,25 +837,20 @@ pub async fn run_server(
                 map
             };
 
+            // Per-transaction fees (same order as the transactions) let block template
+            // builders rank by fee rate
             let mut total_fees = U256::zero();
+            let mut fees = Vec::with_capacity(txs.len());
             for tx in &txs {
-                let mut input_sum = U256::zero();
-                let mut output_sum = U256::zero();
-
-                for inp in &tx.inputs {
-                    let ukey = format!("u:{}:{}", inp.txid, inp.vout);
-                    if let Some(amount) = utxo_amounts.get(&ukey) {
-                        input_sum = input_sum + *amount;
-                    }
-                }
-
-                for out in &tx.outputs {
-                    output_sum = output_sum + out.amount();
-                }
-
-                if input_sum >= output_sum {
-                    total_fees = total_fees + (input_sum - output_sum);
-                }
+                let input_sum = checked_sum(tx.inputs.iter().filter_map(|inp| {
+                    utxo_amounts
+                        .get(&format!("u:{}:{}", inp.txid, inp.vout))
+                        .copied()
+                }));
+
+                let fee = input_sum.and_then(|sum| tx.fee(sum)).unwrap_or_default();
+                total_fees = total_fees.saturating_add(fee);
+                fees.push(format!("0x{:x}", fee));
             }
 
             let bincode_bytes = bincode::encode_to_vec(&txs, *BINCODE_CONFIG).unwrap();
@@ -693,10 +859,84 @@ pub async fn run_server(
             Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
                 "count": txs.len(),
                 "transactions_b64": encoded,
-                "total_fees": format!("0x{:x}", total_fees)
+                "total_fees": format!("0x{:x}", total_fees),
+                "fees": fees
             })))
         });
 
+    // -------------------------------
+    // GET /mempool/feerates - Fee-per-byte percentiles of pending transactions, so
+    // wallets can pick a competitive fee without decoding the mempool
+    // -------------------------------
+    let get_mempool_feerates = warp::path!("mempool" / "feerates")
+        .and(warp::get())
+        .and(node_filter.clone())
+        .and(fee_rates_filter.clone())
+        .and_then(
+            move |node: NodeHandle, fee_rates: std::sync::Arc<FeeRateCache>| async move {
+                let min_relay_fee_rate = node.mempool.lock().unwrap().min_relay_fee_rate();
+                let body = mempool_fee_rates(&fee_rates.get(&node), min_relay_fee_rate);
+                Ok::<_, warp::Rejection>(with_status(warp::reply::json(&body), StatusCode::OK))
+            },
+        );
+
+    // -------------------------------
+    // GET /mempool/graph?limit=N - Pending transactions as a dependency graph
+    // (nodes with fee/size, edges from a mempool tx to the mempool tx spending it)
+    // -------------------------------
+    let get_mempool_graph = warp::path!("mempool" / "graph")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(node_filter.clone())
+        .and_then(|params: HashMap<String, String>, node: NodeHandle| async move {
+            let limit = match params.get("limit").map(|v| v.parse::<usize>()) {
+                None => MAX_MEMPOOL_GRAPH_NODES,
+                Some(Ok(v)) => v.min(MAX_MEMPOOL_GRAPH_NODES),
+                Some(Err(_)) => {
+                    return Ok::<_, warp::Rejection>(bad_request("invalid 'limit'"));
+                }
+            };
+            let (txs, total) = {
+                let mempool = node.mempool.lock().unwrap();
+                let txs: Vec<Transaction> = mempool.pending.iter().take(limit).cloned().collect();
+                (txs, mempool.pending.len())
+            };
+
+            // Amounts of inputs spending confirmed outputs; mempool parents are resolved
+            // from the transactions themselves
+            let in_graph: std::collections::HashSet<&str> =
+                txs.iter().map(|tx| tx.txid.as_str()).collect();
+            let utxo_amounts = {
+                let bc = node.bc.lock().unwrap();
+                let mut map = HashMap::new();
+                for inp in txs.iter().flat_map(|tx| &tx.inputs) {
+                    if in_graph.contains(inp.txid.as_str()) {
+                        continue;
+                    }
+                    let ukey = format!("u:{}:{}", inp.txid, inp.vout);
+                    if let Ok(Some(blob)) = bc.db.get(ukey.as_bytes()) {
+                        if let Ok((utxo, _)) =
+                            bincode::decode_from_slice::<Utxo, _>(&blob, *BINCODE_CONFIG)
+                        {
+                            map.insert(ukey, utxo.amount());
+                        }
+                    }
+                }
+                map
+            };
+
+            let (nodes, edges) = mempool_graph(&txs, &utxo_amounts);
+            Ok::<_, warp::Rejection>(with_status(
+                warp::reply::json(&serde_json::json!({
+                    "total": total,
+                    "truncated": total > txs.len(),
+                    "nodes": nodes,
+                    "edges": edges,
+                })),
+                StatusCode::OK,
+            ))
+        });
+
     // -------------------------------
     // POST /mining/submit - Submit a mined block
     // -------------------------------
@@ -711,80 +951,200 @@ pub async fn run_server(
         .and(node_filter.clone())
         .and(p2p_filter.clone())
         .and(chain_filter.clone())
-        .and_then(|req: SubmitBlockRequest, node: NodeHandle, p2p: std::sync::Arc<PeerManager>, chain_state: std::sync::Arc<std::sync::Mutex<ChainState>>| async move {
-            let bytes = match general_purpose::STANDARD.decode(req.block_b64.as_bytes()) {
-                Ok(b) => b,
-                Err(e) => {
-                    return Ok::<_, warp::Rejection>(with_status(
+        .and_then(
+            |req: SubmitBlockRequest,
+             node: NodeHandle,
+             p2p: std::sync::Arc<PeerManager>,
+             chain_state: std::sync::Arc<std::sync::Mutex<ChainState>>| async move {
+                let bytes = match general_purpose::STANDARD.decode(req.block_b64.as_bytes()) {
+                    Ok(b) => b,
+                    Err(e) => {
+                        return Ok::<_, warp::Rejection>(with_status(
+                            warp::reply::json(&serde_json::json!({
+                                "status": "error",
+                                "message": format!("invalid base64: {}", e)
+                            })),
+                            StatusCode::BAD_REQUEST,
+                        ));
+                    }
+                };
+
+                let (block, _) =
+                    match bincode::decode_from_slice::<Block, _>(&bytes, *BINCODE_CONFIG) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            return Ok::<_, warp::Rejection>(with_status(
+                                warp::reply::json(&serde_json::json!({
+                                    "status": "error",
+                                    "message": format!("invalid block bincode: {}", e)
+                                })),
+                                StatusCode::BAD_REQUEST,
+                            ));
+                        }
+                    };
+
+                match commit_mined_block(&node, p2p, &chain_state, &block) {
+                    Ok(()) => Ok::<_, warp::Rejection>(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "status": "ok",
+                            "hash": block.hash,
+                            "height": block.header.index
+                        })),
+                        StatusCode::OK,
+                    )),
+                    Err(e) => Ok::<_, warp::Rejection>(with_status(
                         warp::reply::json(&serde_json::json!({
                             "status": "error",
-                            "message": format!("invalid base64: {}", e)
+                            "message": format!("block rejected: {}", e)
                         })),
                         StatusCode::BAD_REQUEST,
-                    ));
+                    )),
                 }
-            };
+            },
+        );
 
-            let (block, _) = match bincode::decode_from_slice::<Block, _>(&bytes, *BINCODE_CONFIG)
-            {
-                Ok(v) => v,
-                Err(e) => {
+    // GET /mining/template - Header template for solo miners: the next block on our tip,
+    // paying this node's miner address. Solve it and POST the nonce to /mining/solution
+    // from the same IP; each IP holds only a few jobs at once.
+    let get_work_template = warp::path!("mining" / "template")
+        .and(warp::get())
+        .and(per_ip_limit.clone())
+        .and(warp::addr::remote())
+        .and(node_filter.clone())
+        .and(meta_filter.clone())
+        .and(work_filter.clone())
+        .and_then(
+            |remote: Option<std::net::SocketAddr>,
+             node: NodeHandle,
+             node_meta: std::sync::Arc<NodeMeta>,
+             templates: std::sync::Arc<getwork::WorkTemplates>| async move {
+                let miner_address = node_meta.miner_address.lock().unwrap().clone();
+                if miner_address.is_empty() {
                     return Ok::<_, warp::Rejection>(with_status(
                         warp::reply::json(&serde_json::json!({
-                            "status": "error",
-                            "message": format!("invalid block bincode: {}", e)
+                            "error": "node has no miner address"
                         })),
-                        StatusCode::BAD_REQUEST,
+                        StatusCode::SERVICE_UNAVAILABLE,
                     ));
                 }
-            };
-
-            let state = node.clone();
-            match state.bc.lock().unwrap().validate_and_insert_block(&block) {
-                Ok(_) => {
-                    {
-                        let mut chain = chain_state.lock().unwrap();
-                        chain.blockchain.push(block.clone());
-                        chain.enforce_memory_limit();
-                    }
-                    p2p.set_my_height(block.header.index + 1);
-
-                    let now = chrono::Utc::now().timestamp();
-                    {
-                        let mut chain = chain_state.lock().unwrap();
-                        chain.recently_mined_blocks.insert(block.hash.clone(), now);
-                        chain
-                            .recently_mined_blocks
-                            .retain(|_, &mut timestamp| now - timestamp < 300);
+                let block = match templates.template(&node, &miner_address) {
+                    Ok(block) => block,
+                    Err(e) => {
+                        log::error!("[ERROR] Failed to build mining template: {}", e);
+                        return Ok(with_status(
+                            warp::reply::json(&serde_json::json!({
+                                "error": format!("failed to build template: {}", e)
+                            })),
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                        ));
                     }
+                };
 
-                    let block_to_broadcast = block.clone();
-                    tokio::spawn(async move {
-                        p2p.broadcast_block(&block_to_broadcast).await;
-                    });
+                let header = &block.header;
+                let header_bytes = bincode::encode_to_vec(header, *BINCODE_CONFIG).unwrap();
+                let target = Astram_core::Blockchain::compact_to_target(header.difficulty);
+                let mut reply = serde_json::json!({
+                    "index": header.index,
+                    "prev_hash": header.previous_hash,
+                    "merkle_root": header.merkle_root,
+                    "timestamp": header.timestamp,
+                    "bits": format!("0x{:08x}", header.difficulty),
+                    "target": format!("{:064x}", target),
+                    "transactions": block.transactions.len(),
+                    "coinbase_value": format!("0x{:x}", block.transactions[0].outputs[0].amount()),
+                    "header": general_purpose::STANDARD.encode(&header_bytes),
+                    "encoding": "bincode+base64",
+                    "expires_in": getwork::WORK_TEMPLATE_TTL.as_secs(),
+                });
+                let job_id = templates.insert(block, remote.map(|addr| addr.ip()));
+                reply["job_id"] = serde_json::json!(job_id);
+                Ok(with_status(warp::reply::json(&reply), StatusCode::OK))
+            },
+        );
+
+    // POST /mining/solution - {job_id, nonce, timestamp?} for a /mining/template job; the
+    // node rebuilds the header, checks the work and submits the full block
+    #[derive(Deserialize)]
+    struct WorkSolution {
+        job_id: String,
+        nonce: u64,
+        timestamp: Option<i64>,
+    }
 
-                    Ok::<_, warp::Rejection>(with_status(
-                        warp::reply::json(&serde_json::json!({
-                            "status": "ok",
-                            "hash": block.hash,
-                            "height": block.header.index
-                        })),
+    let submit_work_solution = warp::path!("mining" / "solution")
+        .and(warp::post())
+        .and(per_ip_limit.clone())
+        .and(warp::addr::remote())
+        .and(warp::body::content_length_limit(4 * 1024))
+        .and(warp::body::json())
+        .and(node_filter.clone())
+        .and(p2p_filter.clone())
+        .and(chain_filter.clone())
+        .and(work_filter.clone())
+        .and_then(
+            |remote: Option<std::net::SocketAddr>,
+             solution: WorkSolution,
+             node: NodeHandle,
+             p2p: std::sync::Arc<PeerManager>,
+             chain_state: std::sync::Arc<std::sync::Mutex<ChainState>>,
+             templates: std::sync::Arc<getwork::WorkTemplates>| async move {
+                let tip = node.bc.lock().unwrap().chain_tip.clone();
+                let tip = tip.unwrap_or_default();
+                let solved = templates.solve(
+                    &solution.job_id,
+                    remote.map(|addr| addr.ip()),
+                    solution.nonce,
+                    solution.timestamp,
+                    &tip,
+                );
+                let block = match solved {
+                    Ok(block) => block,
+                    Err(e) => {
+                        let (status, message) = match e {
+                            getwork::SolutionError::UnknownJob => {
+                                (StatusCode::NOT_FOUND, "unknown or expired job")
+                            }
+                            getwork::SolutionError::Stale => {
+                                (StatusCode::CONFLICT, "stale job: the chain tip has moved")
+                            }
+                            getwork::SolutionError::InsufficientWork => {
+                                (StatusCode::BAD_REQUEST, "hash does not meet the target")
+                            }
+                        };
+                        return Ok::<_, warp::Rejection>(with_status(
+                            warp::reply::json(&serde_json::json!({
+                                "status": "error",
+                                "message": message
+                            })),
+                            status,
+                        ));
+                    }
+                };
+
+                match commit_mined_block(&node, p2p, &chain_state, &block) {
+                    Ok(()) => Ok(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "status": "ok",
+                            "hash": block.hash,
+                            "height": block.header.index
+                        })),
                         StatusCode::OK,
-                    ))
+                    )),
+                    Err(e) => Ok(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "status": "error",
+                            "message": format!("block rejected: {}", e)
+                        })),
+                        StatusCode::BAD_REQUEST,
+                    )),
                 }
-                Err(e) => Ok::<_, warp::Rejection>(with_status(
-                    warp::reply::json(&serde_json::json!({
-                        "status": "error",
-                        "message": format!("block rejected: {}", e)
-                    })),
-                    StatusCode::BAD_REQUEST,
-                )),
-            }
-        });
+            },
+        );
 
     // GET /status
     let status = warp::path("status")
         .and(warp::get())
+        .and(per_ip_limit.clone())
         .and(node_filter.clone())
         .and(chain_filter.clone())
         .and_then(|node: NodeHandle, chain_state: std::sync::Arc<std::sync::Mutex<ChainState>>| async move {
@@ -823,15 +1183,123 @@ pub async fn run_server(
             }
         });
 
+    // GET /address/{address}/utxos?cursor=&limit= - UTXOs largest first; the first page
+    // (no cursor) also carries the count and total, and each page the next cursor
     let get_utxos = warp::path!("address" / String / "utxos")
         .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
         .and(node_filter.clone())
-        .and_then(|address: String, node: NodeHandle| async move {
-            match node.bc.lock().unwrap().get_utxos(&address) {
-                Ok(list) => Ok::<_, warp::Rejection>(warp::reply::json(&list)),
+        .and_then(move |address: String, params: HashMap<String, String>, node: NodeHandle| async move {
+            if let Some(refused) = offset_paging_refused(&params) {
+                return Ok::<_, warp::Rejection>(refused);
+            }
+            let cursor = params.get("cursor").map(String::as_str);
+            let limit = match params.get("limit").map(|v| v.parse::<usize>()) {
+                None => MAX_UTXOS_PER_PAGE,
+                Some(Ok(v)) => v.min(MAX_UTXOS_PER_PAGE),
+                Some(Err(_)) => return Ok(bad_request("invalid 'limit'")),
+            };
+
+            let bc = node.bc.lock().unwrap();
+            let totals = match cursor {
+                None => bc.get_utxo_totals(&address).map(Some),
+                Some(_) => Ok(None),
+            };
+            match totals.and_then(|totals| Ok((totals, bc.get_utxo_page(&address, cursor, limit)?))) {
+                Ok((totals, page)) => {
+                    let tip_height = bc.get_next_index().unwrap_or(0).checked_sub(1);
+                    let list: Vec<serde_json::Value> = page
+                        .utxos
+                        .iter()
+                        .map(|u| utxo_json(u, tip_height, min_confirmations_for_spend))
+                        .collect();
+                    let mut body = serde_json::json!({
+                        "address": address,
+                        "limit": limit,
+                        "utxos": list,
+                        "next_cursor": page.next_cursor,
+                    });
+                    if let Some((count, total_amount)) = totals {
+                        body["count"] = serde_json::json!(count);
+                        body["total_amount"] = serde_json::json!(format!("0x{:x}", total_amount));
+                    }
+                    Ok(with_status(warp::reply::json(&body), StatusCode::OK))
+                }
                 Err(e) => {
                     log::warn!("UTXO lookup failed {}: {:?}", address, e);
-                    Ok::<_, warp::Rejection>(warp::reply::json(&Vec::<Utxo>::new()))
+                    Ok(with_status(
+                        warp::reply::json(&serde_json::json!({ "error": "UTXO lookup failed" })),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    ))
+                }
+            }
+        });
+
+    // GET /address/{address}/transactions?cursor=&limit= - confirmed history, newest
+    // first; the first page (no cursor) also carries the total, and each page the next
+    // cursor
+    let get_address_transactions = warp::path!("address" / String / "transactions")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(node_filter.clone())
+        .and_then(|address: String, params: HashMap<String, String>, node: NodeHandle| async move {
+            if let Some(refused) = offset_paging_refused(&params) {
+                return Ok::<_, warp::Rejection>(refused);
+            }
+            let cursor = params.get("cursor").map(String::as_str);
+            let limit = match params.get("limit").map(|v| v.parse::<usize>()) {
+                None => DEFAULT_HISTORY_PAGE,
+                Some(Ok(v)) => v.min(MAX_HISTORY_PAGE),
+                Some(Err(_)) => return Ok(bad_request("invalid 'limit'")),
+            };
+
+            let result = {
+                let bc = node.bc.lock().unwrap();
+                let total = match cursor {
+                    None => bc.count_address_history(&address).map(Some),
+                    Some(_) => Ok(None),
+                };
+                total.and_then(|total| Ok((total, bc.get_address_history(&address, cursor, limit)?)))
+            };
+            match result {
+                Ok((total, (history, next_cursor))) => {
+                    let transactions: Vec<serde_json::Value> = history
+                        .iter()
+                        .map(|entry| {
+                            // Signed hex: what the address gained (or lost, with the fee)
+                            let delta = if entry.received >= entry.sent {
+                                format!("0x{:x}", entry.received - entry.sent)
+                            } else {
+                                format!("-0x{:x}", entry.sent - entry.received)
+                            };
+                            serde_json::json!({
+                                "txid": entry.txid,
+                                "direction": entry.direction(),
+                                "amount_delta": delta,
+                                "received": format!("0x{:x}", entry.received),
+                                "sent": format!("0x{:x}", entry.sent),
+                                "block_height": entry.height,
+                                "timestamp": entry.timestamp,
+                            })
+                        })
+                        .collect();
+                    let mut body = serde_json::json!({
+                        "address": address.to_lowercase(),
+                        "limit": limit,
+                        "transactions": transactions,
+                        "next_cursor": next_cursor,
+                    });
+                    if let Some(total) = total {
+                        body["total"] = serde_json::json!(total);
+                    }
+                    Ok(with_status(warp::reply::json(&body), StatusCode::OK))
+                }
+                Err(e) => {
+                    log::warn!("History lookup failed for {}: {:?}", address, e);
+                    Ok(with_status(
+                        warp::reply::json(&serde_json::json!({ "error": "history lookup failed" })),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    ))
                 }
             }
         });
@@ -905,6 +1373,90 @@ pub async fn run_server(
                     StatusCode::NOT_FOUND,
                 )),
 
+                Err(e) => Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "error": format!("db error: {}", e)
+                    })),
+                    db_error_status(&e),
+                )),
+            }
+        });
+
+    // GET /tx/{txid}/status - Confirmed depth, or mempool rank and blocks-to-confirm estimate
+    let get_tx_status = warp::path!("tx" / String / "status")
+        .and(warp::get())
+        .and(per_ip_limit.clone())
+        .and(node_filter.clone())
+        .and(fee_rates_filter.clone())
+        .and_then(|txid: String, node: NodeHandle, fee_rates: std::sync::Arc<FeeRateCache>| async move {
+            if let Some(status) = mempool_status(&node, &fee_rates, &txid) {
+                return Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&status),
+                    StatusCode::OK,
+                ));
+            }
+
+            let bc = node.bc.lock().unwrap();
+            match bc.get_transaction_height(&txid) {
+                Ok(Some(height)) => {
+                    let tip = bc.get_next_index().unwrap_or(0).saturating_sub(1);
+                    Ok::<_, warp::Rejection>(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "txid": txid,
+                            "status": "confirmed",
+                            "block_height": height,
+                            "confirmations": tip.saturating_sub(height) + 1
+                        })),
+                        StatusCode::OK,
+                    ))
+                }
+                Ok(None) => Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "error": "tx not found"
+                    })),
+                    StatusCode::NOT_FOUND,
+                )),
+                Err(e) => Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "error": format!("db error: {}", e)
+                    })),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                )),
+            }
+        });
+
+    // GET /tx/{txid}/proof - Merkle inclusion proof against the containing block's header
+    let get_tx_proof = warp::path!("tx" / String / "proof")
+        .and(warp::get())
+        .and(node_filter.clone())
+        .and_then(|txid: String, node: NodeHandle| async move {
+            let result = node.bc.lock().unwrap().get_merkle_proof(&txid);
+            match result {
+                Ok(Some((block, index, proof))) => {
+                    let proof: Vec<_> = proof
+                        .into_iter()
+                        .map(|(hash, is_left)| serde_json::json!({ "hash": hash, "is_left": is_left }))
+                        .collect();
+                    Ok::<_, warp::Rejection>(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "txid": txid,
+                            "block_hash": block.hash,
+                            "block_height": block.header.index,
+                            "merkle_root": block.header.merkle_root,
+                            "index": index,
+                            "proof": proof
+                        })),
+                        StatusCode::OK,
+                    ))
+                }
+
+                Ok(None) => Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "error": "tx not found"
+                    })),
+                    StatusCode::NOT_FOUND,
+                )),
+
                 Err(e) => Ok::<_, warp::Rejection>(with_status(
                     warp::reply::json(&serde_json::json!({
                         "error": format!("db error: {}", e)
@@ -914,17 +1466,123 @@ pub async fn run_server(
             }
         });
 
+    // GET /block/hash/{hash} - Single block by hash
+    let get_block_by_hash = warp::path!("block" / "hash" / String)
+        .and(warp::get())
+        .and(node_filter.clone())
+        .and_then(|hash: String, node: NodeHandle| async move {
+            let hash = hash.strip_prefix("0x").unwrap_or(&hash).to_string();
+            let result = node.bc.lock().unwrap().load_block(&hash);
+            Ok::<_, warp::Rejection>(block_reply(result))
+        });
+
+    // GET /block/height/{n} - Single block by height (resolves i:{n})
+    let get_block_by_height = warp::path!("block" / "height" / u64)
+        .and(warp::get())
+        .and(node_filter.clone())
+        .and_then(|height: u64, node: NodeHandle| async move {
+            let result = {
+                let bc = node.bc.lock().unwrap();
+                match bc.db.get(format!("i:{}", height).as_bytes()) {
+                    Ok(Some(hash_bytes)) => {
+                        let hash = String::from_utf8_lossy(&hash_bytes).to_string();
+                        bc.load_block(&hash)
+                    }
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e.into()),
+                }
+            };
+            Ok::<_, warp::Rejection>(block_reply(result))
+        });
+
+    // GET /emission?from=0&to=999 - Scheduled reward and cumulative supply per height
+    // (computed from the halving schedule; `to` defaults to the current tip)
+    let get_emission = warp::path("emission")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(node_filter.clone())
+        .and_then(|params: HashMap<String, String>, node: NodeHandle| async move {
+            let parse = |key: &str| params.get(key).map(|v| v.parse::<u64>());
+            let from = match parse("from") {
+                None => 0,
+                Some(Ok(v)) => v,
+                Some(Err(_)) => {
+                    return Ok::<_, warp::Rejection>(bad_request("invalid 'from' height"));
+                }
+            };
+            let to = match parse("to") {
+                None => {
+                    let next = node.bc.lock().unwrap().get_next_index().unwrap_or(0);
+                    let tip = next.saturating_sub(1);
+                    tip.max(from).min(from.saturating_add(MAX_EMISSION_SPAN - 1))
+                }
+                Some(Ok(v)) => v,
+                Some(Err(_)) => {
+                    return Ok::<_, warp::Rejection>(bad_request("invalid 'to' height"));
+                }
+            };
+            if to < from || to - from >= MAX_EMISSION_SPAN {
+                return Ok::<_, warp::Rejection>(bad_request(&format!(
+                    "'from'..'to' must be ascending and span at most {} heights",
+                    MAX_EMISSION_SPAN
+                )));
+            }
+
+            Ok::<_, warp::Rejection>(with_status(
+                warp::reply::json(&serde_json::json!({
+                    "from": from,
+                    "to": to,
+                    "halving_interval": Astram_core::config::HALVING_INTERVAL,
+                    "max_supply": format!("0x{:x}", Astram_core::config::max_supply()),
+                    "schedule": emission_schedule(from, to),
+                })),
+                StatusCode::OK,
+            ))
+        });
+
+    // GET /fee/estimate?bytes=N - Minimum fee and a mempool-aware suggestion for a
+    // transaction of N bytes
+    let get_fee_estimate = warp::path!("fee" / "estimate")
+        .and(warp::get())
+        .and(per_ip_limit.clone())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(node_filter.clone())
+        .and(fee_rates_filter.clone())
+        .and_then(|params: HashMap<String, String>, node: NodeHandle, fee_rates: std::sync::Arc<FeeRateCache>| async move {
+            let max_bytes = Astram_core::security::MAX_TX_SIZE;
+            let bytes = match params.get("bytes").map(|v| v.parse::<usize>()) {
+                Some(Ok(bytes)) if (1..=max_bytes).contains(&bytes) => bytes,
+                _ => {
+                    return Ok::<_, warp::Rejection>(bad_request(&format!(
+                        "'bytes' must be a transaction size between 1 and {}",
+                        max_bytes
+                    )));
+                }
+            };
+            Ok::<_, warp::Rejection>(with_status(
+                warp::reply::json(&fee_estimate(&fee_rates.get(&node), bytes)),
+                StatusCode::OK,
+            ))
+        });
+
     // -------------------------------
-    // GET /eth_mapping/:eth_hash - Resolve Ethereum tx hash to Astram txid
+    // GET /eth_mapping/:eth_hash - Resolve Ethereum tx hash to Astram txid (pending
+    // transactions from the in-memory map, mined ones from the DB index)
     let get_eth_mapping = warp::path!("eth_mapping" / String)
         .and(warp::get())
+        .and(node_filter.clone())
         .and(meta_filter.clone())
-        .and_then(|eth_hash: String, node_meta: std::sync::Arc<NodeMeta>| async move {
+        .and_then(|eth_hash: String, node: NodeHandle, node_meta: std::sync::Arc<NodeMeta>| async move {
             // Strip 0x prefix if present
             let eth_hash = eth_hash.strip_prefix("0x").unwrap_or(&eth_hash);
 
-            let mapping = node_meta.eth_to_astram_tx.lock().unwrap();
-            match mapping.get(eth_hash) {
+            let resolved = {
+                let bc = node.bc.lock().unwrap();
+                let mapping = node_meta.eth_to_astram_tx.lock().unwrap();
+                crate::resolve_eth_hash(&bc, &mapping, eth_hash)
+            };
+            match resolved {
                 Some(astram_txid) => {
                     Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
                         "eth_hash": format!("0x{}", eth_hash),
@@ -943,39 +1601,1595 @@ pub async fn run_server(
         });
 
     // -------------------------------
-    // GET / - Dashboard HTML
+    // POST /admin/stats/reset - Zero the block validation counters and the per-peer
+    // invalid block tallies (requires `Authorization: Bearer <ADMIN_TOKEN>`)
+    let stats_reset_token = admin_token.clone();
+    let admin_stats_reset = warp::path!("admin" / "stats" / "reset")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(p2p_filter.clone())
+        .map(
+            move |authorization: Option<String>, p2p: std::sync::Arc<PeerManager>| {
+                let refused = admin_auth_error(&stats_reset_token, authorization.as_deref());
+                if let Some((status, message)) = refused {
+                    return with_status(
+                        warp::reply::json(&serde_json::json!({ "error": message })),
+                        status,
+                    );
+                }
+                let cleared: Vec<_> = Astram_core::security::VALIDATION_STATS
+                    .get_stats()
+                    .into_iter()
+                    .filter(|(_, count)| *count > 0)
+                    .collect();
+                Astram_core::security::VALIDATION_STATS.reset();
+                p2p.reset_block_failures();
+                log::info!("Validation stats reset via /admin/stats/reset");
+                with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "status": "reset",
+                        "cleared": cleared,
+                    })),
+                    StatusCode::OK,
+                )
+            },
+        );
+
+    // -------------------------------
+    // POST /admin/peers/connect {address} and /admin/peers/disconnect {address, ban_secs?}
+    // - Dial or drop a peer by hand; both answer with the updated /peers list
+    // (require `Authorization: Bearer <ADMIN_TOKEN>`)
+    #[derive(Deserialize)]
+    struct PeerAdminRequest {
+        address: String,
+        /// Disconnect only: also refuse the peer's IP for this many seconds
+        ban_secs: Option<u64>,
+    }
+
+    let peers_connect_token = admin_token.clone();
+    let admin_peers_connect = warp::path!("admin" / "peers" / "connect")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::content_length_limit(4 * 1024))
+        .and(warp::body::json())
+        .and(p2p_filter.clone())
+        .and_then(
+            move |authorization: Option<String>,
+                  request: PeerAdminRequest,
+                  p2p: std::sync::Arc<PeerManager>| {
+                let refused = admin_auth_error(&peers_connect_token, authorization.as_deref());
+                async move {
+                    if let Some((status, message)) = refused {
+                        return Ok::<_, warp::Rejection>(with_status(
+                            warp::reply::json(&serde_json::json!({ "error": message })),
+                            status,
+                        ));
+                    }
+                    if request.address.parse::<SocketAddr>().is_err() {
+                        return Ok(with_status(
+                            warp::reply::json(&serde_json::json!({
+                                "error": "address must be ip:port"
+                            })),
+                            StatusCode::BAD_REQUEST,
+                        ));
+                    }
+                    let wait = std::time::Duration::from_secs(
+                        crate::p2p::manager::ADMIN_CONNECT_WAIT_SECS,
+                    );
+                    let connected = p2p.clone().connect_peer_and_wait(&request.address, wait);
+                    let (status, outcome) = match connected.await {
+                        Ok(true) => (StatusCode::OK, "connected"),
+                        Ok(false) => (StatusCode::ACCEPTED, "connecting"),
+                        Err(e) => {
+                            log::warn!("Admin connect to {} failed: {}", request.address, e);
+                            return Ok(with_status(
+                                warp::reply::json(&serde_json::json!({
+                                    "error": format!("connect failed: {}", e),
+                                    "peers": p2p.peer_summaries(),
+                                })),
+                                StatusCode::BAD_GATEWAY,
+                            ));
+                        }
+                    };
+                    log::info!("Admin connect to {}: {}", request.address, outcome);
+                    Ok(with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "status": outcome,
+                            "peers": p2p.peer_summaries(),
+                        })),
+                        status,
+                    ))
+                }
+            },
+        );
+
+    let peers_disconnect_token = admin_token.clone();
+    let admin_peers_disconnect = warp::path!("admin" / "peers" / "disconnect")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::content_length_limit(4 * 1024))
+        .and(warp::body::json())
+        .and(p2p_filter.clone())
+        .map(
+            move |authorization: Option<String>,
+                  request: PeerAdminRequest,
+                  p2p: std::sync::Arc<PeerManager>| {
+                let refused = admin_auth_error(&peers_disconnect_token, authorization.as_deref());
+                if let Some((status, message)) = refused {
+                    return with_status(
+                        warp::reply::json(&serde_json::json!({ "error": message })),
+                        status,
+                    );
+                }
+                let max_ban = crate::p2p::manager::MAX_BAN_DURATION_SECS;
+                if request.ban_secs.is_some_and(|secs| secs > max_ban) {
+                    let message = format!("ban_secs must be at most {}", max_ban);
+                    return with_status(
+                        warp::reply::json(&serde_json::json!({ "error": message })),
+                        StatusCode::BAD_REQUEST,
+                    );
+                }
+                let ban = request.ban_secs.map(std::time::Duration::from_secs);
+                let closed = p2p.disconnect_peer(&request.address, ban);
+                // Banning an IP we aren't connected to is still worth doing
+                let status = if closed == 0 && ban.is_none() {
+                    StatusCode::NOT_FOUND
+                } else {
+                    StatusCode::OK
+                };
+                with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "closed": closed,
+                        "banned_secs": request.ban_secs,
+                        "peers": p2p.peer_summaries(),
+                    })),
+                    status,
+                )
+            },
+        );
+
+    // -------------------------------
+    // POST /admin/backup - Snapshot the chain DB into BACKUP_DIR while the node keeps
+    // running (requires `Authorization: Bearer <ADMIN_TOKEN>`)
+    let admin_backup = warp::path!("admin" / "backup")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(node_filter.clone())
+        .and_then(move |authorization: Option<String>, node: NodeHandle| {
+            let refused = admin_auth_error(&admin_token, authorization.as_deref());
+            let backup_dir = backup_dir.clone();
+            async move {
+                if let Some((status, message)) = refused {
+                    return Ok::<_, warp::Rejection>(with_status(
+                        warp::reply::json(&serde_json::json!({ "error": message })),
+                        status,
+                    ));
+                }
+                match create_backup(&node, &backup_dir) {
+                    Ok(body) => Ok(with_status(warp::reply::json(&body), StatusCode::OK)),
+                    Err(e) => {
+                        log::error!("Backup failed: {}", e);
+                        Ok(with_status(
+                            warp::reply::json(&serde_json::json!({
+                                "error": format!("backup failed: {}", e)
+                            })),
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                        ))
+                    }
+                }
+            }
+        });
+
+    // -------------------------------
+    // GET / - Dashboard HTML (loaded once at startup; built-in page if the file is missing)
+    let dashboard_html = load_dashboard(&dashboard_path);
     let dashboard = warp::path::end()
         .and(warp::get())
-        .map(|| {
-            warp::reply::html(include_str!("../../web/dashboard.html"))
-        });
+        .map(move || warp::reply::html(dashboard_html.clone()));
 
     // -------------------------------
     // combine routes
     // NOTE: Order matters! More specific routes must come before general ones
-    let routes = dashboard
+    let limited_routes = dashboard
         .or(get_chain_db)          // /blockchain/db - specific
         .or(get_chain_memory)      // /blockchain/memory - specific
         .or(get_chain_range)       // /blockchain/range - specific
         .or(get_chain)             // /blockchain - general (must be last)
         .or(get_counts)
         .or(get_status)
+        .or(get_peers)
         .or(debug_counts)
-        .or(health_check)
+        .or(debug_checkpoints)
+        .or(validate_tx)           // /tx/validate - before /tx, which matches any suffix
         .or(post_tx)
         .or(relay_tx)
+        .or(get_mempool_graph)     // /mempool/graph - before /mempool, which matches any suffix
+        .or(get_mempool_feerates)  // /mempool/feerates - likewise
         .or(get_mempool)
         .or(submit_block)
+        .or(get_work_template)
+        .or(submit_work_solution)
         .or(status)
         .or(get_balance)
         .or(get_address_info)
         .or(get_utxos)
+        .or(get_address_transactions)
+        .or(get_headers)
+        .or(get_blocks_by_time)
         .or(get_tx)
+        .or(get_tx_proof)
+        .or(get_tx_status)
+        .or(get_block_by_hash)
+        .or(get_block_by_height)
+        .or(get_emission)
+        .or(get_fee_estimate)
         .or(get_eth_mapping)
-        .with(warp::log("Astram::http"))
+        .or(admin_stats_reset)
+        .or(admin_peers_connect)
+        .or(admin_peers_disconnect)
+        .or(admin_backup)
+        .boxed();
+
+    // Everything but /health and /metrics counts toward the in-flight cap, so monitoring
+    // can still see a node that is shedding load
+    let in_flight =
+        std::sync::Arc::new(tokio::sync::Semaphore::new(max_concurrent_requests.max(1)));
+    let routes = health_check
+        .or(metrics)
+        .or(concurrency_limited(limited_routes, in_flight)
+            .recover(rate_limit::recover_rate_limited)
+            .unify())
+        .with(warp::log::custom(move |info| {
+            if access_log {
+                log::debug!(
+                    target: "Astram::http",
+                    "{} {} {} {:?}",
+                    info.method(),
+                    info.path(),
+                    info.status().as_u16(),
+                    info.elapsed()
+                );
+            }
+        }))
         .boxed();
 
     println!("HTTP server running at http://{}", bind_addr);
     warp::serve(routes).run(bind_addr).await;
 }
 
+/// Default cap on HTTP API requests handled at once (HTTP_MAX_CONCURRENT_REQUESTS)
+pub const DEFAULT_HTTP_MAX_CONCURRENT_REQUESTS: usize = 64;
+
+/// Rejection for a request that arrives while every in-flight slot is taken
+#[derive(Debug)]
+struct Overloaded;
+
+impl warp::reject::Reject for Overloaded {}
+
+/// `routes` with at most `in_flight`'s permits worth of requests handled at once. A
+/// request that finds no free permit is answered 503 straight away rather than queued,
+/// so a flood can't pile up work behind the expensive handlers.
+fn concurrency_limited<F, T>(
+    routes: F,
+    in_flight: std::sync::Arc<tokio::sync::Semaphore>,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone
+where
+    F: Filter<Extract = (T,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+    T: warp::Reply,
+{
+    warp::any()
+        .and_then(move || {
+            let in_flight = in_flight.clone();
+            async move {
+                in_flight
+                    .try_acquire_owned()
+                    .map_err(|_| warp::reject::custom(Overloaded))
+            }
+        })
+        .and(routes)
+        // The permit is held until the handler has produced its reply
+        .map(|_permit: tokio::sync::OwnedSemaphorePermit, reply: T| reply.into_response())
+        .recover(|err: warp::Rejection| async move {
+            if err.find::<Overloaded>().is_some() {
+                let busy = warp::reply::json(&serde_json::json!({
+                    "error": "server busy, retry shortly"
+                }));
+                Ok(warp::Reply::into_response(with_status(
+                    busy,
+                    StatusCode::SERVICE_UNAVAILABLE,
+                )))
+            } else {
+                Err(err)
+            }
+        })
+        .unify()
+}
+
+/// Served at `/` when the dashboard file cannot be read
+const FALLBACK_DASHBOARD_HTML: &str = "<!DOCTYPE html>
+<html>
+<head><meta charset=\"utf-8\"><title>Astram Node</title></head>
+<body>
+<h1>Astram Node</h1>
+<p>The dashboard asset is not installed. Set DASHBOARD_PATH in nodeSettings.conf to enable it.</p>
+<ul>
+<li><a href=\"/status\">/status</a></li>
+<li><a href=\"/health\">/health</a></li>
+<li><a href=\"/mempool\">/mempool</a></li>
+</ul>
+</body>
+</html>
+";
+
+/// Read the dashboard HTML from disk, falling back to a minimal built-in page
+fn load_dashboard(path: &Path) -> String {
+    match std::fs::read_to_string(path) {
+        Ok(html) => {
+            info!("Serving dashboard from {:?}", path);
+            html
+        }
+        Err(e) => {
+            log::warn!("Dashboard not loaded from {:?} ({}); using built-in page", path, e);
+            FALLBACK_DASHBOARD_HTML.to_string()
+        }
+    }
+}
+
+/// Blocks on top of (and including) the one that created `utxo`; 0 when there is no chain
+fn utxo_confirmations(utxo: &Utxo, tip_height: Option<u64>) -> u64 {
+    tip_height
+        .and_then(|tip| tip.checked_sub(utxo.height))
+        .map_or(0, |depth| depth + 1)
+}
+
+/// UTXO as JSON plus `confirmations` and `spend_recommended`, which is false while the
+/// output is shallower than `min_confirmations` (a warning only; the spend stays valid)
+fn utxo_json(utxo: &Utxo, tip_height: Option<u64>, min_confirmations: u64) -> serde_json::Value {
+    let confirmations = utxo_confirmations(utxo, tip_height);
+    let mut value = serde_json::to_value(utxo).unwrap_or_default();
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("confirmations".to_string(), serde_json::json!(confirmations));
+        obj.insert(
+            "spend_recommended".to_string(),
+            serde_json::json!(confirmations >= min_confirmations),
+        );
+    }
+    value
+}
+
+/// Recent blocks averaged for the fullness reported alongside confirmation estimates
+const FULLNESS_WINDOW_BLOCKS: u64 = 10;
+
+/// (txid, fee per byte, size) of each pending transaction, as the miner orders them
+fn pending_fee_rates(bc: &Astram_core::Blockchain, pending: &[Transaction]) -> Vec<FeeRateEntry> {
+    pending
+        .iter()
+        .map(|tx| {
+            let size = bincode::encode_to_vec(tx, *BINCODE_CONFIG)
+                .map(|b| b.len())
+                .unwrap_or(0)
+                .max(1);
+            let fee = crate::confirmed_input_fee(bc, tx);
+            (tx.txid.clone(), fee / U256::from(size), size)
+        })
+        .collect()
+}
+
+/// (txid, fee per byte, size) of a pending transaction
+type FeeRateEntry = (String, U256, usize);
+
+/// How long cached fee rates are served before the mempool is rescanned
+const FEE_RATES_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Pending fee rates and recent block fullness as of one tip
+struct FeeRateSnapshot {
+    tip: Option<String>,
+    taken: std::time::Instant,
+    computed_at: i64,
+    entries: Vec<FeeRateEntry>,
+    /// Average size of the last FULLNESS_WINDOW_BLOCKS blocks relative to the
+    /// per-block budget
+    recent_fullness: f64,
+}
+
+/// Fee rates shared by the fee and mempool-status routes, so a request doesn't rescan
+/// the mempool and re-encode recent blocks under the bc lock. A snapshot is served for
+/// FEE_RATES_CACHE_TTL while the tip stays put; a refresh on the same tip only reads
+/// the UTXO set for transactions it hasn't priced yet, and fullness is measured once
+/// per tip.
+#[derive(Default)]
+struct FeeRateCache {
+    snapshot: std::sync::Mutex<Option<std::sync::Arc<FeeRateSnapshot>>>,
+}
+
+impl FeeRateCache {
+    /// The current snapshot, refreshed if stale
+    fn get(&self, node: &NodeHandle) -> std::sync::Arc<FeeRateSnapshot> {
+        let tip = node.bc.lock().unwrap().chain_tip.clone();
+        let previous = self.snapshot.lock().unwrap().clone();
+        match previous {
+            Some(snapshot)
+                if snapshot.tip == tip && snapshot.taken.elapsed() < FEE_RATES_CACHE_TTL =>
+            {
+                snapshot
+            }
+            previous => self.refresh(node, tip, previous),
+        }
+    }
+
+    /// A fresh snapshot including `txid` if it is pending, or None if it isn't
+    fn get_with(&self, node: &NodeHandle, txid: &str) -> Option<std::sync::Arc<FeeRateSnapshot>> {
+        let snapshot = self.get(node);
+        if snapshot.entries.iter().any(|(id, _, _)| id == txid) {
+            return Some(snapshot);
+        }
+        // Admitted since the snapshot was taken?
+        let pending = {
+            let mempool = node.mempool.lock().unwrap();
+            mempool.pending.iter().any(|tx| tx.txid == txid)
+        };
+        if !pending {
+            return None;
+        }
+        let tip = snapshot.tip.clone();
+        Some(self.refresh(node, tip, Some(snapshot)))
+    }
+
+    fn refresh(
+        &self,
+        node: &NodeHandle,
+        tip: Option<String>,
+        previous: Option<std::sync::Arc<FeeRateSnapshot>>,
+    ) -> std::sync::Arc<FeeRateSnapshot> {
+        // Fees priced on the same tip still hold; a new tip may have confirmed parents
+        let previous = previous.filter(|snapshot| snapshot.tip == tip);
+        let known: HashMap<&str, &FeeRateEntry> = previous
+            .iter()
+            .flat_map(|snapshot| snapshot.entries.iter())
+            .map(|entry| (entry.0.as_str(), entry))
+            .collect();
+        let (mut entries, unpriced) = {
+            let mempool = node.mempool.lock().unwrap();
+            let mut entries = Vec::with_capacity(mempool.pending.len());
+            let mut unpriced = Vec::new();
+            for tx in &mempool.pending {
+                match known.get(tx.txid.as_str()) {
+                    Some(entry) => entries.push((*entry).clone()),
+                    None => unpriced.push(tx.clone()),
+                }
+            }
+            (entries, unpriced)
+        };
+
+        let blocks = {
+            let bc = node.bc.lock().unwrap();
+            entries.extend(pending_fee_rates(&bc, &unpriced));
+            // Fullness only changes with the tip
+            if previous.is_none() {
+                recent_blocks(&bc)
+            } else {
+                Vec::new()
+            }
+        };
+        let recent_fullness = match &previous {
+            Some(snapshot) => snapshot.recent_fullness,
+            None => block_fullness(&blocks),
+        };
+
+        let snapshot = std::sync::Arc::new(FeeRateSnapshot {
+            tip,
+            taken: std::time::Instant::now(),
+            computed_at: chrono::Utc::now().timestamp(),
+            entries,
+            recent_fullness,
+        });
+        *self.snapshot.lock().unwrap() = Some(snapshot.clone());
+        snapshot
+    }
+}
+
+/// The last FULLNESS_WINDOW_BLOCKS blocks, oldest first
+fn recent_blocks(bc: &Astram_core::Blockchain) -> Vec<Astram_core::block::Block> {
+    let Some(tip) = bc.get_next_index().unwrap_or(0).checked_sub(1) else {
+        return Vec::new();
+    };
+    let from = tip.saturating_sub(FULLNESS_WINDOW_BLOCKS - 1);
+    bc.get_blocks_range(from, Some(tip)).unwrap_or_default()
+}
+
+/// Average encoded size of `blocks` relative to the per-block budget
+fn block_fullness(blocks: &[Astram_core::block::Block]) -> f64 {
+    if blocks.is_empty() {
+        return 0.0;
+    }
+    let bytes: usize = blocks
+        .iter()
+        .filter_map(|b| bincode::encode_to_vec(b, *BINCODE_CONFIG).ok())
+        .map(|b| b.len())
+        .sum();
+    bytes as f64 / blocks.len() as f64 / crate::TARGET_BLOCK_TX_BYTES as f64
+}
+
+/// Fee-per-byte percentiles of the pending transactions; `min_relay_fee_rate` for
+/// every percentile when the mempool is empty. A fee of `base_min_fee` plus
+/// `min_relay_fee_per_byte` for each byte always clears admission.
+fn mempool_fee_rates(snapshot: &FeeRateSnapshot, min_relay_fee_rate: U256) -> serde_json::Value {
+    let entries = &snapshot.entries;
+    let rates =
+        crate::fee_rate_percentiles(entries, &crate::FEE_RATE_PERCENTILES, min_relay_fee_rate);
+
+    let percentiles: serde_json::Map<String, serde_json::Value> = crate::FEE_RATE_PERCENTILES
+        .iter()
+        .zip(rates)
+        .map(|(p, rate)| (format!("p{}", p), serde_json::json!(format!("0x{:x}", rate))))
+        .collect();
+    serde_json::json!({
+        "mempool_size": entries.len(),
+        "fee_per_byte": percentiles,
+        "min_relay_fee_per_byte": format!("0x{:x}", min_relay_fee_rate),
+        "base_min_fee": format!("0x{:x}", Astram_core::config::BASE_MIN_FEE),
+        "computed_at": snapshot.computed_at,
+    })
+}
+
+/// Point-in-time values exported by `/metrics`
+#[derive(Debug, Default)]
+struct NodeMetrics {
+    height: u64,
+    mempool_txs: usize,
+    mempool_bytes: usize,
+    peers: usize,
+    blocks_mined: u64,
+    difficulty_bits: u32,
+    hashrate: f64,
+    validation_failures: Vec<(String, u64)>,
+    uptime_secs: u64,
+}
+
+/// Snapshot the exported values, taking each lock on its own and only long enough to
+/// read it, like `/status`
+fn collect_metrics(node: &NodeHandle, p2p: &PeerManager, node_meta: &NodeMeta) -> NodeMetrics {
+    use std::sync::atomic::Ordering;
+
+    let height = {
+        let bc = node.bc.lock().unwrap();
+        bc.get_next_index().unwrap_or(0).saturating_sub(1)
+    };
+    let (mempool_txs, mempool_bytes) = {
+        let mut mempool = node.mempool.lock().unwrap();
+        (mempool.pending.len(), mempool.pending_bytes())
+    };
+
+    NodeMetrics {
+        height,
+        mempool_txs,
+        mempool_bytes,
+        peers: p2p.peer_count(),
+        blocks_mined: node.mining.blocks_mined.load(Ordering::Relaxed),
+        difficulty_bits: *node.mining.current_difficulty.lock().unwrap(),
+        hashrate: *node.mining.current_hashrate.lock().unwrap(),
+        validation_failures: Astram_core::security::VALIDATION_STATS.get_stats(),
+        uptime_secs: node_meta.node_start_time.elapsed().as_secs(),
+    }
+}
+
+/// Prometheus text format (version 0.0.4) for `metrics`
+fn render_metrics(metrics: &NodeMetrics) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: &dyn std::fmt::Display| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value);
+    };
+    metric(
+        "astram_block_height",
+        "gauge",
+        "Height of the chain tip",
+        &metrics.height,
+    );
+    metric(
+        "astram_mempool_transactions",
+        "gauge",
+        "Pending transactions",
+        &metrics.mempool_txs,
+    );
+    metric(
+        "astram_mempool_bytes",
+        "gauge",
+        "Encoded size of the pending transactions",
+        &metrics.mempool_bytes,
+    );
+    metric("astram_peers", "gauge", "Connected peers", &metrics.peers);
+    metric(
+        "astram_blocks_mined_total",
+        "counter",
+        "Blocks mined by this node",
+        &metrics.blocks_mined,
+    );
+    metric(
+        "astram_difficulty_bits",
+        "gauge",
+        "Current mining difficulty as compact target bits",
+        &metrics.difficulty_bits,
+    );
+    metric(
+        "astram_hashrate",
+        "gauge",
+        "Local mining hashrate in hashes per second",
+        &metrics.hashrate,
+    );
+    metric(
+        "astram_uptime_seconds",
+        "gauge",
+        "Seconds since the node started",
+        &metrics.uptime_secs,
+    );
+
+    let name = "astram_block_validation_failures_total";
+    let _ = writeln!(out, "# HELP {} Rejected blocks by failure reason", name);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for (reason, count) in &metrics.validation_failures {
+        let _ = writeln!(out, "{}{{reason=\"{}\"}} {}", name, reason, count);
+    }
+    out
+}
+
+/// Minimum and suggested fee for a transaction of `bytes`. The suggestion is the
+/// wallet default, raised when the mempool is busy enough that it would miss the
+/// next block.
+fn fee_estimate(snapshot: &FeeRateSnapshot, bytes: usize) -> serde_json::Value {
+    use Astram_core::config::{calculate_default_fee, calculate_min_fee};
+
+    let entries = &snapshot.entries;
+    let next_block_rate = crate::next_block_fee_rate(entries, bytes, crate::TARGET_BLOCK_TX_BYTES);
+
+    let min_fee = calculate_min_fee(bytes);
+    let suggested_fee = calculate_default_fee(bytes)
+        .max(next_block_rate.saturating_mul(U256::from(bytes)))
+        .max(min_fee);
+
+    serde_json::json!({
+        "bytes": bytes,
+        "min_fee": format!("0x{:x}", min_fee),
+        "suggested_fee": format!("0x{:x}", suggested_fee),
+        "next_block_fee_per_byte": format!("0x{:x}", next_block_rate),
+        "mempool_size": entries.len(),
+    })
+}
+
+/// Fee-rate rank and estimated blocks to confirmation for a pending transaction, or
+/// None if it isn't in the mempool
+fn mempool_status(
+    node: &NodeHandle,
+    fee_rates: &FeeRateCache,
+    txid: &str,
+) -> Option<serde_json::Value> {
+    let snapshot = fee_rates.get_with(node, txid)?;
+    pending_status(&snapshot.entries, snapshot.recent_fullness, txid)
+}
+
+/// `mempool_status` for a transaction `/tx` just admitted, ranked against the cached
+/// rates rather than forcing a refresh: `entry` is added and the transactions it
+/// `replaced` are left out
+fn admitted_status(
+    node: &NodeHandle,
+    fee_rates: &FeeRateCache,
+    entry: FeeRateEntry,
+    replaced: &[String],
+) -> Option<serde_json::Value> {
+    let snapshot = fee_rates.get(node);
+    let txid = entry.0.clone();
+    let mut entries: Vec<FeeRateEntry> = snapshot
+        .entries
+        .iter()
+        .filter(|(id, _, _)| *id != txid && !replaced.contains(id))
+        .cloned()
+        .collect();
+    entries.push(entry);
+    pending_status(&entries, snapshot.recent_fullness, &txid)
+}
+
+/// Rank and confirmation estimate of `txid` among `entries`
+fn pending_status(
+    entries: &[FeeRateEntry],
+    recent_fullness: f64,
+    txid: &str,
+) -> Option<serde_json::Value> {
+    let position = crate::mempool_position(entries, txid, crate::TARGET_BLOCK_TX_BYTES)?;
+    let fee_per_byte = entries
+        .iter()
+        .find(|(id, _, _)| id == txid)
+        .map(|(_, rate, _)| *rate)
+        .unwrap_or_default();
+
+    Some(serde_json::json!({
+        "txid": txid,
+        "status": "pending",
+        "fee_per_byte": fee_per_byte.to_string(),
+        "mempool_rank": position.rank,
+        "mempool_size": entries.len(),
+        "bytes_ahead": position.bytes_through,
+        "estimated_blocks": position.estimated_blocks,
+        "recent_block_fullness": recent_fullness,
+    }))
+}
+
+/// What `check_transaction` learned about a transaction, kept when it is rejected so
+/// `/tx/validate` can still report the fee it would have paid
+#[derive(Default)]
+struct TxReport {
+    /// Input total minus output total, once every input has been found
+    fee: Option<U256>,
+    /// Encoded size in bytes, which the minimum fee is charged on
+    size: usize,
+    /// Policy warnings (never a reason to reject)
+    warnings: Vec<String>,
+}
+
+/// The checks a transaction submitted to `/tx` must pass before the mempool is
+/// consulted: a valid signature, every input an existing and mature UTXO, amounts
+/// that don't overflow, and a fee of at least `MempoolState::min_relay_fee` for its size.
+/// Shared with `/tx/validate` so the dry run can't drift from the real thing.
+fn check_transaction(
+    node: &NodeHandle,
+    tx: &Transaction,
+    min_confirmations_for_spend: u64,
+    report: &mut TxReport,
+) -> Result<(), String> {
+    report.size = bincode::encode_to_vec(tx, *BINCODE_CONFIG)
+        .map(|bytes| bytes.len())
+        .map_err(|e| format!("failed to encode transaction: {}", e))?;
+
+    if tx.is_expired_at(chrono::Utc::now().timestamp()) {
+        return Err(format!(
+            "transaction expired: valid until {}",
+            tx.valid_until.unwrap_or_default()
+        ));
+    }
+
+    // Signature check (remembered so block validation can skip it)
+    if !SIGNATURE_CACHE.verify(tx).unwrap_or(false) {
+        return Err("invalid signature".to_string());
+    }
+
+    let mut input_sum = U256::zero();
+    {
+        let bc = node.bc.lock().unwrap();
+        let next_height = bc.get_next_index().unwrap_or(0);
+        if !bc.params.allows_tx_version(tx.version(), next_height) {
+            return Err(format!(
+                "transaction version {} is not allowed before height {}",
+                tx.version(),
+                bc.params.tx_expiry_height
+            ));
+        }
+        for inp in &tx.inputs {
+            let ukey = format!("u:{}:{}", inp.txid, inp.vout);
+            let utxo = match bc.db.get(ukey.as_bytes()) {
+                Ok(Some(blob)) => bincode::decode_from_slice::<Utxo, _>(&blob, *BINCODE_CONFIG)
+                    .ok()
+                    .map(|(utxo, _)| utxo),
+                _ => None,
+            };
+            let Some(utxo) = utxo else {
+                return Err(format!(
+                    "input {}:{} is not an unspent output",
+                    inp.txid, inp.vout
+                ));
+            };
+            // Coinbase maturity: the next block would reject this spend
+            if !utxo.is_mature_at(next_height) {
+                return Err(format!(
+                    "coinbase output {}:{} is not mature until height {}",
+                    inp.txid,
+                    inp.vout,
+                    utxo.height + Astram_core::config::COINBASE_MATURITY
+                ));
+            }
+            let confirmations = utxo_confirmations(&utxo, next_height.checked_sub(1));
+            if confirmations < min_confirmations_for_spend {
+                report.warnings.push(format!(
+                    "input {}:{} has {} of {} recommended confirmations and could be reorged away",
+                    inp.txid, inp.vout, confirmations, min_confirmations_for_spend
+                ));
+            }
+            input_sum = input_sum
+                .checked_add(utxo.amount())
+                .ok_or_else(|| "input amounts overflow".to_string())?;
+        }
+    }
+
+    if tx.output_total().is_none() {
+        return Err("output amounts overflow".to_string());
+    }
+    let fee = tx
+        .fee(input_sum)
+        .ok_or_else(|| "outputs exceed inputs".to_string())?;
+    report.fee = Some(fee);
+
+    let min_fee = node.mempool.lock().unwrap().min_relay_fee(report.size);
+    if fee < min_fee {
+        return Err(format!(
+            "fee too low: got {} ram, need {} ram",
+            fee, min_fee
+        ));
+    }
+    Ok(())
+}
+
+/// Put `tx`, saved at the last shutdown, back in the mempool through the same checks
+/// and replace-by-fee rules as `/tx`. Returns why it was dropped, if it was.
+pub fn restore_transaction(
+    node: &NodeHandle,
+    tx: &Transaction,
+    min_confirmations_for_spend: u64,
+) -> Result<(), String> {
+    let mut report = TxReport::default();
+    check_transaction(node, tx, min_confirmations_for_spend, &mut report)?;
+
+    // Same lock order as /tx: conflict fees from bc before the mempool lock
+    let conflict_fees = node.conflict_fees(tx);
+    let mut mempool = node.mempool.lock().unwrap();
+    if mempool.seen_tx.contains_key(&tx.txid) {
+        return Err("already seen by this node".to_string());
+    }
+    let now = chrono::Utc::now().timestamp();
+    let fee = report.fee.unwrap_or_default();
+    mempool.replace_conflicting(tx, fee, report.size, &conflict_fees, now)?;
+    mempool.seen_tx.insert(tx.txid.clone(), now);
+    mempool.pending.push(tx.clone());
+    Ok(())
+}
+
+/// `/tx/validate`'s verdict on `tx`: everything `/tx` checks, the mempool double-spend
+/// and replace-by-fee rules included, without queueing or relaying it
+fn validate_transaction(
+    node: &NodeHandle,
+    tx: &Transaction,
+    min_confirmations_for_spend: u64,
+) -> serde_json::Value {
+    let mut report = TxReport::default();
+    let mut replaces = Vec::new();
+    let mut verdict = check_transaction(node, tx, min_confirmations_for_spend, &mut report);
+    if verdict.is_ok() {
+        // Same lock order as /tx: conflict fees from bc before the mempool lock
+        let conflict_fees = node.conflict_fees(tx);
+        let mempool = node.mempool.lock().unwrap();
+        verdict = if mempool.seen_tx.contains_key(&tx.txid) {
+            Err("already seen by this node".to_string())
+        } else {
+            let fee = report.fee.unwrap_or_default();
+            mempool
+                .check_replacement(tx, fee, report.size, &conflict_fees)
+                .map(|txids| replaces = txids)
+        };
+    }
+
+    serde_json::json!({
+        "txid": tx.txid,
+        "valid": verdict.is_ok(),
+        "reason": verdict.err(),
+        "size": report.size,
+        "fee": report.fee.map(|fee| format!("0x{:x}", fee)),
+        "fee_per_byte": report
+            .fee
+            .map(|fee| format!("0x{:x}", fee / U256::from(report.size.max(1)))),
+        "min_fee": format!("0x{:x}", node.mempool.lock().unwrap().min_relay_fee(report.size)),
+        "replaces": replaces,
+        "warnings": report.warnings,
+    })
+}
+
+/// Why a request to an `/admin` route is refused, if it is: always while no ADMIN_TOKEN
+/// is configured, otherwise unless `authorization` is exactly `Bearer <token>`
+fn admin_auth_error(
+    admin_token: &str,
+    authorization: Option<&str>,
+) -> Option<(StatusCode, &'static str)> {
+    if admin_token.is_empty() {
+        return Some((
+            StatusCode::FORBIDDEN,
+            "admin API disabled: set ADMIN_TOKEN in nodeSettings.conf",
+        ));
+    }
+    let presented = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or("");
+    // Look at every byte so the response time doesn't give away how much matched
+    let differs = presented.len() != admin_token.len()
+        || presented
+            .bytes()
+            .zip(admin_token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            != 0;
+    differs.then_some((StatusCode::UNAUTHORIZED, "missing or invalid admin token"))
+}
+
+/// Snapshot the chain database into a new `snapshot-<unix ms>` directory under
+/// `backup_dir`. The tip is read under the same lock, so it is the one the snapshot holds.
+fn create_backup(node: &NodeHandle, backup_dir: &Path) -> anyhow::Result<serde_json::Value> {
+    std::fs::create_dir_all(backup_dir)?;
+    let dest = backup_dir.join(format!(
+        "snapshot-{}",
+        chrono::Utc::now().timestamp_millis()
+    ));
+    let bc = node.bc.lock().unwrap();
+    bc.create_checkpoint(&dest.to_string_lossy())?;
+    info!("Database snapshot written to {:?}", dest);
+    Ok(serde_json::json!({
+        "status": "ok",
+        "path": dest.to_string_lossy(),
+        "tip": bc.chain_tip,
+    }))
+}
+
+fn bad_request(message: &str) -> warp::reply::WithStatus<warp::reply::Json> {
+    with_status(
+        warp::reply::json(&serde_json::json!({ "error": message })),
+        StatusCode::BAD_REQUEST,
+    )
+}
+
+/// Address listings page by cursor; a nonzero `offset` from an older client is refused
+/// rather than silently answered from the start
+fn offset_paging_refused(
+    params: &HashMap<String, String>,
+) -> Option<warp::reply::WithStatus<warp::reply::Json>> {
+    match params.get("offset").map(|v| v.parse::<usize>()) {
+        None | Some(Ok(0)) => None,
+        Some(_) => Some(bad_request("'offset' is not supported, page with 'cursor'")),
+    }
+}
+
+/// Maximum number of heights a single /emission request may cover
+pub const MAX_EMISSION_SPAN: u64 = 1000;
+
+/// Per-height reward and cumulative supply for `from..=to`, from the halving schedule
+fn emission_schedule(from: u64, to: u64) -> Vec<serde_json::Value> {
+    use Astram_core::config::{calculate_block_reward, cumulative_emission};
+
+    let mut supply = if from == 0 {
+        U256::zero()
+    } else {
+        cumulative_emission(from - 1)
+    };
+    (from..=to)
+        .map(|height| {
+            let reward = calculate_block_reward(height);
+            supply += reward;
+            serde_json::json!({
+                "height": height,
+                "reward": format!("0x{:x}", reward),
+                "cumulative_supply": format!("0x{:x}", supply),
+            })
+        })
+        .collect()
+}
+
+/// Maximum number of transactions (graph nodes) a /mempool/graph reply may include
+pub const MAX_MEMPOOL_GRAPH_NODES: usize = 2000;
+
+/// Nodes and edges of the dependency graph among `txs`. An input spending another tx in
+/// `txs` becomes an edge and takes its amount from that tx's output; other inputs are
+/// looked up in `utxo_amounts` (keyed `u:<txid>:<vout>`). A fee is null when any input
+/// amount is unknown.
+fn mempool_graph(
+    txs: &[Transaction],
+    utxo_amounts: &HashMap<String, U256>,
+) -> (Vec<serde_json::Value>, Vec<serde_json::Value>) {
+    let by_txid: HashMap<&str, &Transaction> =
+        txs.iter().map(|tx| (tx.txid.as_str(), tx)).collect();
+
+    let mut nodes = Vec::with_capacity(txs.len());
+    let mut edges = Vec::new();
+    for tx in txs {
+        let mut input_sum = Some(U256::zero());
+        for inp in &tx.inputs {
+            let amount = match by_txid.get(inp.txid.as_str()) {
+                Some(parent) => {
+                    edges.push(serde_json::json!({
+                        "from": parent.txid,
+                        "to": tx.txid,
+                        "vout": inp.vout,
+                    }));
+                    parent.outputs.get(inp.vout as usize).map(|out| out.amount())
+                }
+                None => utxo_amounts
+                    .get(&format!("u:{}:{}", inp.txid, inp.vout))
+                    .copied(),
+            };
+            input_sum = input_sum
+                .zip(amount)
+                .and_then(|(sum, amount)| sum.checked_add(amount));
+        }
+
+        let size = bincode::encode_to_vec(tx, *BINCODE_CONFIG)
+            .map(|bytes| bytes.len())
+            .unwrap_or(0);
+        let fee = input_sum.and_then(|sum| tx.fee(sum));
+
+        nodes.push(serde_json::json!({
+            "txid": tx.txid,
+            "size": size,
+            "fee": fee.map(|fee| format!("0x{:x}", fee)),
+            "fee_per_byte": fee.map(|fee| format!("0x{:x}", fee / U256::from(size.max(1)))),
+        }));
+    }
+    (nodes, edges)
+}
+
+/// Maximum number of headers a single /headers request may return
+pub const MAX_HEADERS_PER_REQUEST: usize = 2000;
+
+/// Maximum number of UTXOs a single /address/{address}/utxos page may return
+pub const MAX_UTXOS_PER_PAGE: usize = 1000;
+
+/// Page size of /address/{address}/transactions when no limit is given, and its cap
+pub const DEFAULT_HISTORY_PAGE: usize = 50;
+pub const MAX_HISTORY_PAGE: usize = 500;
+
+/// Maximum number of blocks a single /blocks/time request may return
+pub const MAX_BLOCKS_PER_TIME_QUERY: usize = 500;
+
+/// Maximum number of blocks a single /blockchain/db request may return
+pub const MAX_DB_QUERY_SPAN: u64 = 1000;
+
+/// Validate the inclusive `from`/`to` heights of a /blockchain/db query.
+/// `from` and either `to` or a block count `limit` are required, and the span may not
+/// exceed MAX_DB_QUERY_SPAN blocks.
+fn db_query_span(params: &HashMap<String, String>) -> Result<(u64, u64), String> {
+    let parse = |key: &str| -> Result<u64, String> {
+        let raw = params.get(key).ok_or_else(|| {
+            format!(
+                "'{}' is required; /blockchain/db no longer returns the whole chain",
+                key
+            )
+        })?;
+        raw.parse::<u64>()
+            .map_err(|_| format!("invalid '{}' height: {}", key, raw))
+    };
+
+    let from = parse("from")?;
+    let to = match params.get("limit") {
+        Some(raw) if !params.contains_key("to") => match raw.parse::<u64>() {
+            Ok(limit) if limit > 0 => from.saturating_add(limit - 1),
+            _ => return Err(format!("invalid 'limit': {}", raw)),
+        },
+        _ => parse("to")?,
+    };
+    if to < from {
+        return Err(format!("'to' ({}) is below 'from' ({})", to, from));
+    }
+    if to - from >= MAX_DB_QUERY_SPAN {
+        return Err(format!(
+            "span of {} blocks exceeds the maximum of {}",
+            to - from + 1,
+            MAX_DB_QUERY_SPAN
+        ));
+    }
+    Ok((from, to))
+}
+
+/// Inclusive heights for a /blockchain/range query. `from` defaults to 0; the end is
+/// `to`, or `from + limit - 1`, and is clamped so one page holds at most
+/// MAX_DB_QUERY_SPAN blocks. Heights past the tip just come back empty.
+fn range_query_span(params: &HashMap<String, String>) -> (u64, u64) {
+    let height = |key: &str| params.get(key).and_then(|s| s.parse::<u64>().ok());
+    let from = height("from").unwrap_or(0);
+    let last = from.saturating_add(MAX_DB_QUERY_SPAN - 1);
+    let to = match (height("to"), height("limit")) {
+        (Some(to), _) => to,
+        (None, Some(limit)) if limit > 0 => from.saturating_add(limit - 1),
+        _ => last,
+    };
+    (from, to.min(last))
+}
+
+/// Validate and store a block mined for this node (/mining/submit, /mining/solution),
+/// then announce it: height, event subscribers, mempool cleanup and peer broadcast
+fn commit_mined_block(
+    node: &NodeHandle,
+    p2p: std::sync::Arc<PeerManager>,
+    chain_state: &std::sync::Mutex<ChainState>,
+    block: &Block,
+) -> anyhow::Result<()> {
+    // DB insert, in-memory chain and recently-mined marker change under one
+    // bc -> chain critical section so a peer copy of the same block can't interleave
+    {
+        let mut bc = node.bc.lock().unwrap();
+        let mut chain = chain_state.lock().unwrap();
+        bc.validate_and_insert_block(block)?;
+        chain.push_block(block);
+        chain.mark_mined(&block.hash, chrono::Utc::now().timestamp());
+    }
+
+    p2p.set_my_height(block.header.index + 1);
+    node.notify_block(block);
+
+    let removed = node.mempool.lock().unwrap().remove_confirmed(block);
+    if removed > 0 {
+        log::info!(
+            "[INFO] Removed {} transactions from mempool (included in submitted block)",
+            removed
+        );
+    }
+
+    let block_to_broadcast = block.clone();
+    tokio::spawn(async move {
+        p2p.broadcast_block(&block_to_broadcast).await;
+    });
+    Ok(())
+}
+
+/// Build the reply for single-block lookups: bincode+base64 block plus a decoded summary
+fn block_reply(
+    result: anyhow::Result<Option<Block>>,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    match result {
+        Ok(Some(block)) => {
+            let bincode_bytes = match bincode::encode_to_vec(&block, *BINCODE_CONFIG) {
+                Ok(b) => b,
+                Err(e) => {
+                    return with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "error": format!("encode error: {}", e)
+                        })),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    );
+                }
+            };
+            let encoded = general_purpose::STANDARD.encode(&bincode_bytes);
+
+            with_status(
+                warp::reply::json(&serde_json::json!({
+                    "hash": block.hash,
+                    "block": encoded,
+                    "encoding": "bincode+base64",
+                    "summary": {
+                        "index": block.header.index,
+                        "previous_hash": block.header.previous_hash,
+                        "timestamp": block.header.timestamp,
+                        "tx_count": block.transactions.len(),
+                        "merkle_root": block.header.merkle_root,
+                        "difficulty": block.header.difficulty,
+                        "nonce": block.header.nonce
+                    }
+                })),
+                StatusCode::OK,
+            )
+        }
+        Ok(None) => with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "block not found"
+            })),
+            StatusCode::NOT_FOUND,
+        ),
+        Err(e) => with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": format!("db error: {}", e)
+            })),
+            db_error_status(&e),
+        ),
+    }
+}
+
+/// 410 Gone for a block body removed by pruning, 500 for any other storage error
+fn db_error_status(e: &anyhow::Error) -> StatusCode {
+    if e.downcast_ref::<BlockPruned>().is_some() {
+        StatusCode::GONE
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span_params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn admin_routes_need_the_exact_bearer_token() {
+        let status = |token: &str, header: Option<&str>| {
+            admin_auth_error(token, header).map(|(status, _)| status)
+        };
+        // No token configured: disabled whatever the client sends
+        assert_eq!(status("", Some("Bearer ")), Some(StatusCode::FORBIDDEN));
+        assert_eq!(status("", None), Some(StatusCode::FORBIDDEN));
+
+        assert_eq!(status("s3cret", Some("Bearer s3cret")), None);
+        for bad in [
+            None,
+            Some("s3cret"),
+            Some("Bearer s3cre"),
+            Some("Bearer s3cret2"),
+            Some("Basic s3cret"),
+        ] {
+            let refused = status("s3cret", bad);
+            assert_eq!(refused, Some(StatusCode::UNAUTHORIZED), "{:?}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_over_the_in_flight_cap_are_shed() {
+        use tokio::sync::Semaphore;
+
+        // Handlers park on `gate` until the test opens it
+        let gate = std::sync::Arc::new(Semaphore::new(0));
+        let slow = {
+            let gate = gate.clone();
+            warp::path("slow").and_then(move || {
+                let gate = gate.clone();
+                async move {
+                    let _open = gate.acquire().await;
+                    Ok::<_, warp::Rejection>("done")
+                }
+            })
+        };
+        let in_flight = std::sync::Arc::new(Semaphore::new(2));
+        let filter = concurrency_limited(slow, in_flight.clone());
+
+        let parked: Vec<_> = (0..2)
+            .map(|_| {
+                let filter = filter.clone();
+                tokio::spawn(async move {
+                    let request = warp::test::request().path("/slow");
+                    request.reply(&filter).await
+                })
+            })
+            .collect();
+        while in_flight.available_permits() > 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let shed = warp::test::request().path("/slow").reply(&filter).await;
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        gate.add_permits(1);
+        for handle in parked {
+            assert_eq!(handle.await.unwrap().status(), StatusCode::OK);
+        }
+        let served = warp::test::request().path("/slow").reply(&filter).await;
+        assert_eq!(served.status(), StatusCode::OK);
+        // Unknown paths still 404 rather than being reported as busy
+        let missing = warp::test::request().path("/missing").reply(&filter).await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn metrics_render_as_prometheus_text() {
+        let text = render_metrics(&NodeMetrics {
+            height: 42,
+            mempool_txs: 3,
+            mempool_bytes: 900,
+            peers: 5,
+            difficulty_bits: 0x1d0fffff,
+            hashrate: 1500.5,
+            validation_failures: vec![("invalid_pow".to_string(), 2), ("other".to_string(), 0)],
+            ..Default::default()
+        });
+
+        let lines: Vec<&str> = text.lines().collect();
+        for expected in [
+            "# TYPE astram_block_height gauge",
+            "astram_block_height 42",
+            "astram_mempool_bytes 900",
+            "astram_peers 5",
+            "astram_difficulty_bits 487587839",
+            "astram_hashrate 1500.5",
+            "astram_blocks_mined_total 0",
+            "# TYPE astram_block_validation_failures_total counter",
+            "astram_block_validation_failures_total{reason=\"invalid_pow\"} 2",
+            "astram_block_validation_failures_total{reason=\"other\"} 0",
+        ] {
+            assert!(lines.contains(&expected), "missing {:?}", expected);
+        }
+        // Comments and `name value` samples only
+        let well_formed = |l: &&str| l.starts_with('#') || l.split(' ').count() == 2;
+        assert!(lines.iter().all(well_formed));
+    }
+
+    #[test]
+    fn utxo_confirmations_count_the_including_block() {
+        let utxo = Utxo::new(
+            "ab".repeat(32),
+            0,
+            "0x0000000000000000000000000000000000000001".to_string(),
+            U256::from(1u64),
+            10,
+            false,
+        );
+        assert_eq!(utxo_json(&utxo, Some(10), 0)["confirmations"], 1);
+        assert_eq!(utxo_json(&utxo, Some(14), 0)["confirmations"], 5);
+        assert_eq!(utxo_json(&utxo, None, 0)["confirmations"], 0);
+        assert_eq!(utxo_json(&utxo, Some(14), 0)["height"], 10);
+    }
+
+    #[test]
+    fn shallow_utxo_is_not_recommended_to_spend() {
+        let utxo = Utxo::new(
+            "cd".repeat(32),
+            1,
+            "0x0000000000000000000000000000000000000002".to_string(),
+            U256::from(5u64),
+            100,
+            false,
+        );
+        // 3 confirmations against a 6-block policy: flagged, still listed
+        let shallow = utxo_json(&utxo, Some(102), 6);
+        assert_eq!(shallow["confirmations"], 3);
+        assert_eq!(shallow["spend_recommended"], false);
+        assert_eq!(utxo_json(&utxo, Some(105), 6)["spend_recommended"], true);
+        assert_eq!(utxo_json(&utxo, None, 6)["spend_recommended"], false);
+    }
+
+    /// Node over an empty chain whose UTXO set holds `utxos`
+    pub(super) fn node_with_utxos(name: &str, utxos: &[Utxo]) -> (NodeHandle, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "astram-{}-{}-{}",
+            name,
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let bc = Astram_core::Blockchain::new(dir.to_str().unwrap()).unwrap();
+        for utxo in utxos {
+            bc.db
+                .put(
+                    format!("u:{}:{}", utxo.txid, utxo.vout).as_bytes(),
+                    bincode::encode_to_vec(utxo, *BINCODE_CONFIG).unwrap(),
+                )
+                .unwrap();
+        }
+        bc.rebuild_utxo_filter().unwrap();
+        let node = std::sync::Arc::new(crate::NodeHandles {
+            bc: std::sync::Arc::new(std::sync::Mutex::new(bc)),
+            mempool: std::sync::Arc::new(std::sync::Mutex::new(crate::MempoolState::default())),
+            mining: std::sync::Arc::new(crate::MiningState::default()),
+            events: tokio::sync::broadcast::channel(crate::NODE_EVENT_CAPACITY).0,
+        });
+        (node, dir)
+    }
+
+    /// `keypair`'s signed spend of `txid:0` paying `amount` back to itself
+    fn signed_spend(
+        keypair: &Astram_core::crypto::WalletKeypair,
+        txid: &str,
+        amount: U256,
+    ) -> Transaction {
+        use Astram_core::transaction::{TransactionInput, TransactionOutput};
+        let mut tx = Transaction {
+            txid: String::new(),
+            eth_hash: String::new(),
+            inputs: vec![TransactionInput {
+                txid: txid.to_string(),
+                vout: 0,
+                pubkey: keypair.public_hex(),
+                signature: None,
+            }],
+            outputs: vec![TransactionOutput::new(
+                keypair.address().to_lowercase(),
+                amount,
+            )],
+            timestamp: chrono::Utc::now().timestamp(),
+            valid_until: None,
+        };
+        tx.sign(keypair).unwrap();
+        tx.with_hashes()
+    }
+
+    #[test]
+    fn submitted_transactions_are_checked_against_the_utxo_set() {
+        let keypair = Astram_core::crypto::WalletKeypair::new();
+        let owner = keypair.address().to_lowercase();
+        let asrm = Astram_core::config::RAM_PER_ASRM;
+        let funding = "f".repeat(64);
+        let reward = "c".repeat(64);
+        let utxos = [
+            Utxo::new(funding.clone(), 0, owner.clone(), asrm * 100, 0, false),
+            Utxo::new(reward.clone(), 0, owner, asrm * 100, 0, true),
+        ];
+        let (node, dir) = node_with_utxos("check-tx", &utxos);
+        let check = |tx: &Transaction| {
+            let mut report = TxReport::default();
+            check_transaction(&node, tx, 0, &mut report).map(|()| report.fee)
+        };
+
+        let good = signed_spend(&keypair, &funding, asrm * 99);
+        assert_eq!(check(&good), Ok(Some(asrm)));
+
+        // /tx only spends confirmed outputs: an unknown input, or one of a pending
+        // transaction, is refused
+        let err = check(&signed_spend(&keypair, &"e".repeat(64), asrm)).unwrap_err();
+        assert!(err.contains("is not an unspent output"), "{}", err);
+        let err = check(&signed_spend(&keypair, &good.txid, asrm)).unwrap_err();
+        assert!(err.contains("is not an unspent output"), "{}", err);
+
+        let err = check(&signed_spend(&keypair, &reward, asrm * 99)).unwrap_err();
+        assert!(err.contains("is not mature"), "{}", err);
+
+        let err = check(&signed_spend(&keypair, &funding, asrm * 100 - 1)).unwrap_err();
+        assert!(err.contains("fee too low"), "{}", err);
+
+        let mut forged = good.clone();
+        forged.outputs[0] = Astram_core::transaction::TransactionOutput::new(
+            "0x0000000000000000000000000000000000000009".to_string(),
+            asrm * 99,
+        );
+        let forged = forged.with_hashes();
+        assert_eq!(check(&forged), Err("invalid signature".to_string()));
+
+        drop(node);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn validate_reports_the_verdict_without_queueing() {
+        let keypair = Astram_core::crypto::WalletKeypair::new();
+        let owner = keypair.address().to_lowercase();
+        let asrm = Astram_core::config::RAM_PER_ASRM;
+        let funding = "f".repeat(64);
+        let utxos = [Utxo::new(funding.clone(), 0, owner, asrm * 100, 0, false)];
+        let (node, dir) = node_with_utxos("validate-tx", &utxos);
+
+        let tx = signed_spend(&keypair, &funding, asrm * 99);
+        let verdict = validate_transaction(&node, &tx, 0);
+        assert_eq!(verdict["valid"], true);
+        assert!(verdict["reason"].is_null());
+        assert_eq!(verdict["fee"], format!("0x{:x}", asrm));
+        assert_eq!(verdict["replaces"], serde_json::json!([]));
+        assert!(node.mempool.lock().unwrap().pending.is_empty());
+
+        // A rejected transaction still reports its size and fee
+        let cheap = signed_spend(&keypair, &funding, asrm * 100 - 1);
+        let verdict = validate_transaction(&node, &cheap, 0);
+        assert_eq!(verdict["valid"], false);
+        assert!(verdict["reason"].as_str().unwrap().contains("fee too low"));
+        assert_eq!(verdict["fee"], "0x1");
+        assert!(verdict["size"].as_u64().unwrap() > 0);
+
+        // Shallow inputs warn but don't fail
+        let verdict = validate_transaction(&node, &tx, 6);
+        assert_eq!(verdict["valid"], true);
+        assert_eq!(verdict["warnings"].as_array().unwrap().len(), 1);
+
+        // Once pending: the same tx is a duplicate and a same-fee rival can't replace it
+        {
+            let mut mempool = node.mempool.lock().unwrap();
+            mempool
+                .seen_tx
+                .insert(tx.txid.clone(), chrono::Utc::now().timestamp());
+            mempool.pending.push(tx.clone());
+        }
+        let verdict = validate_transaction(&node, &tx, 0);
+        assert_eq!(verdict["reason"], "already seen by this node");
+        let mut rival = signed_spend(&keypair, &funding, asrm * 99);
+        rival.timestamp += 1;
+        rival.sign(&keypair).unwrap();
+        let rival = rival.with_hashes();
+        assert_ne!(rival.txid, tx.txid);
+        assert_eq!(validate_transaction(&node, &rival, 0)["valid"], false);
+        // ...while one paying more would replace it
+        let bump = signed_spend(&keypair, &funding, asrm * 97);
+        let verdict = validate_transaction(&node, &bump, 0);
+        assert_eq!(verdict["valid"], true);
+        assert_eq!(verdict["replaces"], serde_json::json!([tx.txid]));
+        assert_eq!(node.mempool.lock().unwrap().pending.len(), 1);
+
+        drop(node);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn mempool_graph_links_spenders_to_mempool_parents() {
+        use Astram_core::transaction::{TransactionInput, TransactionOutput};
+
+        let tx = |txid: &str, inputs: &[(&str, u32)], outputs: &[u64]| Transaction {
+            txid: txid.to_string(),
+            eth_hash: String::new(),
+            inputs: inputs
+                .iter()
+                .map(|(txid, vout)| TransactionInput {
+                    txid: txid.to_string(),
+                    vout: *vout,
+                    pubkey: String::new(),
+                    signature: None,
+                })
+                .collect(),
+            outputs: outputs
+                .iter()
+                .map(|amount| TransactionOutput::new("0xabc".to_string(), U256::from(*amount)))
+                .collect(),
+            timestamp: 0,
+            valid_until: None,
+        };
+        let parent = tx("parent", &[("confirmed", 0)], &[900, 50]);
+        let child = tx("child", &[("parent", 0)], &[850]);
+        let stray = tx("stray", &[("unknown", 3)], &[10]);
+        let utxo_amounts = HashMap::from([("u:confirmed:0".to_string(), U256::from(1000u64))]);
+
+        let (nodes, edges) = mempool_graph(&[parent, child, stray], &utxo_amounts);
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[0]["fee"], "0x32"); // 1000 - 950
+        assert_eq!(nodes[1]["fee"], "0x32"); // 900 from the mempool parent - 850
+        assert!(nodes[2]["fee"].is_null());
+        assert_eq!(
+            edges,
+            vec![serde_json::json!({ "from": "parent", "to": "child", "vout": 0 })]
+        );
+    }
+
+    #[test]
+    fn emission_schedule_tracks_cumulative_supply() {
+        let from = Astram_core::config::HALVING_INTERVAL - 2;
+        let schedule = emission_schedule(from, from + 3);
+        assert_eq!(schedule.len(), 4);
+        assert_ne!(schedule[1]["reward"], schedule[2]["reward"]); // halving boundary
+        assert_eq!(
+            schedule[3]["cumulative_supply"],
+            format!("0x{:x}", Astram_core::config::cumulative_emission(from + 3))
+        );
+    }
+
+    #[test]
+    fn dashboard_falls_back_when_file_missing() {
+        let missing = std::env::temp_dir().join(format!(
+            "astram-no-dashboard-{}/dashboard.html",
+            std::process::id()
+        ));
+        let html = load_dashboard(&missing);
+        assert_eq!(html, FALLBACK_DASHBOARD_HTML);
+        assert!(html.contains("<html>"));
+    }
+
+    #[test]
+    fn db_query_span_is_bounded() {
+        assert_eq!(
+            db_query_span(&span_params(&[("from", "0"), ("to", "999")])),
+            Ok((0, 999))
+        );
+        // one block over the limit
+        assert!(db_query_span(&span_params(&[("from", "0"), ("to", "1000")])).is_err());
+        assert!(db_query_span(&span_params(&[("from", "5"), ("to", "4")])).is_err());
+        // unbounded requests are rejected
+        assert!(db_query_span(&span_params(&[])).is_err());
+        assert!(db_query_span(&span_params(&[("from", "0")])).is_err());
+
+        // a block count works in place of 'to'
+        assert_eq!(
+            db_query_span(&span_params(&[("from", "10"), ("limit", "1000")])),
+            Ok((10, 1009))
+        );
+        assert!(db_query_span(&span_params(&[("from", "0"), ("limit", "1001")])).is_err());
+        assert!(db_query_span(&span_params(&[("from", "0"), ("limit", "0")])).is_err());
+    }
+
+    #[test]
+    fn range_query_pages_are_capped() {
+        // no end: one full page from 'from'
+        assert_eq!(range_query_span(&span_params(&[])), (0, 999));
+        assert_eq!(
+            range_query_span(&span_params(&[("from", "5000")])),
+            (5000, 5999)
+        );
+        assert_eq!(
+            range_query_span(&span_params(&[("from", "10"), ("limit", "20")])),
+            (10, 29)
+        );
+        // oversized requests are clamped rather than served whole
+        assert_eq!(
+            range_query_span(&span_params(&[("from", "0"), ("to", "50000")])),
+            (0, 999)
+        );
+        assert_eq!(
+            range_query_span(&span_params(&[("from", "0"), ("limit", "50000")])),
+            (0, 999)
+        );
+    }
+}
@@ -0,0 +1,211 @@
+// Per-client-IP token buckets in front of the expensive HTTP and JSON-RPC routes
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use warp::http::StatusCode;
+use warp::{Filter, Reply};
+
+/// Default sustained requests per second per client IP (HTTP_RATE_LIMIT_PER_SEC)
+pub const DEFAULT_RATE_LIMIT_PER_SEC: u32 = 10;
+/// Default requests a client can make in one go before the rate applies (HTTP_RATE_LIMIT_BURST)
+pub const DEFAULT_RATE_LIMIT_BURST: u32 = 50;
+
+/// Clients tracked before buckets that have refilled (idle clients) are swept
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant, per_sec: f64, capacity: f64) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_sec).min(capacity);
+        self.updated = now;
+    }
+}
+
+/// Token bucket per client IP: `burst` requests at once, refilled at `per_sec`.
+/// A `per_sec` of 0 disables limiting; whitelisted IPs are never limited.
+pub struct RateLimiter {
+    per_sec: u32,
+    burst: u32,
+    whitelist: Vec<IpAddr>,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_sec: u32, burst: u32, whitelist: Vec<IpAddr>) -> Self {
+        Self {
+            per_sec,
+            burst,
+            whitelist,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for `ip`. False if its bucket is empty.
+    pub fn allow(&self, ip: IpAddr) -> bool {
+        self.allow_at(ip, Instant::now())
+    }
+
+    /// Take `n` tokens for `ip` at once, or none if it has fewer left. More than the
+    /// burst costs the whole burst, so it needs, and empties, a full bucket.
+    pub fn allow_n(&self, ip: IpAddr, n: u32) -> bool {
+        self.allow_n_at(ip, n, Instant::now())
+    }
+
+    fn allow_at(&self, ip: IpAddr, now: Instant) -> bool {
+        self.allow_n_at(ip, 1, now)
+    }
+
+    fn allow_n_at(&self, ip: IpAddr, n: u32, now: Instant) -> bool {
+        if n == 0 || self.per_sec == 0 || self.whitelist.contains(&ip) {
+            return true;
+        }
+        let per_sec = self.per_sec as f64;
+        let capacity = self.burst.max(1) as f64;
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&ip) {
+            buckets.retain(|_, bucket| {
+                bucket.refill(now, per_sec, capacity);
+                bucket.tokens < capacity
+            });
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.refill(now, per_sec, capacity);
+        let cost = (n as f64).min(capacity);
+        if bucket.tokens < cost {
+            return false;
+        }
+        bucket.tokens -= cost;
+        true
+    }
+}
+
+/// Rejection for a client that has used up its bucket
+#[derive(Debug)]
+struct RateLimited;
+
+impl warp::reject::Reject for RateLimited {}
+
+/// Passes requests while the connecting IP has tokens left, otherwise rejects with
+/// `RateLimited`. Put it after a route's path and method so only requests that route
+/// would serve are counted. Requests without a remote address are let through.
+pub fn rate_limit(
+    limiter: Arc<RateLimiter>,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::addr::remote()
+        .and_then(move |remote: Option<SocketAddr>| {
+            let limiter = limiter.clone();
+            async move {
+                match remote {
+                    Some(addr) if !limiter.allow(addr.ip()) => Err(rate_limited()),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// The rejection `rate_limit` gives, for handlers that charge more than one token
+pub fn rate_limited() -> warp::Rejection {
+    warp::reject::custom(RateLimited)
+}
+
+/// Answer a `RateLimited` rejection with 429; anything else is passed on
+pub async fn recover_rate_limited(
+    err: warp::Rejection,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    if err.find::<RateLimited>().is_none() {
+        return Err(err);
+    }
+    let reply = warp::reply::json(&serde_json::json!({
+        "error": "rate limit exceeded, retry shortly"
+    }));
+    let reply = warp::reply::with_header(reply, "retry-after", "1");
+    Ok(warp::reply::with_status(reply, StatusCode::TOO_MANY_REQUESTS).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn bucket_allows_burst_then_refills_at_rate() {
+        let limiter = RateLimiter::new(2, 3, Vec::new());
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        let other: IpAddr = "203.0.113.8".parse().unwrap();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.allow_at(client, start));
+        }
+        assert!(!limiter.allow_at(client, start));
+        // Buckets are per IP
+        assert!(limiter.allow_at(other, start));
+
+        // Two tokens a second: one back after half a second, never more than the burst
+        assert!(limiter.allow_at(client, start + Duration::from_millis(500)));
+        assert!(!limiter.allow_at(client, start + Duration::from_millis(500)));
+        let later = start + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(limiter.allow_at(client, later));
+        }
+        assert!(!limiter.allow_at(client, later));
+    }
+
+    #[test]
+    fn several_tokens_are_taken_together_or_not_at_all() {
+        let limiter = RateLimiter::new(1, 4, Vec::new());
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        let now = Instant::now();
+
+        assert!(limiter.allow_n_at(client, 3, now));
+        assert!(!limiter.allow_n_at(client, 2, now));
+        assert!(limiter.allow_at(client, now));
+        assert!(!limiter.allow_at(client, now));
+
+        // Past the burst, a full bucket is the price
+        let later = now + Duration::from_secs(60);
+        assert!(limiter.allow_n_at(client, 100, later));
+        assert!(!limiter.allow_at(client, later));
+    }
+
+    #[test]
+    fn whitelist_and_zero_rate_are_unlimited() {
+        let miner: IpAddr = "127.0.0.1".parse().unwrap();
+        let limiter = RateLimiter::new(1, 1, vec![miner]);
+        let now = Instant::now();
+        assert!((0..100).all(|_| limiter.allow_at(miner, now)));
+
+        let disabled = RateLimiter::new(0, 1, Vec::new());
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        assert!((0..100).all(|_| disabled.allow_at(client, now)));
+    }
+
+    #[tokio::test]
+    async fn limited_route_answers_429() {
+        let limiter = Arc::new(RateLimiter::new(1, 2, Vec::new()));
+        let route = warp::path("status")
+            .and(rate_limit(limiter))
+            .map(|| "ok")
+            .recover(recover_rate_limited);
+        let client: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+        let request = || warp::test::request().path("/status").remote_addr(client);
+
+        for _ in 0..2 {
+            assert_eq!(request().reply(&route).await.status(), StatusCode::OK);
+        }
+        let limited = request().reply(&route).await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limited.headers()["retry-after"], "1");
+    }
+}
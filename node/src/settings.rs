@@ -0,0 +1,520 @@
+use astram_config::ports::Ports;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Node runtime settings loaded from `config/nodeSettings.conf` (KEY=VALUE, `#` comments)
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeSettings {
+    pub data_dir: String,
+    pub p2p_bind_addr: String,
+    pub p2p_port: u16,
+    pub http_bind_addr: String,
+    pub http_port: u16,
+    pub eth_rpc_bind_addr: String,
+    pub eth_rpc_port: u16,
+    /// DNS seed registries, queried in order and merged (`DNS_SEEDS`, comma-separated;
+    /// the legacy single-valued `DNS_SERVER_URL` is still accepted)
+    pub dns_seeds: Vec<String>,
+    /// Peer addresses dialled only when DNS yields nothing and no peers.json peer connects
+    /// (`FALLBACK_SEEDS`)
+    pub fallback_seeds: Vec<String>,
+    /// Overrides the network chain id (otherwise resolved from ASTRAM_CHAIN_ID / ASTRAM_NETWORK)
+    pub chain_id: Option<u64>,
+    /// CPU mining worker threads; 0 = one per core (MINING_THREADS env var overrides)
+    pub mining_threads: usize,
+    /// Percentage of wall time CPU mining workers spend hashing; 0 or 100 = unthrottled
+    pub mining_throttle_percent: u8,
+    /// Broadcast a freshly mined block before the local bookkeeping (stats, mempool
+    /// cleanup, event notification) instead of after it
+    pub mining_propagate_first: bool,
+    /// Minimum fee rate (ram per byte) for admitting and relaying transactions; it can
+    /// only raise the consensus minimum (`calculate_min_fee`), never lower it
+    pub min_relay_fee_per_byte: u64,
+    /// Extra fee (ram per byte of the replacement) a transaction must pay over the
+    /// pending ones it double-spends to replace them
+    pub rbf_fee_increment_per_byte: u64,
+    /// Maximum outbound peer connections, a few of which only the DNS refresh may fill
+    pub max_outbound_peers: usize,
+    /// Maximum inbound peer connections; when full, a newcomer only gets in by evicting
+    /// a worse-behaved inbound peer
+    pub max_inbound_peers: usize,
+    /// Longest startup wait for sync before mining begins; mining is skipped while the
+    /// node is still far behind its peers
+    pub sync_max_wait_secs: u64,
+    /// Confirmations a UTXO needs before the API recommends spending it; shallower
+    /// outputs are still valid but could be reorged away
+    pub min_confirmations_for_spend: u64,
+    /// Skip signature checks during initial sync for blocks on the header chain that
+    /// leads to the latest pinned checkpoint
+    pub checkpoint_sync: bool,
+    /// Seconds a peer IP stays banned once its misbehavior score reaches the threshold
+    pub peer_ban_secs: u64,
+    /// Dashboard HTML served at `/`; relative paths are tried next to the executable,
+    /// then the working directory, then the node crate's source directory
+    pub dashboard_path: String,
+    /// Log method, path, status and latency of every HTTP API request at debug level
+    pub access_log: bool,
+    /// HTTP API requests handled at once; more are answered 503 (`/health` is exempt)
+    pub http_max_concurrent_requests: usize,
+    /// Sustained requests per second each client IP may make to the expensive HTTP
+    /// routes (`/status`, `/blockchain*`) and the Ethereum JSON-RPC; 0 disables limiting
+    pub http_rate_limit_per_sec: u32,
+    /// Requests a client IP may make at once before `http_rate_limit_per_sec` applies
+    pub http_rate_limit_burst: u32,
+    /// Client IPs never rate limited, e.g. a local miner or explorer (`HTTP_RATE_LIMIT_WHITELIST`)
+    pub http_rate_limit_whitelist: Vec<String>,
+    /// Bearer token for the `/admin` HTTP routes; empty disables them
+    pub admin_token: String,
+    /// Directory that `POST /admin/backup` writes database snapshots into
+    pub backup_dir: String,
+    /// Keep block bodies only this many blocks below the tip (at least the reorg depth);
+    /// older blocks keep just their headers. 0 keeps everything. `--prune N` overrides.
+    pub prune_depth: u64,
+}
+
+impl Default for NodeSettings {
+    fn default() -> Self {
+        let ports = Ports::default();
+        Self {
+            data_dir: default_data_dir(),
+            p2p_bind_addr: "0.0.0.0".to_string(),
+            p2p_port: ports.p2p,
+            http_bind_addr: "127.0.0.1".to_string(),
+            http_port: ports.http_rpc,
+            eth_rpc_bind_addr: "127.0.0.1".to_string(),
+            eth_rpc_port: ports.eth_rpc,
+            dns_seeds: vec![format!("http://161.33.19.183:{}", ports.dns)],
+            fallback_seeds: DEFAULT_FALLBACK_SEEDS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            chain_id: None,
+            mining_threads: 0,
+            mining_throttle_percent: 100,
+            mining_propagate_first: true,
+            min_relay_fee_per_byte: crate::MIN_RELAY_FEE_PER_BYTE,
+            rbf_fee_increment_per_byte: crate::DEFAULT_RBF_FEE_INCREMENT_PER_BYTE,
+            max_outbound_peers: crate::p2p::manager::MAX_OUTBOUND,
+            max_inbound_peers: crate::p2p::manager::MAX_INBOUND,
+            sync_max_wait_secs: 120,
+            min_confirmations_for_spend: crate::DEFAULT_MIN_CONFIRMATIONS_FOR_SPEND,
+            peer_ban_secs: crate::p2p::manager::DEFAULT_BAN_DURATION_SECS,
+            checkpoint_sync: false,
+            dashboard_path: "web/dashboard.html".to_string(),
+            access_log: false,
+            http_max_concurrent_requests: crate::server::DEFAULT_HTTP_MAX_CONCURRENT_REQUESTS,
+            http_rate_limit_per_sec: crate::server::rate_limit::DEFAULT_RATE_LIMIT_PER_SEC,
+            http_rate_limit_burst: crate::server::rate_limit::DEFAULT_RATE_LIMIT_BURST,
+            http_rate_limit_whitelist: vec!["127.0.0.1".to_string(), "::1".to_string()],
+            admin_token: String::new(),
+            backup_dir: default_backup_dir(),
+            prune_depth: 0,
+        }
+    }
+}
+
+/// Known-stable peers compiled in as the last bootstrap resort
+pub const DEFAULT_FALLBACK_SEEDS: &[&str] = &["161.33.19.183:8335"];
+
+fn default_data_dir() -> String {
+    let home = dirs::home_dir().expect("Cannot find home directory");
+
+    if cfg!(target_os = "windows") {
+        let base = dirs::data_dir().unwrap_or(home).join("Astram");
+        return base.join("data").to_string_lossy().into_owned();
+    }
+
+    home.join(".Astram")
+        .join("data")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// `backups` next to the default data directory
+fn default_backup_dir() -> String {
+    let data_dir = PathBuf::from(default_data_dir());
+    data_dir
+        .parent()
+        .unwrap_or(&data_dir)
+        .join("backups")
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn expand_path_value(value: &str) -> String {
+    let expanded = shellexpand::tilde(value).into_owned();
+    if expanded.contains("%USERPROFILE%") {
+        if let Ok(profile) = std::env::var("USERPROFILE") {
+            return expanded.replace("%USERPROFILE%", &profile);
+        }
+    }
+    expanded
+}
+
+/// Split a comma-separated setting, dropping empty entries
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl NodeSettings {
+    /// Locate nodeSettings.conf: next to the executable first, then the working directory
+    pub fn resolve_path() -> PathBuf {
+        let exe_path = std::env::current_exe().ok().and_then(|path| {
+            path.parent()
+                .map(|parent| parent.join("config/nodeSettings.conf"))
+        });
+
+        if let Some(ref path) = exe_path {
+            if path.exists() {
+                return path.clone();
+            }
+        }
+
+        let cwd_path = PathBuf::from("config/nodeSettings.conf");
+        if cwd_path.exists() {
+            return cwd_path;
+        }
+
+        exe_path.unwrap_or(cwd_path)
+    }
+
+    /// Locate the dashboard HTML. Returns the first existing candidate, or the
+    /// configured path as-is so the caller can report it and fall back.
+    pub fn resolve_dashboard_path(&self) -> PathBuf {
+        let configured = PathBuf::from(&self.dashboard_path);
+        if configured.is_absolute() {
+            return configured;
+        }
+
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf));
+        let candidates = exe_dir
+            .into_iter()
+            .chain(std::env::current_dir().ok())
+            .chain(std::iter::once(PathBuf::from(env!("CARGO_MANIFEST_DIR"))))
+            .map(|dir| dir.join(&configured));
+
+        for candidate in candidates {
+            if candidate.exists() {
+                return candidate;
+            }
+        }
+        configured
+    }
+
+    /// Load settings from the default location, falling back to defaults if missing
+    pub fn load() -> Self {
+        Self::load_from(&Self::resolve_path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(err) => {
+                println!("[WARN] Node settings file not found at {:?}: {}", path, err);
+                let mut settings = Self::default();
+                settings.data_dir = expand_path_value(&settings.data_dir);
+                settings
+            }
+        }
+    }
+
+    /// Parse KEY=VALUE settings on top of the defaults
+    pub fn parse(contents: &str) -> Self {
+        let mut settings = Self::default();
+
+        for (line_no, raw_line) in contents.lines().enumerate() {
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some(pair) => pair,
+                None => {
+                    println!(
+                        "[WARN] Invalid node setting on line {}: {}",
+                        line_no + 1,
+                        raw_line
+                    );
+                    continue;
+                }
+            };
+
+            let key = key.trim();
+            let value = value.trim();
+            match key {
+                "DATA_DIR" => settings.data_dir = expand_path_value(value),
+                "P2P_BIND_ADDR" => settings.p2p_bind_addr = value.to_string(),
+                "P2P_PORT" => settings.p2p_port = value.parse().unwrap_or(settings.p2p_port),
+                "HTTP_BIND_ADDR" => settings.http_bind_addr = value.to_string(),
+                "HTTP_PORT" => settings.http_port = value.parse().unwrap_or(settings.http_port),
+                "ETH_RPC_BIND_ADDR" => settings.eth_rpc_bind_addr = value.to_string(),
+                "ETH_RPC_PORT" => {
+                    settings.eth_rpc_port = value.parse().unwrap_or(settings.eth_rpc_port)
+                }
+                "DNS_SEEDS" | "DNS_SERVER_URL" => settings.dns_seeds = parse_list(value),
+                "FALLBACK_SEEDS" => settings.fallback_seeds = parse_list(value),
+                "CHAIN_ID" => {
+                    settings.chain_id = if value.is_empty() {
+                        None
+                    } else {
+                        value.parse().ok().or(settings.chain_id)
+                    }
+                }
+                "MINING_THREADS" => {
+                    settings.mining_threads = value.parse().unwrap_or(settings.mining_threads)
+                }
+                "MINING_THROTTLE_PERCENT" => {
+                    settings.mining_throttle_percent =
+                        value.parse().unwrap_or(settings.mining_throttle_percent)
+                }
+                "MINING_PROPAGATE_FIRST" => {
+                    settings.mining_propagate_first =
+                        value.parse().unwrap_or(settings.mining_propagate_first)
+                }
+                "MIN_RELAY_FEE_PER_BYTE" => {
+                    settings.min_relay_fee_per_byte =
+                        value.parse().unwrap_or(settings.min_relay_fee_per_byte)
+                }
+                "RBF_FEE_INCREMENT_PER_BYTE" => {
+                    settings.rbf_fee_increment_per_byte =
+                        value.parse().unwrap_or(settings.rbf_fee_increment_per_byte)
+                }
+                "MAX_OUTBOUND_PEERS" => {
+                    settings.max_outbound_peers =
+                        value.parse().unwrap_or(settings.max_outbound_peers)
+                }
+                "MAX_INBOUND_PEERS" => {
+                    settings.max_inbound_peers = value.parse().unwrap_or(settings.max_inbound_peers)
+                }
+                "SYNC_MAX_WAIT_SECS" => {
+                    settings.sync_max_wait_secs =
+                        value.parse().unwrap_or(settings.sync_max_wait_secs)
+                }
+                "MIN_CONFIRMATIONS_FOR_SPEND" => {
+                    settings.min_confirmations_for_spend =
+                        value.parse().unwrap_or(settings.min_confirmations_for_spend)
+                }
+                "PEER_BAN_SECS" => {
+                    settings.peer_ban_secs = value.parse().unwrap_or(settings.peer_ban_secs)
+                }
+                "CHECKPOINT_SYNC" => {
+                    settings.checkpoint_sync = value.parse().unwrap_or(settings.checkpoint_sync)
+                }
+                "DASHBOARD_PATH" => settings.dashboard_path = expand_path_value(value),
+                "ACCESS_LOG" => settings.access_log = value.parse().unwrap_or(settings.access_log),
+                "HTTP_MAX_CONCURRENT_REQUESTS" => {
+                    settings.http_max_concurrent_requests = value
+                        .parse()
+                        .unwrap_or(settings.http_max_concurrent_requests)
+                }
+                "HTTP_RATE_LIMIT_PER_SEC" => {
+                    settings.http_rate_limit_per_sec =
+                        value.parse().unwrap_or(settings.http_rate_limit_per_sec)
+                }
+                "HTTP_RATE_LIMIT_BURST" => {
+                    settings.http_rate_limit_burst =
+                        value.parse().unwrap_or(settings.http_rate_limit_burst)
+                }
+                "HTTP_RATE_LIMIT_WHITELIST" => {
+                    settings.http_rate_limit_whitelist = parse_list(value)
+                }
+                "ADMIN_TOKEN" => settings.admin_token = value.to_string(),
+                "BACKUP_DIR" => settings.backup_dir = expand_path_value(value),
+                "PRUNE_DEPTH" => {
+                    settings.prune_depth = value.parse().unwrap_or(settings.prune_depth)
+                }
+                _ => println!("[WARN] Unknown node setting key: {}", key),
+            }
+        }
+
+        settings.data_dir = expand_path_value(&settings.data_dir);
+        settings
+    }
+
+    /// Render settings in nodeSettings.conf format, with a comment for every key
+    pub fn to_conf_string(&self) -> String {
+        let chain_id = self.chain_id.map(|id| id.to_string()).unwrap_or_default();
+        format!(
+            "# Astram node settings (KEY=VALUE, lines starting with # are ignored)\n\
+             \n\
+             # Blockchain database directory\n\
+             DATA_DIR={}\n\
+             \n\
+             # Peer-to-peer listener\n\
+             P2P_BIND_ADDR={}\n\
+             P2P_PORT={}\n\
+             \n\
+             # HTTP API + dashboard (explorer and wallet-cli connect here)\n\
+             HTTP_BIND_ADDR={}\n\
+             HTTP_PORT={}\n\
+             \n\
+             # Ethereum-compatible JSON-RPC (MetaMask)\n\
+             ETH_RPC_BIND_ADDR={}\n\
+             ETH_RPC_PORT={}\n\
+             \n\
+             # DNS seed registries used for peer discovery (comma-separated, tried in order)\n\
+             DNS_SEEDS={}\n\
+             \n\
+             # Peers dialled only when DNS and saved peers yield nothing (comma-separated host:port)\n\
+             FALLBACK_SEEDS={}\n\
+             \n\
+             # Chain id override; leave empty to use ASTRAM_CHAIN_ID / ASTRAM_NETWORK\n\
+             CHAIN_ID={}\n\
+             \n\
+             # CPU mining worker threads; 0 = one per core (MINING_THREADS env var overrides)\n\
+             MINING_THREADS={}\n\
+             \n\
+             # Share of time CPU miners spend hashing, 1-100 (lower leaves CPU for other work)\n\
+             MINING_THROTTLE_PERCENT={}\n\
+             \n\
+             # Broadcast mined blocks before local bookkeeping to cut orphan risk: true or false\n\
+             MINING_PROPAGATE_FIRST={}\n\
+             \n\
+             # Minimum relay fee rate in ram per byte (values below the consensus rate have no effect)\n\
+             MIN_RELAY_FEE_PER_BYTE={}\n\
+             \n\
+             # Fee in ram per byte a replace-by-fee tx must add over the pending txs it replaces\n\
+             RBF_FEE_INCREMENT_PER_BYTE={}\n\
+             \n\
+             # Maximum outbound peer connections (2 are kept for peers from the DNS refresh)\n\
+             MAX_OUTBOUND_PEERS={}\n\
+             \n\
+             # Maximum inbound peer connections\n\
+             MAX_INBOUND_PEERS={}\n\
+             \n\
+             # Seconds to wait for initial sync before mining (mining stays off while far behind)\n\
+             SYNC_MAX_WAIT_SECS={}\n\
+             \n\
+             # Confirmations before a UTXO is recommended for spending (shallower ones are flagged, not rejected)\n\
+             MIN_CONFIRMATIONS_FOR_SPEND={}\n\
+             \n\
+             # Seconds a misbehaving peer's IP stays banned\n\
+             PEER_BAN_SECS={}\n\
+             \n\
+             # Skip signature checks during initial sync for blocks on the header chain leading to the latest checkpoint (PoW, merkle and UTXO checks still run)\n\
+             CHECKPOINT_SYNC={}\n\
+             \n\
+             # Dashboard HTML served at / (a built-in page is used if missing)\n\
+             DASHBOARD_PATH={}\n\
+             \n\
+             # Debug-level HTTP access log (method, path, status, latency): true or false\n\
+             ACCESS_LOG={}\n\
+             \n\
+             # HTTP API requests handled at once; extra requests get 503 (/health is exempt)\n\
+             HTTP_MAX_CONCURRENT_REQUESTS={}\n\
+             \n\
+             # Per-client-IP requests per second to /status, /blockchain* and the Ethereum JSON-RPC; 0 = unlimited\n\
+             HTTP_RATE_LIMIT_PER_SEC={}\n\
+             \n\
+             # Requests a client may make at once before the per-second limit applies (429 beyond it)\n\
+             HTTP_RATE_LIMIT_BURST={}\n\
+             \n\
+             # Client IPs exempt from rate limiting (comma-separated), e.g. your own miner or explorer\n\
+             HTTP_RATE_LIMIT_WHITELIST={}\n\
+             \n\
+             # Bearer token for /admin routes (e.g. POST /admin/backup); empty disables them\n\
+             ADMIN_TOKEN={}\n\
+             \n\
+             # Where POST /admin/backup (or `Astram-node --backup`) writes database snapshots\n\
+             BACKUP_DIR={}\n\
+             \n\
+             # Keep full blocks only this far below the tip (min 100, the reorg limit); 0 = keep all\n\
+             PRUNE_DEPTH={}\n",
+            self.data_dir,
+            self.p2p_bind_addr,
+            self.p2p_port,
+            self.http_bind_addr,
+            self.http_port,
+            self.eth_rpc_bind_addr,
+            self.eth_rpc_port,
+            self.dns_seeds.join(","),
+            self.fallback_seeds.join(","),
+            chain_id,
+            self.mining_threads,
+            self.mining_throttle_percent,
+            self.mining_propagate_first,
+            self.min_relay_fee_per_byte,
+            self.rbf_fee_increment_per_byte,
+            self.max_outbound_peers,
+            self.max_inbound_peers,
+            self.sync_max_wait_secs,
+            self.min_confirmations_for_spend,
+            self.peer_ban_secs,
+            self.checkpoint_sync,
+            self.dashboard_path,
+            self.access_log,
+            self.http_max_concurrent_requests,
+            self.http_rate_limit_per_sec,
+            self.http_rate_limit_burst,
+            self.http_rate_limit_whitelist.join(","),
+            self.admin_token,
+            self.backup_dir,
+            self.prune_depth,
+        )
+    }
+
+    /// Write a fully populated default settings file. Refuses to overwrite an existing file.
+    pub fn write_default(path: &Path) -> anyhow::Result<()> {
+        if path.exists() {
+            return Err(anyhow::anyhow!(
+                "node settings file already exists at {:?}",
+                path
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        fs::write(path, Self::default().to_conf_string())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_settings_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "astram-settings-{}/nodeSettings.conf",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        NodeSettings::write_default(&path).unwrap();
+        let loaded = NodeSettings::load_from(&path);
+        assert_eq!(loaded, NodeSettings::default());
+        assert!(NodeSettings::write_default(&path).is_err());
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn rate_limit_settings_parse() {
+        let settings = NodeSettings::parse(
+            "HTTP_RATE_LIMIT_PER_SEC=0\nHTTP_RATE_LIMIT_BURST=5\nHTTP_RATE_LIMIT_WHITELIST=10.0.0.2, ::1\n",
+        );
+        assert_eq!(settings.http_rate_limit_per_sec, 0);
+        assert_eq!(settings.http_rate_limit_burst, 5);
+        assert_eq!(settings.http_rate_limit_whitelist, vec!["10.0.0.2", "::1"]);
+    }
+
+    #[test]
+    fn dns_seeds_accept_list_and_legacy_key() {
+        let settings = NodeSettings::parse("DNS_SEEDS=http://a:8053, http://b:8053,\n");
+        assert_eq!(settings.dns_seeds, vec!["http://a:8053", "http://b:8053"]);
+
+        let legacy = NodeSettings::parse("DNS_SERVER_URL=http://c:8053\n");
+        assert_eq!(legacy.dns_seeds, vec!["http://c:8053"]);
+    }
+}
@@ -1,28 +1,21 @@
-use crate::wallet::Wallet;
-use Astram_core::transaction::{BINCODE_CONFIG, Transaction, TransactionInput, TransactionOutput};
+use crate::wallet::{DEFAULT_DERIVATION_PATH, Wallet, generate_mnemonic};
+use Astram_core::transaction::{BINCODE_CONFIG, Transaction, TransactionOutput};
+use Astram_core::config::calculate_default_fee;
+use Astram_core::security::{MAX_TX_OUTPUTS, MIN_OUTPUT_VALUE};
+use Astram_core::txbuilder::{FeePolicy, build_transaction, estimated_signed_size};
+use Astram_core::units::{checked_sum, format_amount, parse_asrm};
+use Astram_core::utxo::Utxo;
 use astram_config::config::Config;
+use base64::{Engine as _, engine::general_purpose};
 use primitive_types::U256;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::PathBuf;
-// ASRM unit constants (18 decimal places, same as Ethereum)
-const RAM_PER_ASRM: u128 = 1_000_000_000_000_000_000; // 1 ASRM = 10^18 ram
-
-/// Convert ASRM to ram (smallest unit) as U256
-pub fn asrm_to_ram(asrm: f64) -> U256 {
-    let ram = (asrm * RAM_PER_ASRM as f64) as u128;
-    U256::from(ram)
-}
-
-/// Convert ram (U256) to ASRM for display
-pub fn ram_to_asrm(ram: U256) -> f64 {
-    // Convert U256 to u128 (safe for reasonable amounts)
-    let ram_u128 = ram.low_u128();
-    ram_u128 as f64 / RAM_PER_ASRM as f64
-}
 
 #[derive(clap::Subcommand)]
 pub enum Commands {
@@ -32,15 +25,81 @@ pub enum Commands {
     /// Create a new Ethereum-compatible wallet (secp256k1) for MetaMask
     GenerateEth,
 
+    /// Create an HD wallet from a new BIP39 mnemonic (BIP44 derivation)
+    GenerateHd {
+        #[arg(long, default_value_t = 12, help = "Mnemonic length: 12 or 24 words")]
+        words: usize,
+    },
+
+    /// Rebuild a wallet from its BIP39 mnemonic
+    Restore {
+        #[arg(help = "Mnemonic phrase, quoted")]
+        mnemonic: String,
+        #[arg(long, help = "Derivation path (default m/44'/60'/0'/0/0)")]
+        path: Option<String>,
+    },
+
     /// Check the balance of a specific address
     Balance { address: String },
 
+    /// List confirmed transactions involving an address, newest first
+    History {
+        address: String,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Resume after this point; the previous page prints it
+        #[arg(long)]
+        cursor: Option<String>,
+    },
+
     /// Create, sign, and broadcast a transaction to the network
     /// Amount should be specified in ASRM (e.g., 1.5 for 1.5 ASRM)
     Send {
         to: String,
-        #[arg(help = "Amount in ASRM (e.g., 1.5)")]
-        amount: f64,
+        #[arg(help = "Amount in ASRM (e.g., 1.5), up to 18 decimals")]
+        amount: String,
+        #[arg(
+            long,
+            help = "Spend from the UTXOs saved by `refresh` instead of asking the node"
+        )]
+        offline: bool,
+    },
+
+    /// Pay several recipients in one transaction, signed once with a single change output
+    BatchSend {
+        #[arg(
+            long,
+            help = "JSON array of {\"to\": address, \"amount\": ASRM} recipients"
+        )]
+        recipients: String,
+        #[arg(
+            long,
+            help = "Spend from the UTXOs saved by `refresh` instead of asking the node"
+        )]
+        offline: bool,
+    },
+
+    /// Save the wallet's UTXOs and balance locally for `send --offline`
+    Refresh,
+
+    /// Build and sign a transaction offline from a saved UTXO list (for cold wallets)
+    BuildTx {
+        to: String,
+        #[arg(help = "Amount in ASRM (e.g., 1.5), up to 18 decimals")]
+        amount: String,
+        #[arg(
+            long,
+            help = "JSON from /address/{addr}/utxos, saved on an online machine"
+        )]
+        utxos_file: String,
+        #[arg(long, help = "Write the signed transaction here instead of stdout")]
+        out: Option<String>,
+    },
+
+    /// Broadcast a transaction signed with build-tx
+    Broadcast {
+        #[arg(long, help = "File holding the base64 transaction from build-tx")]
+        tx_file: String,
     },
 
     /// Manage CLI configuration
@@ -61,6 +120,11 @@ pub enum ConfigCommands {
 struct WalletJson {
     secret_key: String,
     address: String,
+    /// Set for HD wallets so the key can be re-derived
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mnemonic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    derivation_path: Option<String>,
 }
 
 fn get_wallet_path() -> PathBuf {
@@ -68,7 +132,7 @@ fn get_wallet_path() -> PathBuf {
     cfg.wallet_path_resolved()
 }
 
-fn save_wallet_json(wallet: &Wallet, path: &str) -> std::io::Result<()> {
+fn save_wallet_json(wallet: &Wallet, path: &str, hd: Option<(&str, &str)>) -> std::io::Result<()> {
     // Create parent directories if they don't exist
     if let Some(parent) = std::path::Path::new(path).parent() {
         fs::create_dir_all(parent)?;
@@ -77,6 +141,8 @@ fn save_wallet_json(wallet: &Wallet, path: &str) -> std::io::Result<()> {
     let wallet_json = WalletJson {
         secret_key: wallet.secret_hex(),
         address: wallet.address.clone(),
+        mnemonic: hd.map(|(mnemonic, _)| mnemonic.to_string()),
+        derivation_path: hd.map(|(_, path)| path.to_string()),
     };
     let data = serde_json::to_string_pretty(&wallet_json).unwrap();
     fs::write(path, data)
@@ -94,7 +160,60 @@ pub fn generate_wallet() {
     println!("   You can import this into MetaMask using the private key.");
 
     let path = get_wallet_path();
-    save_wallet_json(&wallet, path.to_str().unwrap()).expect("Failed to save wallet");
+    save_wallet_json(&wallet, path.to_str().unwrap(), None).expect("Failed to save wallet");
+}
+
+pub fn generate_hd_wallet(words: usize) {
+    let mnemonic = match generate_mnemonic(words) {
+        Ok(m) => m,
+        Err(e) => {
+            println!("[ERROR] {}", e);
+            return;
+        }
+    };
+    let wallet = Wallet::from_mnemonic(&mnemonic, DEFAULT_DERIVATION_PATH)
+        .expect("freshly generated mnemonic must derive");
+
+    println!("[OK] New HD wallet created successfully!");
+    println!("Address: {}", wallet.address);
+    println!("Derivation Path: {}", DEFAULT_DERIVATION_PATH);
+    println!("Mnemonic: {}", mnemonic);
+    println!();
+    println!("[WARN] IMPORTANT: Write down your mnemonic and keep it offline!");
+    println!("   Anyone with these words controls the funds; `restore` rebuilds the wallet.");
+
+    let path = get_wallet_path();
+    save_wallet_json(
+        &wallet,
+        path.to_str().unwrap(),
+        Some((&mnemonic, DEFAULT_DERIVATION_PATH)),
+    )
+    .expect("Failed to save wallet");
+}
+
+pub fn restore_wallet(mnemonic: &str, derivation_path: Option<&str>) {
+    let derivation_path = derivation_path.unwrap_or(DEFAULT_DERIVATION_PATH);
+    let wallet = match Wallet::from_mnemonic(mnemonic, derivation_path) {
+        Ok(w) => w,
+        Err(e) => {
+            println!("[ERROR] Restore failed: {}", e);
+            return;
+        }
+    };
+    // Store the phrase normalized so the file matches what generate-hd writes
+    let mnemonic = mnemonic.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    println!("[OK] Wallet restored!");
+    println!("Address: {}", wallet.address);
+    println!("Derivation Path: {}", derivation_path);
+
+    let path = get_wallet_path();
+    save_wallet_json(
+        &wallet,
+        path.to_str().unwrap(),
+        Some((&mnemonic, derivation_path)),
+    )
+    .expect("Failed to save wallet");
 }
 
 pub fn generate_eth_wallet() {
@@ -108,10 +227,18 @@ pub fn generate_eth_wallet() {
     println!("   Currency Symbol: ASRM");
 }
 
-fn load_wallet() -> Wallet {
+/// Address of the configured wallet, read without loading (or printing) its key
+fn load_wallet_address() -> Result<String, String> {
     let path = get_wallet_path();
-    let data = fs::read_to_string(&path).expect("Failed to read wallet file");
-    let wallet_json: WalletJson = serde_json::from_str(&data).expect("Failed to parse wallet JSON");
+    let data = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read wallet file {:?}: {}", path, e))?;
+    let wallet_json: WalletJson =
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse wallet JSON: {}", e))?;
+    Ok(wallet_json.address)
+}
+
+fn load_wallet() -> Wallet {
+    let wallet_json = read_wallet_json();
 
     println!("[INFO] Wallet loaded: {}", wallet_json.address);
     println!("Private key: {}", wallet_json.secret_key);
@@ -119,6 +246,17 @@ fn load_wallet() -> Wallet {
     Wallet::from_hex(&wallet_json.secret_key)
 }
 
+/// `load_wallet` without printing anything, for commands whose stdout is data
+fn load_wallet_quietly() -> Wallet {
+    Wallet::from_hex(&read_wallet_json().secret_key)
+}
+
+fn read_wallet_json() -> WalletJson {
+    let path = get_wallet_path();
+    let data = fs::read_to_string(&path).expect("Failed to read wallet file");
+    serde_json::from_str(&data).expect("Failed to parse wallet JSON")
+}
+
 pub fn get_balance(address: &str) {
     let cfg = Config::load();
     let url = format!("{}/address/{}/balance", cfg.node_rpc_url, address);
@@ -138,119 +276,359 @@ pub fn get_balance(address: &str) {
                     .map(U256::from)
                     .unwrap_or_else(U256::zero)
             };
-            let balance_asrm = ram_to_asrm(balance_ram);
-            println!("Balance: {} ASRM", balance_asrm);
+            println!("Balance: {}", format_amount(balance_ram));
         }
         Err(e) => println!("[ERROR] Query failed: {}", e),
     }
 }
 
-pub fn send_transaction(to: &str, amount_ram: U256) {
+/// All UTXOs of `address`, following the node's pages (largest outputs come first)
+fn fetch_utxos(client: &Client, node_url: &str, address: &str) -> Result<Vec<Value>, String> {
+    let mut utxos = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut url = format!("{}/address/{}/utxos", node_url, address);
+        if let Some(cursor) = &cursor {
+            url = format!("{}?cursor={}", url, cursor);
+        }
+        let page: Value = client
+            .get(&url)
+            .send()
+            .map_err(|e| format!("Query failed: {}", e))?
+            .json()
+            .map_err(|e| format!("Failed to parse UTXOs JSON: {}", e))?;
+        // Older nodes return a bare array with every UTXO
+        if let Value::Array(list) = page {
+            return Ok(list);
+        }
+        let list = page["utxos"].as_array().cloned().unwrap_or_default();
+        let done = list.is_empty();
+        utxos.extend(list);
+        cursor = page["next_cursor"].as_str().map(str::to_string);
+        if done || cursor.is_none() {
+            return Ok(utxos);
+        }
+    }
+}
+
+/// Longest a UTXO cache is trusted by `send --offline` when the node can't be asked
+/// whether the chain has moved since it was saved
+const MAX_UTXO_CACHE_AGE_SECS: i64 = 10 * 60;
+
+/// Chain tip as the node reports it on /health. `hash` is None for older nodes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct ChainTip {
+    height: u64,
+    hash: Option<String>,
+}
+
+fn fetch_tip(client: &Client, node_url: &str) -> Result<ChainTip, String> {
+    let health: Value = client
+        .get(format!("{}/health", node_url))
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|res| res.json())
+        .map_err(|e| format!("Query failed: {}", e))?;
+    Ok(ChainTip {
+        height: health["height"].as_u64().unwrap_or(0),
+        hash: health["tip"].as_str().map(str::to_string),
+    })
+}
+
+/// The wallet's UTXOs and balance at one chain tip, saved by `refresh` next to the
+/// wallet file
+#[derive(Serialize, Deserialize)]
+struct UtxoCache {
+    address: String,
+    tip: ChainTip,
+    /// Unix time of the refresh
+    fetched_at: i64,
+    /// Sum of `utxos` in ram, as 0x-prefixed hex like the node reports amounts
+    balance: String,
+    /// Entries as `/address/{addr}/utxos` returns them
+    utxos: Vec<Value>,
+}
+
+impl UtxoCache {
+    fn path() -> PathBuf {
+        get_wallet_path().with_file_name("utxo_cache.json")
+    }
+
+    fn load() -> Result<Self, String> {
+        let path = Self::path();
+        let data = fs::read_to_string(&path)
+            .map_err(|_| format!("No UTXO cache at {:?}; run `refresh` first", path))?;
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        let data = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, data).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+    }
+
+    /// Why the cache can't be spent from for `address`, if it can't: it belongs to
+    /// another wallet, the node's tip (`current_tip`, None when the node is unreachable)
+    /// has moved since the refresh, or, with no node to ask, it is older than
+    /// `MAX_UTXO_CACHE_AGE_SECS`
+    fn staleness(&self, address: &str, current_tip: Option<&ChainTip>, now: i64) -> Option<String> {
+        if !self.address.eq_ignore_ascii_case(address) {
+            return Some(format!("it was saved for {}", self.address));
+        }
+        match current_tip {
+            Some(tip) if *tip != self.tip => Some(format!(
+                "the chain moved from height {} to {}",
+                self.tip.height, tip.height
+            )),
+            Some(_) => None,
+            None if now - self.fetched_at > MAX_UTXO_CACHE_AGE_SECS => Some(format!(
+                "it is {}s old and the node can't be reached to confirm it",
+                now - self.fetched_at
+            )),
+            None => None,
+        }
+    }
+}
+
+/// Save the wallet's UTXOs and balance at the node's current tip for `send --offline`
+pub fn refresh_utxo_cache() {
     let cfg = Config::load();
-    let wallet = load_wallet();
     let client = Client::new();
+    let refreshed = load_wallet_address().and_then(|address| {
+        let tip = fetch_tip(&client, &cfg.node_rpc_url)?;
+        let utxos = fetch_utxos(&client, &cfg.node_rpc_url, &address)?;
+        // A block landing mid-fetch could leave a mix of two tips
+        if fetch_tip(&client, &cfg.node_rpc_url)? != tip {
+            return Err("The chain moved during the refresh; run it again".to_string());
+        }
+        let balance = checked_sum(utxos.iter().map(utxo_amount))
+            .ok_or_else(|| "UTXO sum overflows".to_string())?;
+        let cache = UtxoCache {
+            address,
+            tip,
+            fetched_at: chrono::Utc::now().timestamp(),
+            balance: format!("0x{:x}", balance),
+            utxos,
+        };
+        cache.save()?;
+        Ok((cache, balance))
+    });
+    match refreshed {
+        Ok((cache, balance)) => {
+            println!(
+                "[OK] Cached {} UTXO(s) for {} at height {}",
+                cache.utxos.len(),
+                cache.address,
+                cache.tip.height
+            );
+            println!("Balance: {}", format_amount(balance));
+        }
+        Err(e) => println!("[ERROR] {}", e),
+    }
+}
 
-    let url = format!("{}/address/{}/utxos", cfg.node_rpc_url, wallet.address);
-    let utxos: Vec<Value> = match client.get(&url).send() {
-        Ok(res) => match res.json() {
-            Ok(v) => v,
-            Err(e) => {
-                println!("[ERROR] Failed to parse UTXOs JSON: {}", e);
-                return;
-            }
-        },
-        Err(e) => {
-            println!("[ERROR] Query failed: {}", e);
-            return;
+/// UTXOs from the cache for `send --offline`, or why it can't be used. A cache the
+/// chain has moved past is deleted, so nothing spends from it again.
+fn cached_utxos(client: &Client, node_url: &str, address: &str) -> Result<Vec<Value>, String> {
+    let cache = UtxoCache::load()?;
+    let current_tip = fetch_tip(client, node_url).ok();
+    let now = chrono::Utc::now().timestamp();
+    if let Some(reason) = cache.staleness(address, current_tip.as_ref(), now) {
+        if current_tip.is_some() {
+            let _ = fs::remove_file(UtxoCache::path());
         }
-    };
+        return Err(format!("UTXO cache is stale ({}); run `refresh`", reason));
+    }
+    Ok(cache.utxos)
+}
 
-    if utxos.is_empty() {
-        println!("[WARN] No UTXOs available for address {}", wallet.address);
+/// Drop the inputs of a broadcast `tx` from the cache, so a later offline send doesn't
+/// pick them again before the next block invalidates it
+fn forget_spent_utxos(tx: &Transaction) {
+    let Ok(mut cache) = UtxoCache::load() else {
         return;
+    };
+    let spent = |u: &Value| {
+        tx.inputs.iter().any(|inp| {
+            u["txid"].as_str() == Some(inp.txid.as_str())
+                && u["vout"].as_u64() == Some(inp.vout as u64)
+        })
+    };
+    cache.utxos.retain(|u| !spent(u));
+    let balance = checked_sum(cache.utxos.iter().map(utxo_amount)).unwrap_or_default();
+    cache.balance = format!("0x{:x}", balance);
+    if let Err(e) = cache.save() {
+        println!("[WARN] {}", e);
     }
+}
 
-    let mut selected_inputs = vec![];
-    let mut input_sum = U256::zero();
-
-    for u in &utxos {
-        let txid = u["txid"].as_str().unwrap().to_string();
-        let vout = u["vout"].as_u64().unwrap() as u32;
-        // Parse amount as hex string (0x...) or number
-        let amt = if let Some(s) = u["amount"].as_str() {
-            if let Some(hex_str) = s.strip_prefix("0x") {
-                U256::from_str_radix(hex_str, 16).unwrap_or_else(|_| U256::zero())
-            } else {
-                U256::from_dec_str(s).unwrap_or_else(|_| U256::zero())
-            }
+/// UTXO amount from the node's JSON: hex string (0x...), decimal string or number
+fn utxo_amount(u: &Value) -> U256 {
+    if let Some(s) = u["amount"].as_str() {
+        if let Some(hex_str) = s.strip_prefix("0x") {
+            U256::from_str_radix(hex_str, 16).unwrap_or_else(|_| U256::zero())
         } else {
-            u["amount"]
-                .as_u64()
-                .map(U256::from)
-                .unwrap_or_else(U256::zero)
-        };
-        selected_inputs.push(TransactionInput {
-            txid,
-            vout,
-            pubkey: wallet.address.clone(),
-            signature: None,
-        });
-        input_sum = input_sum + amt;
-        if input_sum >= amount_ram {
-            break;
+            U256::from_dec_str(s).unwrap_or_else(|_| U256::zero())
         }
+    } else {
+        u["amount"]
+            .as_u64()
+            .map(U256::from)
+            .unwrap_or_else(U256::zero)
     }
+}
 
-    if input_sum < amount_ram {
-        println!(
-            "[WARN] Insufficient balance: have {} ASRM, need {} ASRM",
-            ram_to_asrm(input_sum),
-            ram_to_asrm(amount_ram)
-        );
-        return;
+/// A signed transaction and the numbers shown to the user before it is sent
+struct SignedTx {
+    tx: Transaction,
+    fee: U256,
+    change: U256,
+}
+
+/// An output worth less than this many times the fee of spending it counts as dust
+const DUST_FEE_MULTIPLE: u64 = 10;
+
+/// Most dust outputs swept into a single payment
+const MAX_CONSOLIDATED_INPUTS: usize = 10;
+
+/// Order `spendable` (UTXO, deep enough to spend) so that the shortest prefix covering
+/// a payment is also the smallest set of inputs: largest first, outputs the node
+/// recommends before shallow ones. Outputs worth less than the fee to spend them are
+/// left out. When `fee_for` quotes no more than the wallet default (a quiet mempool),
+/// up to `MAX_CONSOLIDATED_INPUTS` dust outputs are put first so they are swept into
+/// the change while inputs are cheap.
+fn select_coins(spendable: &[(Utxo, bool)], fee_for: &dyn Fn(usize) -> U256) -> Vec<Utxo> {
+    let one_input = estimated_signed_size(1, 1);
+    let input_cost = fee_for(estimated_signed_size(2, 1)).saturating_sub(fee_for(one_input));
+    let dust_limit = input_cost.saturating_mul(U256::from(DUST_FEE_MULTIPLE));
+    let quiet = fee_for(one_input) <= calculate_default_fee(one_input);
+
+    let largest_first = |deep: bool| {
+        let mut utxos: Vec<&Utxo> = spendable
+            .iter()
+            .filter(|(utxo, recommended)| *recommended == deep && utxo.amount() > input_cost)
+            .map(|(utxo, _)| utxo)
+            .collect();
+        utxos.sort_by(|a, b| b.amount().cmp(&a.amount()));
+        utxos
+    };
+    let mut deep = largest_first(true);
+    let shallow = largest_first(false);
+
+    let mut ordered = Vec::with_capacity(spendable.len());
+    if quiet {
+        let dust_start = deep.partition_point(|utxo| utxo.amount() >= dust_limit);
+        let sweep = (deep.len() - dust_start).min(MAX_CONSOLIDATED_INPUTS);
+        // Dust goes first, so the shortest prefix covering the payment always sweeps it
+        ordered.extend(deep.drain(deep.len() - sweep..).rev().cloned());
     }
+    ordered.extend(deep.into_iter().cloned());
+    ordered.extend(shallow.into_iter().cloned());
+    ordered
+}
 
-    // Calculate estimated transaction size and fee
-    // Typical transaction structure:
-    // - Base: ~100 bytes
-    // - Per input: ~100 bytes (txid, vout, pubkey, signature)
-    // - Per output: ~50 bytes (address, amount)
-    let estimated_tx_size = 100 + (selected_inputs.len() * 100) + (2 * 50); // Assume 2 outputs max
-    let fee = Astram_core::config::calculate_default_fee(estimated_tx_size);
+/// Select inputs from `utxos` (see `select_coins`) to pay `outputs`, return the change
+/// to the wallet's own address, and sign with `wallet`. `fee_for` maps a transaction
+/// size in bytes to the fee to pay, judged on the signed encoding (see
+/// `Astram_core::txbuilder`). Needs no network beyond what `fee_for` does, so it also
+/// runs on an air-gapped machine.
+fn build_signed_tx(
+    wallet: &Wallet,
+    utxos: Vec<Value>,
+    outputs: Vec<TransactionOutput>,
+    fee_for: impl Fn(usize) -> U256,
+) -> Result<SignedTx, String> {
+    if utxos.is_empty() {
+        return Err(format!("No UTXOs available for address {}", wallet.address));
+    }
 
-    println!("Transaction Details:");
-    println!("   Inputs: {} UTXO(s)", selected_inputs.len());
-    println!("   Estimated size: {} bytes", estimated_tx_size);
-    println!("   Fee: {} ASRM ({} ram)", ram_to_asrm(fee), fee);
+    // Spend outputs the node considers deep enough first; older nodes omit the flag
+    let spend_recommended = |u: &Value| u["spend_recommended"].as_bool().unwrap_or(true);
 
-    // Check if we have enough for amount + fee
-    if input_sum < amount_ram + fee {
-        println!(
-            "[WARN] Insufficient balance for amount + fee: have {} ASRM, need {} ASRM",
-            ram_to_asrm(input_sum),
-            ram_to_asrm(amount_ram + fee)
+    let mut spendable = Vec::with_capacity(utxos.len());
+    for u in &utxos {
+        let (Some(txid), Some(vout)) = (u["txid"].as_str(), u["vout"].as_u64()) else {
+            return Err(format!("Malformed UTXO entry: {}", u));
+        };
+        let utxo = Utxo::new(
+            txid.to_string(),
+            vout as u32,
+            wallet.address.clone(),
+            utxo_amount(u),
+            u["height"].as_u64().unwrap_or(0),
+            u["coinbase"].as_bool().unwrap_or(false),
         );
-        return;
+        spendable.push((utxo, spend_recommended(u)));
     }
+    let candidates = select_coins(&spendable, &fee_for);
 
-    let mut outputs = vec![TransactionOutput::new(to.to_string(), amount_ram)];
+    let recipients = outputs.len();
+    let amount_ram = checked_sum(outputs.iter().map(|o| o.amount()))
+        .ok_or_else(|| "Output sum overflows".to_string())?;
+    let tx = build_transaction(
+        &candidates,
+        outputs,
+        &FeePolicy::BySize(&fee_for),
+        &wallet.address,
+        |tx| sign_transaction(wallet, tx),
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Inputs are taken in order, so the selection is a prefix of the candidates
+    let selected = &candidates[..tx.inputs.len()];
+    let input_sum = checked_sum(selected.iter().map(|utxo| utxo.amount()))
+        .ok_or_else(|| "Input sum overflows".to_string())?;
+    let fee = tx
+        .fee(input_sum)
+        .ok_or_else(|| "Outputs exceed inputs".to_string())?;
+    // Change, when there is any, follows the requested outputs
+    let change = tx
+        .outputs
+        .get(recipients)
+        .map(|o| o.amount())
+        .unwrap_or_default();
+    let is_shallow = |utxo: &Utxo| {
+        spendable
+            .iter()
+            .any(|(u, deep)| !deep && u.txid == utxo.txid && u.vout == utxo.vout)
+    };
+    let shallow_inputs = selected.iter().filter(|utxo| is_shallow(utxo)).count();
+    let size = bincode::encode_to_vec(&tx, *BINCODE_CONFIG)
+        .map(|b| b.len())
+        .unwrap_or(0);
 
-    let change = input_sum - amount_ram - fee;
+    eprintln!("Transaction Details:");
+    eprintln!("   Inputs: {} UTXO(s)", tx.inputs.len());
+    eprintln!("   Size: {} bytes", size);
+    eprintln!("   Fee: {}", format_amount(fee));
+    if shallow_inputs > 0 {
+        eprintln!(
+            "[WARN] {} input(s) have fewer confirmations than the node recommends and could be reorged away",
+            shallow_inputs
+        );
+    }
+    if change.is_zero() {
+        eprintln!("   No change (exact amount + fee)");
+    }
+
+    eprintln!("[OK] Transaction created successfully!");
+    eprintln!("   TXID (internal): {}", tx.txid);
+    eprintln!("   ETH Hash (external): {}", tx.eth_hash);
+    if recipients > 1 {
+        eprintln!("   Recipients: {}", recipients);
+    }
+    eprintln!("   Amount: {}", format_amount(amount_ram));
+    eprintln!("   Fee: {}", format_amount(fee));
     if change > U256::zero() {
-        outputs.push(TransactionOutput::new(wallet.address.clone(), change));
-    } else {
-        println!("   No change (exact amount + fee)");
+        eprintln!("   Change: {}", format_amount(change));
     }
 
-    let mut tx = Transaction {
-        txid: "".to_string(),
-        eth_hash: "".to_string(),
-        inputs: selected_inputs,
-        outputs,
-        timestamp: chrono::Utc::now().timestamp(),
-    };
+    Ok(SignedTx { tx, fee, change })
+}
 
-    // Step 5: Sign transaction (secp256k1)
+/// Sign every input of `tx` with `wallet` and fill in the hashes
+fn sign_transaction(wallet: &Wallet, mut tx: Transaction) -> anyhow::Result<Transaction> {
+    // Sign transaction (secp256k1)
     use Astram_core::crypto::WalletKeypair;
     use secp256k1::SecretKey;
 
@@ -264,45 +642,87 @@ pub fn send_transaction(to: &str, amount_ram: U256) {
         public_key,
     };
 
-    if let Err(e) = tx.sign(&keypair) {
-        println!("??Failed to sign transaction: {}", e);
-        return;
-    }
+    tx.sign(&keypair)
+        .map_err(|e| anyhow::anyhow!("Failed to sign transaction: {}", e))?;
 
     tx.verify_signatures()
         .expect("Signature verification failed after signing");
 
-    // Step 6: Populate txid and eth_hash
-    tx = tx.with_hashes();
+    // Populate txid and eth_hash
+    Ok(tx.with_hashes())
+}
 
-    println!("[OK] Transaction created successfully!");
-    println!("   TXID (internal): {}", tx.txid);
-    println!("   ETH Hash (external): {}", tx.eth_hash);
-    println!("   Amount: {} ASRM", ram_to_asrm(amount_ram));
-    println!("   Fee: {} ASRM ({} ram)", ram_to_asrm(fee), fee);
-    if change > U256::zero() {
-        println!("   Change: {} ASRM", ram_to_asrm(change));
+/// Most a node's fee quote may exceed the wallet default fee by, so a faulty or hostile
+/// node can't make the wallet overpay
+const MAX_FEE_QUOTE_MULTIPLE: u64 = 10;
+
+/// The node's quote for `bytes`, capped at MAX_FEE_QUOTE_MULTIPLE times the wallet default
+fn cap_fee_quote(quote: U256, bytes: usize) -> U256 {
+    quote.min(calculate_default_fee(bytes) * U256::from(MAX_FEE_QUOTE_MULTIPLE))
+}
+
+/// Suggested fee for a transaction of `bytes` from the node's /fee/estimate, or None
+/// when the node is unreachable or predates the endpoint
+fn fetch_fee_estimate(client: &Client, node_url: &str, bytes: usize) -> Option<U256> {
+    let url = format!("{}/fee/estimate?bytes={}", node_url, bytes);
+    client
+        .get(&url)
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|res| res.json::<Value>())
+        .ok()
+        .and_then(|reply| {
+            let hex_str = reply["suggested_fee"].as_str()?.strip_prefix("0x")?;
+            U256::from_str_radix(hex_str, 16).ok()
+        })
+}
+
+/// Serialize a signed transaction as base64 of its bincode encoding, the same bytes
+/// POST /tx accepts
+fn encode_signed_tx(tx: &Transaction) -> Result<String, String> {
+    let bytes = bincode::encode_to_vec(tx, *BINCODE_CONFIG)
+        .map_err(|e| format!("Failed to serialize transaction: {}", e))?;
+    Ok(general_purpose::STANDARD.encode(bytes))
+}
+
+/// Decode a base64 blob from `encode_signed_tx` and check it offline: it must decode
+/// completely, carry valid signatures and hash to the txid it claims
+fn decode_signed_tx(blob: &str) -> Result<Transaction, String> {
+    let bytes = general_purpose::STANDARD
+        .decode(blob.trim())
+        .map_err(|e| format!("Transaction blob is not base64: {}", e))?;
+    let (tx, read) = bincode::decode_from_slice::<Transaction, _>(&bytes, *BINCODE_CONFIG)
+        .map_err(|e| format!("Transaction blob is not a valid transaction: {}", e))?;
+    if read != bytes.len() {
+        return Err("Transaction blob has trailing bytes".to_string());
     }
-    println!(
-        "Signature: {}",
-        tx.inputs
-            .get(0)
-            .and_then(|i| i.signature.as_deref())
-            .unwrap_or("no signature")
-    );
+    match tx.verify_signatures() {
+        Ok(true) => {}
+        Ok(false) => return Err("Transaction signature is invalid".to_string()),
+        Err(e) => return Err(format!("Transaction signature check failed: {}", e)),
+    }
+    if tx.compute_txid().ok().as_deref() != Some(tx.txid.as_str()) {
+        return Err(format!(
+            "Transaction txid {} does not match its contents",
+            tx.txid
+        ));
+    }
+    Ok(tx)
+}
 
-    // Step 7: Serialize
-    let body = match bincode::encode_to_vec(&tx, *BINCODE_CONFIG) {
+/// POST a signed transaction to the node's /tx and report the outcome. True if the
+/// node accepted it.
+fn broadcast_signed_tx(client: &Client, node_url: &str, tx: &Transaction) -> bool {
+    let body = match bincode::encode_to_vec(tx, *BINCODE_CONFIG) {
         Ok(b) => b,
         Err(e) => {
             println!("[ERROR] Failed to serialize transaction: {}", e);
-            return;
+            return false;
         }
     };
 
-    // Step 8: POST /tx
     match client
-        .post(format!("{}/tx", cfg.node_rpc_url))
+        .post(format!("{}/tx", node_url))
         .body(body)
         .header("Content-Type", "application/octet-stream")
         .send()
@@ -313,12 +733,626 @@ pub fn send_transaction(to: &str, amount_ram: U256) {
             response.read_to_string(&mut text).unwrap_or_default();
             if status.is_success() {
                 println!("[OK] Transaction broadcast completed!");
+                let reply: Value = serde_json::from_str(&text).unwrap_or_default();
+                for warning in reply["warnings"].as_array().into_iter().flatten() {
+                    println!("[WARN] {}", warning.as_str().unwrap_or_default());
+                }
+                if let Some(blocks) = reply["mempool"]["estimated_blocks"].as_u64() {
+                    println!(
+                        "   Mempool rank: {} of {}, expected to confirm in ~{} block(s)",
+                        reply["mempool"]["mempool_rank"],
+                        reply["mempool"]["mempool_size"],
+                        blocks
+                    );
+                }
+                true
             } else {
                 println!("[ERROR] Transaction failed!");
                 println!("Status: {}", status);
                 println!("Response body: {}", text);
+                false
+            }
+        }
+        Err(e) => {
+            println!("[ERROR] Transaction failed (network/reqwest error): {}", e);
+            false
+        }
+    }
+}
+
+pub fn show_history(address: &str, limit: usize, cursor: Option<&str>) {
+    let cfg = Config::load();
+    let mut url = format!(
+        "{}/address/{}/transactions?limit={}",
+        cfg.node_rpc_url, address, limit
+    );
+    if let Some(cursor) = cursor {
+        url = format!("{}&cursor={}", url, cursor);
+    }
+    let page: Value = match Client::new().get(&url).send().and_then(|res| res.json()) {
+        Ok(page) => page,
+        Err(e) => {
+            println!("[ERROR] Query failed: {}", e);
+            return;
+        }
+    };
+    let Some(transactions) = page["transactions"].as_array() else {
+        println!("[ERROR] Unexpected response: {}", page);
+        return;
+    };
+    if transactions.is_empty() {
+        println!("No transactions for {}", address);
+        return;
+    }
+
+    // "0x.." or "-0x.." from the node
+    let signed_amount = |v: &Value| {
+        let s = v.as_str().unwrap_or("0x0");
+        let (sign, hex_str) = match s.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("+", s),
+        };
+        let ram = U256::from_str_radix(hex_str.trim_start_matches("0x"), 16)
+            .unwrap_or_else(|_| U256::zero());
+        format!("{}{}", sign, format_amount(ram))
+    };
+
+    println!(
+        "{:<8} {:<19} {:<9} {:>52}  {}",
+        "Height", "Time (UTC)", "Type", "Amount", "TXID"
+    );
+    for tx in transactions {
+        let time = tx["timestamp"]
+            .as_i64()
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        println!(
+            "{:<8} {:<19} {:<9} {:>52}  {}",
+            tx["block_height"],
+            time,
+            tx["direction"].as_str().unwrap_or("?"),
+            signed_amount(&tx["amount_delta"]),
+            tx["txid"].as_str().unwrap_or_default()
+        );
+    }
+    match page["total"].as_u64() {
+        Some(total) => println!("Showing {} of {}", transactions.len(), total),
+        None => println!("Showing {}", transactions.len()),
+    }
+    if let Some(next) = page["next_cursor"].as_str() {
+        println!("More: --cursor {}", next);
+    }
+}
+
+/// With `offline`, inputs come from the `refresh` cache and the fee is the wallet
+/// default, so the node is only needed for the broadcast itself
+pub fn send_transaction(to: &str, amount_ram: U256, offline: bool) {
+    send_outputs(
+        vec![TransactionOutput::new(to.to_string(), amount_ram)],
+        offline,
+    );
+}
+
+/// Pay every recipient listed in `recipients_file` with one transaction (see
+/// `parse_recipients` for the format)
+pub fn batch_send(recipients_file: &str, offline: bool) {
+    let outputs = match fs::read_to_string(recipients_file)
+        .map_err(|e| format!("Failed to read {}: {}", recipients_file, e))
+        .and_then(|data| parse_recipients(&data))
+    {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            println!("[ERROR] {}", e);
+            return;
+        }
+    };
+
+    println!("Sending to {} recipients:", outputs.len());
+    for out in &outputs {
+        println!("   {} -> {}", format_amount(out.amount()), out.to);
+    }
+    send_outputs(outputs, offline);
+}
+
+/// Parse a batch payment list: a JSON array of `{"to": "0x...", "amount": "1.5"}`, the
+/// amount in ASRM as a string or a number. Every entry is checked before anything is
+/// signed, so one bad line fails the whole batch.
+fn parse_recipients(data: &str) -> Result<Vec<TransactionOutput>, String> {
+    let entries: Vec<Value> = serde_json::from_str(data)
+        .map_err(|e| format!("Recipients must be a JSON array of {{to, amount}}: {}", e))?;
+    if entries.is_empty() {
+        return Err("Recipient list is empty".to_string());
+    }
+    // One output is left for change
+    if entries.len() >= MAX_TX_OUTPUTS {
+        return Err(format!(
+            "Too many recipients: {} (max {} per transaction)",
+            entries.len(),
+            MAX_TX_OUTPUTS - 1
+        ));
+    }
+
+    let mut outputs = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let line = i + 1;
+        let to = entry["to"]
+            .as_str()
+            .ok_or_else(|| format!("Recipient {}: missing \"to\" address", line))?;
+        let is_address = to.len() == 42
+            && to.starts_with("0x")
+            && to[2..].bytes().all(|b| b.is_ascii_hexdigit());
+        if !is_address {
+            return Err(format!("Recipient {}: invalid address {:?}", line, to));
+        }
+
+        let amount = match &entry["amount"] {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            _ => return Err(format!("Recipient {}: missing \"amount\"", line)),
+        };
+        let amount_ram = parse_asrm(&amount)
+            .ok_or_else(|| format!("Recipient {}: invalid amount {:?}", line, amount))?;
+        if amount_ram < U256::from(MIN_OUTPUT_VALUE) {
+            return Err(format!(
+                "Recipient {}: amount {} is below the minimum output of {}",
+                line,
+                format_amount(amount_ram),
+                format_amount(U256::from(MIN_OUTPUT_VALUE))
+            ));
+        }
+        outputs.push(TransactionOutput::new(to.to_string(), amount_ram));
+    }
+    Ok(outputs)
+}
+
+/// Sign one transaction paying `outputs` and broadcast it (see `send_transaction`)
+fn send_outputs(outputs: Vec<TransactionOutput>, offline: bool) {
+    let cfg = Config::load();
+    let wallet = load_wallet();
+    let client = Client::new();
+
+    let utxos = if offline {
+        cached_utxos(&client, &cfg.node_rpc_url, &wallet.address)
+    } else {
+        fetch_utxos(&client, &cfg.node_rpc_url, &wallet.address)
+    };
+    let utxos = match utxos {
+        Ok(utxos) => utxos,
+        Err(e) => {
+            println!("[ERROR] {}", e);
+            return;
+        }
+    };
+
+    // Coin selection and fee settling ask about the same few sizes; query each once and
+    // fall back to the wallet default if the node can't say
+    let quotes = RefCell::new(HashMap::new());
+    let fee_for = |bytes: usize| {
+        if offline {
+            return calculate_default_fee(bytes);
+        }
+        let mut quotes = quotes.borrow_mut();
+        if let Some(fee) = quotes.get(&bytes) {
+            return *fee;
+        }
+        let fee = match fetch_fee_estimate(&client, &cfg.node_rpc_url, bytes) {
+            Some(quote) => {
+                let fee = cap_fee_quote(quote, bytes);
+                if fee < quote && quotes.is_empty() {
+                    println!(
+                        "[WARN] Node fee estimate is over {}x the wallet default, paying the cap",
+                        MAX_FEE_QUOTE_MULTIPLE
+                    );
+                }
+                fee
+            }
+            None => {
+                if quotes.is_empty() {
+                    println!("[WARN] Node fee estimate unavailable, using the wallet default fee");
+                }
+                calculate_default_fee(bytes)
+            }
+        };
+        quotes.insert(bytes, fee);
+        fee
+    };
+    let signed = match build_signed_tx(&wallet, utxos, outputs, fee_for) {
+        Ok(signed) => signed,
+        Err(e) => {
+            println!("[WARN] {}", e);
+            return;
+        }
+    };
+    println!(
+        "Signature: {}",
+        signed
+            .tx
+            .inputs
+            .get(0)
+            .and_then(|i| i.signature.as_deref())
+            .unwrap_or("no signature")
+    );
+
+    if broadcast_signed_tx(&client, &cfg.node_rpc_url, &signed.tx) {
+        forget_spent_utxos(&signed.tx);
+    }
+}
+
+/// Build and sign a transaction from UTXOs in a local file (the JSON `/address/{addr}/utxos`
+/// returns, saved on an online machine) without contacting a node. The base64 blob goes
+/// to `out`, or stdout when none is given.
+/// With no `out` file the blob is the only thing written to stdout, so it can be piped
+/// or redirected; diagnostics go to stderr.
+pub fn build_offline_transaction(to: &str, amount_ram: U256, utxos_file: &str, out: Option<&str>) {
+    let wallet = load_wallet_quietly();
+
+    let utxos = match fs::read_to_string(utxos_file)
+        .map_err(|e| format!("Failed to read {}: {}", utxos_file, e))
+        .and_then(|data| {
+            serde_json::from_str::<Value>(&data)
+                .map_err(|e| format!("Failed to parse {}: {}", utxos_file, e))
+        }) {
+        // Either a bare array or a page object from the node
+        Ok(Value::Array(list)) => list,
+        Ok(page) => page["utxos"].as_array().cloned().unwrap_or_default(),
+        Err(e) => {
+            eprintln!("[ERROR] {}", e);
+            return;
+        }
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    write_offline_transaction(&wallet, to, amount_ram, utxos, out, &mut stdout);
+}
+
+/// Sign and check the offline transaction, then write its blob to `out` or, without
+/// one, as the only line on `stdout`. Everything else goes to stderr.
+fn write_offline_transaction(
+    wallet: &Wallet,
+    to: &str,
+    amount_ram: U256,
+    utxos: Vec<Value>,
+    out: Option<&str>,
+    stdout: &mut impl Write,
+) {
+    // No node to ask offline, so pay the wallet default for the signed size
+    let fee_for = calculate_default_fee;
+    let outputs = vec![TransactionOutput::new(to.to_string(), amount_ram)];
+    let signed = match build_signed_tx(wallet, utxos, outputs, fee_for) {
+        Ok(signed) => signed,
+        Err(e) => {
+            eprintln!("[WARN] {}", e);
+            return;
+        }
+    };
+
+    // Check the blob before it leaves this machine: what the online side relays must
+    // decode to this exact, correctly signed transaction
+    let blob = match encode_signed_tx(&signed.tx).and_then(|blob| {
+        let decoded = decode_signed_tx(&blob)?;
+        if decoded.txid != signed.tx.txid {
+            return Err("Transaction blob does not round-trip".to_string());
+        }
+        Ok(blob)
+    }) {
+        Ok(blob) => blob,
+        Err(e) => {
+            eprintln!("[ERROR] {}", e);
+            return;
+        }
+    };
+
+    match out {
+        Some(path) => match fs::write(path, &blob) {
+            Ok(()) => {
+                eprintln!("[OK] Signed transaction written to {}", path);
+                eprintln!(
+                    "   Fee {}, change {}. Relay it from an online machine with `broadcast --tx-file {}`",
+                    format_amount(signed.fee),
+                    format_amount(signed.change),
+                    path
+                );
+            }
+            Err(e) => eprintln!("[ERROR] Failed to write {}: {}", path, e),
+        },
+        None => {
+            if let Err(e) = writeln!(stdout, "{}", blob) {
+                eprintln!("[ERROR] Failed to write the transaction: {}", e);
+                return;
             }
+            eprintln!(
+                "[OK] Fee {}, change {}",
+                format_amount(signed.fee),
+                format_amount(signed.change)
+            );
+        }
+    }
+}
+
+/// Relay a transaction signed elsewhere (see `build_offline_transaction`)
+pub fn broadcast_transaction_file(tx_file: &str) {
+    let cfg = Config::load();
+    let tx = match fs::read_to_string(tx_file)
+        .map_err(|e| format!("Failed to read {}: {}", tx_file, e))
+        .and_then(|blob| decode_signed_tx(&blob))
+    {
+        Ok(tx) => tx,
+        Err(e) => {
+            println!("[ERROR] {}", e);
+            return;
         }
-        Err(e) => println!("[ERROR] Transaction failed (network/reqwest error): {}", e),
+    };
+
+    println!("Broadcasting transaction {}", tx.txid);
+    broadcast_signed_tx(&Client::new(), &cfg.node_rpc_url, &tx);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn funded(wallet: &Wallet) -> Vec<Value> {
+        vec![serde_json::json!({
+            "txid": "ab".repeat(32),
+            "vout": 0,
+            "to": wallet.address,
+            "amount": format!("0x{:x}", U256::from(10u64).pow(U256::from(19u64))),
+        })]
+    }
+
+    /// One deep-enough UTXO per amount, distinguished by txid
+    fn coins(wallet: &Wallet, amounts: &[U256]) -> Vec<Value> {
+        amounts
+            .iter()
+            .enumerate()
+            .map(|(i, amount)| {
+                serde_json::json!({
+                    "txid": format!("{:02x}", i).repeat(32),
+                    "vout": 0,
+                    "to": wallet.address,
+                    "amount": format!("0x{:x}", amount),
+                    "spend_recommended": true,
+                })
+            })
+            .collect()
+    }
+
+    fn asrm(n: u64) -> U256 {
+        Astram_core::config::RAM_PER_ASRM * U256::from(n)
+    }
+
+    fn encoded_len(tx: &Transaction) -> usize {
+        bincode::encode_to_vec(tx, *BINCODE_CONFIG).unwrap().len()
+    }
+
+    fn pay(amount: U256) -> Vec<TransactionOutput> {
+        vec![TransactionOutput::new("0xabc".to_string(), amount)]
+    }
+
+    #[test]
+    fn utxo_cache_goes_stale_with_the_tip_or_age() {
+        let tip = |height: u64, hash: &str| ChainTip {
+            height,
+            hash: Some(hash.to_string()),
+        };
+        let cache = UtxoCache {
+            address: "0xAbC".to_string(),
+            tip: tip(10, "aa"),
+            fetched_at: 1_000,
+            balance: "0x0".to_string(),
+            utxos: Vec::new(),
+        };
+        let now = 1_000 + MAX_UTXO_CACHE_AGE_SECS * 2;
+
+        let stale = |address: &str, current: ChainTip| {
+            cache.staleness(address, Some(&current), now).is_some()
+        };
+
+        // Same tip: fresh however old, and addresses compare case-insensitively
+        assert!(!stale("0xabc", tip(10, "aa")));
+        assert!(stale("0xdef", tip(10, "aa")));
+        // A new block, or a reorg to another block at the same height
+        assert!(stale("0xabc", tip(11, "bb")));
+        assert!(stale("0xabc", tip(10, "bb")));
+        // Node unreachable: trusted only while young
+        let young = 1_000 + MAX_UTXO_CACHE_AGE_SECS;
+        assert_eq!(cache.staleness("0xabc", None, young), None);
+        assert!(cache.staleness("0xabc", None, young + 1).is_some());
+    }
+
+    #[test]
+    fn offline_blob_round_trips_and_verifies() {
+        let wallet = Wallet::new();
+        let amount = U256::from(10u64).pow(U256::from(18u64));
+        let signed =
+            build_signed_tx(&wallet, funded(&wallet), pay(amount), calculate_default_fee).unwrap();
+
+        let blob = encode_signed_tx(&signed.tx).unwrap();
+        let decoded = decode_signed_tx(&blob).unwrap();
+        assert_eq!(decoded.txid, signed.tx.txid);
+        assert_eq!(decoded.outputs[0].amount(), amount);
+        // surrounding whitespace from a copied file is fine
+        assert!(decode_signed_tx(&format!("{}\n", blob)).is_ok());
+    }
+
+    #[test]
+    fn offline_stdout_is_only_the_encoded_transaction() {
+        let wallet = Wallet::new();
+        let amount = U256::from(10u64).pow(U256::from(18u64));
+        let mut stdout = Vec::new();
+        write_offline_transaction(&wallet, "0xabc", amount, funded(&wallet), None, &mut stdout);
+
+        let stdout = String::from_utf8(stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.len(), 1, "{}", stdout);
+        let tx = decode_signed_tx(lines[0]).unwrap();
+        assert_eq!(tx.outputs[0].amount(), amount);
+
+        // A failure leaves stdout empty rather than holding a half message
+        let mut stdout = Vec::new();
+        write_offline_transaction(&wallet, "0xabc", amount, Vec::new(), None, &mut stdout);
+        assert!(stdout.is_empty());
+    }
+
+    #[test]
+    fn fee_matches_the_signed_size() {
+        let wallet = Wallet::new();
+        let amount = U256::from(10u64).pow(U256::from(18u64));
+        let input = utxo_amount(&funded(&wallet)[0]);
+        let quote = calculate_default_fee;
+        let busy = |bytes: usize| calculate_default_fee(bytes) * U256::from(3u8);
+
+        for fee_for in [&quote as &dyn Fn(usize) -> U256, &busy] {
+            let signed = build_signed_tx(&wallet, funded(&wallet), pay(amount), fee_for).unwrap();
+            let size = encoded_len(&signed.tx);
+            // At, or a byte's worth above, the quote for this exact size
+            assert!(signed.fee >= fee_for(size));
+            assert!(signed.fee <= fee_for(size + 2));
+            assert_eq!(signed.change, input - amount - signed.fee);
+            assert_eq!(signed.tx.outputs[1].amount(), signed.change);
+        }
+    }
+
+    #[test]
+    fn node_fee_quotes_are_capped() {
+        let default = calculate_default_fee(300);
+        let cap = default * U256::from(MAX_FEE_QUOTE_MULTIPLE);
+        let busy = default * U256::from(3u8);
+        assert_eq!(cap_fee_quote(busy, 300), busy);
+        assert_eq!(cap_fee_quote(cap, 300), cap);
+        assert_eq!(cap_fee_quote(U256::MAX, 300), cap);
+    }
+
+    #[test]
+    fn selection_uses_the_fewest_inputs() {
+        let wallet = Wallet::new();
+        let utxos = coins(&wallet, &[asrm(1), asrm(1), asrm(1), asrm(5), asrm(2)]);
+        let busy = |bytes: usize| calculate_default_fee(bytes) * U256::from(3u8);
+
+        // Greedy in listing order would take four inputs; one is enough
+        let signed = build_signed_tx(&wallet, utxos.clone(), pay(asrm(4)), busy).unwrap();
+        assert_eq!(signed.tx.inputs.len(), 1);
+        assert_eq!(signed.tx.inputs[0].txid, "03".repeat(32));
+
+        let signed = build_signed_tx(&wallet, utxos.clone(), pay(asrm(6)), busy).unwrap();
+        assert_eq!(signed.tx.inputs.len(), 2);
+        assert_eq!(signed.tx.outputs[1].to, wallet.address);
+        assert_eq!(asrm(7), asrm(6) + signed.fee + signed.change);
+
+        let short = build_signed_tx(&wallet, utxos, pay(asrm(10)), busy);
+        assert!(short.unwrap_err().contains("Insufficient funds"));
+    }
+
+    #[test]
+    fn quiet_mempool_sweeps_dust_into_change() {
+        let wallet = Wallet::new();
+        // Spending one input costs ~0.0001 ASRM at the default rate: three outputs are
+        // dust worth sweeping, the last one isn't worth its own fee
+        let dust = U256::from(500_000_000_000_000u64);
+        let uneconomic = U256::from(50_000_000_000_000u64);
+        let utxos = coins(&wallet, &[dust, asrm(5), dust, dust, uneconomic]);
+        let swept_inputs = |signed: &SignedTx| {
+            let mut txids: Vec<_> = signed.tx.inputs.iter().map(|i| i.txid.clone()).collect();
+            txids.sort();
+            txids
+        };
+
+        let quiet =
+            build_signed_tx(&wallet, utxos.clone(), pay(asrm(1)), calculate_default_fee).unwrap();
+        let expected: Vec<_> = ["00", "01", "02", "03"]
+            .iter()
+            .map(|b| b.repeat(32))
+            .collect();
+        assert_eq!(swept_inputs(&quiet), expected);
+        assert_eq!(quiet.tx.outputs[1].to, wallet.address);
+        assert_eq!(
+            asrm(5) + dust * U256::from(3u8),
+            asrm(1) + quiet.fee + quiet.change
+        );
+
+        // At three times the rate sweeping isn't worth it
+        let busy = |bytes: usize| calculate_default_fee(bytes) * U256::from(3u8);
+        let busy = build_signed_tx(&wallet, utxos, pay(asrm(1)), busy).unwrap();
+        assert_eq!(swept_inputs(&busy), vec!["01".repeat(32)]);
+    }
+
+    #[test]
+    fn batch_pays_every_recipient_with_one_change_output() {
+        let wallet = Wallet::new();
+        let alice = format!("0x{}", "a1".repeat(20));
+        let bob = format!("0x{}", "b2".repeat(20));
+        let json = format!(
+            r#"[{{"to": "{}", "amount": "1.5"}}, {{"to": "{}", "amount": 2}}]"#,
+            alice, bob
+        );
+        let outputs = parse_recipients(&json).unwrap();
+        assert_eq!(outputs[0].amount(), asrm(3) / U256::from(2u8));
+        assert_eq!(outputs[1].amount(), asrm(2));
+
+        let signed =
+            build_signed_tx(&wallet, funded(&wallet), outputs, calculate_default_fee).unwrap();
+        let tx = &signed.tx;
+        assert_eq!(tx.outputs.len(), 3);
+        assert_eq!(
+            (tx.outputs[0].to.as_str(), tx.outputs[1].to.as_str()),
+            (&*alice, &*bob)
+        );
+        assert_eq!(tx.outputs[2].to, wallet.address);
+        assert_eq!(tx.outputs[2].amount(), signed.change);
+        assert_eq!(
+            asrm(10),
+            asrm(3) / U256::from(2u8) + asrm(2) + signed.fee + signed.change
+        );
+        assert!(signed.fee >= calculate_default_fee(encoded_len(tx)));
+        assert!(tx.verify_signatures().unwrap());
+    }
+
+    #[test]
+    fn bad_recipient_lists_are_rejected() {
+        let alice = format!("0x{}", "a1".repeat(20));
+        let entry = |to: &str, amount: &str| format!(r#"{{"to": "{}", "amount": {}}}"#, to, amount);
+        let list = |entries: &[String]| format!("[{}]", entries.join(","));
+
+        assert!(parse_recipients("[]").is_err());
+        assert!(parse_recipients(r#"{"to": "0x00"}"#).is_err());
+        for bad in [
+            entry(&alice, "\"0\""),
+            entry(&alice, "0"),
+            entry(&alice, "\"-1\""),
+            entry(&alice, "-1"),
+            entry(&alice, "\"abc\""),
+            entry(&alice, "\"0.0000000000000000001\""),
+            // Below the node's dust limit
+            entry(&alice, "\"0.0000001\""),
+            entry("0xabc", "\"1\""),
+            entry(&alice[2..], "\"1\""),
+            r#"{"amount": "1"}"#.to_string(),
+        ] {
+            // One bad entry fails the whole batch
+            let json = list(&[entry(&alice, "\"1\""), bad.clone()]);
+            assert!(parse_recipients(&json).is_err(), "{}", bad);
+        }
+
+        let full = vec![entry(&alice, "\"1\""); MAX_TX_OUTPUTS];
+        assert!(parse_recipients(&list(&full)).is_err());
+        assert_eq!(
+            parse_recipients(&list(&full[1..])).unwrap().len(),
+            MAX_TX_OUTPUTS - 1
+        );
+    }
+
+    #[test]
+    fn tampered_blob_is_rejected_offline() {
+        let wallet = Wallet::new();
+        let amount = U256::from(10u64).pow(U256::from(18u64));
+        let mut tx = build_signed_tx(&wallet, funded(&wallet), pay(amount), calculate_default_fee)
+            .unwrap()
+            .tx;
+        tx.outputs[0].set_amount(amount * U256::from(2u8));
+
+        assert!(decode_signed_tx(&encode_signed_tx(&tx).unwrap()).is_err());
+        assert!(decode_signed_tx("not base64!").is_err());
+        assert!(decode_signed_tx(&general_purpose::STANDARD.encode([1u8, 2, 3])).is_err());
     }
 }
@@ -4,6 +4,7 @@ mod wallet;
 use clap::Parser;
 use commands::*;
 
+use Astram_core::units::{format_amount, parse_asrm};
 use astram_config::config::Config;
 
 #[derive(Parser)]
@@ -20,12 +21,48 @@ fn main() {
     match cli.command {
         Commands::Generate => generate_wallet(),
         Commands::GenerateEth => generate_eth_wallet(),
+        Commands::GenerateHd { words } => generate_hd_wallet(words),
+        Commands::Restore { mnemonic, path } => restore_wallet(&mnemonic, path.as_deref()),
         Commands::Balance { address } => get_balance(&address),
-        Commands::Send { to, amount } => {
-            let amount_ram = asrm_to_ram(amount);
-            println!("Sending {} ASRM to {}", amount, to);
-            send_transaction(&to, amount_ram)
-        }
+        Commands::History {
+            address,
+            limit,
+            cursor,
+        } => show_history(&address, limit, cursor.as_deref()),
+        Commands::Send {
+            to,
+            amount,
+            offline,
+        } => match parse_asrm(&amount) {
+            Some(amount_ram) => {
+                println!("Sending {} to {}", format_amount(amount_ram), to);
+                send_transaction(&to, amount_ram, offline)
+            }
+            None => println!(
+                "[ERROR] Invalid amount {:?}: expected ASRM, e.g. 1.5",
+                amount
+            ),
+        },
+        Commands::BuildTx {
+            to,
+            amount,
+            utxos_file,
+            out,
+        } => match parse_asrm(&amount) {
+            Some(amount_ram) => {
+                build_offline_transaction(&to, amount_ram, &utxos_file, out.as_deref())
+            }
+            None => println!(
+                "[ERROR] Invalid amount {:?}: expected ASRM, e.g. 1.5",
+                amount
+            ),
+        },
+        Commands::BatchSend {
+            recipients,
+            offline,
+        } => batch_send(&recipients, offline),
+        Commands::Refresh => refresh_utxo_cache(),
+        Commands::Broadcast { tx_file } => broadcast_transaction_file(&tx_file),
         Commands::Config { subcommand } => match subcommand {
             ConfigCommands::View => {
                 let cfg = Config::load();
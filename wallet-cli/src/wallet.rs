@@ -4,6 +4,10 @@ use rand::RngCore;
 use hex;
 use tiny_keccak::{Hasher, Keccak};
 
+/// BIP44 path of the first account on Ethereum's coin type, so the same mnemonic
+/// restores the same address in MetaMask
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
 pub struct Wallet {
     pub secret_key: SecretKey,
     pub public_key: PublicKey,
@@ -49,6 +53,28 @@ impl Wallet {
         to_checksum_address(&self.address)
     }
 
+    /// Derive the wallet at `path` (BIP32) from a BIP39 mnemonic, with an empty passphrase
+    pub fn from_mnemonic(phrase: &str, path: &str) -> Result<Self, String> {
+        let mnemonic = bip39::Mnemonic::parse_normalized(phrase.trim())
+            .map_err(|e| format!("invalid mnemonic: {}", e))?;
+        let path: bip32::DerivationPath = path
+            .parse()
+            .map_err(|e| format!("invalid derivation path {}: {}", path, e))?;
+        let xprv = bip32::XPrv::derive_from_path(mnemonic.to_seed(""), &path)
+            .map_err(|e| format!("key derivation failed: {}", e))?;
+        let secret_key = SecretKey::from_slice(&xprv.private_key().to_bytes())
+            .map_err(|e| format!("derived key is invalid: {}", e))?;
+
+        let secp = Secp256k1::new();
+        let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+        let address = Self::address_from_public(&public_key);
+        Ok(Self {
+            secret_key,
+            public_key,
+            address,
+        })
+    }
+
     /// 16진수 개인키로부터 복원
     pub fn from_hex(hex_str: &str) -> Self {
         let secp = Secp256k1::new();
@@ -66,6 +92,16 @@ impl Wallet {
     }
 }
 
+/// Fresh English BIP39 mnemonic of 12 or 24 words
+pub fn generate_mnemonic(words: usize) -> Result<String, String> {
+    if words != 12 && words != 24 {
+        return Err(format!("mnemonic must be 12 or 24 words, not {}", words));
+    }
+    bip39::Mnemonic::generate(words)
+        .map(|m| m.to_string())
+        .map_err(|e| e.to_string())
+}
+
 fn keccak256(data: &[u8]) -> [u8; 32] {
     let mut hasher = Keccak::v256();
     let mut output = [0u8; 32];
@@ -93,3 +129,34 @@ fn to_checksum_address(address: &str) -> String {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hardhat's well-known test mnemonic; account 0 on the Ethereum path
+    const TEST_MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+    #[test]
+    fn restore_from_known_mnemonic_is_deterministic() {
+        for _ in 0..3 {
+            let wallet = Wallet::from_mnemonic(TEST_MNEMONIC, DEFAULT_DERIVATION_PATH).unwrap();
+            assert_eq!(wallet.address, "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        }
+        let second = Wallet::from_mnemonic(TEST_MNEMONIC, "m/44'/60'/0'/0/1").unwrap();
+        assert_ne!(second.address, "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        assert!(Wallet::from_mnemonic("not a mnemonic", DEFAULT_DERIVATION_PATH).is_err());
+    }
+
+    #[test]
+    fn generated_mnemonic_round_trips() {
+        for words in [12, 24] {
+            let phrase = generate_mnemonic(words).unwrap();
+            assert_eq!(phrase.split_whitespace().count(), words);
+            let a = Wallet::from_mnemonic(&phrase, DEFAULT_DERIVATION_PATH).unwrap();
+            let b = Wallet::from_mnemonic(&phrase, DEFAULT_DERIVATION_PATH).unwrap();
+            assert_eq!(a.address, b.address);
+        }
+        assert!(generate_mnemonic(13).is_err());
+    }
+}